@@ -0,0 +1,30 @@
+// src/api/access.rs
+
+use axum::{
+    extract::Request,
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Rejects requests that would change state, for an API in read-only mode
+pub async fn read_only(request: Request, next: Next) -> Response {
+    // Validating a config or an identity token only reads it, and a reload only
+    // applies the config files
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) || matches!(
+        request.uri().path(),
+        "/validate" | "/identity/verify" | "/reload"
+    ) {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "The API is read-only; change the config files instead" })),
+    )
+        .into_response()
+}
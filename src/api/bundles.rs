@@ -0,0 +1,98 @@
+// src/api/bundles.rs
+
+use crate::bundle::{installed_bundles, read_manifest, BundleManifest, BUNDLES_ROOT};
+use crate::config::get_config_by_service;
+use crate::config::validate::validate_service_name;
+use crate::container::INSTANCE_STORE;
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::time::SystemTime;
+
+#[derive(Debug, Serialize)]
+pub struct BundleStatus {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub installed_at: SystemTime,
+    pub updated_at: SystemTime,
+    pub services: Vec<BundleServiceStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BundleServiceStatus {
+    pub service_name: String,
+    /// Whether the daemon has loaded the service's config
+    pub loaded: bool,
+    pub pods: usize,
+}
+
+async fn bundle_status(manifest: BundleManifest) -> BundleStatus {
+    let mut services = Vec::new();
+    for service_name in manifest.services {
+        let loaded = get_config_by_service(&service_name).await.is_some();
+        let pods = match INSTANCE_STORE.get() {
+            Some(store) => store
+                .read()
+                .await
+                .get(&service_name)
+                .map(|instances| instances.len())
+                .unwrap_or(0),
+            None => 0,
+        };
+        services.push(BundleServiceStatus {
+            service_name,
+            loaded,
+            pods,
+        });
+    }
+
+    BundleStatus {
+        name: manifest.name,
+        version: manifest.version,
+        installed_at: manifest.installed_at,
+        updated_at: manifest.updated_at,
+        services,
+    }
+}
+
+/// Installed bundles and the state of their services.
+pub async fn get_bundles() -> Json<Vec<BundleStatus>> {
+    let mut bundles = Vec::new();
+    if let Some(root) = BUNDLES_ROOT.get() {
+        for manifest in installed_bundles(root).await {
+            bundles.push(bundle_status(manifest).await);
+        }
+    }
+    Json(bundles)
+}
+
+pub async fn get_bundle(Path(name): Path<String>) -> Response {
+    // Bundle names follow the service name rules, which also keeps the path inside the root
+    if let Err(e) = validate_service_name(&name) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Invalid bundle name: {}", e) })),
+        )
+            .into_response();
+    }
+
+    let manifest = match BUNDLES_ROOT.get() {
+        Some(root) => read_manifest(&root.join(&name)).await.ok(),
+        None => None,
+    };
+
+    match manifest {
+        Some(manifest) => Json(bundle_status(manifest).await).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Bundle {} not found", name) })),
+        )
+            .into_response(),
+    }
+}
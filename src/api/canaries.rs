@@ -0,0 +1,9 @@
+// src/api/canaries.rs
+
+use crate::container::canary::{canaries_status, CanaryStatus};
+use axum::Json;
+
+/// Canary image updates in progress, with the requests their pods have served
+pub async fn get_canaries() -> Json<Vec<CanaryStatus>> {
+    Json(canaries_status())
+}
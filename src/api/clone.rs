@@ -0,0 +1,35 @@
+// src/api/clone.rs
+
+use crate::clone::{clone_service, CloneRequest};
+use crate::config::get_config_by_service;
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Clone the service under a new name; the daemon starts it once the watcher
+/// picks up the new config file.
+pub async fn post_clone(
+    Path(service_name): Path<String>,
+    Json(request): Json<CloneRequest>,
+) -> Response {
+    if get_config_by_service(&service_name).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Service {} not found", service_name) })),
+        )
+            .into_response();
+    }
+
+    match clone_service(&service_name, &request).await {
+        Ok(clone) => (StatusCode::CREATED, Json(clone)).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
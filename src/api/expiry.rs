@@ -0,0 +1,9 @@
+// src/api/expiry.rs
+
+use crate::container::expiry::{get_expiry_status, ExpiryItem};
+use axum::Json;
+
+/// Secrets and certificates tracked by the expiry monitor, as of its last check
+pub async fn get_expiry() -> Json<Vec<ExpiryItem>> {
+    Json(get_expiry_status().await)
+}
@@ -0,0 +1,88 @@
+// src/api/groups.rs
+
+use crate::container::groups::{self, GroupOperation, DEFAULT_CONCURRENCY, MAX_FACTOR};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize)]
+pub struct GroupQuery {
+    /// Services operated on at once
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScaleGroupRequest {
+    /// What the pods and instance count bounds are multiplied by
+    pub factor: f64,
+}
+
+/// Every group and its services
+pub async fn get_groups() -> Response {
+    Json(json!({ "groups": groups::groups().await })).into_response()
+}
+
+/// Replace the pods of every service in the group, as the services' rolling updates do
+pub async fn post_restart(Path(group): Path<String>, Query(query): Query<GroupQuery>) -> Response {
+    run(&group, GroupOperation::Restart, query).await
+}
+
+/// Multiply the pods and instance count bounds of every service in the group
+pub async fn post_scale(
+    Path(group): Path<String>,
+    Query(query): Query<GroupQuery>,
+    Json(request): Json<ScaleGroupRequest>,
+) -> Response {
+    if !request.factor.is_finite() || request.factor <= 0.0 || request.factor > MAX_FACTOR {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("factor must be above 0 and at most {}", MAX_FACTOR) })),
+        )
+            .into_response();
+    }
+    run(&group, GroupOperation::Scale(request.factor), query).await
+}
+
+/// Stop every service in the group, as the stop endpoint does
+pub async fn post_pause(Path(group): Path<String>, Query(query): Query<GroupQuery>) -> Response {
+    run(&group, GroupOperation::Pause, query).await
+}
+
+/// Start the group's services again
+pub async fn post_resume(Path(group): Path<String>, Query(query): Query<GroupQuery>) -> Response {
+    run(&group, GroupOperation::Resume, query).await
+}
+
+// Answers once every service has been handled, with how it went for each
+async fn run(group: &str, operation: GroupOperation, query: GroupQuery) -> Response {
+    if query.concurrency == Some(0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "concurrency must be at least 1" })),
+        )
+            .into_response();
+    }
+    let concurrency = query.concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+
+    match groups::run(group, operation, concurrency).await {
+        Ok(services) => {
+            let failed = services.iter().filter(|service| !service.ok).count();
+            Json(json!({
+                "group": group,
+                "failed": failed,
+                "services": services,
+            }))
+            .into_response()
+        }
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
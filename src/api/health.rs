@@ -0,0 +1,132 @@
+// src/api/health.rs
+
+use crate::config::get_config_by_service;
+use crate::container::health::{get_container_health, HealthState};
+use crate::container::INSTANCE_STORE;
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum AggregateHealth {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContainerHealthSummary {
+    pub name: String,
+    /// None for containers Orbit doesn't monitor, such as adopted ones
+    pub state: Option<HealthState>,
+    pub ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PodHealth {
+    pub uuid: Uuid,
+    pub status: AggregateHealth,
+    pub containers: Vec<ContainerHealthSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceHealth {
+    pub service_name: String,
+    pub status: AggregateHealth,
+    pub healthy_pods: usize,
+    pub total_pods: usize,
+    pub pods: Vec<PodHealth>,
+}
+
+// A pod is healthy when every container is healthy and ready, and unhealthy
+// when one has failed or none is healthy
+fn pod_status(containers: &[ContainerHealthSummary]) -> AggregateHealth {
+    let healthy = |container: &&ContainerHealthSummary| {
+        container.ready && matches!(container.state, None | Some(HealthState::Healthy))
+    };
+    let failed = containers
+        .iter()
+        .any(|container| matches!(container.state, Some(HealthState::Failed)));
+
+    if containers.iter().all(|container| healthy(&container)) {
+        AggregateHealth::Healthy
+    } else if failed || !containers.iter().any(|container| healthy(&container)) {
+        AggregateHealth::Unhealthy
+    } else {
+        AggregateHealth::Degraded
+    }
+}
+
+/// Health of the service's pods, aggregated per pod and for the service: healthy
+/// when all its pods are, unhealthy when none is. Answers `503 Service
+/// Unavailable` for an unhealthy service, for uptime monitors.
+pub async fn get_service_health(Path(service_name): Path<String>) -> Response {
+    if get_config_by_service(&service_name).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Service {} not found", service_name) })),
+        )
+            .into_response();
+    }
+
+    let mut instances: Vec<_> = {
+        let store = INSTANCE_STORE.get().unwrap().read().await;
+        store
+            .get(&service_name)
+            .map(|instances| instances.values().cloned().collect())
+            .unwrap_or_default()
+    };
+    instances.sort_by_key(|instance| instance.created_at);
+
+    let mut pods = Vec::with_capacity(instances.len());
+    for instance in instances {
+        let mut containers = Vec::with_capacity(instance.containers.len());
+        for container in &instance.containers {
+            let health = get_container_health(&container.name).await;
+            containers.push(ContainerHealthSummary {
+                name: container.name.clone(),
+                state: health.as_ref().map(|health| health.state.clone()),
+                ready: health.as_ref().is_none_or(|health| health.ready),
+                message: health.and_then(|health| health.message),
+            });
+        }
+        pods.push(PodHealth {
+            uuid: instance.uuid,
+            status: pod_status(&containers),
+            containers,
+        });
+    }
+
+    let healthy_pods = pods
+        .iter()
+        .filter(|pod| pod.status == AggregateHealth::Healthy)
+        .count();
+    let status = if !pods.is_empty() && healthy_pods == pods.len() {
+        AggregateHealth::Healthy
+    } else if healthy_pods == 0 {
+        AggregateHealth::Unhealthy
+    } else {
+        AggregateHealth::Degraded
+    };
+
+    let code = match status {
+        AggregateHealth::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    let report = ServiceHealth {
+        service_name,
+        status,
+        healthy_pods,
+        total_pods: pods.len(),
+        pods,
+    };
+    (code, Json(report)).into_response()
+}
@@ -0,0 +1,28 @@
+// src/api/identity.rs
+
+use crate::container::identity::verify;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyRequest {
+    pub token: String,
+}
+
+/// Check a pod identity token, answering with the service and pod it was
+/// issued to while the pod is running
+pub async fn post_verify(Json(request): Json<VerifyRequest>) -> Response {
+    match verify(&request.token).await {
+        Ok(identity) => Json(identity).into_response(),
+        Err(e) => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,41 @@
+// src/api/images.rs
+
+use crate::container::images::{image_report, prefetch, PrefetchRequest};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// The services' images and the layers they share
+pub async fn get_images() -> Response {
+    match image_report().await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to inspect images: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Pull images ahead of their first deploy
+pub async fn post_prefetch(Json(request): Json<PrefetchRequest>) -> Response {
+    if request.images.iter().any(|image| image.trim().is_empty()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Image names must not be empty" })),
+        )
+            .into_response();
+    }
+
+    match prefetch(&request.images).await {
+        Ok(results) => Json(results).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to prefetch images: {}", e) })),
+        )
+            .into_response(),
+    }
+}
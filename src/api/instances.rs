@@ -0,0 +1,155 @@
+// src/api/instances.rs
+
+use super::query::{list_response, ListQuery};
+use crate::container::{
+    changes::{changes_since, current_version, InstanceChange},
+    InstanceMetadata, INSTANCE_STORE,
+};
+use axum::{
+    extract::Query,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+pub const SNAPSHOT_VERSION_HEADER: &str = "x-snapshot-version";
+
+#[derive(Serialize, Deserialize)]
+pub struct ServiceInstance {
+    pub service_name: String,
+    #[serde(flatten)]
+    pub instance: InstanceMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    pub since: u64,
+    /// Comma separated service names
+    pub service: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ChangesResponse {
+    pub version: u64,
+    pub changes: Vec<InstanceChange>,
+}
+
+/// Raw instance store contents, one entry per pod. The snapshot's version is
+/// returned in `X-Snapshot-Version` for use with `/instances/changes`.
+pub async fn get_instances(Query(query): Query<ListQuery>, headers: HeaderMap) -> Response {
+    let instance_store = INSTANCE_STORE
+        .get()
+        .expect("Instance store not initialized");
+
+    let (version, mut instances): (u64, Vec<ServiceInstance>) = {
+        let store = instance_store.read().await;
+        // Writers record changes under the write lock, so this matches the snapshot
+        let version = current_version();
+        let instances = store
+            .iter()
+            .filter(|(service_name, _)| query.matches_service(service_name))
+            .flat_map(|(service_name, pods)| {
+                pods.values()
+                    .filter(|metadata| query.matches_annotations(&metadata.annotations))
+                    .map(|metadata| ServiceInstance {
+                        service_name: service_name.clone(),
+                        instance: metadata.clone(),
+                    })
+            })
+            .collect();
+        (version, instances)
+    };
+
+    instances.sort_by(|a, b| {
+        a.service_name
+            .cmp(&b.service_name)
+            .then(a.instance.created_at.cmp(&b.instance.created_at))
+    });
+
+    let mut response = list_response(instances, &query, &headers);
+    response
+        .headers_mut()
+        .insert(SNAPSHOT_VERSION_HEADER, HeaderValue::from(version));
+    response
+}
+
+/// Changes to the instance store after version `since`. Answers `410 Gone` when
+/// the caller is too far behind (or the daemon restarted) and must refetch
+/// `/instances`.
+pub async fn get_instance_changes(Query(query): Query<ChangesQuery>) -> Response {
+    let Some((version, changes)) = changes_since(query.since) else {
+        return (
+            StatusCode::GONE,
+            Json(json!({
+                "error": "Changes since this version are no longer available, refetch /instances",
+                "version": current_version(),
+            })),
+        )
+            .into_response();
+    };
+
+    let filter = ListQuery {
+        service: query.service,
+        ..Default::default()
+    };
+    let changes = changes
+        .into_iter()
+        .filter(|change| filter.matches_service(&change.service_name))
+        .collect();
+
+    Json(ChangesResponse { version, changes }).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::changes::{record_change, InstanceEvent};
+
+    async fn changes(since: u64, service: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let query = ChangesQuery {
+            since,
+            service: service.map(str::to_string),
+        };
+        let response = get_instance_changes(Query(query)).await;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_changes_since_version() {
+        let since = current_version();
+        record_change("changes-test", InstanceEvent::ServiceRemoved);
+        record_change("changes-other", InstanceEvent::ServiceRemoved);
+        record_change("changes-test", InstanceEvent::ServiceRemoved);
+
+        let (status, body) = changes(since, Some("changes-test")).await;
+        assert_eq!(status, StatusCode::OK);
+        let versions: Vec<u64> = body["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| change["version"].as_u64().unwrap())
+            .collect();
+        assert_eq!(versions.len(), 2);
+        assert!(versions[0] > since && versions[0] < versions[1]);
+        assert!(body["version"].as_u64().unwrap() >= versions[1]);
+
+        // Nothing for the service after its last change
+        let (status, body) = changes(versions[1], Some("changes-test")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["changes"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_changes_gone() {
+        // A version from before a daemon restart
+        let (status, body) = changes(u64::MAX, None).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert!(body["version"].as_u64().is_some());
+    }
+}
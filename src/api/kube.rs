@@ -0,0 +1,624 @@
+// src/api/kube.rs
+//
+// A read-only facade of the Kubernetes API, so `kubectl get pods` and existing
+// dashboards can observe Orbit. Services are projected as apps/v1 Deployments
+// and their pods as v1 Pods, in the service's runtime namespace or "default".
+// Only discovery, get and list are served: there is no watch, and writes are
+// rejected by the router.
+use crate::{
+    config::{daemon::get_daemon_config, parse_container_name, ServiceConfig, CONFIG_STORE},
+    container::{
+        changes::current_version, health::get_container_health, readiness::container_healthy,
+        InstanceMetadata, INSTANCE_STORE, POD_LABEL, SERVICE_LABEL,
+    },
+    node::get_node_status,
+};
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use humantime_serde::re::humantime;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::SystemTime;
+
+const DEFAULT_NAMESPACE: &str = "default";
+// The API version the facade's objects follow
+const KUBERNETES_MAJOR: &str = "1";
+const KUBERNETES_MINOR: &str = "29";
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct KubeQuery {
+    /// e.g. "app=web,tier!=cache,canary"
+    pub label_selector: Option<String>,
+}
+
+// An object and its row in a kubectl table
+struct Projected {
+    namespace: String,
+    name: String,
+    labels: BTreeMap<String, String>,
+    object: Value,
+    cells: Vec<Value>,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Pod,
+    Deployment,
+}
+
+impl Kind {
+    fn resource(self) -> &'static str {
+        match self {
+            Kind::Pod => "pods",
+            Kind::Deployment => "deployments.apps",
+        }
+    }
+
+    fn api_version(self) -> &'static str {
+        match self {
+            Kind::Pod => "v1",
+            Kind::Deployment => "apps/v1",
+        }
+    }
+
+    fn list_kind(self) -> &'static str {
+        match self {
+            Kind::Pod => "PodList",
+            Kind::Deployment => "DeploymentList",
+        }
+    }
+
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Kind::Pod => &["Name", "Ready", "Status", "Restarts", "Age", "IP", "Node"],
+            Kind::Deployment => &["Name", "Ready", "Up-to-date", "Available", "Age"],
+        }
+    }
+}
+
+pub async fn get_version() -> Json<Value> {
+    Json(json!({
+        "major": KUBERNETES_MAJOR,
+        "minor": KUBERNETES_MINOR,
+        "gitVersion": format!(
+            "v{}.{}.0+orbit-{}",
+            KUBERNETES_MAJOR,
+            KUBERNETES_MINOR,
+            env!("CARGO_PKG_VERSION")
+        ),
+        "platform": format!("{}/{}", std::env::consts::OS, std::env::consts::ARCH),
+    }))
+}
+
+pub async fn get_api_versions() -> Json<Value> {
+    Json(json!({
+        "kind": "APIVersions",
+        "versions": ["v1"],
+        "serverAddressByClientCIDRs": [],
+    }))
+}
+
+pub async fn get_api_groups() -> Json<Value> {
+    let version = json!({ "groupVersion": "apps/v1", "version": "v1" });
+    Json(json!({
+        "kind": "APIGroupList",
+        "apiVersion": "v1",
+        "groups": [{
+            "name": "apps",
+            "versions": [version],
+            "preferredVersion": version,
+        }],
+    }))
+}
+
+pub async fn get_core_resources() -> Json<Value> {
+    Json(json!({
+        "kind": "APIResourceList",
+        "groupVersion": "v1",
+        "resources": [
+            resource("pods", "Pod", true, &["po"]),
+            resource("namespaces", "Namespace", false, &["ns"]),
+        ],
+    }))
+}
+
+pub async fn get_apps_resources() -> Json<Value> {
+    Json(json!({
+        "kind": "APIResourceList",
+        "apiVersion": "v1",
+        "groupVersion": "apps/v1",
+        "resources": [resource("deployments", "Deployment", true, &["deploy"])],
+    }))
+}
+
+fn resource(name: &str, kind: &str, namespaced: bool, short_names: &[&str]) -> Value {
+    json!({
+        "name": name,
+        "singularName": kind.to_lowercase(),
+        "namespaced": namespaced,
+        "kind": kind,
+        "verbs": ["get", "list"],
+        "shortNames": short_names,
+    })
+}
+
+/// Every namespace that has a service, and "default"
+pub async fn list_namespaces() -> Json<Value> {
+    let mut namespaces = BTreeSet::from([DEFAULT_NAMESPACE.to_string()]);
+    for config in service_configs().await {
+        namespaces.insert(namespace_of(&config));
+    }
+
+    let items: Vec<Value> = namespaces
+        .into_iter()
+        .map(|name| {
+            json!({
+                "metadata": { "name": name },
+                "status": { "phase": "Active" },
+            })
+        })
+        .collect();
+    Json(json!({
+        "kind": "NamespaceList",
+        "apiVersion": "v1",
+        "metadata": { "resourceVersion": current_version().to_string() },
+        "items": items,
+    }))
+}
+
+pub async fn list_pods(Query(query): Query<KubeQuery>, headers: HeaderMap) -> Response {
+    list(Kind::Pod, None, &query, &headers).await
+}
+
+pub async fn list_namespaced_pods(
+    Path(namespace): Path<String>,
+    Query(query): Query<KubeQuery>,
+    headers: HeaderMap,
+) -> Response {
+    list(Kind::Pod, Some(&namespace), &query, &headers).await
+}
+
+pub async fn get_pod(
+    Path((namespace, name)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    get(Kind::Pod, &namespace, &name, &headers).await
+}
+
+pub async fn list_deployments(Query(query): Query<KubeQuery>, headers: HeaderMap) -> Response {
+    list(Kind::Deployment, None, &query, &headers).await
+}
+
+pub async fn list_namespaced_deployments(
+    Path(namespace): Path<String>,
+    Query(query): Query<KubeQuery>,
+    headers: HeaderMap,
+) -> Response {
+    list(Kind::Deployment, Some(&namespace), &query, &headers).await
+}
+
+pub async fn get_deployment(
+    Path((namespace, name)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    get(Kind::Deployment, &namespace, &name, &headers).await
+}
+
+async fn list(
+    kind: Kind,
+    namespace: Option<&str>,
+    query: &KubeQuery,
+    headers: &HeaderMap,
+) -> Response {
+    let selector = match parse_selector(query.label_selector.as_deref().unwrap_or_default()) {
+        Ok(selector) => selector,
+        Err(message) => return status(StatusCode::BAD_REQUEST, "BadRequest", message),
+    };
+
+    let objects: Vec<Projected> = project(kind)
+        .await
+        .into_iter()
+        .filter(|object| namespace.is_none_or(|namespace| object.namespace == namespace))
+        .filter(|object| {
+            selector
+                .iter()
+                .all(|requirement| requirement.matches(&object.labels))
+        })
+        .collect();
+
+    if wants_table(headers) {
+        return Json(table(kind, objects)).into_response();
+    }
+    Json(json!({
+        "kind": kind.list_kind(),
+        "apiVersion": kind.api_version(),
+        "metadata": { "resourceVersion": current_version().to_string() },
+        "items": objects.into_iter().map(|object| object.object).collect::<Vec<_>>(),
+    }))
+    .into_response()
+}
+
+async fn get(kind: Kind, namespace: &str, name: &str, headers: &HeaderMap) -> Response {
+    let Some(object) = project(kind)
+        .await
+        .into_iter()
+        .find(|object| object.namespace == namespace && object.name == name)
+    else {
+        return status(
+            StatusCode::NOT_FOUND,
+            "NotFound",
+            format!("{} \"{}\" not found", kind.resource(), name),
+        );
+    };
+
+    if wants_table(headers) {
+        return Json(table(kind, vec![object])).into_response();
+    }
+    Json(object.object).into_response()
+}
+
+// kubectl asks for server-side tables, e.g. "application/json;as=Table;v=v1;g=meta.k8s.io"
+fn wants_table(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("as=Table"))
+}
+
+fn table(kind: Kind, objects: Vec<Projected>) -> Value {
+    let columns: Vec<Value> = kind
+        .columns()
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "type": "string",
+                "format": if *name == "Name" { "name" } else { "" },
+                "description": "",
+                "priority": 0,
+            })
+        })
+        .collect();
+    let rows: Vec<Value> = objects
+        .into_iter()
+        .map(|object| {
+            json!({
+                "cells": object.cells,
+                "object": {
+                    "kind": "PartialObjectMetadata",
+                    "apiVersion": "meta.k8s.io/v1",
+                    "metadata": object.object["metadata"],
+                },
+            })
+        })
+        .collect();
+
+    json!({
+        "kind": "Table",
+        "apiVersion": "meta.k8s.io/v1",
+        "metadata": { "resourceVersion": current_version().to_string() },
+        "columnDefinitions": columns,
+        "rows": rows,
+    })
+}
+
+fn status(code: StatusCode, reason: &str, message: String) -> Response {
+    (
+        code,
+        Json(json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "metadata": {},
+            "status": "Failure",
+            "message": message,
+            "reason": reason,
+            "code": code.as_u16(),
+        })),
+    )
+        .into_response()
+}
+
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+    NotExists(String),
+}
+
+impl Requirement {
+    fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        match self {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::Exists(key) => labels.contains_key(key),
+            Requirement::NotExists(key) => !labels.contains_key(key),
+        }
+    }
+}
+
+// Equality-based selectors only; set-based ones such as "env in (a,b)" are rejected
+fn parse_selector(selector: &str) -> Result<Vec<Requirement>, String> {
+    selector
+        .split(',')
+        .map(str::trim)
+        .filter(|requirement| !requirement.is_empty())
+        .map(|requirement| {
+            if requirement.contains(['(', ' ']) {
+                return Err(format!("Unsupported label selector '{}'", requirement));
+            }
+            let parsed = if let Some((key, value)) = requirement.split_once("!=") {
+                Requirement::NotEquals(key.to_string(), value.to_string())
+            } else if let Some((key, value)) = requirement.split_once("==") {
+                Requirement::Equals(key.to_string(), value.to_string())
+            } else if let Some((key, value)) = requirement.split_once('=') {
+                Requirement::Equals(key.to_string(), value.to_string())
+            } else if let Some(key) = requirement.strip_prefix('!') {
+                Requirement::NotExists(key.to_string())
+            } else {
+                Requirement::Exists(requirement.to_string())
+            };
+            Ok(parsed)
+        })
+        .collect()
+}
+
+async fn project(kind: Kind) -> Vec<Projected> {
+    let mut objects = match kind {
+        Kind::Pod => project_pods().await,
+        Kind::Deployment => project_deployments().await,
+    };
+    objects.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+    objects
+}
+
+async fn service_configs() -> Vec<ServiceConfig> {
+    let Some(store) = CONFIG_STORE.get() else {
+        return Vec::new();
+    };
+    store
+        .read()
+        .await
+        .values()
+        .map(|(_, config)| config.clone())
+        .collect()
+}
+
+async fn service_pods(service_name: &str) -> Vec<InstanceMetadata> {
+    let Some(store) = INSTANCE_STORE.get() else {
+        return Vec::new();
+    };
+    let mut pods: Vec<InstanceMetadata> = store
+        .read()
+        .await
+        .get(service_name)
+        .map(|pods| pods.values().cloned().collect())
+        .unwrap_or_default();
+    pods.sort_by_key(|pod| pod.created_at);
+    pods
+}
+
+fn namespace_of(config: &ServiceConfig) -> String {
+    config
+        .namespace
+        .clone()
+        .or_else(|| {
+            get_daemon_config()
+                .runtime
+                .docker
+                .and_then(|docker| docker.namespace)
+        })
+        .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string())
+}
+
+fn selector_labels(service_name: &str) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("app".to_string(), service_name.to_string()),
+        (SERVICE_LABEL.to_string(), service_name.to_string()),
+    ])
+}
+
+fn container_specs(config: &ServiceConfig) -> Vec<Value> {
+    config
+        .spec
+        .containers
+        .iter()
+        .map(|container| {
+            let ports: Vec<Value> = container
+                .ports
+                .iter()
+                .flatten()
+                .map(|port| json!({ "containerPort": port.port, "protocol": "TCP" }))
+                .collect();
+            json!({ "name": container.name, "image": container.image, "ports": ports })
+        })
+        .collect()
+}
+
+fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+// Ages as kubectl prints them, e.g. "45s", "12m", "5h" or "3d"
+fn age(since: SystemTime) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(since)
+        .unwrap_or_default()
+        .as_secs();
+    match seconds {
+        0..120 => format!("{}s", seconds),
+        120..7_200 => format!("{}m", seconds / 60),
+        7_200..172_800 => format!("{}h", seconds / 3_600),
+        _ => format!("{}d", seconds / 86_400),
+    }
+}
+
+async fn project_pods() -> Vec<Projected> {
+    let node_name = get_node_status()
+        .map(|node| node.hostname)
+        .unwrap_or_default();
+
+    let mut objects = Vec::new();
+    for config in service_configs().await {
+        let namespace = namespace_of(&config);
+        for pod in service_pods(&config.name).await {
+            let name = format!("{}-{}", config.name, pod.uuid);
+            let mut labels = selector_labels(&config.name);
+            labels.insert(POD_LABEL.to_string(), pod.uuid.to_string());
+
+            let mut statuses = Vec::new();
+            let mut ready_count = 0;
+            let mut restarts = 0;
+            for container in &pod.containers {
+                let container_name = parse_container_name(&container.name)
+                    .map(|parts| parts.container_name)
+                    .unwrap_or_else(|_| container.name.clone());
+                let image = config
+                    .spec
+                    .containers
+                    .iter()
+                    .find(|c| c.name == container_name)
+                    .map(|c| c.image.clone())
+                    .unwrap_or_default();
+                let ready = container_healthy(&config, &container.name).await;
+                let restart_count = get_container_health(&container.name)
+                    .await
+                    .map_or(0, |health| health.restart_count);
+
+                ready_count += usize::from(ready);
+                restarts += restart_count;
+                statuses.push(json!({
+                    "name": container_name,
+                    "image": image,
+                    "imageID": pod.image_hash.get(&container_name).cloned().unwrap_or_default(),
+                    "containerID": container.name,
+                    "ready": ready,
+                    "started": true,
+                    "restartCount": restart_count,
+                    "state": { "running": { "startedAt": timestamp(pod.created_at) } },
+                }));
+            }
+
+            let ready = !pod.containers.is_empty() && ready_count == pod.containers.len();
+            let pod_ip = pod
+                .containers
+                .first()
+                .map(|container| container.ip_address.clone())
+                .unwrap_or_default();
+            let condition = |type_: &str| json!({ "type": type_, "status": if ready { "True" } else { "False" } });
+
+            let object = json!({
+                "kind": "Pod",
+                "apiVersion": "v1",
+                "metadata": {
+                    "name": name,
+                    "namespace": namespace,
+                    "uid": pod.uuid,
+                    "creationTimestamp": timestamp(pod.created_at),
+                    "labels": labels,
+                    "annotations": pod.annotations,
+                },
+                "spec": {
+                    "containers": container_specs(&config),
+                    "nodeName": node_name,
+                },
+                "status": {
+                    "phase": "Running",
+                    "podIP": pod_ip,
+                    "startTime": timestamp(pod.created_at),
+                    "conditions": [condition("Ready"), condition("ContainersReady")],
+                    "containerStatuses": statuses,
+                },
+            });
+            let cells = vec![
+                json!(name),
+                json!(format!("{}/{}", ready_count, pod.containers.len())),
+                json!("Running"),
+                json!(restarts),
+                json!(age(pod.created_at)),
+                json!(pod_ip),
+                json!(node_name),
+            ];
+
+            objects.push(Projected {
+                namespace: namespace.clone(),
+                name,
+                labels,
+                object,
+                cells,
+            });
+        }
+    }
+    objects
+}
+
+async fn project_deployments() -> Vec<Projected> {
+    let mut objects = Vec::new();
+    for config in service_configs().await {
+        // External services run no pods
+        if config.external.is_some() {
+            continue;
+        }
+
+        let namespace = namespace_of(&config);
+        let pods = service_pods(&config.name).await;
+        let mut ready = 0;
+        for pod in &pods {
+            let mut pod_ready = !pod.containers.is_empty();
+            for container in &pod.containers {
+                pod_ready &= container_healthy(&config, &container.name).await;
+            }
+            ready += usize::from(pod_ready);
+        }
+        // The oldest pod stands in for when the service was deployed
+        let created_at = pods.first().map(|pod| pod.created_at);
+        let labels = selector_labels(&config.name);
+
+        let object = json!({
+            "kind": "Deployment",
+            "apiVersion": "apps/v1",
+            "metadata": {
+                "name": config.name,
+                "namespace": namespace,
+                "creationTimestamp": created_at.map(timestamp),
+                "labels": labels,
+                "annotations": config.annotations,
+            },
+            "spec": {
+                "replicas": pods.len(),
+                "selector": { "matchLabels": labels },
+                "template": {
+                    "metadata": { "labels": labels },
+                    "spec": { "containers": container_specs(&config) },
+                },
+            },
+            "status": {
+                "replicas": pods.len(),
+                "readyReplicas": ready,
+                "availableReplicas": ready,
+                "updatedReplicas": pods.len(),
+            },
+        });
+        let cells = vec![
+            json!(config.name),
+            json!(format!("{}/{}", ready, pods.len())),
+            json!(pods.len()),
+            json!(ready),
+            json!(created_at.map_or_else(|| "<unknown>".to_string(), age)),
+        ];
+
+        objects.push(Projected {
+            namespace,
+            name: config.name.clone(),
+            labels,
+            object,
+            cells,
+        });
+    }
+    objects
+}
@@ -1,2 +1,24 @@
 // src/api/mod.rs
+pub mod access;
+pub mod bundles;
+pub mod canaries;
+pub mod clone;
+pub mod expiry;
+pub mod groups;
+pub mod health;
+pub mod identity;
+pub mod images;
+pub mod instances;
+pub mod kube;
+pub mod node;
+pub mod query;
+pub mod readiness;
+pub mod reload;
+pub mod replay;
+pub mod scaling;
+pub mod schedule;
 pub mod status;
+pub mod stopped;
+pub mod support;
+pub mod ttl;
+pub mod validate;
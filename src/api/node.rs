@@ -0,0 +1,21 @@
+// src/api/node.rs
+
+use crate::node::get_node_status;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Host metrics and runtime details of this node, as of the last sample
+pub async fn get_node() -> Response {
+    match get_node_status() {
+        Some(status) => Json(status).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Node has not been sampled yet" })),
+        )
+            .into_response(),
+    }
+}
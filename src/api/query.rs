@@ -0,0 +1,300 @@
+// src/api/query.rs
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+const DEFAULT_PER_PAGE: usize = 50;
+pub const MAX_PER_PAGE: usize = 500;
+
+/// Query parameters shared by the list endpoints, e.g.
+/// `/status?service=web,api&annotation=team=payments&fields=service_name,pods&page=2&per_page=10`
+#[derive(Debug, Deserialize, Default)]
+pub struct ListQuery {
+    /// Comma separated service names
+    pub service: Option<String>,
+    /// Comma separated `key=value` annotation selectors, all of which must match
+    pub annotation: Option<String>,
+    /// Comma separated top-level fields to include in each item
+    pub fields: Option<String>,
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+impl ListQuery {
+    pub fn matches_service(&self, service_name: &str) -> bool {
+        match &self.service {
+            Some(services) => split_list(services).any(|s| s == service_name),
+            None => true,
+        }
+    }
+
+    pub fn matches_annotations(&self, annotations: &HashMap<String, String>) -> bool {
+        let Some(selectors) = &self.annotation else {
+            return true;
+        };
+
+        split_list(selectors).all(|selector| match selector.split_once('=') {
+            Some((key, value)) => annotations.get(key).map(String::as_str) == Some(value),
+            None => annotations.contains_key(selector),
+        })
+    }
+
+    // Lists are only paginated when asked for, so existing clients get every item
+    fn paginated(&self) -> bool {
+        self.page.is_some() || self.per_page.is_some()
+    }
+
+    fn per_page(&self) -> usize {
+        self.per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE)
+    }
+
+    fn page(&self) -> usize {
+        self.page.unwrap_or(1).max(1)
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Paginate, project and serialise `items`, answering `304 Not Modified` when the
+/// caller's `If-None-Match` matches the current representation.
+///
+/// Items are only paginated when `page` or `per_page` is given. Pagination
+/// details are returned in `X-Total-Count`, `X-Page` and `X-Per-Page` headers so
+/// the body keeps its existing shape.
+pub fn list_response<T: Serialize>(
+    items: Vec<T>,
+    query: &ListQuery,
+    headers: &HeaderMap,
+) -> Response {
+    let total = items.len();
+    let paginated = query.paginated();
+    let per_page = if paginated {
+        query.per_page()
+    } else {
+        total.max(1)
+    };
+    let page = if paginated { query.page() } else { 1 };
+
+    let fields: Option<Vec<&str>> = query.fields.as_deref().map(|f| split_list(f).collect());
+
+    let body: Vec<Value> = items
+        .into_iter()
+        .skip((page - 1).saturating_mul(per_page))
+        .take(per_page)
+        .filter_map(|item| serde_json::to_value(item).ok())
+        .map(|value| match (&fields, value) {
+            (Some(fields), Value::Object(mut map)) => {
+                map.retain(|key, _| fields.contains(&key.as_str()));
+                Value::Object(map)
+            }
+            (_, value) => value,
+        })
+        .collect();
+
+    let etag = compute_etag(&body, total);
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(body).into_response()
+    };
+
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    response_headers.insert("x-total-count", HeaderValue::from(total));
+    if paginated {
+        response_headers.insert("x-page", HeaderValue::from(page));
+        response_headers.insert("x-per-page", HeaderValue::from(per_page));
+    }
+
+    response
+}
+
+// Weak validator over the page contents; the total is included so that
+// changes on other pages still invalidate cached responses
+fn compute_etag(body: &[Value], total: usize) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(body)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    total.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Item {
+        name: String,
+        pods: usize,
+    }
+
+    fn items(count: usize) -> Vec<Item> {
+        (0..count)
+            .map(|pods| Item {
+                name: format!("service-{}", pods),
+                pods,
+            })
+            .collect()
+    }
+
+    async fn body(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response
+            .headers()
+            .get(name)
+            .map(|value| value.to_str().unwrap())
+    }
+
+    #[test]
+    fn test_matches_service_and_annotations() {
+        let query = ListQuery {
+            service: Some("web, api".to_string()),
+            annotation: Some("team=payments,tier".to_string()),
+            ..Default::default()
+        };
+        assert!(query.matches_service("api"));
+        assert!(!query.matches_service("db"));
+
+        let annotations = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        assert!(query.matches_annotations(&annotations(&[("team", "payments"), ("tier", "")])));
+        assert!(!query.matches_annotations(&annotations(&[("team", "payments")])));
+        assert!(!query.matches_annotations(&annotations(&[("team", "search"), ("tier", "1")])));
+        assert!(ListQuery::default().matches_annotations(&HashMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        let query = ListQuery {
+            page: Some(2),
+            per_page: Some(2),
+            ..Default::default()
+        };
+        let response = list_response(items(5), &query, &HeaderMap::new());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, "x-total-count"), Some("5"));
+        assert_eq!(header(&response, "x-page"), Some("2"));
+        assert_eq!(header(&response, "x-per-page"), Some("2"));
+        let names: Vec<Value> = body(response)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].clone())
+            .collect();
+        assert_eq!(names, vec!["service-2", "service-3"]);
+
+        // Past the last page
+        let query = ListQuery {
+            page: Some(4),
+            per_page: Some(2),
+            ..Default::default()
+        };
+        let response = list_response(items(5), &query, &HeaderMap::new());
+        assert_eq!(body(response).await, Value::Array(Vec::new()));
+
+        // per_page is kept within 1 and MAX_PER_PAGE
+        let query = ListQuery {
+            per_page: Some(0),
+            ..Default::default()
+        };
+        let response = list_response(items(5), &query, &HeaderMap::new());
+        assert_eq!(header(&response, "x-page"), Some("1"));
+        assert_eq!(header(&response, "x-per-page"), Some("1"));
+        assert_eq!(body(response).await.as_array().unwrap().len(), 1);
+        let query = ListQuery {
+            per_page: Some(10_000),
+            ..Default::default()
+        };
+        let response = list_response(items(600), &query, &HeaderMap::new());
+        assert_eq!(body(response).await.as_array().unwrap().len(), MAX_PER_PAGE);
+
+        // Without page or per_page, every item is returned
+        let response = list_response(items(60), &ListQuery::default(), &HeaderMap::new());
+        assert_eq!(header(&response, "x-total-count"), Some("60"));
+        assert_eq!(header(&response, "x-page"), None);
+        assert_eq!(body(response).await.as_array().unwrap().len(), 60);
+    }
+
+    #[tokio::test]
+    async fn test_field_projection() {
+        let query = ListQuery {
+            fields: Some("name,missing".to_string()),
+            ..Default::default()
+        };
+        let response = list_response(items(2), &query, &HeaderMap::new());
+        assert_eq!(
+            body(response).await,
+            serde_json::json!([{"name": "service-0"}, {"name": "service-1"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_etag_not_modified() {
+        let query = ListQuery::default();
+        let response = list_response(items(3), &query, &HeaderMap::new());
+        let etag = header(&response, "etag").unwrap().to_string();
+        assert!(etag.starts_with("W/\""));
+
+        let if_none_match = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        let response = list_response(items(3), &query, &if_none_match(&etag));
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(header(&response, "etag"), Some(etag.as_str()));
+        let response = list_response(
+            items(3),
+            &query,
+            &if_none_match(&format!("W/\"other\", {}", etag)),
+        );
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let response = list_response(items(3), &query, &if_none_match("*"));
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // A change, even to another page, gives a new ETag
+        let response = list_response(items(4), &query, &if_none_match(&etag));
+        assert_eq!(response.status(), StatusCode::OK);
+        let paged = ListQuery {
+            per_page: Some(2),
+            ..Default::default()
+        };
+        let first = list_response(items(3), &paged, &HeaderMap::new());
+        let grown = list_response(items(4), &paged, &HeaderMap::new());
+        assert_ne!(header(&first, "etag"), header(&grown, "etag"));
+    }
+}
@@ -0,0 +1,52 @@
+// src/api/readiness.rs
+
+use crate::config::get_config_by_service;
+use crate::container::readiness::{evaluate, wait_until_ready};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+// Keeps a single request from holding a connection open indefinitely
+const MAX_WAIT_SECS: u64 = 600;
+
+#[derive(Debug, Deserialize)]
+pub struct ReadinessQuery {
+    /// Seconds to wait for the service to become ready before answering
+    pub wait: Option<u64>,
+}
+
+/// Evaluate the service's readiness definition. Answers `200 OK` when ready and
+/// `503 Service Unavailable` otherwise, with the report as the body either way.
+pub async fn get_service_readiness(
+    Path(service_name): Path<String>,
+    Query(query): Query<ReadinessQuery>,
+) -> Response {
+    let Some(config) = get_config_by_service(&service_name).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Service {} not found", service_name) })),
+        )
+            .into_response();
+    };
+
+    let report = match query.wait {
+        Some(wait) => {
+            let wait = Duration::from_secs(wait.min(MAX_WAIT_SECS));
+            wait_until_ready(&config, None, wait).await
+        }
+        None => evaluate(&config, None).await,
+    };
+
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report)).into_response()
+}
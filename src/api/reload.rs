@@ -0,0 +1,29 @@
+// src/api/reload.rs
+
+use crate::config::reload::{reload, CONFIG_DIR};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Re-read every config file and apply what changed, as on SIGHUP
+pub async fn post_reload() -> Response {
+    let Some(config_dir) = CONFIG_DIR.get() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Configs have not been loaded yet" })),
+        )
+            .into_response();
+    };
+
+    match reload(config_dir).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to reload configs: {}", e) })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,74 @@
+// src/api/replay.rs
+
+use crate::config::get_config_by_service;
+use crate::replay::{clear_recording, recorded_requests, replay_job, start_replay, ReplayRequest};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+fn not_found(service_name: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": format!("Service {} not found", service_name) })),
+    )
+        .into_response()
+}
+
+/// Requests sampled by the proxy for the service, oldest first.
+pub async fn get_recording(Path(service_name): Path<String>) -> Response {
+    if get_config_by_service(&service_name).await.is_none() {
+        return not_found(&service_name);
+    }
+
+    Json(json!({
+        "service_name": service_name,
+        "requests": recorded_requests(&service_name),
+    }))
+    .into_response()
+}
+
+pub async fn delete_recording(Path(service_name): Path<String>) -> Response {
+    if get_config_by_service(&service_name).await.is_none() {
+        return not_found(&service_name);
+    }
+
+    clear_recording(&service_name);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Start replaying the recording through the service's proxy. The replay is
+/// polled for its report at `/services/{service}/replay/{id}`.
+pub async fn post_replay(
+    Path(service_name): Path<String>,
+    Json(request): Json<ReplayRequest>,
+) -> Response {
+    let Some(config) = get_config_by_service(&service_name).await else {
+        return not_found(&service_name);
+    };
+
+    match start_replay(&config, &request) {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// A replay of the service, with its report once it has finished
+pub async fn get_replay(Path((service_name, id)): Path<(String, Uuid)>) -> Response {
+    match replay_job(id).filter(|job| job.service_name == service_name) {
+        Some(job) => Json(job).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Replay {} not found", id) })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,61 @@
+// src/api/scaling.rs
+
+use crate::config::get_config_by_service;
+use crate::container::scaling::history::{scaling_history, ScalingEvent};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// RFC 3339 timestamp; only events after it are returned
+    pub since: Option<String>,
+    /// Return at most this many of the most recent events
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryResponse {
+    pub service: String,
+    pub events: Vec<ScalingEvent>,
+}
+
+/// Scaling actions taken for a service, oldest first. The history outlives the
+/// service, so removed services answer with what was recorded for them.
+pub async fn get_scaling_history(
+    Path(service_name): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let since = match query.since.as_deref().map(humantime::parse_rfc3339_weak) {
+        Some(Ok(since)) => Some(since),
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Invalid since: {}", e) })),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+
+    let events = scaling_history(&service_name, since, query.limit);
+    if events.is_empty() && get_config_by_service(&service_name).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Service {} not found", service_name) })),
+        )
+            .into_response();
+    }
+
+    Json(HistoryResponse {
+        service: service_name,
+        events,
+    })
+    .into_response()
+}
@@ -0,0 +1,9 @@
+// src/api/schedule.rs
+
+use crate::container::schedule::{scheduled_tasks, ScheduledTask};
+use axum::Json;
+
+/// When each service's periodic tasks run, after spreading
+pub async fn get_schedule() -> Json<Vec<ScheduledTask>> {
+    Json(scheduled_tasks())
+}
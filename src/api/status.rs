@@ -1,15 +1,19 @@
 // src/api/status.rs
 
+use super::query::{list_response, ListQuery};
 use crate::{
     config::get_config_by_service,
     container::{
         health::{self, ContainerHealthState},
-        INSTANCE_STORE, SERVICE_STATS,
+        secrets::{secret_summaries, SecretSummary},
+        shaping::{get_shaping_state, ShapingState},
+        stopped, INSTANCE_STORE, SERVICE_STATS,
     },
     proxy::SERVER_BACKENDS,
 };
-use axum::Json;
+use axum::{extract::Query, http::HeaderMap, response::Response};
 use serde::Serialize;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 #[derive(Serialize)]
@@ -31,6 +35,13 @@ pub struct ServiceStatus {
     pub service_name: String,
     pub service_ports: Vec<u16>,
     pub service_urls: Vec<ServiceUrl>, // Add this field
+    pub annotations: HashMap<String, String>,
+    /// Stopped by its config or through the API, and running no pods
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stopped: bool,
+    /// Names and targets only, values are never returned
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<SecretSummary>,
     pub pods: Vec<PodStatus>,
 }
 
@@ -58,8 +69,13 @@ pub struct ContainerStatus {
     pub cpu_percentage_relative: Option<f64>,
     pub memory_usage: Option<u64>,
     pub memory_limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpu_percentage: Option<f64>,
     pub urls: Vec<ContainerUrl>,
     pub health_status: Option<ContainerHealthState>,
+    /// Bandwidth limits applied to the container's interface
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_shaping: Option<ShapingState>,
 }
 
 #[derive(Debug, Serialize)]
@@ -73,7 +89,7 @@ pub struct ContainerInfo {
     memory_limit: Option<u64>,
 }
 
-pub async fn get_status() -> Json<Vec<ServiceStatus>> {
+pub async fn get_status(Query(query): Query<ListQuery>, headers: HeaderMap) -> Response {
     let instance_store = INSTANCE_STORE
         .get()
         .expect("Instance store not initialized");
@@ -90,8 +106,18 @@ pub async fn get_status() -> Json<Vec<ServiceStatus>> {
     let service_stats_read = service_stats.read().await;
     let backends_map = server_backends.read().await;
 
-    for (service_name, instances) in store_map.iter() {
-        let service_config = get_config_by_service(service_name).await;
+    // Sort by name so pages are stable between requests
+    let mut service_names: Vec<&String> = store_map
+        .keys()
+        .filter(|name| query.matches_service(name))
+        .collect();
+    service_names.sort();
+
+    for service_name in service_names {
+        let instances = &store_map[service_name];
+        let service_config = get_config_by_service(service_name)
+            .await
+            .filter(|config| query.matches_annotations(&config.annotations));
 
         if let Some(config) = service_config {
             // Get service stats if available, using the existing read lock
@@ -147,7 +173,7 @@ pub async fn get_status() -> Json<Vec<ServiceStatus>> {
                         let ports = futures::future::join_all(container.ports.iter().map(
                             |port_info| async {
                                 let container_addr =
-                                    format!("{}:{}", container.ip_address, port_info.port);
+                                    port_info.backend_address(&container.ip_address);
                                 let healthy = match port_info.node_port {
                                     Some(node_port) => {
                                         let proxy_key = format!("{}__{}", service_name, node_port);
@@ -183,6 +209,7 @@ pub async fn get_status() -> Json<Vec<ServiceStatus>> {
                         ContainerStatus {
                             urls,
                             health_status,
+                            network_shaping: get_shaping_state(&container.name),
                             name: container.name.clone(),
                             ip_address: container.ip_address.clone(),
                             ports,
@@ -192,8 +219,7 @@ pub async fn get_status() -> Json<Vec<ServiceStatus>> {
                                 if has_node_port {
                                     let mut running = false;
                                     for port_info in &container.ports {
-                                        let addr =
-                                            format!("{}:{}", container.ip_address, port_info.port);
+                                        let addr = port_info.backend_address(&container.ip_address);
                                         if let Some(node_port) = port_info.node_port {
                                             let proxy_key =
                                                 format!("{}__{}", service_name, node_port);
@@ -227,6 +253,7 @@ pub async fn get_status() -> Json<Vec<ServiceStatus>> {
                                 .map(|s| s.cpu_percentage_relative),
                             memory_usage: container_stats.as_ref().map(|s| s.memory_usage),
                             memory_limit: container_stats.as_ref().map(|s| s.memory_limit),
+                            gpu_percentage: container_stats.as_ref().and_then(|s| s.gpu_percentage),
                         }
                     }))
                     .await;
@@ -242,6 +269,9 @@ pub async fn get_status() -> Json<Vec<ServiceStatus>> {
                 service_name: service_name.clone(),
                 service_ports,
                 service_urls,
+                annotations: config.annotations.clone(),
+                stopped: stopped::is_stopped(&config),
+                secrets: secret_summaries(&config),
                 pods,
             });
         }
@@ -252,5 +282,5 @@ pub async fn get_status() -> Json<Vec<ServiceStatus>> {
     drop(service_stats_read);
     drop(store_map);
 
-    Json(services)
+    list_response(services, &query, &headers)
 }
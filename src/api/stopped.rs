@@ -0,0 +1,62 @@
+// src/api/stopped.rs
+
+use crate::config::get_config_by_service;
+use crate::container::stopped::{start, stop};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+fn not_found(service_name: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": format!("Service {} not found", service_name) })),
+    )
+        .into_response()
+}
+
+/// Stop the service's pods but keep it registered. Answers once the pods have
+/// drained and stopped.
+pub async fn post_stop(Path(service_name): Path<String>) -> Response {
+    if get_config_by_service(&service_name).await.is_none() {
+        return not_found(&service_name);
+    }
+
+    match stop(&service_name).await {
+        Ok(pods_stopped) => Json(json!({
+            "service_name": service_name,
+            "stopped": true,
+            "pods_stopped": pods_stopped,
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Start a service stopped through the API. Services disabled by their config
+/// can only be started by changing it.
+pub async fn post_start(Path(service_name): Path<String>) -> Response {
+    if get_config_by_service(&service_name).await.is_none() {
+        return not_found(&service_name);
+    }
+
+    match start(&service_name).await {
+        Ok(()) => Json(json!({
+            "service_name": service_name,
+            "stopped": false,
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::CONFLICT,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
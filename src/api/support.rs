@@ -0,0 +1,32 @@
+// src/api/support.rs
+
+use crate::support::build_bundle;
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// A support bundle of the daemon's configs, status, events, scaling history,
+/// metrics and logs, as a gzipped tarball
+pub async fn get_support_bundle() -> Response {
+    match build_bundle().await {
+        Ok(archive) => (
+            [
+                (header::CONTENT_TYPE, "application/gzip"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"orbit-support.tar.gz\"",
+                ),
+            ],
+            archive,
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to build support bundle: {}", e) })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,51 @@
+// src/api/ttl.rs
+
+use crate::container::ttl::{extend_ttl, get_ttl};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+pub struct ExtendRequest {
+    #[serde(with = "humantime_serde")]
+    pub extend_by: Duration,
+}
+
+fn not_found(service_name: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": format!("Service {} not found or has no expires_after", service_name) })),
+    )
+        .into_response()
+}
+
+pub async fn get_service_ttl(Path(service_name): Path<String>) -> Response {
+    match get_ttl(&service_name).await {
+        Some(status) => Json(status).into_response(),
+        None => not_found(&service_name),
+    }
+}
+
+pub async fn extend_service_ttl(
+    Path(service_name): Path<String>,
+    Json(request): Json<ExtendRequest>,
+) -> Response {
+    if get_ttl(&service_name).await.is_none() {
+        return not_found(&service_name);
+    }
+
+    match extend_ttl(&service_name, request.extend_by).await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
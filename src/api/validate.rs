@@ -0,0 +1,29 @@
+// src/api/validate.rs
+
+use crate::config::parse_yaml_config;
+use crate::config::reload::CONFIG_DIR;
+use crate::config::report::ValidationReport;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::path::Path;
+
+/// Validate a service config sent as YAML, as a new version of the service of
+/// the same name, so it may reuse that service's name and ports. Relative paths
+/// are taken as relative to the config directory.
+pub async fn post_validate(body: String) -> Response {
+    let name = serde_yaml::from_str::<serde_yaml::Value>(&body)
+        .ok()
+        .and_then(|value| value.get("name")?.as_str().map(str::to_string));
+    let config_dir = CONFIG_DIR.get().map_or(Path::new("."), |dir| dir.as_path());
+    let result = parse_yaml_config(&body, name.as_deref(), config_dir).await;
+    let report = ValidationReport::new("-".to_string(), &result);
+    let status = if report.valid {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    (status, Json(report)).into_response()
+}
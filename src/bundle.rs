@@ -0,0 +1,719 @@
+// src/bundle.rs
+//
+// Application bundles: several services and shared values, installed, upgraded
+// and removed as one unit. `orbit bundle install` renders every service with
+// the values and validates all of them before writing any file. The rendered
+// configs go to `<config_dir>/bundles/<name>/`, where the daemon loads them
+// like any other config.
+use crate::config::validate::{
+    check_container_name_uniqueness, check_port_conflicts, check_service_name_uniqueness,
+    check_volume_claim_conflicts, validate_container_env, validate_container_filesystem,
+    validate_container_limits, validate_container_metadata, validate_external,
+    validate_runtime_support, validate_secrets, validate_service_name, validate_service_ports,
+    validate_volume_claims,
+};
+use crate::config::{
+    config_files, read_yaml_config, resolve_env_files, ServiceConfig, CONFIG_STORE,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use tokio::process::Command;
+
+pub const BUNDLES_DIR: &str = "bundles";
+/// Annotation added to every service of a bundle
+pub const BUNDLE_ANNOTATION: &str = "orbit.bundle";
+const DEFINITION_FILE: &str = "bundle.yaml";
+// Not YAML, so the config watcher leaves it alone
+const MANIFEST_FILE: &str = "bundle.json";
+
+// Where the daemon looks for installed bundles, set at startup
+pub static BUNDLES_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Deserialize)]
+struct BundleDefinition {
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+    /// Defaults for `${name}` placeholders in the service files
+    #[serde(default)]
+    values: BTreeMap<String, serde_yaml::Value>,
+    /// Service files relative to the bundle; every YAML file in `services/` when empty
+    #[serde(default)]
+    services: Vec<PathBuf>,
+}
+
+/// Written next to the rendered services to track the bundle as one unit
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleManifest {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub services: Vec<String>,
+    pub installed_at: SystemTime,
+    pub updated_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleAction {
+    Install,
+    Upgrade,
+}
+
+struct RenderedService {
+    config: ServiceConfig,
+    contents: String,
+}
+
+fn bundle_dir(config_dir: &Path, name: &str) -> PathBuf {
+    config_dir.join(BUNDLES_DIR).join(name)
+}
+
+pub async fn read_manifest(bundle_dir: &Path) -> Result<BundleManifest> {
+    let path = bundle_dir.join(MANIFEST_FILE);
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Manifests of every installed bundle, ordered by name
+pub async fn installed_bundles(bundles_root: &Path) -> Vec<BundleManifest> {
+    let mut manifests = Vec::new();
+    let Ok(mut entries) = tokio::fs::read_dir(bundles_root).await else {
+        return manifests;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Ok(manifest) = read_manifest(&entry.path()).await {
+            manifests.push(manifest);
+        }
+    }
+    manifests.sort_by(|a, b| a.name.cmp(&b.name));
+    manifests
+}
+
+// Directories are used as-is; archives are unpacked with the system tar
+async fn unpack(source: &Path) -> Result<(PathBuf, Option<tempfile::TempDir>)> {
+    if source.is_dir() {
+        return Ok((source.to_path_buf(), None));
+    }
+
+    let temp_dir = tempfile::tempdir()?;
+    let status = Command::new("tar")
+        .arg("-xf")
+        .arg(source)
+        .arg("-C")
+        .arg(temp_dir.path())
+        .status()
+        .await
+        .map_err(|e| anyhow!("Failed to run tar: {}", e))?;
+    if !status.success() {
+        return Err(anyhow!(
+            "Failed to unpack bundle {} ({})",
+            source.display(),
+            status
+        ));
+    }
+
+    // Archives usually wrap the bundle in a single top-level directory
+    let mut root = temp_dir.path().to_path_buf();
+    if !root.join(DEFINITION_FILE).exists() {
+        let mut dirs = std::fs::read_dir(&root)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir());
+        if let (Some(dir), None) = (dirs.next(), dirs.next()) {
+            root = dir;
+        }
+    }
+    Ok((root, Some(temp_dir)))
+}
+
+fn value_to_string(name: &str, value: &serde_yaml::Value) -> Result<String> {
+    match value {
+        serde_yaml::Value::String(s) => Ok(s.clone()),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        serde_yaml::Value::Null => Ok(String::new()),
+        _ => Err(anyhow!(
+            "Value {} must be a string, number or boolean",
+            name
+        )),
+    }
+}
+
+/// Replace `${name}` placeholders; `$${` is a literal `${`
+fn render(template: &str, values: &BTreeMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unterminated placeholder"))?;
+        let name = rest[start + 2..start + end].trim();
+        let value = values
+            .get(name)
+            .ok_or_else(|| anyhow!("No value for placeholder ${{{}}}", name))?;
+        output.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+// Bundle defaults, then each values file in order, then --set overrides
+async fn merge_values(
+    defaults: &BTreeMap<String, serde_yaml::Value>,
+    values_files: &[PathBuf],
+    overrides: &[String],
+) -> Result<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    for (name, value) in defaults {
+        values.insert(name.clone(), value_to_string(name, value)?);
+    }
+
+    for path in values_files {
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| anyhow!("Failed to read values file {}: {}", path.display(), e))?;
+        let file: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid values file {}: {}", path.display(), e))?;
+        for (name, value) in &file {
+            values.insert(name.clone(), value_to_string(name, value)?);
+        }
+    }
+
+    for assignment in overrides {
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected KEY=VALUE, got {}", assignment))?;
+        values.insert(name.trim().to_string(), value.to_string());
+    }
+
+    Ok(values)
+}
+
+// Listed service files must stay inside the bundle, symlinks included
+fn bundle_file(root: &Path, file: &Path) -> Result<PathBuf> {
+    if !file
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(anyhow!(
+            "Service file {} must be a relative path within the bundle",
+            file.display()
+        ));
+    }
+
+    let path = root.join(file);
+    let resolved = path
+        .canonicalize()
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    if !resolved.starts_with(root.canonicalize()?) {
+        return Err(anyhow!(
+            "Service file {} points outside the bundle",
+            file.display()
+        ));
+    }
+    Ok(path)
+}
+
+fn service_files(root: &Path, definition: &BundleDefinition) -> Result<Vec<PathBuf>> {
+    if !definition.services.is_empty() {
+        return definition
+            .services
+            .iter()
+            .map(|file| bundle_file(root, file))
+            .collect();
+    }
+
+    let services_dir = root.join("services");
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&services_dir)
+        .map_err(|e| anyhow!("Failed to read {}: {}", services_dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("yaml") | Some("yml")
+            )
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+async fn render_services(
+    root: &Path,
+    definition: &BundleDefinition,
+    values: &BTreeMap<String, String>,
+) -> Result<Vec<RenderedService>> {
+    let mut rendered = Vec::new();
+
+    for path in service_files(root, definition)? {
+        let template = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let text = render(&template, values).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        // Tag the service with its bundle so it can be selected by annotation
+        let mut document: serde_yaml::Value = serde_yaml::from_str(&text)
+            .map_err(|e| anyhow!("{}: invalid YAML: {}", path.display(), e))?;
+        let mapping = document
+            .as_mapping_mut()
+            .ok_or_else(|| anyhow!("{}: expected a service config", path.display()))?;
+        let annotations = mapping
+            .entry("annotations".into())
+            .or_insert_with(|| serde_yaml::Value::Mapping(Default::default()));
+        if let Some(annotations) = annotations.as_mapping_mut() {
+            annotations.insert(BUNDLE_ANNOTATION.into(), definition.name.clone().into());
+        }
+
+        let config: ServiceConfig = serde_yaml::from_value(document.clone())
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        let contents = format!(
+            "# Installed by `orbit bundle` from {}; upgrades overwrite this file\n{}",
+            definition.name,
+            serde_yaml::to_string(&document)?
+        );
+        rendered.push(RenderedService { config, contents });
+    }
+
+    if rendered.is_empty() {
+        return Err(anyhow!("Bundle {} has no services", definition.name));
+    }
+    Ok(rendered)
+}
+
+// Check every service against the configs outside the bundle and each other
+async fn validate_services(
+    config_dir: &Path,
+    bundle_dir: &Path,
+    services: &[RenderedService],
+) -> Result<()> {
+    let config_store = CONFIG_STORE.get().expect("Config store not initialized");
+
+    for path in config_files(config_dir)? {
+        if path.starts_with(bundle_dir) {
+            continue;
+        }
+        if let Ok(config) = read_yaml_config(&path, None).await {
+            config_store
+                .write()
+                .await
+                .insert(path.display().to_string(), (path.clone(), config));
+        }
+    }
+
+    let mut errors = Vec::new();
+    for service in services {
+        // As the daemon will load it from the bundle's directory
+        let mut config = service.config.clone();
+        resolve_env_files(&mut config, bundle_dir);
+        let config = &config;
+        let result: Result<()> = async {
+            validate_service_name(&config.name)?;
+            check_service_name_uniqueness(config, None).await?;
+            check_container_name_uniqueness(config)?;
+            validate_service_ports(config)?;
+            validate_container_env(config).await?;
+            validate_container_limits(config)?;
+            validate_container_metadata(config)?;
+            validate_container_filesystem(config)?;
+            validate_external(config)?;
+            validate_runtime_support(config)?;
+            validate_secrets(config)?;
+            validate_volume_claims(config)?;
+            check_port_conflicts(config, None).await?;
+            check_volume_claim_conflicts(config, None).await?;
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                let path = bundle_dir.join(format!("{}.yaml", config.name));
+                config_store
+                    .write()
+                    .await
+                    .insert(path.display().to_string(), (path, config.clone()));
+            }
+            Err(e) => errors.push(format!("{}: {}", config.name, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "Bundle is invalid, nothing was changed:\n  {}",
+            errors.join("\n  ")
+        ));
+    }
+    Ok(())
+}
+
+// A hidden file next to `path`, whose extension the watcher ignores
+fn hidden_sibling(path: &Path, extension: &str) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid path {}", path.display()))?;
+    Ok(path.with_file_name(format!(".{}.{}", file_name, extension)))
+}
+
+// Write to a hidden temp file first, so the watcher only sees complete files
+pub(crate) async fn write_atomic(path: &Path, contents: &str) -> Result<PathBuf> {
+    let temp_path = hidden_sibling(path, "tmp")?;
+    tokio::fs::write(&temp_path, contents).await?;
+    Ok(temp_path)
+}
+
+// Write every file to its temp file, as (temp path, path) pairs. Nothing is
+// left behind if one of them fails.
+async fn stage(files: &[(PathBuf, String)]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut staged = Vec::new();
+    for (path, contents) in files {
+        match write_atomic(path, contents).await {
+            Ok(temp_path) => staged.push((temp_path, path.clone())),
+            Err(e) => {
+                for (temp_path, _) in &staged {
+                    let _ = tokio::fs::remove_file(temp_path).await;
+                }
+                return Err(anyhow!("Failed to write {}: {}", path.display(), e));
+            }
+        }
+    }
+    Ok(staged)
+}
+
+// Move staged files into place. The file each one replaces is kept as a hard
+// link until all are moved, so that if one move fails, those already moved
+// are put back and the bundle is left as it was.
+async fn commit(staged: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let mut moved: Vec<(&Path, Option<PathBuf>)> = Vec::new();
+    let mut failure = None;
+
+    for (temp_path, path) in staged {
+        let backup = match hidden_sibling(path, "bak") {
+            Ok(backup) => backup,
+            Err(e) => {
+                failure = Some(e);
+                break;
+            }
+        };
+        let _ = tokio::fs::remove_file(&backup).await;
+        let backup = match tokio::fs::hard_link(path, &backup).await {
+            Ok(()) => Some(backup),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                failure = Some(anyhow!("Failed to back up {}: {}", path.display(), e));
+                break;
+            }
+        };
+        if let Err(e) = tokio::fs::rename(temp_path, path).await {
+            if let Some(backup) = &backup {
+                let _ = tokio::fs::remove_file(backup).await;
+            }
+            failure = Some(anyhow!("Failed to replace {}: {}", path.display(), e));
+            break;
+        }
+        moved.push((path, backup));
+    }
+
+    if let Some(e) = failure {
+        for (path, backup) in moved.into_iter().rev() {
+            let restored = match backup {
+                Some(backup) => tokio::fs::rename(&backup, path).await,
+                None => tokio::fs::remove_file(path).await,
+            };
+            if let Err(restore_error) = restored {
+                slog::error!(slog_scope::logger(), "Failed to restore bundle file";
+                    "path" => path.display().to_string(),
+                    "error" => restore_error.to_string()
+                );
+            }
+        }
+        for (temp_path, _) in staged {
+            let _ = tokio::fs::remove_file(temp_path).await;
+        }
+        return Err(e);
+    }
+
+    for (_, backup) in moved {
+        if let Some(backup) = backup {
+            let _ = tokio::fs::remove_file(backup).await;
+        }
+    }
+    Ok(())
+}
+
+pub async fn install(
+    config_dir: &Path,
+    source: &Path,
+    values_files: &[PathBuf],
+    overrides: &[String],
+    action: BundleAction,
+) -> Result<BundleManifest> {
+    let (root, _unpacked) = unpack(source).await?;
+    let definition_path = root.join(DEFINITION_FILE);
+    let definition: BundleDefinition = serde_yaml::from_str(
+        &tokio::fs::read_to_string(&definition_path)
+            .await
+            .map_err(|e| anyhow!("Failed to read {}: {}", definition_path.display(), e))?,
+    )
+    .map_err(|e| anyhow!("Invalid {}: {}", definition_path.display(), e))?;
+    validate_service_name(&definition.name).map_err(|e| anyhow!("Invalid bundle name: {}", e))?;
+
+    let bundle_dir = bundle_dir(config_dir, &definition.name);
+    let existing = read_manifest(&bundle_dir).await.ok();
+    match (action, &existing) {
+        (BundleAction::Install, Some(_)) => {
+            return Err(anyhow!(
+                "Bundle {} is already installed, use `orbit bundle upgrade`",
+                definition.name
+            ))
+        }
+        (BundleAction::Upgrade, None) => {
+            return Err(anyhow!("Bundle {} is not installed", definition.name))
+        }
+        _ => {}
+    }
+
+    let values = merge_values(&definition.values, values_files, overrides).await?;
+    let services = render_services(&root, &definition, &values).await?;
+    validate_services(config_dir, &bundle_dir, &services).await?;
+
+    let names: Vec<String> = services.iter().map(|s| s.config.name.clone()).collect();
+    let now = SystemTime::now();
+    let manifest = BundleManifest {
+        name: definition.name.clone(),
+        version: definition.version.clone(),
+        services: names.clone(),
+        installed_at: existing.as_ref().map(|m| m.installed_at).unwrap_or(now),
+        updated_at: now,
+    };
+
+    // Everything is valid: stage all files, then move them into place together
+    tokio::fs::create_dir_all(&bundle_dir).await?;
+    let mut files: Vec<(PathBuf, String)> = services
+        .iter()
+        .map(|service| {
+            (
+                bundle_dir.join(format!("{}.yaml", service.config.name)),
+                service.contents.clone(),
+            )
+        })
+        .collect();
+    files.push((
+        bundle_dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    ));
+    commit(&stage(&files).await?).await?;
+
+    // Services dropped from the bundle are removed
+    if let Some(existing) = &existing {
+        for name in existing
+            .services
+            .iter()
+            .filter(|name| !names.contains(name))
+        {
+            let _ = tokio::fs::remove_file(bundle_dir.join(format!("{}.yaml", name))).await;
+        }
+    }
+
+    Ok(manifest)
+}
+
+pub async fn uninstall(config_dir: &Path, name: &str) -> Result<BundleManifest> {
+    validate_service_name(name).map_err(|e| anyhow!("Invalid bundle name: {}", e))?;
+    let bundle_dir = bundle_dir(config_dir, name);
+    let manifest = read_manifest(&bundle_dir)
+        .await
+        .map_err(|_| anyhow!("Bundle {} is not installed", name))?;
+
+    // The daemon stops each service as its config disappears
+    tokio::fs::remove_dir_all(&bundle_dir).await?;
+    Ok(manifest)
+}
+
+/// Entry point for `orbit bundle`
+pub async fn run(config_dir: &Path, command: &BundleCommand) -> Result<()> {
+    match command {
+        BundleCommand::Install {
+            source,
+            values,
+            set,
+        }
+        | BundleCommand::Upgrade {
+            source,
+            values,
+            set,
+        } => {
+            let action = match command {
+                BundleCommand::Install { .. } => BundleAction::Install,
+                _ => BundleAction::Upgrade,
+            };
+            let manifest = install(config_dir, source, values, set, action).await?;
+            println!(
+                "{} bundle {} {}({})",
+                if action == BundleAction::Install {
+                    "installed"
+                } else {
+                    "upgraded"
+                },
+                manifest.name,
+                manifest
+                    .version
+                    .as_ref()
+                    .map(|v| format!("{} ", v))
+                    .unwrap_or_default(),
+                manifest.services.join(", ")
+            );
+        }
+        BundleCommand::Uninstall { name } => {
+            let manifest = uninstall(config_dir, name).await?;
+            println!(
+                "uninstalled bundle {} ({})",
+                manifest.name,
+                manifest.services.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum BundleCommand {
+    /// Install a bundle directory or archive
+    Install {
+        /// Bundle directory, or a .tar/.tar.gz archive of one
+        source: PathBuf,
+        /// YAML file of values, overriding the bundle defaults (repeatable)
+        #[arg(short = 'f', long)]
+        values: Vec<PathBuf>,
+        /// Override a single value, as KEY=VALUE (repeatable)
+        #[arg(long)]
+        set: Vec<String>,
+    },
+    /// Replace an installed bundle with a new version
+    Upgrade {
+        /// Bundle directory, or a .tar/.tar.gz archive of one
+        source: PathBuf,
+        /// YAML file of values, overriding the bundle defaults (repeatable)
+        #[arg(short = 'f', long)]
+        values: Vec<PathBuf>,
+        /// Override a single value, as KEY=VALUE (repeatable)
+        #[arg(long)]
+        set: Vec<String>,
+    },
+    /// Remove an installed bundle and all of its services
+    Uninstall {
+        /// Bundle name
+        name: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let values = BTreeMap::from([("tag".to_string(), "1.4.0".to_string())]);
+        assert_eq!(
+            render("image: shop/web:${ tag }", &values).unwrap(),
+            "image: shop/web:1.4.0"
+        );
+        assert_eq!(render("cmd: $${HOME}", &values).unwrap(), "cmd: ${HOME}");
+        assert!(render("image: ${missing}", &values).is_err());
+        assert!(render("image: ${tag", &values).is_err());
+    }
+
+    #[test]
+    fn test_bundle_file_stays_in_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("shop");
+        std::fs::create_dir_all(root.join("services")).unwrap();
+        std::fs::write(root.join("services/web.yaml"), "name: web\n").unwrap();
+        std::fs::write(dir.path().join("secret.yaml"), "name: secret\n").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret.yaml"), root.join("link.yaml")).unwrap();
+
+        assert_eq!(
+            bundle_file(&root, Path::new("services/web.yaml")).unwrap(),
+            root.join("services/web.yaml")
+        );
+        assert!(bundle_file(&root, Path::new("../secret.yaml")).is_err());
+        assert!(bundle_file(&root, Path::new("services/../../secret.yaml")).is_err());
+        assert!(bundle_file(&root, &dir.path().join("secret.yaml")).is_err());
+        assert!(bundle_file(&root, Path::new("link.yaml")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_commit_restores_files_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let web = dir.path().join("web.yaml");
+        let api = dir.path().join("api.yaml");
+        std::fs::write(&web, "old").unwrap();
+
+        // The api file's temp file is gone, so moving it fails after web was replaced
+        let mut staged = stage(&[
+            (web.clone(), "new".to_string()),
+            (api.clone(), "new".to_string()),
+        ])
+        .await
+        .unwrap();
+        std::fs::remove_file(&staged[1].0).unwrap();
+        assert!(commit(&staged).await.is_err());
+        assert_eq!(std::fs::read_to_string(&web).unwrap(), "old");
+        assert!(!api.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        staged = stage(&[
+            (web.clone(), "new".to_string()),
+            (api.clone(), "new".to_string()),
+        ])
+        .await
+        .unwrap();
+        commit(&staged).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&web).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(&api).unwrap(), "new");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_rejects_invalid_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(dir.path().join(BUNDLES_DIR)).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let manifest = BundleManifest {
+            name: "outside".to_string(),
+            version: None,
+            services: Vec::new(),
+            installed_at: SystemTime::now(),
+            updated_at: SystemTime::now(),
+        };
+        std::fs::write(
+            outside.join(MANIFEST_FILE),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        // Resolves to the outside directory, which a bundle manifest would let through
+        let err = uninstall(dir.path(), "../outside").await.unwrap_err();
+        assert!(err.to_string().starts_with("Invalid bundle name"));
+        assert!(outside.exists());
+    }
+}
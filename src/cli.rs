@@ -0,0 +1,618 @@
+// src/cli.rs
+//
+// The orbit command line: parses the arguments, runs one-off subcommands and
+// otherwise starts the daemon. Embedders that register their own runtimes
+// call `run` the way src/main.rs does.
+use crate::config::{daemon::ApiListen, CONFIG_STORE};
+use crate::container::{
+    create_runtime, health::CONTAINER_HEALTH, pod_numbers::initialize_pod_numbers,
+    scaling::codel::initialize_codel_metrics, volumes::initialize_volume_store, CONTAINER_STATS,
+    IMAGE_CHECK_TASKS, INSTANCE_STORE, NETWORK_USAGE, RUNTIME, SCALING_TASKS, SERVICE_STATS,
+};
+use crate::logger::setup_logger;
+use crate::metrics::{volumes::start_volume_metrics_task, MetricsUpdate};
+use crate::proxy::{SERVER_BACKENDS, SERVER_TASKS};
+use crate::{
+    api, bundle, clone, config, container, diff, egress, metrics, node, redact, replay, support,
+    systemd, validate, wait,
+};
+use anyhow::Result;
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use clap::{Parser, Subcommand};
+use rustc_hash::FxHashMap;
+use std::{
+    fs,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::PathBuf,
+    process,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::RwLock;
+
+// Permissions of the API's Unix socket, which needs write access to connect
+const API_SOCKET_MODE: u32 = 0o660;
+
+macro_rules! crate_version {
+    () => {
+        env!("CARGO_PKG_VERSION")
+    };
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "Orbit by AirPipe.io",
+    author = "AirPipe.io",
+    version = crate_version!(),
+    about = "Orbit Container Management",
+    long_about = None,
+    help_template = "{author-with-newline} \
+        {about-section}\
+        Version: {version}\n\n\
+        {usage-heading} {usage}\n\n\
+        {all-args}",
+)]
+pub struct Args {
+    /// Directory containing service configuration YAML files
+    #[arg(short, long, default_value = "configs")]
+    config_dir: PathBuf,
+    /// Directory of partial service configs merged over those in the config
+    /// directory; may be repeated, later ones taking precedence
+    #[arg(long = "override-dir")]
+    override_dirs: Vec<PathBuf>,
+    /// Container runtime
+    #[arg(short, long, default_value = "docker")]
+    runtime: String,
+    /// Log level
+    #[arg(
+        short,
+        long,
+        default_value = "info",
+        env = "LOG_LVL",
+        help = "Log Levels: info, debug, warning, error, trace, critical"
+    )]
+    log_level: String,
+
+    /// Directory for storing persistent volumes
+    #[arg(long, default_value = "volumes")]
+    volume_dir: PathBuf,
+
+    /// Directory for daemon state that must survive restarts (e.g. pod numbers)
+    #[arg(long, default_value = "state")]
+    state_dir: PathBuf,
+
+    /// Daemon configuration file (runtime connection settings)
+    #[arg(long, env = "ORBIT_DAEMON_CONFIG")]
+    daemon_config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compare declared configs against the runtime and print any drift
+    Diff {
+        /// Only diff this service
+        #[arg(short, long)]
+        service: Option<String>,
+        /// Status API of a running Orbit, used for image and backend checks
+        #[arg(long, default_value = "http://localhost:4112")]
+        api: String,
+    },
+    /// Block until a service meets its readiness definition
+    Wait {
+        /// Service to wait for
+        service: String,
+        /// Give up after this many seconds
+        #[arg(short, long, default_value_t = 300)]
+        timeout: u64,
+        /// Status API of the running Orbit
+        #[arg(long, default_value = "http://localhost:4112")]
+        api: String,
+    },
+    /// Replay a service's recorded traffic against it and report how it coped
+    Replay {
+        /// Service to load test
+        service: String,
+        /// Requests per second
+        #[arg(short, long, default_value_t = 10.0)]
+        rate: f64,
+        /// How long to replay for, in seconds
+        #[arg(short, long, default_value_t = 60)]
+        duration: u64,
+        /// Maximum requests in flight
+        #[arg(short, long, default_value_t = 64)]
+        concurrency: usize,
+        /// Also replay requests other than GET and HEAD, without their bodies
+        #[arg(long)]
+        all_methods: bool,
+        /// Status API of the running Orbit
+        #[arg(long, default_value = "http://localhost:4112")]
+        api: String,
+    },
+    /// Clone a service under a new name, e.g. for a preview environment
+    Clone {
+        /// Service to clone
+        service: String,
+        /// Name of the new service
+        name: String,
+        /// Image tag for the clone's containers
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Only retag these containers (repeatable)
+        #[arg(long)]
+        container: Vec<String>,
+        /// Remove the clone after this long, e.g. "2h" or "3days"
+        #[arg(long, value_parser = humantime_serde::re::humantime::parse_duration)]
+        ttl: Option<Duration>,
+        /// Status API of the running Orbit
+        #[arg(long, default_value = "http://localhost:4112")]
+        api: String,
+    },
+    /// Install, upgrade or remove a bundle of services
+    Bundle {
+        #[command(subcommand)]
+        command: bundle::BundleCommand,
+    },
+    /// Check config files without starting anything, reporting each problem
+    Validate {
+        /// Files to check (default: every config in the config directory)
+        files: Vec<PathBuf>,
+        /// Print the reports as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Save a support bundle of configs, status, events, scaling history,
+    /// metrics and logs for a bug report
+    SupportBundle {
+        /// Where to write the archive
+        #[arg(short, long, default_value = "orbit-support.tar.gz")]
+        output: PathBuf,
+        /// Status API of the running Orbit
+        #[arg(long, default_value = "http://localhost:4112")]
+        api: String,
+    },
+    /// Pull images ahead of their first deploy, e.g. on a new node
+    Prefetch {
+        /// Images to pull (default: the services' images not pulled yet)
+        images: Vec<String>,
+        /// Take the images from the Orbit at this API, e.g. an existing node
+        #[arg(long)]
+        from: Option<String>,
+        /// Only images sharing layers with another image
+        #[arg(long)]
+        shared: bool,
+        /// Status API of the Orbit that pulls them
+        #[arg(long, default_value = "http://localhost:4112")]
+        api: String,
+    },
+    /// Replay a service's recorded stats through the scaler with a candidate
+    /// policy, and report the pod count over time and the SLO impact
+    Simulate {
+        /// Service whose stats to replay
+        service: String,
+        /// Candidate service config (default: the service's config)
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Candidate scaling policy, replacing the config's
+        #[arg(long)]
+        policy: Option<PathBuf>,
+        /// How much of the recording to replay, e.g. "1day"
+        #[arg(long, default_value = "1day", value_parser = humantime_serde::re::humantime::parse_duration)]
+        last: Duration,
+        /// Per-pod CPU percentage above which the SLO is missed (default: the
+        /// service's CPU threshold, or 80)
+        #[arg(long)]
+        slo_cpu: Option<f64>,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Run the command given on the command line, the daemon by default. Call
+/// `systemd::take_activation_env` first, before any thread is started.
+pub async fn run() -> Result<()> {
+    // Initialize the global stores
+    CONFIG_STORE.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    INSTANCE_STORE.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    CONTAINER_HEALTH.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    CONTAINER_STATS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SCALING_TASKS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SERVICE_STATS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SERVER_TASKS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SERVER_BACKENDS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    IMAGE_CHECK_TASKS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    NETWORK_USAGE.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+
+    initialize_codel_metrics();
+
+    // Parse command line arguments
+    let args = Args::parse();
+
+    // Only talks to a running daemon, so none of the local setup is needed
+    if let Some(Command::Wait {
+        service,
+        timeout,
+        api,
+    }) = &args.command
+    {
+        let ready = wait::run(service, api, Duration::from_secs(*timeout)).await?;
+        process::exit(if ready { 0 } else { 1 });
+    }
+    if let Some(Command::Replay {
+        service,
+        rate,
+        duration,
+        concurrency,
+        all_methods,
+        api,
+    }) = &args.command
+    {
+        let request = replay::ReplayRequest {
+            rate: *rate,
+            duration: Duration::from_secs(*duration),
+            concurrency: *concurrency,
+            timeout: Duration::from_secs(10),
+            all_methods: *all_methods,
+        };
+        replay::run(service, api, &request).await?;
+        process::exit(0);
+    }
+    if let Some(Command::Clone {
+        service,
+        name,
+        tag,
+        container,
+        ttl,
+        api,
+    }) = &args.command
+    {
+        let request = clone::CloneRequest {
+            name: name.clone(),
+            image_tag: tag.clone(),
+            containers: container.clone(),
+            ttl: *ttl,
+        };
+        if let Err(e) = clone::run(service, api, &request).await {
+            eprintln!("error: {:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+    if let Some(Command::SupportBundle { output, api }) = &args.command {
+        if let Err(e) = support::run(api, output).await {
+            eprintln!("error: {:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+    if let Some(Command::Prefetch {
+        images,
+        from,
+        shared,
+        api,
+    }) = &args.command
+    {
+        match container::images::run(api, from.as_deref(), images, *shared).await {
+            Ok(pulled) => process::exit(if pulled { 0 } else { 1 }),
+            Err(e) => {
+                eprintln!("error: {:#}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Writes to the config directory, where a running daemon picks up the changes
+    if let Some(Command::Bundle { command }) = &args.command {
+        fs::create_dir_all(&args.config_dir)?;
+        if let Err(e) = bundle::run(&args.config_dir, command).await {
+            eprintln!("error: {:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Only reads the config files, with the overrides a daemon would apply
+    if let Some(Command::Validate { files, json }) = &args.command {
+        let _ = config::layers::OVERRIDE_DIRS.set(args.override_dirs.clone());
+        let valid = validate::run(&args.config_dir, files, *json).await?;
+        process::exit(if valid { 0 } else { 1 });
+    }
+
+    // Reads the config files and the recorded stats, without starting anything
+    if let Some(Command::Simulate {
+        service,
+        config,
+        policy,
+        last,
+        slo_cpu,
+        json,
+    }) = &args.command
+    {
+        let _ = config::layers::OVERRIDE_DIRS.set(args.override_dirs.clone());
+        let options = container::scaling::simulate::SimulationOptions {
+            window: *last,
+            slo_cpu_percentage: *slo_cpu,
+        };
+        if let Err(e) = container::scaling::simulate::run(
+            &args.config_dir,
+            &args.state_dir,
+            service,
+            config.as_deref(),
+            policy.as_deref(),
+            &options,
+            *json,
+        )
+        .await
+        {
+            eprintln!("error: {:#}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Reads the config files and asks the runtime what runs, so it must not
+    // touch the state directory of a daemon that may be running
+    if let Some(Command::Diff { service, api }) = &args.command {
+        let _ = config::layers::OVERRIDE_DIRS.set(args.override_dirs.clone());
+        let daemon_config = config::daemon::load_daemon_config(args.daemon_config.as_deref())?;
+        let _ = config::daemon::DAEMON_CONFIG.set(daemon_config.clone());
+        let runtime = create_runtime(&args.runtime, &daemon_config)?;
+        let in_sync = diff::run(&args.config_dir, runtime, service.as_deref(), api).await?;
+        process::exit(if in_sync { 0 } else { 1 });
+    }
+
+    // Initialize volume store with configured path
+    initialize_volume_store(&args.volume_dir).await?;
+    start_volume_metrics_task().await;
+    initialize_pod_numbers(&args.state_dir)?;
+    container::ttl::initialize_service_ttls(&args.state_dir)?;
+    container::stopped::initialize_stopped_services(&args.state_dir)?;
+    container::crash_dumps::initialize_crash_dumps(&args.state_dir)?;
+    container::secrets::initialize_secrets_dir(&args.state_dir)?;
+    container::identity::initialize_identity_key(&args.state_dir)?;
+    container::rollback::initialize_rollbacks(&args.state_dir)?;
+    container::scaling::history::initialize_scaling_history(&args.state_dir)?;
+    container::scaling::predictive::initialize_stats_history(&args.state_dir)?;
+
+    setup_logger(args.log_level);
+    let log = slog_scope::logger();
+
+    // Pick up any sockets passed by systemd before binding listeners
+    systemd::initialize_activated_listeners(&args.state_dir)?;
+
+    // Setup logger
+    slog::info!(log, "Starting";
+        "config_dir" => args.config_dir.display().to_string(),
+        "override_dirs" => format!("{:?}", args.override_dirs),
+        "runtime" => args.runtime.to_string()
+    );
+
+    // Check if config directory exists, create if it doesn't
+    if !args.config_dir.exists() {
+        match fs::create_dir_all(&args.config_dir) {
+            Ok(_) => {
+                slog::info!(log, "Created configuration directory";
+                    "path" => args.config_dir.display().to_string()
+                );
+            }
+            Err(e) => {
+                slog::error!(log, "Failed to create configuration directory";
+                    "path" => args.config_dir.display().to_string(),
+                    "error" => e.to_string()
+                );
+                process::exit(1);
+            }
+        }
+    }
+
+    // Partial configs merged over those in the config directory
+    let _ = config::layers::OVERRIDE_DIRS.set(args.override_dirs.clone());
+
+    // Load daemon-level settings
+    let daemon_config = config::daemon::load_daemon_config(args.daemon_config.as_deref())?;
+    let _ = config::daemon::DAEMON_CONFIG.set(daemon_config.clone());
+
+    // init container runtime
+    let runtime = create_runtime(&args.runtime, &daemon_config)?;
+    RUNTIME.set(runtime).expect("Failed to set runtime");
+
+    // Initialise existing configs
+    config::initialize_configs(&args.config_dir).await?;
+    let _ = bundle::BUNDLES_ROOT.set(args.config_dir.join(bundle::BUNDLES_DIR));
+    let _ = clone::CONFIG_DIR.set(args.config_dir.clone());
+    let _ = config::reload::CONFIG_DIR.set(args.config_dir.clone());
+    config::reload::start_reload_on_hangup(args.config_dir.clone());
+    container::ttl::start_ttl_monitor();
+    container::restart::start_remediation();
+    container::anomaly::start_anomaly_detection();
+    container::recycling::start_recycling();
+    container::preemption::start_pressure_monitor();
+    node::start_node_monitor(vec![
+        PathBuf::from("/"),
+        args.volume_dir.clone(),
+        args.state_dir.clone(),
+    ]);
+
+    if let Some(egress_config) = daemon_config.egress.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = egress::run_egress_proxy(egress_config).await {
+                slog::error!(slog_scope::logger(), "Egress proxy stopped"; "error" => e.to_string());
+            }
+        });
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = config::watch_directory(args.config_dir.to_path_buf()).await {
+            let log = slog_scope::logger();
+            slog::error!(log, "failed to watch directory"; "err" => &e.to_string());
+        }
+    });
+
+    // Initialize metrics system
+    let _ = metrics::initialize_metrics();
+
+    // Needs the metrics registry for its expiry gauge
+    container::expiry::start_expiry_monitor();
+
+    // Start metrics collection task
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(Duration::from_secs(15));
+        loop {
+            interval.tick().await;
+            let instance_store = INSTANCE_STORE
+                .get()
+                .expect("Instance store not initialized");
+
+            // Get a read lock and calculate totals
+            let store = instance_store.read().await;
+            let total_services = store.len();
+            let total_instances: usize = store.values().map(|instances| instances.len()).sum();
+
+            // Explicitly drop the read lock
+            drop(store);
+            // Send updates asynchronously
+            let _ =
+                metrics::send_metrics_update(MetricsUpdate::TotalServices(total_services)).await;
+            let _ =
+                metrics::send_metrics_update(MetricsUpdate::TotalInstances(total_instances)).await;
+        }
+    });
+
+    let app = Router::new()
+        .route("/status", get(api::status::get_status))
+        .route("/instances", get(api::instances::get_instances))
+        .route(
+            "/instances/changes",
+            get(api::instances::get_instance_changes),
+        )
+        .route(
+            "/services/{service}/ready",
+            get(api::readiness::get_service_readiness),
+        )
+        .route(
+            "/services/{service}/health",
+            get(api::health::get_service_health),
+        )
+        .route(
+            "/services/{service}/recording",
+            get(api::replay::get_recording).delete(api::replay::delete_recording),
+        )
+        .route("/services/{service}/replay", post(api::replay::post_replay))
+        .route(
+            "/services/{service}/replay/{id}",
+            get(api::replay::get_replay),
+        )
+        .route("/services/{service}/clone", post(api::clone::post_clone))
+        .route("/services/{service}/stop", post(api::stopped::post_stop))
+        .route("/services/{service}/start", post(api::stopped::post_start))
+        .route(
+            "/services/{service}/scaling/history",
+            get(api::scaling::get_scaling_history),
+        )
+        .route(
+            "/services/{service}/ttl",
+            get(api::ttl::get_service_ttl).post(api::ttl::extend_service_ttl),
+        )
+        .route("/expiry", get(api::expiry::get_expiry))
+        .route("/canaries", get(api::canaries::get_canaries))
+        .route("/identity/verify", post(api::identity::post_verify))
+        .route("/schedule", get(api::schedule::get_schedule))
+        .route("/node", get(api::node::get_node))
+        .route("/groups", get(api::groups::get_groups))
+        .route("/groups/{group}/restart", post(api::groups::post_restart))
+        .route("/groups/{group}/scale", post(api::groups::post_scale))
+        .route("/groups/{group}/pause", post(api::groups::post_pause))
+        .route("/groups/{group}/resume", post(api::groups::post_resume))
+        .route("/images", get(api::images::get_images))
+        .route("/images/prefetch", post(api::images::post_prefetch))
+        .route("/bundles", get(api::bundles::get_bundles))
+        .route("/bundles/{bundle}", get(api::bundles::get_bundle))
+        .route("/validate", post(api::validate::post_validate))
+        .route("/reload", post(api::reload::post_reload))
+        .route("/support-bundle", get(api::support::get_support_bundle))
+        .route("/metrics", get(metrics::metrics_handler))
+        // Read-only Kubernetes API facade
+        .route("/version", get(api::kube::get_version))
+        .route("/api", get(api::kube::get_api_versions))
+        .route("/apis", get(api::kube::get_api_groups))
+        .route("/api/v1", get(api::kube::get_core_resources))
+        .route("/apis/apps/v1", get(api::kube::get_apps_resources))
+        .route("/api/v1/namespaces", get(api::kube::list_namespaces))
+        .route("/api/v1/pods", get(api::kube::list_pods))
+        .route(
+            "/api/v1/namespaces/{namespace}/pods",
+            get(api::kube::list_namespaced_pods),
+        )
+        .route(
+            "/api/v1/namespaces/{namespace}/pods/{name}",
+            get(api::kube::get_pod),
+        )
+        .route(
+            "/apis/apps/v1/deployments",
+            get(api::kube::list_deployments),
+        )
+        .route(
+            "/apis/apps/v1/namespaces/{namespace}/deployments",
+            get(api::kube::list_namespaced_deployments),
+        )
+        .route(
+            "/apis/apps/v1/namespaces/{namespace}/deployments/{name}",
+            get(api::kube::get_deployment),
+        );
+
+    // Responses may echo env vars and configs holding secrets
+    let app = app.layer(middleware::from_fn(redact::redact_responses));
+    let read_only = daemon_config.api.read_only;
+    let app = if read_only {
+        app.layer(middleware::from_fn(api::access::read_only))
+    } else {
+        app
+    };
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    match daemon_config.api.listen_address()? {
+        ApiListen::Tcp(addr) => {
+            let listener = systemd::bind_or_activate(addr).await?;
+            slog::info!(log, "Status server running on http://{}", addr; "read_only" => read_only);
+
+            systemd::notify_ready();
+            systemd::start_watchdog_task();
+
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await?;
+        }
+        ApiListen::Unix(path) => {
+            // A socket left behind by an unclean shutdown
+            if fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                fs::remove_file(&path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            // Owner and group only, whatever the umask
+            fs::set_permissions(&path, fs::Permissions::from_mode(API_SOCKET_MODE))?;
+            slog::info!(log, "Status server running on unix:{}", path.display(); "read_only" => read_only);
+
+            systemd::notify_ready();
+            systemd::start_watchdog_task();
+
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await?;
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    systemd::notify_stopping();
+    slog::info!(log, "Shutting down");
+
+    Ok(())
+}
@@ -0,0 +1,254 @@
+// src/clone.rs
+//
+// Service cloning for preview environments: a copy of an existing service is
+// written to the config directory under a new name, optionally with another
+// image tag, and with fresh node and host ports so it can run next to the
+// original. A TTL becomes the clone's `expires_after`.
+use crate::bundle::{write_atomic, BUNDLE_ANNOTATION};
+use crate::config::validate::{
+    check_port_conflicts, check_service_name_uniqueness, check_volume_claim_conflicts,
+    validate_service_name, validate_service_ports,
+};
+use crate::config::{get_config_by_service, ServiceConfig, CONFIG_STORE};
+
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::net::TcpListener;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+
+/// Config directory clones are written to, set at startup
+pub static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Name of the service a clone was made from
+pub const CLONE_SOURCE_ANNOTATION: &str = "orbit.clone/source";
+
+const PORT_RANGE: RangeInclusive<u16> = 30000..=32767;
+
+// Ports handed out to each clone whose config the watcher may not have loaded
+// yet. A clone's ports are dropped once its config is loaded, as the config
+// store then holds them, or once its file is gone. Holding the lock also
+// serialises clones, so two never get the same port.
+static PENDING_PORTS: Mutex<BTreeMap<String, Vec<u16>>> = Mutex::const_new(BTreeMap::new());
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CloneRequest {
+    /// Name of the new service
+    pub name: String,
+    /// Replaces the tag of the cloned containers' images
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_tag: Option<String>,
+    /// Containers `image_tag` applies to, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub containers: Vec<String>,
+    /// Remove the clone once this much time has passed
+    #[serde(with = "humantime_serde", default)]
+    pub ttl: Option<Duration>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClonedPort {
+    pub container: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_port: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CloneResponse {
+    pub service_name: String,
+    pub source: String,
+    pub ports: Vec<ClonedPort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+// Replace the tag (and any digest) of an image reference
+fn with_tag(image: &str, tag: &str) -> String {
+    let name = image.split('@').next().unwrap_or(image);
+    let name = match name.rfind(':') {
+        // A colon before the last '/' belongs to a registry port
+        Some(index) if !name[index..].contains('/') => &name[..index],
+        _ => name,
+    };
+    format!("{}:{}", name, tag)
+}
+
+fn port_is_free(port: u16) -> bool {
+    TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
+
+/// Write a copy of `source` under a new name, with newly allocated ports
+pub async fn clone_service(source: &str, request: &CloneRequest) -> Result<CloneResponse> {
+    let config_dir = CONFIG_DIR
+        .get()
+        .ok_or_else(|| anyhow!("Config directory not initialised"))?;
+    let original = get_config_by_service(source)
+        .await
+        .ok_or_else(|| anyhow!("Service {} not found", source))?;
+
+    validate_service_name(&request.name)?;
+    if let Some(tag) = &request.image_tag {
+        if tag.is_empty() || tag.contains(['/', ':', '@']) || tag.contains(char::is_whitespace) {
+            return Err(anyhow!("Invalid image tag {:?}", tag));
+        }
+    }
+    for name in &request.containers {
+        if !original.spec.containers.iter().any(|c| &c.name == name) {
+            return Err(anyhow!("Service {} has no container {}", source, name));
+        }
+    }
+
+    let path = config_dir.join(format!("{}.yaml", request.name));
+    if path.exists() {
+        return Err(anyhow!("{} already exists", path.display()));
+    }
+
+    let mut config: ServiceConfig = original.clone();
+    config.name = request.name.clone();
+    config.annotations.remove(BUNDLE_ANNOTATION);
+    config
+        .annotations
+        .insert(CLONE_SOURCE_ANNOTATION.to_string(), source.to_string());
+    if request.ttl.is_some() {
+        config.expires_after = request.ttl;
+    }
+    // The TTL starts once the daemon loads the clone, so this is approximate
+    let expires_at = config
+        .expires_after
+        .map(|ttl| humantime::format_rfc3339_seconds(SystemTime::now() + ttl).to_string());
+
+    let mut pending = PENDING_PORTS.lock().await;
+
+    let mut used = HashSet::new();
+    {
+        let store = CONFIG_STORE
+            .get()
+            .expect("Config store not initialized")
+            .read()
+            .await;
+        pending.retain(|name, _| {
+            !store.contains_key(name) && config_dir.join(format!("{}.yaml", name)).exists()
+        });
+        used.extend(pending.values().flatten().copied());
+        for (_, existing) in store.values() {
+            for port in existing
+                .spec
+                .containers
+                .iter()
+                .flat_map(|c| c.ports.iter().flatten())
+            {
+                used.extend(port.node_port);
+                used.extend(port.target_port);
+            }
+        }
+    }
+    let mut ports = Vec::new();
+    let mut new_ports = Vec::new();
+    {
+        let mut candidates = PORT_RANGE.filter(|port| !used.contains(port) && port_is_free(*port));
+        let mut next_port = || {
+            candidates
+                .next()
+                .ok_or_else(|| anyhow!("No free ports left in {:?}", PORT_RANGE))
+        };
+
+        for container in &mut config.spec.containers {
+            if let Some(tag) = &request.image_tag {
+                if request.containers.is_empty() || request.containers.contains(&container.name) {
+                    container.image = with_tag(&container.image, tag);
+                }
+            }
+
+            for port in container.ports.iter_mut().flatten() {
+                if port.target_port.is_some() {
+                    port.target_port = Some(next_port()?);
+                }
+                if port.node_port.is_some() {
+                    port.node_port = Some(next_port()?);
+                }
+                new_ports.extend(port.target_port);
+                new_ports.extend(port.node_port);
+                ports.push(ClonedPort {
+                    container: container.name.clone(),
+                    port: port.port,
+                    target_port: port.target_port,
+                    node_port: port.node_port,
+                });
+            }
+        }
+    }
+
+    check_service_name_uniqueness(&config, None).await?;
+    validate_service_ports(&config)?;
+    check_port_conflicts(&config, None).await?;
+    // The original keeps the volume claims it writes
+    check_volume_claim_conflicts(&config, None).await?;
+
+    let temp_path = write_atomic(&path, &serde_yaml::to_string(&config)?).await?;
+    tokio::fs::rename(&temp_path, &path).await?;
+    pending.insert(config.name.clone(), new_ports);
+
+    slog::info!(slog_scope::logger(), "Service cloned";
+        "service" => &config.name,
+        "source" => source,
+        "path" => path.display().to_string(),
+        "expires_at" => expires_at.as_deref().unwrap_or("never")
+    );
+
+    Ok(CloneResponse {
+        service_name: config.name,
+        source: source.to_string(),
+        ports,
+        expires_at,
+    })
+}
+
+/// `orbit clone`: ask a running daemon to clone a service.
+pub async fn run(source: &str, api_url: &str, request: &CloneRequest) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+    let url = format!(
+        "{}/services/{}/clone",
+        api_url.trim_end_matches('/'),
+        source
+    );
+
+    let response = client
+        .post(&url)
+        .json(request)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to reach Orbit at {}: {}", api_url, e))?;
+    if !response.status().is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(anyhow!(
+            "Clone failed: {}",
+            body["error"].as_str().unwrap_or("unknown error")
+        ));
+    }
+
+    let clone: CloneResponse = response.json().await?;
+    println!("{}: cloned from {}", clone.service_name, clone.source);
+    for port in &clone.ports {
+        let mut mapping = format!("  {} port {}", port.container, port.port);
+        if let Some(target_port) = port.target_port {
+            mapping.push_str(&format!("  target_port {}", target_port));
+        }
+        if let Some(node_port) = port.node_port {
+            mapping.push_str(&format!("  node_port {}", node_port));
+        }
+        println!("{}", mapping);
+    }
+    if let Some(expires_at) = &clone.expires_at {
+        println!("  expires at {}", expires_at);
+    }
+    Ok(())
+}
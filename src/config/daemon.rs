@@ -0,0 +1,507 @@
+// src/config/daemon.rs
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::net::SocketAddr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use super::startup::StartupPolicy;
+use crate::container::health::webhooks::WebhookConfig;
+use crate::redact::RedactionConfig;
+
+// Process-wide settings, as opposed to the per-service YAML files in the config directory
+pub static DAEMON_CONFIG: OnceLock<DaemonConfig> = OnceLock::new();
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DaemonConfig {
+    #[serde(default)]
+    pub runtime: RuntimeConfigs,
+    /// Forward proxy for services with an egress allowlist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub egress: Option<EgressProxyConfig>,
+    /// Expiry checks for secrets and certificates
+    #[serde(default)]
+    pub expiry: ExpiryMonitorConfig,
+    /// Scale down lower-priority services when the node runs short of memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preemption: Option<PreemptionConfig>,
+    /// Where the management API listens, and whether it accepts changes
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// Notified when any container fails, crashes or recovers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub health_webhooks: Vec<WebhookConfig>,
+    /// Env vars whose values are masked in logs, events and API responses
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    /// Start services in dependency and priority order, a few at a time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup: Option<StartupPolicy>,
+    /// How changes to the config directory are noticed
+    #[serde(default)]
+    pub config_watch: ConfigWatchConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigWatchConfig {
+    #[serde(default)]
+    pub mode: WatchMode,
+    /// How often the directory is scanned when polling
+    #[serde(with = "humantime_serde", default = "default_poll_interval")]
+    pub poll_interval: Duration,
+    /// Hash file contents when polling, for filesystems with coarse or unreliable mtimes
+    #[serde(default)]
+    pub compare_contents: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchMode {
+    /// Poll on network and FUSE filesystems, use file events elsewhere
+    #[default]
+    Auto,
+    /// File events from the kernel, e.g. inotify
+    Events,
+    Poll,
+}
+
+impl Default for ConfigWatchConfig {
+    fn default() -> Self {
+        Self {
+            mode: WatchMode::default(),
+            poll_interval: default_poll_interval(),
+            compare_contents: false,
+        }
+    }
+}
+
+fn default_poll_interval() -> Duration {
+    Duration::from_secs(2)
+}
+
+impl ConfigWatchConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.poll_interval < Duration::from_millis(100) {
+            return Err(anyhow!("config_watch.poll_interval must be at least 100ms"));
+        }
+        Ok(())
+    }
+
+    /// Whether `dir` is watched by polling rather than with file events
+    pub fn polls(&self, dir: &Path) -> bool {
+        match self.mode {
+            WatchMode::Auto => on_network_filesystem(dir),
+            WatchMode::Events => false,
+            WatchMode::Poll => true,
+        }
+    }
+}
+
+// Filesystems whose changes don't reach inotify when made elsewhere: NFS,
+// SMB/CIFS, FUSE (incl. virtiofs and Docker Desktop mounts) and 9p (WSL)
+const NETWORK_FILESYSTEMS: [i64; 6] = [
+    0x6969, 0x517B, 0xFF534D42, 0xFE534D42, 0x65735546, 0x01021997,
+];
+
+fn on_network_filesystem(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    NETWORK_FILESYSTEMS.contains(&(stat.f_type as i64))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiConfig {
+    /// "host:port", or "unix:<path>" for a Unix socket
+    #[serde(default = "default_api_listen")]
+    pub listen: String,
+    /// Reject requests that would change state, leaving config files as the only source of changes
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// A parsed `api.listen`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiListen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            listen: default_api_listen(),
+            read_only: false,
+        }
+    }
+}
+
+impl ApiConfig {
+    pub fn listen_address(&self) -> Result<ApiListen> {
+        if let Some(path) = self.listen.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(anyhow!("api.listen needs a socket path after 'unix:'"));
+            }
+            return Ok(ApiListen::Unix(PathBuf::from(path)));
+        }
+        self.listen
+            .parse()
+            .map(ApiListen::Tcp)
+            .map_err(|e| anyhow!("Invalid api.listen {:?}: {}", self.listen, e))
+    }
+}
+
+// Parts of the API change services and have no authentication
+fn default_api_listen() -> String {
+    "127.0.0.1:4112".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PreemptionConfig {
+    /// Node memory usage, in percent, at which pods are preempted
+    #[serde(default = "default_critical_memory_percentage")]
+    pub critical_memory_percentage: f64,
+    #[serde(
+        with = "humantime_serde",
+        default = "default_preemption_check_interval"
+    )]
+    pub check_interval: Duration,
+    /// Minimum time between preemptions under memory pressure
+    #[serde(with = "humantime_serde", default = "default_preemption_cooldown")]
+    pub cooldown: Duration,
+    /// What memory pressure does to a lower-priority pod
+    #[serde(default)]
+    pub mode: PreemptionMode,
+    /// Node memory usage, in percent, below which paused pods are resumed
+    #[serde(default = "default_resume_memory_percentage")]
+    pub resume_memory_percentage: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreemptionMode {
+    /// Scale the service down by the pod
+    #[default]
+    Evict,
+    /// Freeze the pod's containers and take it out of the load balancer
+    /// until memory pressure passes
+    Pause,
+}
+
+fn default_critical_memory_percentage() -> f64 {
+    95.0
+}
+
+fn default_preemption_check_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_preemption_cooldown() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_resume_memory_percentage() -> f64 {
+    85.0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExpiryMonitorConfig {
+    #[serde(with = "humantime_serde", default = "default_expiry_check_interval")]
+    pub check_interval: Duration,
+    /// How long before expiry to start warning
+    #[serde(with = "humantime_serde", default = "default_expiry_warn_before")]
+    pub warn_before: Duration,
+    /// Roll a service's pods when one of its file secrets changes on disk
+    #[serde(default = "default_roll_on_renewal")]
+    pub roll_on_renewal: bool,
+}
+
+impl Default for ExpiryMonitorConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: default_expiry_check_interval(),
+            warn_before: default_expiry_warn_before(),
+            roll_on_renewal: default_roll_on_renewal(),
+        }
+    }
+}
+
+fn default_expiry_check_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_expiry_warn_before() -> Duration {
+    Duration::from_secs(30 * 24 * 3600)
+}
+
+fn default_roll_on_renewal() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EgressProxyConfig {
+    #[serde(default = "default_egress_listen")]
+    pub listen: SocketAddr,
+    /// Address pods use to reach the proxy; resolved to the host gateway when not an IP
+    #[serde(default = "default_egress_advertise_host")]
+    pub advertise_host: String,
+}
+
+impl Default for EgressProxyConfig {
+    fn default() -> Self {
+        Self {
+            listen: default_egress_listen(),
+            advertise_host: default_egress_advertise_host(),
+        }
+    }
+}
+
+fn default_egress_listen() -> SocketAddr {
+    SocketAddr::from(([0, 0, 0, 0], 3128))
+}
+
+fn default_egress_advertise_host() -> String {
+    "host.docker.internal".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RuntimeConfigs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docker: Option<DockerRuntimeConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub firecracker: Option<FirecrackerRuntimeConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nspawn: Option<NspawnRuntimeConfig>,
+    /// Timeouts, retries and circuit breaking applied to every runtime call
+    #[serde(default)]
+    pub policy: RuntimePolicyConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RuntimePolicyConfig {
+    /// Deadline for quick calls such as inspect, list and stop
+    #[serde(with = "humantime_serde", default = "default_call_timeout")]
+    pub timeout: Duration,
+    /// Deadline for container starts and image pulls
+    #[serde(with = "humantime_serde", default = "default_start_timeout")]
+    pub start_timeout: Duration,
+    /// Extra attempts for read-only calls that fail or time out
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    #[serde(with = "humantime_serde", default = "default_retry_backoff")]
+    pub retry_backoff: Duration,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+}
+
+impl Default for RuntimePolicyConfig {
+    fn default() -> Self {
+        Self {
+            timeout: default_call_timeout(),
+            start_timeout: default_start_timeout(),
+            retries: default_retries(),
+            retry_backoff: default_retry_backoff(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive daemon failures before calls start failing fast
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long to fail fast before letting calls through again
+    #[serde(with = "humantime_serde", default = "default_reset_after")]
+    pub reset_after: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            reset_after: default_reset_after(),
+        }
+    }
+}
+
+fn default_call_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_start_timeout() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_reset_after() -> Duration {
+    Duration::from_secs(30)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DockerRuntimeConfig {
+    /// Daemon endpoint in DOCKER_HOST form, e.g. "tcp://10.0.0.5:2376" or "unix:///run/docker.sock"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Client certificates for TLS-protected endpoints
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<DockerTlsConfig>,
+    /// Request timeout for calls to the daemon
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<Duration>,
+    /// Force rootless handling on or off instead of detecting it from the daemon
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rootless: Option<bool>,
+    /// Label containers and networks with this namespace and only list matching ones,
+    /// so several Orbit daemons can share one Docker host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DockerTlsConfig {
+    pub ca: PathBuf,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl DockerTlsConfig {
+    // Follow the docker CLI layout: ca.pem, cert.pem and key.pem in DOCKER_CERT_PATH
+    pub fn from_cert_path(dir: &Path) -> Self {
+        Self {
+            ca: dir.join("ca.pem"),
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FirecrackerRuntimeConfig {
+    /// Path to the firecracker binary
+    #[serde(default = "default_firecracker_binary")]
+    pub binary: PathBuf,
+    /// Uncompressed guest kernel (vmlinux) shared by all microVMs
+    pub kernel_image: PathBuf,
+    /// Extra kernel command line arguments appended to the defaults
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_args: Option<String>,
+    /// Directory holding one ext4 root filesystem per image, e.g. `nginx_latest.ext4`
+    pub rootfs_dir: PathBuf,
+    /// Working directory for API sockets, logs and per-VM rootfs copies
+    #[serde(default = "default_firecracker_state_dir")]
+    pub state_dir: PathBuf,
+    /// Host bridge the microVM tap devices are attached to
+    #[serde(default = "default_firecracker_bridge")]
+    pub bridge: String,
+    /// Subnet for guest addresses; the first host address is assigned to the bridge
+    #[serde(default = "default_firecracker_subnet")]
+    pub subnet: String,
+}
+
+fn default_firecracker_binary() -> PathBuf {
+    PathBuf::from("firecracker")
+}
+
+fn default_firecracker_state_dir() -> PathBuf {
+    PathBuf::from("/var/lib/orbit/firecracker")
+}
+
+fn default_firecracker_bridge() -> String {
+    "orbit-fc0".to_string()
+}
+
+fn default_firecracker_subnet() -> String {
+    "172.30.0.0/24".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NspawnRuntimeConfig {
+    /// Path to the systemd-nspawn binary
+    #[serde(default = "default_nspawn_binary")]
+    pub binary: PathBuf,
+    /// Directory holding one OS tree (or .raw disk image) per image, e.g. `debian_12/`
+    #[serde(default = "default_nspawn_images_dir")]
+    pub images_dir: PathBuf,
+    /// Working directory for machine logs
+    #[serde(default = "default_nspawn_state_dir")]
+    pub state_dir: PathBuf,
+    /// How long a machine may take to shut down before it is killed
+    #[serde(with = "humantime_serde", default = "default_nspawn_stop_timeout")]
+    pub stop_timeout: Duration,
+}
+
+impl Default for NspawnRuntimeConfig {
+    fn default() -> Self {
+        Self {
+            binary: default_nspawn_binary(),
+            images_dir: default_nspawn_images_dir(),
+            state_dir: default_nspawn_state_dir(),
+            stop_timeout: default_nspawn_stop_timeout(),
+        }
+    }
+}
+
+fn default_nspawn_binary() -> PathBuf {
+    PathBuf::from("systemd-nspawn")
+}
+
+fn default_nspawn_images_dir() -> PathBuf {
+    PathBuf::from("/var/lib/machines")
+}
+
+fn default_nspawn_state_dir() -> PathBuf {
+    PathBuf::from("/var/lib/orbit/nspawn")
+}
+
+fn default_nspawn_stop_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+pub fn load_daemon_config(path: Option<&Path>) -> Result<DaemonConfig> {
+    let config: DaemonConfig = match path {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("Failed to read daemon config {:?}: {}", path, e))?;
+            serde_yaml::from_str(&contents)
+                .map_err(|e| anyhow!("Invalid daemon config {:?}: {}", path, e))?
+        }
+        None => DaemonConfig::default(),
+    };
+    config.api.listen_address()?;
+    for webhook in &config.health_webhooks {
+        webhook.validate()?;
+    }
+    if let Some(startup) = &config.startup {
+        startup.validate()?;
+    }
+    config.config_watch.validate()?;
+
+    Ok(config)
+}
+
+pub fn get_daemon_config() -> DaemonConfig {
+    DAEMON_CONFIG.get().cloned().unwrap_or_default()
+}
@@ -0,0 +1,217 @@
+// src/config/layers.rs
+//
+// Override directories layered over the config directory, so packages can
+// ship base service configs that are adjusted locally. A YAML file in an
+// override directory holds part of a service config and applies to the
+// service with the same `name`. Maps are merged key by key, lists of named
+// items (such as `spec.containers`) item by item, and other values replaced.
+// Later override directories take precedence. A broken override file only
+// fails the service it targets; it is skipped for the others.
+use anyhow::{anyhow, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+pub static OVERRIDE_DIRS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+// The service each override file was last applied to, to reload it when the file is removed
+static APPLIED: OnceLock<Mutex<HashMap<PathBuf, String>>> = OnceLock::new();
+
+fn applied() -> &'static Mutex<HashMap<PathBuf, String>> {
+    APPLIED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn override_dirs() -> &'static [PathBuf] {
+    OVERRIDE_DIRS.get().map_or(&[], |dirs| dirs.as_slice())
+}
+
+pub fn is_override_path(path: &Path) -> bool {
+    override_dirs().iter().any(|dir| {
+        path.starts_with(dir) || dir.canonicalize().is_ok_and(|dir| path.starts_with(dir))
+    })
+}
+
+// The YAML files of an override directory, in name order. The paths are
+// absolute, like those of watcher events.
+async fn override_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(tokio::fs::canonicalize(dir).await?).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if entry.file_type().await?.is_file()
+            && matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("yaml") | Some("yml")
+            )
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// The override files of every existing override directory, in precedence order
+async fn all_override_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for dir in override_dirs() {
+        if !tokio::fs::metadata(dir).await.is_ok_and(|m| m.is_dir()) {
+            continue;
+        }
+        match override_files(dir).await {
+            Ok(dir_files) => files.extend(dir_files),
+            Err(e) => slog::warn!(slog_scope::logger(), "Failed to list override directory";
+                "directory" => dir.to_str(),
+                "error" => e.to_string()
+            ),
+        }
+    }
+    files
+}
+
+fn parse_override(path: &Path, contents: &str) -> Result<Value> {
+    serde_yaml::from_str(contents).map_err(|e| anyhow!("Invalid override {:?}: {}", path, e))
+}
+
+// The top-level `name` of an override that doesn't parse, so that the error
+// goes to the service it was meant for
+fn raw_target(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| {
+        let value = line.strip_prefix("name:")?.trim();
+        let value = value.split(" #").next().unwrap_or(value).trim();
+        Some(value.trim_matches(|c| c == '"' || c == '\''))
+    })
+}
+
+/// The service an override file applies to, by its `name`
+pub async fn override_target(path: &Path) -> Option<String> {
+    let contents = tokio::fs::read_to_string(path).await.ok()?;
+    parse_override(path, &contents)
+        .ok()?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Services affected by a change to an override file: the one it applies to
+/// now, and the one it applied to before
+pub async fn affected_services(path: &Path) -> Vec<String> {
+    let mut services: Vec<String> = override_target(path).await.into_iter().collect();
+    if let Some(previous) = applied().lock().unwrap().get(path) {
+        if !services.contains(previous) {
+            services.push(previous.clone());
+        }
+    }
+    services
+}
+
+/// Merge the override files for the service over its config. An override
+/// file that can't be read or parsed fails the service only if it targets it.
+pub async fn apply_overrides(service_name: &str, config: &mut Value) -> Result<()> {
+    let log = slog_scope::logger();
+    let mut overlays = Vec::new();
+
+    for path in all_override_files().await {
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) => {
+                slog::warn!(log, "Failed to read override, skipping it";
+                    "service" => service_name,
+                    "path" => path.to_str(),
+                    "error" => e.to_string()
+                );
+                continue;
+            }
+        };
+        let overlay = match parse_override(&path, &contents) {
+            Ok(overlay) => overlay,
+            Err(e) if raw_target(&contents) == Some(service_name) => return Err(e),
+            Err(e) => {
+                slog::warn!(log, "Invalid override skipped";
+                    "service" => service_name,
+                    "path" => path.to_str(),
+                    "error" => e.to_string()
+                );
+                continue;
+            }
+        };
+        if overlay.get("name").and_then(Value::as_str) == Some(service_name) {
+            overlays.push((path, overlay));
+        }
+    }
+
+    let mut applied = applied().lock().unwrap();
+    applied.retain(|_, service| service != service_name);
+    for (path, overlay) in overlays {
+        merge(config, overlay);
+        applied.insert(path, service_name.to_string());
+    }
+    Ok(())
+}
+
+/// Warn about override files whose service isn't in the config directory,
+/// as they're ignored until it is
+pub async fn warn_unknown_targets(services: &HashSet<String>) {
+    for path in all_override_files().await {
+        if let Some(target) = override_target(&path).await {
+            if !services.contains(&target) {
+                slog::warn!(slog_scope::logger(), "Override for unknown service ignored";
+                    "service" => &target,
+                    "path" => path.to_str()
+                );
+            }
+        }
+    }
+}
+
+fn item_name(value: &Value) -> Option<&str> {
+    value.get("name")?.as_str()
+}
+
+/// Merge `overlay` into `base`
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => merge_mappings(base, overlay),
+        (Value::Sequence(base), Value::Sequence(overlay))
+            if base
+                .iter()
+                .chain(overlay.iter())
+                .all(|v| item_name(v).is_some()) =>
+        {
+            for item in overlay {
+                match base.iter_mut().find(|b| item_name(b) == item_name(&item)) {
+                    Some(existing) => merge(existing, item),
+                    None => base.push(item),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn merge_mappings(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match base.get_mut(&key) {
+            Some(existing) => merge(existing, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_target() {
+        assert_eq!(raw_target("name: web\nspec: [\n"), Some("web"));
+        assert_eq!(
+            raw_target("spec:\n  name: app\nname: \"api\" # base\n"),
+            Some("api")
+        );
+        assert_eq!(raw_target("spec:\n  - name: app\n"), None);
+    }
+}
@@ -1,22 +1,39 @@
 // src/config/mod.rs
+pub mod daemon;
+pub mod layers;
+pub mod reload;
+pub mod report;
+pub mod startup;
 pub mod utils;
 pub mod validate;
 use rustc_hash::FxHashMap;
 pub use utils::*;
 
+use crate::container::anomaly::AnomalyDetectionConfig;
+use crate::container::health::webhooks::WebhookConfig;
 use crate::container::health::{HealthState, CONTAINER_HEALTH};
+use crate::container::readiness::ReadinessConfig;
+use crate::container::registry::ImagePullSecret;
 use crate::container::scaling::manager::ScalingPolicy;
+use crate::container::scaling::wake::ScaleToZeroConfig;
+use crate::container::secrets::SecretConfig;
 use crate::container::volumes::VolumeData;
-use crate::container::{rolling_update, Container, IMAGE_CHECK_TASKS};
+use crate::container::{
+    lifecycle, rolling_update, Container, LoggingConfig, UlimitValue, IMAGE_CHECK_TASKS,
+};
 use anyhow::{anyhow, Result};
-use notify::{EventKind, RecursiveMode};
-use notify_debouncer_full::{new_debouncer, DebounceEventResult, DebouncedEvent};
+use daemon::get_daemon_config;
+use futures::StreamExt;
+use notify::{EventKind, PollWatcher, RecursiveMode, Watcher};
+use notify_debouncer_full::{
+    new_debouncer, new_debouncer_opt, DebounceEventResult, DebouncedEvent, Debouncer, FileIdCache,
+    NoCache,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::fs;
 use std::sync::Arc;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::PathBuf,
     path::Path,
     sync::OnceLock,
@@ -26,17 +43,34 @@ use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
 use validate::{
     check_container_name_uniqueness, check_port_conflicts, check_service_name_uniqueness,
-    validate_service_name, validate_service_ports,
+    check_volume_claim_conflicts, validate_anomaly_detection, validate_container_env,
+    validate_container_filesystem, validate_container_limits, validate_container_metadata,
+    validate_external, validate_groups, validate_health_webhooks, validate_identity,
+    validate_load_balancing, validate_max_pod_lifetime, validate_request_limits,
+    validate_rolling_update, validate_runtime_support, validate_scale_to_zero,
+    validate_scaling_policy, validate_secrets, validate_service_name, validate_service_ports,
+    validate_upstream, validate_volume_claims,
 };
 use validator::Validate;
 
 use crate::{
     container::{
-        self, clean_up, manage, remove_container_stats, scaling::auto_scale, ContainerInfo,
-        ContainerMetadata, ContainerPortMetadata, ContainerStats, InstanceMetadata, INSTANCE_STORE,
-        RUNTIME, SCALING_TASKS,
+        self,
+        canary::{CanaryConfig, UpdateStrategy},
+        changes::{record_change, InstanceEvent},
+        clean_up, find_host_port,
+        identity::IdentityConfig,
+        manage, remove_container_stats,
+        scaling::auto_scale,
+        ContainerInfo, ContainerMetadata, ContainerPortMetadata, ContainerRuntime, ContainerStats,
+        InstanceMetadata, INSTANCE_STORE, LOOPBACK_ADDRESS, RUNTIME, SCALING_TASKS,
     },
+    egress::ProxyAllowlist,
+    external::{self, ExternalConfig},
     proxy::{self, SERVER_BACKENDS},
+    replay::TrafficRecordingConfig,
+    request_limits::RequestLimits,
+    upstream::UpstreamConfig,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -47,6 +81,12 @@ pub struct RollingUpdateConfig {
     pub max_surge: u8,
     #[serde(with = "humantime_serde")]
     pub timeout: Duration,
+    /// "rolling" or "canary", for image updates
+    #[serde(default)]
+    pub strategy: UpdateStrategy,
+    /// Canary settings, with the canary strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary: Option<CanaryConfig>,
 }
 
 fn default_max_unavailable() -> u8 {
@@ -62,6 +102,8 @@ impl Default for RollingUpdateConfig {
             max_unavailable: default_max_unavailable(),
             max_surge: default_max_surge(),
             timeout: Duration::from_secs(300), // 5 minute default timeout
+            strategy: UpdateStrategy::default(),
+            canary: None,
         }
     }
 }
@@ -80,10 +122,34 @@ pub enum ScaleMessage {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum PullPolicyValue {
     Always,
+    IfNotPresent,
     Never,
 }
 
-pub static CONFIG_UPDATES: OnceLock<mpsc::Sender<(String, ScaleMessage)>> = OnceLock::new();
+// Each service's scaler, paused and resumed around config and rolling updates
+pub static CONFIG_UPDATES: OnceLock<
+    std::sync::Mutex<FxHashMap<String, mpsc::Sender<ScaleMessage>>>,
+> = OnceLock::new();
+
+/// Register the channel of the service's scaler, replacing the previous one
+pub fn register_scaler(service_name: &str, sender: mpsc::Sender<ScaleMessage>) {
+    CONFIG_UPDATES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .insert(service_name.to_string(), sender);
+}
+
+/// Tell the service's scaler to pause or resume. A service without a running
+/// scaler has nothing to pause.
+pub async fn send_scale_message(service_name: &str, message: ScaleMessage) {
+    let sender = CONFIG_UPDATES
+        .get()
+        .and_then(|scalers| scalers.lock().unwrap().get(service_name).cloned());
+    if let Some(sender) = sender {
+        let _ = sender.send(message).await;
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PortRange {
@@ -91,7 +157,7 @@ pub struct PortRange {
     pub end: u16,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct InstanceCount {
     pub min: u8, // Minimum instances to keep running
     pub max: u8,
@@ -122,6 +188,23 @@ pub struct CoDelConfig {
     /// HTTP status code to return when overloaded
     #[serde(default)]
     pub overload_status_code: Option<u16>,
+
+    /// Instances to scale up at once in burst mode, which bypasses cooldowns
+    /// while queue delay or error rate is above its emergency threshold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_scale_step_burst: Option<u32>,
+
+    /// Average queue delay that starts a burst, defaults to four times the target
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub burst_queue_delay: Option<Duration>,
+
+    /// Percentage of 5xx responses that starts a burst
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst_error_percentage: Option<f64>,
 }
 
 fn default_consecutive_intervals() -> u32 {
@@ -137,11 +220,14 @@ pub struct ServiceConfig {
     #[validate(length(max = 210))]
     pub name: String,
     pub network: Option<String>,
+    /// Not needed by external services
+    #[serde(default)]
     pub spec: ServiceSpec,
     pub memory_limit: Option<Value>,
     pub pull_policy: Option<PullPolicyValue>,
     pub cpu_limit: Option<Value>,
     pub resource_thresholds: Option<ResourceThresholds>,
+    #[serde(default)]
     pub instance_count: InstanceCount,
     #[serde(default = "default_instance_count")]
     pub adopt_orphans: bool,
@@ -149,19 +235,123 @@ pub struct ServiceConfig {
     #[serde(with = "humantime_serde", default)]
     pub image_check_interval: Option<Duration>,
     pub rolling_update_config: Option<RollingUpdateConfig>,
+    /// Pods older than this are replaced with fresh ones, a few at a time
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_pod_lifetime: Option<Duration>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub volumes: Option<HashMap<String, VolumeData>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub codel: Option<CoDelConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scaling_policy: Option<ScalingPolicy>,
+    /// Run no pods while idle, with `instance_count.min: 0`, and start one on the first request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_to_zero: Option<ScaleToZeroConfig>,
+    /// Free-form key/value metadata, carried through to the instance store, API and proxy
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+    /// Maximum concurrent runtime operations (starts, stops, inspects) for this service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime_concurrency: Option<usize>,
+    /// Runtime namespace for this service's containers, overriding the daemon default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// What "deployment complete" means for this service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessConfig>,
+    /// Sample proxied requests so they can be replayed for load testing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traffic_recording: Option<TrafficRecordingConfig>,
+    /// Log every proxied request with its request ID and pod, not only failures
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub access_log: bool,
+    /// Ramp a newly added backend's share of traffic up to a full share over this window
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub slow_start: Option<Duration>,
+    /// Share of a full load balancer weight kept by backends whose health checks fail
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub degraded_weight: Option<f64>,
+    /// How the proxy connects to the backends, e.g. over TLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<UpstreamConfig>,
+    /// Largest URI, headers and body the proxy passes to the pods
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_limits: Option<RequestLimits>,
+    /// Notified when one of the service's containers fails, crashes or recovers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub health_webhooks: Vec<WebhookConfig>,
+    /// Report pods whose CPU, memory or error rate deviates from their siblings'
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anomaly_detection: Option<AnomalyDetectionConfig>,
+    /// Proxy to backends found by resolving a hostname instead of running containers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external: Option<ExternalConfig>,
+    /// Point outbound HTTP(S) at the egress proxy, which only reaches these
+    /// domains. Advisory: clients may ignore the proxy variables.
+    #[serde(alias = "egress", skip_serializing_if = "Option::is_none")]
+    pub proxy_allowlist: Option<ProxyAllowlist>,
+    /// Nameservers for every container, unless a container sets its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_search: Option<Vec<String>>,
+    /// "hostname:address" entries added to every container's /etc/hosts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_hosts: Option<Vec<String>>,
+    /// Injected into every container as env vars or files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<SecretConfig>,
+    /// Signed pod identity token injected into every container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<IdentityConfig>,
+    /// Logging driver for every container, unless a container sets its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingConfig>,
+    /// Credentials for pulling the service's images from private registries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image_pull_secrets: Vec<ImagePullSecret>,
+    /// Remove the service once this long has passed since it was first loaded
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_after: Option<Duration>,
+    /// Higher-priority services may preempt pods of lower-priority ones (default: 0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Services started before this one when the daemon starts with a startup policy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub start_after: Vec<String>,
+    /// Groups the service belongs to, for operations on all of a group's services
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// When false the service is stopped: it keeps its config and node ports but runs no pods
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
 }
 
 fn default_instance_count() -> bool {
     false
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ServiceSpec {
     pub containers: Vec<Container>,
 }
@@ -185,16 +375,20 @@ pub struct ResourceThresholds {
     pub cpu_percentage: Option<u8>,
     pub cpu_percentage_relative: Option<u8>,
     pub memory_percentage: Option<u8>,
+    /// Average utilisation of the pod's GPUs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_percentage: Option<u8>,
     #[serde(default)]
     pub metrics_strategy: PodMetricsStrategy,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct PodStats {
     pub cpu_percentage: f64,
     pub cpu_percentage_relative: f64,
     pub memory_usage: u64,
     pub memory_limit: u64,
+    pub gpu_percentage: f64,
 }
 
 pub fn aggregate_pod_stats(
@@ -208,6 +402,7 @@ pub fn aggregate_pod_stats(
                 cpu_percentage_relative: 0.0,
                 memory_usage: 0,
                 memory_limit: 0,
+                gpu_percentage: 0.0,
             };
 
             for stats in container_stats {
@@ -217,6 +412,9 @@ pub fn aggregate_pod_stats(
                     .max(stats.2.cpu_percentage_relative);
                 max_stats.memory_usage = max_stats.memory_usage.max(stats.2.memory_usage);
                 max_stats.memory_limit = max_stats.memory_limit.max(stats.2.memory_limit);
+                max_stats.gpu_percentage = max_stats
+                    .gpu_percentage
+                    .max(stats.2.gpu_percentage.unwrap_or(0.0));
             }
 
             max_stats
@@ -229,12 +427,14 @@ pub fn aggregate_pod_stats(
                     cpu_percentage_relative: 0.0,
                     memory_usage: 0,
                     memory_limit: 0,
+                    gpu_percentage: 0.0,
                 },
                 |mut acc, stats| {
                     acc.cpu_percentage += stats.2.cpu_percentage;
                     acc.cpu_percentage_relative += stats.2.cpu_percentage_relative;
                     acc.memory_usage += stats.2.memory_usage;
                     acc.memory_limit += stats.2.memory_limit;
+                    acc.gpu_percentage += stats.2.gpu_percentage.unwrap_or(0.0);
                     acc
                 },
             );
@@ -244,6 +444,14 @@ pub fn aggregate_pod_stats(
                 cpu_percentage_relative: sum_stats.cpu_percentage_relative / count,
                 memory_usage: sum_stats.memory_usage / count as u64,
                 memory_limit: sum_stats.memory_limit / count as u64,
+                gpu_percentage: {
+                    // Only containers with GPUs count towards the average
+                    let gpu_containers = container_stats
+                        .iter()
+                        .filter(|stats| stats.2.gpu_percentage.is_some())
+                        .count();
+                    sum_stats.gpu_percentage / gpu_containers.max(1) as f64
+                },
             }
         }
     }
@@ -258,38 +466,123 @@ pub async fn watch_directory(config_dir: PathBuf) -> notify::Result<()> {
     let (tx, mut rx) = mpsc::channel(100);
 
     let tx_clone: mpsc::Sender<DebouncedEvent> = tx.clone();
-    let mut debouncer = new_debouncer(
-        Duration::from_millis(100), // Adjust as needed
-        None,
-        move |res: DebounceEventResult| {
-            let tx = tx_clone.clone();
-            if let Ok(events) = res {
-                for event in events {
-                    if event.paths.iter().any(|path| {
-                        path.extension()
-                            .and_then(|ext| ext.to_str())
-                            .map_or(false, |ext| ext == "yml" || ext == "yaml")
-                    }) && matches!(
-                        event.kind,
-                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
-                    ) {
-                        let _ = tx.blocking_send(event);
-                    }
+    let handle_events = move |res: DebounceEventResult| {
+        let tx = tx_clone.clone();
+        if let Ok(events) = res {
+            for event in events {
+                if event.paths.iter().any(|path| {
+                    path.extension()
+                        .and_then(|ext| ext.to_str())
+                        .map_or(false, |ext| ext == "yml" || ext == "yaml")
+                }) && matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                ) {
+                    let _ = tx.blocking_send(event);
                 }
             }
-        },
-    )?;
+        }
+    };
 
-    debouncer.watch(&config_dir, RecursiveMode::Recursive)?;
-    slog::debug!(log, "watching directory"; "directory" => config_dir.to_str());
+    // File events don't fire for changes made on another host of a network
+    // filesystem, so those directories are scanned instead
+    let watch_config = get_daemon_config().config_watch;
+    // Kept alive for as long as events are processed
+    let _debouncer: Box<dyn std::any::Any + Send> = if watch_config.polls(&config_dir) {
+        slog::info!(log, "Polling config directory for changes";
+            "directory" => config_dir.to_str(),
+            "interval" => format!("{:?}", watch_config.poll_interval),
+            "compare_contents" => watch_config.compare_contents
+        );
+        let mut debouncer = new_debouncer_opt::<_, PollWatcher, _>(
+            Duration::from_millis(100),
+            None,
+            handle_events,
+            NoCache,
+            notify::Config::default()
+                .with_poll_interval(watch_config.poll_interval)
+                .with_compare_contents(watch_config.compare_contents),
+        )?;
+        watch_config_dirs(&mut debouncer, &config_dir)?;
+        Box::new(debouncer)
+    } else {
+        let mut debouncer = new_debouncer(
+            Duration::from_millis(100), // Adjust as needed
+            None,
+            handle_events,
+        )?;
+        watch_config_dirs(&mut debouncer, &config_dir)?;
+        Box::new(debouncer)
+    };
 
     while let Some(event) = rx.recv().await {
-        process_event(event, &config_dir).await;
+        if event
+            .paths
+            .iter()
+            .any(|path| layers::is_override_path(path))
+        {
+            process_override_event(event, &config_dir).await;
+        } else {
+            process_event(event, &config_dir).await;
+        }
     }
 
     Ok(())
 }
 
+// Watch the config directory and the override directories that exist
+fn watch_config_dirs<T: Watcher, C: FileIdCache>(
+    debouncer: &mut Debouncer<T, C>,
+    config_dir: &Path,
+) -> notify::Result<()> {
+    let log = slog_scope::logger();
+    debouncer.watch(config_dir, RecursiveMode::Recursive)?;
+    slog::debug!(log, "watching directory"; "directory" => config_dir.to_str());
+    for dir in layers::override_dirs() {
+        if dir.is_dir() {
+            debouncer.watch(dir, RecursiveMode::NonRecursive)?;
+            slog::debug!(log, "watching override directory"; "directory" => dir.to_str());
+        }
+    }
+    Ok(())
+}
+
+// Reload the services whose overrides changed, as if their config file had been modified
+async fn process_override_event(event: DebouncedEvent, config_dir: &Path) {
+    let config_store = CONFIG_STORE.get().unwrap();
+
+    for path in event.paths.iter() {
+        for service_name in layers::affected_services(path).await {
+            let config_path = {
+                let store = config_store.read().await;
+                store
+                    .values()
+                    .find(|(_, config)| config.name == service_name)
+                    .map(|(path, _)| path.clone())
+            };
+            let Some(config_path) = config_path else {
+                slog::warn!(slog_scope::logger(), "Override for unknown service ignored";
+                    "service" => &service_name,
+                    "path" => path.to_str()
+                );
+                continue;
+            };
+
+            slog::info!(slog_scope::logger(), "Override changed, reloading service";
+                "service" => &service_name,
+                "path" => path.to_str()
+            );
+            let reload = notify::Event::new(EventKind::Modify(notify::event::ModifyKind::Any))
+                .add_path(config_path);
+            process_event(
+                DebouncedEvent::new(reload, std::time::Instant::now()),
+                config_dir,
+            )
+            .await;
+        }
+    }
+}
+
 async fn process_event(event: DebouncedEvent, config_dir: &Path) {
     let config_store = CONFIG_STORE.get().unwrap();
     let scaling_tasks = SCALING_TASKS.get().unwrap();
@@ -478,119 +771,252 @@ pub async fn read_yaml_config(
     path: &PathBuf,
     exclude_service: Option<&str>,
 ) -> Result<ServiceConfig> {
-    let log = slog_scope::logger();
-
     let path_str = path.to_str().unwrap();
     if path_str.ends_with(".yml") || path_str.ends_with(".yaml") {
         let contents = tokio::fs::read_to_string(path).await?;
-        let config: ServiceConfig = serde_yaml::from_str(&contents)?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        return parse_yaml_config(&contents, exclude_service, base_dir).await;
+    }
 
-        // Validate service name format
-        validate_service_name(&config.name)?;
+    Err(anyhow!("Not a yaml file {:?}", path))
+}
 
-        // Check for duplicate service names (no exclusion for new configs)
-        check_service_name_uniqueness(&config, exclude_service).await?;
+/// Parse a service config, apply its overrides and validate it, also against
+/// the other loaded services. Relative paths in it are relative to `base_dir`.
+pub async fn parse_yaml_config(
+    contents: &str,
+    exclude_service: Option<&str>,
+    base_dir: &Path,
+) -> Result<ServiceConfig> {
+    let log = slog_scope::logger();
 
-        // Check for duplicate container names
-        check_container_name_uniqueness(&config)?;
+    let document: serde_yaml::Value =
+        serde_yaml::from_str(contents).map_err(report::syntax_error)?;
+    let mut value = document.clone();
+    let name = value
+        .get("name")
+        .and_then(|name| name.as_str())
+        .map(str::to_string);
+    if let Some(name) = name {
+        layers::apply_overrides(&name, &mut value).await?;
+    }
+    // Parsing the text itself keeps line numbers, unless overrides changed it
+    let mut config = if value == document {
+        report::deserialize_config(serde_yaml::Deserializer::from_str(contents))?
+    } else {
+        report::deserialize_config(value)?
+    };
+    resolve_env_files(&mut config, base_dir);
 
-        // Validate ports within the service
-        validate_service_ports(&config)?;
+    // Validate service name format
+    validate_service_name(&config.name)?;
 
-        // Check for conflicts with other services
-        check_port_conflicts(&config, None).await?;
+    // Check for duplicate service names (no exclusion for new configs)
+    check_service_name_uniqueness(&config, exclude_service).await?;
 
-        // Debug log the parsed thresholds
-        if let Some(thresholds) = &config.resource_thresholds {
-            slog::debug!(log, "Parsed config thresholds";
-                    "service" => &config.name,
-                    "cpu_percentage" => thresholds.cpu_percentage,
-                    "cpu_relative" => thresholds.cpu_percentage_relative,
-                    "memory_percentage" => thresholds.memory_percentage);
-        }
+    // Check for duplicate container names
+    check_container_name_uniqueness(&config)?;
+
+    // Validate ports within the service
+    validate_service_ports(&config)?;
+
+    // Validate environment variable names
+    validate_container_env(&config).await?;
+
+    // Validate ulimits, pids_limit, shm_size, sysctls, network_limit and blkio_limit
+    validate_container_limits(&config)?;
+
+    // Validate hostnames, labels and DNS settings
+    validate_container_metadata(&config)?;
+
+    // Validate read_only_root_fs, tmpfs and userns_mode
+    validate_container_filesystem(&config)?;
+
+    // Validate external services, and that other services have containers
+    validate_external(&config)?;
+
+    // Validate what the runtime in use supports
+    validate_runtime_support(&config)?;
+
+    // Validate degraded_weight
+    validate_load_balancing(&config)?;
+
+    // Validate upstream TLS settings
+    validate_upstream(&config)?;
+
+    // Validate proxy request limits
+    validate_request_limits(&config)?;
+
+    // Validate group names
+    validate_groups(&config)?;
+
+    // Validate health webhook URLs
+    validate_health_webhooks(&config)?;
+
+    // Validate anomaly detection thresholds
+    validate_anomaly_detection(&config)?;
+
+    // Validate scale-to-zero settings against the instance count
+    validate_scale_to_zero(&config)?;
+
+    // Validate the scaling policy
+    validate_scaling_policy(&config)?;
+
+    // Validate the update strategy
+    validate_rolling_update(&config)?;
+
+    // Validate the pod lifetime limit
+    validate_max_pod_lifetime(&config)?;
+
+    // Validate secret sources and targets
+    validate_secrets(&config)?;
+
+    // Validate the pod identity token target
+    validate_identity(&config)?;
+
+    // Validate volume claims and their access modes
+    validate_volume_claims(&config)?;
+
+    // Mask the values of sensitive env vars
+    crate::redact::register_service_env(&config);
+
+    // Check for conflicts with other services
+    check_port_conflicts(&config, None).await?;
 
-        return Ok(config);
+    // Check for a second writer of a shared volume claim
+    check_volume_claim_conflicts(&config, None).await?;
+
+    // Debug log the parsed thresholds
+    if let Some(thresholds) = &config.resource_thresholds {
+        slog::debug!(log, "Parsed config thresholds";
+                "service" => &config.name,
+                "cpu_percentage" => thresholds.cpu_percentage,
+                "cpu_relative" => thresholds.cpu_percentage_relative,
+                "memory_percentage" => thresholds.memory_percentage,
+                "gpu_percentage" => thresholds.gpu_percentage);
     }
 
-    Err(anyhow!("Not a yaml file {:?}", path))
+    Ok(config)
 }
 
-pub async fn initialize_configs(config_dir: &PathBuf) -> Result<()> {
+pub async fn initialize_configs(config_dir: &Path) -> Result<()> {
     let config_store = CONFIG_STORE.get().unwrap();
-    let scaling_tasks = SCALING_TASKS.get().expect("Scaling tasks not initialized");
-    let image_check_tasks = IMAGE_CHECK_TASKS
-        .get()
-        .expect("Image check tasks not initialized");
     let log = slog_scope::logger();
+    let startup = get_daemon_config().startup;
+    let mut services = Vec::new();
 
-    for entry in fs::read_dir(config_dir)? {
-        let entry = entry?;
-        let path = entry.path();
+    for path in config_files(config_dir)? {
+        match read_yaml_config(&path, None).await {
+            Ok(config) => {
+                slog::info!(log, "Initialising config";
+                    "service" => &config.name,
+                    "path" => path.display().to_string()
+                );
 
-        if path.extension().and_then(|ext| ext.to_str()) == Some("yaml")
-            || path.extension().and_then(|ext| ext.to_str()) == Some("yml")
-        {
-            match read_yaml_config(&path, None).await {
-                Ok(config) => {
-                    slog::info!(log, "Initialising config";
-                        "service" => &config.name,
-                        "path" => path.display().to_string()
-                    );
+                // Insert with write lock
+                {
+                    let mut store = config_store.write().await;
+                    store.insert(path.display().to_string(), (path.clone(), config.clone()));
+                }
 
-                    // Insert with write lock
-                    {
-                        let mut store = config_store.write().await;
-                        store.insert(path.display().to_string(), (path.clone(), config.clone()));
-                    }
+                if startup.is_some() {
+                    services.push(config);
+                } else {
+                    start_service(config).await?;
+                }
+            }
+            Err(e) => {
+                slog::error!(log, "Failed to load config";
+                    "file" => path.to_str(),
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
 
-                    // Handle orphaned containers based on the adopt_orphans flag
-                    handle_orphans(&config).await?;
+    let loaded = {
+        let store = config_store.read().await;
+        store
+            .values()
+            .map(|(_, config)| config.name.clone())
+            .collect()
+    };
+    layers::warn_unknown_targets(&loaded).await;
 
-                    container::manage(&config.name, config.clone()).await;
-                    proxy::run_proxy_for_service(config.name.to_string(), config.clone()).await;
+    // With a startup policy, services start in waves once every config is loaded
+    let Some(policy) = startup else {
+        return Ok(());
+    };
+    for wave in startup::startup_waves(services) {
+        slog::info!(log, "Starting services";
+            "services" => wave
+                .iter()
+                .map(|config| config.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        futures::stream::iter(wave.iter().cloned().map(start_service))
+            .buffer_unordered(policy.concurrency)
+            .collect::<Vec<Result<()>>>()
+            .await
+            .into_iter()
+            .collect::<Result<()>>()?;
+
+        if policy.wait_for_ready {
+            futures::future::join_all(
+                wave.iter()
+                    .map(|config| startup::wait_until_started(config, policy.ready_timeout)),
+            )
+            .await;
+        }
+    }
 
-                    let service_name: String = config.name.clone();
+    Ok(())
+}
 
-                    // Start auto-scaling task
-                    // Update scaling task creation:
-                    let service_name_clone = service_name.clone();
-                    let handle = tokio::spawn(async move {
-                        auto_scale(service_name_clone).await;
-                    });
+// Adopt or remove the service's orphans, start its pods and proxy, and its
+// scaling and image check tasks
+async fn start_service(config: ServiceConfig) -> Result<()> {
+    let scaling_tasks = SCALING_TASKS.get().expect("Scaling tasks not initialized");
+    let image_check_tasks = IMAGE_CHECK_TASKS
+        .get()
+        .expect("Image check tasks not initialized");
 
-                    // Store the task handle with write lock
-                    {
-                        let mut tasks = scaling_tasks.write().await;
-                        tasks.insert(service_name.clone(), handle);
-                    }
+    // Handle orphaned containers based on the adopt_orphans flag
+    handle_orphans(&config).await?;
 
-                    let svc_name: String = config.name.clone();
+    container::manage(&config.name, config.clone()).await;
+    proxy::run_proxy_for_service(config.name.to_string(), config.clone()).await;
 
-                    let handle = tokio::spawn(async move {
-                        if let Err(e) =
-                            rolling_update::start_image_check_task(service_name.clone(), config)
-                                .await
-                        {
-                            slog::error!(slog_scope::logger(), "Image check task failed";
-                                "error" => e.to_string()
-                            );
-                        }
-                    });
+    let service_name: String = config.name.clone();
 
-                    // Store the task handle with write lock
-                    {
-                        let mut tasks = image_check_tasks.write().await;
-                        tasks.insert(svc_name.clone(), handle);
-                    }
-                }
-                Err(e) => {
-                    slog::error!(log, "Failed to load config";
-                        "file" => path.to_str(),
-                        "error" => e.to_string()
-                    );
-                }
-            }
+    // Start auto-scaling task
+    // Update scaling task creation:
+    let service_name_clone = service_name.clone();
+    let handle = tokio::spawn(async move {
+        auto_scale(service_name_clone).await;
+    });
+
+    // Store the task handle with write lock
+    {
+        let mut tasks = scaling_tasks.write().await;
+        tasks.insert(service_name.clone(), handle);
+    }
+
+    let svc_name: String = config.name.clone();
+
+    let handle = tokio::spawn(async move {
+        if let Err(e) = rolling_update::start_image_check_task(service_name.clone(), config).await {
+            slog::error!(slog_scope::logger(), "Image check task failed";
+                "error" => e.to_string()
+            );
         }
+    });
+
+    // Store the task handle with write lock
+    {
+        let mut tasks = image_check_tasks.write().await;
+        tasks.insert(svc_name.clone(), handle);
     }
 
     Ok(())
@@ -604,6 +1030,7 @@ pub async fn handle_orphans(config: &ServiceConfig) -> Result<()> {
 
     let orphaned_containers = runtime.list_containers(Some(service_name)).await?;
     if orphaned_containers.is_empty() {
+        remove_orphaned_pod_networks(&runtime, service_name, &HashSet::new()).await;
         return Ok(());
     }
 
@@ -613,15 +1040,13 @@ pub async fn handle_orphans(config: &ServiceConfig) -> Result<()> {
         "count" => orphan_count
     );
 
+    let mut adopted_pods = HashSet::new();
     if config.adopt_orphans {
         let mut pod_containers: HashMap<Uuid, Vec<ContainerInfo>> = HashMap::new();
 
         for container in orphaned_containers {
-            if let Ok(parts) = parse_container_name(&container.name) {
-                pod_containers
-                    .entry(parts.uuid)
-                    .or_default()
-                    .push(container);
+            if let Some(uuid) = container.pod_uuid() {
+                pod_containers.entry(uuid).or_default().push(container);
             }
         }
 
@@ -662,6 +1087,7 @@ pub async fn handle_orphans(config: &ServiceConfig) -> Result<()> {
         for uuid in incomplete_pod_uuids {
             pod_containers.remove(&uuid);
         }
+        adopted_pods.extend(pod_containers.keys().copied());
 
         // Update instance store with write lock
         {
@@ -694,6 +1120,10 @@ pub async fn handle_orphans(config: &ServiceConfig) -> Result<()> {
                                             port: p.port,
                                             target_port: p.target_port,
                                             node_port: p.node_port,
+                                            host_port: (container_data.ip_address
+                                                == LOOPBACK_ADDRESS)
+                                                .then(|| find_host_port(&container_data, p.port))
+                                                .flatten(),
                                         })
                                         .collect();
 
@@ -745,7 +1175,17 @@ pub async fn handle_orphans(config: &ServiceConfig) -> Result<()> {
                             created_at: now,
                             network: network_name,
                             image_hash: image_hashes,
+                            dns_names: container::dns::pod_dns_names(
+                                pod_metadata.iter().map(|container| container.name.as_str()),
+                            ),
                             containers: pod_metadata,
+                            annotations: config.annotations.clone(),
+                        },
+                    );
+                    record_change(
+                        service_name,
+                        InstanceEvent::PodAdded {
+                            instance: instances[uuid].clone(),
                         },
                     );
                 }
@@ -757,13 +1197,46 @@ pub async fn handle_orphans(config: &ServiceConfig) -> Result<()> {
                 "adopted_containers" => adopted_count.to_string()
             );
         }
+
+        // Adopted containers are restarted, have their crashes collected and
+        // run their pre_stop hooks like those Orbit started
+        let adopted_containers: Vec<String> = pod_containers
+            .values()
+            .flatten()
+            .map(|container| container.name.clone())
+            .collect();
+        container::restart::supervise_adopted(service_name, config, &adopted_containers);
+
+        let tracked: HashMap<String, ContainerMetadata> = {
+            let store = instance_store.read().await;
+            store
+                .get(service_name)
+                .into_iter()
+                .flat_map(|instances| instances.values())
+                .flat_map(|instance| instance.containers.iter())
+                .map(|container| (container.name.clone(), container.clone()))
+                .collect()
+        };
+        let mut adopted_hooks = Vec::new();
+        for container_name in &adopted_containers {
+            let (ip_address, ports) = match tracked.get(container_name) {
+                Some(container) => (container.ip_address.clone(), container.ports.clone()),
+                // Containers without ports aren't inspected above
+                None => match runtime.inspect_container(container_name).await {
+                    Ok(stats) => (stats.ip_address, Vec::new()),
+                    Err(_) => (String::new(), Vec::new()),
+                },
+            };
+            adopted_hooks.push((container_name.clone(), ip_address, ports));
+        }
+        container::lifecycle::register_adopted_hooks(service_name, config, &adopted_hooks);
     } else {
         // Group containers by their network
         let mut network_containers: HashMap<String, Vec<String>> = HashMap::new();
 
         for container in &orphaned_containers {
-            if let Ok(parts) = parse_container_name(&container.name) {
-                let network_name = format!("{}__{}", service_name, parts.uuid);
+            if let Some(uuid) = container.pod_uuid() {
+                let network_name = format!("{}__{}", service_name, uuid);
                 network_containers
                     .entry(network_name)
                     .or_default()
@@ -812,9 +1285,44 @@ pub async fn handle_orphans(config: &ServiceConfig) -> Result<()> {
         }
     }
 
+    remove_orphaned_pod_networks(&runtime, service_name, &adopted_pods).await;
     Ok(())
 }
 
+// Remove the service's pod networks, found by their labels, except those of adopted pods
+async fn remove_orphaned_pod_networks(
+    runtime: &Arc<dyn ContainerRuntime>,
+    service_name: &str,
+    adopted_pods: &HashSet<Uuid>,
+) {
+    let networks = match runtime.list_pod_networks(service_name).await {
+        Ok(networks) => networks,
+        Err(e) => {
+            slog::warn!(slog_scope::logger(), "Failed to list pod networks";
+                "service" => service_name,
+                "error" => e.to_string()
+            );
+            return;
+        }
+    };
+
+    for (network_name, pod) in networks {
+        if adopted_pods.contains(&pod) {
+            continue;
+        }
+        if let Err(e) = runtime
+            .remove_pod_network(&network_name, service_name)
+            .await
+        {
+            slog::error!(slog_scope::logger(), "Failed to remove orphaned network";
+                "service" => service_name,
+                "network" => &network_name,
+                "error" => e.to_string()
+            );
+        }
+    }
+}
+
 // Update the stop_service function to ensure complete cleanup
 pub async fn stop_service(service_name: &str) {
     let log = slog_scope::logger();
@@ -833,6 +1341,9 @@ pub async fn stop_service(service_name: &str) {
                 "service" => service_name
             );
         }
+        if let Some(scalers) = CONFIG_UPDATES.get() {
+            scalers.lock().unwrap().remove(service_name);
+        }
         // Stop updater task if it exists
         let updater_key = format!("{}_updater", service_name);
         if let Some(handle) = tasks.remove(&updater_key) {
@@ -862,11 +1373,18 @@ pub async fn stop_service(service_name: &str) {
         let mut backends_map = server_backends.write().await;
         backends_map.remove(service_name);
     }
+    external::stop_discovery(service_name).await;
+    // A service added again under this name starts like a new one
+    container::stopped::forget(service_name);
 
     // Get instance data and remove from store with write lock
     let instances = {
         let mut store = instance_store.write().await;
-        store.remove(service_name)
+        let removed = store.remove(service_name);
+        if removed.is_some() {
+            record_change(service_name, InstanceEvent::ServiceRemoved);
+        }
+        removed
     };
 
     // Clean up instances if they exist
@@ -900,7 +1418,7 @@ pub async fn stop_service(service_name: &str) {
 
             // Stop each container in the metadata
             for container in &metadata.containers {
-                if let Err(e) = runtime.stop_container(&container.name).await {
+                if let Err(e) = lifecycle::stop_container(&runtime, &container.name).await {
                     slog::error!(log, "Failed to stop container during service cleanup";
                         "service" => service_name,
                         "container" => &container.name,
@@ -957,6 +1475,155 @@ pub fn parse_memory_limit(memory_limit: &serde_json::Value) -> Result<u64> {
     }
 }
 
+/// Size of /dev/shm in bytes, in the same format as memory limits
+pub fn parse_shm_size(shm_size: &serde_json::Value) -> Result<u64> {
+    let size = parse_memory_limit(shm_size)?;
+    if size == 0 {
+        return Err(anyhow!("shm_size must be greater than zero"));
+    }
+    Ok(size)
+}
+
+// Names accepted by setrlimit(2), without the RLIMIT_ prefix
+const ULIMIT_NAMES: [&str; 15] = [
+    "core",
+    "cpu",
+    "data",
+    "fsize",
+    "locks",
+    "memlock",
+    "msgqueue",
+    "nice",
+    "nofile",
+    "nproc",
+    "rss",
+    "rtprio",
+    "rttime",
+    "sigpending",
+    "stack",
+];
+
+fn parse_ulimit_value(value: &serde_json::Value) -> Result<i64> {
+    match value {
+        serde_json::Value::Number(num) => num
+            .as_i64()
+            .filter(|v| *v >= 0)
+            .ok_or_else(|| anyhow!("Invalid ulimit value: {}", num)),
+        serde_json::Value::String(s) if s == "unlimited" => Ok(-1),
+        serde_json::Value::String(s) => s
+            .parse::<i64>()
+            .ok()
+            .filter(|v| *v >= 0)
+            .ok_or_else(|| anyhow!("Invalid ulimit value: {}", s)),
+        _ => Err(anyhow!("Unsupported ulimit value type")),
+    }
+}
+
+// Sysctls that only affect the container and cannot starve other containers
+const SAFE_SYSCTLS: [&str; 11] = [
+    "kernel.shm_rmid_forced",
+    "net.core.somaxconn",
+    "net.ipv4.ip_local_port_range",
+    "net.ipv4.ip_local_reserved_ports",
+    "net.ipv4.ip_unprivileged_port_start",
+    "net.ipv4.ping_group_range",
+    "net.ipv4.tcp_fin_timeout",
+    "net.ipv4.tcp_keepalive_intvl",
+    "net.ipv4.tcp_keepalive_probes",
+    "net.ipv4.tcp_keepalive_time",
+    "net.ipv4.tcp_syncookies",
+];
+
+// Sysctls in namespaces a container has its own copy of; others would change the host
+fn is_namespaced_sysctl(name: &str) -> bool {
+    [
+        "kernel.msgmax",
+        "kernel.msgmnb",
+        "kernel.msgmni",
+        "kernel.sem",
+    ]
+    .contains(&name)
+        || name.starts_with("kernel.shm")
+        || name.starts_with("fs.mqueue.")
+        || name.starts_with("net.")
+}
+
+/// Check a sysctl may be set: safe ones always, other namespaced ones only
+/// with `allow_unsafe`, and never those that would change the host
+pub fn check_sysctl(name: &str, allow_unsafe: bool) -> Result<()> {
+    if SAFE_SYSCTLS.contains(&name) {
+        return Ok(());
+    }
+    if !is_namespaced_sysctl(name) {
+        return Err(anyhow!(
+            "Sysctl {} is not namespaced and would change the host",
+            name
+        ));
+    }
+    if !allow_unsafe {
+        return Err(anyhow!(
+            "Sysctl {} is unsafe, set allow_unsafe_sysctls to use it",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Soft and hard limit of a ulimit, -1 standing for unlimited
+pub fn parse_ulimit(name: &str, value: &UlimitValue) -> Result<(i64, i64)> {
+    if !ULIMIT_NAMES.contains(&name) {
+        return Err(anyhow!("Unknown ulimit: {}", name));
+    }
+
+    let (soft, hard) = match value {
+        UlimitValue::Single(value) => {
+            let limit = parse_ulimit_value(value)?;
+            (limit, limit)
+        }
+        UlimitValue::Range { soft, hard } => (parse_ulimit_value(soft)?, parse_ulimit_value(hard)?),
+    };
+
+    // -1 is unlimited, so it is above any other value
+    let above = |a: i64, b: i64| a != b && (a == -1 || (b != -1 && a > b));
+    if above(soft, hard) {
+        return Err(anyhow!(
+            "Soft limit of ulimit {} is above its hard limit",
+            name
+        ));
+    }
+    Ok((soft, hard))
+}
+
+/// Split a tmpfs entry such as "/run:size=64m,mode=1777" into its path and options
+pub fn parse_tmpfs(entry: &str) -> Result<(&str, &str)> {
+    let (path, options) = entry.split_once(':').unwrap_or((entry, ""));
+    if !path.starts_with('/') {
+        return Err(anyhow!("tmpfs path '{}' must be absolute", path));
+    }
+    if path == "/" {
+        return Err(anyhow!("tmpfs cannot be mounted on /"));
+    }
+    Ok((path, options))
+}
+
+/// Check a user namespace mode: "host", "private", or one of Podman's modes
+pub fn validate_userns_mode(mode: &str) -> Result<()> {
+    let (name, options) = mode.split_once(':').unwrap_or((mode, ""));
+    let valid = match name {
+        "host" | "private" | "nomap" => options.is_empty(),
+        "auto" | "keep-id" => true,
+        "ns" | "container" => !options.is_empty(),
+        _ => false,
+    };
+    if !valid {
+        return Err(anyhow!(
+            "'{}' must be host, private, auto, keep-id, nomap, ns:<path> or container:<id>",
+            mode
+        ));
+    }
+    Ok(())
+}
+
 pub fn parse_cpu_limit(cpu_limit: &serde_json::Value) -> Result<u64> {
     match cpu_limit {
         serde_json::Value::Number(num) => {
@@ -987,9 +1654,72 @@ pub async fn handle_config_update(service_name: &str, config: ServiceConfig) ->
     // Validate ports within the service
     validate_service_ports(&config)?;
 
+    // Validate environment variable names
+    validate_container_env(&config).await?;
+
+    // Validate ulimits, pids_limit, shm_size, sysctls, network_limit and blkio_limit
+    validate_container_limits(&config)?;
+
+    // Validate hostnames, labels and DNS settings
+    validate_container_metadata(&config)?;
+
+    // Validate read_only_root_fs, tmpfs and userns_mode
+    validate_container_filesystem(&config)?;
+
+    // Validate external services, and that other services have containers
+    validate_external(&config)?;
+
+    // Validate what the runtime in use supports
+    validate_runtime_support(&config)?;
+
+    // Validate degraded_weight
+    validate_load_balancing(&config)?;
+
+    // Validate upstream TLS settings
+    validate_upstream(&config)?;
+
+    // Validate proxy request limits
+    validate_request_limits(&config)?;
+
+    // Validate group names
+    validate_groups(&config)?;
+
+    // Validate health webhook URLs
+    validate_health_webhooks(&config)?;
+
+    // Validate anomaly detection thresholds
+    validate_anomaly_detection(&config)?;
+
+    // Validate scale-to-zero settings against the instance count
+    validate_scale_to_zero(&config)?;
+
+    // Validate the scaling policy
+    validate_scaling_policy(&config)?;
+
+    // Validate the update strategy
+    validate_rolling_update(&config)?;
+
+    // Validate the pod lifetime limit
+    validate_max_pod_lifetime(&config)?;
+
+    // Validate secret sources and targets
+    validate_secrets(&config)?;
+
+    // Validate the pod identity token target
+    validate_identity(&config)?;
+
+    // Validate volume claims and their access modes
+    validate_volume_claims(&config)?;
+
+    // Mask the values of sensitive env vars
+    crate::redact::register_service_env(&config);
+
     // Check for conflicts with other services
     check_port_conflicts(&config, None).await?;
 
+    // Check for a second writer of a shared volume claim
+    check_volume_claim_conflicts(&config, None).await?;
+
     // Only check for service name uniqueness if it's different from the current name
     if service_name != config.name {
         check_service_name_uniqueness(&config, Some(service_name)).await?;
@@ -1023,12 +1753,7 @@ pub async fn handle_config_update(service_name: &str, config: ServiceConfig) ->
         }
     } else {
         // Existing service - send pause signal
-        if let Some(sender) = CONFIG_UPDATES.get() {
-            sender
-                .send((service_name.to_string(), ScaleMessage::ConfigUpdate))
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to send config update: {}", e))?;
-        }
+        send_scale_message(service_name, ScaleMessage::ConfigUpdate).await;
     }
 
     // Update config in store
@@ -1043,18 +1768,31 @@ pub async fn handle_config_update(service_name: &str, config: ServiceConfig) ->
         }
     }
 
+    // Running pods pick up annotation changes without a restart
+    if let Some(instance_store) = INSTANCE_STORE.get() {
+        let mut store = instance_store.write().await;
+        if let Some(instances) = store.get_mut(service_name) {
+            for metadata in instances.values_mut() {
+                if metadata.annotations != config.annotations {
+                    metadata.annotations = config.annotations.clone();
+                    record_change(
+                        service_name,
+                        InstanceEvent::PodUpdated {
+                            instance: metadata.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
     // Handle containers and proxy
     manage(service_name, config.clone()).await;
     proxy::run_proxy_for_service(service_name.to_string(), config.clone()).await;
 
     // If it's an existing service, send resume signal
     if !is_new_service {
-        if let Some(sender) = CONFIG_UPDATES.get() {
-            sender
-                .send((service_name.to_string(), ScaleMessage::Resume))
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to send resume signal: {}", e))?;
-        }
+        send_scale_message(service_name, ScaleMessage::Resume).await;
     }
 
     slog::debug!(log, "Completed config update process";
@@ -1085,6 +1823,7 @@ mod tests {
                 cpu_percentage: Some(70),
                 cpu_percentage_relative: Some(80),
                 memory_percentage: Some(75),
+                gpu_percentage: None,
                 metrics_strategy: PodMetricsStrategy::Maximum,
             }),
             instance_count: InstanceCount { min: 1, max: 10 },
@@ -1092,12 +1831,49 @@ mod tests {
             interval_seconds: Some(30),
             image_check_interval: Some(Duration::from_secs(300)),
             rolling_update_config: None,
+            max_pod_lifetime: None,
             volumes: None,
             codel: None,
             scaling_policy: Some(ScalingPolicy {
                 cooldown_duration: Some(Duration::from_secs(60)),
                 scale_down_threshold_percentage: Some(50.0),
+                schedules: Vec::new(),
+                idle_timeout: None,
+                target_rps_per_instance: None,
+                latency_slo: None,
+                predictive: None,
+                behavior: None,
+                triggers: Vec::new(),
+                webhook: None,
+                vertical: None,
             }),
+            scale_to_zero: None,
+            annotations: HashMap::new(),
+            runtime_concurrency: None,
+            namespace: None,
+            readiness: None,
+            traffic_recording: None,
+            access_log: false,
+            slow_start: None,
+            degraded_weight: None,
+            upstream: None,
+            request_limits: None,
+            health_webhooks: vec![],
+            anomaly_detection: None,
+            external: None,
+            proxy_allowlist: None,
+            dns: None,
+            dns_search: None,
+            extra_hosts: None,
+            secrets: Vec::new(),
+            identity: None,
+            logging: None,
+            image_pull_secrets: Vec::new(),
+            expires_after: None,
+            priority: None,
+            start_after: Vec::new(),
+            groups: Vec::new(),
+            enabled: true,
         }
     }
 
@@ -1133,12 +1909,16 @@ mod tests {
         );
 
         let mut pod_stats = HashMap::new();
-        pod_stats.insert(Uuid::new_v4(), PodStats {
-            cpu_percentage: 85.0,
-            cpu_percentage_relative: 90.0,
-            memory_usage: 900,
-            memory_limit: 1000,
-        });
+        pod_stats.insert(
+            Uuid::new_v4(),
+            PodStats {
+                cpu_percentage: 85.0,
+                cpu_percentage_relative: 90.0,
+                memory_usage: 900,
+                memory_limit: 1000,
+                gpu_percentage: 0.0,
+            },
+        );
 
         let result = manager.evaluate(3, &pod_stats).await;
         assert!(matches!(result, ScalingDecision::NoChange));
@@ -1155,12 +1935,16 @@ mod tests {
         );
 
         let mut pod_stats = HashMap::new();
-        pod_stats.insert(Uuid::new_v4(), PodStats {
-            cpu_percentage: 10.0,
-            cpu_percentage_relative: 15.0,
-            memory_usage: 200,
-            memory_limit: 1000,
-        });
+        pod_stats.insert(
+            Uuid::new_v4(),
+            PodStats {
+                cpu_percentage: 10.0,
+                cpu_percentage_relative: 15.0,
+                memory_usage: 200,
+                memory_limit: 1000,
+                gpu_percentage: 0.0,
+            },
+        );
 
         let result = manager.evaluate(3, &pod_stats).await;
         assert!(matches!(result, ScalingDecision::NoChange));
@@ -1172,4 +1956,71 @@ mod tests {
         assert_eq!(config.instance_count.min, 1);
         assert_eq!(config.instance_count.max, 10);
     }
+
+    #[test]
+    fn test_parse_ulimit() {
+        let single = UlimitValue::Single(Value::from(65536));
+        assert_eq!(parse_ulimit("nofile", &single).unwrap(), (65536, 65536));
+
+        let range = UlimitValue::Range {
+            soft: Value::from(1024),
+            hard: Value::from("unlimited"),
+        };
+        assert_eq!(parse_ulimit("nproc", &range).unwrap(), (1024, -1));
+
+        let inverted = UlimitValue::Range {
+            soft: Value::from("unlimited"),
+            hard: Value::from(1024),
+        };
+        assert!(parse_ulimit("nproc", &inverted).is_err());
+        assert!(parse_ulimit("files", &single).is_err());
+    }
+
+    #[test]
+    fn test_check_sysctl() {
+        assert!(check_sysctl("net.core.somaxconn", false).is_ok());
+        assert!(check_sysctl("net.ipv4.tcp_rmem", false).is_err());
+        assert!(check_sysctl("net.ipv4.tcp_rmem", true).is_ok());
+        assert!(check_sysctl("kernel.shmmax", true).is_ok());
+        assert!(check_sysctl("vm.swappiness", true).is_err());
+    }
+
+    #[test]
+    fn test_merge_overrides() {
+        let mut base: serde_yaml::Value = serde_yaml::from_str(
+            "name: web\ninstance_count: {min: 1, max: 3}\nspec:\n  containers:\n    - {name: app, image: app:1}\n    - {name: sidecar, image: proxy:1}\n",
+        )
+        .unwrap();
+        let overlay: serde_yaml::Value = serde_yaml::from_str(
+            "name: web\ninstance_count: {max: 10}\nspec:\n  containers:\n    - {name: app, image: app:2}\n",
+        )
+        .unwrap();
+        layers::merge(&mut base, overlay);
+
+        let config: ServiceConfig = serde_yaml::from_value(base).unwrap();
+        assert_eq!(config.instance_count.min, 1);
+        assert_eq!(config.instance_count.max, 10);
+        assert_eq!(config.spec.containers.len(), 2);
+        assert_eq!(config.spec.containers[0].image, "app:2");
+        assert_eq!(config.spec.containers[1].image, "proxy:1");
+    }
+
+    #[test]
+    fn test_parse_issue_locates_field() {
+        let contents = "name: web\nspec:\n  containers:\n    - name: app\n      image: app:1\n      health_check:\n        liveness_period: 1,5s\n";
+        let error =
+            report::deserialize_config(serde_yaml::Deserializer::from_str(contents)).unwrap_err();
+        let issue = error.issue;
+        assert_eq!(
+            issue.path.as_deref(),
+            Some("spec.containers[0].health_check.liveness_period")
+        );
+        assert_eq!((issue.line, issue.column), (Some(7), Some(26)));
+        assert_eq!(issue.example.as_deref(), Some("30s, 5m or 1h 30m"));
+
+        let missing = "name: web\nspec:\n  containers:\n    - image: app:1\n";
+        let error =
+            report::deserialize_config(serde_yaml::Deserializer::from_str(missing)).unwrap_err();
+        assert_eq!(error.issue.path.as_deref(), Some("spec.containers[0].name"));
+    }
 }
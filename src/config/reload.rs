@@ -0,0 +1,177 @@
+// src/config/reload.rs
+//
+// Full reloads. The file watcher applies config changes as they happen, but
+// it can miss events, e.g. on network filesystems where inotify is
+// unreliable. On SIGHUP, or a call to the reload API, every config file is
+// read and validated against the other services again, and only what changed
+// is applied: new files start services, changed files update them, and
+// services whose file is gone are removed. A file that fails to load is
+// reported and leaves its service as it was.
+use anyhow::Result;
+use notify::event::{CreateKind, ModifyKind, RemoveKind};
+use notify::EventKind;
+use notify_debouncer_full::DebouncedEvent;
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Instant;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Mutex;
+
+use super::report::ValidationReport;
+use super::{config_files, process_event, read_yaml_config, ServiceConfig, CONFIG_STORE};
+
+pub static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// Reloads run one at a time
+static RELOAD_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReloadReport {
+    /// Services started, updated and removed
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// Services whose config was already loaded as it is
+    pub unchanged: Vec<String>,
+    /// Files that failed to load; their services were left as they were
+    pub invalid: Vec<ValidationReport>,
+}
+
+// Whether two configs are the same once loaded
+fn same_config(a: &ServiceConfig, b: &ServiceConfig) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Apply a file change the way the watcher would
+async fn apply(kind: EventKind, path: PathBuf, config_dir: &Path) {
+    let event = notify::Event::new(kind).add_path(path);
+    process_event(DebouncedEvent::new(event, Instant::now()), config_dir).await;
+}
+
+/// Re-read every config in the config directory and apply what changed
+pub async fn reload(config_dir: &Path) -> Result<ReloadReport> {
+    let _guard = RELOAD_LOCK.lock().await;
+    let config_store = CONFIG_STORE.get().unwrap();
+    let mut report = ReloadReport::default();
+
+    let files = config_files(config_dir)?;
+    let keys: FxHashSet<String> = files
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+
+    // Removed first, so their names and ports are free for the other files
+    let removed: Vec<(String, String)> = {
+        let store = config_store.read().await;
+        store
+            .iter()
+            .filter(|(key, _)| !keys.contains(*key))
+            .map(|(key, (_, config))| (key.clone(), config.name.clone()))
+            .collect()
+    };
+    for (key, service_name) in removed {
+        apply(
+            EventKind::Remove(RemoveKind::File),
+            PathBuf::from(key),
+            config_dir,
+        )
+        .await;
+        report.removed.push(service_name);
+    }
+
+    // A file that fails against a service another file changes is retried
+    // once that change is applied
+    let mut pending = files;
+    loop {
+        let mut applied = false;
+        let mut failed = Vec::new();
+        for path in pending {
+            let existing = {
+                let store = config_store.read().await;
+                store
+                    .get(&path.display().to_string())
+                    .map(|(_, config)| config.clone())
+            };
+            let result = read_yaml_config(&path, existing.as_ref().map(|c| c.name.as_str())).await;
+            let config = match result {
+                Ok(config) => config,
+                Err(e) => {
+                    failed.push((path, Err(e)));
+                    continue;
+                }
+            };
+
+            match existing {
+                Some(existing) if same_config(&existing, &config) => {
+                    report.unchanged.push(config.name);
+                }
+                Some(_) => {
+                    apply(EventKind::Modify(ModifyKind::Any), path, config_dir).await;
+                    report.updated.push(config.name);
+                    applied = true;
+                }
+                None => {
+                    apply(EventKind::Create(CreateKind::File), path, config_dir).await;
+                    report.added.push(config.name);
+                    applied = true;
+                }
+            }
+        }
+
+        if !applied || failed.is_empty() {
+            report.invalid = failed
+                .into_iter()
+                .map(|(path, result)| ValidationReport::new(path.display().to_string(), &result))
+                .collect();
+            break;
+        }
+        pending = failed.into_iter().map(|(path, _)| path).collect();
+    }
+
+    let log = slog_scope::logger();
+    for invalid in &report.invalid {
+        for issue in &invalid.issues {
+            slog::warn!(log, "Config not reloaded";
+                "file" => &invalid.file,
+                "error" => issue.to_string()
+            );
+        }
+    }
+    slog::info!(log, "Configs reloaded";
+        "added" => report.added.len(),
+        "updated" => report.updated.len(),
+        "removed" => report.removed.len(),
+        "unchanged" => report.unchanged.len(),
+        "invalid" => report.invalid.len()
+    );
+    Ok(report)
+}
+
+/// Reload every config each time Orbit receives SIGHUP
+pub fn start_reload_on_hangup(config_dir: PathBuf) {
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                slog::error!(slog_scope::logger(), "Failed to handle SIGHUP";
+                    "error" => e.to_string()
+                );
+                return;
+            }
+        };
+
+        while hangups.recv().await.is_some() {
+            slog::info!(slog_scope::logger(), "SIGHUP received, reloading configs");
+            if let Err(e) = reload(&config_dir).await {
+                slog::error!(slog_scope::logger(), "Failed to reload configs";
+                    "error" => e.to_string()
+                );
+            }
+        }
+    });
+}
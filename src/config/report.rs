@@ -0,0 +1,217 @@
+// src/config/report.rs
+//
+// Validation reports. A config that fails to parse or validate is described
+// by an issue naming the field it is about, as a serde path like
+// `spec.containers[0].health_check.interval`, with the line and column when
+// they are known, what the field expects and an example of a valid value.
+// `orbit validate` and the validate API return them as reports, and the same
+// issues make up the errors logged when a config can't be loaded.
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+use super::validate::{ConfigValidationError, PortValidationError};
+use super::ServiceConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// The field the issue is about, when it is known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub message: String,
+    /// What the field accepts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    /// A valid value for the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(example) = &self.example {
+            write!(f, ", e.g. {}", example)?;
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, " (line {}, column {})", line, column)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// The config file, or "-" for a config sent to the API
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    pub valid: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// The report for a config that parsed and validated, or failed to
+    pub fn new(file: String, result: &anyhow::Result<ServiceConfig>) -> Self {
+        match result {
+            Ok(config) => Self {
+                file,
+                service: Some(config.name.clone()),
+                valid: true,
+                issues: Vec::new(),
+            },
+            Err(e) => Self {
+                file,
+                service: None,
+                valid: false,
+                issues: vec![issue_from_error(e)],
+            },
+        }
+    }
+}
+
+/// A config that could not be parsed, with the issue describing why
+#[derive(Debug, Error)]
+#[error("{issue}")]
+pub struct ConfigParseError {
+    pub issue: Box<ValidationIssue>,
+}
+
+/// Deserialize a service config, tracking the path to the field that fails
+pub fn deserialize_config<'de, D>(deserializer: D) -> Result<ServiceConfig, ConfigParseError>
+where
+    D: Deserializer<'de, Error = serde_yaml::Error>,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let issue = parse_issue(Some(path).filter(|path| path != "."), e.inner());
+        ConfigParseError {
+            issue: Box::new(issue),
+        }
+    })
+}
+
+/// The issue for a document that isn't valid YAML
+pub fn syntax_error(error: serde_yaml::Error) -> ConfigParseError {
+    ConfigParseError {
+        issue: Box::new(parse_issue(None, &error)),
+    }
+}
+
+fn parse_issue(mut path: Option<String>, error: &serde_yaml::Error) -> ValidationIssue {
+    let location = error.location();
+    let mut message = error.to_string();
+    // serde_yaml prefixes the path and adds the location when it knows them
+    if let Some(location) = &location {
+        let at = format!(" at line {} column {}", location.line(), location.column());
+        message = message.replacen(&at, "", 1);
+    }
+    if let Some(stripped) = path
+        .as_ref()
+        .and_then(|path| message.strip_prefix(&format!("{}: ", path)))
+    {
+        message = stripped.to_string();
+    }
+
+    let (expected, example) = match between(&message, "missing field `", "`") {
+        Some(field) => {
+            path = Some(match path {
+                Some(path) => format!("{}.{}", path, field),
+                None => field.to_string(),
+            });
+            (Some("a value".to_string()), None)
+        }
+        None => match message.split_once(", expected ") {
+            Some((_, expected)) => {
+                let (expected, example) = describe(expected);
+                (Some(expected), example)
+            }
+            None => (None, None),
+        },
+    };
+    ValidationIssue {
+        path,
+        message,
+        expected,
+        example,
+        line: location.as_ref().map(|location| location.line()),
+        column: location.as_ref().map(|location| location.column()),
+    }
+}
+
+// What serde's "expected ..." means in config terms, and an example value
+fn describe(expected: &str) -> (String, Option<String>) {
+    let (description, example) = match expected {
+        "a duration" => (
+            "a duration: whole numbers with units such as ms, s, m, h or days",
+            "30s, 5m or 1h 30m",
+        ),
+        "u8" => ("a whole number from 0 to 255", "3"),
+        "u16" => ("a whole number from 0 to 65535", "8080"),
+        "u32" | "u64" | "usize" => ("a whole number", "10"),
+        "i32" | "i64" => ("a whole number, possibly negative", "-1"),
+        "f32" | "f64" => ("a number", "50.0"),
+        "a boolean" => ("true or false", "true"),
+        "a string" => ("a string", "\"value\""),
+        "a sequence" => ("a list", "[a, b]"),
+        _ => {
+            // Enums list their variants: "one of `A`, `B`" or "`A` or `B`"
+            let first_variant = between(expected, "`", "`");
+            return (
+                expected.to_string(),
+                first_variant.map(|variant| variant.to_string()),
+            );
+        }
+    };
+    (description.to_string(), Some(example.to_string()))
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let rest = &text[text.find(start)? + start.len()..];
+    Some(&rest[..rest.find(end)?])
+}
+
+/// The issue for an error from loading a config: where and why parsing
+/// failed, or the validation rule it broke
+pub fn issue_from_error(error: &anyhow::Error) -> ValidationIssue {
+    if let Some(e) = error.downcast_ref::<ConfigParseError>() {
+        return (*e.issue).clone();
+    }
+    if let Some(e) = error.downcast_ref::<serde_yaml::Error>() {
+        return parse_issue(None, e);
+    }
+    let message = error.to_string();
+    // Service-level rules name the field first, as in "scaling_policy.idle_timeout: ..."
+    let path = match error.downcast_ref::<ConfigValidationError>() {
+        Some(ConfigValidationError::InvalidService(_, reason)) => reason
+            .split_once(": ")
+            .map(|(path, _)| path)
+            .filter(|path| {
+                path.chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_.".contains(c))
+            })
+            .map(str::to_string),
+        _ => None,
+    };
+    let path = path.or_else(|| {
+        error
+            .downcast_ref::<PortValidationError>()
+            .map(|_| "spec.containers.ports".to_string())
+    });
+    ValidationIssue {
+        path,
+        message,
+        expected: None,
+        example: None,
+        line: None,
+        column: None,
+    }
+}
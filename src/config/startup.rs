@@ -0,0 +1,109 @@
+// src/config/startup.rs
+//
+// Ordered daemon startup. Without a startup policy the services of the config
+// directory are initialised one after another in file order. With one they
+// start in waves: a service waits for the services it lists in `start_after`,
+// higher `priority` services start first within a wave, at most `concurrency`
+// at a time, and each wave can be held until its services are ready.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::ServiceConfig;
+use crate::container::{readiness, stopped};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StartupPolicy {
+    /// Services initialised at once within a wave
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Hold the next wave until this wave's services are ready
+    #[serde(default = "default_wait_for_ready")]
+    pub wait_for_ready: bool,
+    /// How long a wave waits for a service to be ready before moving on
+    #[serde(with = "humantime_serde", default = "default_ready_timeout")]
+    pub ready_timeout: Duration,
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_wait_for_ready() -> bool {
+    true
+}
+
+fn default_ready_timeout() -> Duration {
+    Duration::from_secs(300)
+}
+
+impl StartupPolicy {
+    pub fn validate(&self) -> Result<()> {
+        if self.concurrency == 0 {
+            return Err(anyhow!("startup.concurrency must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Group services into waves that start one after another: each service
+/// comes after the services it lists in `start_after`, and each wave is in
+/// descending `priority` order. Services in a `start_after` cycle start
+/// together once nothing else can.
+pub fn startup_waves(mut services: Vec<ServiceConfig>) -> Vec<Vec<ServiceConfig>> {
+    let names: HashSet<String> = services.iter().map(|config| config.name.clone()).collect();
+    for config in &services {
+        for dependency in &config.start_after {
+            if !names.contains(dependency) {
+                slog::warn!(slog_scope::logger(), "start_after names an unknown service, ignoring it";
+                    "service" => &config.name,
+                    "start_after" => dependency
+                );
+            }
+        }
+    }
+
+    let mut started: HashSet<String> = HashSet::new();
+    let mut waves = Vec::new();
+    while !services.is_empty() {
+        let (mut wave, mut waiting): (Vec<_>, Vec<_>) = services.into_iter().partition(|config| {
+            config
+                .start_after
+                .iter()
+                .all(|dependency| started.contains(dependency) || !names.contains(dependency))
+        });
+        if wave.is_empty() {
+            slog::warn!(slog_scope::logger(), "Services wait for each other in start_after, starting them together";
+                "services" => waiting
+                    .iter()
+                    .map(|config| config.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            wave = std::mem::take(&mut waiting);
+        }
+        wave.sort_by_key(|config| Reverse(config.priority.unwrap_or(0)));
+        started.extend(wave.iter().map(|config| config.name.clone()));
+        waves.push(wave);
+        services = waiting;
+    }
+    waves
+}
+
+/// Wait until the service meets its readiness definition, up to `timeout`.
+/// External and stopped services run no pods, so there is nothing to wait for.
+pub async fn wait_until_started(config: &ServiceConfig, timeout: Duration) {
+    if config.external.is_some() || stopped::is_stopped(config) {
+        return;
+    }
+    let report = readiness::wait_until_ready(config, None, timeout).await;
+    if !report.ready {
+        slog::warn!(slog_scope::logger(), "Service not ready at startup, starting the next services anyway";
+            "service" => &config.name,
+            "timeout" => format!("{:?}", timeout),
+            "pending" => report.pending.join(", ")
+        );
+    }
+}
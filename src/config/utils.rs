@@ -1,10 +1,11 @@
 // src/config/utils.rs
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
 use uuid::Uuid;
 
 use super::{ServiceConfig, CONFIG_STORE};
+use crate::bundle::BUNDLES_DIR;
 
 #[derive(Debug)]
 pub struct ContainerNameParts {
@@ -77,3 +78,132 @@ pub fn get_relative_config_path(full_path: &Path, config_dir: &Path) -> Option<S
     }
     None
 }
+
+fn is_yaml(path: &Path) -> bool {
+    path.is_file()
+        && matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml") | Some("yml")
+        )
+}
+
+/// Service config files: the YAML files in the config directory, followed by
+/// those of installed bundles in `bundles/<name>/`
+pub fn config_files(config_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(config_dir)? {
+        let path = entry?.path();
+        if is_yaml(&path) {
+            files.push(path);
+        }
+    }
+
+    let bundles_dir = config_dir.join(BUNDLES_DIR);
+    if bundles_dir.is_dir() {
+        let mut bundle_files = Vec::new();
+        for bundle in std::fs::read_dir(&bundles_dir)? {
+            let bundle = bundle?.path();
+            if !bundle.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&bundle)? {
+                let path = entry?.path();
+                if is_yaml(&path) {
+                    bundle_files.push(path);
+                }
+            }
+        }
+        bundle_files.sort();
+        files.extend(bundle_files);
+    }
+
+    Ok(files)
+}
+
+/// Make the service's relative `env_file` paths relative to `base_dir`, the
+/// directory of its config file, rather than Orbit's working directory
+pub fn resolve_env_files(config: &mut ServiceConfig, base_dir: &Path) {
+    for container in &mut config.spec.containers {
+        for path in container.env_file.iter_mut().flatten() {
+            if path.is_relative() {
+                *path = base_dir.join(&*path);
+            }
+        }
+    }
+}
+
+/// Read a Docker-style env file: KEY=VALUE lines, `#` comments and blank lines
+/// ignored. A bare KEY takes its value from Orbit's own environment, if set.
+pub async fn parse_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| anyhow!("Failed to read env file {}: {}", path.display(), e))?;
+
+    let mut vars = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) => vars.push((key.to_string(), value.to_string())),
+            None => {
+                let key = line.trim_end();
+                if let Ok(value) = std::env::var(key) {
+                    vars.push((key.to_string(), value));
+                }
+            }
+        }
+    }
+
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_env_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.env");
+        std::fs::write(&path, "# comment\n\nLEVEL=debug\nURL=http://a?b=c\n").unwrap();
+
+        let vars = parse_env_file(&path).await.unwrap();
+        assert_eq!(
+            vars,
+            vec![
+                ("LEVEL".to_string(), "debug".to_string()),
+                ("URL".to_string(), "http://a?b=c".to_string()),
+            ]
+        );
+        assert!(parse_env_file(&dir.path().join("missing.env"))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_resolve_env_files() {
+        let mut config: ServiceConfig = serde_yaml::from_str(
+            r#"
+name: web
+instance_count: { min: 1, max: 1 }
+spec:
+  containers:
+    - name: app
+      image: example/app:1
+      env_file: [app.env, /etc/orbit/shared.env]
+"#,
+        )
+        .unwrap();
+        resolve_env_files(&mut config, Path::new("/srv/orbit/bundles/shop"));
+        assert_eq!(
+            config.spec.containers[0].env_file,
+            Some(vec![
+                PathBuf::from("/srv/orbit/bundles/shop/app.env"),
+                PathBuf::from("/etc/orbit/shared.env"),
+            ])
+        );
+    }
+}
@@ -1,10 +1,23 @@
 // src/config/validate.rs
 use anyhow::Result;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use thiserror::Error;
 
-use super::{ServiceConfig, CONFIG_STORE};
+use super::{
+    check_sysctl, parse_env_file, parse_shm_size, parse_tmpfs, parse_ulimit, validate_userns_mode,
+    ServiceConfig, CONFIG_STORE,
+};
+use crate::container::canary::UpdateStrategy;
+use crate::container::dependencies::start_order;
+use crate::container::dns::{dns_for, validate_dns};
+use crate::container::identity;
+use crate::container::resources;
+use crate::container::secrets::{is_absolute_mount, secrets_for, SecretConfig, SecretSource};
+use crate::container::shaping::validate_network_limit;
+use crate::container::volumes::AccessMode;
+use crate::container::{logging_for, validate_stop_signal, RUNTIME};
 
 #[derive(Error, Debug)]
 pub enum ConfigValidationError {
@@ -16,6 +29,18 @@ pub enum ConfigValidationError {
     InvalidServiceName(String, String),
     #[error("Invalid container name '{0}': {1}")]
     InvalidContainerName(String, String),
+    #[error("Invalid environment variable '{0}' in container '{1}': {2}")]
+    InvalidEnvKey(String, String, String),
+    #[error("Invalid env_file for container '{0}': {1}")]
+    InvalidEnvFile(String, String),
+    #[error("Invalid secret '{0}' in service '{1}': {2}")]
+    InvalidSecret(String, String, String),
+    #[error("Invalid limit in container '{0}': {1}")]
+    InvalidLimit(String, String),
+    #[error("Invalid {1} in container '{0}': {2}")]
+    InvalidContainerOption(String, String, String),
+    #[error("Invalid service '{0}': {1}")]
+    InvalidService(String, String),
 }
 
 #[derive(Error, Debug)]
@@ -94,6 +119,9 @@ pub async fn check_port_conflicts(
             }
         }
     }
+    if let Some(external) = &new_config.external {
+        new_node_ports.insert(external.node_port);
+    }
 
     // Check against all existing services
     for (_, (_, existing_config)) in store.iter() {
@@ -141,6 +169,65 @@ pub async fn check_port_conflicts(
                 }
             }
         }
+
+        if let Some(external) = &existing_config.external {
+            if new_node_ports.contains(&external.node_port)
+                || new_target_ports.contains(&external.node_port)
+            {
+                return Err(PortValidationError::PortConflictBetweenServices {
+                    port_type: "node".to_string(),
+                    port: external.node_port,
+                    service1: new_config.name.clone(),
+                    service2: existing_config.name.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that no other service holds a ReadWriteOnce claim this service
+/// also holds ReadWriteOnce, so a shared volume has a single writer
+pub async fn check_volume_claim_conflicts(
+    new_config: &ServiceConfig,
+    exclude_service: Option<&str>,
+) -> Result<(), ConfigValidationError> {
+    let read_write_claims = |config: &ServiceConfig| -> HashSet<String> {
+        config
+            .volumes
+            .iter()
+            .flatten()
+            .filter_map(|(_, data)| data.claim.as_ref())
+            .filter(|claim| claim.access_mode == AccessMode::ReadWriteOnce)
+            .map(|claim| claim.name.clone())
+            .collect()
+    };
+    let new_claims = read_write_claims(new_config);
+    if new_claims.is_empty() {
+        return Ok(());
+    }
+
+    let config_store = CONFIG_STORE.get().expect("Config store not initialized");
+    let store = config_store.read().await;
+    for (_, (_, existing_config)) in store.iter() {
+        if existing_config.name == new_config.name
+            || exclude_service.is_some_and(|exclude| existing_config.name == exclude)
+        {
+            continue;
+        }
+        if let Some(claim) = read_write_claims(existing_config)
+            .iter()
+            .find(|claim| new_claims.contains(*claim))
+        {
+            return Err(ConfigValidationError::InvalidService(
+                new_config.name.clone(),
+                format!(
+                    "claim '{}' is already mounted ReadWriteOnce by service '{}'",
+                    claim, existing_config.name
+                ),
+            ));
+        }
     }
 
     Ok(())
@@ -171,6 +258,660 @@ pub fn validate_container_name(name: &str) -> Result<(), ConfigValidationError>
     Ok(())
 }
 
+pub fn validate_env_key(key: &str, container: &str) -> Result<(), ConfigValidationError> {
+    // POSIX shell variable names, which every runtime accepts
+    let key_regex = regex::Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    if !key_regex.is_match(key) {
+        return Err(ConfigValidationError::InvalidEnvKey(
+            key.to_string(),
+            container.to_string(),
+            "Environment variable names must contain only letters, digits and '_', and must not start with a digit".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Check env keys, including those from env files, which must be readable at load time
+pub async fn validate_container_env(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    for container in &config.spec.containers {
+        for key in container.env.iter().flat_map(|env| env.keys()) {
+            validate_env_key(key, &container.name)?;
+        }
+
+        for path in container.env_file.iter().flatten() {
+            let vars = parse_env_file(path).await.map_err(|e| {
+                ConfigValidationError::InvalidEnvFile(container.name.clone(), e.to_string())
+            })?;
+            for (key, _) in vars {
+                validate_env_key(&key, &container.name)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Check resources, ulimits, pids_limit, shm_size, sysctls, network_limit and blkio_limit, which are otherwise only parsed at start
+pub fn validate_container_limits(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    for container in &config.spec.containers {
+        let invalid =
+            |reason: String| ConfigValidationError::InvalidLimit(container.name.clone(), reason);
+
+        resources::validate(container, config).map_err(|e| invalid(e.to_string()))?;
+        for (name, value) in container.ulimits.iter().flatten() {
+            parse_ulimit(name, value).map_err(|e| invalid(e.to_string()))?;
+        }
+        if container
+            .pids_limit
+            .is_some_and(|limit| limit == 0 || limit < -1)
+        {
+            return Err(invalid(
+                "pids_limit must be positive, or -1 for unlimited".to_string(),
+            ));
+        }
+        if let Some(shm_size) = &container.shm_size {
+            parse_shm_size(shm_size).map_err(|e| invalid(e.to_string()))?;
+        }
+        if let Some(network_limit) = &container.network_limit {
+            validate_network_limit(network_limit).map_err(|e| invalid(e.to_string()))?;
+        }
+        if let Some(blkio_limit) = &container.blkio_limit {
+            blkio_limit
+                .validate()
+                .map_err(|e| invalid(format!("blkio_limit: {}", e)))?;
+        }
+        let allow_unsafe = container.allow_unsafe_sysctls == Some(true);
+        for name in container.sysctls.iter().flat_map(|sysctls| sysctls.keys()) {
+            check_sysctl(name, allow_unsafe).map_err(|e| invalid(e.to_string()))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Check hostname, labels, DNS settings, logging and the stop signal; Orbit's own labels use the "orbit." prefix
+pub fn validate_container_metadata(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let hostname_regex =
+        regex::Regex::new(r"^[A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?$").unwrap();
+    let digest_regex = regex::Regex::new(r"^sha256:[a-f0-9]{64}$").unwrap();
+
+    for container in &config.spec.containers {
+        let invalid = |option: &str, reason: String| {
+            ConfigValidationError::InvalidContainerOption(
+                container.name.clone(),
+                option.to_string(),
+                reason,
+            )
+        };
+
+        if let Some(digest) = container.pinned_digest() {
+            if !digest_regex.is_match(digest) {
+                return Err(invalid(
+                    "image",
+                    format!(
+                        "digest '{}' must be 'sha256:' followed by 64 hex digits",
+                        digest
+                    ),
+                ));
+            }
+        }
+        if let Some(hostname) = &container.hostname {
+            if !hostname_regex.is_match(hostname) {
+                return Err(invalid(
+                    "hostname",
+                    format!(
+                        "'{}' must be 1-63 letters, digits or '-', not starting or ending with '-'",
+                        hostname
+                    ),
+                ));
+            }
+        }
+        validate_dns(&dns_for(config, container)).map_err(|e| invalid("dns", e.to_string()))?;
+        if let Some(logging) = logging_for(config, container) {
+            logging
+                .validate()
+                .map_err(|e| invalid("logging", e.to_string()))?;
+        }
+        if let Some(health_check) = &container.health_check {
+            health_check
+                .validate()
+                .map_err(|e| invalid("health_check", e.to_string()))?;
+        }
+        if let Some(crash_dumps) = &container.crash_dumps {
+            crash_dumps
+                .validate()
+                .map_err(|e| invalid("crash_dumps", e.to_string()))?;
+        }
+        if let Some(signal) = &container.stop_signal {
+            validate_stop_signal(signal).map_err(|e| invalid("stop_signal", e.to_string()))?;
+        }
+        for key in container.labels.iter().flat_map(|labels| labels.keys()) {
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(invalid(
+                    "labels",
+                    format!("'{}' is not a valid label key", key),
+                ));
+            }
+            if key.starts_with("orbit.") {
+                return Err(invalid(
+                    "labels",
+                    format!("'{}' uses the reserved 'orbit.' prefix", key),
+                ));
+            }
+        }
+    }
+    start_order(&config.spec.containers)
+        .map_err(|e| ConfigValidationError::InvalidService(config.name.clone(), e.to_string()))?;
+
+    Ok(())
+}
+
+/// Check an external service's hostname and ports, and that it runs no
+/// containers. Other services need containers and instances.
+/// Validate what only the runtime in use can check, e.g. limits its microVMs
+/// can't honor. Skipped when no runtime has been created.
+pub fn validate_runtime_support(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    match RUNTIME.get() {
+        Some(runtime) => runtime
+            .validate_service(config)
+            .map_err(|e| ConfigValidationError::InvalidService(config.name.clone(), e.to_string())),
+        None => Ok(()),
+    }
+}
+
+pub fn validate_external(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let invalid =
+        |reason: String| ConfigValidationError::InvalidService(config.name.clone(), reason);
+
+    match &config.external {
+        Some(external) => {
+            external
+                .validate()
+                .map_err(|e| invalid(format!("external: {}", e)))?;
+            if !config.spec.containers.is_empty() {
+                return Err(invalid(
+                    "external services are not run by Orbit and cannot have containers".to_string(),
+                ));
+            }
+        }
+        None => {
+            if config.spec.containers.is_empty() {
+                return Err(invalid("spec.containers is required".to_string()));
+            }
+            if config.instance_count.max == 0 {
+                return Err(invalid("instance_count.max must be at least 1".to_string()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the URLs and timeouts of the health webhooks
+pub fn validate_health_webhooks(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    for webhook in &config.health_webhooks {
+        webhook.validate().map_err(|e| {
+            ConfigValidationError::InvalidService(
+                config.name.clone(),
+                format!("health_webhooks: {}", e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Check that the anomaly detection threshold and smoothing are in range
+pub fn validate_anomaly_detection(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    if let Some(detection) = &config.anomaly_detection {
+        detection.validate().map_err(|e| {
+            ConfigValidationError::InvalidService(
+                config.name.clone(),
+                format!("anomaly_detection: {}", e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Check the scaling policy: the cron expressions and bounds of scheduled
+/// scaling windows, the idle timeout, the request rate target and the latency SLO
+pub fn validate_scaling_policy(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let Some(policy) = &config.scaling_policy else {
+        return Ok(());
+    };
+    let invalid = |reason: String| {
+        ConfigValidationError::InvalidService(
+            config.name.clone(),
+            format!("scaling_policy.{}", reason),
+        )
+    };
+    for schedule in &policy.schedules {
+        schedule
+            .validate()
+            .map_err(|e| invalid(format!("schedules: {}", e)))?;
+    }
+    if policy.idle_timeout.is_some_and(|timeout| timeout.is_zero()) {
+        return Err(invalid("idle_timeout must be positive".to_string()));
+    }
+    if policy
+        .target_rps_per_instance
+        .is_some_and(|target| !target.is_finite() || target <= 0.0)
+    {
+        return Err(invalid(
+            "target_rps_per_instance must be a positive number".to_string(),
+        ));
+    }
+    if let Some(slo) = &policy.latency_slo {
+        slo.validate()
+            .map_err(|e| invalid(format!("latency_slo: {}", e)))?;
+    }
+    if let Some(predictive) = &policy.predictive {
+        predictive
+            .validate()
+            .map_err(|e| invalid(format!("predictive: {}", e)))?;
+    }
+    if let Some(behavior) = &policy.behavior {
+        behavior
+            .validate()
+            .map_err(|e| invalid(format!("behavior: {}", e)))?;
+    }
+    for trigger in &policy.triggers {
+        trigger
+            .validate()
+            .map_err(|e| invalid(format!("triggers: {}", e)))?;
+    }
+    if let Some(webhook) = &policy.webhook {
+        webhook
+            .validate()
+            .map_err(|e| invalid(format!("webhook: {}", e)))?;
+    }
+    if let Some(vertical) = &policy.vertical {
+        vertical
+            .validate(config)
+            .map_err(|e| invalid(format!("vertical: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Check the canary settings, which only the canary strategy uses
+pub fn validate_rolling_update(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let Some(update) = &config.rolling_update_config else {
+        return Ok(());
+    };
+    let invalid = |reason: String| {
+        ConfigValidationError::InvalidService(
+            config.name.clone(),
+            format!("rolling_update_config.{}", reason),
+        )
+    };
+    if update.strategy == UpdateStrategy::Canary && config.external.is_some() {
+        return Err(invalid(
+            "strategy: external services have no pods to update".to_string(),
+        ));
+    }
+    if let Some(canary) = &update.canary {
+        if update.strategy != UpdateStrategy::Canary {
+            return Err(invalid("canary needs strategy: canary".to_string()));
+        }
+        canary
+            .validate()
+            .map_err(|e| invalid(format!("canary: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Check the pod lifetime limit, which needs pods that run long enough to be
+/// worth replacing
+pub fn validate_max_pod_lifetime(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let Some(lifetime) = config.max_pod_lifetime else {
+        return Ok(());
+    };
+    let max_surge = config
+        .rolling_update_config
+        .as_ref()
+        .map_or(1, |update| update.max_surge);
+    let reason = if lifetime < Duration::from_secs(10 * 60) {
+        "must be at least 10m"
+    } else if config.external.is_some() {
+        "external services have no pods to replace"
+    } else if max_surge == 0 {
+        "pods are replaced new ones first, which needs rolling_update_config.max_surge of at least 1"
+    } else {
+        return Ok(());
+    };
+    Err(ConfigValidationError::InvalidService(
+        config.name.clone(),
+        format!("max_pod_lifetime: {}", reason),
+    ))
+}
+
+/// Check scale-to-zero settings, which need a service that may run no pods
+pub fn validate_scale_to_zero(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let Some(scale_to_zero) = &config.scale_to_zero else {
+        return Ok(());
+    };
+    let invalid = |reason: String| {
+        ConfigValidationError::InvalidService(
+            config.name.clone(),
+            format!("scale_to_zero: {}", reason),
+        )
+    };
+    scale_to_zero
+        .validate()
+        .map_err(|e| invalid(e.to_string()))?;
+    if config.external.is_some() {
+        return Err(invalid("external services run no pods".to_string()));
+    }
+    if config.instance_count.min != 0 {
+        return Err(invalid("instance_count.min must be 0".to_string()));
+    }
+    if config.instance_count.max == 0 {
+        return Err(invalid("instance_count.max must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+/// Check that degraded backends keep part of their load balancer weight
+pub fn validate_load_balancing(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    if let Some(weight) = config.degraded_weight {
+        if !(weight > 0.0 && weight <= 1.0) {
+            return Err(ConfigValidationError::InvalidService(
+                config.name.clone(),
+                format!(
+                    "degraded_weight must be above 0 and at most 1, got {}",
+                    weight
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check how the proxy connects to the backends
+pub fn validate_upstream(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    if let Some(upstream) = &config.upstream {
+        upstream.validate().map_err(|e| {
+            ConfigValidationError::InvalidService(config.name.clone(), format!("upstream: {}", e))
+        })?;
+    }
+    Ok(())
+}
+
+/// Check where the pod identity token goes
+pub fn validate_identity(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let Some(identity) = &config.identity else {
+        return Ok(());
+    };
+    identity.validate().map_err(|e| {
+        ConfigValidationError::InvalidService(config.name.clone(), format!("identity: {}", e))
+    })?;
+    let secrets = config.secrets.iter().chain(
+        config
+            .spec
+            .containers
+            .iter()
+            .flat_map(|c| c.secrets.iter().flatten()),
+    );
+    for secret in secrets {
+        if secret.name == identity::SECRET_NAME {
+            return Err(ConfigValidationError::InvalidSecret(
+                secret.name.clone(),
+                config.name.clone(),
+                "The name is taken by the pod identity token".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check the proxy's limits on request sizes
+pub fn validate_request_limits(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    if let Some(limits) = &config.request_limits {
+        limits.validate().map_err(|e| {
+            ConfigValidationError::InvalidService(
+                config.name.clone(),
+                format!("request_limits: {}", e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Check that group names are DNS labels, like service names
+pub fn validate_groups(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let name_regex = regex::Regex::new(r"^[a-z0-9][a-z0-9-]{0,61}[a-z0-9]$").unwrap();
+    for group in &config.groups {
+        if !name_regex.is_match(group) {
+            return Err(ConfigValidationError::InvalidService(
+                config.name.clone(),
+                format!("groups: '{}' must be a valid DNS label", group),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check read-only root filesystems, tmpfs mounts and user namespace modes
+/// against the container's volume mounts
+pub fn validate_container_filesystem(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    for container in &config.spec.containers {
+        let invalid = |option: &str, reason: String| {
+            ConfigValidationError::InvalidContainerOption(
+                container.name.clone(),
+                option.to_string(),
+                reason,
+            )
+        };
+        let mounts = container.volume_mounts.iter().flatten();
+
+        if let Some(mode) = &container.userns_mode {
+            validate_userns_mode(mode).map_err(|e| invalid("userns_mode", e.to_string()))?;
+        }
+
+        let mut tmpfs_paths = HashSet::new();
+        for entry in container.tmpfs.iter().flatten() {
+            let (path, _) = parse_tmpfs(entry).map_err(|e| invalid("tmpfs", e.to_string()))?;
+            let path = path.trim_end_matches('/');
+            if !tmpfs_paths.insert(path) {
+                return Err(invalid("tmpfs", format!("'{}' is mounted twice", path)));
+            }
+            if mounts
+                .clone()
+                .any(|mount| mount.mount_path.trim_end_matches('/') == path)
+            {
+                return Err(invalid(
+                    "tmpfs",
+                    format!("'{}' is also the mount path of a volume", path),
+                ));
+            }
+        }
+
+        if container.read_only_root_fs != Some(true) {
+            continue;
+        }
+        for mount in mounts {
+            if mount.mount_path.trim_end_matches('/').is_empty() {
+                return Err(invalid(
+                    "read_only_root_fs",
+                    format!("volume '{}' cannot be mounted on /", mount.name),
+                ));
+            }
+            // Only writable mounts can be written to, so they must exist
+            let defined = config
+                .volumes
+                .as_ref()
+                .is_some_and(|volumes| volumes.contains_key(&mount.name));
+            if mount.read_only == Some(false) && !defined {
+                return Err(invalid(
+                    "read_only_root_fs",
+                    format!(
+                        "writable volume mount '{}' at {} has no volume definition",
+                        mount.name, mount.mount_path
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check volume claims, and that the service's mounts respect their access modes
+pub fn validate_volume_claims(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    let invalid =
+        |reason: String| ConfigValidationError::InvalidService(config.name.clone(), reason);
+    let mut modes = HashMap::new();
+    for (volume, data) in config.volumes.iter().flatten() {
+        let Some(claim) = &data.claim else {
+            continue;
+        };
+        claim
+            .validate()
+            .map_err(|e| invalid(format!("volume '{}': {}", volume, e)))?;
+        if data.host_path.is_some() || data.files.is_some() || data.named_volume.is_some() {
+            return Err(invalid(format!(
+                "volume '{}' can't combine a claim with host_path, files or named_volume",
+                volume
+            )));
+        }
+        if *modes.entry(&claim.name).or_insert(claim.access_mode) != claim.access_mode {
+            return Err(invalid(format!(
+                "claim '{}' is used with different access modes",
+                claim.name
+            )));
+        }
+    }
+
+    for container in &config.spec.containers {
+        for mount in container.volume_mounts.iter().flatten() {
+            let claim = config
+                .volumes
+                .as_ref()
+                .and_then(|volumes| volumes.get(&mount.name))
+                .and_then(|data| data.claim.as_ref());
+            if let Some(claim) = claim {
+                if claim.access_mode == AccessMode::ReadOnlyMany && mount.read_only == Some(false) {
+                    return Err(ConfigValidationError::InvalidContainerOption(
+                        container.name.clone(),
+                        "volume_mounts".to_string(),
+                        format!(
+                            "claim '{}' is ReadOnlyMany and can't be mounted read-write",
+                            claim.name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_secret(
+    secret: &SecretConfig,
+    config: &ServiceConfig,
+) -> Result<(), ConfigValidationError> {
+    let invalid = |reason: &str| {
+        ConfigValidationError::InvalidSecret(
+            secret.name.clone(),
+            config.name.clone(),
+            reason.to_string(),
+        )
+    };
+
+    // Names become file names on the host
+    let name_regex = regex::Regex::new(r"^[A-Za-z0-9][A-Za-z0-9._-]{0,127}$").unwrap();
+    if !name_regex.is_match(&secret.name) {
+        return Err(invalid(
+            "Secret names must contain only letters, digits, '.', '_' or '-', starting with a letter or digit",
+        ));
+    }
+    if secret.env.is_none() && secret.mount.is_none() {
+        return Err(invalid("Secret must set env, mount or both"));
+    }
+    if secret
+        .env
+        .as_ref()
+        .is_some_and(|env| validate_env_key(env, &secret.name).is_err())
+    {
+        return Err(invalid(
+            "Secret env must contain only letters, digits and '_', and must not start with a digit",
+        ));
+    }
+    if secret
+        .mount
+        .as_ref()
+        .is_some_and(|mount| !is_absolute_mount(mount))
+    {
+        return Err(invalid("Secret mount must be an absolute file path"));
+    }
+    if matches!(&secret.source, SecretSource::Command(command) if command.is_empty()) {
+        return Err(invalid("from_command must not be empty"));
+    }
+    Ok(())
+}
+
+// Check secret definitions; values are only resolved when containers start
+pub fn validate_secrets(config: &ServiceConfig) -> Result<(), ConfigValidationError> {
+    for secret in config.secrets.iter().chain(
+        config
+            .spec
+            .containers
+            .iter()
+            .flat_map(|c| c.secrets.iter().flatten()),
+    ) {
+        validate_secret(secret, config)?;
+    }
+
+    let scopes = std::iter::once(&config.secrets).chain(
+        config
+            .spec
+            .containers
+            .iter()
+            .filter_map(|c| c.secrets.as_ref()),
+    );
+    for secrets in scopes {
+        let mut names = HashSet::new();
+        if let Some(secret) = secrets.iter().find(|s| !names.insert(&s.name)) {
+            return Err(ConfigValidationError::InvalidSecret(
+                secret.name.clone(),
+                config.name.clone(),
+                "Secret is defined twice".to_string(),
+            ));
+        }
+    }
+
+    for (index, secret) in config.image_pull_secrets.iter().enumerate() {
+        secret.validate().map_err(|e| {
+            ConfigValidationError::InvalidSecret(
+                format!("image_pull_secrets[{}]", index),
+                config.name.clone(),
+                e.to_string(),
+            )
+        })?;
+    }
+
+    for container in &config.spec.containers {
+        let mut mounts = HashSet::new();
+        for secret in secrets_for(config, container) {
+            if let Some(mount) = &secret.mount {
+                if !mounts.insert(mount) {
+                    return Err(ConfigValidationError::InvalidSecret(
+                        secret.name.clone(),
+                        config.name.clone(),
+                        format!(
+                            "Mount {} is used by another secret of container '{}'",
+                            mount.display(),
+                            container.name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 // Add this function to check for duplicate service names
 pub async fn check_service_name_uniqueness(
     config: &ServiceConfig,
@@ -0,0 +1,524 @@
+// src/container/anomaly.rs
+//
+// Anomaly detection over container stats. Each container keeps exponentially
+// weighted moving averages of its CPU, memory and proxied request error rate.
+// Every detection round, a container is compared with its siblings, the same
+// container in the service's other pods: one whose average lies more than
+// `threshold` standard deviations from theirs is reported, once, until it
+// falls back in line. Reports are logged and recorded as `pod_anomaly` changes
+// in the instance change journal, so a bad pod shows up before users notice.
+use anyhow::{anyhow, Result};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::changes::{record_change, InstanceEvent};
+use super::{INSTANCE_STORE, SERVICE_STATS};
+use crate::config::{utils::parse_container_name, CONFIG_STORE};
+
+static DETECTOR: OnceLock<Mutex<Detector>> = OnceLock::new();
+
+const DETECTION_INTERVAL: Duration = Duration::from_secs(15);
+// Rounds a container must have been sampled before it is judged
+const MIN_SAMPLES: u32 = 4;
+// Pods needed for the siblings' spread to mean anything
+const MIN_PODS: usize = 3;
+// Siblings that all look alike still get some slack, relative to their mean
+const MIN_RELATIVE_SPREAD: f64 = 0.1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnomalyDetectionConfig {
+    /// Standard deviations from the siblings' mean that count as anomalous
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+    /// Weight of each new sample in the moving averages
+    #[serde(default = "default_smoothing")]
+    pub smoothing: f64,
+}
+
+fn default_threshold() -> f64 {
+    3.0
+}
+
+fn default_smoothing() -> f64 {
+    0.3
+}
+
+impl AnomalyDetectionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.threshold.is_nan() || self.threshold <= 0.0 {
+            return Err(anyhow!(
+                "threshold must be positive, got {}",
+                self.threshold
+            ));
+        }
+        if !(self.smoothing > 0.0 && self.smoothing <= 1.0) {
+            return Err(anyhow!(
+                "smoothing must be above 0 and at most 1, got {}",
+                self.smoothing
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyMetric {
+    Cpu,
+    Memory,
+    ErrorRate,
+}
+
+impl AnomalyMetric {
+    const ALL: [AnomalyMetric; 3] = [
+        AnomalyMetric::Cpu,
+        AnomalyMetric::Memory,
+        AnomalyMetric::ErrorRate,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyMetric::Cpu => "cpu",
+            AnomalyMetric::Memory => "memory",
+            AnomalyMetric::ErrorRate => "error_rate",
+        }
+    }
+
+    // Smallest spread a deviation is measured against: a percent of CPU,
+    // 16MiB of memory or a percent of requests
+    fn min_spread(&self) -> f64 {
+        match self {
+            AnomalyMetric::Cpu => 1.0,
+            AnomalyMetric::Memory => (16 * 1024 * 1024) as f64,
+            AnomalyMetric::ErrorRate => 0.01,
+        }
+    }
+
+    // Failing fewer requests than the siblings is no problem
+    fn is_anomalous(&self, z_score: f64, threshold: f64) -> bool {
+        match self {
+            AnomalyMetric::ErrorRate => z_score > threshold,
+            _ => z_score.abs() > threshold,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Ewma {
+    value: f64,
+    samples: u32,
+}
+
+impl Ewma {
+    fn update(&mut self, sample: f64, smoothing: f64) {
+        if self.samples == 0 {
+            self.value = sample;
+        } else {
+            self.value += smoothing * (sample - self.value);
+        }
+        self.samples += 1;
+    }
+
+    fn current(&self) -> Option<f64> {
+        (self.samples >= MIN_SAMPLES).then_some(self.value)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Baseline {
+    cpu: Ewma,
+    memory: Ewma,
+    error_rate: Ewma,
+    // Metrics already reported, so a container is reported once per anomaly
+    reported: FxHashSet<AnomalyMetric>,
+}
+
+impl Baseline {
+    fn metric(&self, metric: AnomalyMetric) -> &Ewma {
+        match metric {
+            AnomalyMetric::Cpu => &self.cpu,
+            AnomalyMetric::Memory => &self.memory,
+            AnomalyMetric::ErrorRate => &self.error_rate,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RequestCounts {
+    total: u64,
+    failed: u64,
+}
+
+#[derive(Debug, Default)]
+struct Detector {
+    baselines: FxHashMap<String, Baseline>,
+    requests: FxHashMap<String, RequestCounts>,
+}
+
+fn detector() -> &'static Mutex<Detector> {
+    DETECTOR.get_or_init(|| Mutex::new(Detector::default()))
+}
+
+/// Count a request proxied to a container, towards its error rate
+pub fn record_request(container_name: &str, failed: bool) {
+    let mut detector = detector().lock().unwrap();
+    let counts = detector
+        .requests
+        .entry(container_name.to_string())
+        .or_default();
+    counts.total += 1;
+    if failed {
+        counts.failed += 1;
+    }
+}
+
+struct Sample {
+    container_name: String,
+    cpu_percentage: f64,
+    memory_usage: u64,
+}
+
+/// A container whose moving average of `metric` deviates from its siblings'
+#[derive(Debug, Serialize, Clone)]
+pub struct Anomaly {
+    pub uuid: Uuid,
+    pub container: String,
+    pub metric: AnomalyMetric,
+    pub value: f64,
+    pub siblings_mean: f64,
+    pub z_score: f64,
+}
+
+/// Compare every container of the services with `anomaly_detection` to its
+/// siblings, every 15 seconds
+pub fn start_anomaly_detection() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(DETECTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            detect().await;
+        }
+    });
+}
+
+async fn detect() {
+    let (Some(configs), Some(service_stats)) = (CONFIG_STORE.get(), SERVICE_STATS.get()) else {
+        return;
+    };
+    let services: Vec<(String, AnomalyDetectionConfig)> = configs
+        .read()
+        .await
+        .values()
+        .filter_map(|(_, config)| {
+            config
+                .anomaly_detection
+                .clone()
+                .map(|detection| (config.name.clone(), detection))
+        })
+        .collect();
+
+    let samples: Vec<(String, AnomalyDetectionConfig, Vec<Sample>)> = {
+        let stats = service_stats.read().await;
+        services
+            .into_iter()
+            .map(|(service_name, detection)| {
+                let samples = stats
+                    .get(&service_name)
+                    .map(|service| {
+                        service
+                            .container_stats
+                            .iter()
+                            .map(|(container_name, stats)| Sample {
+                                container_name: container_name.clone(),
+                                cpu_percentage: stats.cpu_percentage,
+                                memory_usage: stats.memory_usage,
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (service_name, detection, samples)
+            })
+            .collect()
+    };
+
+    let Findings { anomalies, cleared } = {
+        let mut detector = detector().lock().unwrap();
+        detector.update(&samples)
+    };
+
+    let log = slog_scope::logger();
+    for (service_name, container_name, metric) in cleared {
+        slog::info!(log, "Container back in line with its siblings";
+            "service" => service_name,
+            "container" => container_name,
+            "metric" => metric.as_str()
+        );
+    }
+    if anomalies.is_empty() {
+        return;
+    }
+    for (service_name, anomaly) in &anomalies {
+        slog::warn!(log, "Container deviates from its siblings";
+            "service" => service_name,
+            "container" => &anomaly.container,
+            "metric" => anomaly.metric.as_str(),
+            "value" => anomaly.value,
+            "siblings_mean" => anomaly.siblings_mean,
+            "z_score" => anomaly.z_score
+        );
+    }
+    if let Some(instance_store) = INSTANCE_STORE.get() {
+        // Recorded under the store's lock like every other change
+        let _store = instance_store.write().await;
+        for (service_name, anomaly) in anomalies {
+            record_change(&service_name, InstanceEvent::PodAnomaly(anomaly));
+        }
+    }
+}
+
+// What a detection round found: new anomalies, and the service, container and
+// metric of those that cleared
+#[derive(Default)]
+struct Findings {
+    anomalies: Vec<(String, Anomaly)>,
+    cleared: Vec<(String, String, AnomalyMetric)>,
+}
+
+impl Detector {
+    // Feed the round's samples into the moving averages, then judge every
+    // container
+    fn update(&mut self, services: &[(String, AnomalyDetectionConfig, Vec<Sample>)]) -> Findings {
+        let sampled: FxHashSet<&str> = services
+            .iter()
+            .flat_map(|(_, _, samples)| samples.iter())
+            .map(|sample| sample.container_name.as_str())
+            .collect();
+        self.baselines
+            .retain(|name, _| sampled.contains(name.as_str()));
+        let requests = std::mem::take(&mut self.requests);
+
+        let mut findings = Findings::default();
+        for (service_name, detection, samples) in services {
+            // Siblings are the containers with the same name in the spec
+            let mut groups: FxHashMap<String, Vec<(&str, Uuid)>> = FxHashMap::default();
+            for sample in samples {
+                let Ok(parts) = parse_container_name(&sample.container_name) else {
+                    continue;
+                };
+                let baseline = self
+                    .baselines
+                    .entry(sample.container_name.clone())
+                    .or_default();
+                baseline
+                    .cpu
+                    .update(sample.cpu_percentage, detection.smoothing);
+                baseline
+                    .memory
+                    .update(sample.memory_usage as f64, detection.smoothing);
+                // Containers that served no requests keep their error rate
+                if let Some(counts) = requests.get(&sample.container_name) {
+                    if counts.total > 0 {
+                        baseline.error_rate.update(
+                            counts.failed as f64 / counts.total as f64,
+                            detection.smoothing,
+                        );
+                    }
+                }
+                groups
+                    .entry(parts.container_name)
+                    .or_default()
+                    .push((sample.container_name.as_str(), parts.uuid));
+            }
+
+            for siblings in groups.values() {
+                for metric in AnomalyMetric::ALL {
+                    self.judge(service_name, detection, siblings, metric, &mut findings);
+                }
+            }
+        }
+        findings
+    }
+
+    fn judge(
+        &mut self,
+        service_name: &str,
+        detection: &AnomalyDetectionConfig,
+        siblings: &[(&str, Uuid)],
+        metric: AnomalyMetric,
+        findings: &mut Findings,
+    ) {
+        let values: Vec<(&str, Uuid, f64)> = siblings
+            .iter()
+            .filter_map(|(name, uuid)| {
+                let value = self.baselines.get(*name)?.metric(metric).current()?;
+                Some((*name, *uuid, value))
+            })
+            .collect();
+        if values.len() < MIN_PODS {
+            return;
+        }
+
+        for (index, (name, uuid, value)) in values.iter().enumerate() {
+            // Each container is measured against the others, so an outlier
+            // doesn't widen the spread it is judged by
+            let others: Vec<f64> = values
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != index)
+                .map(|(_, (_, _, value))| *value)
+                .collect();
+            let mean = others.iter().sum::<f64>() / others.len() as f64;
+            let variance = others
+                .iter()
+                .map(|other| (other - mean).powi(2))
+                .sum::<f64>()
+                / others.len() as f64;
+            let spread = variance
+                .sqrt()
+                .max(mean.abs() * MIN_RELATIVE_SPREAD)
+                .max(metric.min_spread());
+            let z_score = (value - mean) / spread;
+
+            let Some(baseline) = self.baselines.get_mut(*name) else {
+                continue;
+            };
+            if metric.is_anomalous(z_score, detection.threshold) {
+                if baseline.reported.insert(metric) {
+                    findings.anomalies.push((
+                        service_name.to_string(),
+                        Anomaly {
+                            uuid: *uuid,
+                            container: name.to_string(),
+                            metric,
+                            value: *value,
+                            siblings_mean: mean,
+                            z_score,
+                        },
+                    ));
+                }
+            } else if baseline.reported.remove(&metric) {
+                findings
+                    .cleared
+                    .push((service_name.to_string(), name.to_string(), metric));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PODS: [&str; 4] = [
+        "00000000-0000-0000-0000-000000000001",
+        "00000000-0000-0000-0000-000000000002",
+        "00000000-0000-0000-0000-000000000003",
+        "00000000-0000-0000-0000-000000000004",
+    ];
+
+    fn round(cpu: [f64; 4]) -> Vec<(String, AnomalyDetectionConfig, Vec<Sample>)> {
+        let samples = PODS
+            .iter()
+            .zip(cpu)
+            .enumerate()
+            .map(|(index, (uuid, cpu_percentage))| Sample {
+                container_name: format!("web__{}__app__{}", index + 1, uuid),
+                cpu_percentage,
+                memory_usage: 64 * 1024 * 1024,
+            })
+            .collect();
+        let detection = AnomalyDetectionConfig {
+            threshold: 3.0,
+            smoothing: 1.0,
+        };
+        vec![("web".to_string(), detection, samples)]
+    }
+
+    #[test]
+    fn test_update_reports_an_outlier_once() {
+        let mut detector = Detector::default();
+        for _ in 1..MIN_SAMPLES {
+            let findings = detector.update(&round([10.0, 11.0, 9.0, 80.0]));
+            assert!(findings.anomalies.is_empty());
+        }
+
+        let findings = detector.update(&round([10.0, 11.0, 9.0, 80.0]));
+        assert_eq!(findings.anomalies.len(), 1);
+        let (service_name, anomaly) = &findings.anomalies[0];
+        assert_eq!(service_name, "web");
+        assert_eq!(anomaly.uuid.to_string(), PODS[3]);
+        assert_eq!(anomaly.metric, AnomalyMetric::Cpu);
+        assert_eq!(anomaly.siblings_mean, 10.0);
+
+        let findings = detector.update(&round([10.0, 11.0, 9.0, 80.0]));
+        assert!(findings.anomalies.is_empty());
+
+        let findings = detector.update(&round([10.0, 11.0, 9.0, 10.0]));
+        assert!(findings.anomalies.is_empty());
+        assert_eq!(findings.cleared.len(), 1);
+        assert_eq!(findings.cleared[0].2, AnomalyMetric::Cpu);
+    }
+
+    #[test]
+    fn test_judge_error_rate_only_flags_more_failures() {
+        let mut detector = Detector::default();
+        let names: Vec<String> = PODS
+            .iter()
+            .enumerate()
+            .map(|(index, uuid)| format!("web__{}__app__{}", index + 1, uuid))
+            .collect();
+        for (name, error_rate) in names.iter().zip([0.1, 0.1, 0.1, 0.0]) {
+            let baseline = detector.baselines.entry(name.clone()).or_default();
+            for _ in 0..MIN_SAMPLES {
+                baseline.error_rate.update(error_rate, 1.0);
+            }
+        }
+        let siblings: Vec<(&str, Uuid)> = names
+            .iter()
+            .zip(PODS)
+            .map(|(name, uuid)| (name.as_str(), uuid.parse().unwrap()))
+            .collect();
+        let detection = AnomalyDetectionConfig {
+            threshold: 3.0,
+            smoothing: 1.0,
+        };
+
+        let mut findings = Findings::default();
+        detector.judge(
+            "web",
+            &detection,
+            &siblings,
+            AnomalyMetric::ErrorRate,
+            &mut findings,
+        );
+        assert!(findings.anomalies.is_empty());
+
+        // Too few pods with enough samples to judge
+        let mut findings = Findings::default();
+        detector.judge(
+            "web",
+            &detection,
+            &siblings[..2],
+            AnomalyMetric::ErrorRate,
+            &mut findings,
+        );
+        assert!(findings.anomalies.is_empty());
+
+        let baseline = detector.baselines.get_mut(&names[3]).unwrap();
+        for _ in 0..MIN_SAMPLES {
+            baseline.error_rate.update(0.9, 1.0);
+        }
+        detector.judge(
+            "web",
+            &detection,
+            &siblings,
+            AnomalyMetric::ErrorRate,
+            &mut findings,
+        );
+        assert_eq!(findings.anomalies.len(), 1);
+        assert_eq!(findings.anomalies[0].1.container, names[3]);
+    }
+}
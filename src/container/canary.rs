@@ -0,0 +1,316 @@
+// src/container/canary.rs
+//
+// Canary image updates. With `rolling_update_config.strategy: canary`, an
+// image update first starts a few pods with the new image next to the old
+// ones. Once they are healthy, the proxy sends them a set percentage of the
+// service's traffic, and counts their failed requests and response times.
+// After the analysis period the canary is promoted, and the old pods are
+// replaced as in any rolling update, unless its error rate or latency went
+// over the thresholds first, in which case the canary pods are removed and
+// the old ones keep all the traffic.
+use anyhow::{anyhow, Result};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use super::health::{self, HealthState};
+use super::scaling::latency::{Histogram, LatencyPercentile};
+
+static CANARIES: OnceLock<Mutex<FxHashMap<String, Canary>>> = OnceLock::new();
+
+// How often the canary's requests are checked against the thresholds
+const ANALYSIS_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStrategy {
+    /// Replace the pods once the new ones are healthy
+    #[default]
+    Rolling,
+    /// Send part of the traffic to a few new pods first, and only replace
+    /// the rest if they do well
+    Canary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    /// Pods started with the new image
+    #[serde(default = "default_pods")]
+    pub pods: u8,
+    /// Share of the service's requests sent to them, in percent
+    #[serde(default = "default_traffic_percentage")]
+    pub traffic_percentage: u8,
+    /// How long they take traffic before they are promoted
+    #[serde(with = "humantime_serde", default = "default_analysis")]
+    pub analysis: Duration,
+    /// Share of their requests that may fail, in percent
+    #[serde(default = "default_max_error_percentage")]
+    pub max_error_percentage: f64,
+    /// Response time the latency percentile must stay under
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_latency: Option<Duration>,
+    /// "p95" or "p99"
+    #[serde(default)]
+    pub latency_percentile: LatencyPercentile,
+    /// Requests they must have served before the thresholds apply
+    #[serde(default = "default_min_requests")]
+    pub min_requests: u64,
+}
+
+fn default_pods() -> u8 {
+    1
+}
+
+fn default_traffic_percentage() -> u8 {
+    10
+}
+
+fn default_analysis() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_max_error_percentage() -> f64 {
+    5.0
+}
+
+fn default_min_requests() -> u64 {
+    20
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            pods: default_pods(),
+            traffic_percentage: default_traffic_percentage(),
+            analysis: default_analysis(),
+            max_error_percentage: default_max_error_percentage(),
+            max_latency: None,
+            latency_percentile: LatencyPercentile::default(),
+            min_requests: default_min_requests(),
+        }
+    }
+}
+
+impl CanaryConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.pods == 0 {
+            return Err(anyhow!("pods must be at least 1"));
+        }
+        if !(1..=99).contains(&self.traffic_percentage) {
+            return Err(anyhow!("traffic_percentage must be between 1 and 99"));
+        }
+        if self.analysis < ANALYSIS_INTERVAL {
+            return Err(anyhow!("analysis must be at least {:?}", ANALYSIS_INTERVAL));
+        }
+        if !(0.0..=100.0).contains(&self.max_error_percentage) {
+            return Err(anyhow!("max_error_percentage must be between 0 and 100"));
+        }
+        if self.max_latency.is_some_and(|latency| latency.is_zero()) {
+            return Err(anyhow!("max_latency must be positive"));
+        }
+        if self.min_requests == 0 {
+            return Err(anyhow!("min_requests must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+// A canary being analyzed, and what its pods have served so far
+struct Canary {
+    pods: FxHashSet<Uuid>,
+    traffic_percentage: u8,
+    started: SystemTime,
+    requests: u64,
+    failed: u64,
+    latencies: Histogram,
+}
+
+/// A canary in progress, as the canary API shows it
+#[derive(Debug, Serialize)]
+pub struct CanaryStatus {
+    pub service: String,
+    pub pods: Vec<Uuid>,
+    pub traffic_percentage: u8,
+    #[serde(with = "humantime_serde")]
+    pub started: SystemTime,
+    pub requests: u64,
+    pub failed: u64,
+    #[serde(with = "humantime_serde")]
+    pub p95: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    pub p99: Option<Duration>,
+}
+
+/// Removes the canary's traffic split when dropped, once it is promoted or
+/// its pods are rolled back
+pub struct CanaryGuard {
+    service_name: String,
+}
+
+impl Drop for CanaryGuard {
+    fn drop(&mut self) {
+        canaries().lock().unwrap().remove(&self.service_name);
+    }
+}
+
+fn canaries() -> &'static Mutex<FxHashMap<String, Canary>> {
+    CANARIES.get_or_init(|| Mutex::new(FxHashMap::default()))
+}
+
+/// Mark the pods as the service's canary, before they take any traffic
+pub fn start(service_name: &str, config: &CanaryConfig, pods: &[Uuid]) -> CanaryGuard {
+    canaries().lock().unwrap().insert(
+        service_name.to_string(),
+        Canary {
+            pods: pods.iter().copied().collect(),
+            traffic_percentage: config.traffic_percentage,
+            started: SystemTime::now(),
+            requests: 0,
+            failed: 0,
+            latencies: Histogram::default(),
+        },
+    );
+    CanaryGuard {
+        service_name: service_name.to_string(),
+    }
+}
+
+/// The service's canary pods and the percentage of its traffic they get,
+/// while a canary is running
+pub fn traffic_split(service_name: &str) -> Option<(FxHashSet<Uuid>, u8)> {
+    canaries()
+        .lock()
+        .unwrap()
+        .get(service_name)
+        .map(|canary| (canary.pods.clone(), canary.traffic_percentage))
+}
+
+/// Count a request the proxy sent to a pod, if it is a canary pod
+pub fn record_request(service_name: &str, pod: Uuid, failed: bool, latency: Duration) {
+    let mut canaries = canaries().lock().unwrap();
+    let Some(canary) = canaries
+        .get_mut(service_name)
+        .filter(|canary| canary.pods.contains(&pod))
+    else {
+        return;
+    };
+    canary.requests += 1;
+    if failed {
+        canary.failed += 1;
+    }
+    canary.latencies.record(latency);
+}
+
+/// Every canary in progress
+pub fn canaries_status() -> Vec<CanaryStatus> {
+    let mut statuses: Vec<CanaryStatus> = canaries()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(service_name, canary)| {
+            let latency = |percentile| {
+                (canary.latencies.samples() > 0).then(|| canary.latencies.percentile(percentile))
+            };
+            CanaryStatus {
+                service: service_name.clone(),
+                pods: canary.pods.iter().copied().collect(),
+                traffic_percentage: canary.traffic_percentage,
+                started: canary.started,
+                requests: canary.requests,
+                failed: canary.failed,
+                p95: latency(LatencyPercentile::P95),
+                p99: latency(LatencyPercentile::P99),
+            }
+        })
+        .collect();
+    statuses.sort_by(|a, b| a.service.cmp(&b.service));
+    statuses
+}
+
+/// Watch the canary for its analysis period. Fails as soon as one of its
+/// containers has failed, or its error rate or latency is over the
+/// thresholds once it has served `min_requests`.
+pub async fn analyze(service_name: &str, config: &CanaryConfig, containers: &[&str]) -> Result<()> {
+    slog::info!(slog_scope::logger(), "Analyzing canary";
+        "service" => service_name,
+        "traffic_percentage" => config.traffic_percentage,
+        "analysis" => format!("{:?}", config.analysis)
+    );
+
+    let checks = config
+        .analysis
+        .as_secs()
+        .div_ceil(ANALYSIS_INTERVAL.as_secs());
+    for _ in 0..checks {
+        tokio::time::sleep(ANALYSIS_INTERVAL).await;
+        for name in containers {
+            if let Some(status) = health::get_container_health(name).await {
+                if matches!(status.state, HealthState::Failed) {
+                    return Err(anyhow!(
+                        "{} failed: {}",
+                        name,
+                        status.message.unwrap_or_default()
+                    ));
+                }
+            }
+        }
+        check_thresholds(service_name, config)?;
+    }
+
+    let requests = canaries()
+        .lock()
+        .unwrap()
+        .get(service_name)
+        .map_or(0, |canary| canary.requests);
+    if requests < config.min_requests {
+        slog::warn!(slog_scope::logger(), "Canary served too few requests to judge, promoting it on health alone";
+            "service" => service_name,
+            "requests" => requests,
+            "min_requests" => config.min_requests
+        );
+    } else {
+        slog::info!(slog_scope::logger(), "Canary passed, promoting it";
+            "service" => service_name,
+            "requests" => requests
+        );
+    }
+    Ok(())
+}
+
+fn check_thresholds(service_name: &str, config: &CanaryConfig) -> Result<()> {
+    let canaries = canaries().lock().unwrap();
+    let Some(canary) = canaries.get(service_name) else {
+        return Ok(());
+    };
+    if canary.requests < config.min_requests {
+        return Ok(());
+    }
+
+    let error_percentage = canary.failed as f64 / canary.requests as f64 * 100.0;
+    if error_percentage > config.max_error_percentage {
+        return Err(anyhow!(
+            "{:.1}% of {} requests failed, over {}%",
+            error_percentage,
+            canary.requests,
+            config.max_error_percentage
+        ));
+    }
+    if let Some(max_latency) = config.max_latency {
+        let latency = canary.latencies.percentile(config.latency_percentile);
+        if latency > max_latency {
+            return Err(anyhow!(
+                "{:?} latency of {:?} over {:?}",
+                config.latency_percentile,
+                latency,
+                max_latency
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+// src/container/changes.rs
+//
+// Versioned journal of instance store changes. Every write to INSTANCE_STORE
+// records an event here while still holding the store's write lock, so the
+// version read alongside a snapshot always matches its contents and clients
+// can catch up with deltas instead of refetching everything.
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use uuid::Uuid;
+
+use super::anomaly::Anomaly;
+use super::rollback::RolledBackUpdate;
+use super::rolling_update::InPlaceUpdate;
+use super::InstanceMetadata;
+
+pub static INSTANCE_CHANGES: OnceLock<Mutex<ChangeLog>> = OnceLock::new();
+
+// Older changes are dropped; clients further behind must refetch a snapshot
+const MAX_RETAINED_CHANGES: usize = 1024;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InstanceEvent {
+    PodAdded { instance: InstanceMetadata },
+    PodUpdated { instance: InstanceMetadata },
+    PodRemoved { uuid: Uuid },
+    ServiceRemoved,
+    ServiceExpiring { expires_at: String },
+    PodAnomaly(Anomaly),
+    UpdateRolledBack(RolledBackUpdate),
+    UpdateInPlace(InPlaceUpdate),
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct InstanceChange {
+    pub version: u64,
+    pub service_name: String,
+    #[serde(flatten)]
+    pub event: InstanceEvent,
+}
+
+#[derive(Debug, Default)]
+pub struct ChangeLog {
+    version: u64,
+    changes: VecDeque<InstanceChange>,
+}
+
+impl ChangeLog {
+    fn record(&mut self, service_name: &str, event: InstanceEvent) {
+        self.version += 1;
+        self.changes.push_back(InstanceChange {
+            version: self.version,
+            service_name: service_name.to_string(),
+            event,
+        });
+        if self.changes.len() > MAX_RETAINED_CHANGES {
+            self.changes.pop_front();
+        }
+    }
+
+    fn since(&self, since: u64) -> Option<(u64, Vec<InstanceChange>)> {
+        if since > self.version {
+            return None;
+        }
+        let oldest_retained = self.changes.front().map_or(self.version + 1, |c| c.version);
+        if since + 1 < oldest_retained {
+            return None;
+        }
+
+        let changes = self
+            .changes
+            .iter()
+            .filter(|change| change.version > since)
+            .cloned()
+            .collect();
+        Some((self.version, changes))
+    }
+}
+
+fn change_log() -> &'static Mutex<ChangeLog> {
+    INSTANCE_CHANGES.get_or_init(|| Mutex::new(ChangeLog::default()))
+}
+
+/// Record a change to the instance store. Call with the store's write lock held.
+pub fn record_change(service_name: &str, event: InstanceEvent) {
+    change_log().lock().unwrap().record(service_name, event);
+}
+
+/// Version of the latest recorded change. Read with the store's lock held to
+/// tag a snapshot.
+pub fn current_version() -> u64 {
+    change_log().lock().unwrap().version
+}
+
+/// Every retained change, oldest first
+pub fn recent_changes() -> Vec<InstanceChange> {
+    change_log()
+        .lock()
+        .unwrap()
+        .changes
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Changes after `since`, or None when some of them are no longer retained.
+pub fn changes_since(since: u64) -> Option<(u64, Vec<InstanceChange>)> {
+    change_log().lock().unwrap().since(since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(changes: &[InstanceChange]) -> Vec<u64> {
+        changes.iter().map(|change| change.version).collect()
+    }
+
+    #[test]
+    fn test_versions_increase() {
+        let mut log = ChangeLog::default();
+        let (version, changes) = log.since(0).unwrap();
+        assert_eq!(version, 0);
+        assert!(changes.is_empty());
+
+        log.record("web", InstanceEvent::PodRemoved { uuid: Uuid::nil() });
+        log.record("api", InstanceEvent::ServiceRemoved);
+        log.record("web", InstanceEvent::ServiceRemoved);
+
+        let (version, changes) = log.since(0).unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(versions(&changes), vec![1, 2, 3]);
+        assert_eq!(changes[1].service_name, "api");
+    }
+
+    #[test]
+    fn test_since_returns_newer_changes() {
+        let mut log = ChangeLog::default();
+        for _ in 0..5 {
+            log.record("web", InstanceEvent::ServiceRemoved);
+        }
+
+        let (version, changes) = log.since(3).unwrap();
+        assert_eq!(version, 5);
+        assert_eq!(versions(&changes), vec![4, 5]);
+        // Up to date
+        assert!(log.since(5).unwrap().1.is_empty());
+        // A version that was never handed out
+        assert!(log.since(6).is_none());
+    }
+
+    #[test]
+    fn test_gap_when_changes_dropped() {
+        let mut log = ChangeLog::default();
+        let total = MAX_RETAINED_CHANGES as u64 + 10;
+        for _ in 0..total {
+            log.record("web", InstanceEvent::ServiceRemoved);
+        }
+
+        // Change 11 is the oldest retained, so a client at 10 misses nothing
+        assert!(log.since(9).is_none());
+        let (version, changes) = log.since(10).unwrap();
+        assert_eq!(version, total);
+        assert_eq!(changes.len(), MAX_RETAINED_CHANGES);
+        assert_eq!(changes[0].version, 11);
+    }
+}
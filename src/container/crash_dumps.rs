@@ -0,0 +1,294 @@
+// src/container/crash_dumps.rs
+//
+// Crash dump collection. A container with `crash_dumps` writes its core dumps
+// and other crash artifacts to a directory, e.g. the one the kernel's
+// core_pattern points at. Each container gets its own host directory mounted
+// there, so the replicas of a service never mix their dumps. When it exits
+// with a non-zero code, Orbit moves the files found there into a directory of
+// their own under the service's crash dump directory, prunes old collections
+// and links the new one from the `crashed` health webhook.
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use super::health::webhooks;
+use super::Container;
+
+static CRASH_DUMPS_DIR: OnceLock<PathBuf> = OnceLock::new();
+// The directories mounted into containers, one per container
+static DUMP_MOUNTS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+const DIR_NAME: &str = "crash_dumps";
+const MOUNTS_DIR_NAME: &str = "crash_dump_mounts";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CrashDumpConfig {
+    /// Directory in the container it writes its dumps to
+    pub path: PathBuf,
+    /// Collections kept per service, the oldest removed first
+    #[serde(default = "default_retain")]
+    pub retain: usize,
+    /// Collections older than this are removed
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_age: Option<Duration>,
+}
+
+fn default_retain() -> usize {
+    5
+}
+
+impl CrashDumpConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.path.is_absolute() {
+            return Err(anyhow!("path '{}' must be absolute", self.path.display()));
+        }
+        if self.retain == 0 {
+            return Err(anyhow!("retain must be at least 1"));
+        }
+        if self.max_age.is_some_and(|age| age.is_zero()) {
+            return Err(anyhow!("max_age must be positive"));
+        }
+        Ok(())
+    }
+}
+
+pub fn initialize_crash_dumps(state_dir: &Path) -> Result<()> {
+    let dir = state_dir.join(DIR_NAME);
+    let mounts_dir = state_dir.join(MOUNTS_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    fs::create_dir_all(&mounts_dir)?;
+    CRASH_DUMPS_DIR
+        .set(dir)
+        .map_err(|_| anyhow!("Crash dumps already initialized"))?;
+    DUMP_MOUNTS_DIR
+        .set(mounts_dir)
+        .map_err(|_| anyhow!("Crash dumps already initialized"))
+}
+
+fn dump_dir(container_name: &str) -> Option<PathBuf> {
+    DUMP_MOUNTS_DIR.get().map(|dir| dir.join(container_name))
+}
+
+/// Create the container's own dump directory, returning it and the path to
+/// mount it at, if the container collects crash dumps
+pub fn prepare_dump_dir(
+    container: &Container,
+    container_name: &str,
+) -> Result<Option<(PathBuf, PathBuf)>> {
+    let (Some(config), Some(dir)) = (&container.crash_dumps, dump_dir(container_name)) else {
+        return Ok(None);
+    };
+    fs::create_dir_all(&dir)?;
+    Ok(Some((dir, config.path.clone())))
+}
+
+/// Remove a container's dump directory, once the container is removed
+pub fn remove_dump_dir(container_name: &str) {
+    let Some(dir) = dump_dir(container_name) else {
+        return;
+    };
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            slog::warn!(slog_scope::logger(), "Failed to remove crash dump directory";
+                "container" => container_name,
+                "path" => dir.display().to_string(),
+                "error" => e.to_string()
+            );
+        }
+        _ => {}
+    }
+}
+
+/// Handle a container's non-zero exit: collect its crash dumps if it has
+/// `crash_dumps`, and notify the health webhooks
+pub async fn on_crash(
+    service_name: &str,
+    container_name: &str,
+    container: &Container,
+    exit_code: i64,
+) {
+    let mut collected = None;
+    if let (Some(config), Some(root), Some(dump_dir)) = (
+        &container.crash_dumps,
+        CRASH_DUMPS_DIR.get(),
+        dump_dir(container_name),
+    ) {
+        let config = config.clone();
+        let service_dir = root.join(service_name);
+        let name = container_name.to_string();
+        let result =
+            tokio::task::spawn_blocking(move || collect(&config, &dump_dir, &service_dir, &name))
+                .await;
+
+        match result
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result)
+        {
+            Ok(Some(dir)) => {
+                slog::info!(slog_scope::logger(), "Collected crash dumps";
+                    "service" => service_name,
+                    "container" => container_name,
+                    "exit_code" => exit_code,
+                    "path" => dir.display().to_string()
+                );
+                collected = Some(dir);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                slog::error!(slog_scope::logger(), "Failed to collect crash dumps";
+                    "service" => service_name,
+                    "container" => container_name,
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+
+    webhooks::notify_crash(service_name, container_name, exit_code, collected);
+}
+
+// Move the files in the container's dump directory into a new collection.
+// Returns the collection, or None when there was nothing to collect.
+fn collect(
+    config: &CrashDumpConfig,
+    dump_dir: &Path,
+    service_dir: &Path,
+    container_name: &str,
+) -> Result<Option<PathBuf>> {
+    let files: Vec<PathBuf> = match fs::read_dir(dump_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+            .map(|entry| entry.path())
+            .collect(),
+        Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let timestamp = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    let collection = service_dir.join(format!("{}__{}", timestamp, container_name));
+    fs::create_dir_all(&collection)?;
+    for file in files {
+        let Some(file_name) = file.file_name() else {
+            continue;
+        };
+        let target = collection.join(file_name);
+        // Dumps on another filesystem can't be renamed
+        if fs::rename(&file, &target).is_err() {
+            fs::copy(&file, &target)?;
+            fs::remove_file(&file)?;
+        }
+    }
+
+    prune(service_dir, config.retain, config.max_age)?;
+    Ok(Some(collection))
+}
+
+// Remove the service's oldest collections past `retain`, and those older than `max_age`
+fn prune(service_dir: &Path, retain: usize, max_age: Option<Duration>) -> Result<()> {
+    // Collections are named after the time they were made, so they sort by age
+    let mut collections: Vec<PathBuf> = fs::read_dir(service_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .map(|entry| entry.path())
+        .collect();
+    collections.sort();
+    collections.reverse();
+
+    for (index, collection) in collections.iter().enumerate() {
+        let expired = max_age.is_some_and(|max_age| {
+            fs::metadata(collection)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > max_age)
+        });
+        if index >= retain || expired {
+            fs::remove_dir_all(collection)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(retain: usize) -> CrashDumpConfig {
+        CrashDumpConfig {
+            path: PathBuf::from("/cores"),
+            retain,
+            max_age: None,
+        }
+    }
+
+    #[test]
+    fn test_collect_moves_the_containers_dumps() {
+        let root = tempfile::tempdir().unwrap();
+        let dump_dir = root.path().join("web__1__app__a");
+        let other_dir = root.path().join("web__2__app__b");
+        let service_dir = root.path().join("web");
+        fs::create_dir_all(&dump_dir).unwrap();
+        fs::create_dir_all(&other_dir).unwrap();
+        fs::write(dump_dir.join("core.1"), "dump").unwrap();
+        fs::write(other_dir.join("core.2"), "other").unwrap();
+
+        let collection = collect(&config(5), &dump_dir, &service_dir, "web__1__app__a")
+            .unwrap()
+            .unwrap();
+        assert!(collection.starts_with(&service_dir));
+        assert!(collection
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .ends_with("__web__1__app__a"));
+        assert_eq!(
+            fs::read_to_string(collection.join("core.1")).unwrap(),
+            "dump"
+        );
+        assert!(!dump_dir.join("core.1").exists());
+        // Another replica's dumps are left for its own crash
+        assert!(other_dir.join("core.2").exists());
+
+        // Nothing left to collect
+        let nothing = collect(&config(5), &dump_dir, &service_dir, "web__1__app__a").unwrap();
+        assert!(nothing.is_none());
+        let missing = root.path().join("missing");
+        assert!(collect(&config(5), &missing, &service_dir, "x")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_prune_keeps_the_newest() {
+        let service_dir = tempfile::tempdir().unwrap();
+        for name in [
+            "2024-01-01T00:00:00Z__a",
+            "2024-01-02T00:00:00Z__a",
+            "2024-01-03T00:00:00Z__a",
+        ] {
+            fs::create_dir(service_dir.path().join(name)).unwrap();
+        }
+
+        prune(service_dir.path(), 2, None).unwrap();
+        let mut left: Vec<String> = fs::read_dir(service_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["2024-01-02T00:00:00Z__a", "2024-01-03T00:00:00Z__a"]);
+
+        prune(service_dir.path(), 5, Some(Duration::from_secs(3600))).unwrap();
+        assert_eq!(fs::read_dir(service_dir.path()).unwrap().count(), 2);
+    }
+}
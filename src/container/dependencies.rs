@@ -0,0 +1,147 @@
+// src/container/dependencies.rs
+//
+// Start order of the containers in a pod. A container starts after the
+// containers it lists in `depends_on`, and with `condition: healthy` only once
+// their health checks pass. Containers without dependencies keep their order
+// in the config.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::health;
+use super::{Container, ContainerRuntime};
+
+/// A container this one waits for, either just its name or the name with a condition
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ContainerDependency {
+    Name(String),
+    Condition {
+        container: String,
+        #[serde(default)]
+        condition: DependencyCondition,
+        /// How long to wait for the dependency to become healthy
+        #[serde(with = "humantime_serde", default = "default_dependency_timeout")]
+        timeout: Duration,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyCondition {
+    /// The dependency has been started
+    #[default]
+    Started,
+    /// The dependency's health check passes
+    Healthy,
+}
+
+fn default_dependency_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
+impl ContainerDependency {
+    pub fn container(&self) -> &str {
+        match self {
+            ContainerDependency::Name(name) => name,
+            ContainerDependency::Condition { container, .. } => container,
+        }
+    }
+
+    pub fn condition(&self) -> DependencyCondition {
+        match self {
+            ContainerDependency::Name(_) => DependencyCondition::Started,
+            ContainerDependency::Condition { condition, .. } => *condition,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        match self {
+            ContainerDependency::Name(_) => default_dependency_timeout(),
+            ContainerDependency::Condition { timeout, .. } => *timeout,
+        }
+    }
+}
+
+/// The pod's containers, each after its dependencies. Fails on a dependency
+/// that isn't in the pod, one that needs a missing health check, or a cycle.
+pub fn start_order(containers: &[Container]) -> Result<Vec<&Container>> {
+    for container in containers {
+        for dependency in container.depends_on.iter().flatten() {
+            let Some(target) = containers.iter().find(|c| c.name == dependency.container()) else {
+                return Err(anyhow!(
+                    "container '{}' depends on '{}', which is not in the pod",
+                    container.name,
+                    dependency.container()
+                ));
+            };
+            if target.name == container.name {
+                return Err(anyhow!("container '{}' depends on itself", container.name));
+            }
+            if dependency.condition() == DependencyCondition::Healthy
+                && target.health_check.is_none()
+            {
+                return Err(anyhow!(
+                    "container '{}' waits for '{}' to be healthy, but it has no health_check",
+                    container.name,
+                    target.name
+                ));
+            }
+        }
+    }
+
+    let mut ordered: Vec<&Container> = Vec::with_capacity(containers.len());
+    while ordered.len() < containers.len() {
+        let next = containers.iter().find(|container| {
+            !ordered.iter().any(|c| c.name == container.name)
+                && container
+                    .depends_on
+                    .iter()
+                    .flatten()
+                    .all(|dependency| ordered.iter().any(|c| c.name == dependency.container()))
+        });
+        match next {
+            Some(container) => ordered.push(container),
+            None => {
+                let waiting: Vec<&str> = containers
+                    .iter()
+                    .filter(|container| !ordered.iter().any(|c| c.name == container.name))
+                    .map(|container| container.name.as_str())
+                    .collect();
+                return Err(anyhow!("dependency cycle between {}", waiting.join(", ")));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// Wait for the dependencies of `container` that have to be healthy before it
+/// starts. `runtime_name` gives the name a container of the pod was started as.
+pub async fn wait_for_dependencies(
+    runtime: &dyn ContainerRuntime,
+    container: &Container,
+    containers: &[Container],
+    runtime_name: impl Fn(&Container) -> Result<String>,
+) -> Result<()> {
+    for dependency in container.depends_on.iter().flatten() {
+        if dependency.condition() != DependencyCondition::Healthy {
+            continue;
+        }
+        let Some(target) = containers.iter().find(|c| c.name == dependency.container()) else {
+            continue;
+        };
+        let Some(health_check) = &target.health_check else {
+            continue;
+        };
+
+        let target_name = runtime_name(target)?;
+        slog::info!(slog_scope::logger(), "Waiting for dependency to become healthy";
+            "container" => &container.name,
+            "dependency" => &target_name
+        );
+        health::wait_until_healthy(runtime, &target_name, health_check, dependency.timeout())
+            .await
+            .map_err(|e| anyhow!("Dependency of {} not ready: {}", container.name, e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+// src/container/dns.rs
+//
+// Name resolution for pods: nameservers, search domains and extra hosts
+// entries. Services set defaults for all their containers; a container's own
+// `dns` and `dns_search` replace the service's, and its `extra_hosts` are
+// added to them. The containers of a pod also reach each other by their
+// container names, through network aliases or hosts entries the runtime adds.
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::config::{parse_container_name, ServiceConfig};
+use crate::container::Container;
+
+/// Resolves to the host's address inside the container, as in Docker
+pub const HOST_GATEWAY: &str = "host-gateway";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DnsSettings {
+    pub servers: Vec<String>,
+    pub search: Vec<String>,
+    /// "hostname:address" entries
+    pub extra_hosts: Vec<String>,
+}
+
+impl DnsSettings {
+    /// Contents of a resolv.conf, None when the runtime's default should be kept
+    pub fn resolv_conf(&self) -> Option<String> {
+        if self.servers.is_empty() && self.search.is_empty() {
+            return None;
+        }
+        let mut contents = String::new();
+        for server in &self.servers {
+            contents.push_str(&format!("nameserver {}\n", server));
+        }
+        if !self.search.is_empty() {
+            contents.push_str(&format!("search {}\n", self.search.join(" ")));
+        }
+        Some(contents)
+    }
+
+    /// Contents of an /etc/hosts with the extra entries, `host-gateway` being
+    /// replaced by `gateway`. None when there are no extra entries.
+    pub fn hosts_file(&self, hostname: &str, gateway: &str) -> Result<Option<String>> {
+        if self.extra_hosts.is_empty() {
+            return Ok(None);
+        }
+        let mut contents = format!("127.0.0.1\tlocalhost {}\n::1\tlocalhost\n", hostname);
+        for entry in &self.extra_hosts {
+            let (host, address) = parse_extra_host(entry)?;
+            let address = if address == HOST_GATEWAY {
+                gateway
+            } else {
+                address
+            };
+            contents.push_str(&format!("{}\t{}\n", address, host));
+        }
+        Ok(Some(contents))
+    }
+}
+
+/// The DNS settings a container runs with
+pub fn dns_for(service_config: &ServiceConfig, container: &Container) -> DnsSettings {
+    let mut extra_hosts: Vec<String> = Vec::new();
+    for entry in service_config
+        .extra_hosts
+        .iter()
+        .chain(container.extra_hosts.iter())
+        .flatten()
+    {
+        // A container's entry replaces the service's for the same hostname
+        let host = entry
+            .split_once(':')
+            .map_or(entry.as_str(), |(host, _)| host);
+        extra_hosts.retain(|existing| !existing.starts_with(&format!("{}:", host)));
+        extra_hosts.push(entry.clone());
+    }
+
+    DnsSettings {
+        servers: container
+            .dns
+            .as_ref()
+            .or(service_config.dns.as_ref())
+            .cloned()
+            .unwrap_or_default(),
+        search: container
+            .dns_search
+            .as_ref()
+            .or(service_config.dns_search.as_ref())
+            .cloned()
+            .unwrap_or_default(),
+        extra_hosts,
+    }
+}
+
+/// The names a pod's containers reach each other by, their names in the
+/// spec, with the runtime container each resolves to. Empty for pods of a
+/// single container.
+pub fn pod_dns_names<'a>(
+    container_names: impl IntoIterator<Item = &'a str>,
+) -> BTreeMap<String, String> {
+    let names: BTreeMap<String, String> = container_names
+        .into_iter()
+        .filter_map(|name| {
+            let parts = parse_container_name(name).ok()?;
+            Some((parts.container_name, name.to_string()))
+        })
+        .collect();
+    if names.len() < 2 {
+        return BTreeMap::new();
+    }
+    names
+}
+
+/// Split a "hostname:address" entry. The address may be an IPv6 address or
+/// `host-gateway`.
+pub fn parse_extra_host(entry: &str) -> Result<(&str, &str)> {
+    let (host, address) = entry
+        .split_once(':')
+        .ok_or_else(|| anyhow!("'{}' must be in the form hostname:address", entry))?;
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(anyhow!("'{}' has an invalid hostname", entry));
+    }
+    if address != HOST_GATEWAY && address.parse::<IpAddr>().is_err() {
+        return Err(anyhow!(
+            "'{}' must map to an IP address or {}",
+            entry,
+            HOST_GATEWAY
+        ));
+    }
+    Ok((host, address))
+}
+
+/// A hosts file with "hostname:address" entries added, replacing the lines
+/// of the same hostnames
+pub fn with_hosts_entries(contents: &str, entries: &[String]) -> Result<String> {
+    let hosts = entries
+        .iter()
+        .map(|entry| parse_extra_host(entry))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut lines: Vec<String> = contents
+        .lines()
+        .filter(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            !matches!(fields[..], [_, host] if hosts.iter().any(|(name, _)| *name == host))
+        })
+        .map(str::to_string)
+        .collect();
+    lines.extend(
+        hosts
+            .iter()
+            .map(|(host, address)| format!("{}\t{}", address, host)),
+    );
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Check nameservers, search domains and extra hosts entries
+pub fn validate_dns(settings: &DnsSettings) -> Result<()> {
+    for server in &settings.servers {
+        if server.parse::<IpAddr>().is_err() {
+            return Err(anyhow!("nameserver '{}' is not an IP address", server));
+        }
+    }
+    for domain in &settings.search {
+        if domain.is_empty() || domain.contains(char::is_whitespace) {
+            return Err(anyhow!("'{}' is not a valid search domain", domain));
+        }
+    }
+    for entry in &settings.extra_hosts {
+        parse_extra_host(entry)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pod_dns_names() {
+        let uuid = "550e8400-e29b-41d4-a716-446655440000";
+        let app = format!("web__0__app__{}", uuid);
+        let sidecar = format!("web__0__sidecar__{}", uuid);
+        let names = pod_dns_names([app.as_str(), sidecar.as_str()]);
+        assert_eq!(names.get("app"), Some(&app));
+        assert_eq!(names.get("sidecar"), Some(&sidecar));
+
+        assert!(pod_dns_names([app.as_str()]).is_empty());
+    }
+
+    #[test]
+    fn test_with_hosts_entries() {
+        let hosts = "127.0.0.1\tlocalhost\n172.18.0.2\tapp\n172.18.0.9\tsidecar\n";
+        let entries = vec![
+            "sidecar:172.18.0.3".to_string(),
+            "db:172.18.0.4".to_string(),
+        ];
+        assert_eq!(
+            with_hosts_entries(hosts, &entries).unwrap(),
+            "127.0.0.1\tlocalhost\n172.18.0.2\tapp\n172.18.0.3\tsidecar\n172.18.0.4\tdb\n"
+        );
+
+        assert!(with_hosts_entries(hosts, &["sidecar".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,508 @@
+// src/container/expiry.rs
+//
+// Watches secrets and certificates for upcoming expiry and renewal. Items
+// close to expiring are logged and exported as metrics, and a renewed secret
+// file on disk rolls the pods that were started with the old value.
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+use super::dates::days_from_civil;
+use super::rolling_update::restart_pods;
+use super::secrets::{resolve_secret, SecretConfig, SecretSource};
+use crate::config::daemon::{get_daemon_config, ExpiryMonitorConfig};
+use crate::config::CONFIG_STORE;
+use crate::metrics::EXPIRY_SECONDS;
+
+pub static EXPIRY_STATUS: OnceLock<Arc<RwLock<Vec<ExpiryItem>>>> = OnceLock::new();
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryState {
+    Valid,
+    Expiring,
+    Expired,
+    /// No expiry known, e.g. the value could not be read
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ExpiryItem {
+    /// "secret" or "docker_tls"
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<SystemTime>,
+    pub state: ExpiryState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Identifies a monitored item across checks: kind, service, container and name
+type ItemKey = (&'static str, Option<String>, Option<String>, String);
+
+fn item_key(item: &ExpiryItem) -> ItemKey {
+    (
+        item.kind,
+        item.service_name.clone(),
+        item.container.clone(),
+        item.name.clone(),
+    )
+}
+
+// The metric's labels, unique per item
+fn metric_labels(key: &ItemKey) -> [&str; 4] {
+    [
+        key.1.as_deref().unwrap_or_default(),
+        key.2.as_deref().unwrap_or_default(),
+        key.3.as_str(),
+        key.0,
+    ]
+}
+
+#[derive(Default)]
+struct MonitorState {
+    fingerprints: HashMap<ItemKey, u64>,
+    states: HashMap<ItemKey, ExpiryState>,
+}
+
+pub async fn get_expiry_status() -> Vec<ExpiryItem> {
+    match EXPIRY_STATUS.get() {
+        Some(status) => status.read().await.clone(),
+        None => Vec::new(),
+    }
+}
+
+pub fn start_expiry_monitor() {
+    let config = get_daemon_config().expiry;
+    EXPIRY_STATUS.get_or_init(|| Arc::new(RwLock::new(Vec::new())));
+
+    tokio::spawn(async move {
+        let mut state = MonitorState::default();
+        let mut interval = tokio::time::interval(config.check_interval);
+        loop {
+            interval.tick().await;
+            let items = check(&config, &mut state).await;
+            if let Some(status) = EXPIRY_STATUS.get() {
+                *status.write().await = items;
+            }
+        }
+    });
+}
+
+fn fingerprint(value: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn classify(expires_at: Option<SystemTime>, warn_before: Duration) -> ExpiryState {
+    let Some(expires_at) = expires_at else {
+        return ExpiryState::Unknown;
+    };
+    let now = SystemTime::now();
+    if expires_at <= now {
+        ExpiryState::Expired
+    } else if expires_at <= now + warn_before {
+        ExpiryState::Expiring
+    } else {
+        ExpiryState::Valid
+    }
+}
+
+// Secrets worth watching: those with an expiry, and files that can be renewed in place
+fn monitored(secret: &SecretConfig) -> bool {
+    secret.certificate
+        || secret.expires_at.is_some()
+        || matches!(secret.source, SecretSource::File(_))
+}
+
+async fn check(config: &ExpiryMonitorConfig, state: &mut MonitorState) -> Vec<ExpiryItem> {
+    let mut items = Vec::new();
+    let mut renewed_services = Vec::new();
+
+    let services = match CONFIG_STORE.get() {
+        Some(store) => store
+            .read()
+            .await
+            .values()
+            .map(|(_, config)| config.clone())
+            .collect::<Vec<_>>(),
+        None => Vec::new(),
+    };
+
+    for service in &services {
+        let mut scoped: Vec<(Option<String>, &SecretConfig)> = service
+            .secrets
+            .iter()
+            .map(|secret| (None, secret))
+            .collect();
+        for container in &service.spec.containers {
+            for secret in container.secrets.iter().flatten() {
+                scoped.push((Some(container.name.clone()), secret));
+            }
+        }
+        scoped.retain(|(_, secret)| monitored(secret));
+
+        for (container, secret) in scoped {
+            let mut item = ExpiryItem {
+                kind: "secret",
+                service_name: Some(service.name.clone()),
+                container,
+                name: secret.name.clone(),
+                expires_at: secret.expires_at,
+                state: ExpiryState::Unknown,
+                error: None,
+            };
+
+            // Command sources are only run when their expiry has to be read
+            let needs_value = secret.certificate || matches!(secret.source, SecretSource::File(_));
+            if needs_value {
+                match resolve_secret(secret).await {
+                    Ok(value) => {
+                        crate::redact::register_for_service(&service.name, value.expose());
+                        if secret.certificate {
+                            match certificate_expiry(value.expose()) {
+                                Some(not_after) => item.expires_at = Some(not_after),
+                                None => {
+                                    item.error = Some("No valid PEM certificate found".to_string())
+                                }
+                            }
+                        }
+
+                        if matches!(secret.source, SecretSource::File(_)) {
+                            let current = fingerprint(value.expose().as_bytes());
+                            let previous = state.fingerprints.insert(item_key(&item), current);
+                            if previous.is_some_and(|previous| previous != current) {
+                                slog::info!(slog_scope::logger(), "Secret renewed on disk";
+                                    "service" => &service.name,
+                                    "secret" => &secret.name
+                                );
+                                if !renewed_services.contains(&service.name) {
+                                    renewed_services.push(service.name.clone());
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => item.error = Some(e.to_string()),
+                }
+            }
+
+            item.state = classify(item.expires_at, config.warn_before);
+            report(&item, state);
+            items.push(item);
+        }
+    }
+
+    // The Docker client certificates are read once at startup, so only alert
+    if let Some(tls) = get_daemon_config()
+        .runtime
+        .docker
+        .and_then(|docker| docker.tls)
+    {
+        for (name, path) in [("docker-ca", tls.ca), ("docker-cert", tls.cert)] {
+            let mut item = ExpiryItem {
+                kind: "docker_tls",
+                service_name: None,
+                container: None,
+                name: name.to_string(),
+                expires_at: None,
+                state: ExpiryState::Unknown,
+                error: None,
+            };
+            match read_certificate_expiry(&path).await {
+                Ok(expires_at) => item.expires_at = Some(expires_at),
+                Err(e) => item.error = Some(e),
+            }
+            item.state = classify(item.expires_at, config.warn_before);
+            report(&item, state);
+            items.push(item);
+        }
+    }
+
+    // Forget items that are no longer configured, and their metric
+    let current: HashSet<ItemKey> = items.iter().map(item_key).collect();
+    state.states.retain(|key, _| {
+        if current.contains(key) {
+            return true;
+        }
+        if let Some(gauge) = EXPIRY_SECONDS.get() {
+            let _ = gauge.remove_label_values(&metric_labels(key));
+        }
+        false
+    });
+    state
+        .fingerprints
+        .retain(|key, _| state.states.contains_key(key));
+
+    if config.roll_on_renewal {
+        for service_name in renewed_services {
+            tokio::spawn(async move {
+                if let Err(e) = restart_pods(&service_name).await {
+                    slog::error!(slog_scope::logger(), "Failed to roll pods after secret renewal";
+                        "service" => &service_name,
+                        "error" => e.to_string()
+                    );
+                }
+            });
+        }
+    }
+
+    items
+}
+
+// Log state changes once, and keep the metric current
+fn report(item: &ExpiryItem, state: &mut MonitorState) {
+    let service = item.service_name.clone().unwrap_or_default();
+    let key = item_key(item);
+
+    if let Some(gauge) = EXPIRY_SECONDS.get() {
+        let labels = metric_labels(&key);
+        match item.expires_at {
+            Some(expires_at) => {
+                let remaining = match expires_at.duration_since(SystemTime::now()) {
+                    Ok(remaining) => remaining.as_secs_f64(),
+                    Err(e) => -e.duration().as_secs_f64(),
+                };
+                gauge.with_label_values(&labels).set(remaining);
+            }
+            None => {
+                let _ = gauge.remove_label_values(&labels);
+            }
+        }
+    }
+
+    if state.states.insert(key, item.state) == Some(item.state) {
+        return;
+    }
+    let expires_at = item
+        .expires_at
+        .map(|at| humantime_serde::re::humantime::format_rfc3339_seconds(at).to_string());
+    match item.state {
+        ExpiryState::Expiring => {
+            slog::warn!(slog_scope::logger(), "Expiring soon";
+                "kind" => item.kind,
+                "service" => &service,
+                "name" => &item.name,
+                "expires_at" => expires_at
+            );
+        }
+        ExpiryState::Expired => {
+            slog::error!(slog_scope::logger(), "Expired";
+                "kind" => item.kind,
+                "service" => &service,
+                "name" => &item.name,
+                "expires_at" => expires_at
+            );
+        }
+        ExpiryState::Unknown if item.error.is_some() => {
+            slog::warn!(slog_scope::logger(), "Unable to check expiry";
+                "kind" => item.kind,
+                "service" => &service,
+                "name" => &item.name,
+                "error" => item.error.clone()
+            );
+        }
+        _ => {}
+    }
+}
+
+async fn read_certificate_expiry(path: &Path) -> Result<SystemTime, String> {
+    let pem = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    certificate_expiry(&pem)
+        .ok_or_else(|| format!("No valid PEM certificate found in {}", path.display()))
+}
+
+/// Earliest notAfter of the certificates in a PEM bundle
+pub fn certificate_expiry(pem: &str) -> Option<SystemTime> {
+    let mut earliest: Option<SystemTime> = None;
+    let mut block: Option<String> = None;
+
+    for line in pem.lines().map(str::trim) {
+        if line == "-----BEGIN CERTIFICATE-----" {
+            block = Some(String::new());
+        } else if line == "-----END CERTIFICATE-----" {
+            let not_after = block
+                .take()
+                .and_then(|b64| base64_decode(&b64))
+                .and_then(|der| not_after(&der));
+            if let Some(not_after) = not_after {
+                earliest = Some(earliest.map_or(not_after, |e| e.min(not_after)));
+            }
+        } else if let Some(block) = block.as_mut() {
+            block.push_str(line);
+        }
+    }
+
+    earliest
+}
+
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in input.bytes().filter(|c| *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+// One DER element: (tag, contents, rest)
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | *b as usize);
+        (len, &rest[count..])
+    };
+
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+// Certificate -> TBSCertificate -> Validity -> notAfter
+fn not_after(der: &[u8]) -> Option<SystemTime> {
+    let (_, certificate, _) = der_element(der)?;
+    let (_, tbs, _) = der_element(certificate)?;
+
+    let mut rest = tbs;
+    let (tag, _, after) = der_element(rest)?;
+    // Skip the explicit [0] version when present
+    if tag == 0xa0 {
+        rest = after;
+    }
+    // serialNumber, signature, issuer
+    for _ in 0..3 {
+        rest = der_element(rest)?.2;
+    }
+
+    let (_, validity, _) = der_element(rest)?;
+    let (_, _, after_not_before) = der_element(validity)?;
+    let (tag, time, _) = der_element(after_not_before)?;
+    parse_asn1_time(tag, std::str::from_utf8(time).ok()?)
+}
+
+// UTCTime (YYMMDDHHMMSSZ) or GeneralizedTime (YYYYMMDDHHMMSSZ)
+fn parse_asn1_time(tag: u8, time: &str) -> Option<SystemTime> {
+    let digits = time.strip_suffix('Z')?;
+    let (year, rest) = match tag {
+        0x17 if digits.len() == 12 => {
+            let yy: i64 = digits[..2].parse().ok()?;
+            (if yy < 50 { 2000 + yy } else { 1900 + yy }, &digits[2..])
+        }
+        0x18 if digits.len() == 14 => (digits[..4].parse().ok()?, &digits[4..]),
+        _ => return None,
+    };
+
+    let field = |i: usize| rest.get(i..i + 2)?.parse::<i64>().ok();
+    let (month, day, hour, minute, second) =
+        (field(0)?, field(2)?, field(4)?, field(6)?, field(8)?);
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    u64::try_from(seconds)
+        .ok()
+        .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // notAfter 2034-06-15 12:30:00 UTC, a UTCTime
+    const UTC_TIME_CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBgDCCASWgAwIBAgIUD5TBPDhopH5hBgWfH6nwfBj3e1AwCgYIKoZIzj0EAwIw
+FTETMBEGA1UEAwwKb3JiaXQtdGVzdDAeFw0yNDAxMDEwMDAwMDBaFw0zNDA2MTUx
+MjMwMDBaMBUxEzARBgNVBAMMCm9yYml0LXRlc3QwWTATBgcqhkjOPQIBBggqhkjO
+PQMBBwNCAAQ7RRe2Z/HVRaWWPr03/FNHSSqR+0+ucUOp8Pblox/ZfjGBy1Z2Ia/1
+xn2mF7eXC8rLxcaKHET80NC/TDGEJp1eo1MwUTAdBgNVHQ4EFgQU1EEsA3xQX4QP
+zP8K4VyEWO4au80wHwYDVR0jBBgwFoAU1EEsA3xQX4QPzP8K4VyEWO4au80wDwYD
+VR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNJADBGAiEA6Wz1q0mzImDZW1V6E1KO
+d2bXmfLl3PuMIA3QwBkPTBwCIQChGK482wO25ealL/qLtQnDLGkGAAX8620gIrSr
+LMJ08w==
+-----END CERTIFICATE-----
+";
+
+    // notAfter 2051-01-01 00:00:00 UTC, a GeneralizedTime
+    const GENERALIZED_TIME_CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBgjCCASegAwIBAgIUUuDDTSXOiF2GFlLhiUrof7NXjzYwCgYIKoZIzj0EAwIw
+FTETMBEGA1UEAwwKb3JiaXQtdGVzdDAgFw0yNDAxMDEwMDAwMDBaGA8yMDUxMDEw
+MTAwMDAwMFowFTETMBEGA1UEAwwKb3JiaXQtdGVzdDBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABC5sSYzztj8wJp0yAJ0HTGTydSZb8GJmQ2Q3DbwtceIwkgFIJi65
+ipBlw7T07J2mSAMSj6W6J00VXx+lhaisimajUzBRMB0GA1UdDgQWBBTEG+2jCELa
+hWRE6IgRLK5KWgWOXjAfBgNVHSMEGDAWgBTEG+2jCELahWRE6IgRLK5KWgWOXjAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQCWiG6xk2xIT7/lWkTU
+7JmKHw0Atbk0U+hVCoL9PpogrwIhALkbpCq/dkMcLQTJ/IuVkXeAGqu/r52auj3Y
+2PaeqTNF
+-----END CERTIFICATE-----
+";
+
+    fn at(seconds: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn test_parse_utc_time() {
+        assert_eq!(parse_asn1_time(0x17, "991231235959Z"), at(946_684_799));
+        assert_eq!(parse_asn1_time(0x17, "240229000000Z"), at(1_709_164_800));
+        // Years from 50 are in the 1900s
+        assert_eq!(parse_asn1_time(0x17, "700101000000Z"), at(0));
+        assert_eq!(parse_asn1_time(0x17, "991231235959"), None);
+        assert_eq!(parse_asn1_time(0x17, "20991231235959Z"), None);
+    }
+
+    #[test]
+    fn test_parse_generalized_time() {
+        assert_eq!(parse_asn1_time(0x18, "20491231235959Z"), at(2_524_607_999));
+        assert_eq!(parse_asn1_time(0x18, "20510101000000Z"), at(2_556_144_000));
+        assert_eq!(parse_asn1_time(0x18, "510101000000Z"), None);
+        assert_eq!(parse_asn1_time(0x04, "20510101000000Z"), None);
+    }
+
+    #[test]
+    fn test_certificate_expiry() {
+        assert_eq!(certificate_expiry(UTC_TIME_CERT), at(2_033_987_400));
+        assert_eq!(certificate_expiry(GENERALIZED_TIME_CERT), at(2_556_144_000));
+
+        // The earliest certificate of a bundle
+        let bundle = format!("{}{}", GENERALIZED_TIME_CERT, UTC_TIME_CERT);
+        assert_eq!(certificate_expiry(&bundle), at(2_033_987_400));
+
+        assert_eq!(certificate_expiry("not a certificate"), None);
+    }
+}
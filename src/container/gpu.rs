@@ -0,0 +1,255 @@
+// src/container/gpu.rs
+//
+// GPU requests for containers and GPU utilisation sampling. Docker does not
+// report GPU usage in its stats, so Orbit asks nvidia-smi for the usage of the
+// GPUs a container was given and feeds it into the container's stats.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+// Stats are gathered per container, so share one nvidia-smi run between them
+const SAMPLE_TTL: Duration = Duration::from_secs(5);
+
+static LAST_SAMPLE: Mutex<Option<(Instant, Vec<GpuDevice>)>> = Mutex::new(None);
+
+// GPUs handed to each container, by container name
+static ASSIGNED: Mutex<BTreeMap<String, Vec<String>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GpuConfig {
+    /// Number of GPUs, or "all"; ignored when device_ids is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<GpuCount>,
+    /// GPU indexes or UUIDs as listed by nvidia-smi
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_ids: Option<Vec<String>>,
+    #[serde(default = "default_gpu_driver")]
+    pub driver: String,
+    #[serde(default = "default_gpu_capabilities")]
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum GpuCount {
+    Count(u32),
+    All(String),
+}
+
+fn default_gpu_driver() -> String {
+    "nvidia".to_string()
+}
+
+fn default_gpu_capabilities() -> Vec<String> {
+    vec!["gpu".to_string()]
+}
+
+impl GpuConfig {
+    /// The count as Docker expects it, -1 meaning all GPUs
+    pub fn docker_count(&self) -> Result<Option<i64>> {
+        if self.device_ids.is_some() {
+            return Ok(None);
+        }
+        match &self.count {
+            None => Ok(Some(-1)),
+            Some(GpuCount::Count(count)) => Ok(Some(*count as i64)),
+            Some(GpuCount::All(value)) if value == "all" => Ok(Some(-1)),
+            Some(GpuCount::All(value)) => Err(anyhow!(
+                "Invalid GPU count {:?}, expected a number or \"all\"",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct GpuDevice {
+    index: String,
+    uuid: String,
+    utilization: f64,
+    memory_used: u64,
+    memory_total: u64,
+}
+
+/// Average utilisation and total memory of a container's GPUs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpuUsage {
+    pub percentage: f64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+}
+
+async fn sample_devices() -> Result<Vec<GpuDevice>> {
+    if let Some((sampled_at, devices)) = LAST_SAMPLE.lock().unwrap().as_ref() {
+        if sampled_at.elapsed() < SAMPLE_TTL {
+            return Ok(devices.clone());
+        }
+    }
+
+    let output = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=index,uuid,utilization.gpu,memory.used,memory.total",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "nvidia-smi failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut devices = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if let [index, uuid, utilization, memory_used, memory_total] = fields[..] {
+            // Memory is reported in MiB
+            devices.push(GpuDevice {
+                index: index.to_string(),
+                uuid: uuid.to_string(),
+                utilization: utilization.parse().unwrap_or(0.0),
+                memory_used: memory_used.parse::<u64>().unwrap_or(0) * 1024 * 1024,
+                memory_total: memory_total.parse::<u64>().unwrap_or(0) * 1024 * 1024,
+            });
+        }
+    }
+
+    *LAST_SAMPLE.lock().unwrap() = Some((Instant::now(), devices.clone()));
+    Ok(devices)
+}
+
+fn is_device(device: &GpuDevice, id: &str) -> bool {
+    id == device.index || id == device.uuid
+}
+
+// The first `count` devices no other container holds
+fn pick_free(devices: &[GpuDevice], taken: &[&String], count: usize) -> Result<Vec<String>> {
+    let free: Vec<String> = devices
+        .iter()
+        .filter(|device| !taken.iter().any(|id| is_device(device, id)))
+        .map(|device| device.index.clone())
+        .collect();
+    if free.len() < count {
+        return Err(anyhow!(
+            "{} GPUs requested but only {} of {} are free",
+            count,
+            free.len(),
+            devices.len()
+        ));
+    }
+    Ok(free.into_iter().take(count).collect())
+}
+
+/// The device IDs a container gets. A numeric count is turned into that many
+/// GPUs not yet assigned to another container, as Docker would otherwise hand
+/// out the same GPUs again. None leaves the choice to the runtime, for "all"
+/// or when the GPUs cannot be listed.
+pub async fn assign_devices(
+    container_name: &str,
+    config: &GpuConfig,
+) -> Result<Option<Vec<String>>> {
+    let count = match (&config.device_ids, config.docker_count()?) {
+        (Some(ids), _) => {
+            ASSIGNED
+                .lock()
+                .unwrap()
+                .insert(container_name.to_string(), ids.clone());
+            return Ok(Some(ids.clone()));
+        }
+        (None, Some(count)) if count >= 0 => count as usize,
+        _ => return Ok(None),
+    };
+
+    let devices = match sample_devices().await {
+        Ok(devices) => devices,
+        Err(e) => {
+            slog::warn!(slog_scope::logger(), "Failed to list GPUs, leaving the choice to the runtime";
+                "container" => container_name,
+                "error" => e.to_string()
+            );
+            return Ok(None);
+        }
+    };
+
+    let mut assigned = ASSIGNED.lock().unwrap();
+    let taken: Vec<&String> = assigned
+        .iter()
+        .filter(|(name, _)| name.as_str() != container_name)
+        .flat_map(|(_, ids)| ids)
+        .collect();
+    let ids = pick_free(&devices, &taken, count)?;
+    assigned.insert(container_name.to_string(), ids.clone());
+    Ok(Some(ids))
+}
+
+/// Frees the GPUs held by a removed container
+pub fn release_devices(container_name: &str) {
+    ASSIGNED.lock().unwrap().remove(container_name);
+}
+
+/// Usage of the GPUs a container was given. GPUs picked by the runtime, for
+/// "all" or when none were assigned, count every GPU on the host.
+pub async fn gpu_usage(container_name: &str, config: &GpuConfig) -> Option<GpuUsage> {
+    let devices = match sample_devices().await {
+        Ok(devices) => devices,
+        Err(e) => {
+            slog::debug!(slog_scope::logger(), "Failed to sample GPU usage";
+                "error" => e.to_string()
+            );
+            return None;
+        }
+    };
+
+    let assigned = ASSIGNED
+        .lock()
+        .unwrap()
+        .get(container_name)
+        .cloned()
+        .or_else(|| config.device_ids.clone());
+    let selected: Vec<&GpuDevice> = match &assigned {
+        Some(ids) => devices
+            .iter()
+            .filter(|device| ids.iter().any(|id| is_device(device, id)))
+            .collect(),
+        None => devices.iter().collect(),
+    };
+    if selected.is_empty() {
+        return None;
+    }
+
+    Some(GpuUsage {
+        percentage: selected.iter().map(|d| d.utilization).sum::<f64>() / selected.len() as f64,
+        memory_usage: selected.iter().map(|d| d.memory_used).sum(),
+        memory_limit: selected.iter().map(|d| d.memory_total).sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(index: &str) -> GpuDevice {
+        GpuDevice {
+            index: index.to_string(),
+            uuid: format!("GPU-{}", index),
+            utilization: 0.0,
+            memory_used: 0,
+            memory_total: 0,
+        }
+    }
+
+    #[test]
+    fn test_pick_free() {
+        let devices: Vec<GpuDevice> = ["0", "1", "2", "3"].into_iter().map(device).collect();
+        let held = ["0".to_string(), "GPU-2".to_string()];
+        let taken: Vec<&String> = held.iter().collect();
+
+        assert_eq!(pick_free(&devices, &taken, 2).unwrap(), vec!["1", "3"]);
+        assert_eq!(pick_free(&devices, &[], 1).unwrap(), vec!["0"]);
+        assert!(pick_free(&devices, &taken, 3).is_err());
+    }
+}
@@ -0,0 +1,250 @@
+// src/container/groups.rs
+//
+// Service groups. A service lists the groups it belongs to in `groups`, e.g.
+// every service of one application, and the API runs an operation on all of
+// a group's services at once: a rolling restart, scaling by a factor, or
+// pausing and resuming them, a few services at a time. Scaling by a factor
+// also multiplies the instance count bounds the scaler keeps the services
+// within, until the group is scaled by 1 again or Orbit restarts.
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::rolling_update::restart_pods;
+use super::scaling::history::{
+    record_scaling_event, ScalingDirection, ScalingEvent, ScalingReason,
+};
+use super::scaling::{scale_down, scale_up};
+use super::{stopped, INSTANCE_STORE, RUNTIME};
+use crate::config::{get_config_by_service, InstanceCount, CONFIG_STORE};
+use crate::proxy::run_proxy_for_service;
+
+// Services operated on at once when the request doesn't say
+pub const DEFAULT_CONCURRENCY: usize = 4;
+pub const MAX_FACTOR: f64 = 100.0;
+
+static SCALE_FACTORS: OnceLock<Mutex<FxHashMap<String, f64>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy)]
+pub enum GroupOperation {
+    Restart,
+    Scale(f64),
+    Pause,
+    Resume,
+}
+
+/// How the operation went for one service of the group
+#[derive(Debug, Serialize)]
+pub struct ServiceOutcome {
+    pub service: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Pods before and after, when they were scaled or paused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<usize>,
+}
+
+fn scale_factors() -> &'static Mutex<FxHashMap<String, f64>> {
+    SCALE_FACTORS.get_or_init(|| Mutex::new(FxHashMap::default()))
+}
+
+/// The instance count bounds of the service, multiplied by the factor its
+/// group was last scaled by
+pub fn scaled_bounds(service_name: &str, bounds: InstanceCount) -> InstanceCount {
+    match scale_factors().lock().unwrap().get(service_name) {
+        Some(factor) => multiply(&bounds, *factor),
+        None => bounds,
+    }
+}
+
+fn multiply(bounds: &InstanceCount, factor: f64) -> InstanceCount {
+    let scale = |count: u8| (f64::from(count) * factor).ceil().min(f64::from(u8::MAX)) as u8;
+    InstanceCount {
+        min: scale(bounds.min),
+        max: scale(bounds.max).max(1),
+    }
+}
+
+/// Every group, with the names of its services
+pub async fn groups() -> BTreeMap<String, BTreeSet<String>> {
+    let mut groups: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let Some(store) = CONFIG_STORE.get() else {
+        return groups;
+    };
+    for (_, config) in store.read().await.values() {
+        for group in &config.groups {
+            groups
+                .entry(group.clone())
+                .or_default()
+                .insert(config.name.clone());
+        }
+    }
+    groups
+}
+
+/// Run the operation on each of the group's services, `concurrency` at a
+/// time. Fails only when the group has no services.
+pub async fn run(
+    group: &str,
+    operation: GroupOperation,
+    concurrency: usize,
+) -> Result<Vec<ServiceOutcome>> {
+    let services = groups()
+        .await
+        .remove(group)
+        .ok_or_else(|| anyhow!("Group {} not found", group))?;
+    slog::info!(slog_scope::logger(), "Running group operation";
+        "group" => group,
+        "operation" => format!("{:?}", operation),
+        "services" => services.len()
+    );
+
+    let mut outcomes: Vec<ServiceOutcome> = futures::stream::iter(services)
+        .map(|service| async move {
+            let result = match operation {
+                GroupOperation::Restart => restart_pods(&service).await.map(|()| None),
+                GroupOperation::Scale(factor) => scale(&service, factor).await.map(Some),
+                GroupOperation::Pause => stopped::stop(&service)
+                    .await
+                    .map(|stopped| Some((stopped, 0))),
+                GroupOperation::Resume => stopped::start(&service).await.map(|()| None),
+            };
+            if let Err(e) = &result {
+                slog::error!(slog_scope::logger(), "Group operation failed for service";
+                    "group" => group,
+                    "service" => &service,
+                    "error" => e.to_string()
+                );
+            }
+            match result {
+                Ok(pods) => ServiceOutcome {
+                    service,
+                    ok: true,
+                    error: None,
+                    from: pods.map(|(from, _)| from),
+                    to: pods.map(|(_, to)| to),
+                },
+                Err(e) => ServiceOutcome {
+                    service,
+                    ok: false,
+                    error: Some(e.to_string()),
+                    from: None,
+                    to: None,
+                },
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    outcomes.sort_by(|a, b| a.service.cmp(&b.service));
+    Ok(outcomes)
+}
+
+// Multiply the service's pods and instance count bounds by the factor.
+// Returns the pods before and after.
+async fn scale(service_name: &str, factor: f64) -> Result<(usize, usize)> {
+    let mut config = get_config_by_service(service_name)
+        .await
+        .ok_or_else(|| anyhow!("Service {} not found", service_name))?;
+    if config.external.is_some() {
+        return Err(anyhow!(
+            "Service {} is external and has no pods",
+            service_name
+        ));
+    }
+    if stopped::is_stopped(&config) {
+        return Err(anyhow!("Service {} is stopped", service_name));
+    }
+
+    {
+        let mut factors = scale_factors().lock().unwrap();
+        if factor == 1.0 {
+            factors.remove(service_name);
+        } else {
+            factors.insert(service_name.to_string(), factor);
+        }
+    }
+    config.instance_count = multiply(&config.instance_count, factor);
+
+    let pods: Vec<Uuid> = INSTANCE_STORE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .get(service_name)
+        .map(|instances| instances.keys().copied().collect())
+        .unwrap_or_default();
+    let current = pods.len();
+    let target = ((current as f64 * factor).ceil() as usize).clamp(
+        config.instance_count.min as usize,
+        config.instance_count.max as usize,
+    );
+    if target == current {
+        return Ok((current, current));
+    }
+
+    let runtime = RUNTIME.get().unwrap().clone();
+    let mut reached = current;
+    let mut error = None;
+    if target > current {
+        for _ in current..target {
+            if let Err(e) = scale_up(service_name, config.clone(), runtime.clone()).await {
+                error = Some(e);
+                break;
+            }
+            reached += 1;
+        }
+    } else {
+        for uuid in pods.into_iter().take(current - target) {
+            if let Err(e) = scale_down(service_name, uuid, config.clone(), runtime.clone()).await {
+                error = Some(e);
+                break;
+            }
+            reached -= 1;
+        }
+    }
+
+    record_scaling_event(
+        service_name,
+        ScalingEvent {
+            timestamp: SystemTime::now(),
+            direction: if target > current {
+                ScalingDirection::Up
+            } else {
+                ScalingDirection::Down
+            },
+            from: current,
+            to: reached,
+            reason: ScalingReason {
+                policy: "group".to_string(),
+                metrics: BTreeMap::from([
+                    ("factor".to_string(), factor),
+                    (
+                        "min_instances".to_string(),
+                        f64::from(config.instance_count.min),
+                    ),
+                    (
+                        "max_instances".to_string(),
+                        f64::from(config.instance_count.max),
+                    ),
+                ]),
+            },
+            error: error.as_ref().map(|e| e.to_string()),
+        },
+    );
+    if reached != current {
+        run_proxy_for_service(service_name.to_string(), config).await;
+    }
+    match error {
+        Some(e) => Err(e),
+        None => Ok((current, reached)),
+    }
+}
@@ -1,4 +1,5 @@
 // src/container/health/config.rs
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -12,8 +13,41 @@ pub struct HealthCheckConfig {
     pub liveness_period: Duration,
     #[serde(default = "default_liveness_threshold")]
     pub liveness_failure_threshold: u32,
+    /// Time after the container starts before the tcp and exec checks begin
+    #[serde(with = "humantime_serde", default)]
+    pub initial_delay: Duration,
+    /// Passing checks in a row before the container is healthy
+    #[serde(default = "default_success_threshold")]
+    pub success_threshold: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tcp_check: Option<TcpHealthCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exec_check: Option<ExecHealthCheck>,
+    /// Restarts the container in place once it fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liveness_probe: Option<HttpProbe>,
+    /// Takes the container out of the load balancer while it fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readiness_probe: Option<HttpProbe>,
+    /// Holds off liveness checking until it passes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup_probe: Option<StartupProbe>,
+    /// When the container's health changes too often to be trusted
+    #[serde(default)]
+    pub flapping: FlappingConfig,
+}
+
+/// A container is flapping once its health state changed `transitions` times
+/// within `window`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlappingConfig {
+    #[serde(default = "default_flapping_transitions")]
+    pub transitions: u32,
+    #[serde(with = "humantime_serde", default = "default_flapping_window")]
+    pub window: Duration,
+    /// Restart a flapping container only after the longest backoff
+    #[serde(default)]
+    pub back_off_restarts: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +61,154 @@ pub struct TcpHealthCheck {
     pub failure_threshold: u32,
 }
 
+/// A command run inside the container, healthy when it exits 0. Runtimes with
+/// their own healthcheck run it themselves and Orbit reads the result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecHealthCheck {
+    pub command: Vec<String>,
+    #[serde(with = "humantime_serde", default = "default_exec_interval")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde", default = "default_exec_timeout")]
+    pub timeout: Duration,
+    /// Failures in a row before the container is unhealthy
+    #[serde(default = "default_exec_retries", alias = "failure_threshold")]
+    pub retries: u32,
+}
+
+/// A GET request to a container port, passing on a 2xx or 3xx response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpProbe {
+    pub port: u16,
+    #[serde(default = "default_probe_path")]
+    pub path: String,
+    #[serde(with = "humantime_serde", default = "default_probe_interval")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde", default = "default_probe_timeout")]
+    pub timeout: Duration,
+    /// Time after the container starts before the first probe
+    #[serde(with = "humantime_serde", default)]
+    pub initial_delay: Duration,
+    /// Failures in a row before the probe acts
+    #[serde(default = "default_probe_threshold")]
+    pub failure_threshold: u32,
+    /// Passes in a row before a failing readiness probe passes again
+    #[serde(default = "default_success_threshold")]
+    pub success_threshold: u32,
+}
+
+/// Checked after the container starts until it first passes, so slow-booting
+/// containers aren't restarted by their liveness checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartupProbe {
+    pub port: u16,
+    /// Sends a GET request to this path; without it, only connects to the port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(with = "humantime_serde", default = "default_probe_interval")]
+    pub period: Duration,
+    #[serde(with = "humantime_serde", default = "default_probe_timeout")]
+    pub timeout: Duration,
+    /// Time after the container starts before the first check
+    #[serde(with = "humantime_serde", default)]
+    pub initial_delay: Duration,
+    /// Failures in a row before the container is restarted
+    #[serde(default = "default_startup_probe_threshold")]
+    pub failure_threshold: u32,
+}
+
+impl StartupProbe {
+    fn validate(&self) -> Result<()> {
+        if self
+            .path
+            .as_ref()
+            .is_some_and(|path| !path.starts_with('/'))
+        {
+            return Err(anyhow!("startup_probe.path must start with '/'"));
+        }
+        if self.period.is_zero() || self.timeout.is_zero() {
+            return Err(anyhow!("startup_probe period and timeout must be positive"));
+        }
+        if self.failure_threshold == 0 {
+            return Err(anyhow!(
+                "startup_probe.failure_threshold must be at least 1"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl HttpProbe {
+    fn validate(&self, name: &str) -> Result<()> {
+        if !self.path.starts_with('/') {
+            return Err(anyhow!("{}.path must start with '/'", name));
+        }
+        if self.interval.is_zero() || self.timeout.is_zero() {
+            return Err(anyhow!("{} interval and timeout must be positive", name));
+        }
+        if self.failure_threshold == 0 {
+            return Err(anyhow!("{}.failure_threshold must be at least 1", name));
+        }
+        if self.success_threshold == 0 {
+            return Err(anyhow!("{}.success_threshold must be at least 1", name));
+        }
+        Ok(())
+    }
+}
+
+impl HealthCheckConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.success_threshold == 0 {
+            return Err(anyhow!("success_threshold must be at least 1"));
+        }
+        if let Some(probe) = &self.liveness_probe {
+            probe.validate("liveness_probe")?;
+            // A passing liveness probe has nothing to hold back
+            if probe.success_threshold != 1 {
+                return Err(anyhow!("liveness_probe.success_threshold must be 1"));
+            }
+        }
+        if let Some(probe) = &self.readiness_probe {
+            probe.validate("readiness_probe")?;
+        }
+        if let Some(probe) = &self.startup_probe {
+            probe.validate()?;
+        }
+        if self.flapping.transitions < 2 || self.flapping.window.is_zero() {
+            return Err(anyhow!(
+                "flapping needs at least 2 transitions within a positive window"
+            ));
+        }
+        if let Some(exec_check) = &self.exec_check {
+            if exec_check.command.first().is_none_or(|arg| arg.is_empty()) {
+                return Err(anyhow!("exec_check.command cannot be empty"));
+            }
+            if exec_check.interval.is_zero() || exec_check.timeout.is_zero() {
+                return Err(anyhow!("exec_check interval and timeout must be positive"));
+            }
+            if exec_check.retries == 0 {
+                return Err(anyhow!("exec_check.retries must be at least 1"));
+            }
+        }
+        Ok(())
+    }
+
+    /// How often the container is checked
+    pub fn period(&self) -> Duration {
+        self.exec_check
+            .as_ref()
+            .map_or(self.liveness_period, |exec_check| exec_check.interval)
+    }
+
+    /// Failed checks in a row before the container is unhealthy
+    pub fn failure_threshold(&self) -> u32 {
+        self.exec_check
+            .as_ref()
+            .map_or(self.liveness_failure_threshold, |exec_check| {
+                exec_check.retries
+            })
+    }
+}
+
 fn default_startup_timeout() -> Duration {
     Duration::from_secs(30)
 }
@@ -39,6 +221,9 @@ fn default_liveness_period() -> Duration {
 fn default_liveness_threshold() -> u32 {
     3
 }
+fn default_success_threshold() -> u32 {
+    1
+}
 fn default_tcp_timeout() -> Duration {
     Duration::from_secs(5)
 }
@@ -48,6 +233,46 @@ fn default_tcp_period() -> Duration {
 fn default_tcp_threshold() -> u32 {
     3
 }
+fn default_exec_interval() -> Duration {
+    Duration::from_secs(10)
+}
+fn default_exec_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+fn default_exec_retries() -> u32 {
+    3
+}
+fn default_probe_path() -> String {
+    "/".to_string()
+}
+fn default_probe_interval() -> Duration {
+    Duration::from_secs(10)
+}
+fn default_probe_timeout() -> Duration {
+    Duration::from_secs(1)
+}
+fn default_probe_threshold() -> u32 {
+    3
+}
+fn default_startup_probe_threshold() -> u32 {
+    30
+}
+fn default_flapping_transitions() -> u32 {
+    5
+}
+fn default_flapping_window() -> Duration {
+    Duration::from_secs(600)
+}
+
+impl Default for FlappingConfig {
+    fn default() -> Self {
+        Self {
+            transitions: default_flapping_transitions(),
+            window: default_flapping_window(),
+            back_off_restarts: false,
+        }
+    }
+}
 
 impl Default for HealthCheckConfig {
     fn default() -> Self {
@@ -56,7 +281,14 @@ impl Default for HealthCheckConfig {
             startup_failure_threshold: default_startup_threshold(),
             liveness_period: default_liveness_period(),
             liveness_failure_threshold: default_liveness_threshold(),
+            initial_delay: Duration::ZERO,
+            success_threshold: default_success_threshold(),
             tcp_check: None,
+            exec_check: None,
+            liveness_probe: None,
+            readiness_probe: None,
+            startup_probe: None,
+            flapping: FlappingConfig::default(),
         }
     }
 }
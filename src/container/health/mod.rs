@@ -1,22 +1,35 @@
 // src/container/health/mod.rs
-use crate::container::ContainerRuntime;
-use anyhow::Result;
+use crate::container::{
+    find_host_port, preemption, ContainerRuntime, ContainerStats, LOOPBACK_ADDRESS,
+};
+use anyhow::{anyhow, Result};
 use rustc_hash::FxHashMap;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::sync::OnceLock;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::net::TcpStream;
 use tokio::sync::RwLock;
 
-pub use self::config::HealthCheckConfig;
+pub use self::config::{
+    ExecHealthCheck, FlappingConfig, HealthCheckConfig, HttpProbe, StartupProbe,
+};
 use super::RUNTIME;
+use crate::proxy::set_container_backends;
 mod config;
+mod probes;
+pub mod webhooks;
 
 pub static CONTAINER_HEALTH: OnceLock<Arc<RwLock<FxHashMap<String, ContainerHealthState>>>> =
     OnceLock::new();
+static NEXT_MONITOR_ID: AtomicU64 = AtomicU64::new(0);
 
-#[derive(Debug, Clone, Serialize)]
+// Transitions kept per container
+const HISTORY_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HealthState {
     Starting,
     Healthy,
@@ -34,6 +47,26 @@ pub struct ContainerHealthState {
     pub failure_count: u32,
     pub last_failure: Option<SystemTime>,
     pub message: Option<String>,
+    /// False while the container fails its readiness probe
+    pub ready: bool,
+    /// The latest state transitions, oldest first
+    pub history: VecDeque<HealthTransition>,
+    /// The state changed too often lately, see `FlappingConfig`
+    pub flapping: bool,
+    // The monitoring task that owns this state, so a replaced one stops
+    #[serde(skip)]
+    monitor_id: u64,
+    #[serde(skip)]
+    flapping_config: FlappingConfig,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthTransition {
+    pub from: HealthState,
+    pub to: HealthState,
+    pub at: SystemTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 impl Default for ContainerHealthState {
@@ -47,16 +80,50 @@ impl Default for ContainerHealthState {
             failure_count: 0,
             last_failure: None,
             message: None,
+            ready: true,
+            history: VecDeque::new(),
+            flapping: false,
+            monitor_id: 0,
+            flapping_config: FlappingConfig::default(),
         }
     }
 }
 
 impl ContainerHealthState {
     pub fn transition_to(&mut self, new_state: HealthState, message: Option<String>) {
+        let now = SystemTime::now();
+        if std::mem::discriminant(&self.state) != std::mem::discriminant(&new_state) {
+            if self.history.len() == HISTORY_LIMIT {
+                self.history.pop_front();
+            }
+            self.history.push_back(HealthTransition {
+                from: self.state.clone(),
+                to: new_state.clone(),
+                at: now,
+                message: message.clone(),
+            });
+        }
         self.last_state = Some(self.state.clone());
         self.state = new_state;
-        self.last_transition = SystemTime::now();
+        self.last_transition = now;
         self.message = message;
+        self.update_flapping();
+    }
+
+    // Flapping while the history holds enough transitions within the window
+    pub(super) fn update_flapping(&mut self) {
+        let window = self.flapping_config.window;
+        let recent = self
+            .history
+            .iter()
+            .filter(|transition| transition.at.elapsed().is_ok_and(|age| age <= window))
+            .count();
+        self.flapping = recent >= self.flapping_config.transitions as usize;
+    }
+
+    /// Whether restarts of this container should wait for the longest backoff
+    pub fn backs_off_restarts(&self) -> bool {
+        self.flapping && self.flapping_config.back_off_restarts
     }
 
     fn record_failure(&mut self) {
@@ -64,10 +131,10 @@ impl ContainerHealthState {
         self.last_failure = Some(SystemTime::now());
     }
 
-    // fn record_restart(&mut self) {
-    //     self.restart_count += 1;
-    //     self.last_restart = Some(SystemTime::now());
-    // }
+    fn record_restart(&mut self) {
+        self.restart_count += 1;
+        self.last_restart = Some(SystemTime::now());
+    }
 }
 
 // Update initialize_health_monitoring
@@ -78,11 +145,19 @@ pub async fn initialize_health_monitoring(
 ) -> Result<()> {
     let health_store = CONTAINER_HEALTH.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
     let config = config.unwrap_or_default();
+    let monitor_id = NEXT_MONITOR_ID.fetch_add(1, Ordering::Relaxed);
 
     // Initialize health state
     {
         let mut health_map = health_store.write().await;
-        health_map.insert(container_name.to_string(), ContainerHealthState::default());
+        health_map.insert(
+            container_name.to_string(),
+            ContainerHealthState {
+                monitor_id,
+                flapping_config: config.flapping.clone(),
+                ..Default::default()
+            },
+        );
     }
 
     slog::info!(slog_scope::logger(), "Health monitoring initialized";
@@ -90,10 +165,13 @@ pub async fn initialize_health_monitoring(
         "container" => container_name,
     );
 
+    probes::start_probes(service_name, container_name, &config);
+
     // Spawn monitoring task
     tokio::spawn(monitor_container_health(
         service_name.to_string(),
         container_name.to_string(),
+        monitor_id,
         config,
         RUNTIME.get().expect("Runtime not initialized").clone(),
     ));
@@ -108,10 +186,103 @@ async fn check_tcp_health(addr: &str, port: u16, timeout: Duration) -> bool {
     }
 }
 
+async fn check_exec_health(
+    runtime: &dyn ContainerRuntime,
+    container_name: &str,
+    check: &ExecHealthCheck,
+) -> bool {
+    matches!(
+        tokio::time::timeout(
+            check.timeout,
+            runtime.exec_container(container_name, &check.command)
+        )
+        .await,
+        Ok(Ok(0))
+    )
+}
+
+// Outcome of one round of checks
+enum Verdict {
+    Healthy,
+    // Counts towards the failure threshold
+    Failed,
+    // The runtime's own healthcheck already used up its retries
+    Unhealthy,
+    // The runtime's own healthcheck has no result yet
+    Pending,
+}
+
+async fn run_checks(
+    container_name: &str,
+    stats: &ContainerStats,
+    config: &HealthCheckConfig,
+    runtime: &dyn ContainerRuntime,
+) -> Verdict {
+    // TCP health check if configured
+    if let Some(tcp_check) = &config.tcp_check {
+        // Loopback-published containers are checked through their host port
+        let port = if stats.ip_address == LOOPBACK_ADDRESS {
+            find_host_port(stats, tcp_check.port).unwrap_or(tcp_check.port)
+        } else {
+            tcp_check.port
+        };
+        if !check_tcp_health(&stats.ip_address, port, tcp_check.timeout).await {
+            return Verdict::Failed;
+        }
+    }
+
+    if let Some(exec_check) = &config.exec_check {
+        match &stats.native_health {
+            Some(HealthState::Healthy) => {}
+            Some(HealthState::Starting) => return Verdict::Pending,
+            Some(_) => return Verdict::Unhealthy,
+            None => {
+                if !check_exec_health(runtime, container_name, exec_check).await {
+                    return Verdict::Failed;
+                }
+            }
+        }
+    }
+
+    Verdict::Healthy
+}
+
+/// Check a just-started container until its health check passes, for the
+/// containers of its pod that wait on it. Fails once the check reports it
+/// unhealthy or `timeout` passes.
+pub async fn wait_until_healthy(
+    runtime: &dyn ContainerRuntime,
+    container_name: &str,
+    config: &HealthCheckConfig,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let stats = runtime.inspect_container(container_name).await?;
+        match run_checks(container_name, &stats, config, runtime).await {
+            Verdict::Healthy => return Ok(()),
+            Verdict::Unhealthy => return Err(anyhow!("{} is unhealthy", container_name)),
+            // Failed checks only count once the container is monitored
+            Verdict::Failed | Verdict::Pending => {}
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(anyhow!(
+                "{} not healthy after {:?}",
+                container_name,
+                timeout
+            ));
+        }
+        tokio::time::sleep(config.period().min(remaining)).await;
+    }
+}
+
 // Update monitor_container_health function
 async fn monitor_container_health(
-    _service_name: String,
+    service_name: String,
     container_name: String,
+    monitor_id: u64,
     config: HealthCheckConfig,
     runtime: Arc<dyn ContainerRuntime>,
 ) {
@@ -119,6 +290,7 @@ async fn monitor_container_health(
         .get()
         .expect("Health store not initialized");
     let mut consecutive_failures = 0;
+    let mut consecutive_successes = 0;
 
     // Initial startup period
     for i in 0..config.startup_failure_threshold {
@@ -143,52 +315,82 @@ async fn monitor_container_health(
                     status.record_failure();
                     status
                         .transition_to(HealthState::Failed, Some(format!("Startup failed: {}", e)));
+                    webhooks::notify_transition(&service_name, &container_name, status);
                 }
                 return;
             }
         }
     }
 
+    if let Some(probe) = &config.startup_probe {
+        if !probes::run_startup_probe(
+            &service_name,
+            &container_name,
+            monitor_id,
+            probe,
+            runtime.as_ref(),
+        )
+        .await
+        {
+            return;
+        }
+        register_backends(&service_name, &container_name).await;
+    }
+
+    // Give the application time to warm up before it is judged
+    tokio::time::sleep(config.initial_delay).await;
     loop {
-        let mut is_healthy = true;
-        let container_stats = runtime.inspect_container(&container_name).await;
+        // A pod paused by preemption is judged again once it resumes
+        if preemption::is_paused(&container_name) {
+            tokio::time::sleep(config.period()).await;
+            continue;
+        }
 
+        // Checks run before taking the lock, as an exec can take its whole timeout
+        let verdict = match runtime.inspect_container(&container_name).await {
+            Ok(stats) => Ok(run_checks(&container_name, &stats, &config, runtime.as_ref()).await),
+            Err(e) => Err(e),
+        };
+
+        let mut became_healthy = false;
         {
             let mut health_map = health_store.write().await;
             let current_status = match health_map.get_mut(&container_name) {
-                Some(status) => status,
-                None => return, // Container removed, stop monitoring
+                Some(status) if status.monitor_id == monitor_id => status,
+                _ => return, // Container removed or monitoring restarted
             };
 
             // First check if container inspection succeeded
-            match &container_stats {
-                Ok(stats) => {
-                    // TCP health check if configured
-                    if let Some(tcp_check) = &config.tcp_check {
-                        is_healthy =
-                            check_tcp_health(&stats.ip_address, tcp_check.port, tcp_check.timeout)
-                                .await;
+            match verdict {
+                Ok(Verdict::Healthy) => {
+                    consecutive_failures = 0;
+                    consecutive_successes += 1;
+                    if !matches!(current_status.state, HealthState::Healthy)
+                        && consecutive_successes >= config.success_threshold
+                    {
+                        current_status.transition_to(HealthState::Healthy, None);
+                        webhooks::notify_transition(&service_name, &container_name, current_status);
+                        became_healthy = true;
                     }
+                }
+                Ok(Verdict::Pending) => {}
+                Ok(verdict) => {
+                    consecutive_failures += 1;
+                    consecutive_successes = 0;
+                    current_status.record_failure();
 
-                    if is_healthy {
-                        consecutive_failures = 0;
-                        if !matches!(current_status.state, HealthState::Healthy) {
-                            current_status.transition_to(HealthState::Healthy, None);
-                        }
-                    } else {
-                        consecutive_failures += 1;
-                        current_status.record_failure();
-
-                        if consecutive_failures >= config.liveness_failure_threshold {
-                            current_status.transition_to(
-                                HealthState::Unhealthy,
-                                Some("Health check failed".to_string()),
-                            );
-                        }
+                    if matches!(verdict, Verdict::Unhealthy)
+                        || consecutive_failures >= config.failure_threshold()
+                    {
+                        current_status.transition_to(
+                            HealthState::Unhealthy,
+                            Some("Health check failed".to_string()),
+                        );
                     }
                 }
                 Err(e) => {
                     consecutive_failures += 1;
+                    consecutive_successes = 0;
                     current_status.record_failure();
 
                     if consecutive_failures >= config.liveness_failure_threshold {
@@ -196,21 +398,89 @@ async fn monitor_container_health(
                             HealthState::Failed,
                             Some(format!("Container inspection failed: {}", e)),
                         );
+                        webhooks::notify_transition(&service_name, &container_name, current_status);
                         return;
                     }
                 }
             }
         }
+        if became_healthy {
+            register_backends(&service_name, &container_name).await;
+        }
 
-        tokio::time::sleep(config.liveness_period).await;
+        tokio::time::sleep(config.period()).await;
     }
 }
 
+/// Whether the proxy may route to the container: its health checks pass and
+/// it isn't failing its readiness probe. Unmonitored containers, such as
+/// adopted ones, always may.
+pub async fn is_routable(container_name: &str) -> bool {
+    get_container_health(container_name)
+        .await
+        .is_none_or(|health| matches!(health.state, HealthState::Healthy) && health.ready)
+}
+
+// Add a container that just became healthy to its service's load balancers.
+// One not in the instance store yet is added by whoever stores it.
+async fn register_backends(service_name: &str, container_name: &str) {
+    if !is_routable(container_name).await {
+        return;
+    }
+    if let Some(container) = probes::tracked_container(service_name, container_name).await {
+        set_container_backends(service_name, &container, true).await;
+        slog::info!(slog_scope::logger(), "Container is ready, added to load balancer";
+            "service" => service_name,
+            "container" => container_name
+        );
+    }
+}
+
+/// Count an in-place restart, resuming monitoring if it had given up on the
+/// container. Containers with a startup probe start over in the starting state.
+pub async fn record_container_restart(
+    service_name: &str,
+    container_name: &str,
+    config: Option<HealthCheckConfig>,
+) -> Result<()> {
+    let previous = get_container_health(container_name).await;
+    let has_startup_probe = config
+        .as_ref()
+        .is_some_and(|config| config.startup_probe.is_some());
+    if has_startup_probe
+        || previous
+            .as_ref()
+            .is_none_or(|health| matches!(health.state, HealthState::Failed))
+    {
+        initialize_health_monitoring(service_name, container_name, config).await?;
+    }
+
+    if let Some(store) = CONTAINER_HEALTH.get() {
+        let mut health_map = store.write().await;
+        if let Some(status) = health_map.get_mut(container_name) {
+            if let Some(previous) = previous {
+                status.restart_count = previous.restart_count;
+                // The readiness probe keeps running across the restart
+                status.ready = previous.ready;
+                // Flapping is judged across restarts
+                status.history = previous.history;
+                status.update_flapping();
+            }
+            status.record_restart();
+        }
+    }
+    Ok(())
+}
+
 // Example read operation
 pub async fn get_container_health(container_name: &str) -> Option<ContainerHealthState> {
     if let Some(store) = CONTAINER_HEALTH.get() {
         let health_map = store.read().await;
-        return health_map.get(container_name).cloned();
+        // The flag clears once the transitions age out of the window
+        return health_map.get(container_name).cloned().map(|mut health| {
+            health.update_flapping();
+            health
+        });
     }
     None
 }
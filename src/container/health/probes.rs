@@ -0,0 +1,302 @@
+// src/container/health/probes.rs
+//
+// HTTP liveness and readiness probes. A container that fails its liveness
+// probe `failure_threshold` times in a row is restarted in place; one that
+// fails its readiness probe is taken out of the load balancer, and put back
+// once the probe passes `success_threshold` times in a row. Probes stop with the container's health
+// monitoring. A startup probe holds off liveness checking until the container
+// has booted, and fails it if it doesn't boot in time, for the remediation
+// loop to restart.
+use rustc_hash::FxHashSet;
+use std::sync::{Mutex, OnceLock};
+use tokio::net::TcpStream;
+
+use super::config::{HealthCheckConfig, HttpProbe, StartupProbe};
+use super::{get_container_health, webhooks, HealthState, CONTAINER_HEALTH};
+use crate::container::restart::restart_in_place;
+use crate::container::{
+    find_host_port, ContainerMetadata, ContainerRuntime, ContainerStats, INSTANCE_STORE,
+    LOOPBACK_ADDRESS,
+};
+use crate::proxy::set_container_backends;
+
+// Probes running per container, so resumed monitoring doesn't start them twice
+static RUNNING_PROBES: OnceLock<Mutex<FxHashSet<(String, ProbeKind)>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ProbeKind {
+    Liveness,
+    Readiness,
+}
+
+fn running_probes() -> &'static Mutex<FxHashSet<(String, ProbeKind)>> {
+    RUNNING_PROBES.get_or_init(|| Mutex::new(FxHashSet::default()))
+}
+
+impl ProbeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ProbeKind::Liveness => "liveness",
+            ProbeKind::Readiness => "readiness",
+        }
+    }
+}
+
+pub(super) fn start_probes(service_name: &str, container_name: &str, config: &HealthCheckConfig) {
+    // The liveness probe waits for the startup probe to pass
+    let after_startup = config.startup_probe.is_some();
+    for (kind, probe) in [
+        (ProbeKind::Liveness, config.liveness_probe.clone()),
+        (ProbeKind::Readiness, config.readiness_probe.clone()),
+    ] {
+        let Some(probe) = probe else {
+            continue;
+        };
+        let key = (container_name.to_string(), kind);
+        if !running_probes().lock().unwrap().insert(key.clone()) {
+            continue;
+        }
+
+        let service_name = service_name.to_string();
+        tokio::spawn(async move {
+            run_probe(&service_name, &key.0, kind, &probe, after_startup).await;
+            running_probes().lock().unwrap().remove(&key);
+        });
+    }
+}
+
+// The container as Orbit tracks it, for its address and ports
+pub(super) async fn tracked_container(
+    service_name: &str,
+    container_name: &str,
+) -> Option<ContainerMetadata> {
+    let store = INSTANCE_STORE.get()?.read().await;
+    store
+        .get(service_name)?
+        .values()
+        .flat_map(|instance| instance.containers.iter())
+        .find(|container| container.name == container_name)
+        .cloned()
+}
+
+async fn probe_passes(
+    client: &reqwest::Client,
+    container: &ContainerMetadata,
+    probe: &HttpProbe,
+) -> bool {
+    let address = container
+        .ports
+        .iter()
+        .find(|p| p.port == probe.port)
+        .map(|p| p.backend_address(&container.ip_address))
+        .unwrap_or_else(|| format!("{}:{}", container.ip_address, probe.port));
+    let url = format!("http://{}/{}", address, probe.path.trim_start_matches('/'));
+
+    match client.get(&url).send().await {
+        Ok(response) => response.status().is_success() || response.status().is_redirection(),
+        Err(_) => false,
+    }
+}
+
+async fn set_ready(container_name: &str, ready: bool) {
+    if let Some(store) = CONTAINER_HEALTH.get() {
+        if let Some(status) = store.write().await.get_mut(container_name) {
+            status.ready = ready;
+        }
+    }
+}
+
+async fn run_probe(
+    service_name: &str,
+    container_name: &str,
+    kind: ProbeKind,
+    probe: &HttpProbe,
+    after_startup: bool,
+) {
+    let log = slog_scope::logger();
+    let client = match reqwest::Client::builder()
+        .timeout(probe.timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            slog::error!(log, "Failed to create probe client"; "error" => e.to_string());
+            return;
+        }
+    };
+    let mut failures = 0;
+    let mut successes = 0;
+    let mut ready = true;
+
+    tokio::time::sleep(probe.initial_delay).await;
+    loop {
+        tokio::time::sleep(probe.interval).await;
+
+        // Monitoring ends when the container is removed
+        let Some(health) = get_container_health(container_name).await else {
+            return;
+        };
+        if kind == ProbeKind::Liveness
+            && after_startup
+            && matches!(health.state, HealthState::Starting)
+        {
+            failures = 0;
+            continue;
+        }
+        let Some(container) = tracked_container(service_name, container_name).await else {
+            continue;
+        };
+        // Restarting containers are expected to fail
+        if container.status != "running" {
+            continue;
+        }
+
+        if probe_passes(&client, &container, probe).await {
+            failures = 0;
+            successes += 1;
+            if kind == ProbeKind::Readiness && !ready && successes >= probe.success_threshold {
+                ready = true;
+                set_ready(container_name, true).await;
+                set_container_backends(service_name, &container, true).await;
+                slog::info!(log, "Container passed its readiness probe, added back to load balancer";
+                    "service" => service_name,
+                    "container" => container_name
+                );
+            }
+            continue;
+        }
+
+        failures += 1;
+        successes = 0;
+        slog::debug!(log, "Probe failed";
+            "service" => service_name,
+            "container" => container_name,
+            "probe" => kind.as_str(),
+            "failures" => failures
+        );
+        if failures < probe.failure_threshold {
+            continue;
+        }
+
+        match kind {
+            ProbeKind::Liveness => {
+                failures = 0;
+                slog::warn!(log, "Container failed its liveness probe, restarting";
+                    "service" => service_name,
+                    "container" => container_name
+                );
+                if let Err(e) = restart_in_place(service_name, container_name).await {
+                    slog::error!(log, "Failed to restart container";
+                        "service" => service_name,
+                        "container" => container_name,
+                        "error" => e.to_string()
+                    );
+                }
+            }
+            ProbeKind::Readiness if ready => {
+                ready = false;
+                set_ready(container_name, false).await;
+                set_container_backends(service_name, &container, false).await;
+                slog::warn!(log, "Container failed its readiness probe, removed from load balancer";
+                    "service" => service_name,
+                    "container" => container_name
+                );
+            }
+            ProbeKind::Readiness => {}
+        }
+    }
+}
+
+async fn startup_probe_passes(stats: &ContainerStats, probe: &StartupProbe) -> bool {
+    // Loopback-published containers are checked through their host port
+    let port = if stats.ip_address == LOOPBACK_ADDRESS {
+        find_host_port(stats, probe.port).unwrap_or(probe.port)
+    } else {
+        probe.port
+    };
+    let address = format!("{}:{}", stats.ip_address, port);
+
+    let Some(path) = &probe.path else {
+        return matches!(
+            tokio::time::timeout(probe.timeout, TcpStream::connect(&address)).await,
+            Ok(Ok(_))
+        );
+    };
+    let Ok(client) = reqwest::Client::builder()
+        .timeout(probe.timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+    else {
+        return false;
+    };
+    match client
+        .get(format!("http://{}{}", address, path))
+        .send()
+        .await
+    {
+        Ok(response) => response.status().is_success() || response.status().is_redirection(),
+        Err(_) => false,
+    }
+}
+
+/// Check the startup probe every `period` until it passes, which makes the
+/// container healthy. After `failure_threshold` failures in a row the container
+/// is failed. Returns whether the probe passed.
+pub(super) async fn run_startup_probe(
+    service_name: &str,
+    container_name: &str,
+    monitor_id: u64,
+    probe: &StartupProbe,
+    runtime: &dyn ContainerRuntime,
+) -> bool {
+    let log = slog_scope::logger();
+    let Some(health_store) = CONTAINER_HEALTH.get() else {
+        return false;
+    };
+    let mut failures = 0;
+
+    tokio::time::sleep(probe.initial_delay).await;
+    loop {
+        let passed = match runtime.inspect_container(container_name).await {
+            Ok(stats) => startup_probe_passes(&stats, probe).await,
+            Err(_) => false,
+        };
+
+        {
+            let mut health_map = health_store.write().await;
+            let status = match health_map.get_mut(container_name) {
+                Some(status) if status.monitor_id == monitor_id => status,
+                _ => return false,
+            };
+
+            if passed {
+                status.transition_to(HealthState::Healthy, None);
+                webhooks::notify_transition(service_name, container_name, status);
+                slog::info!(log, "Container passed its startup probe";
+                    "service" => service_name,
+                    "container" => container_name,
+                    "failures" => failures
+                );
+                return true;
+            }
+
+            failures += 1;
+            status.record_failure();
+            if failures >= probe.failure_threshold {
+                status.transition_to(
+                    HealthState::Failed,
+                    Some(format!("Startup probe failed {} times", failures)),
+                );
+                webhooks::notify_transition(service_name, container_name, status);
+                slog::warn!(log, "Container failed its startup probe";
+                    "service" => service_name,
+                    "container" => container_name
+                );
+                return false;
+            }
+        }
+
+        tokio::time::sleep(probe.period).await;
+    }
+}
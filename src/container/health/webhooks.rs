@@ -0,0 +1,177 @@
+// src/container/health/webhooks.rs
+//
+// Webhook notifications of health events. When a container turns Failed, or
+// turns Healthy again after having failed, or a supervised container exits
+// with a non-zero code, a JSON payload is POSTed to every webhook of the
+// daemon config and of the container's service. Delivery is best-effort: a
+// webhook that can't be reached is logged and not retried.
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use super::{get_container_health, ContainerHealthState, HealthState};
+use crate::config::daemon::get_daemon_config;
+use crate::config::get_config_by_service;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Sent with every request, e.g. an Authorization header
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    #[serde(with = "humantime_serde", default = "default_webhook_timeout")]
+    pub timeout: Duration,
+}
+
+fn default_webhook_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> Result<()> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|e| anyhow!("invalid webhook url '{}': {}", self.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!("webhook url '{}' must be http or https", self.url));
+        }
+        if self.timeout.is_zero() {
+            return Err(anyhow!("webhook timeout must be positive"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthEvent {
+    Failed,
+    Recovered,
+    Crashed,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthEventPayload {
+    event: HealthEvent,
+    service_name: String,
+    container_name: String,
+    state: HealthState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    restart_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i64>,
+    /// Where the crash dumps collected for a crash were moved to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crash_dump: Option<String>,
+    timestamp: String,
+}
+
+// The event a container's latest transition amounts to. A container recovers
+// when it turns healthy and the last state it settled in before was Failed,
+// possibly with a restart in between.
+fn event_for(status: &ContainerHealthState) -> Option<HealthEvent> {
+    match status.state {
+        HealthState::Failed => Some(HealthEvent::Failed),
+        HealthState::Healthy => {
+            let recovered = status
+                .history
+                .iter()
+                .rev()
+                .skip(1)
+                .find(|transition| {
+                    matches!(transition.to, HealthState::Healthy | HealthState::Failed)
+                })
+                .is_some_and(|transition| matches!(transition.to, HealthState::Failed));
+            recovered.then_some(HealthEvent::Recovered)
+        }
+        _ => None,
+    }
+}
+
+/// Notify the webhooks if the container just failed or recovered. Called
+/// right after a transition; sends in the background.
+pub fn notify_transition(service_name: &str, container_name: &str, status: &ContainerHealthState) {
+    let Some(event) = event_for(status) else {
+        return;
+    };
+    let payload = HealthEventPayload {
+        event,
+        service_name: service_name.to_string(),
+        container_name: container_name.to_string(),
+        state: status.state.clone(),
+        message: status.message.clone(),
+        restart_count: status.restart_count,
+        exit_code: None,
+        crash_dump: None,
+        timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+    };
+    tokio::spawn(deliver(payload));
+}
+
+/// Notify the webhooks that a container exited with a non-zero code, linking
+/// the crash dumps collected for it. Sends in the background.
+pub fn notify_crash(
+    service_name: &str,
+    container_name: &str,
+    exit_code: i64,
+    crash_dump: Option<PathBuf>,
+) {
+    let service_name = service_name.to_string();
+    let container_name = container_name.to_string();
+    tokio::spawn(async move {
+        let status = get_container_health(&container_name)
+            .await
+            .unwrap_or_default();
+        let payload = HealthEventPayload {
+            event: HealthEvent::Crashed,
+            service_name,
+            container_name,
+            state: status.state,
+            message: None,
+            restart_count: status.restart_count,
+            exit_code: Some(exit_code),
+            crash_dump: crash_dump.map(|path| path.display().to_string()),
+            timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        };
+        deliver(payload).await;
+    });
+}
+
+async fn deliver(mut payload: HealthEventPayload) {
+    // Messages quote container output, which may include secrets
+    payload.message = payload
+        .message
+        .map(|message| crate::redact::redact(&message).into_owned());
+    let mut webhooks = get_daemon_config().health_webhooks;
+    if let Some(config) = get_config_by_service(&payload.service_name).await {
+        webhooks.extend(config.health_webhooks);
+    }
+    for webhook in &webhooks {
+        if let Err(e) = send(webhook, &payload).await {
+            slog::warn!(slog_scope::logger(), "Failed to send health webhook";
+                "service" => &payload.service_name,
+                "container" => &payload.container_name,
+                "url" => &webhook.url,
+                "error" => e.to_string()
+            );
+        }
+    }
+}
+
+async fn send(webhook: &WebhookConfig, payload: &HealthEventPayload) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(webhook.timeout)
+        .build()?;
+    let mut request = client.post(&webhook.url).json(payload);
+    for (name, value) in &webhook.headers {
+        request = request.header(name, value);
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("webhook answered {}", response.status()));
+    }
+    Ok(())
+}
@@ -0,0 +1,235 @@
+// src/container/identity.rs
+//
+// Pod identity tokens. A service with `identity` gets a token in each of its
+// containers, as an env var or a read-only file, naming the service, the pod
+// and when the container was started, signed with a key only this Orbit
+// holds. A backend receiving a call from another Orbit-managed service checks
+// the caller's token with the identity API, instead of both sharing a static
+// secret. Tokens are only valid while their pod is running. The signing key
+// is kept in the state directory, so tokens survive daemon restarts.
+use anyhow::{anyhow, Result};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use super::secrets::{is_absolute_mount, ResolvedSecret, SecretValue};
+use super::INSTANCE_STORE;
+use crate::config::ServiceConfig;
+
+static IDENTITY_KEY: OnceLock<hmac::Key> = OnceLock::new();
+
+const KEY_FILE: &str = "identity.key";
+const KEY_LEN: usize = 32;
+const TOKEN_VERSION: &str = "orbit1";
+// Name of the token among the container's secrets, and its file name
+pub const SECRET_NAME: &str = "orbit-identity";
+pub const DEFAULT_ENV: &str = "ORBIT_IDENTITY_TOKEN";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct IdentityConfig {
+    /// Environment variable holding the token (default: ORBIT_IDENTITY_TOKEN,
+    /// unless the token is mounted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    /// Absolute path of a read-only file holding the token inside the container
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount: Option<PathBuf>,
+}
+
+impl IdentityConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.env.as_deref().is_some_and(str::is_empty) {
+            return Err(anyhow!("env must not be empty"));
+        }
+        if let Some(mount) = &self.mount {
+            if !is_absolute_mount(mount) {
+                return Err(anyhow!("mount must be an absolute file path"));
+            }
+        }
+        Ok(())
+    }
+
+    fn env(&self) -> Option<String> {
+        match (&self.env, &self.mount) {
+            (Some(env), _) => Some(env.clone()),
+            (None, Some(_)) => None,
+            (None, None) => Some(DEFAULT_ENV.to_string()),
+        }
+    }
+}
+
+/// Who a valid token was issued to
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PodIdentity {
+    pub service: String,
+    pub pod: Uuid,
+    #[serde(with = "humantime_serde")]
+    pub started: SystemTime,
+}
+
+/// Load the signing key from the state directory, creating it on first start
+pub fn initialize_identity_key(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let path = state_dir.join(KEY_FILE);
+
+    let key = if path.exists() {
+        let contents = std::fs::read_to_string(&path)?;
+        decode_hex(contents.trim())
+            .filter(|key| key.len() == KEY_LEN)
+            .ok_or_else(|| anyhow!("Invalid identity key in {}", path.display()))?
+    } else {
+        // Only readable by Orbit from the start, and never written through a
+        // file someone else put there
+        let key = generate_key()?;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+        file.write_all(encode_hex(&key).as_bytes())?;
+        file.sync_all()?;
+        key
+    };
+
+    IDENTITY_KEY
+        .set(hmac::Key::new(hmac::HMAC_SHA256, &key))
+        .map_err(|_| anyhow!("Identity key already initialized"))
+}
+
+// Without a state directory, e.g. in tests, tokens last as long as the process
+fn identity_key() -> &'static hmac::Key {
+    IDENTITY_KEY.get_or_init(|| {
+        let key = generate_key().expect("Failed to generate identity key");
+        hmac::Key::new(hmac::HMAC_SHA256, &key)
+    })
+}
+
+fn generate_key() -> Result<Vec<u8>> {
+    let mut key = vec![0; KEY_LEN];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| anyhow!("Failed to generate identity key"))?;
+    Ok(key)
+}
+
+/// A token for the pod of the service, started now
+pub fn issue(service_name: &str, pod: Uuid) -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let claims = format!("{}.{}.{}.{}", TOKEN_VERSION, service_name, pod, started);
+    let signature = hmac::sign(identity_key(), claims.as_bytes());
+    format!("{}.{}", claims, encode_hex(signature.as_ref()))
+}
+
+/// The identity a token was issued to, if it was signed by this Orbit and
+/// its pod is still running
+pub async fn verify(token: &str) -> Result<PodIdentity> {
+    let identity = verify_signature(token)?;
+
+    let running = match INSTANCE_STORE.get() {
+        Some(store) => store
+            .read()
+            .await
+            .get(&identity.service)
+            .is_some_and(|instances| instances.contains_key(&identity.pod)),
+        None => false,
+    };
+    if !running {
+        return Err(anyhow!(
+            "Pod {} of service {} is not running",
+            identity.pod,
+            identity.service
+        ));
+    }
+    Ok(identity)
+}
+
+// The identity a token was issued to, if it was signed by this Orbit
+fn verify_signature(token: &str) -> Result<PodIdentity> {
+    let (claims, signature) = token
+        .trim()
+        .rsplit_once('.')
+        .ok_or_else(|| anyhow!("Malformed token"))?;
+    let signature = decode_hex(signature).ok_or_else(|| anyhow!("Malformed token"))?;
+    hmac::verify(identity_key(), claims.as_bytes(), &signature)
+        .map_err(|_| anyhow!("Invalid signature"))?;
+
+    let parts: Vec<&str> = claims.split('.').collect();
+    let [TOKEN_VERSION, service_name, pod, started] = parts[..] else {
+        return Err(anyhow!("Malformed token"));
+    };
+    let pod: Uuid = pod.parse().map_err(|_| anyhow!("Malformed token"))?;
+    let started: u64 = started.parse().map_err(|_| anyhow!("Malformed token"))?;
+
+    Ok(PodIdentity {
+        service: service_name.to_string(),
+        pod,
+        started: UNIX_EPOCH + Duration::from_secs(started),
+    })
+}
+
+/// The pod's token as a secret of each of its containers, for services with
+/// an identity
+pub fn identity_secret(service_config: &ServiceConfig, pod: Uuid) -> Option<ResolvedSecret> {
+    let identity = service_config.identity.as_ref()?;
+    Some(ResolvedSecret {
+        name: SECRET_NAME.to_string(),
+        value: SecretValue::from(issue(&service_config.name, pod)),
+        env: identity.env(),
+        mount: identity.mount.clone(),
+    })
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    if !input.len().is_multiple_of(2) || !input.is_ascii() {
+        return None;
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&input[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issued_token_verifies() {
+        let pod = Uuid::new_v4();
+        let token = issue("web", pod);
+
+        let identity = verify_signature(&token).unwrap();
+        assert_eq!(identity.service, "web");
+        assert_eq!(identity.pod, pod);
+        let age = SystemTime::now().duration_since(identity.started).unwrap();
+        assert!(age < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_tampered_token_is_rejected() {
+        let token = issue("web", Uuid::new_v4());
+
+        let other_service = token.replacen(".web.", ".admin.", 1);
+        assert!(verify_signature(&other_service).is_err());
+
+        let (claims, signature) = token.rsplit_once('.').unwrap();
+        let flipped = if signature.starts_with('0') { "1" } else { "0" };
+        let other_signature = format!("{}.{}{}", claims, flipped, &signature[1..]);
+        assert!(verify_signature(&other_signature).is_err());
+
+        assert!(verify_signature(claims).is_err());
+    }
+}
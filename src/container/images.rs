@@ -0,0 +1,296 @@
+// src/container/images.rs
+//
+// Image layer statistics and prefetching. Services built on the same base
+// image share its layers on disk, and once one of them is pulled the others
+// only need their own layers. The images API reports which layers each
+// service image shares with the others. Prefetching pulls a list of images
+// ahead of time, e.g. a new node taking another node's images with the most
+// shared first, so a first deploy doesn't wait for its pulls.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use crate::config::{ServiceConfig, CONFIG_STORE};
+use crate::container::RUNTIME;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageReport {
+    /// The images of every service, those sharing the most layers first
+    pub images: Vec<ImageUsage>,
+    /// Layers in more than one image, the most widely shared first
+    pub shared_layers: Vec<SharedLayer>,
+    /// Layers on disk, each counted once
+    pub distinct_layers: usize,
+    /// Layers stored once for several images rather than once per image
+    pub deduplicated_layers: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageUsage {
+    pub image: String,
+    pub services: Vec<String>,
+    /// Whether the image has been pulled on this node
+    pub present: bool,
+    pub layers: usize,
+    /// Its layers that other images have too
+    pub shared_layers: usize,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SharedLayer {
+    pub layer: String,
+    pub images: Vec<String>,
+    pub services: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrefetchRequest {
+    pub images: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrefetchResult {
+    pub image: String,
+    /// "pulled", "present" or "failed"
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Every service config, and the images each of its containers runs
+async fn service_images() -> Result<Vec<(ServiceConfig, BTreeSet<String>)>> {
+    let store = CONFIG_STORE
+        .get()
+        .ok_or_else(|| anyhow!("Config store not initialized"))?
+        .read()
+        .await;
+    Ok(store
+        .values()
+        .map(|(_, config)| {
+            let images = config
+                .spec
+                .containers
+                .iter()
+                .map(|container| container.image.clone())
+                .collect();
+            (config.clone(), images)
+        })
+        .collect())
+}
+
+/// Which layers the services' images share, from the images present locally
+pub async fn image_report() -> Result<ImageReport> {
+    let runtime = RUNTIME
+        .get()
+        .ok_or_else(|| anyhow!("Runtime not initialized"))?;
+
+    let mut services_by_image: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (config, images) in service_images().await? {
+        for image in images {
+            services_by_image
+                .entry(image)
+                .or_default()
+                .insert(config.name.clone());
+        }
+    }
+
+    let mut layers_by_image = BTreeMap::new();
+    let mut images_by_layer: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for image in services_by_image.keys() {
+        let Some(layers) = runtime.image_layers(image).await? else {
+            continue;
+        };
+        for layer in &layers.layers {
+            images_by_layer
+                .entry(layer.clone())
+                .or_default()
+                .insert(image.clone());
+        }
+        layers_by_image.insert(image.clone(), layers);
+    }
+
+    let mut images: Vec<ImageUsage> = services_by_image
+        .iter()
+        .map(|(image, services)| {
+            let layers = layers_by_image.get(image);
+            ImageUsage {
+                image: image.clone(),
+                services: services.iter().cloned().collect(),
+                present: layers.is_some(),
+                layers: layers.map_or(0, |layers| layers.layers.len()),
+                shared_layers: layers.map_or(0, |layers| {
+                    layers
+                        .layers
+                        .iter()
+                        .filter(|layer| images_by_layer[*layer].len() > 1)
+                        .count()
+                }),
+                size: layers.map_or(0, |layers| layers.size),
+            }
+        })
+        .collect();
+    images.sort_by_key(|image| std::cmp::Reverse(image.shared_layers));
+
+    let mut shared_layers: Vec<SharedLayer> = images_by_layer
+        .iter()
+        .filter(|(_, images)| images.len() > 1)
+        .map(|(layer, images)| SharedLayer {
+            layer: layer.clone(),
+            images: images.iter().cloned().collect(),
+            services: images
+                .iter()
+                .flat_map(|image| services_by_image[image].iter().cloned())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        })
+        .collect();
+    shared_layers.sort_by_key(|layer| std::cmp::Reverse(layer.images.len()));
+
+    Ok(ImageReport {
+        distinct_layers: images_by_layer.len(),
+        deduplicated_layers: images_by_layer
+            .values()
+            .map(|images| images.len() - 1)
+            .sum(),
+        images,
+        shared_layers,
+    })
+}
+
+/// Pull the images one after another, with the pull secrets of a service
+/// using each image if there is one
+pub async fn prefetch(images: &[String]) -> Result<Vec<PrefetchResult>> {
+    let runtime = RUNTIME
+        .get()
+        .ok_or_else(|| anyhow!("Runtime not initialized"))?;
+    let services = service_images().await?;
+
+    let mut results = Vec::new();
+    for image in images {
+        let config = services
+            .iter()
+            .find(|(_, service_images)| service_images.contains(image))
+            .map(|(config, _)| config);
+        let result = runtime.prefetch_image(image, config).await;
+        let log = slog_scope::logger();
+        results.push(match result {
+            Ok(pulled) => {
+                if pulled {
+                    slog::info!(log, "Image prefetched"; "image" => image);
+                }
+                PrefetchResult {
+                    image: image.clone(),
+                    status: if pulled { "pulled" } else { "present" }.to_string(),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                slog::warn!(log, "Failed to prefetch image";
+                    "image" => image,
+                    "error" => e.to_string()
+                );
+                PrefetchResult {
+                    image: image.clone(),
+                    status: "failed".to_string(),
+                    error: Some(e.to_string()),
+                }
+            }
+        });
+    }
+    Ok(results)
+}
+
+/// Have the daemon at `api_url` pull `images`, or the images of the node at
+/// `from` (its own images when not given) that it doesn't have yet. With
+/// `shared_only`, only images sharing layers with another image are taken.
+/// False when a pull failed.
+pub async fn run(
+    api_url: &str,
+    from: Option<&str>,
+    images: &[String],
+    shared_only: bool,
+) -> Result<bool> {
+    let api_url = api_url.trim_end_matches('/');
+    let images = if images.is_empty() {
+        let source = from.unwrap_or(api_url).trim_end_matches('/');
+        let report: ImageReport = get_json(&format!("{}/images", source)).await?;
+        let present: BTreeSet<String> = if from.is_some() {
+            let local: ImageReport = get_json(&format!("{}/images", api_url)).await?;
+            local
+                .images
+                .into_iter()
+                .filter(|image| image.present)
+                .map(|image| image.image)
+                .collect()
+        } else {
+            report
+                .images
+                .iter()
+                .filter(|image| image.present)
+                .map(|image| image.image.clone())
+                .collect()
+        };
+        report
+            .images
+            .into_iter()
+            .filter(|image| !shared_only || image.shared_layers > 0)
+            .map(|image| image.image)
+            .filter(|image| !present.contains(image))
+            .collect()
+    } else {
+        images.to_vec()
+    };
+    if images.is_empty() {
+        println!("Nothing to prefetch");
+        return Ok(true);
+    }
+
+    // Pulls can take a while
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(3600))
+        .build()?;
+    let response = client
+        .post(format!("{}/images/prefetch", api_url))
+        .json(&PrefetchRequest { images })
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to reach Orbit at {}: {}", api_url, e))?;
+    if !response.status().is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(anyhow!(
+            "Prefetch failed: {}",
+            body["error"].as_str().unwrap_or("unknown error")
+        ));
+    }
+
+    let results: Vec<PrefetchResult> = response.json().await?;
+    for result in &results {
+        match &result.error {
+            Some(error) => println!("{}: {} ({})", result.image, result.status, error),
+            None => println!("{}: {}", result.image, result.status),
+        }
+    }
+    Ok(results.iter().all(|result| result.error.is_none()))
+}
+
+async fn get_json<T: for<'de> Deserialize<'de>>(url: &str) -> Result<T> {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to reach {}: {}", url, e))?;
+    if !response.status().is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(anyhow!(
+            "{} failed: {}",
+            url,
+            body["error"].as_str().unwrap_or("unknown error")
+        ));
+    }
+    Ok(response.json().await?)
+}
@@ -0,0 +1,215 @@
+// src/container/lifecycle.rs
+//
+// post_start hooks run once a container is up, pre_stop hooks before Orbit
+// stops it, so applications can warm up or drain and shut down cleanly.
+// Hooks are registered with the container when it starts or is adopted, which
+// keeps pre_stop available after the service's config has been removed.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+
+use super::{ContainerPortMetadata, ContainerRuntime, RUNTIME};
+use crate::config::{parse_container_name, ServiceConfig};
+
+static HOOKS: OnceLock<RwLock<FxHashMap<String, RegisteredHooks>>> = OnceLock::new();
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Lifecycle {
+    /// Runs right after the container starts, and again after in-place restarts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_start: Option<LifecycleHook>,
+    /// Runs before the container is stopped; stopping waits for it to finish
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_stop: Option<LifecycleHook>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LifecycleHook {
+    #[serde(flatten)]
+    pub action: HookAction,
+    #[serde(with = "humantime_serde", default = "default_hook_timeout")]
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum HookAction {
+    /// Command run inside the container, failing on a non-zero exit code
+    Exec { command: Vec<String> },
+    /// GET request to a container port, failing on a non-2xx response
+    Http {
+        port: u16,
+        #[serde(default = "default_hook_path")]
+        path: String,
+    },
+}
+
+fn default_hook_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_hook_path() -> String {
+    "/".to_string()
+}
+
+#[derive(Debug, Clone)]
+struct RegisteredHooks {
+    service_name: String,
+    lifecycle: Lifecycle,
+    ip_address: String,
+    ports: Vec<ContainerPortMetadata>,
+}
+
+fn hooks() -> &'static RwLock<FxHashMap<String, RegisteredHooks>> {
+    HOOKS.get_or_init(|| RwLock::new(FxHashMap::default()))
+}
+
+/// Register the hooks of a newly started pod's containers and run their post_start hooks
+pub fn register_hooks(
+    service_name: &str,
+    config: &ServiceConfig,
+    started_containers: &[(String, String, Vec<ContainerPortMetadata>)],
+) {
+    register(service_name, config, started_containers, true);
+}
+
+/// Register the hooks of containers adopted from a previous run, so their
+/// pre_stop hooks run when they are stopped. Their post_start hooks ran when
+/// they were started.
+pub fn register_adopted_hooks(
+    service_name: &str,
+    config: &ServiceConfig,
+    adopted_containers: &[(String, String, Vec<ContainerPortMetadata>)],
+) {
+    register(service_name, config, adopted_containers, false);
+}
+
+fn register(
+    service_name: &str,
+    config: &ServiceConfig,
+    containers: &[(String, String, Vec<ContainerPortMetadata>)],
+    post_start: bool,
+) {
+    for (container_name, ip_address, ports) in containers {
+        let lifecycle = parse_container_name(container_name)
+            .ok()
+            .and_then(|parts| {
+                config
+                    .spec
+                    .containers
+                    .iter()
+                    .find(|c| c.name == parts.container_name)
+            })
+            .and_then(|container| container.lifecycle.clone());
+        let Some(lifecycle) = lifecycle else {
+            continue;
+        };
+
+        let has_post_start = lifecycle.post_start.is_some();
+        hooks().write().unwrap().insert(
+            container_name.clone(),
+            RegisteredHooks {
+                service_name: service_name.to_string(),
+                lifecycle,
+                ip_address: ip_address.clone(),
+                ports: ports.clone(),
+            },
+        );
+
+        if post_start && has_post_start {
+            let container_name = container_name.clone();
+            tokio::spawn(async move { run_post_start(&container_name).await });
+        }
+    }
+}
+
+/// Run the container's post_start hook, if it has one
+pub async fn run_post_start(container_name: &str) {
+    let Some(registered) = hooks().read().unwrap().get(container_name).cloned() else {
+        return;
+    };
+    let Some(hook) = &registered.lifecycle.post_start else {
+        return;
+    };
+    let Some(runtime) = RUNTIME.get() else {
+        return;
+    };
+
+    if let Err(e) = run_hook(runtime, container_name, &registered, hook).await {
+        slog::error!(slog_scope::logger(), "post_start hook failed";
+            "service" => &registered.service_name,
+            "container" => container_name,
+            "error" => e.to_string()
+        );
+    }
+}
+
+/// Stop a container, running its pre_stop hook first. A failed hook is logged
+/// and does not prevent the container from being stopped.
+pub async fn stop_container(
+    runtime: &Arc<dyn ContainerRuntime>,
+    container_name: &str,
+) -> Result<()> {
+    let registered = hooks().write().unwrap().remove(container_name);
+
+    if let Some(registered) = registered {
+        if let Some(hook) = &registered.lifecycle.pre_stop {
+            slog::debug!(slog_scope::logger(), "Running pre_stop hook";
+                "service" => &registered.service_name,
+                "container" => container_name
+            );
+            if let Err(e) = run_hook(runtime, container_name, &registered, hook).await {
+                slog::warn!(slog_scope::logger(), "pre_stop hook failed";
+                    "service" => &registered.service_name,
+                    "container" => container_name,
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+
+    runtime.stop_container(container_name).await
+}
+
+async fn run_hook(
+    runtime: &Arc<dyn ContainerRuntime>,
+    container_name: &str,
+    registered: &RegisteredHooks,
+    hook: &LifecycleHook,
+) -> Result<()> {
+    let action = async {
+        match &hook.action {
+            HookAction::Exec { command } => {
+                if command.is_empty() {
+                    return Err(anyhow!("exec hook has an empty command"));
+                }
+                let exit_code = runtime.exec_container(container_name, command).await?;
+                if exit_code != 0 {
+                    return Err(anyhow!("command exited with code {}", exit_code));
+                }
+                Ok(())
+            }
+            HookAction::Http { port, path } => {
+                let address = registered
+                    .ports
+                    .iter()
+                    .find(|p| p.port == *port)
+                    .map(|p| p.backend_address(&registered.ip_address))
+                    .unwrap_or_else(|| format!("{}:{}", registered.ip_address, port));
+                let url = format!("http://{}/{}", address, path.trim_start_matches('/'));
+
+                let status = reqwest::Client::new().get(&url).send().await?.status();
+                if !status.is_success() {
+                    return Err(anyhow!("{} returned {}", url, status));
+                }
+                Ok(())
+            }
+        }
+    };
+
+    tokio::time::timeout(hook.timeout, action)
+        .await
+        .map_err(|_| anyhow!("hook timed out after {:?}", hook.timeout))?
+}
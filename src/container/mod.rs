@@ -1,15 +1,54 @@
 // src/container/mod.rs
+pub mod anomaly;
+pub mod canary;
+pub mod changes;
+pub mod crash_dumps;
+pub mod dates;
+pub mod dependencies;
+pub mod dns;
+pub mod expiry;
+pub mod gpu;
+pub mod groups;
 pub mod health;
+pub mod identity;
+pub mod images;
+pub mod lifecycle;
+pub mod pod_numbers;
+pub mod preemption;
+pub mod readiness;
+pub mod recycling;
+pub mod registry;
+pub mod resources;
+pub mod restart;
+pub mod rollback;
 pub mod rolling_update;
 mod runtimes;
 pub mod scaling;
+pub mod schedule;
+pub mod secrets;
+pub mod shaping;
+pub mod stopped;
+pub mod ttl;
 pub mod volumes;
 
+use changes::{record_change, InstanceEvent};
+use crash_dumps::CrashDumpConfig;
+use dependencies::ContainerDependency;
+use gpu::{GpuConfig, GpuUsage};
 use health::{HealthCheckConfig, HealthState, CONTAINER_HEALTH};
+use lifecycle::Lifecycle;
+pub use pod_numbers::get_next_pod_number;
+use resources::ResourceRequirements;
+use restart::RestartPolicy;
 pub use rolling_update::*;
 pub use runtimes::*;
+use secrets::SecretConfig;
 
 use docker::DockerRuntime;
+use firecracker::FirecrackerRuntime;
+use isolation::IsolatedRuntime;
+use nspawn::NspawnRuntime;
+use resilience::ResilientRuntime;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -18,7 +57,8 @@ use pingora_load_balancing::Backend;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::{Arc, OnceLock};
 use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
@@ -27,7 +67,8 @@ use uuid::Uuid;
 use volumes::{detach_volume, VolumeMount};
 
 use crate::config::{
-    get_config_by_service, parse_container_name, ResourceThresholds, ServiceConfig,
+    daemon::DaemonConfig, get_config_by_service, parse_container_name, parse_env_file,
+    ResourceThresholds, ServiceConfig,
 };
 use crate::proxy::SERVER_BACKENDS;
 
@@ -38,6 +79,16 @@ pub static IMAGE_CHECK_TASKS: OnceLock<Arc<RwLock<FxHashMap<String, JoinHandle<(
     OnceLock::new();
 pub static CONTAINER_STATS: OnceLock<Arc<RwLock<FxHashMap<String, StatsEntry>>>> = OnceLock::new();
 pub static SERVICE_STATS: OnceLock<Arc<RwLock<FxHashMap<String, ServiceStats>>>> = OnceLock::new();
+// Runtimes plugged in by code embedding orbit, looked up by name in create_runtime
+static RUNTIME_REGISTRY: OnceLock<std::sync::RwLock<FxHashMap<String, Arc<dyn ContainerRuntime>>>> =
+    OnceLock::new();
+
+const BUILTIN_RUNTIMES: [&str; 3] = ["docker", "firecracker", "nspawn"];
+
+// Ownership labels of the containers and networks Orbit creates
+pub const MANAGED_LABEL: &str = "orbit.managed";
+pub const SERVICE_LABEL: &str = "orbit.service";
+pub const POD_LABEL: &str = "orbit.pod";
 
 // Update Container struct to include volume mounts
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -46,8 +97,55 @@ pub struct Container {
     pub image: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub privileged: Option<bool>,
+    /// Linux capabilities added to the runtime's default set, e.g. "NET_ADMIN"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_add: Option<Vec<String>>,
+    /// Linux capabilities removed from the runtime's default set, or "ALL"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_drop: Option<Vec<String>>,
+    /// Passed to the runtime as-is, e.g. "no-new-privileges" or "seccomp=unconfined"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_opt: Option<Vec<String>>,
+    /// Mount the image's root filesystem read-only; writes go to volumes and `tmpfs`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_root_fs: Option<bool>,
+    /// Writable in-memory mounts, e.g. "/tmp" or "/run:size=64m,mode=1777"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmpfs: Option<Vec<String>>,
+    /// User namespace mode, e.g. "host" to opt out of the daemon's userns-remap,
+    /// or Podman's "auto" and "keep-id"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userns_mode: Option<String>,
+    /// Host devices exposed to the container, e.g. "/dev/fuse" or "/dev/snd:/dev/snd:rw"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub devices: Option<Vec<String>>,
+    /// GPUs reserved for the container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpus: Option<GpuConfig>,
+    /// Replaces the image's entrypoint; `command` becomes its arguments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entrypoint: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub command: Option<Vec<String>>,
+    /// User to run as, e.g. "1000", "1000:1000" or "nobody"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    /// Defaults to "<service>-<container>"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Nameservers, replacing the service's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_search: Option<Vec<String>>,
+    /// "hostname:address" entries, added to the service's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_hosts: Option<Vec<String>>,
+    /// Attached to the container in the runtime and reported by `list_containers`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ports: Option<Vec<ContainerPort>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -56,12 +154,186 @@ pub struct Container {
     pub memory_limit: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpu_limit: Option<Value>,
+    /// Memory and CPU requests reserved on the node, and limits enforced by the runtime
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceRequirements>,
+    /// Resource limits by name, e.g. "nofile", each a single value or soft and hard values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ulimits: Option<HashMap<String, UlimitValue>>,
+    /// Maximum number of processes in the container, -1 for unlimited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pids_limit: Option<i64>,
+    /// Size of /dev/shm, in the same format as memory_limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shm_size: Option<Value>,
+    /// Kernel parameters set in the container's namespaces, e.g. "net.core.somaxconn": "1024"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sysctls: Option<HashMap<String, String>>,
+    /// Allow namespaced sysctls outside the safe set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_unsafe_sysctls: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub network_limit: Option<NetworkLimit>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub blkio_limit: Option<BlkioLimit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub resource_thresholds: Option<ResourceThresholds>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub health_check: Option<HealthCheckConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<TimeZoneConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>, // e.g. "en_US.UTF-8", sets LANG and LC_ALL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// Files of KEY=VALUE lines, read when the container starts; `env` takes precedence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<Vec<PathBuf>>,
+    /// Added to the service's secrets, replacing any with the same name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<Vec<SecretConfig>>,
+    /// Restart the container in place when it exits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart_policy: Option<RestartPolicy>,
+    /// Collect crash artifacts from a host directory when the container crashes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crash_dumps: Option<CrashDumpConfig>,
+    /// Logging driver, replacing the service's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingConfig>,
+    /// Signal that asks the container to stop, e.g. "SIGQUIT" (default: SIGTERM)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_signal: Option<String>,
+    /// How long the container may take to exit after the stop signal before
+    /// it is killed (default: 10s)
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stop_grace_period: Option<Duration>,
+    /// post_start and pre_stop hooks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<Lifecycle>,
+    /// Containers of the pod to start before this one, optionally waiting until they are healthy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<ContainerDependency>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TimeZoneConfig {
+    /// Bind mount the host's /etc/localtime (and /etc/timezone if present) read-only
+    #[serde(default)]
+    pub host: bool,
+    /// IANA time zone name exported as TZ, e.g. "Europe/Paris"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tz: Option<String>,
+}
+
+/// A ulimit value: a number or "unlimited", applied as both the soft and hard
+/// limit, or separate soft and hard values
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum UlimitValue {
+    Single(Value),
+    Range { soft: Value, hard: Value },
+}
+
+/// A runtime logging driver and its options, e.g. json-file with max-size
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoggingConfig {
+    pub driver: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub options: HashMap<String, String>,
+}
+
+impl LoggingConfig {
+    /// Check the driver name, and the rotation options of the file drivers
+    pub fn validate(&self) -> Result<()> {
+        let name_regex = regex::Regex::new(r"^[A-Za-z0-9][A-Za-z0-9._/:-]*$")?;
+        if !name_regex.is_match(&self.driver) {
+            return Err(anyhow!("'{}' is not a valid driver name", self.driver));
+        }
+        if !matches!(self.driver.as_str(), "json-file" | "local") {
+            return Ok(());
+        }
+
+        let size_regex = regex::Regex::new(r"^\d+[kmg]?$")?;
+        if let Some(max_size) = self.options.get("max-size") {
+            if !size_regex.is_match(max_size) {
+                return Err(anyhow!(
+                    "max-size '{}' must be a number with an optional k, m or g suffix",
+                    max_size
+                ));
+            }
+        }
+        if let Some(max_file) = self.options.get("max-file") {
+            if !max_file.parse::<u32>().is_ok_and(|n| n > 0) {
+                return Err(anyhow!("max-file '{}' must be a positive number", max_file));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub const DEFAULT_STOP_SIGNAL: &str = "SIGTERM";
+pub const DEFAULT_STOP_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// How a container is asked to stop before it is killed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StopSettings {
+    pub signal: Option<String>,
+    pub grace_period: Option<Duration>,
+}
+
+impl StopSettings {
+    pub fn is_default(&self) -> bool {
+        self.signal.is_none() && self.grace_period.is_none()
+    }
+}
+
+/// A running container's stop signal and grace period, from its service's
+/// config. The defaults when the container is not in a loaded config.
+pub async fn stop_settings(container_name: &str) -> StopSettings {
+    let Ok(parts) = parse_container_name(container_name) else {
+        return StopSettings::default();
+    };
+    let Some(config) = get_config_by_service(&parts.service_name).await else {
+        return StopSettings::default();
+    };
+    config
+        .spec
+        .containers
+        .iter()
+        .find(|container| container.name == parts.container_name)
+        .map(|container| StopSettings {
+            signal: container.stop_signal.clone(),
+            grace_period: container.stop_grace_period,
+        })
+        .unwrap_or_default()
+}
+
+/// Check a stop signal is a signal name, e.g. "SIGTERM" or "TERM", or number
+pub fn validate_stop_signal(signal: &str) -> Result<()> {
+    let signal_regex = regex::Regex::new(r"^((SIG)?[A-Z][A-Z0-9+-]*|[1-9][0-9]?)$")?;
+    if !signal_regex.is_match(signal) {
+        return Err(anyhow!(
+            "'{}' is not a signal name such as SIGTERM, or a signal number",
+            signal
+        ));
+    }
+    Ok(())
+}
+
+/// The logging driver a container runs with: its own, or else the service's
+pub fn logging_for<'a>(
+    service_config: &'a ServiceConfig,
+    container: &'a Container,
+) -> Option<&'a LoggingConfig> {
+    container
+        .logging
+        .as_ref()
+        .or(service_config.logging.as_ref())
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -76,6 +348,41 @@ pub struct NetworkLimit {
     pub egress_burst: Option<String>, // e.g. "10Mb"
 }
 
+/// Disk throughput limits for one block device
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlkioLimit {
+    pub device: String, // e.g. "/dev/sda"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_bps: Option<String>, // e.g. "50MB/s"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_bps: Option<String>, // e.g. "20MB/s"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_iops: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_iops: Option<u64>,
+}
+
+impl BlkioLimit {
+    /// Check the device path and rates, which are otherwise only parsed at start
+    pub fn validate(&self) -> Result<()> {
+        if !self.device.starts_with("/dev/") {
+            return Err(anyhow!(
+                "device '{}' must be a path under /dev",
+                self.device
+            ));
+        }
+        for rate in [&self.read_bps, &self.write_bps].into_iter().flatten() {
+            if parse_disk_rate(rate)? == 0 {
+                return Err(anyhow!("disk rate '{}' must be positive", rate));
+            }
+        }
+        if self.read_iops == Some(0) || self.write_iops == Some(0) {
+            return Err(anyhow!("read_iops and write_iops must be positive"));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ContainerPort {
     pub port: u16,
@@ -122,6 +429,70 @@ impl Container {
             self.name
         ))
     }
+
+    /// The digest the image is pinned to, e.g. `sha256:...` for `nginx@sha256:...`.
+    /// A pinned image never changes, so it is neither re-pulled nor watched for updates.
+    pub fn pinned_digest(&self) -> Option<&str> {
+        self.image.split_once('@').map(|(_, digest)| digest)
+    }
+
+    /// The memory limit enforced by the runtime, from `resources.limits` or `memory_limit`
+    pub fn memory_limit_value(&self) -> Option<&Value> {
+        self.resources
+            .as_ref()
+            .and_then(|resources| resources.limits.memory.as_ref())
+            .or(self.memory_limit.as_ref())
+    }
+
+    /// The CPU limit enforced by the runtime, from `resources.limits` or `cpu_limit`
+    pub fn cpu_limit_value(&self) -> Option<&Value> {
+        self.resources
+            .as_ref()
+            .and_then(|resources| resources.limits.cpu.as_ref())
+            .or(self.cpu_limit.as_ref())
+    }
+
+    /// The entrypoint followed by the command, for runtimes that take a single
+    /// argument list. None when neither is set.
+    pub fn argv(&self) -> Option<Vec<String>> {
+        if self.entrypoint.is_none() && self.command.is_none() {
+            return None;
+        }
+        Some(
+            self.entrypoint
+                .iter()
+                .chain(self.command.iter())
+                .flatten()
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Variables from `env_file` in order, then `env`, later values winning.
+    pub async fn environment(&self, service_name: &str) -> Result<Vec<(String, String)>> {
+        let mut vars: Vec<(String, String)> = Vec::new();
+        let mut set = |key: String, value: String| {
+            vars.retain(|(existing, _)| *existing != key);
+            vars.push((key, value));
+        };
+
+        for path in self.env_file.iter().flatten() {
+            for (key, value) in parse_env_file(path).await? {
+                set(key, value);
+            }
+        }
+
+        if let Some(env) = &self.env {
+            let mut keys: Vec<&String> = env.keys().collect();
+            keys.sort();
+            for key in keys {
+                set(key.clone(), env[key].clone());
+            }
+        }
+
+        crate::redact::register_env(service_name, vars.iter().map(|(key, value)| (key, value)));
+        Ok(vars)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -150,20 +521,68 @@ impl ServiceStats {
     }
 }
 
-// Update the update_container_stats function to use service-level stats
-pub async fn update_container_stats(
+// Raw counters sampled from a container, independent of the runtime that produced them
+#[derive(Debug, Clone, Default)]
+pub struct StatsSample {
+    pub id: String,
+    pub cpu_total_usage: u64, // nanoseconds of CPU time used by the container
+    pub system_cpu_usage: u64, // nanoseconds of CPU time used by the host
+    pub online_cpus: u64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+    pub network_bytes: Option<(u64, u64)>, // (rx, tx)
+    pub disk_bytes: Option<(u64, u64)>,    // (read, write)
+    pub gpu: Option<GpuUsage>,
+}
+
+impl From<&Stats> for StatsSample {
+    fn from(stats: &Stats) -> Self {
+        Self {
+            id: stats.id.clone(),
+            cpu_total_usage: stats.cpu_stats.cpu_usage.total_usage,
+            system_cpu_usage: stats.cpu_stats.system_cpu_usage.unwrap_or(0),
+            online_cpus: stats.cpu_stats.online_cpus.unwrap_or(1),
+            memory_usage: stats.memory_stats.usage.unwrap_or(0),
+            memory_limit: stats.memory_stats.limit.unwrap_or(0),
+            network_bytes: stats.networks.as_ref().map(|networks| {
+                (
+                    networks.values().map(|net| net.rx_bytes).sum(),
+                    networks.values().map(|net| net.tx_bytes).sum(),
+                )
+            }),
+            disk_bytes: stats
+                .blkio_stats
+                .io_service_bytes_recursive
+                .as_ref()
+                .map(|entries| {
+                    let total = |op: &str| {
+                        entries
+                            .iter()
+                            .filter(|entry| entry.op.eq_ignore_ascii_case(op))
+                            .map(|entry| entry.value)
+                            .sum()
+                    };
+                    (total("read"), total("write"))
+                }),
+            gpu: None,
+        }
+    }
+}
+
+// Feed a runtime-neutral sample into CONTAINER_STATS and SERVICE_STATS
+pub async fn record_container_stats(
     service_name: &str,
     container_name: &str,
-    stats: Stats,
+    stats: StatsSample,
     nano_cpus: Option<u64>,
 ) -> ContainerStats {
     let stats_store = CONTAINER_STATS.get().expect("Stats store not initialized");
     let service_stats = SERVICE_STATS.get().expect("Service stats not initialized");
 
     let now = SystemTime::now();
-    let cpu_total = stats.cpu_stats.cpu_usage.total_usage;
-    let system_cpu = stats.cpu_stats.system_cpu_usage.unwrap_or(0);
-    let online_cpus = stats.cpu_stats.online_cpus.unwrap_or(1) as f64;
+    let cpu_total = stats.cpu_total_usage;
+    let system_cpu = stats.system_cpu_usage;
+    let online_cpus = stats.online_cpus.max(1) as f64;
 
     // Get previous stats with minimal lock time
     let previous_stats = {
@@ -200,14 +619,23 @@ pub async fn update_container_stats(
         id: stats.id.clone(),
         cpu_percentage,
         cpu_percentage_relative,
-        memory_usage: stats.memory_stats.usage.unwrap_or(0),
-        memory_limit: stats.memory_stats.limit.unwrap_or(0),
+        memory_usage: stats.memory_usage,
+        memory_limit: stats.memory_limit,
+        cpu_limit: nano_cpus.unwrap_or(0),
         ip_address: String::from(""),
         port_mappings: HashMap::new(),
         network_rx_bytes: 0,
         network_tx_bytes: 0,
         network_rx_rate: 0.0,
         network_tx_rate: 0.0,
+        disk_read_bytes: 0,
+        disk_write_bytes: 0,
+        disk_read_rate: 0.0,
+        disk_write_rate: 0.0,
+        gpu_percentage: stats.gpu.map(|gpu| gpu.percentage),
+        gpu_memory_usage: stats.gpu.map(|gpu| gpu.memory_usage).unwrap_or(0),
+        gpu_memory_limit: stats.gpu.map(|gpu| gpu.memory_limit).unwrap_or(0),
+        native_health: None,
         timestamp: now,
     };
 
@@ -220,7 +648,8 @@ pub async fn update_container_stats(
     };
 
     // Update network stats using previous container stats if available
-    container_stats.update_network_stats(&stats, previous_container_stats.as_ref());
+    container_stats.update_network_stats(stats.network_bytes, previous_container_stats.as_ref());
+    container_stats.update_disk_stats(stats.disk_bytes, previous_container_stats.as_ref());
 
     // Update service-level stats with write lock
     {
@@ -289,6 +718,22 @@ pub struct ContainerPortMetadata {
     pub port: u16,                // Container's exposed port
     pub target_port: Option<u16>, // Optional target port
     pub node_port: Option<u16>,   // Optional external port
+    // Loopback port the container is published on when its own address is not
+    // reachable from the host, as with rootless daemons
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_port: Option<u16>,
+}
+
+pub const LOOPBACK_ADDRESS: &str = "127.0.0.1";
+
+impl ContainerPortMetadata {
+    /// Address Orbit uses to reach this port on a container with the given IP
+    pub fn backend_address(&self, ip_address: &str) -> String {
+        match self.host_port {
+            Some(host_port) => format!("{}:{}", LOOPBACK_ADDRESS, host_port),
+            None => format!("{}:{}", ip_address, self.port),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -298,6 +743,11 @@ pub struct InstanceMetadata {
     pub network: String,
     pub containers: Vec<ContainerMetadata>,
     pub image_hash: HashMap<String, String>, // container_name -> image_hash
+    #[serde(default)]
+    pub annotations: HashMap<String, String>, // copied from the service config
+    /// Names the pod's containers reach each other by -> runtime container name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dns_names: BTreeMap<String, String>,
 }
 
 // Container information struct
@@ -307,6 +757,81 @@ pub struct ContainerInfo {
     pub name: String,  // Container name
     pub state: String, // Container state (e.g., "running")
     pub port: u16,     // Exposed port, if available
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>, // Labels set on the container
+}
+
+impl ContainerInfo {
+    /// Whether Orbit labelled the container as its own
+    pub fn is_labelled(&self) -> bool {
+        self.labels
+            .get(MANAGED_LABEL)
+            .is_some_and(|value| value == "true")
+    }
+
+    /// The service the container belongs to, from its labels or, for
+    /// containers created before Orbit labelled them, its name
+    pub fn service_name(&self) -> Option<String> {
+        if self.is_labelled() {
+            return self.labels.get(SERVICE_LABEL).cloned();
+        }
+        parse_container_name(&self.name)
+            .ok()
+            .map(|parts| parts.service_name)
+    }
+
+    /// The pod the container belongs to, from its labels or its name
+    pub fn pod_uuid(&self) -> Option<Uuid> {
+        if self.is_labelled() {
+            return self
+                .labels
+                .get(POD_LABEL)
+                .and_then(|pod| Uuid::parse_str(pod).ok());
+        }
+        parse_container_name(&self.name)
+            .ok()
+            .map(|parts| parts.uuid)
+    }
+}
+
+/// Labels marking a pod's containers and network as Orbit's
+pub fn ownership_labels(service_name: &str, uuid: &str) -> HashMap<String, String> {
+    HashMap::from([
+        (MANAGED_LABEL.to_string(), "true".to_string()),
+        (SERVICE_LABEL.to_string(), service_name.to_string()),
+        (POD_LABEL.to_string(), uuid.to_string()),
+    ])
+}
+
+/// What the container runtime reports about itself and the host it runs on
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RuntimeInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operating_system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_total: Option<u64>,
+    pub containers: u64,
+    pub containers_running: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<u64>,
+    /// Where the runtime keeps images and container state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_root: Option<String>,
+    pub rootless: bool,
+}
+
+/// A local image's filesystem layers, bottom first, and its size
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ImageLayers {
+    pub layers: Vec<String>,
+    pub size: u64,
 }
 
 // Struct to store container performance stats
@@ -318,20 +843,42 @@ pub struct ContainerStats {
     pub cpu_percentage_relative: f64,
     pub memory_usage: u64,
     pub memory_limit: u64,
+    /// NanoCPUs the container may use, 0 without a limit
+    #[serde(default)]
+    pub cpu_limit: u64,
     pub port_mappings: HashMap<u16, u16>,
     pub network_rx_bytes: u64,
     pub network_tx_bytes: u64,
     pub network_rx_rate: f64, // bytes per second
     pub network_tx_rate: f64, // bytes per second
+    #[serde(default)]
+    pub disk_read_bytes: u64,
+    #[serde(default)]
+    pub disk_write_bytes: u64,
+    #[serde(default)]
+    pub disk_read_rate: f64, // bytes per second
+    #[serde(default)]
+    pub disk_write_rate: f64, // bytes per second
+    /// Average utilisation of the container's GPUs, None without GPUs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_percentage: Option<f64>,
+    #[serde(default)]
+    pub gpu_memory_usage: u64,
+    #[serde(default)]
+    pub gpu_memory_limit: u64,
+    /// State of the runtime's own healthcheck, None when the runtime runs none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub native_health: Option<HealthState>,
     pub timestamp: SystemTime,
 }
 
 impl ContainerStats {
-    pub fn update_network_stats(&mut self, stats: &Stats, previous: Option<&Self>) {
-        if let Some(networks) = &stats.networks {
-            let rx_bytes: u64 = networks.values().map(|net| net.rx_bytes).sum();
-            let tx_bytes: u64 = networks.values().map(|net| net.tx_bytes).sum();
-
+    pub fn update_network_stats(
+        &mut self,
+        network_bytes: Option<(u64, u64)>,
+        previous: Option<&Self>,
+    ) {
+        if let Some((rx_bytes, tx_bytes)) = network_bytes {
             // Calculate rates if we have previous stats
             if let Some(prev) = previous {
                 let time_diff = self
@@ -352,6 +899,28 @@ impl ContainerStats {
             self.network_tx_bytes = tx_bytes;
         }
     }
+
+    pub fn update_disk_stats(&mut self, disk_bytes: Option<(u64, u64)>, previous: Option<&Self>) {
+        if let Some((read_bytes, write_bytes)) = disk_bytes {
+            if let Some(prev) = previous {
+                let time_diff = self
+                    .timestamp
+                    .duration_since(prev.timestamp)
+                    .unwrap_or_else(|_| Duration::from_secs(1))
+                    .as_secs_f64();
+
+                if time_diff > 0.0 {
+                    self.disk_read_rate =
+                        (read_bytes as f64 - prev.disk_read_bytes as f64) / time_diff;
+                    self.disk_write_rate =
+                        (write_bytes as f64 - prev.disk_write_bytes as f64) / time_diff;
+                }
+            }
+
+            self.disk_read_bytes = read_bytes;
+            self.disk_write_bytes = write_bytes;
+        }
+    }
 }
 
 // Define the container runtime trait
@@ -364,6 +933,12 @@ pub trait ContainerRuntime: Send + Sync + std::fmt::Debug {
         current_hashes: &HashMap<String, String>,
     ) -> Result<HashMap<String, bool>>;
     async fn get_image_digest(&self, image: &str) -> Result<String>;
+    async fn pull_image(
+        &self,
+        service_name: &str,
+        containers: &[Container],
+        service_config: &ServiceConfig,
+    ) -> Result<()>;
     async fn remove_pod_network(&self, network_name: &str, service_name: &str) -> Result<()>;
     async fn create_pod_network(&self, service_name: &str, uuid: &str) -> Result<String>;
     async fn start_containers(
@@ -374,8 +949,74 @@ pub trait ContainerRuntime: Send + Sync + std::fmt::Debug {
         service_config: &ServiceConfig,
     ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>>; // Returns vec of (container_name, ports)
     async fn stop_container(&self, name: &str) -> Result<()>;
+    /// Resolves with the exit code once the container stops running
+    async fn wait_container(&self, _name: &str) -> Result<i64> {
+        Err(anyhow!("This runtime can't wait for containers to exit"))
+    }
+    /// Start an exited container again, keeping its pod network. A running
+    /// container is stopped first.
+    async fn restart_container(
+        &self,
+        _name: &str,
+        _container: &Container,
+        _service_config: &ServiceConfig,
+    ) -> Result<()> {
+        Err(anyhow!("This runtime can't restart containers in place"))
+    }
+    /// Run a command inside a running container and return its exit code
+    async fn exec_container(&self, name: &str, command: &[String]) -> Result<i64>;
     async fn inspect_container(&self, name: &str) -> Result<ContainerStats>;
     async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>>;
+    /// The pod networks Orbit created for a service, with the pod each is for
+    async fn list_pod_networks(&self, _service_name: &str) -> Result<Vec<(String, Uuid)>> {
+        Ok(Vec::new())
+    }
+    /// Version and capacity details of the runtime, for the node status API
+    async fn runtime_info(&self) -> Result<RuntimeInfo>;
+    /// The layers of a local image. None when the image isn't present or the
+    /// runtime's images aren't made of layers.
+    async fn image_layers(&self, _image: &str) -> Result<Option<ImageLayers>> {
+        Ok(None)
+    }
+    /// Pull an image ahead of time unless it is present, with the pull
+    /// secrets of the service using it. True when it was pulled.
+    async fn prefetch_image(
+        &self,
+        _image: &str,
+        _service_config: Option<&ServiceConfig>,
+    ) -> Result<bool> {
+        Err(anyhow!("This runtime does not pull images"))
+    }
+    /// Freeze a running container's processes
+    async fn pause_container(&self, _name: &str) -> Result<()> {
+        Err(anyhow!("This runtime can't pause containers"))
+    }
+    /// Let a paused container's processes run again
+    async fn unpause_container(&self, _name: &str) -> Result<()> {
+        Err(anyhow!("This runtime can't pause containers"))
+    }
+    /// Whether the registry has a different image under this reference than
+    /// the one present locally, so it needs pulling. Runtimes that can't ask
+    /// the registry pull on every image check.
+    async fn image_changed_upstream(
+        &self,
+        _image: &str,
+        _service_config: &ServiceConfig,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+    /// Change a running container's memory limit, in bytes, and CPU limit, in
+    /// nanoCPUs, without restarting it
+    async fn update_resources(
+        &self,
+        _name: &str,
+        _memory: Option<u64>,
+        _nano_cpus: Option<u64>,
+    ) -> Result<()> {
+        Err(anyhow!(
+            "This runtime can't change resource limits in place"
+        ))
+    }
     async fn attempt_start_containers(
         &self,
         service_name: &str,
@@ -383,6 +1024,11 @@ pub trait ContainerRuntime: Send + Sync + std::fmt::Debug {
         containers: &Vec<Container>,
         service_config: &ServiceConfig,
     ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>>;
+    /// Reject a service config this runtime can't run as configured, before
+    /// any of its pods is started
+    fn validate_service(&self, _service_config: &ServiceConfig) -> Result<()> {
+        Ok(())
+    }
 }
 
 // Helper function to calculate CPU percentages
@@ -455,38 +1101,103 @@ pub fn parse_network_rate(rate: &str) -> Result<u64> {
     }
 }
 
-pub fn create_runtime(runtime: &str) -> Result<Arc<dyn ContainerRuntime>> {
-    match runtime {
-        "docker" => Ok(Arc::new(DockerRuntime::new()?)),
-        _ => Err(anyhow!("Unsupported runtime: {}", runtime)),
+/// Parse a disk rate in bytes per second, e.g. "512KB/s", "50MB/s" or "1GB/s"
+pub fn parse_disk_rate(rate: &str) -> Result<u64> {
+    let re = regex::Regex::new(r"^(\d+(?:\.\d+)?)(B|KB|MB|GB)/s$")?;
+    if let Some(caps) = re.captures(rate) {
+        let value: f64 = caps[1].parse()?;
+        let multiplier = match &caps[2] {
+            "B" => 1,
+            "KB" => 1_000,
+            "MB" => 1_000_000,
+            "GB" => 1_000_000_000,
+            _ => return Err(anyhow!("Unsupported disk rate unit: {}", &caps[2])),
+        };
+        Ok((value * multiplier as f64) as u64)
+    } else {
+        Err(anyhow!("Invalid disk rate format: {}", rate))
     }
 }
 
-pub async fn get_next_pod_number(service_name: &str) -> u8 {
-    let runtime = RUNTIME.get().expect("Runtime not initialised").clone();
+/// Make a custom runtime selectable with `--runtime <name>`. Must be called before
+/// `create_runtime`; the runtime is still wrapped with the daemon's call policy and
+/// per-service concurrency limits.
+pub fn register_runtime(name: &str, runtime: Arc<dyn ContainerRuntime>) -> Result<()> {
+    if BUILTIN_RUNTIMES.contains(&name) {
+        return Err(anyhow!("Runtime name {} is reserved", name));
+    }
 
-    match runtime.list_containers(Some(service_name)).await {
-        Ok(containers) => containers
-            .iter()
-            .filter_map(|c| parse_container_name(&c.name).ok())
-            .map(|parts| parts.pod_number)
-            .max()
-            .map_or(0, |max| max + 1),
-        Err(_) => 0,
+    let mut registry = RUNTIME_REGISTRY
+        .get_or_init(Default::default)
+        .write()
+        .unwrap();
+    if registry.contains_key(name) {
+        return Err(anyhow!("Runtime {} is already registered", name));
     }
+    registry.insert(name.to_string(), runtime);
+    Ok(())
+}
+
+fn registered_runtime(name: &str) -> Option<Arc<dyn ContainerRuntime>> {
+    RUNTIME_REGISTRY
+        .get()
+        .and_then(|registry| registry.read().unwrap().get(name).cloned())
+}
+
+pub fn create_runtime(
+    runtime: &str,
+    daemon_config: &DaemonConfig,
+) -> Result<Arc<dyn ContainerRuntime>> {
+    let inner: Arc<dyn ContainerRuntime> = match runtime {
+        "docker" => Arc::new(DockerRuntime::with_config(
+            &daemon_config.runtime.docker.clone().unwrap_or_default(),
+        )?),
+        "firecracker" => {
+            let config = daemon_config.runtime.firecracker.clone().ok_or_else(|| {
+                anyhow!("The firecracker runtime requires runtime.firecracker in the daemon config")
+            })?;
+            Arc::new(FirecrackerRuntime::new(config)?)
+        }
+        "nspawn" => Arc::new(NspawnRuntime::new(
+            daemon_config.runtime.nspawn.clone().unwrap_or_default(),
+        )?),
+        name => {
+            registered_runtime(name).ok_or_else(|| anyhow!("Unsupported runtime: {}", runtime))?
+        }
+    };
+
+    // Deadlines apply to the call itself, so they sit inside the per-service queue
+    let resilient = Arc::new(ResilientRuntime::new(
+        inner,
+        daemon_config.runtime.policy.clone(),
+    ));
+
+    // Bound each service's share of runtime operations
+    Ok(Arc::new(IsolatedRuntime::new(resilient)))
 }
 
 pub async fn manage(service_name: &str, config: ServiceConfig) {
     let log = slog_scope::logger();
+
+    // External services run elsewhere; the proxy finds their backends
+    if config.external.is_some() {
+        return;
+    }
+    // Stopped services keep their config but run no pods
+    if stopped::is_stopped(&config) {
+        stopped::stop_pods(service_name, &config).await;
+        return;
+    }
     let instance_store = INSTANCE_STORE.get().unwrap();
     let runtime = RUNTIME.get().expect("Runtime not initialised").clone();
 
     let current_instances = {
-        let store = instance_store.read().await;
+        let mut store = instance_store.write().await;
+        // Services scaled to zero stay in the store, so their scaling task keeps running
         store
-            .get(service_name)
-            .map(|instances| instances.len())
-            .unwrap_or(0)
+            .entry(service_name.to_string())
+            .or_insert_with(FxHashMap::default)
+            .len()
     };
 
     let target_instances = config.instance_count.min as usize;
@@ -500,20 +1211,36 @@ pub async fn manage(service_name: &str, config: ServiceConfig) {
         );
 
         for _ in current_instances..target_instances {
-            let pod_number = get_next_pod_number(service_name).await;
-            let uuid = uuid::Uuid::new_v4();
-            let network_name = format!("{}__{}", service_name, uuid);
-
+            if let Err(e) = preemption::ensure_capacity(&config).await {
+                slog::error!(log, "Not enough node capacity for pod";
+                    "service" => service_name,
+                    "error" => e.to_string()
+                );
+                break;
+            }
+            let pod_number = match get_next_pod_number(service_name).await {
+                Ok(lease) => lease,
+                Err(e) => {
+                    slog::error!(log, "Failed to allocate pod number";
+                        "service" => service_name,
+                        "error" => e.to_string()
+                    );
+                    break;
+                }
+            };
+            let containers = rollback::containers_to_start(&config);
             match runtime
-                .start_containers(
-                    service_name,
-                    pod_number as u8,
-                    &config.spec.containers,
-                    &config,
-                )
+                .start_containers(service_name, pod_number.number(), &containers, &config)
                 .await
             {
                 Ok(started_containers) => {
+                    // The runtime names the containers after the pod's UUID
+                    let uuid = started_containers
+                        .first()
+                        .and_then(|(name, _, _)| parse_container_name(name).ok())
+                        .map_or_else(uuid::Uuid::new_v4, |parts| parts.uuid);
+                    let network_name = format!("{}__{}", service_name, uuid);
+
                     // Initialize health monitoring
                     for (container_name, _, _) in &started_containers {
                         if let Ok(parts) = parse_container_name(container_name) {
@@ -542,7 +1269,7 @@ pub async fn manage(service_name: &str, config: ServiceConfig) {
 
                     // Get image hashes
                     let mut image_hashes = HashMap::new();
-                    for container in &config.spec.containers {
+                    for container in &containers {
                         if let Ok(hash) = runtime.get_image_digest(&container.image).await {
                             image_hashes.insert(container.name.clone(), hash);
                         }
@@ -561,18 +1288,30 @@ pub async fn manage(service_name: &str, config: ServiceConfig) {
                             created_at: now,
                             network: network_name.clone(),
                             image_hash: image_hashes,
+                            annotations: config.annotations.clone(),
+                            dns_names: dns::pod_dns_names(
+                                started_containers.iter().map(|(name, _, _)| name.as_str()),
+                            ),
                             containers: started_containers
-                                .into_iter()
+                                .iter()
                                 .map(|(name, ip, ports)| ContainerMetadata {
-                                    name,
+                                    name: name.clone(),
                                     network: network_name.clone(),
-                                    ip_address: ip,
-                                    ports,
+                                    ip_address: ip.clone(),
+                                    ports: ports.clone(),
                                     status: "running".to_string(),
                                 })
                                 .collect(),
                         },
                     );
+                    record_change(
+                        service_name,
+                        InstanceEvent::PodAdded {
+                            instance: service_instances[&uuid].clone(),
+                        },
+                    );
+                    restart::supervise_containers(service_name, &config, &started_containers);
+                    lifecycle::register_hooks(service_name, &config, &started_containers);
 
                     tokio::task::yield_now().await;
                 }
@@ -624,6 +1363,7 @@ pub async fn clean_up(service_name: &str) {
     // Get write lock and remove service data
     let mut store = instance_store.write().await;
     if let Some(instances) = store.remove(service_name) {
+        record_change(service_name, InstanceEvent::ServiceRemoved);
         // Drop the write lock early since we have the data we need
         drop(store);
 
@@ -646,14 +1386,17 @@ pub async fn clean_up(service_name: &str) {
                         if let Some(volume_mounts) = &container_config.volume_mounts {
                             for mount in volume_mounts.iter() {
                                 if let Some(volume_data) = volumes.get(&mount.name) {
-                                    if let Some(named_volume) = &volume_data.named_volume {
-                                        if let Err(e) =
-                                            detach_volume(&named_volume.name, &container.name).await
-                                        {
+                                    let names = volume_data
+                                        .named_volume
+                                        .iter()
+                                        .map(|named_volume| &named_volume.name)
+                                        .chain(volume_data.claim.iter().map(|claim| &claim.name));
+                                    for name in names {
+                                        if let Err(e) = detach_volume(name, &container.name).await {
                                             slog::error!(log, "Failed to detach volume";
                                                 "service" => service_name,
                                                 "container" => &container.name,
-                                                "volume" => &named_volume.name,
+                                                "volume" => name,
                                                 "error" => e.to_string()
                                             );
                                         }
@@ -675,7 +1418,7 @@ pub async fn clean_up(service_name: &str) {
                         };
 
                         if let Some(backends) = backends {
-                            let addr = format!("{}:{}", container.ip_address, port_metadata.port);
+                            let addr = port_metadata.backend_address(&container.ip_address);
                             if let Ok(backend) = Backend::new(&addr) {
                                 let mut backend_set = backends.write().await;
                                 backend_set.remove(&backend);
@@ -710,7 +1453,7 @@ pub async fn clean_up(service_name: &str) {
                 }
 
                 // Stop each container
-                if let Err(e) = runtime.stop_container(&container.name).await {
+                if let Err(e) = lifecycle::stop_container(&runtime, &container.name).await {
                     slog::error!(log, "Failed to stop container";
                         "service" => service_name,
                         "container" => &container.name,
@@ -738,4 +1481,9 @@ pub async fn clean_up(service_name: &str) {
             services.remove(service_name);
         }
     }
+
+    // Unless a service of the same name was added in the meantime
+    if get_config_by_service(service_name).await.is_none() {
+        crate::redact::unregister_service(service_name);
+    }
 }
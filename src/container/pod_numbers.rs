@@ -0,0 +1,326 @@
+// src/container/pod_numbers.rs
+//
+// Per-service pod number allocator. Numbers are handed out lowest-free-first,
+// persisted so they stay unique across restarts, and reused once nothing
+// running holds them any more.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::config::parse_container_name;
+use crate::container::RUNTIME;
+
+pub static POD_NUMBERS: OnceLock<PodNumberAllocator> = OnceLock::new();
+
+const STATE_FILE: &str = "pod_numbers.json";
+
+#[derive(Debug, Default)]
+struct ServiceNumbers {
+    // Persisted: numbers believed to belong to a pod
+    assigned: BTreeSet<u8>,
+    // Numbers handed out but whose start may not be visible in the runtime yet;
+    // Some(instant) once the lease was dropped
+    leases: FxHashMap<u8, Option<Instant>>,
+}
+
+#[derive(Debug)]
+pub struct PodNumberAllocator {
+    path: Option<PathBuf>,
+    services: Mutex<FxHashMap<String, ServiceNumbers>>,
+    // Bumped on every change, so an older state is never written over a newer one
+    version: AtomicU64,
+    written: tokio::sync::Mutex<u64>,
+}
+
+// The assigned numbers as of one change, to write to the state file
+struct Snapshot {
+    version: u64,
+    json: String,
+}
+
+/// A reserved pod number. The number stays reserved while the lease is held,
+/// and afterwards for as long as the runtime reports containers using it.
+#[derive(Debug)]
+pub struct PodNumberLease {
+    service_name: String,
+    number: u8,
+}
+
+impl PodNumberLease {
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+}
+
+impl Drop for PodNumberLease {
+    fn drop(&mut self) {
+        if let Some(allocator) = POD_NUMBERS.get() {
+            allocator.release(&self.service_name, self.number);
+        }
+    }
+}
+
+pub fn initialize_pod_numbers(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let allocator = PodNumberAllocator::load(state_dir.join(STATE_FILE))?;
+
+    POD_NUMBERS
+        .set(allocator)
+        .map_err(|_| anyhow!("Pod number allocator already initialized"))
+}
+
+fn allocator() -> &'static PodNumberAllocator {
+    // Without a state directory, e.g. in tests, numbers are only tracked in memory
+    POD_NUMBERS.get_or_init(|| PodNumberAllocator::new(None, FxHashMap::default()))
+}
+
+/// Reserve the lowest pod number not used by a running or starting pod of the service.
+pub async fn get_next_pod_number(service_name: &str) -> Result<PodNumberLease> {
+    let runtime = RUNTIME.get().expect("Runtime not initialised").clone();
+
+    let listed_at = Instant::now();
+    let running: Option<BTreeSet<u8>> = match runtime.list_containers(Some(service_name)).await {
+        Ok(containers) => Some(
+            containers
+                .iter()
+                .filter_map(|c| parse_container_name(&c.name).ok())
+                .filter(|parts| parts.service_name == service_name)
+                .map(|parts| parts.pod_number)
+                .collect(),
+        ),
+        Err(e) => {
+            // Fall back to the persisted numbers alone; they still rule out collisions
+            slog::warn!(slog_scope::logger(), "Failed to list containers for pod numbering";
+                "service" => service_name,
+                "error" => e.to_string()
+            );
+            None
+        }
+    };
+
+    let allocator = allocator();
+    let (number, snapshot) = allocator.allocate(service_name, running.as_ref(), listed_at)?;
+    allocator.persist(snapshot).await;
+
+    Ok(PodNumberLease {
+        service_name: service_name.to_string(),
+        number,
+    })
+}
+
+impl PodNumberAllocator {
+    fn new(path: Option<PathBuf>, services: FxHashMap<String, ServiceNumbers>) -> Self {
+        Self {
+            path,
+            services: Mutex::new(services),
+            version: AtomicU64::new(0),
+            written: tokio::sync::Mutex::new(0),
+        }
+    }
+
+    // The numbers saved in `path`, if it exists
+    fn load(path: PathBuf) -> Result<Self> {
+        let mut services = FxHashMap::default();
+        if path.exists() {
+            let saved: FxHashMap<String, BTreeSet<u8>> =
+                serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            for (service_name, assigned) in saved {
+                services.insert(
+                    service_name,
+                    ServiceNumbers {
+                        assigned,
+                        leases: FxHashMap::default(),
+                    },
+                );
+            }
+        }
+        Ok(Self::new(Some(path), services))
+    }
+
+    fn allocate(
+        &self,
+        service_name: &str,
+        running: Option<&BTreeSet<u8>>,
+        listed_at: Instant,
+    ) -> Result<(u8, Snapshot)> {
+        let mut services = self.services.lock().unwrap();
+        let numbers = services.entry(service_name.to_string()).or_default();
+
+        if let Some(running) = running {
+            // A lease released after the listing may belong to a pod the listing missed
+            let recent: BTreeSet<u8> = numbers
+                .leases
+                .iter()
+                .filter(|(_, released)| released.is_none_or(|at| at >= listed_at))
+                .map(|(number, _)| *number)
+                .collect();
+
+            numbers
+                .assigned
+                .retain(|number| running.contains(number) || recent.contains(number));
+            numbers.assigned.extend(running.iter().copied());
+            numbers.leases.retain(|number, _| recent.contains(number));
+        }
+
+        let number = (0..=u8::MAX)
+            .find(|number| !numbers.assigned.contains(number))
+            .ok_or_else(|| {
+                anyhow!(
+                    "No free pod number for service {}, all {} are in use",
+                    service_name,
+                    u8::MAX as usize + 1
+                )
+            })?;
+
+        numbers.assigned.insert(number);
+        numbers.leases.insert(number, None);
+
+        Ok((number, self.snapshot(&services)))
+    }
+
+    fn release(&self, service_name: &str, number: u8) {
+        let mut services = self.services.lock().unwrap();
+        if let Some(lease) = services
+            .get_mut(service_name)
+            .and_then(|numbers| numbers.leases.get_mut(&number))
+        {
+            *lease = Some(Instant::now());
+        }
+    }
+
+    // Called with the services locked, so versions follow the order of changes
+    fn snapshot(&self, services: &FxHashMap<String, ServiceNumbers>) -> Snapshot {
+        let saved: FxHashMap<&str, &BTreeSet<u8>> = services
+            .iter()
+            .filter(|(_, numbers)| !numbers.assigned.is_empty())
+            .map(|(service_name, numbers)| (service_name.as_str(), &numbers.assigned))
+            .collect();
+        Snapshot {
+            version: self.version.fetch_add(1, Ordering::SeqCst) + 1,
+            json: serde_json::to_string(&saved).unwrap_or_default(),
+        }
+    }
+
+    // Written outside the services lock; a snapshot older than the one on
+    // disk is skipped
+    async fn persist(&self, snapshot: Snapshot) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut written = self.written.lock().await;
+        if snapshot.version <= *written {
+            return;
+        }
+
+        // Write then rename so a crash never leaves a truncated file behind
+        let tmp = path.with_extension("json.tmp");
+        let result = async {
+            tokio::fs::write(&tmp, snapshot.json).await?;
+            tokio::fs::rename(&tmp, path).await
+        }
+        .await;
+
+        match result {
+            Ok(()) => *written = snapshot.version,
+            Err(e) => {
+                slog::error!(slog_scope::logger(), "Failed to persist pod numbers";
+                    "path" => path.display().to_string(),
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(numbers: &[u8]) -> BTreeSet<u8> {
+        numbers.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_reuses_lowest_free_number() {
+        let allocator = PodNumberAllocator::new(None, FxHashMap::default());
+        let allocate = |running: Option<&BTreeSet<u8>>, listed_at| {
+            allocator.allocate("app", running, listed_at).unwrap().0
+        };
+
+        assert_eq!(allocate(None, Instant::now()), 0);
+        assert_eq!(allocate(None, Instant::now()), 1);
+        assert_eq!(allocate(None, Instant::now()), 2);
+
+        // Released, but its pod still runs
+        allocator.release("app", 0);
+        assert_eq!(allocate(Some(&numbers(&[0, 1, 2])), Instant::now()), 3);
+
+        // Released after the listing, which may have missed its pod
+        let listed_at = Instant::now();
+        allocator.release("app", 1);
+        assert_eq!(allocate(Some(&numbers(&[0, 2, 3])), listed_at), 4);
+
+        // Gone from the runtime and released before the listing
+        assert_eq!(allocate(Some(&numbers(&[0, 2, 3, 4])), Instant::now()), 1);
+
+        // Other services have their own numbers
+        assert_eq!(allocator.allocate("db", None, Instant::now()).unwrap().0, 0);
+    }
+
+    #[tokio::test]
+    async fn test_reload_from_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATE_FILE);
+
+        let allocator = PodNumberAllocator::load(path.clone()).unwrap();
+        for _ in 0..3 {
+            let (_, snapshot) = allocator.allocate("app", None, Instant::now()).unwrap();
+            allocator.persist(snapshot).await;
+        }
+        let (_, stale) = allocator.allocate("db", None, Instant::now()).unwrap();
+        let (_, snapshot) = allocator.allocate("db", None, Instant::now()).unwrap();
+        allocator.persist(snapshot).await;
+        // An older snapshot doesn't overwrite a newer one
+        allocator.persist(stale).await;
+
+        let saved: FxHashMap<String, BTreeSet<u8>> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["app"], numbers(&[0, 1, 2]));
+        assert_eq!(saved["db"], numbers(&[0, 1]));
+
+        // After a restart, numbers of pods that may still run are not handed out
+        let reloaded = PodNumberAllocator::load(path).unwrap();
+        assert_eq!(reloaded.allocate("app", None, Instant::now()).unwrap().0, 3);
+        // Until the runtime shows which ones are free
+        let running = numbers(&[1]);
+        assert_eq!(
+            reloaded
+                .allocate("db", Some(&running), Instant::now())
+                .unwrap()
+                .0,
+            0
+        );
+    }
+
+    #[test]
+    fn test_exhaustion() {
+        let allocator = PodNumberAllocator::new(None, FxHashMap::default());
+        for expected in 0..=u8::MAX {
+            let (number, _) = allocator.allocate("app", None, Instant::now()).unwrap();
+            assert_eq!(number, expected);
+        }
+        assert!(allocator.allocate("app", None, Instant::now()).is_err());
+
+        // A number frees up once its pod is gone
+        allocator.release("app", 7);
+        let running: BTreeSet<u8> = (0..=u8::MAX).filter(|number| *number != 7).collect();
+        let (number, _) = allocator
+            .allocate("app", Some(&running), Instant::now())
+            .unwrap();
+        assert_eq!(number, 7);
+    }
+}
@@ -0,0 +1,551 @@
+// src/container/preemption.rs
+//
+// Service priorities under resource pressure. A pod is only started if its
+// resource requests fit on the node. With `preemption` set in the daemon
+// config, a scale-up that the node has no memory for first takes a pod
+// from a lower-priority service, and critical memory pressure scales down the
+// lowest-priority service. Services never drop below `instance_count.min`, and
+// each preemption is recorded in the victim's scaling history. In `pause`
+// mode, memory pressure freezes the pod instead, and it is resumed once the
+// pressure passes.
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+use uuid::Uuid;
+
+use super::resources::{allocated_requests, memory_request, pod_requests, ResourceAmounts};
+use super::scaling::history::{
+    record_scaling_event, ScalingDirection, ScalingEvent, ScalingReason,
+};
+use super::scaling::scale_down;
+use super::{health, ContainerMetadata, ContainerRuntime, INSTANCE_STORE, RUNTIME};
+use crate::config::{
+    daemon::{get_daemon_config, PreemptionConfig, PreemptionMode},
+    parse_memory_limit, ServiceConfig, CONFIG_STORE,
+};
+use crate::node::get_node_status;
+use crate::proxy::{run_proxy_for_service, set_container_backends};
+
+pub const DEFAULT_PRIORITY: i32 = 0;
+
+// When the last pod was preempted or resumed, so the node is sampled again
+// before the next
+static LAST_PREEMPTION: Mutex<Option<Instant>> = Mutex::new(None);
+// Serializes picking victims, so concurrent scale-ups don't pick the same one
+static PREEMPTING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+// Pods picked as victims and still being scaled down or paused
+static PREEMPTED_PODS: Mutex<BTreeSet<Uuid>> = Mutex::new(BTreeSet::new());
+// Pods paused under memory pressure, oldest first
+static PAUSED_PODS: Mutex<Vec<PausedPod>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone)]
+struct PausedPod {
+    service_name: String,
+    uuid: Uuid,
+    priority: i32,
+    containers: Vec<ContainerMetadata>,
+}
+
+/// Whether the container belongs to a pod paused by preemption
+pub fn is_paused(container_name: &str) -> bool {
+    PAUSED_PODS.lock().unwrap().iter().any(|pod| {
+        pod.containers
+            .iter()
+            .any(|container| container.name == container_name)
+    })
+}
+
+/// Forget a paused container that is being stopped. True if it was paused,
+/// so it has to be unpaused before it can handle its stop signal.
+pub fn forget_paused(container_name: &str) -> bool {
+    let mut paused = PAUSED_PODS.lock().unwrap();
+    let mut found = false;
+    for pod in paused.iter_mut() {
+        let before = pod.containers.len();
+        pod.containers
+            .retain(|container| container.name != container_name);
+        found |= pod.containers.len() != before;
+    }
+    paused.retain(|pod| !pod.containers.is_empty());
+    found
+}
+
+// Pods that are being preempted or are paused, which no longer count as the
+// service's pods
+fn preempted_pods() -> BTreeSet<Uuid> {
+    let mut pods = PREEMPTED_PODS.lock().unwrap().clone();
+    pods.extend(PAUSED_PODS.lock().unwrap().iter().map(|pod| pod.uuid));
+    pods
+}
+
+pub fn priority_of(config: &ServiceConfig) -> i32 {
+    config.priority.unwrap_or(DEFAULT_PRIORITY)
+}
+
+/// Memory one pod of the service needs, from its containers' requests or limits
+fn pod_memory(config: &ServiceConfig) -> u64 {
+    let service_limit = config
+        .memory_limit
+        .as_ref()
+        .and_then(|limit| parse_memory_limit(limit).ok());
+    config
+        .spec
+        .containers
+        .iter()
+        .filter_map(|container| {
+            memory_request(container).or_else(|| {
+                container
+                    .memory_limit_value()
+                    .and_then(|limit| parse_memory_limit(limit).ok())
+                    .or(service_limit)
+            })
+        })
+        .sum()
+}
+
+/// Make room for another pod of the service. Its resource requests have to
+/// fit in what the node has left after the requests of the pods already
+/// running, and with `preemption` set a pod of a lower-priority service is
+/// preempted if the node is short of memory. Fails when there is no room and
+/// nothing to preempt.
+pub async fn ensure_capacity(config: &ServiceConfig) -> Result<()> {
+    // Nothing to go on until the node has been sampled
+    let Some(node) = get_node_status() else {
+        return Ok(());
+    };
+    let preemption = get_daemon_config().preemption;
+
+    let requested = pod_requests(config);
+    if requested != ResourceAmounts::default() {
+        let allocated = allocated_requests().await + requested;
+        let cpu_capacity = node.cpu.cores * 1_000_000_000;
+        let fits = |allocated: ResourceAmounts| {
+            allocated.memory <= node.memory.total && allocated.nano_cpus <= cpu_capacity
+        };
+        if !fits(allocated) {
+            let metrics = BTreeMap::from([
+                ("requested_memory".to_string(), requested.memory as f64),
+                (
+                    "requested_cpus".to_string(),
+                    requested.nano_cpus as f64 / 1e9,
+                ),
+                ("preemptor_priority".to_string(), priority_of(config) as f64),
+            ]);
+            // A paused pod keeps its requests, so only evicting one makes room
+            let preempted = preemption.is_some()
+                && preempt(Some(priority_of(config)), &config.name, metrics, false).await
+                && fits(allocated_requests().await + requested);
+            if !preempted {
+                return Err(anyhow!(
+                    "Insufficient node capacity: requests of {} bytes and {:.2} CPUs don't fit, {} bytes and {:.2} CPUs already requested of {} bytes and {} CPUs",
+                    requested.memory,
+                    requested.nano_cpus as f64 / 1e9,
+                    allocated.memory - requested.memory,
+                    (allocated.nano_cpus - requested.nano_cpus) as f64 / 1e9,
+                    node.memory.total,
+                    node.cpu.cores
+                ));
+            }
+        }
+    }
+
+    let Some(preemption) = preemption else {
+        return Ok(());
+    };
+
+    let required = pod_memory(config);
+    let critical = node.memory.percentage >= preemption.critical_memory_percentage;
+    if !critical && required <= node.memory.available {
+        return Ok(());
+    }
+
+    let priority = priority_of(config);
+    let metrics = BTreeMap::from([
+        ("memory_percentage".to_string(), node.memory.percentage),
+        ("required_memory".to_string(), required as f64),
+        ("preemptor_priority".to_string(), priority as f64),
+    ]);
+    let pause = preemption.mode == PreemptionMode::Pause;
+    if preempt(Some(priority), &config.name, metrics, pause).await {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Insufficient node capacity: {} bytes needed, {} available ({:.1}% used), nothing to preempt",
+        required,
+        node.memory.available,
+        node.memory.percentage
+    ))
+}
+
+/// How many more pods of the service fit in what the node has left after the
+/// requests of the pods already running. None if the service requests
+/// nothing or the node hasn't been sampled yet.
+pub async fn free_pod_slots(config: &ServiceConfig) -> Option<usize> {
+    let node = get_node_status()?;
+    let requested = pod_requests(config);
+    if requested == ResourceAmounts::default() {
+        return None;
+    }
+
+    let capacity = ResourceAmounts {
+        memory: node.memory.total,
+        nano_cpus: node.cpu.cores * 1_000_000_000,
+    };
+    Some(pod_slots(capacity, allocated_requests().await, requested))
+}
+
+// How many pods requesting `requested` fit in `capacity` once `allocated` is
+// taken, limited by whichever resource runs out first
+fn pod_slots(
+    capacity: ResourceAmounts,
+    allocated: ResourceAmounts,
+    requested: ResourceAmounts,
+) -> usize {
+    let slots = |capacity: u64, allocated: u64, requested: u64| match requested {
+        0 => usize::MAX,
+        requested => (capacity.saturating_sub(allocated) / requested) as usize,
+    };
+    slots(capacity.memory, allocated.memory, requested.memory).min(slots(
+        capacity.nano_cpus,
+        allocated.nano_cpus,
+        requested.nano_cpus,
+    ))
+}
+
+/// Scale down the lowest-priority service while node memory is critical
+pub fn start_pressure_monitor() {
+    let Some(preemption) = get_daemon_config().preemption else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(preemption.check_interval);
+        loop {
+            interval.tick().await;
+            check_pressure(&preemption).await;
+        }
+    });
+}
+
+async fn check_pressure(preemption: &PreemptionConfig) {
+    let Some(node) = get_node_status() else {
+        return;
+    };
+    // The last preemption may not show in the node's memory yet
+    if LAST_PREEMPTION
+        .lock()
+        .unwrap()
+        .is_some_and(|last| last.elapsed() < preemption.cooldown)
+    {
+        return;
+    }
+    if node.memory.percentage < preemption.critical_memory_percentage {
+        if node.memory.percentage < preemption.resume_memory_percentage {
+            resume_pod(node.memory.percentage).await;
+        }
+        return;
+    }
+
+    slog::warn!(slog_scope::logger(), "Critical memory pressure";
+        "memory_percentage" => node.memory.percentage,
+        "threshold" => preemption.critical_memory_percentage
+    );
+    let metrics = BTreeMap::from([("memory_percentage".to_string(), node.memory.percentage)]);
+    let pause = preemption.mode == PreemptionMode::Pause;
+    preempt(None, "memory pressure", metrics, pause).await;
+}
+
+// The newest pod of the lowest-priority service with pods above its minimum,
+// below `below` if set. Ties go to the service with the most pods to spare.
+// Pods already preempted or paused are left out.
+async fn select_victim(below: Option<i32>) -> Option<(ServiceConfig, usize, Uuid)> {
+    let configs: Vec<ServiceConfig> = {
+        let store = CONFIG_STORE.get()?.read().await;
+        store.values().map(|(_, config)| config.clone()).collect()
+    };
+    let preempted = preempted_pods();
+    let instances = INSTANCE_STORE.get()?.read().await;
+    let pods_of = |service_name: &str| {
+        instances.get(service_name).map_or(0, |pods| {
+            pods.keys()
+                .filter(|uuid| !preempted.contains(*uuid))
+                .count()
+        })
+    };
+
+    let (victim, pods) = configs
+        .into_iter()
+        .filter(|config| config.external.is_none())
+        .filter(|config| below.is_none_or(|below| priority_of(config) < below))
+        .filter_map(|config| {
+            let pods = pods_of(&config.name);
+            (pods > config.instance_count.min as usize).then_some((config, pods))
+        })
+        .min_by(|(a, a_pods), (b, b_pods)| {
+            let spare =
+                |config: &ServiceConfig, pods: usize| pods - config.instance_count.min as usize;
+            priority_of(a)
+                .cmp(&priority_of(b))
+                .then(spare(b, *b_pods).cmp(&spare(a, *a_pods)))
+                .then(a.name.cmp(&b.name))
+        })?;
+    let uuid = instances[&victim.name]
+        .values()
+        .filter(|pod| !preempted.contains(&pod.uuid))
+        .max_by_key(|pod| pod.created_at)?
+        .uuid;
+    Some((victim, pods, uuid))
+}
+
+// Scale down, or pause if `pause` is set, the newest pod of the selected
+// victim. Returns whether a pod was taken.
+async fn preempt(
+    below: Option<i32>,
+    preemptor: &str,
+    mut metrics: BTreeMap<String, f64>,
+    pause: bool,
+) -> bool {
+    let log = slog_scope::logger();
+
+    // The victim is marked before the lock is released, so the scale-down
+    // doesn't hold up other preemptions
+    let (victim, pods, uuid) = {
+        let _guard = PREEMPTING.lock().await;
+        let Some((victim, pods, uuid)) = select_victim(below).await else {
+            slog::warn!(log, "No pod to preempt"; "preemptor" => preemptor);
+            return false;
+        };
+        PREEMPTED_PODS.lock().unwrap().insert(uuid);
+        (victim, pods, uuid)
+    };
+
+    slog::warn!(log, "Preempting pod";
+        "service" => &victim.name,
+        "pod" => uuid.to_string(),
+        "priority" => priority_of(&victim),
+        "preemptor" => preemptor,
+        "pause" => pause
+    );
+    let runtime = RUNTIME.get().expect("Runtime not initialized").clone();
+    let paused = pause && pause_pod(&victim, uuid, runtime.as_ref()).await;
+    let result = if paused {
+        Ok(())
+    } else {
+        scale_down(&victim.name, uuid, victim.clone(), runtime).await
+    };
+    PREEMPTED_PODS.lock().unwrap().remove(&uuid);
+    *LAST_PREEMPTION.lock().unwrap() = Some(Instant::now());
+
+    metrics.insert("priority".to_string(), priority_of(&victim) as f64);
+    if paused {
+        metrics.insert("paused".to_string(), 1.0);
+    }
+    record_scaling_event(
+        &victim.name,
+        ScalingEvent {
+            timestamp: SystemTime::now(),
+            direction: ScalingDirection::Down,
+            from: pods,
+            to: if result.is_ok() { pods - 1 } else { pods },
+            reason: ScalingReason {
+                policy: "preemption".to_string(),
+                metrics,
+            },
+            error: result.as_ref().err().map(|e| e.to_string()),
+        },
+    );
+
+    match result {
+        Ok(()) if paused => true,
+        Ok(()) => {
+            run_proxy_for_service(victim.name.clone(), victim).await;
+            true
+        }
+        Err(e) => {
+            slog::error!(log, "Failed to preempt pod";
+                "service" => &victim.name,
+                "error" => e.to_string()
+            );
+            false
+        }
+    }
+}
+
+// Take the pod out of the load balancer and freeze its containers. A pod that
+// can't be paused as a whole is unpaused again, to be evicted instead.
+async fn pause_pod(victim: &ServiceConfig, uuid: Uuid, runtime: &dyn ContainerRuntime) -> bool {
+    let containers = match INSTANCE_STORE.get() {
+        Some(store) => store
+            .read()
+            .await
+            .get(&victim.name)
+            .and_then(|pods| pods.get(&uuid))
+            .map(|pod| pod.containers.clone())
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    if containers.is_empty() {
+        return false;
+    }
+
+    // Marked first, so health checks leave the containers alone
+    PAUSED_PODS.lock().unwrap().push(PausedPod {
+        service_name: victim.name.clone(),
+        uuid,
+        priority: priority_of(victim),
+        containers: containers.clone(),
+    });
+    for container in &containers {
+        set_container_backends(&victim.name, container, false).await;
+    }
+
+    for (i, container) in containers.iter().enumerate() {
+        if let Err(e) = runtime.pause_container(&container.name).await {
+            slog::warn!(slog_scope::logger(), "Failed to pause pod, evicting it instead";
+                "service" => &victim.name,
+                "container" => &container.name,
+                "error" => e.to_string()
+            );
+            for container in &containers[..i] {
+                let _ = runtime.unpause_container(&container.name).await;
+            }
+            PAUSED_PODS.lock().unwrap().retain(|pod| pod.uuid != uuid);
+            return false;
+        }
+    }
+    true
+}
+
+// Resume the paused pod of the highest-priority service, oldest first, and
+// put its ready containers back in the load balancer
+async fn resume_pod(memory_percentage: f64) {
+    let pod = {
+        let mut paused = PAUSED_PODS.lock().unwrap();
+        let Some(index) = paused
+            .iter()
+            .enumerate()
+            .max_by(|(a_index, a), (b_index, b)| {
+                a.priority.cmp(&b.priority).then(b_index.cmp(a_index))
+            })
+            .map(|(index, _)| index)
+        else {
+            return;
+        };
+        paused.remove(index)
+    };
+
+    let runtime = RUNTIME.get().expect("Runtime not initialized").clone();
+    let mut error = None;
+    for container in &pod.containers {
+        if let Err(e) = runtime.unpause_container(&container.name).await {
+            error = Some(e.to_string());
+            continue;
+        }
+        if health::is_routable(&container.name).await {
+            set_container_backends(&pod.service_name, container, true).await;
+        }
+    }
+    *LAST_PREEMPTION.lock().unwrap() = Some(Instant::now());
+
+    slog::info!(slog_scope::logger(), "Resumed preempted pod";
+        "service" => &pod.service_name,
+        "pod" => pod.uuid.to_string(),
+        "memory_percentage" => memory_percentage
+    );
+    // Pods of the service that serve, the resumed one included
+    let preempted = preempted_pods();
+    let serving = match INSTANCE_STORE.get() {
+        Some(store) => store.read().await.get(&pod.service_name).map_or(0, |pods| {
+            pods.keys()
+                .filter(|uuid| !preempted.contains(*uuid))
+                .count()
+        }),
+        None => 0,
+    };
+    record_scaling_event(
+        &pod.service_name,
+        ScalingEvent {
+            timestamp: SystemTime::now(),
+            direction: ScalingDirection::Up,
+            from: serving.saturating_sub(1),
+            to: serving,
+            reason: ScalingReason {
+                policy: "preemption".to_string(),
+                metrics: BTreeMap::from([
+                    ("memory_percentage".to_string(), memory_percentage),
+                    ("priority".to_string(), pod.priority as f64),
+                    ("resumed".to_string(), 1.0),
+                ]),
+            },
+            error,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+    const CPU: u64 = 1_000_000_000;
+
+    fn amounts(memory: u64, nano_cpus: u64) -> ResourceAmounts {
+        ResourceAmounts { memory, nano_cpus }
+    }
+
+    #[test]
+    fn test_pod_slots() {
+        let capacity = amounts(8 * GIB, 4 * CPU);
+
+        // Memory runs out first
+        assert_eq!(
+            pod_slots(capacity, amounts(2 * GIB, CPU), amounts(2 * GIB, CPU / 4)),
+            3
+        );
+        // CPU runs out first
+        assert_eq!(
+            pod_slots(capacity, amounts(0, 3 * CPU), amounts(GIB, CPU / 2)),
+            2
+        );
+        // A resource that isn't requested doesn't limit
+        assert_eq!(pod_slots(capacity, amounts(0, 0), amounts(GIB, 0)), 8);
+        // Overcommitted
+        assert_eq!(
+            pod_slots(capacity, amounts(9 * GIB, 0), amounts(GIB, CPU)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_forget_paused() {
+        let container = |name: &str| ContainerMetadata {
+            name: name.to_string(),
+            network: String::new(),
+            ip_address: String::new(),
+            ports: Vec::new(),
+            status: String::new(),
+        };
+        let uuid = Uuid::new_v4();
+        PAUSED_PODS.lock().unwrap().push(PausedPod {
+            service_name: "batch".to_string(),
+            uuid,
+            priority: -1,
+            containers: vec![
+                container("batch__1__app__x"),
+                container("batch__1__sidecar__x"),
+            ],
+        });
+
+        assert!(is_paused("batch__1__app__x"));
+        assert!(preempted_pods().contains(&uuid));
+        assert!(forget_paused("batch__1__app__x"));
+        assert!(!is_paused("batch__1__app__x"));
+        assert!(!forget_paused("batch__1__app__x"));
+
+        // The pod is forgotten with its last container
+        assert!(preempted_pods().contains(&uuid));
+        assert!(forget_paused("batch__1__sidecar__x"));
+        assert!(!preempted_pods().contains(&uuid));
+    }
+}
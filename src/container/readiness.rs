@@ -0,0 +1,204 @@
+// src/container/readiness.rs
+//
+// What "deployment complete" means for a service: how many pods must be ready,
+// whether every health check has to pass, and which smoke tests must succeed.
+// Used by rolling updates, the readiness API and `orbit wait`.
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::health::{get_container_health, HealthState};
+use super::{InstanceMetadata, INSTANCE_STORE};
+use crate::config::{parse_container_name, ServiceConfig};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReadinessConfig {
+    /// Pods that must be ready, defaults to instance_count.min
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ready: Option<u8>,
+    /// A pod is only ready once all of its containers report healthy
+    #[serde(default = "default_require_healthy")]
+    pub require_healthy: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub smoke_tests: Vec<SmokeTest>,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            min_ready: None,
+            require_healthy: default_require_healthy(),
+            smoke_tests: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SmokeTest {
+    pub name: String,
+    /// Requested with GET, e.g. "http://localhost:30080/healthz"
+    pub url: String,
+    /// Expected status code, any 2xx when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expect_status: Option<u16>,
+    #[serde(with = "humantime_serde", default = "default_smoke_timeout")]
+    pub timeout: Duration,
+}
+
+fn default_require_healthy() -> bool {
+    true
+}
+
+fn default_smoke_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReadinessReport {
+    pub service_name: String,
+    pub ready: bool,
+    pub ready_pods: usize,
+    pub required_pods: usize,
+    pub total_pods: usize,
+    /// Reasons the service is not ready yet
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<String>,
+}
+
+async fn pod_ready(
+    config: &ServiceConfig,
+    readiness: &ReadinessConfig,
+    pod: &InstanceMetadata,
+) -> bool {
+    if pod.containers.is_empty() {
+        return false;
+    }
+    if !readiness.require_healthy {
+        return true;
+    }
+
+    for container in &pod.containers {
+        if !container_healthy(config, &container.name).await {
+            return false;
+        }
+    }
+    true
+}
+
+/// Whether a container reports healthy and passes its readiness probe.
+/// Unmonitored containers (e.g. adopted ones) only count if they have no probe.
+pub async fn container_healthy(config: &ServiceConfig, container_name: &str) -> bool {
+    match get_container_health(container_name).await {
+        Some(health) => matches!(health.state, HealthState::Healthy) && health.ready,
+        None => parse_container_name(container_name)
+            .ok()
+            .and_then(|parts| {
+                config
+                    .spec
+                    .containers
+                    .iter()
+                    .find(|c| c.name == parts.container_name)
+            })
+            .is_none_or(|c| c.health_check.is_none()),
+    }
+}
+
+async fn run_smoke_test(test: &SmokeTest) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(test.timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let status = client
+        .get(&test.url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .status();
+
+    let passed = match test.expect_status {
+        Some(expected) => status.as_u16() == expected,
+        None => status.is_success(),
+    };
+    if passed {
+        Ok(())
+    } else {
+        Err(format!("returned {}", status))
+    }
+}
+
+/// Evaluate the service's readiness definition. With `pods`, only those pods are
+/// considered and all of them must be ready (used while rolling out new pods).
+pub async fn evaluate(config: &ServiceConfig, pods: Option<&[Uuid]>) -> ReadinessReport {
+    let readiness = config.readiness.clone().unwrap_or_default();
+
+    let instances: Vec<InstanceMetadata> = match INSTANCE_STORE.get() {
+        Some(store) => store
+            .read()
+            .await
+            .get(&config.name)
+            .map(|instances| {
+                instances
+                    .values()
+                    .filter(|pod| pods.is_none_or(|pods| pods.contains(&pod.uuid)))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let mut ready_pods = 0;
+    for pod in &instances {
+        if pod_ready(config, &readiness, pod).await {
+            ready_pods += 1;
+        }
+    }
+
+    let required_pods = match pods {
+        Some(pods) => pods.len(),
+        None => readiness.min_ready.unwrap_or(config.instance_count.min) as usize,
+    };
+
+    let mut pending = Vec::new();
+    if ready_pods < required_pods {
+        pending.push(format!(
+            "{} of {} required pods ready",
+            ready_pods, required_pods
+        ));
+    }
+
+    // Smoke tests only make sense once enough pods are serving
+    if pending.is_empty() {
+        for test in &readiness.smoke_tests {
+            if let Err(e) = run_smoke_test(test).await {
+                pending.push(format!("smoke test {} failed: {}", test.name, e));
+            }
+        }
+    }
+
+    ReadinessReport {
+        service_name: config.name.clone(),
+        ready: pending.is_empty(),
+        ready_pods,
+        required_pods,
+        total_pods: instances.len(),
+        pending,
+    }
+}
+
+/// Poll `evaluate` until the service is ready or `timeout` elapses, returning the last report.
+pub async fn wait_until_ready(
+    config: &ServiceConfig,
+    pods: Option<&[Uuid]>,
+    timeout: Duration,
+) -> ReadinessReport {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let report = evaluate(config, pods).await;
+        if report.ready || tokio::time::Instant::now() >= deadline {
+            return report;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
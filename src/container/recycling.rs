@@ -0,0 +1,97 @@
+// src/container/recycling.rs
+//
+// Time-based pod recycling. Pods of a service with `max_pod_lifetime` are
+// replaced once they are older than it, which bounds slow memory leaks and
+// picks up freshly pulled images. Expired pods go through the rolling update
+// path, oldest first and at most `rolling_update_config.max_surge` at a time:
+// their replacements must be healthy and ready before they are removed, so
+// recycling never takes a service below its current capacity.
+use rustc_hash::FxHashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::rolling_update::recycle_pods;
+use super::{stopped, INSTANCE_STORE};
+use crate::config::CONFIG_STORE;
+
+static RECYCLING: OnceLock<Mutex<FxHashSet<String>>> = OnceLock::new();
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+fn recycling() -> &'static Mutex<FxHashSet<String>> {
+    RECYCLING.get_or_init(|| Mutex::new(FxHashSet::default()))
+}
+
+pub fn start_recycling() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            check().await;
+        }
+    });
+}
+
+async fn check() {
+    let (Some(configs), Some(instance_store)) = (CONFIG_STORE.get(), INSTANCE_STORE.get()) else {
+        return;
+    };
+    let services: Vec<(String, Duration, usize)> = configs
+        .read()
+        .await
+        .values()
+        .filter(|(_, config)| config.external.is_none() && !stopped::is_stopped(config))
+        .filter_map(|(_, config)| {
+            let max_surge = config
+                .rolling_update_config
+                .as_ref()
+                .map_or(1, |update| update.max_surge as usize);
+            config
+                .max_pod_lifetime
+                .map(|lifetime| (config.name.clone(), lifetime, max_surge))
+        })
+        .collect();
+
+    for (service_name, lifetime, max_surge) in services {
+        let expired: Vec<Uuid> = {
+            let store = instance_store.read().await;
+            let Some(instances) = store.get(&service_name) else {
+                continue;
+            };
+            let mut expired: Vec<_> = instances
+                .values()
+                .filter(|metadata| {
+                    metadata
+                        .created_at
+                        .elapsed()
+                        .is_ok_and(|age| age > lifetime)
+                })
+                .collect();
+            expired.sort_by_key(|metadata| metadata.created_at);
+            expired
+                .into_iter()
+                .take(max_surge)
+                .map(|metadata| metadata.uuid)
+                .collect()
+        };
+        if expired.is_empty() || !recycling().lock().unwrap().insert(service_name.clone()) {
+            continue;
+        }
+
+        slog::info!(slog_scope::logger(), "Recycling pods past their maximum lifetime";
+            "service" => &service_name,
+            "pods" => expired.len(),
+            "max_pod_lifetime" => format!("{:?}", lifetime)
+        );
+        tokio::spawn(async move {
+            if let Err(e) = recycle_pods(&service_name, &expired).await {
+                slog::error!(slog_scope::logger(), "Failed to recycle pods";
+                    "service" => &service_name,
+                    "error" => e.to_string()
+                );
+            }
+            recycling().lock().unwrap().remove(&service_name);
+        });
+    }
+}
@@ -0,0 +1,176 @@
+// src/container/registry.rs
+//
+// Credentials for pulling images from private registries. A service lists its
+// image pull secrets; each image uses the first one whose registry matches,
+// as a username and password, a registry token, or the entry for the
+// registry in a docker config.json.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::secrets::{resolve_source, SecretSource, SecretValue};
+use crate::config::ServiceConfig;
+
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImagePullSecret {
+    /// Registry host the secret is for, e.g. "ghcr.io"; any registry when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<SecretSource>,
+    /// Registry token, sent instead of a username and password
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<SecretSource>,
+    /// A docker config.json, e.g. written by `docker login`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_json: Option<PathBuf>,
+}
+
+/// Credentials for one registry, resolved when an image is pulled
+#[derive(Debug, Clone, Default)]
+pub struct RegistryCredentials {
+    pub registry: String,
+    pub username: Option<String>,
+    pub password: Option<SecretValue>,
+    /// base64 of "username:password", as stored in config.json
+    pub auth: Option<SecretValue>,
+    pub identity_token: Option<SecretValue>,
+    pub registry_token: Option<SecretValue>,
+}
+
+#[derive(Deserialize)]
+struct DockerConfigFile {
+    #[serde(default)]
+    auths: HashMap<String, DockerConfigAuth>,
+}
+
+#[derive(Deserialize)]
+struct DockerConfigAuth {
+    auth: Option<String>,
+    identitytoken: Option<String>,
+}
+
+/// The registry host of an image reference, "docker.io" for Docker Hub
+pub fn image_registry(image: &str) -> String {
+    match image.split_once('/') {
+        // Like Docker, only a first component that looks like a host is a registry
+        Some((host, _)) if host.contains(['.', ':']) || host == "localhost" => {
+            normalize_registry(host)
+        }
+        _ => DEFAULT_REGISTRY.to_string(),
+    }
+}
+
+// "https://index.docker.io/v1/" and "docker.io" name the same registry
+fn normalize_registry(registry: &str) -> String {
+    let host = registry
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match host.as_str() {
+        "index.docker.io" | "registry-1.docker.io" => DEFAULT_REGISTRY.to_string(),
+        _ => host,
+    }
+}
+
+impl ImagePullSecret {
+    /// Check the secret sets exactly one kind of credentials
+    pub fn validate(&self) -> Result<()> {
+        let kinds = [
+            self.username.is_some() || self.password.is_some(),
+            self.token.is_some(),
+            self.config_json.is_some(),
+        ];
+        match kinds.iter().filter(|set| **set).count() {
+            1 => {}
+            0 => return Err(anyhow!("Set username and password, token or config_json")),
+            _ => {
+                return Err(anyhow!(
+                    "Only one of username and password, token or config_json may be set"
+                ))
+            }
+        }
+        if self.username.is_some() != self.password.is_some() {
+            return Err(anyhow!("username and password must be set together"));
+        }
+        if matches!(&self.password, Some(SecretSource::Command(command)) if command.is_empty())
+            || matches!(&self.token, Some(SecretSource::Command(command)) if command.is_empty())
+        {
+            return Err(anyhow!("from_command must not be empty"));
+        }
+        Ok(())
+    }
+
+    async fn credentials(&self, registry: &str) -> Result<Option<RegistryCredentials>> {
+        if self
+            .registry
+            .as_deref()
+            .is_some_and(|r| normalize_registry(r) != registry)
+        {
+            return Ok(None);
+        }
+
+        let name = format!("image pull secret for {}", registry);
+        let mut credentials = RegistryCredentials {
+            registry: registry.to_string(),
+            ..Default::default()
+        };
+
+        if let Some(path) = &self.config_json {
+            let contents = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+            let config: DockerConfigFile = serde_json::from_str(&contents)
+                .map_err(|e| anyhow!("Invalid docker config {}: {}", path.display(), e))?;
+            let Some(auth) = config
+                .auths
+                .into_iter()
+                .find(|(key, _)| normalize_registry(key) == registry)
+                .map(|(_, auth)| auth)
+            else {
+                return Ok(None);
+            };
+            credentials.auth = auth.auth.map(SecretValue::from);
+            credentials.identity_token = auth.identitytoken.map(SecretValue::from);
+        } else if let Some(token) = &self.token {
+            credentials.registry_token = Some(resolve_source(&name, token).await?);
+        } else if let Some(password) = &self.password {
+            credentials.username = self.username.clone();
+            credentials.password = Some(resolve_source(&name, password).await?);
+        }
+
+        Ok(Some(credentials))
+    }
+}
+
+/// Credentials for pulling `image` with the service's image pull secrets,
+/// None to pull anonymously
+pub async fn registry_credentials(
+    service_config: &ServiceConfig,
+    image: &str,
+) -> Result<Option<RegistryCredentials>> {
+    let registry = image_registry(image);
+    for secret in &service_config.image_pull_secrets {
+        if let Some(credentials) = secret.credentials(&registry).await? {
+            let values = [
+                &credentials.password,
+                &credentials.auth,
+                &credentials.identity_token,
+                &credentials.registry_token,
+            ];
+            for value in values.into_iter().flatten() {
+                crate::redact::register_for_service(&service_config.name, value.expose());
+            }
+            return Ok(Some(credentials));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,188 @@
+// src/container/resources.rs
+//
+// Resource requests and limits, as in Kubernetes. A container's `limits` are
+// enforced by the runtime's cgroups, like `memory_limit` and `cpu_limit`,
+// while its `requests` are what it is guaranteed: they are reserved on the
+// node when a pod is scheduled, and a pod whose requests don't fit in what is
+// left of the node's memory and CPUs is not started.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Container, INSTANCE_STORE};
+use crate::config::{parse_cpu_limit, parse_memory_limit, ServiceConfig, CONFIG_STORE};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResourceRequirements {
+    /// Reserved on the node for scheduling
+    #[serde(default, skip_serializing_if = "ResourceList::is_empty")]
+    pub requests: ResourceList,
+    /// Enforced by the runtime, replacing memory_limit and cpu_limit
+    #[serde(default, skip_serializing_if = "ResourceList::is_empty")]
+    pub limits: ResourceList,
+}
+
+/// Memory and CPU, in the same formats as memory_limit and cpu_limit
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResourceList {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<Value>,
+}
+
+impl ResourceList {
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.cpu.is_none()
+    }
+}
+
+/// Memory in bytes and CPU in nanoCPUs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceAmounts {
+    pub memory: u64,
+    pub nano_cpus: u64,
+}
+
+impl std::ops::Add for ResourceAmounts {
+    type Output = ResourceAmounts;
+
+    fn add(self, other: ResourceAmounts) -> ResourceAmounts {
+        ResourceAmounts {
+            memory: self.memory + other.memory,
+            nano_cpus: self.nano_cpus + other.nano_cpus,
+        }
+    }
+}
+
+fn requests_of(container: &Container) -> Option<&ResourceList> {
+    container
+        .resources
+        .as_ref()
+        .map(|resources| &resources.requests)
+}
+
+/// Memory the container requests, in bytes
+pub fn memory_request(container: &Container) -> Option<u64> {
+    requests_of(container)?
+        .memory
+        .as_ref()
+        .and_then(|memory| parse_memory_limit(memory).ok())
+}
+
+/// What one pod of the service requests, the sum of its containers' requests
+pub fn pod_requests(config: &ServiceConfig) -> ResourceAmounts {
+    config
+        .spec
+        .containers
+        .iter()
+        .filter_map(requests_of)
+        .map(|requests| ResourceAmounts {
+            memory: requests
+                .memory
+                .as_ref()
+                .and_then(|memory| parse_memory_limit(memory).ok())
+                .unwrap_or(0),
+            nano_cpus: requests
+                .cpu
+                .as_ref()
+                .and_then(|cpu| parse_cpu_limit(cpu).ok())
+                .unwrap_or(0),
+        })
+        .fold(ResourceAmounts::default(), |total, pod| total + pod)
+}
+
+/// What the pods running on the node request together
+pub async fn allocated_requests() -> ResourceAmounts {
+    let (Some(configs), Some(instances)) = (CONFIG_STORE.get(), INSTANCE_STORE.get()) else {
+        return ResourceAmounts::default();
+    };
+    let configs = configs.read().await;
+    let instances = instances.read().await;
+
+    configs
+        .values()
+        .map(|(_, config)| {
+            let pods = instances.get(&config.name).map_or(0, |pods| pods.len()) as u64;
+            let requests = pod_requests(config);
+            ResourceAmounts {
+                memory: requests.memory * pods,
+                nano_cpus: requests.nano_cpus * pods,
+            }
+        })
+        .fold(ResourceAmounts::default(), |total, service| total + service)
+}
+
+/// Check that requests and limits parse, that limits aren't also set through
+/// memory_limit or cpu_limit, and that no request exceeds its limit
+pub fn validate(container: &Container, config: &ServiceConfig) -> Result<()> {
+    let Some(resources) = &container.resources else {
+        return Ok(());
+    };
+    let parse = |value: &Option<Value>, parser: fn(&Value) -> Result<u64>, name: &str| {
+        value
+            .as_ref()
+            .map(|value| parser(value).map_err(|e| anyhow!("{}: {}", name, e)))
+            .transpose()
+    };
+
+    if resources.limits.memory.is_some() && container.memory_limit.is_some() {
+        return Err(anyhow!(
+            "resources.limits.memory and memory_limit can't both be set"
+        ));
+    }
+    if resources.limits.cpu.is_some() && container.cpu_limit.is_some() {
+        return Err(anyhow!(
+            "resources.limits.cpu and cpu_limit can't both be set"
+        ));
+    }
+
+    let memory_request = parse(
+        &resources.requests.memory,
+        parse_memory_limit,
+        "resources.requests.memory",
+    )?;
+    let cpu_request = parse(
+        &resources.requests.cpu,
+        parse_cpu_limit,
+        "resources.requests.cpu",
+    )?;
+    parse(
+        &resources.limits.memory,
+        parse_memory_limit,
+        "resources.limits.memory",
+    )?;
+    parse(
+        &resources.limits.cpu,
+        parse_cpu_limit,
+        "resources.limits.cpu",
+    )?;
+
+    let memory_limit = container
+        .memory_limit_value()
+        .or(config.memory_limit.as_ref())
+        .and_then(|limit| parse_memory_limit(limit).ok());
+    if let (Some(request), Some(limit)) = (memory_request, memory_limit) {
+        if request > limit {
+            return Err(anyhow!(
+                "memory request of {} bytes exceeds the limit of {} bytes",
+                request,
+                limit
+            ));
+        }
+    }
+    let cpu_limit = container
+        .cpu_limit_value()
+        .or(config.cpu_limit.as_ref())
+        .and_then(|limit| parse_cpu_limit(limit).ok());
+    if let (Some(request), Some(limit)) = (cpu_request, cpu_limit) {
+        if request > limit {
+            return Err(anyhow!(
+                "CPU request of {} nanoCPUs exceeds the limit of {} nanoCPUs",
+                request,
+                limit
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,547 @@
+// src/container/restart.rs
+//
+// Containers with a restart policy get a supervisor task that waits for the
+// runtime to report their exit and starts them again in place, keeping the
+// pod, its network and its load balancer entries. A remediation loop restarts
+// containers whose health monitoring failed, with the same backoff. Flapping
+// containers can be made to wait for the longest backoff. Containers that
+// collect crash dumps are supervised too, for their crashes.
+use anyhow::{anyhow, Result};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::changes::{record_change, InstanceEvent};
+use super::crash_dumps;
+use super::health::{
+    get_container_health, is_routable, record_container_restart, HealthState, CONTAINER_HEALTH,
+};
+use super::lifecycle::run_post_start;
+use super::{
+    find_host_port, Container, ContainerPortMetadata, ContainerRuntime, INSTANCE_STORE, RUNTIME,
+};
+use crate::config::{get_config_by_service, parse_container_name, ServiceConfig};
+use crate::proxy::set_container_backends;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+// A container that stays up this long starts over with a fresh retry budget
+const RESET_AFTER: Duration = Duration::from_secs(600);
+const WAIT_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const REMEDIATION_INTERVAL: Duration = Duration::from_secs(10);
+
+// Supervised containers Orbit restarted itself, whose exit the supervisor skips
+static RESTARTED_IN_PLACE: OnceLock<Mutex<FxHashSet<String>>> = OnceLock::new();
+
+fn restarted_in_place() -> &'static Mutex<FxHashSet<String>> {
+    RESTARTED_IN_PLACE.get_or_init(|| Mutex::new(FxHashSet::default()))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicyValue {
+    Always,
+    OnFailure,
+    Never,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestartPolicy {
+    pub policy: RestartPolicyValue,
+    /// Consecutive restarts before giving up; unlimited when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+}
+
+impl RestartPolicy {
+    fn should_restart(&self, exit_code: i64) -> bool {
+        match self.policy {
+            RestartPolicyValue::Always => true,
+            RestartPolicyValue::OnFailure => exit_code != 0,
+            RestartPolicyValue::Never => false,
+        }
+    }
+}
+
+// Whether a supervisor waits for the container's exits
+fn is_supervised(container: &Container) -> bool {
+    container.restart_policy.is_some() || container.crash_dumps.is_some()
+}
+
+/// Supervise the containers of a newly started pod that have a restart policy
+/// or collect crash dumps
+pub fn supervise_containers(
+    service_name: &str,
+    config: &ServiceConfig,
+    started_containers: &[(String, String, Vec<ContainerPortMetadata>)],
+) {
+    supervise_named(
+        service_name,
+        config,
+        started_containers.iter().map(|(name, _, _)| name),
+    );
+}
+
+/// Supervise the containers of pods adopted from a previous run, as if Orbit
+/// had started them
+pub fn supervise_adopted(service_name: &str, config: &ServiceConfig, container_names: &[String]) {
+    supervise_named(service_name, config, container_names.iter());
+}
+
+fn supervise_named<'a>(
+    service_name: &str,
+    config: &ServiceConfig,
+    container_names: impl Iterator<Item = &'a String>,
+) {
+    let Some(runtime) = RUNTIME.get() else {
+        return;
+    };
+
+    for container_name in container_names {
+        let supervised = parse_container_name(container_name)
+            .ok()
+            .and_then(|parts| {
+                config
+                    .spec
+                    .containers
+                    .iter()
+                    .find(|c| c.name == parts.container_name)
+            })
+            .is_some_and(is_supervised);
+
+        if supervised {
+            tokio::spawn(supervise(
+                service_name.to_string(),
+                container_name.clone(),
+                runtime.clone(),
+            ));
+        }
+    }
+}
+
+// The current config of the container, so reloads apply to running supervisors.
+// A container without a restart policy is never restarted.
+async fn current_config(
+    service_name: &str,
+    container_name: &str,
+) -> Option<(ServiceConfig, Container, RestartPolicy)> {
+    let parts = parse_container_name(container_name).ok()?;
+    let config = get_config_by_service(service_name).await?;
+    let container = config
+        .spec
+        .containers
+        .iter()
+        .find(|c| c.name == parts.container_name)?
+        .clone();
+    let policy = container.restart_policy.clone().unwrap_or(RestartPolicy {
+        policy: RestartPolicyValue::Never,
+        max_retries: None,
+    });
+    Some((config, container, policy))
+}
+
+/// Restart a running container in place, e.g. after it failed its liveness probe,
+/// keeping its pod and load balancer entries
+pub async fn restart_in_place(service_name: &str, container_name: &str) -> Result<()> {
+    let runtime = RUNTIME
+        .get()
+        .ok_or_else(|| anyhow!("Runtime not initialized"))?;
+    let parts = parse_container_name(container_name)?;
+    let config = get_config_by_service(service_name)
+        .await
+        .ok_or_else(|| anyhow!("Service {} not found", service_name))?;
+    let container = config
+        .spec
+        .containers
+        .iter()
+        .find(|c| c.name == parts.container_name)
+        .ok_or_else(|| anyhow!("Container {} not found in config", parts.container_name))?
+        .clone();
+
+    if !set_status(service_name, container_name, "restarting").await {
+        return Ok(());
+    }
+    if is_supervised(&container) {
+        restarted_in_place()
+            .lock()
+            .unwrap()
+            .insert(container_name.to_string());
+    }
+    if let Err(e) = runtime
+        .restart_container(container_name, &container, &config)
+        .await
+    {
+        set_status(service_name, container_name, "failed").await;
+        return Err(e);
+    }
+
+    set_status(service_name, container_name, "running").await;
+    refresh_address(runtime.as_ref(), service_name, container_name).await;
+    run_post_start(container_name).await;
+    record_container_restart(service_name, container_name, container.health_check).await
+}
+
+// A restarted container may come back with a new address. Update the instance
+// store and move its load balancer entries to the new address.
+async fn refresh_address(runtime: &dyn ContainerRuntime, service_name: &str, container_name: &str) {
+    let stats = match runtime.inspect_container(container_name).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            slog::warn!(slog_scope::logger(), "Failed to inspect restarted container";
+                "service" => service_name,
+                "container" => container_name,
+                "error" => e.to_string()
+            );
+            return;
+        }
+    };
+    let Some(instance_store) = INSTANCE_STORE.get() else {
+        return;
+    };
+
+    let (previous, current) = {
+        let mut store = instance_store.write().await;
+        let Some(instances) = store.get_mut(service_name) else {
+            return;
+        };
+        let Some(instance) = instances
+            .values_mut()
+            .find(|instance| instance.containers.iter().any(|c| c.name == container_name))
+        else {
+            return;
+        };
+        let container = instance
+            .containers
+            .iter_mut()
+            .find(|c| c.name == container_name)
+            .expect("container was just found");
+
+        let previous = container.clone();
+        if !stats.ip_address.is_empty() {
+            container.ip_address = stats.ip_address.clone();
+        }
+        for port in container.ports.iter_mut().filter(|p| p.host_port.is_some()) {
+            port.host_port = find_host_port(&stats, port.port).or(port.host_port);
+        }
+        let unchanged = container.ip_address == previous.ip_address
+            && container
+                .ports
+                .iter()
+                .zip(&previous.ports)
+                .all(|(a, b)| a.host_port == b.host_port);
+        if unchanged {
+            return;
+        }
+
+        let current = container.clone();
+        record_change(
+            service_name,
+            InstanceEvent::PodUpdated {
+                instance: instance.clone(),
+            },
+        );
+        (previous, current)
+    };
+
+    slog::info!(slog_scope::logger(), "Restarted container has a new address";
+        "service" => service_name,
+        "container" => container_name,
+        "previous" => &previous.ip_address,
+        "ip" => &current.ip_address
+    );
+    set_container_backends(service_name, &previous, false).await;
+    // A container that isn't ready yet is added once it is
+    if is_routable(container_name).await {
+        set_container_backends(service_name, &current, true).await;
+    }
+}
+
+// Update the container's status in the instance store; false once Orbit no longer tracks it
+async fn set_status(service_name: &str, container_name: &str, status: &str) -> bool {
+    let Some(instance_store) = INSTANCE_STORE.get() else {
+        return false;
+    };
+    let mut store = instance_store.write().await;
+    let Some(instances) = store.get_mut(service_name) else {
+        return false;
+    };
+
+    for instance in instances.values_mut() {
+        if let Some(container) = instance
+            .containers
+            .iter_mut()
+            .find(|c| c.name == container_name)
+        {
+            if container.status != status {
+                container.status = status.to_string();
+                record_change(
+                    service_name,
+                    InstanceEvent::PodUpdated {
+                        instance: instance.clone(),
+                    },
+                );
+            }
+            return true;
+        }
+    }
+    false
+}
+
+async fn is_tracked(service_name: &str, container_name: &str) -> bool {
+    let Some(instance_store) = INSTANCE_STORE.get() else {
+        return false;
+    };
+    let store = instance_store.read().await;
+    store.get(service_name).is_some_and(|instances| {
+        instances
+            .values()
+            .any(|instance| instance.containers.iter().any(|c| c.name == container_name))
+    })
+}
+
+async fn supervise(
+    service_name: String,
+    container_name: String,
+    runtime: Arc<dyn ContainerRuntime>,
+) {
+    let log = slog_scope::logger();
+    let mut restarts: u32 = 0;
+    let mut started_at = Instant::now();
+
+    loop {
+        let exit_code = match runtime.wait_container(&container_name).await {
+            Ok(exit_code) => exit_code,
+            Err(e) => {
+                if !is_tracked(&service_name, &container_name).await {
+                    return;
+                }
+                slog::debug!(log, "Failed to wait for container exit";
+                    "service" => &service_name,
+                    "container" => &container_name,
+                    "error" => e.to_string()
+                );
+                tokio::time::sleep(WAIT_RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+
+        // Pods being removed are taken out of the instance store
+        if !is_tracked(&service_name, &container_name).await {
+            return;
+        }
+        // The exit of a container Orbit restarted itself
+        if restarted_in_place().lock().unwrap().remove(&container_name) {
+            continue;
+        }
+        let Some((config, container, policy)) =
+            current_config(&service_name, &container_name).await
+        else {
+            set_status(&service_name, &container_name, "exited").await;
+            return;
+        };
+
+        if exit_code != 0 {
+            crash_dumps::on_crash(&service_name, &container_name, &container, exit_code).await;
+        }
+        if started_at.elapsed() >= RESET_AFTER {
+            restarts = 0;
+        }
+
+        if !policy.should_restart(exit_code) {
+            slog::info!(log, "Container exited";
+                "service" => &service_name,
+                "container" => &container_name,
+                "exit_code" => exit_code
+            );
+            set_status(&service_name, &container_name, "exited").await;
+            return;
+        }
+
+        if policy.max_retries.is_some_and(|max| restarts >= max) {
+            slog::error!(log, "Container exceeded its restart limit";
+                "service" => &service_name,
+                "container" => &container_name,
+                "exit_code" => exit_code,
+                "restarts" => restarts
+            );
+            set_status(&service_name, &container_name, "failed").await;
+            return;
+        }
+
+        let flapping = get_container_health(&container_name)
+            .await
+            .is_some_and(|health| health.backs_off_restarts());
+        let backoff = if flapping {
+            MAX_BACKOFF
+        } else {
+            INITIAL_BACKOFF
+                .saturating_mul(2u32.saturating_pow(restarts))
+                .min(MAX_BACKOFF)
+        };
+        slog::warn!(log, "Container exited, restarting";
+            "service" => &service_name,
+            "container" => &container_name,
+            "exit_code" => exit_code,
+            "backoff" => format!("{:?}", backoff),
+            "flapping" => flapping
+        );
+        if !set_status(&service_name, &container_name, "restarting").await {
+            return;
+        }
+        tokio::time::sleep(backoff).await;
+
+        if !is_tracked(&service_name, &container_name).await {
+            return;
+        }
+        if let Err(e) = runtime
+            .restart_container(&container_name, &container, &config)
+            .await
+        {
+            slog::error!(log, "Failed to restart container";
+                "service" => &service_name,
+                "container" => &container_name,
+                "error" => e.to_string()
+            );
+            set_status(&service_name, &container_name, "failed").await;
+            return;
+        }
+
+        restarts += 1;
+        started_at = Instant::now();
+        set_status(&service_name, &container_name, "running").await;
+        refresh_address(runtime.as_ref(), &service_name, &container_name).await;
+        run_post_start(&container_name).await;
+        if let Err(e) =
+            record_container_restart(&service_name, &container_name, container.health_check).await
+        {
+            slog::error!(log, "Failed to resume health monitoring";
+                "service" => &service_name,
+                "container" => &container_name,
+                "error" => e.to_string()
+            );
+        }
+        slog::info!(log, "Container restarted";
+            "service" => &service_name,
+            "container" => &container_name,
+            "restarts" => restarts
+        );
+    }
+}
+
+// Restarts of a container by the remediation loop
+struct Remediation {
+    restarts: u32,
+    last_restart: Instant,
+}
+
+/// Restart containers whose health monitoring has failed, e.g. because they
+/// stopped or can no longer be inspected. Restarts of the same container back
+/// off exponentially, until it has stayed up for a while.
+pub fn start_remediation() {
+    tokio::spawn(async {
+        let mut remediations: FxHashMap<String, Remediation> = FxHashMap::default();
+        let mut interval = tokio::time::interval(REMEDIATION_INTERVAL);
+        loop {
+            interval.tick().await;
+            remediate(&mut remediations).await;
+        }
+    });
+}
+
+async fn remediate(remediations: &mut FxHashMap<String, Remediation>) {
+    let log = slog_scope::logger();
+    let Some(health_store) = CONTAINER_HEALTH.get() else {
+        return;
+    };
+    let failed: Vec<(String, bool)> = {
+        let health_map = health_store.read().await;
+        remediations.retain(|name, remediation| {
+            health_map.get(name).is_some_and(|health| {
+                matches!(health.state, HealthState::Failed)
+                    || remediation.last_restart.elapsed() < RESET_AFTER
+            })
+        });
+        health_map
+            .iter()
+            .filter(|(_, health)| matches!(health.state, HealthState::Failed))
+            .map(|(name, health)| {
+                let mut health = health.clone();
+                health.update_flapping();
+                (name.clone(), health.backs_off_restarts())
+            })
+            .collect()
+    };
+
+    for (container_name, flapping) in failed {
+        if let Some(remediation) = remediations.get(&container_name) {
+            let backoff = if flapping {
+                MAX_BACKOFF
+            } else {
+                INITIAL_BACKOFF
+                    .saturating_mul(2u32.saturating_pow(remediation.restarts))
+                    .min(MAX_BACKOFF)
+            };
+            if remediation.last_restart.elapsed() < backoff {
+                continue;
+            }
+        }
+        let Ok(parts) = parse_container_name(&container_name) else {
+            continue;
+        };
+        // Supervisors and probes restarting the container themselves
+        if container_status(&parts.service_name, &container_name)
+            .await
+            .is_none_or(|status| status == "restarting")
+        {
+            continue;
+        }
+        // Containers that are never restarted are left alone
+        let never = get_config_by_service(&parts.service_name)
+            .await
+            .and_then(|config| {
+                config
+                    .spec
+                    .containers
+                    .into_iter()
+                    .find(|c| c.name == parts.container_name)
+            })
+            .and_then(|container| container.restart_policy)
+            .is_some_and(|policy| policy.policy == RestartPolicyValue::Never);
+        if never {
+            continue;
+        }
+
+        let remediation = remediations
+            .entry(container_name.clone())
+            .or_insert(Remediation {
+                restarts: 0,
+                last_restart: Instant::now(),
+            });
+        remediation.restarts += 1;
+        remediation.last_restart = Instant::now();
+
+        slog::warn!(log, "Restarting failed container";
+            "service" => &parts.service_name,
+            "container" => &container_name,
+            "restarts" => remediation.restarts
+        );
+        if let Err(e) = restart_in_place(&parts.service_name, &container_name).await {
+            slog::error!(log, "Failed to restart failed container";
+                "service" => &parts.service_name,
+                "container" => &container_name,
+                "error" => e.to_string()
+            );
+        }
+    }
+}
+
+async fn container_status(service_name: &str, container_name: &str) -> Option<String> {
+    let store = INSTANCE_STORE.get()?.read().await;
+    store.get(service_name)?.values().find_map(|instance| {
+        instance
+            .containers
+            .iter()
+            .find(|c| c.name == container_name)
+            .map(|c| c.status.clone())
+    })
+}
@@ -0,0 +1,250 @@
+// src/container/rollback.rs
+//
+// Image rollbacks. When the new pods of an image update don't become healthy
+// and are removed, the old pods keep serving, but the image's tag now points
+// at the broken build, so any pod started later, to scale up or replace a
+// crashed one, would run it. The previous image digest of each updated
+// container, taken from the old pods' `image_hash`, is therefore pinned:
+// pods of the service are started from it until the image changes again, and
+// pods already running the failed image are replaced. Pins are persisted, so
+// they survive daemon restarts. Only Docker image IDs can be started by
+// digest; with other runtimes the update is rolled back without a pin.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::changes::{record_change, InstanceEvent};
+use super::rolling_update::recycle_pods;
+use super::{Container, INSTANCE_STORE};
+use crate::config::ServiceConfig;
+
+static ROLLBACKS: OnceLock<RollbackStore> = OnceLock::new();
+
+const STATE_FILE: &str = "rollbacks.json";
+// Prefix of the digests a runtime can start a container from
+const IMAGE_ID_PREFIX: &str = "sha256:";
+
+/// A container's image, pinned to the digest it ran before a failed update
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinnedImage {
+    /// The image as referenced in the config
+    pub image: String,
+    /// The digest pods are started from
+    pub digest: String,
+    /// The digest of the update that failed
+    pub failed: String,
+}
+
+/// A failed image update, with the digests its containers are pinned to
+#[derive(Debug, Serialize, Clone)]
+pub struct RolledBackUpdate {
+    pub images: BTreeMap<String, String>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Rollback {
+    images: BTreeMap<String, PinnedImage>,
+    #[serde(with = "humantime_serde")]
+    at: SystemTime,
+}
+
+#[derive(Debug)]
+struct RollbackStore {
+    path: Option<PathBuf>,
+    services: Mutex<BTreeMap<String, Rollback>>,
+}
+
+/// Load the pins persisted by a previous run
+pub fn initialize_rollbacks(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let path = state_dir.join(STATE_FILE);
+
+    let services = if path.exists() {
+        serde_json::from_str(&std::fs::read_to_string(&path)?)?
+    } else {
+        BTreeMap::new()
+    };
+
+    ROLLBACKS
+        .set(RollbackStore {
+            path: Some(path),
+            services: Mutex::new(services),
+        })
+        .map_err(|_| anyhow!("Rollbacks already initialized"))
+}
+
+fn rollback_store() -> &'static RollbackStore {
+    ROLLBACKS.get_or_init(|| RollbackStore {
+        path: None,
+        services: Mutex::new(BTreeMap::new()),
+    })
+}
+
+impl RollbackStore {
+    fn persist(&self, services: &BTreeMap<String, Rollback>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string(services)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, path)?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            slog::error!(slog_scope::logger(), "Failed to persist rollbacks";
+                "path" => path.display().to_string(),
+                "error" => e.to_string()
+            );
+        }
+    }
+}
+
+/// The service's containers as new pods start them, with the images of a
+/// failed update pinned to their previous digest. A pin no longer applies
+/// once the container's image is changed in the config.
+pub fn containers_to_start(config: &ServiceConfig) -> Vec<Container> {
+    let services = rollback_store().services.lock().unwrap();
+    let rollback = services.get(&config.name);
+    config
+        .spec
+        .containers
+        .iter()
+        .map(|container| {
+            let pinned = rollback
+                .and_then(|rollback| rollback.images.get(&container.name))
+                .filter(|pinned| pinned.image == container.image);
+            match pinned {
+                Some(pinned) => Container {
+                    image: pinned.digest.clone(),
+                    ..container.clone()
+                },
+                None => container.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Pin the containers whose update failed to the digests the old pods ran,
+/// and record the rollback. Returns whether any container was pinned.
+pub async fn pin(
+    config: &ServiceConfig,
+    previous: &HashMap<String, String>,
+    failed: &HashMap<String, String>,
+    error: &str,
+) -> bool {
+    let images: BTreeMap<String, PinnedImage> = config
+        .spec
+        .containers
+        .iter()
+        .filter_map(|container| {
+            let digest = previous.get(&container.name)?;
+            let failed = failed.get(&container.name)?;
+            (digest != failed && digest.starts_with(IMAGE_ID_PREFIX)).then(|| {
+                let pinned = PinnedImage {
+                    image: container.image.clone(),
+                    digest: digest.clone(),
+                    failed: failed.clone(),
+                };
+                (container.name.clone(), pinned)
+            })
+        })
+        .collect();
+    if images.is_empty() {
+        return false;
+    }
+
+    let digests: BTreeMap<String, String> = images
+        .iter()
+        .map(|(name, pinned)| (name.clone(), pinned.digest.clone()))
+        .collect();
+    slog::warn!(slog_scope::logger(), "Pinning images to their previous digest";
+        "service" => &config.name,
+        "images" => format!("{:?}", digests)
+    );
+    let event = InstanceEvent::UpdateRolledBack(RolledBackUpdate {
+        images: digests,
+        error: error.to_string(),
+    });
+    {
+        let store = rollback_store();
+        let mut services = store.services.lock().unwrap();
+        services.insert(
+            config.name.clone(),
+            Rollback {
+                images,
+                at: SystemTime::now(),
+            },
+        );
+        store.persist(&services);
+    }
+    if let Some(instance_store) = INSTANCE_STORE.get() {
+        // Recorded under the store's lock like every other change
+        let _store = instance_store.write().await;
+        record_change(&config.name, event);
+    }
+    true
+}
+
+/// Drop the service's pins, once its images have changed again
+pub fn unpin(service_name: &str) {
+    let store = rollback_store();
+    let mut services = store.services.lock().unwrap();
+    if services.remove(service_name).is_some() {
+        slog::info!(slog_scope::logger(), "Images changed, unpinning rolled back images";
+            "service" => service_name
+        );
+        store.persist(&services);
+    }
+}
+
+/// Replace the service's pods that run an image of the failed update, e.g.
+/// ones started to scale up after its image was pulled
+pub async fn redeploy(service_name: &str) -> Result<()> {
+    let failed: Vec<(String, String)> =
+        match rollback_store().services.lock().unwrap().get(service_name) {
+            Some(rollback) => rollback
+                .images
+                .iter()
+                .map(|(name, pinned)| (name.clone(), pinned.failed.clone()))
+                .collect(),
+            None => return Ok(()),
+        };
+
+    let pods: Vec<Uuid> = INSTANCE_STORE
+        .get()
+        .expect("Instance store not initialized")
+        .read()
+        .await
+        .get(service_name)
+        .map(|instances| {
+            instances
+                .values()
+                .filter(|pod| {
+                    failed
+                        .iter()
+                        .any(|(name, digest)| pod.image_hash.get(name) == Some(digest))
+                })
+                .map(|pod| pod.uuid)
+                .collect()
+        })
+        .unwrap_or_default();
+    if pods.is_empty() {
+        return Ok(());
+    }
+
+    slog::info!(slog_scope::logger(), "Redeploying pods running the rolled back images";
+        "service" => service_name,
+        "pods" => pods.len()
+    );
+    recycle_pods(service_name, &pods).await
+}
@@ -2,26 +2,41 @@
 
 use anyhow::{anyhow, Result};
 use pingora_load_balancing::Backend;
+use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
-    sync::Arc,
+    sync::{Arc, Mutex, OnceLock},
     time::{Duration, Instant, SystemTime},
 };
-use tokio::time::interval;
+use tokio::sync::OwnedMutexGuard;
 use uuid::Uuid;
 
 use crate::{
     config::{
-        get_config_by_service, parse_container_name, ScaleMessage, ServiceConfig, CONFIG_UPDATES,
+        get_config_by_service, parse_container_name, send_scale_message, ScaleMessage,
+        ServiceConfig,
     },
     container::{
-        get_next_pod_number, ContainerMetadata, ContainerRuntime, InstanceMetadata, INSTANCE_STORE,
-        RUNTIME,
+        canary::{self, CanaryConfig, UpdateStrategy},
+        changes::{record_change, InstanceEvent},
+        dns, get_next_pod_number,
+        health::{self, HealthState, CONTAINER_HEALTH},
+        lifecycle, preemption, readiness,
+        restart::supervise_containers,
+        rollback,
+        schedule::{self, PeriodicTask},
+        ContainerMetadata, ContainerPortMetadata, ContainerRuntime, InstanceMetadata,
+        INSTANCE_STORE, RUNTIME,
     },
-    proxy::SERVER_BACKENDS,
+    proxy::{set_container_backends, SERVER_BACKENDS},
 };
 
+// One lock per service, so image updates, restarts and recycling don't
+// replace the same pods at once
+static UPDATE_LOCKS: OnceLock<Mutex<FxHashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    OnceLock::new();
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateState {
     pub in_progress: bool,
@@ -30,54 +45,134 @@ pub struct UpdateState {
     pub start_time: Option<SystemTime>,
 }
 
+/// An update that took old pods out before starting new ones, as the node had
+/// no room for the surge
+#[derive(Debug, Serialize, Clone)]
+pub struct InPlaceUpdate {
+    pub max_surge: u8,
+    pub max_unavailable: u8,
+    /// New pods the node had room for
+    pub free_pods: usize,
+}
+
 pub async fn start_image_check_task(service_name: String, config: ServiceConfig) -> Result<()> {
     let runtime = RUNTIME.get().unwrap();
     let mut last_image_hashes = HashMap::new();
     let check_interval = config
         .image_check_interval
         .unwrap_or(Duration::from_secs(300));
-    let mut interval = interval(check_interval);
+    let mut ticker = schedule::ticker(&service_name, PeriodicTask::ImageCheck, check_interval);
 
     loop {
-        interval.tick().await;
+        ticker.tick().await;
 
         let current_config = match get_config_by_service(&service_name).await {
             Some(cfg) => cfg,
             None => break,
         };
 
-        // Get current image hashes once
+        // Pull only the images the registry has a different digest for.
+        // Digest-pinned images can't change.
+        let mut changed = Vec::new();
+        for container in &current_config.spec.containers {
+            if container.pinned_digest().is_some() {
+                continue;
+            }
+            match runtime
+                .image_changed_upstream(&container.image, &current_config)
+                .await
+            {
+                Ok(true) => changed.push(container.clone()),
+                Ok(false) => {}
+                Err(e) => {
+                    slog::warn!(slog_scope::logger(), "Failed to check image for updates";
+                        "service" => &service_name,
+                        "image" => &container.image,
+                        "error" => e.to_string()
+                    );
+                }
+            }
+        }
+        if !changed.is_empty() {
+            if let Err(e) = runtime
+                .pull_image(&service_name, &changed, &current_config)
+                .await
+            {
+                slog::warn!(slog_scope::logger(), "Failed to refresh images";
+                    "service" => &service_name,
+                    "error" => e.to_string()
+                );
+            }
+        }
+
+        // Get current image hashes once. An image that can't be resolved, e.g.
+        // one pulled for another platform, skips the update until it can.
         let mut current_hashes = HashMap::new();
+        let mut resolved = true;
         for container in &current_config.spec.containers {
-            if let Ok(hash) = runtime.get_image_digest(&container.image).await {
-                current_hashes.insert(container.name.clone(), hash);
+            if container.pinned_digest().is_some() {
+                continue;
+            }
+            match runtime.get_image_digest(&container.image).await {
+                Ok(hash) => {
+                    current_hashes.insert(container.name.clone(), hash);
+                }
+                Err(e) => {
+                    slog::warn!(slog_scope::logger(), "Failed to resolve image, skipping update";
+                        "service" => &service_name,
+                        "image" => &container.image,
+                        "error" => e.to_string()
+                    );
+                    resolved = false;
+                }
             }
         }
+        if !resolved {
+            continue;
+        }
 
         // Only trigger update if hashes changed
         if !last_image_hashes.is_empty() && current_hashes != last_image_hashes {
             slog::info!(slog_scope::logger(), "Image updates detected";
                 "service" => &service_name
             );
+            rollback::unpin(&service_name);
 
-            if let Some(sender) = CONFIG_UPDATES.get() {
-                sender
-                    .send((service_name.clone(), ScaleMessage::RollingUpdate))
-                    .await?;
-            }
+            send_scale_message(&service_name, ScaleMessage::RollingUpdate).await;
+
+            let update_config = current_config
+                .rolling_update_config
+                .clone()
+                .unwrap_or_default();
+            let canary = (update_config.strategy == UpdateStrategy::Canary)
+                .then(|| update_config.canary.unwrap_or_default());
 
-            perform_rolling_update(
+            // A rolled back update is retried once the images change again
+            if let Err(e) = perform_rolling_update(
                 &service_name,
                 &current_config,
                 runtime.clone(),
                 &current_hashes,
+                None,
+                canary.as_ref(),
             )
-            .await?;
+            .await
+            {
+                slog::error!(slog_scope::logger(), "Rolling update failed";
+                    "service" => &service_name,
+                    "error" => e.to_string()
+                );
+            }
 
-            if let Some(sender) = CONFIG_UPDATES.get() {
-                sender
-                    .send((service_name.clone(), ScaleMessage::RollingUpdateComplete))
-                    .await?;
+            send_scale_message(&service_name, ScaleMessage::RollingUpdateComplete).await;
+
+            // Pods started from the new images while the update ran are
+            // replaced with ones running the pinned images
+            if let Err(e) = rollback::redeploy(&service_name).await {
+                slog::error!(slog_scope::logger(), "Failed to redeploy rolled back images";
+                    "service" => &service_name,
+                    "error" => e.to_string()
+                );
             }
         }
 
@@ -87,104 +182,432 @@ pub async fn start_image_check_task(service_name: String, config: ServiceConfig)
     Ok(())
 }
 
-async fn perform_rolling_update(
+async fn update_lock(service_name: &str) -> OwnedMutexGuard<()> {
+    let lock = UPDATE_LOCKS
+        .get_or_init(|| Mutex::new(FxHashMap::default()))
+        .lock()
+        .unwrap()
+        .entry(service_name.to_string())
+        .or_default()
+        .clone();
+    lock.lock_owned().await
+}
+
+/// Replace every pod of the service with fresh ones running the same images,
+/// e.g. once a secret or certificate they were started with has been renewed.
+pub async fn restart_pods(service_name: &str) -> Result<()> {
+    replace_pods(service_name, None).await
+}
+
+/// Replace some of the service's pods with fresh ones running the same
+/// images, new pods first, e.g. pods that have reached their maximum lifetime.
+pub async fn recycle_pods(service_name: &str, pods: &[Uuid]) -> Result<()> {
+    replace_pods(service_name, Some(pods)).await
+}
+
+async fn replace_pods(service_name: &str, only: Option<&[Uuid]>) -> Result<()> {
+    let runtime = RUNTIME.get().expect("Runtime not initialised").clone();
+    let config = get_config_by_service(service_name)
+        .await
+        .ok_or_else(|| anyhow!("Service {} not found", service_name))?;
+
+    // Pods are replaced with the images they run, pinned ones included
+    let mut current_hashes = HashMap::new();
+    for container in rollback::containers_to_start(&config) {
+        if let Ok(hash) = runtime.get_image_digest(&container.image).await {
+            current_hashes.insert(container.name.clone(), hash);
+        }
+    }
+
+    send_scale_message(service_name, ScaleMessage::RollingUpdate).await;
+
+    let result =
+        perform_rolling_update(service_name, &config, runtime, &current_hashes, only, None).await;
+
+    send_scale_message(service_name, ScaleMessage::RollingUpdateComplete).await;
+
+    result
+}
+
+/// Replace the service's pods, or only some of them, with pods started from
+/// the config's images, first through a canary if one is given. The new
+/// pods are rolled back if they don't become healthy.
+pub async fn perform_rolling_update(
     service_name: &str,
     config: &ServiceConfig,
     runtime: Arc<dyn ContainerRuntime>,
     new_image_hashes: &HashMap<String, String>,
+    only: Option<&[Uuid]>,
+    canary: Option<&CanaryConfig>,
 ) -> Result<()> {
+    let _guard = update_lock(service_name).await;
     let instance_store = INSTANCE_STORE
         .get()
         .expect("Instance store not initialized");
-    let server_backends = SERVER_BACKENDS
-        .get()
-        .expect("Server backends not initialized");
     let _log = slog_scope::logger();
 
-    // Get pods with read lock
+    // Get pods with read lock, those still running if only some are replaced
     let pods = {
         let store = instance_store.read().await;
         match store.get(service_name) {
             Some(instances) => instances
                 .iter()
+                .filter(|(uuid, _)| only.is_none_or(|only| only.contains(uuid)))
                 .map(|(uuid, metadata)| (*uuid, metadata.clone()))
                 .collect::<Vec<_>>(),
             None => return Err(anyhow!("Service not found")),
         }
     };
+    if pods.is_empty() {
+        return Ok(());
+    }
 
     let total_pods = pods.len();
     let update_config = config.rolling_update_config.clone().unwrap_or_default();
     let max_surge = update_config.max_surge as usize;
     let timeout = update_config.timeout;
+    // What the old pods ran, to pin if the update is rolled back
+    let previous_hashes = pods[0].1.image_hash.clone();
+
+    // Calculate how many new pods we can create at once based on max_surge,
+    // or start the canary's
+    let allowed_new_pods = match canary {
+        Some(canary) => canary.pods as usize,
+        None => (total_pods + max_surge).saturating_sub(total_pods),
+    };
+    let mut new_pod_count = total_pods.min(allowed_new_pods);
+
+    // New pods are started next to the old ones, so their resource requests
+    // must fit on the node. Otherwise up to max_unavailable old pods are
+    // taken out first to make room, rather than failing once some are started.
+    let mut pods = pods;
+    // Old pods taken out before the new ones started, to replace if the
+    // update is rolled back
+    let mut drained = 0;
+    let free_pods = preemption::free_pod_slots(config)
+        .await
+        .unwrap_or(usize::MAX);
+    if free_pods < new_pod_count {
+        let max_unavailable = update_config.max_unavailable as usize;
+        if canary.is_some() || free_pods + max_unavailable == 0 {
+            return Err(anyhow!(
+                "Insufficient node capacity for new pods of {}: {} needed, room for {}",
+                service_name,
+                new_pod_count,
+                free_pods
+            ));
+        }
+        slog::warn!(slog_scope::logger(), "Surge pods don't fit on the node, updating in place";
+            "service" => service_name,
+            "max_surge" => max_surge,
+            "free_pods" => free_pods,
+            "max_unavailable" => max_unavailable
+        );
+        {
+            let _store = instance_store.write().await;
+            record_change(
+                service_name,
+                InstanceEvent::UpdateInPlace(InPlaceUpdate {
+                    max_surge: update_config.max_surge,
+                    max_unavailable: update_config.max_unavailable,
+                    free_pods,
+                }),
+            );
+        }
+        drained = max_unavailable.min(total_pods);
+        for (old_uuid, old_metadata) in pods.drain(..drained) {
+            remove_old_pod(service_name, old_uuid, &old_metadata, runtime.clone()).await;
+        }
+        new_pod_count = new_pod_count.min(free_pods + drained);
+    }
+
+    // Old pods drained to update in place are replaced if no new pod starts
+    let mut new_pods = match start_new_pods(
+        service_name,
+        config,
+        runtime.clone(),
+        new_pod_count,
+        new_image_hashes,
+    )
+    .await
+    {
+        Ok(new_pods) => new_pods,
+        Err(e) => {
+            roll_back_update(
+                config,
+                runtime,
+                &[],
+                &previous_hashes,
+                new_image_hashes,
+                drained,
+                &e,
+            )
+            .await;
+            return Err(e);
+        }
+    };
+
+    // A canary's share of the traffic is set before its pods take any
+    let new_uuids: Vec<Uuid> = new_pods.iter().map(|(uuid, _)| *uuid).collect();
+    let canary_split = canary.map(|config| canary::start(service_name, config, &new_uuids));
+    route_new_pods(service_name, &new_pods).await;
+
+    // Old pods are only removed once the new ones are healthy and pass their
+    // readiness probes, and meet the readiness definition if there is one.
+    // Otherwise the new pods are removed and the old ones keep serving.
+    if let Err(e) = wait_for_new_pods(config, &new_pods, Instant::now() + timeout).await {
+        slog::warn!(slog_scope::logger(), "New pods did not become healthy, rolling back";
+            "service" => service_name,
+            "timeout" => format!("{:?}", timeout),
+            "error" => e.to_string()
+        );
+        roll_back_update(
+            config,
+            runtime,
+            &new_pods,
+            &previous_hashes,
+            new_image_hashes,
+            drained,
+            &e,
+        )
+        .await;
+        return Err(anyhow!(
+            "New pods for {} did not become healthy within {:?}: {}",
+            service_name,
+            timeout,
+            e
+        ));
+    }
+
+    // A canary is promoted once it has taken its share of the traffic for
+    // the analysis period, and removed if it fails the thresholds meanwhile
+    if let Some(canary) = canary {
+        let container_names: Vec<&str> = new_pods
+            .iter()
+            .flat_map(|(_, containers)| containers.iter().map(|(name, _, _)| name.as_str()))
+            .collect();
+        if let Err(e) = canary::analyze(service_name, canary, &container_names).await {
+            slog::warn!(slog_scope::logger(), "Canary failed, rolling back";
+                "service" => service_name,
+                "error" => e.to_string()
+            );
+            roll_back_update(
+                config,
+                runtime,
+                &new_pods,
+                &previous_hashes,
+                new_image_hashes,
+                drained,
+                &e,
+            )
+            .await;
+            return Err(anyhow!("Canary for {} failed: {}", service_name, e));
+        }
+
+        // Once promoted, the rest of the old pods get new ones before they go,
+        // so the service keeps its size
+        let remaining = total_pods.saturating_sub(new_pods.len());
+        let promoted = match start_new_pods(
+            service_name,
+            config,
+            runtime.clone(),
+            remaining,
+            new_image_hashes,
+        )
+        .await
+        {
+            Ok(promoted) => promoted,
+            Err(e) => {
+                roll_back_update(
+                    config,
+                    runtime,
+                    &new_pods,
+                    &previous_hashes,
+                    new_image_hashes,
+                    drained,
+                    &e,
+                )
+                .await;
+                return Err(e);
+            }
+        };
+        route_new_pods(service_name, &promoted).await;
+        let result = wait_for_new_pods(config, &promoted, Instant::now() + timeout).await;
+        new_pods.extend(promoted);
+        if let Err(e) = result {
+            slog::warn!(slog_scope::logger(), "Pods of the promoted canary did not become healthy, rolling back";
+                "service" => service_name,
+                "timeout" => format!("{:?}", timeout),
+                "error" => e.to_string()
+            );
+            roll_back_update(
+                config,
+                runtime,
+                &new_pods,
+                &previous_hashes,
+                new_image_hashes,
+                drained,
+                &e,
+            )
+            .await;
+            return Err(anyhow!(
+                "New pods for {} did not become healthy within {:?}: {}",
+                service_name,
+                timeout,
+                e
+            ));
+        }
+    }
+    // The new pods take their full share as the old ones go
+    drop(canary_split);
+
+    // Remove old pods one by one
+    for (old_uuid, old_metadata) in pods {
+        remove_old_pod(service_name, old_uuid, &old_metadata, runtime.clone()).await;
+    }
+
+    Ok(())
+}
 
-    // Calculate how many new pods we can create at once based on max_surge
-    let allowed_new_pods = (total_pods + max_surge).saturating_sub(total_pods);
-    let new_pod_count = total_pods.min(allowed_new_pods);
+// A new pod's uuid and its started containers
+type NewPod = (Uuid, Vec<(String, String, Vec<ContainerPortMetadata>)>);
+
+// Start pods with the config's images in parallel, and add them to the
+// instance store with health monitoring
+async fn start_new_pods(
+    service_name: &str,
+    config: &ServiceConfig,
+    runtime: Arc<dyn ContainerRuntime>,
+    count: usize,
+    new_image_hashes: &HashMap<String, String>,
+) -> Result<Vec<NewPod>> {
+    let instance_store = INSTANCE_STORE
+        .get()
+        .expect("Instance store not initialized");
 
     // Create all new pods in parallel
     let mut new_pod_futures = Vec::new();
     let mut pod_numbers = Vec::new();
-    for _ in 0..new_pod_count {
-        pod_numbers.push(get_next_pod_number(service_name).await);
+    for _ in 0..count {
+        pod_numbers.push(get_next_pod_number(service_name).await?);
     }
 
+    let containers = rollback::containers_to_start(config);
     for pod_number in pod_numbers {
         let runtime = runtime.clone();
         let config = config.clone();
+        let containers = containers.clone();
         let service_name = service_name.to_string();
 
         new_pod_futures.push(tokio::spawn(async move {
             runtime
-                .start_containers(&service_name, pod_number, &config.spec.containers, &config)
+                .start_containers(&service_name, pod_number.number(), &containers, &config)
                 .await
         }));
     }
 
-    // Collect results and update instance store
+    // Collect results and update instance store. A failed start doesn't stop
+    // the collection, so the pods that did start can be removed.
     let mut new_pods = Vec::new();
+    let mut error = None;
     for future in new_pod_futures {
-        match future.await? {
-            Ok(new_containers) => {
-                if !new_containers.is_empty() {
-                    let new_uuid = parse_container_name(&new_containers[0].0)?.uuid;
-                    let network_name = format!("{}__{}", service_name, new_uuid);
-
-                    // Update instance store with write lock
-                    {
-                        let mut store = instance_store.write().await;
-                        if let Some(instances) = store.get_mut(service_name) {
-                            instances.insert(
-                                new_uuid,
-                                InstanceMetadata {
-                                    uuid: new_uuid,
-                                    created_at: SystemTime::now(),
+        let new_containers = match future.await {
+            Ok(Ok(new_containers)) => new_containers,
+            Ok(Err(e)) => {
+                error.get_or_insert(e);
+                continue;
+            }
+            Err(e) => {
+                error.get_or_insert(e.into());
+                continue;
+            }
+        };
+        if !new_containers.is_empty() {
+            let new_uuid = parse_container_name(&new_containers[0].0)?.uuid;
+            let network_name = format!("{}__{}", service_name, new_uuid);
+
+            // Update instance store with write lock
+            {
+                let mut store = instance_store.write().await;
+                if let Some(instances) = store.get_mut(service_name) {
+                    instances.insert(
+                        new_uuid,
+                        InstanceMetadata {
+                            uuid: new_uuid,
+                            created_at: SystemTime::now(),
+                            network: network_name.clone(),
+                            image_hash: new_image_hashes.clone(),
+                            annotations: config.annotations.clone(),
+                            dns_names: dns::pod_dns_names(
+                                new_containers.iter().map(|(name, _, _)| name.as_str()),
+                            ),
+                            containers: new_containers
+                                .iter()
+                                .map(|(name, ip, ports)| ContainerMetadata {
+                                    name: name.clone(),
                                     network: network_name.clone(),
-                                    image_hash: new_image_hashes.clone(),
-                                    containers: new_containers
-                                        .iter()
-                                        .map(|(name, ip, ports)| ContainerMetadata {
-                                            name: name.clone(),
-                                            network: network_name.clone(),
-                                            ip_address: ip.clone(),
-                                            ports: ports.clone(),
-                                            status: "running".to_string(),
-                                        })
-                                        .collect(),
-                                },
-                            );
-                        }
-                    }
-                    new_pods.push((new_uuid, new_containers));
+                                    ip_address: ip.clone(),
+                                    ports: ports.clone(),
+                                    status: "running".to_string(),
+                                })
+                                .collect(),
+                        },
+                    );
+                    record_change(
+                        service_name,
+                        InstanceEvent::PodAdded {
+                            instance: instances[&new_uuid].clone(),
+                        },
+                    );
                 }
             }
-            Err(e) => return Err(e.into()),
+            for (container_name, _, _) in &new_containers {
+                let health_check = parse_container_name(container_name)
+                    .ok()
+                    .and_then(|parts| {
+                        config
+                            .spec
+                            .containers
+                            .iter()
+                            .find(|c| c.name == parts.container_name)
+                    })
+                    .and_then(|c| c.health_check.clone());
+                if let Err(e) =
+                    health::initialize_health_monitoring(service_name, container_name, health_check)
+                        .await
+                {
+                    slog::error!(slog_scope::logger(), "Failed to initialize health monitoring";
+                        "service" => service_name,
+                        "container" => container_name,
+                        "error" => e.to_string()
+                    );
+                }
+            }
+            supervise_containers(service_name, config, &new_containers);
+            lifecycle::register_hooks(service_name, config, &new_containers);
+            new_pods.push((new_uuid, new_containers));
         }
     }
 
-    // Update load balancer for all new pods
-    for (_, containers) in &new_pods {
-        for (_, ip, ports) in containers {
+    if let Some(e) = error {
+        for (uuid, _) in &new_pods {
+            roll_back(service_name, *uuid, runtime.clone()).await;
+        }
+        return Err(e);
+    }
+    Ok(new_pods)
+}
+
+// Update load balancer for all new pods, adding each container once it is ready
+async fn route_new_pods(service_name: &str, new_pods: &[NewPod]) {
+    let server_backends = SERVER_BACKENDS
+        .get()
+        .expect("Server backends not initialized");
+    for (_, containers) in new_pods {
+        for (container_name, ip, ports) in containers {
+            if !health::is_routable(container_name).await {
+                continue;
+            }
             for port_info in ports {
                 if let Some(node_port) = port_info.node_port {
                     let proxy_key = format!("{}__{}", service_name, node_port);
@@ -195,7 +618,7 @@ async fn perform_rolling_update(
                     };
 
                     if let Some(backends) = backends {
-                        let addr = format!("{}:{}", ip, port_info.port);
+                        let addr = port_info.backend_address(ip);
                         if let Ok(backend) = Backend::new(&addr) {
                             let mut backend_set = backends.write().await;
                             backend_set.insert(backend);
@@ -205,64 +628,180 @@ async fn perform_rolling_update(
             }
         }
     }
+}
 
-    // Wait for new pods to be ready
-    let start = Instant::now();
-    while start.elapsed() < timeout {
-        let mut all_ready = true;
-        for (_, containers) in &new_pods {
-            for (name, _, _) in containers {
-                if runtime.inspect_container(name).await.is_err() {
-                    all_ready = false;
-                    break;
-                }
-            }
-        }
-        if all_ready {
-            break;
+// Wait until the new pods are healthy and ready, and meet the service's
+// readiness definition if it has one
+async fn wait_for_new_pods(
+    config: &ServiceConfig,
+    new_pods: &[NewPod],
+    deadline: Instant,
+) -> Result<()> {
+    let container_names: Vec<&str> = new_pods
+        .iter()
+        .flat_map(|(_, containers)| containers.iter().map(|(name, _, _)| name.as_str()))
+        .collect();
+    wait_until_healthy(&container_names, deadline).await?;
+    if config.readiness.is_some() {
+        let new_uuids: Vec<Uuid> = new_pods.iter().map(|(uuid, _)| *uuid).collect();
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let report = readiness::wait_until_ready(config, Some(&new_uuids), remaining).await;
+        if !report.ready {
+            return Err(anyhow!("{}", report.pending.join(", ")));
         }
-        tokio::time::sleep(Duration::from_secs(1)).await;
     }
+    Ok(())
+}
 
-    // Remove old pods one by one
-    for (old_uuid, old_metadata) in pods {
-        // Remove from load balancer
-        for container in &old_metadata.containers {
-            for port_info in &container.ports {
-                if let Some(node_port) = port_info.node_port {
-                    let proxy_key = format!("{}__{}", service_name, node_port);
+// Remove the update's new pods, pin the images the old pods run, and replace
+// the old pods removed to make room for them
+async fn roll_back_update(
+    config: &ServiceConfig,
+    runtime: Arc<dyn ContainerRuntime>,
+    new_pods: &[NewPod],
+    previous_hashes: &HashMap<String, String>,
+    new_image_hashes: &HashMap<String, String>,
+    drained: usize,
+    error: &anyhow::Error,
+) {
+    let service_name = &config.name;
+    for (uuid, _) in new_pods {
+        roll_back(service_name, *uuid, runtime.clone()).await;
+    }
+    rollback::pin(
+        config,
+        previous_hashes,
+        new_image_hashes,
+        &error.to_string(),
+    )
+    .await;
+
+    // Old pods removed to update in place are replaced, with the pinned
+    // images, so the service is back to its size
+    if drained > 0 {
+        match start_new_pods(service_name, config, runtime, drained, previous_hashes).await {
+            Ok(replacements) => route_new_pods(service_name, &replacements).await,
+            Err(e) => {
+                slog::error!(slog_scope::logger(), "Failed to replace pods removed for the update";
+                    "service" => service_name,
+                    "pods" => drained,
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+}
 
-                    let backends = {
-                        let backends_map = server_backends.read().await;
-                        backends_map.get(&proxy_key).cloned()
-                    };
+// Take an old pod out of the load balancer, give its requests time to drain,
+// then remove it
+async fn remove_old_pod(
+    service_name: &str,
+    old_uuid: Uuid,
+    old_metadata: &InstanceMetadata,
+    runtime: Arc<dyn ContainerRuntime>,
+) {
+    let instance_store = INSTANCE_STORE
+        .get()
+        .expect("Instance store not initialized");
+    let server_backends = SERVER_BACKENDS
+        .get()
+        .expect("Server backends not initialized");
 
-                    if let Some(backends) = backends {
-                        let addr = format!("{}:{}", container.ip_address, port_info.port);
-                        if let Ok(backend) = Backend::new(&addr) {
-                            let mut backend_set = backends.write().await;
-                            backend_set.remove(&backend);
-                        }
+    // Remove from load balancer
+    for container in &old_metadata.containers {
+        for port_info in &container.ports {
+            if let Some(node_port) = port_info.node_port {
+                let proxy_key = format!("{}__{}", service_name, node_port);
+
+                let backends = {
+                    let backends_map = server_backends.read().await;
+                    backends_map.get(&proxy_key).cloned()
+                };
+
+                if let Some(backends) = backends {
+                    let addr = port_info.backend_address(&container.ip_address);
+                    if let Ok(backend) = Backend::new(&addr) {
+                        let mut backend_set = backends.write().await;
+                        backend_set.remove(&backend);
                     }
                 }
             }
         }
+    }
 
-        tokio::time::sleep(Duration::from_secs(5)).await;
+    tokio::time::sleep(Duration::from_secs(5)).await;
 
-        // Remove from instance store with write lock
-        {
-            let mut store = instance_store.write().await;
-            if let Some(instances) = store.get_mut(service_name) {
-                instances.remove(&old_uuid);
+    // Remove from instance store with write lock
+    {
+        let mut store = instance_store.write().await;
+        if let Some(instances) = store.get_mut(service_name) {
+            if instances.remove(&old_uuid).is_some() {
+                record_change(service_name, InstanceEvent::PodRemoved { uuid: old_uuid });
             }
         }
+    }
+
+    // Clean up containers and network
+    let _ = cleanup_pod(old_metadata, service_name, runtime).await;
+}
 
-        // Clean up containers and network
-        let _ = cleanup_pod(&old_metadata, service_name, runtime.clone()).await;
+// Wait until every container of the new pods is healthy and passes its
+// readiness probe. Fails as soon as one of them has failed.
+async fn wait_until_healthy(container_names: &[&str], deadline: Instant) -> Result<()> {
+    loop {
+        let mut pending = Vec::new();
+        for name in container_names {
+            if let Some(status) = health::get_container_health(name).await {
+                if matches!(status.state, HealthState::Failed) {
+                    return Err(anyhow!(
+                        "{} failed: {}",
+                        name,
+                        status.message.unwrap_or_default()
+                    ));
+                }
+            }
+            if !health::is_routable(name).await {
+                pending.push(*name);
+            }
+        }
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow!("{} not healthy", pending.join(", ")));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
     }
+}
 
-    Ok(())
+// Remove a new pod of a failed update, first from health monitoring so it
+// isn't added to the load balancer meanwhile
+async fn roll_back(service_name: &str, uuid: Uuid, runtime: Arc<dyn ContainerRuntime>) {
+    let Some(metadata) = INSTANCE_STORE
+        .get()
+        .expect("Instance store not initialized")
+        .write()
+        .await
+        .get_mut(service_name)
+        .and_then(|instances| {
+            let metadata = instances.remove(&uuid)?;
+            record_change(service_name, InstanceEvent::PodRemoved { uuid });
+            Some(metadata)
+        })
+    else {
+        return;
+    };
+
+    if let Some(health_store) = CONTAINER_HEALTH.get() {
+        let mut health_map = health_store.write().await;
+        for container in &metadata.containers {
+            health_map.remove(&container.name);
+        }
+    }
+    for container in &metadata.containers {
+        set_container_backends(service_name, container, false).await;
+    }
+    let _ = cleanup_pod(&metadata, service_name, runtime).await;
 }
 
 async fn cleanup_pod(
@@ -275,7 +814,7 @@ async fn cleanup_pod(
     for container in &metadata.containers {
         service_uuid = parse_container_name(&container.name)?.uuid.to_string();
 
-        if let Err(e) = runtime.stop_container(&container.name).await {
+        if let Err(e) = lifecycle::stop_container(&runtime, &container.name).await {
             slog::error!(slog_scope::logger(), "Failed to stop container";
                 "service" => service_name,
                 "container" => &container.name,
@@ -1,42 +1,342 @@
 // src/container/runtimes/docker.rs
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use bollard::auth::DockerCredentials;
 use bollard::container::{
-    Config, CreateContainerOptions, RemoveContainerOptions, StartContainerOptions, StatsOptions,
+    Config, CreateContainerOptions, KillContainerOptions, NetworkingConfig, RemoveContainerOptions,
+    StartContainerOptions, StatsOptions, UpdateContainerOptions, WaitContainerOptions,
 };
-use bollard::errors::Error::DockerResponseServerError;
+use bollard::errors::Error::{DockerContainerWaitError, DockerResponseServerError};
+use bollard::exec::{CreateExecOptions, StartExecOptions, StartExecResults};
 use bollard::image::CreateImageOptions;
-use bollard::models::{HostConfig, PortBinding};
-use bollard::network::CreateNetworkOptions;
+use bollard::models::{
+    DeviceMapping, EndpointSettings, HealthConfig, HealthStatusEnum, HostConfig,
+    HostConfigLogConfig, PortBinding, PortMap, ResourcesUlimits, ThrottleDevice,
+};
+use bollard::network::{CreateNetworkOptions, ListNetworksOptions};
 use bollard::secret::{DeviceRequest, Mount, MountBindOptions, MountTypeEnum};
 use bollard::Docker;
 use futures::StreamExt;
 use std::collections::{HashMap, HashSet};
 use std::default::Default;
+use std::env;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
 use crate::config::{
-    get_config_by_service, parse_cpu_limit, parse_memory_limit, PullPolicyValue, ServiceConfig,
+    daemon::{get_daemon_config, DockerRuntimeConfig, DockerTlsConfig},
+    get_config_by_service, parse_container_name, parse_cpu_limit, parse_memory_limit,
+    parse_shm_size, parse_tmpfs, parse_ulimit, PullPolicyValue, ServiceConfig, CONFIG_STORE,
 };
 use crate::container::{
-    parse_network_rate, update_container_stats, Container, ContainerInfo, ContainerPortMetadata,
-    ContainerRuntime, ContainerStats, NetworkLimit,
+    crash_dumps, dependencies,
+    dns::{dns_for, with_hosts_entries},
+    gpu::{assign_devices, gpu_usage, release_devices},
+    health::{HealthCheckConfig, HealthState},
+    logging_for, ownership_labels, parse_disk_rate, preemption, record_container_stats,
+    registry::{registry_credentials, RegistryCredentials},
+    shaping::{apply_network_limit, clear_shaping_state},
+    stop_settings,
+    volumes::{claim_volume, AccessMode},
+    Container, ContainerInfo, ContainerPortMetadata, ContainerRuntime, ContainerStats, ImageLayers,
+    RuntimeInfo, StatsSample, StopSettings, DEFAULT_STOP_GRACE_PERIOD, DEFAULT_STOP_SIGNAL,
+    LOOPBACK_ADDRESS, MANAGED_LABEL, POD_LABEL, SERVICE_LABEL,
 };
 
 use super::NETWORK_USAGE;
+use crate::container::secrets::{
+    remove_secret_files, resolve_secrets, write_secret_files, ResolvedSecret, SecretValue,
+};
+use crate::egress::proxy_environment;
+
+// Matches bollard's default request timeout
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+pub const NAMESPACE_LABEL: &str = "orbit.namespace";
 
 #[derive(Debug, Clone)]
 pub struct DockerRuntime {
     client: Docker,
+    // Host OS and platform reported by the daemon, resolved on first use
+    host: Arc<OnceCell<HostPlatform>>,
+    // Explicit rootless setting from the daemon config, otherwise detected
+    rootless_override: Option<bool>,
+    // Default namespace for services that don't set their own
+    namespace: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HostPlatform {
+    pub os_type: String,
+    pub architecture: String,
+    pub variant: Option<String>,
+    pub rootless: bool,
+}
+
+impl HostPlatform {
+    // Map the kernel architecture reported by `docker info` to its OCI name
+    fn from_daemon(os_type: Option<String>, architecture: Option<String>) -> Self {
+        let os_type = os_type
+            .unwrap_or_else(|| "linux".to_string())
+            .to_lowercase();
+        let (architecture, variant) = match architecture.as_deref().unwrap_or("x86_64") {
+            "x86_64" | "amd64" => ("amd64", None),
+            "aarch64" | "arm64" => ("arm64", None),
+            "armv7l" | "armhf" => ("arm", Some("v7")),
+            "armv6l" => ("arm", Some("v6")),
+            "i386" | "i686" => ("386", None),
+            other => (other, None),
+        };
+
+        Self {
+            os_type,
+            architecture: architecture.to_string(),
+            variant: variant.map(str::to_string),
+            rootless: false,
+        }
+    }
+
+    /// Platform string in the `os/arch[/variant]` form used when pulling
+    pub fn as_platform(&self) -> String {
+        match &self.variant {
+            Some(variant) => format!("{}/{}/{}", self.os_type, self.architecture, variant),
+            None => format!("{}/{}", self.os_type, self.architecture),
+        }
+    }
+
+    // A variant is only told apart when both sides name one, as arm64 images
+    // usually say "v8" where the daemon reports none
+    fn matches(&self, os: Option<&str>, architecture: Option<&str>, variant: Option<&str>) -> bool {
+        os.is_none_or(|os| os.eq_ignore_ascii_case(&self.os_type))
+            && architecture.is_none_or(|arch| arch == self.architecture)
+            && variant
+                .is_none_or(|variant| self.variant.as_deref().is_none_or(|own| own == variant))
+    }
 }
 
 impl DockerRuntime {
     pub fn new() -> Result<Self> {
-        let client = Docker::connect_with_local_defaults()
-            .map_err(|e| anyhow!("Failed to connect to Docker: {:?}", e))?;
-        Ok(Self { client })
+        Self::with_config(&DockerRuntimeConfig::default())
+    }
+
+    /// Connect to the daemon described by `config`, falling back to DOCKER_HOST and then
+    /// the local socket (or named pipe on Windows)
+    pub fn with_config(config: &DockerRuntimeConfig) -> Result<Self> {
+        let client =
+            Self::connect(config).map_err(|e| anyhow!("Failed to connect to Docker: {:?}", e))?;
+        Ok(Self {
+            client,
+            host: Arc::new(OnceCell::new()),
+            rootless_override: config.rootless,
+            namespace: config.namespace.clone(),
+        })
+    }
+
+    fn connect(config: &DockerRuntimeConfig) -> Result<Docker, bollard::errors::Error> {
+        let timeout = config
+            .timeout
+            .unwrap_or(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+            .as_secs();
+
+        let host = match config.host.clone().or_else(|| env::var("DOCKER_HOST").ok()) {
+            Some(host) if !host.is_empty() => host,
+            _ => return Self::connect_local(),
+        };
+
+        // Explicit TLS settings win, otherwise follow the docker CLI environment
+        let tls = config.tls.clone().or_else(|| {
+            let verify = env::var("DOCKER_TLS_VERIFY").is_ok_and(|v| !v.is_empty() && v != "0");
+            let cert_path = env::var_os("DOCKER_CERT_PATH")?;
+            verify.then(|| DockerTlsConfig::from_cert_path(Path::new(&cert_path)))
+        });
+
+        slog::info!(slog_scope::logger(), "Connecting to Docker daemon";
+            "host" => &host,
+            "tls" => tls.is_some()
+        );
+
+        #[cfg(unix)]
+        if host.starts_with("unix://") {
+            return Docker::connect_with_unix(&host, timeout, bollard::API_DEFAULT_VERSION);
+        }
+
+        #[cfg(windows)]
+        if host.starts_with("npipe://") {
+            return Docker::connect_with_named_pipe(&host, timeout, bollard::API_DEFAULT_VERSION);
+        }
+
+        match tls {
+            Some(tls) => Docker::connect_with_ssl(
+                &host,
+                &tls.key,
+                &tls.cert,
+                &tls.ca,
+                timeout,
+                bollard::API_DEFAULT_VERSION,
+            ),
+            None => Docker::connect_with_http(&host, timeout, bollard::API_DEFAULT_VERSION),
+        }
+    }
+
+    #[cfg(windows)]
+    fn connect_local() -> Result<Docker, bollard::errors::Error> {
+        // Docker on Windows listens on a named pipe rather than a unix socket
+        Docker::connect_with_named_pipe_defaults()
+    }
+
+    #[cfg(not(windows))]
+    fn connect_local() -> Result<Docker, bollard::errors::Error> {
+        // Without a system daemon, look for a rootless Docker or Podman socket
+        if !Path::new("/var/run/docker.sock").exists() {
+            if let Some(runtime_dir) = env::var_os("XDG_RUNTIME_DIR") {
+                let runtime_dir = Path::new(&runtime_dir);
+                for socket in ["docker.sock", "podman/podman.sock"] {
+                    let path = runtime_dir.join(socket);
+                    if path.exists() {
+                        slog::info!(slog_scope::logger(), "Using rootless container socket";
+                            "socket" => path.display().to_string()
+                        );
+                        return Docker::connect_with_unix(
+                            &path.to_string_lossy(),
+                            DEFAULT_TIMEOUT_SECS,
+                            bollard::API_DEFAULT_VERSION,
+                        );
+                    }
+                }
+            }
+        }
+
+        Docker::connect_with_local_defaults()
+    }
+
+    // Only a platform the daemon reported is kept; after a failed query the
+    // fallback is used for this call and the daemon is asked again next time
+    async fn host_platform(&self) -> HostPlatform {
+        let result = self
+            .host
+            .get_or_try_init(|| async {
+                let info = self.client.info().await?;
+                // Both rootless Docker and Podman advertise "name=rootless"
+                let rootless = info
+                    .security_options
+                    .iter()
+                    .flatten()
+                    .any(|option| option.contains("rootless"));
+                if rootless {
+                    slog::info!(slog_scope::logger(), "Detected rootless container daemon");
+                }
+                Ok::<_, bollard::errors::Error>(HostPlatform {
+                    rootless,
+                    ..HostPlatform::from_daemon(info.os_type, info.architecture)
+                })
+            })
+            .await;
+        match result {
+            Ok(platform) => platform.clone(),
+            Err(e) => {
+                slog::warn!(slog_scope::logger(), "Failed to query Docker host platform, assuming linux/amd64";
+                    "error" => e.to_string()
+                );
+                HostPlatform::from_daemon(None, None)
+            }
+        }
+    }
+
+    // Rootless daemons run containers in a user namespace whose addresses the
+    // host cannot route to, and cannot apply root-only settings such as tc
+    async fn is_rootless(&self) -> bool {
+        match self.rootless_override {
+            Some(rootless) => rootless,
+            None => self.host_platform().await.rootless,
+        }
+    }
+
+    async fn is_windows_host(&self) -> bool {
+        self.host_platform().await.os_type == "windows"
+    }
+
+    // Send the stop signal and give the container its grace period to exit.
+    // Unlike Docker's stop, this doesn't hold a request open for the whole
+    // grace period.
+    async fn stop_gracefully(&self, name: &str, stop: &StopSettings) -> Result<()> {
+        let signal = stop.signal.as_deref().unwrap_or(DEFAULT_STOP_SIGNAL);
+        let grace_period = stop.grace_period.unwrap_or(DEFAULT_STOP_GRACE_PERIOD);
+
+        match self
+            .client
+            .kill_container(name, Some(KillContainerOptions { signal }))
+            .await
+        {
+            Ok(()) => {}
+            // Already stopped
+            Err(DockerResponseServerError {
+                status_code: 409, ..
+            }) => return Ok(()),
+            Err(e) => return Err(anyhow!("Failed to stop container {}: {:?}", name, e)),
+        }
+
+        if tokio::time::timeout(grace_period, self.wait_container(name))
+            .await
+            .is_err()
+        {
+            slog::warn!(slog_scope::logger(), "Container did not exit within its grace period, killing it";
+                "container" => name,
+                "signal" => signal,
+                "grace_period" => format!("{:?}", grace_period)
+            );
+            match self
+                .client
+                .kill_container(name, Some(KillContainerOptions { signal: "SIGKILL" }))
+                .await
+            {
+                Ok(())
+                | Err(DockerResponseServerError {
+                    status_code: 409, ..
+                }) => {}
+                Err(e) => return Err(anyhow!("Failed to kill container {}: {:?}", name, e)),
+            }
+        }
+        Ok(())
+    }
+
+    fn namespace_for(&self, config: Option<&ServiceConfig>) -> Option<String> {
+        config
+            .and_then(|config| config.namespace.clone())
+            .or_else(|| self.namespace.clone())
+    }
+
+    fn namespace_labels(namespace: Option<String>) -> HashMap<String, String> {
+        namespace
+            .map(|namespace| HashMap::from([(NAMESPACE_LABEL.to_string(), namespace)]))
+            .unwrap_or_default()
+    }
+
+    // Namespaces whose containers this daemon may see; None stands for unlabelled containers
+    async fn visible_namespaces(&self, service_name: Option<&str>) -> HashSet<Option<String>> {
+        if let Some(service_name) = service_name {
+            let config = get_config_by_service(service_name).await;
+            return HashSet::from([self.namespace_for(config.as_ref())]);
+        }
+
+        let mut namespaces = HashSet::from([self.namespace.clone()]);
+        if let Some(store) = CONFIG_STORE.get() {
+            for (_, config) in store.read().await.values() {
+                namespaces.insert(self.namespace_for(Some(config)));
+            }
+        }
+        namespaces
+    }
+
+    // Windows hosts use the "nat" driver in place of "bridge"
+    async fn default_network(&self) -> &'static str {
+        if self.is_windows_host().await {
+            "nat"
+        } else {
+            "bridge"
+        }
     }
 
     async fn track_network_usage(&self, network_name: &str, service_name: &str) {
@@ -76,10 +376,14 @@ impl DockerRuntime {
                     .iter()
                     .any(|n| n.name == Some(network_name.clone()))
                 {
+                    // Shared by services, so it has no service or pod label
+                    let mut labels = Self::namespace_labels(self.namespace_for(Some(config)));
+                    labels.insert(MANAGED_LABEL.to_string(), "true".to_string());
                     self.client
                         .create_network(CreateNetworkOptions {
                             name: network_name.clone(),
-                            driver: "bridge".to_string(),
+                            driver: self.default_network().await.to_string(),
+                            labels,
                             ..Default::default()
                         })
                         .await?;
@@ -96,6 +400,8 @@ impl DockerRuntime {
 
         // For multi-container pods without specified network, create dedicated network
         let network_name = format!("{}__{}", service_name, uuid);
+        let mut labels = Self::namespace_labels(self.namespace_for(Some(config)));
+        labels.extend(ownership_labels(service_name, uuid));
 
         // Check if network exists and remove if it does
         if let Ok(networks) = self.client.list_networks::<String>(None).await {
@@ -108,15 +414,29 @@ impl DockerRuntime {
         }
 
         // Create network
-        self.client
+        let created = self
+            .client
             .create_network(CreateNetworkOptions {
                 name: network_name.clone(),
-                driver: "bridge".to_string(),
+                driver: self.default_network().await.to_string(),
+                labels,
                 ..Default::default()
             })
-            .await?;
-
-        Ok(Some(network_name))
+            .await;
+
+        match created {
+            Ok(_) => Ok(Some(network_name)),
+            // Some rootless setups cannot create bridges; share the default network instead
+            Err(e) if self.is_rootless().await => {
+                slog::warn!(slog_scope::logger(), "Failed to create pod network, using default network";
+                    "service" => service_name,
+                    "network" => &network_name,
+                    "error" => e.to_string()
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     async fn setup_volume_mounts(
@@ -126,6 +446,7 @@ impl DockerRuntime {
         config: &ServiceConfig,
     ) -> Result<(Option<tempfile::TempDir>, Vec<Mount>)> {
         let mut mounts = Vec::new();
+        let windows_host = self.is_windows_host().await;
         let temp_dir = if container.volume_mounts.is_some() {
             Some(
                 tempfile::Builder::new()
@@ -139,7 +460,35 @@ impl DockerRuntime {
         if let (Some(volume_mounts), Some(volumes)) = (&container.volume_mounts, &config.volumes) {
             for mount in volume_mounts {
                 if let Some(volume_data) = volumes.get(&mount.name) {
-                    if let Some(host_path) = &volume_data.host_path {
+                    if let Some(claim) = &volume_data.claim {
+                        let volume_dir = claim_volume(claim, container_name).await?;
+                        let source = match &mount.sub_path {
+                            Some(sub_path) => volume_dir.join(sub_path),
+                            None => volume_dir,
+                        };
+                        tokio::fs::create_dir_all(&source).await?;
+                        // Claims shared read-only are never writable, whatever the mount says
+                        let read_only = claim.access_mode == AccessMode::ReadOnlyMany
+                            || mount.read_only.unwrap_or(false);
+
+                        slog::info!(slog_scope::logger(), "Setting up volume claim mount";
+                            "container" => container_name,
+                            "claim" => &claim.name,
+                            "read_only" => read_only,
+                            "mount_path" => &mount.mount_path
+                        );
+
+                        mounts.push(Mount {
+                            target: Some(mount.mount_path.clone()),
+                            source: Some(source.to_string_lossy().into_owned()),
+                            typ: Some(MountTypeEnum::BIND),
+                            read_only: Some(read_only),
+                            bind_options: bind_options(windows_host),
+                            volume_options: None,
+                            tmpfs_options: None,
+                            consistency: mount_consistency(windows_host),
+                        });
+                    } else if let Some(host_path) = &volume_data.host_path {
                         let host_path = Path::new(host_path);
                         if !host_path.exists() {
                             return Err(anyhow!("Host path does not exist: {:?}", host_path));
@@ -156,16 +505,10 @@ impl DockerRuntime {
                             source: Some(host_path.to_string_lossy().into_owned()),
                             typ: Some(MountTypeEnum::BIND),
                             read_only: Some(mount.read_only.unwrap_or(true)),
-                            bind_options: Some(MountBindOptions {
-                                propagation: None,
-                                non_recursive: Some(false),
-                                create_mountpoint: Some(false),
-                                read_only_force_recursive: Some(false),
-                                read_only_non_recursive: Some(false),
-                            }),
+                            bind_options: bind_options(windows_host),
                             volume_options: None,
                             tmpfs_options: None,
-                            consistency: Some("default".to_string()),
+                            consistency: mount_consistency(windows_host),
                         });
                     } else if let Some(files) = &volume_data.files {
                         let temp_dir = temp_dir.as_ref().expect("Temp dir should exist");
@@ -188,16 +531,10 @@ impl DockerRuntime {
                                 source: Some(source_file.to_string_lossy().into_owned()),
                                 typ: Some(MountTypeEnum::BIND),
                                 read_only: Some(mount.read_only.unwrap_or(true)),
-                                bind_options: Some(MountBindOptions {
-                                    propagation: None,
-                                    non_recursive: Some(false),
-                                    create_mountpoint: Some(false),
-                                    read_only_force_recursive: Some(false),
-                                    read_only_non_recursive: Some(false),
-                                }),
+                                bind_options: bind_options(windows_host),
                                 volume_options: None,
                                 tmpfs_options: None,
-                                consistency: Some("default".to_string()),
+                                consistency: mount_consistency(windows_host),
                             });
                         } else {
                             mounts.push(Mount {
@@ -205,16 +542,10 @@ impl DockerRuntime {
                                 source: Some(volume_dir.to_string_lossy().into_owned()),
                                 typ: Some(MountTypeEnum::BIND),
                                 read_only: Some(mount.read_only.unwrap_or(true)),
-                                bind_options: Some(MountBindOptions {
-                                    propagation: None,
-                                    non_recursive: Some(false),
-                                    create_mountpoint: Some(false),
-                                    read_only_force_recursive: Some(false),
-                                    read_only_non_recursive: Some(false),
-                                }),
+                                bind_options: bind_options(windows_host),
                                 volume_options: None,
                                 tmpfs_options: None,
-                                consistency: Some("default".to_string()),
+                                consistency: mount_consistency(windows_host),
                             });
                         }
                     }
@@ -222,41 +553,200 @@ impl DockerRuntime {
             }
         }
 
+        if let Some(time_zone) = &container.time_zone {
+            if time_zone.host {
+                if windows_host {
+                    slog::warn!(slog_scope::logger(), "Ignoring host time zone mount on Windows host";
+                        "container" => container_name
+                    );
+                } else {
+                    for path in ["/etc/localtime", "/etc/timezone"] {
+                        if !Path::new(path).exists() {
+                            continue;
+                        }
+
+                        mounts.push(Mount {
+                            target: Some(path.to_string()),
+                            source: Some(path.to_string()),
+                            typ: Some(MountTypeEnum::BIND),
+                            read_only: Some(true),
+                            bind_options: bind_options(windows_host),
+                            volume_options: None,
+                            tmpfs_options: None,
+                            consistency: mount_consistency(windows_host),
+                        });
+                    }
+                }
+            }
+        }
+
+        // The container's own crash dump directory
+        if let Some((source, target)) = crash_dumps::prepare_dump_dir(container, container_name)? {
+            mounts.push(Mount {
+                target: Some(target.to_string_lossy().into_owned()),
+                source: Some(source.to_string_lossy().into_owned()),
+                typ: Some(MountTypeEnum::BIND),
+                read_only: Some(false),
+                bind_options: bind_options(windows_host),
+                volume_options: None,
+                tmpfs_options: None,
+                consistency: mount_consistency(windows_host),
+            });
+        }
+
         Ok((temp_dir, mounts))
     }
 
-    fn prepare_network_limits(&self, network_limit: &NetworkLimit) -> Result<Vec<DeviceRequest>> {
-        let mut device_requests = Vec::new();
+    async fn prepare_environment(
+        &self,
+        container: &Container,
+        service_name: &str,
+    ) -> Result<Vec<String>> {
+        let mut env = Vec::new();
+
+        if let Some(tz) = container.time_zone.as_ref().and_then(|t| t.tz.as_ref()) {
+            env.push(format!("TZ={}", tz));
+        }
 
-        if let (Some(ingress), Some(egress)) =
-            (&network_limit.ingress_rate, &network_limit.egress_rate)
-        {
-            // Convert rates to bits per second
-            let ingress_bps = parse_network_rate(ingress)?;
-            let egress_bps = parse_network_rate(egress)?;
-
-            // Create TC (traffic control) rules
-            device_requests.push(DeviceRequest {
-                driver: Some("tc-ingress".to_string()),
-                count: Some(1),
-                capabilities: Some(vec![vec![format!("rate={}", ingress_bps)]]),
-                ..Default::default()
-            });
+        if let Some(locale) = &container.locale {
+            env.push(format!("LANG={}", locale));
+            env.push(format!("LC_ALL={}", locale));
+        }
+
+        // Listed last so explicit variables override TZ and LANG
+        for (key, value) in container.environment(service_name).await? {
+            env.push(format!("{}={}", key, value));
+        }
+
+        Ok(env)
+    }
+
+    async fn prepare_secrets(
+        &self,
+        container_name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+        pod: Uuid,
+    ) -> Result<(Vec<ResolvedSecret>, Vec<Mount>)> {
+        let secrets = resolve_secrets(service_config, container, pod).await?;
+        let windows_host = self.is_windows_host().await;
+
+        let mounts = write_secret_files(container_name, &secrets)?
+            .into_iter()
+            .map(|(source, target)| Mount {
+                target: Some(target.to_string_lossy().into_owned()),
+                source: Some(source.to_string_lossy().into_owned()),
+                typ: Some(MountTypeEnum::BIND),
+                read_only: Some(true),
+                bind_options: bind_options(windows_host),
+                volume_options: None,
+                tmpfs_options: None,
+                consistency: mount_consistency(windows_host),
+            })
+            .collect();
+
+        Ok((secrets, mounts))
+    }
 
-            device_requests.push(DeviceRequest {
-                driver: Some("tc-egress".to_string()),
-                count: Some(1),
-                capabilities: Some(vec![vec![format!("rate={}", egress_bps)]]),
+    // Add "hostname:address" entries to a running container's hosts file,
+    // replacing older entries for the same hostnames. Docker keeps the file
+    // on the host and bind-mounts it, so it is rewritten in place.
+    async fn add_hosts_entries(&self, name: &str, entries: &[String]) -> Result<()> {
+        let hosts_path = self
+            .client
+            .inspect_container(name, None)
+            .await?
+            .hosts_path
+            .ok_or_else(|| anyhow!("Container {} has no hosts file", name))?;
+        let contents = tokio::fs::read_to_string(&hosts_path).await?;
+        tokio::fs::write(&hosts_path, with_hosts_entries(&contents, entries)?).await?;
+        Ok(())
+    }
+
+    // Hosts entries for the other containers of a pod on a shared network,
+    // where they have no aliases. None when the pod has a network of its own.
+    async fn peer_hosts_entries(&self, name: &str) -> Result<Option<Vec<String>>> {
+        let parts = parse_container_name(name)?;
+        let pod_network = format!("{}__{}", parts.service_name, parts.uuid);
+        let filters = HashMap::from([(
+            "label".to_string(),
+            vec![format!("{}={}", POD_LABEL, parts.uuid)],
+        )]);
+        let pod = self
+            .client
+            .list_containers(Some(bollard::container::ListContainersOptions {
+                filters,
                 ..Default::default()
-            });
+            }))
+            .await?;
+
+        let mut entries = Vec::new();
+        for peer in pod {
+            let networks = peer
+                .network_settings
+                .and_then(|settings| settings.networks)
+                .unwrap_or_default();
+            if networks.contains_key(&pod_network) {
+                return Ok(None);
+            }
+            let peer_name = peer
+                .names
+                .and_then(|names| names.into_iter().next())
+                .unwrap_or_default();
+            let peer_name = peer_name.trim_start_matches('/');
+            let Ok(peer_parts) = parse_container_name(peer_name) else {
+                continue;
+            };
+            if peer_name == name {
+                continue;
+            }
+            let ip = networks
+                .values()
+                .filter_map(|network| network.ip_address.clone())
+                .find(|ip| !ip.is_empty());
+            if let Some(ip) = ip {
+                entries.push(format!("{}:{}", peer_parts.container_name, ip));
+            }
         }
+        Ok(Some(entries))
+    }
 
-        Ok(device_requests)
+    // Proxy variables (and a host entry for the proxy) for services with a proxy allowlist
+    fn prepare_egress(
+        &self,
+        service_name: &str,
+        service_config: &ServiceConfig,
+        rootless: bool,
+    ) -> Option<(Vec<String>, Option<String>)> {
+        service_config.proxy_allowlist.as_ref()?;
+
+        let Some(proxy) = get_daemon_config().egress else {
+            slog::warn!(slog_scope::logger(), "Ignoring proxy_allowlist, no egress proxy in the daemon config";
+                "service" => service_name
+            );
+            return None;
+        };
+        // The proxy tells pods apart by their address, which rootless pods share
+        // with the host, so it would refuse all their connections
+        if rootless {
+            slog::warn!(slog_scope::logger(), "Ignoring proxy_allowlist, the egress proxy can't attribute rootless pods";
+                "service" => service_name
+            );
+            return None;
+        }
+
+        let extra_host = proxy
+            .advertise_host
+            .parse::<std::net::IpAddr>()
+            .is_err()
+            .then(|| format!("{}:host-gateway", proxy.advertise_host));
+        Some((proxy_environment(&proxy), extra_host))
     }
 
     async fn prepare_port_configuration(
         &self,
         container: &Container,
+        rootless: bool,
     ) -> Result<(
         HashMap<String, Option<Vec<PortBinding>>>,
         HashMap<String, HashMap<(), ()>>,
@@ -279,35 +769,92 @@ impl DockerRuntime {
                         host_port: Some(target_port.to_string()),
                     };
                     port_bindings.insert(container_port_key, Some(vec![host_binding]));
+                } else if rootless {
+                    // Publish on an ephemeral loopback port so Orbit can reach it
+                    let host_binding = PortBinding {
+                        host_ip: Some(LOOPBACK_ADDRESS.to_string()),
+                        host_port: None,
+                    };
+                    port_bindings.insert(container_port_key, Some(vec![host_binding]));
                 }
 
                 assigned_port_metadata.push(ContainerPortMetadata {
                     port: container_port,
                     target_port: port_config.target_port,
                     node_port: port_config.node_port,
+                    host_port: None,
                 });
             }
         }
 
         Ok((port_bindings, exposed_ports, assigned_port_metadata))
     }
+}
+
+#[async_trait]
+impl ContainerRuntime for DockerRuntime {
+    async fn get_image_digest(&self, image: &str) -> Result<String> {
+        let inspect = self.client.inspect_image(image).await?;
+        let platform = self.host_platform().await;
+
+        // An image for another platform can't run here, so it is never
+        // reported as the one to update to
+        if !platform.matches(
+            inspect.os.as_deref(),
+            inspect.architecture.as_deref(),
+            inspect.variant.as_deref(),
+        ) {
+            return Err(anyhow!(
+                "Image {} is for {}/{}, not the host platform {}",
+                image,
+                inspect.os.as_deref().unwrap_or("unknown"),
+                inspect.architecture.as_deref().unwrap_or("unknown"),
+                platform.as_platform()
+            ));
+        }
+
+        if let Some(id) = inspect.id {
+            Ok(id)
+        } else {
+            Err(anyhow!("Failed to get image digest"))
+        }
+    }
 
-    pub async fn pull_image(
+    async fn pull_image(
         &self,
         service_name: &str,
-        containers: &Vec<Container>,
+        containers: &[Container],
         service_config: &ServiceConfig,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<()> {
         match service_config.pull_policy {
-            Some(PullPolicyValue::Always) => {
+            Some(PullPolicyValue::Always | PullPolicyValue::IfNotPresent) => {
+                // Resolve manifest lists to the variant for this host
+                let platform = self.host_platform().await.as_platform();
+
                 for container in containers {
                     let image_name = &container.image.clone();
+                    // A digest-pinned image is the same wherever it came from
+                    let if_not_present = matches!(
+                        service_config.pull_policy,
+                        Some(PullPolicyValue::IfNotPresent)
+                    ) || container.pinned_digest().is_some();
+                    if if_not_present && self.client.inspect_image(image_name).await.is_ok() {
+                        slog::debug!(slog_scope::logger(), "Image present, skipping image pull.";
+                            "service" => service_name,
+                            "image" => image_name
+                        );
+                        continue;
+                    }
                     let options = Some(CreateImageOptions {
                         from_image: image_name.clone(),
+                        platform: platform.clone(),
                         ..Default::default()
                     });
 
-                    let mut stream = self.client.create_image(options, None, None);
+                    let credentials = registry_credentials(service_config, image_name)
+                        .await?
+                        .map(docker_credentials);
+                    let mut stream = self.client.create_image(options, None, credentials);
 
                     while let Some(result) = stream.next().await {
                         match result {
@@ -315,6 +862,7 @@ impl DockerRuntime {
                                 slog::debug!(slog_scope::logger(), "Progress pulling image";
                                     "service" => service_name,
                                     "image" => image_name,
+                                    "platform" => &platform,
                                     "progress" =>  format!("{:?}", progress)
                                 );
                             }
@@ -322,6 +870,7 @@ impl DockerRuntime {
                                 slog::error!(slog_scope::logger(), "Error pulling image";
                                     "service" => service_name,
                                     "image" => image_name,
+                                    "platform" => &platform,
                                     "error" => e.to_string()
                                 );
                             }
@@ -341,19 +890,151 @@ impl DockerRuntime {
 
         Ok(())
     }
-}
 
-#[async_trait]
-impl ContainerRuntime for DockerRuntime {
-    async fn get_image_digest(&self, image: &str) -> Result<String> {
-        let inspect = self.client.inspect_image(image).await?;
+    async fn image_layers(&self, image: &str) -> Result<Option<ImageLayers>> {
+        let inspect = match self.client.inspect_image(image).await {
+            Ok(inspect) => inspect,
+            Err(DockerResponseServerError {
+                status_code: 404, ..
+            }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(ImageLayers {
+            layers: inspect
+                .root_fs
+                .and_then(|root_fs| root_fs.layers)
+                .unwrap_or_default(),
+            size: inspect.size.unwrap_or_default().max(0) as u64,
+        }))
+    }
 
-        // Get the image digest
-        if let Some(id) = inspect.id {
-            Ok(id)
-        } else {
-            Err(anyhow!("Failed to get image digest"))
+    async fn prefetch_image(
+        &self,
+        image: &str,
+        service_config: Option<&ServiceConfig>,
+    ) -> Result<bool> {
+        if self.client.inspect_image(image).await.is_ok() {
+            return Ok(false);
+        }
+        let credentials = match service_config {
+            Some(config) => registry_credentials(config, image)
+                .await?
+                .map(docker_credentials),
+            None => None,
+        };
+        let options = Some(CreateImageOptions {
+            from_image: image.to_string(),
+            platform: self.host_platform().await.as_platform(),
+            ..Default::default()
+        });
+
+        let mut stream = self.client.create_image(options, None, credentials);
+        while let Some(result) = stream.next().await {
+            result?;
+        }
+        Ok(true)
+    }
+
+    async fn pause_container(&self, name: &str) -> Result<()> {
+        self.client
+            .pause_container(name)
+            .await
+            .map_err(|e| anyhow!("Failed to pause container {}: {:?}", name, e))
+    }
+
+    async fn unpause_container(&self, name: &str) -> Result<()> {
+        self.client
+            .unpause_container(name)
+            .await
+            .map_err(|e| anyhow!("Failed to unpause container {}: {:?}", name, e))
+    }
+
+    async fn image_changed_upstream(
+        &self,
+        image: &str,
+        service_config: &ServiceConfig,
+    ) -> Result<bool> {
+        // Only images that are always pulled follow the registry
+        if !matches!(service_config.pull_policy, Some(PullPolicyValue::Always)) {
+            return Ok(false);
         }
+
+        let credentials = registry_credentials(service_config, image)
+            .await?
+            .map(docker_credentials);
+        let distribution = self
+            .client
+            .inspect_registry_image(image, credentials)
+            .await?;
+
+        let platform = self.host_platform().await;
+        if !distribution.platforms.is_empty()
+            && !distribution.platforms.iter().any(|p| {
+                platform.matches(
+                    p.os.as_deref(),
+                    p.architecture.as_deref(),
+                    p.variant.as_deref(),
+                )
+            })
+        {
+            return Err(anyhow!(
+                "Image {} has no variant for the host platform {}",
+                image,
+                platform.as_platform()
+            ));
+        }
+
+        let remote_digest = distribution
+            .descriptor
+            .digest
+            .ok_or_else(|| anyhow!("Registry returned no digest for image {}", image))?;
+        let local = match self.client.inspect_image(image).await {
+            Ok(inspect) => inspect,
+            Err(DockerResponseServerError {
+                status_code: 404, ..
+            }) => return Ok(true),
+            Err(e) => return Err(e.into()),
+        };
+
+        // A pulled tag records the digest of the manifest (list) it resolved to
+        Ok(!has_repo_digest(
+            local.repo_digests.as_deref().unwrap_or_default(),
+            &remote_digest,
+        ))
+    }
+
+    async fn update_resources(
+        &self,
+        name: &str,
+        memory: Option<u64>,
+        nano_cpus: Option<u64>,
+    ) -> Result<()> {
+        // The swap limit includes memory and can't be below it, so it moves
+        // with the memory limit to keep the container's swap allowance
+        let memory_swap = match memory {
+            Some(memory) => {
+                let host_config = self
+                    .client
+                    .inspect_container(name, None)
+                    .await?
+                    .host_config
+                    .unwrap_or_default();
+                resized_memory_swap(
+                    memory as i64,
+                    host_config.memory.unwrap_or(0),
+                    host_config.memory_swap.unwrap_or(0),
+                )
+            }
+            None => None,
+        };
+        let options = UpdateContainerOptions::<String> {
+            memory: memory.map(|memory| memory as i64),
+            memory_swap,
+            nano_cpus: nano_cpus.map(|nano_cpus| nano_cpus as i64),
+            ..Default::default()
+        };
+        self.client.update_container(name, options).await?;
+        Ok(())
     }
 
     async fn check_image_updates(
@@ -365,6 +1046,10 @@ impl ContainerRuntime for DockerRuntime {
         let mut updates = HashMap::new();
 
         for container in containers {
+            if container.pinned_digest().is_some() {
+                updates.insert(container.name.clone(), false);
+                continue;
+            }
             let current_hash = current_hashes.get(&container.name);
             let new_hash = self.get_image_digest(&container.image).await?;
 
@@ -378,11 +1063,22 @@ impl ContainerRuntime for DockerRuntime {
     }
 
     async fn remove_pod_network(&self, network_name: &str, service_name: &str) -> Result<()> {
-        if network_name == "bridge" {
+        if network_name == "bridge" || network_name == "nat" {
             return Ok(());
         }
 
-        if network_name.contains("__") {
+        let network_labels = self
+            .client
+            .inspect_network::<String>(network_name, None)
+            .await
+            .ok()
+            .and_then(|network| network.labels)
+            .unwrap_or_default();
+        // Networks created before Orbit labelled them only have their name to go by
+        let pod_network = network_labels.contains_key(POD_LABEL)
+            || (!network_labels.contains_key(MANAGED_LABEL) && network_name.contains("__"));
+
+        if pod_network {
             // Always remove auto-generated networks
             self.client.remove_network(network_name).await?;
         } else {
@@ -396,6 +1092,9 @@ impl ContainerRuntime for DockerRuntime {
 
     async fn create_pod_network(&self, service_name: &str, uuid: &str) -> Result<String> {
         let network_name = format!("{}__{}", service_name, uuid);
+        let config = get_config_by_service(service_name).await;
+        let mut labels = Self::namespace_labels(self.namespace_for(config.as_ref()));
+        labels.extend(ownership_labels(service_name, uuid));
 
         // Check if network exists and remove if it does
         if let Ok(networks) = self.client.list_networks::<String>(None).await {
@@ -411,7 +1110,8 @@ impl ContainerRuntime for DockerRuntime {
         self.client
             .create_network(CreateNetworkOptions {
                 name: network_name.clone(),
-                driver: "bridge".to_string(),
+                driver: self.default_network().await.to_string(),
+                labels,
                 ..Default::default()
             })
             .await?;
@@ -471,30 +1171,76 @@ impl ContainerRuntime for DockerRuntime {
             )
             .await?;
 
+        let windows_host = self.is_windows_host().await;
+        let default_network = self.default_network().await;
+        let rootless = self.is_rootless().await;
+        let mut labels = Self::namespace_labels(self.namespace_for(Some(service_config)));
+        labels.extend(ownership_labels(service_name, &uuid.to_string()));
+        let egress = self.prepare_egress(service_name, service_config, rootless);
+        // On a network of its own the pod's containers reach each other by name
+        // through aliases. Elsewhere aliases would clash with other pods', so
+        // each container gets hosts entries for those started before it, and
+        // for those started after it once they have their addresses.
+        let pod_network = network_name.is_some() && service_config.network.is_none();
+        let mut peer_hosts: Vec<String> = Vec::new();
+
         let mut started_containers = Vec::new();
         let mut containers_to_cleanup = Vec::new();
         let mut pod_creation_failed = false;
         let mut temp_dirs = Vec::new();
 
-        for container in containers {
+        for container in dependencies::start_order(containers)? {
             let container_name =
                 container.generate_runtime_name(service_name, pod_number, &uuid.to_string())?;
 
+            if let Err(e) = dependencies::wait_for_dependencies(self, container, containers, |c| {
+                Ok(c.generate_runtime_name(service_name, pod_number, &uuid.to_string())?)
+            })
+            .await
+            {
+                slog::error!(slog_scope::logger(), "Failed to start container";
+                    "service" => service_name,
+                    "container" => &container_name,
+                    "error" => e.to_string()
+                );
+                pod_creation_failed = true;
+                break;
+            }
+
             // Setup volume mounts first and keep temp_dir alive
-            let (temp_dir, mounts) = self
+            let (temp_dir, mut mounts) = self
                 .setup_volume_mounts(container, &container_name, service_config)
                 .await?;
             if let Some(dir) = temp_dir {
                 temp_dirs.push(dir);
             }
 
-            let (port_bindings, exposed_ports, assigned_port_metadata) =
-                self.prepare_port_configuration(container).await?;
+            let secrets = match self
+                .prepare_secrets(&container_name, container, service_config, uuid)
+                .await
+            {
+                Ok((secrets, secret_mounts)) => {
+                    mounts.extend(secret_mounts);
+                    secrets
+                }
+                Err(e) => {
+                    slog::error!(slog_scope::logger(), "Failed to resolve secrets";
+                        "service" => service_name,
+                        "container" => &container_name,
+                        "error" => e.to_string()
+                    );
+                    remove_secret_files(&container_name);
+                    pod_creation_failed = true;
+                    break;
+                }
+            };
+
+            let (port_bindings, exposed_ports, mut assigned_port_metadata) =
+                self.prepare_port_configuration(container, rootless).await?;
 
             // Get container-specific limits, falling back to service-level limits
             let memory_limit = container
-                .memory_limit
-                .as_ref()
+                .memory_limit_value()
                 .map(parse_memory_limit)
                 .transpose()?
                 .or_else(|| {
@@ -509,8 +1255,7 @@ impl ContainerRuntime for DockerRuntime {
                 .unwrap_or(0);
 
             let cpu_limit = container
-                .cpu_limit
-                .as_ref()
+                .cpu_limit_value()
                 .map(parse_cpu_limit)
                 .transpose()?
                 .or_else(|| {
@@ -524,25 +1269,185 @@ impl ContainerRuntime for DockerRuntime {
                 })
                 .unwrap_or(0);
 
+            let ulimits = container
+                .ulimits
+                .iter()
+                .flatten()
+                .map(|(name, value)| {
+                    let (soft, hard) = parse_ulimit(name, value)?;
+                    Ok(ResourcesUlimits {
+                        name: Some(name.clone()),
+                        soft: Some(soft),
+                        hard: Some(hard),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let shm_size = container
+                .shm_size
+                .as_ref()
+                .map(parse_shm_size)
+                .transpose()?;
+
             let mut host_config = HostConfig {
                 port_bindings: Some(port_bindings),
                 memory: Some(memory_limit.try_into().unwrap()),
                 nano_cpus: Some(cpu_limit as i64),
-                network_mode: network_name.clone().or(Some("bridge".to_string())),
+                ulimits: (!ulimits.is_empty()).then_some(ulimits),
+                pids_limit: container.pids_limit,
+                shm_size: shm_size.map(|size| size as i64),
+                sysctls: container.sysctls.clone(),
+                network_mode: network_name.clone().or(Some(default_network.to_string())),
                 privileged: container.privileged,
+                cap_add: container.cap_add.clone(),
+                cap_drop: container.cap_drop.clone(),
+                security_opt: container.security_opt.clone(),
+                readonly_rootfs: container.read_only_root_fs,
+                userns_mode: container.userns_mode.clone(),
                 ..Default::default()
             };
+            if let Some(tmpfs) = &container.tmpfs {
+                host_config.tmpfs = Some(
+                    tmpfs
+                        .iter()
+                        .map(|entry| {
+                            let (path, options) = parse_tmpfs(entry)?;
+                            Ok((path.to_string(), options.to_string()))
+                        })
+                        .collect::<Result<HashMap<_, _>>>()?,
+                );
+            }
+            if let Some(blkio_limit) = &container.blkio_limit {
+                let throttle = |rate: Option<u64>| {
+                    rate.map(|rate| {
+                        vec![ThrottleDevice {
+                            path: Some(blkio_limit.device.clone()),
+                            rate: Some(rate as i64),
+                        }]
+                    })
+                };
+                let read_bps = blkio_limit.read_bps.as_deref().map(parse_disk_rate);
+                let write_bps = blkio_limit.write_bps.as_deref().map(parse_disk_rate);
+                host_config.blkio_device_read_bps = throttle(read_bps.transpose()?);
+                host_config.blkio_device_write_bps = throttle(write_bps.transpose()?);
+                host_config.blkio_device_read_iops = throttle(blkio_limit.read_iops);
+                host_config.blkio_device_write_iops = throttle(blkio_limit.write_iops);
+            }
+            if let Some(logging) = logging_for(service_config, container) {
+                host_config.log_config = Some(HostConfigLogConfig {
+                    typ: Some(logging.driver.clone()),
+                    config: Some(logging.options.clone()),
+                });
+            }
+
+            let mut dns = dns_for(service_config, container);
+            if let Some((_, Some(extra_host))) = &egress {
+                dns.extra_hosts.push(extra_host.clone());
+            }
+            dns.extra_hosts.extend(peer_hosts.iter().cloned());
+            host_config.dns = (!dns.servers.is_empty()).then_some(dns.servers);
+            host_config.dns_search = (!dns.search.is_empty()).then_some(dns.search);
+            host_config.extra_hosts = (!dns.extra_hosts.is_empty()).then_some(dns.extra_hosts);
+
+            // Privileged mode, capabilities and security options are Linux-only
+            if windows_host && host_config.privileged.is_some() {
+                slog::warn!(slog_scope::logger(), "Ignoring privileged flag on Windows host";
+                    "service" => service_name,
+                    "container" => &container_name
+                );
+                host_config.privileged = None;
+            }
+            if windows_host
+                && (host_config.cap_add.is_some()
+                    || host_config.cap_drop.is_some()
+                    || host_config.security_opt.is_some())
+            {
+                slog::warn!(slog_scope::logger(), "Ignoring cap_add, cap_drop and security_opt on Windows host";
+                    "service" => service_name,
+                    "container" => &container_name
+                );
+                host_config.cap_add = None;
+                host_config.cap_drop = None;
+                host_config.security_opt = None;
+            }
+            if windows_host
+                && (host_config.ulimits.is_some()
+                    || host_config.pids_limit.is_some()
+                    || host_config.shm_size.is_some()
+                    || host_config.sysctls.is_some())
+            {
+                slog::warn!(slog_scope::logger(), "Ignoring ulimits, pids_limit, shm_size and sysctls on Windows host";
+                    "service" => service_name,
+                    "container" => &container_name
+                );
+                host_config.ulimits = None;
+                host_config.pids_limit = None;
+                host_config.shm_size = None;
+                host_config.sysctls = None;
+            }
+            if windows_host && container.blkio_limit.is_some() {
+                slog::warn!(slog_scope::logger(), "Ignoring blkio_limit on Windows host";
+                    "service" => service_name,
+                    "container" => &container_name
+                );
+                host_config.blkio_device_read_bps = None;
+                host_config.blkio_device_write_bps = None;
+                host_config.blkio_device_read_iops = None;
+                host_config.blkio_device_write_iops = None;
+            }
+            if windows_host
+                && (host_config.readonly_rootfs.is_some()
+                    || host_config.tmpfs.is_some()
+                    || host_config.userns_mode.is_some())
+            {
+                slog::warn!(slog_scope::logger(), "Ignoring read_only_root_fs, tmpfs and userns_mode on Windows host";
+                    "service" => service_name,
+                    "container" => &container_name
+                );
+                host_config.readonly_rootfs = None;
+                host_config.tmpfs = None;
+                host_config.userns_mode = None;
+            }
 
             if !mounts.is_empty() {
                 host_config.mounts = Some(mounts);
             }
 
-            // Apply network limits if specified
-            if let Some(network_limit) = &container.network_limit {
-                let device_requests = self.prepare_network_limits(network_limit)?;
-                if !device_requests.is_empty() {
-                    host_config.device_requests = Some(device_requests);
+            // Network limits are applied once the container runs (traffic control is Linux-only)
+            let network_limit = container.network_limit.as_ref().filter(|_| {
+                let supported = !windows_host && !rootless;
+                if !supported {
+                    slog::warn!(slog_scope::logger(), "Ignoring network_limit, traffic control needs a rootful Linux host";
+                        "service" => service_name,
+                        "container" => &container_name
+                    );
                 }
+                supported
+            });
+
+            if let Some(devices) = &container.devices {
+                host_config.devices = Some(
+                    devices
+                        .iter()
+                        .map(|device| parse_device_mapping(device))
+                        .collect::<Result<Vec<_>>>()?,
+                );
+            }
+
+            if let Some(gpus) = &container.gpus {
+                let device_ids = assign_devices(&container_name, gpus).await?;
+                host_config
+                    .device_requests
+                    .get_or_insert_with(Vec::new)
+                    .push(DeviceRequest {
+                        driver: Some(gpus.driver.clone()),
+                        count: match device_ids {
+                            Some(_) => None,
+                            None => gpus.docker_count()?,
+                        },
+                        device_ids,
+                        capabilities: Some(vec![gpus.capabilities.clone()]),
+                        ..Default::default()
+                    });
             }
 
             let mut config = Config {
@@ -550,14 +1455,67 @@ impl ContainerRuntime for DockerRuntime {
                 host_config: Some(host_config),
                 exposed_ports: Some(exposed_ports),
                 // this helps avoid a collision if networks are being shared, as service_name is unique
-                hostname: Some(format!("{}-{}", service_name, container.name)),
+                hostname: Some(
+                    container
+                        .hostname
+                        .clone()
+                        .unwrap_or_else(|| format!("{}-{}", service_name, container.name)),
+                ),
+                user: container.user.clone(),
+                working_dir: container.working_dir.clone(),
+                // Also honored by a manual `docker stop`
+                stop_signal: container.stop_signal.clone(),
+                stop_timeout: container
+                    .stop_grace_period
+                    .map(|grace| grace.as_secs_f64().ceil() as i64),
+                healthcheck: container.health_check.as_ref().and_then(native_healthcheck),
                 ..Default::default()
             };
+            if let (true, Some(network)) = (pod_network, &network_name) {
+                config.networking_config = Some(NetworkingConfig {
+                    endpoints_config: HashMap::from([(
+                        network.clone(),
+                        EndpointSettings {
+                            aliases: Some(vec![container.name.clone()]),
+                            ..Default::default()
+                        },
+                    )]),
+                });
+            }
 
+            // Orbit's own labels win over the spec's
+            let mut container_labels = container.labels.clone().unwrap_or_default();
+            container_labels.extend(labels.clone());
+            if !container_labels.is_empty() {
+                config.labels = Some(container_labels);
+            }
+
+            if let Some(entrypoint) = &container.entrypoint {
+                config.entrypoint = Some(entrypoint.clone());
+            }
             if let Some(cmd) = &container.command {
                 config.cmd = Some(cmd.clone());
             }
 
+            // Egress variables first so a container's own env can still override them
+            let mut env: Vec<String> = egress
+                .as_ref()
+                .map(|(env, _)| env.clone())
+                .unwrap_or_default();
+            env.extend(
+                self.prepare_environment(container, &service_config.name)
+                    .await?,
+            );
+            // Secrets last, they take precedence over plain variables
+            for secret in &secrets {
+                if let Some(name) = &secret.env {
+                    env.push(format!("{}={}", name, secret.value.expose()));
+                }
+            }
+            if !env.is_empty() {
+                config.env = Some(env);
+            }
+
             match self
                 .client
                 .create_container(
@@ -579,18 +1537,38 @@ impl ContainerRuntime for DockerRuntime {
                             if let Ok(container_data) =
                                 self.client.inspect_container(&container_name, None).await
                             {
+                                if let Some(network_limit) = network_limit {
+                                    let pid = container_data.state.as_ref().and_then(|s| s.pid);
+                                    apply_network_limit(&container_name, pid, network_limit).await;
+                                }
                                 if let Some(network_settings) = container_data.network_settings {
                                     if let Some(networks) = network_settings.networks {
                                         // Handle Option<String> for network_name
                                         let network_key =
-                                            network_name.as_deref().unwrap_or("bridge");
+                                            network_name.as_deref().unwrap_or(default_network);
                                         if let Some(network) = networks.get(network_key) {
                                             if let Some(ip) = &network.ip_address {
+                                                if !pod_network && containers.len() > 1 {
+                                                    peer_hosts
+                                                        .push(format!("{}:{}", container.name, ip));
+                                                }
+                                                let mut ip = ip.clone();
+                                                if rootless {
+                                                    // Pod addresses live in the daemon's namespace
+                                                    let published = published_ports(
+                                                        network_settings.ports.as_ref(),
+                                                    );
+                                                    for port in &mut assigned_port_metadata {
+                                                        port.host_port =
+                                                            published.get(&port.port).copied();
+                                                    }
+                                                    ip = LOOPBACK_ADDRESS.to_string();
+                                                }
                                                 containers_to_cleanup
                                                     .push((container_name.clone(), ip.clone()));
                                                 started_containers.push((
                                                     container_name,
-                                                    ip.clone(),
+                                                    ip,
                                                     assigned_port_metadata,
                                                 ));
                                                 continue;
@@ -607,12 +1585,14 @@ impl ContainerRuntime for DockerRuntime {
                                 "container" => &container_name,
                                 "error" => e.to_string()
                             );
+                            remove_secret_files(&container_name);
                             pod_creation_failed = true;
                             break;
                         }
                     }
                 }
                 Err(e) => {
+                    remove_secret_files(&container_name);
                     match e {
                         DockerResponseServerError { status_code, .. } => {
                             if status_code == 404 {
@@ -622,7 +1602,9 @@ impl ContainerRuntime for DockerRuntime {
                                     "error" => e.to_string()
                                 );
                                 match service_config.pull_policy {
-                                    Some(PullPolicyValue::Always) => {
+                                    Some(
+                                        PullPolicyValue::Always | PullPolicyValue::IfNotPresent,
+                                    ) => {
                                         match self
                                             .pull_image(service_name, containers, service_config)
                                             .await
@@ -686,15 +1668,44 @@ impl ContainerRuntime for DockerRuntime {
             return Err(anyhow!("Failed to create one or more containers in pod"));
         }
 
+        for (index, (container_name, _, _)) in started_containers.iter().enumerate() {
+            let later = peer_hosts.get(index + 1..).unwrap_or_default();
+            if later.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.add_hosts_entries(container_name, later).await {
+                slog::warn!(slog_scope::logger(), "Failed to add hosts entries for containers started later";
+                    "service" => service_name,
+                    "container" => container_name,
+                    "error" => e.to_string()
+                );
+            }
+        }
+
         Ok(started_containers)
     }
 
     async fn stop_container(&self, name: &str) -> Result<()> {
+        // A frozen container can't handle its stop signal
+        if preemption::forget_paused(name) {
+            let _ = self.client.unpause_container(name).await;
+        }
+
         // Stop the container
-        self.client
-            .stop_container(name, None)
-            .await
-            .map_err(|e| anyhow!("Failed to stop container {}: {:?}", name, e))?;
+        let stop = stop_settings(name).await;
+        if stop.is_default() {
+            // Docker's own stop uses the container's stop signal and timeout
+            self.client
+                .stop_container(name, None)
+                .await
+                .map_err(|e| anyhow!("Failed to stop container {}: {:?}", name, e))?;
+        } else {
+            self.stop_gracefully(name, &stop).await?;
+        }
+        remove_secret_files(name);
+        crash_dumps::remove_dump_dir(name);
+        clear_shaping_state(name);
+        release_devices(name);
 
         // Remove the container
         self.client
@@ -711,6 +1722,110 @@ impl ContainerRuntime for DockerRuntime {
         Ok(())
     }
 
+    async fn wait_container(&self, name: &str) -> Result<i64> {
+        let mut responses = self.client.wait_container(
+            name,
+            Some(WaitContainerOptions {
+                condition: "not-running",
+            }),
+        );
+
+        // bollard reports non-zero exit codes as errors
+        match responses.next().await {
+            Some(Ok(response)) => Ok(response.status_code),
+            Some(Err(DockerContainerWaitError { code, .. })) => Ok(code),
+            Some(Err(e)) => Err(anyhow!("Failed to wait for container {}: {:?}", name, e)),
+            None => Err(anyhow!("No exit status for container {}", name)),
+        }
+    }
+
+    async fn restart_container(
+        &self,
+        name: &str,
+        container: &Container,
+        _service_config: &ServiceConfig,
+    ) -> Result<()> {
+        let running = self
+            .client
+            .inspect_container(name, None)
+            .await
+            .ok()
+            .and_then(|data| data.state?.running)
+            .unwrap_or(false);
+        if running {
+            self.client
+                .stop_container(name, None)
+                .await
+                .map_err(|e| anyhow!("Failed to stop container {}: {:?}", name, e))?;
+        }
+
+        // The stopped container keeps its network, mounts and secret files
+        self.client
+            .start_container(name, None::<StartContainerOptions<String>>)
+            .await
+            .map_err(|e| anyhow!("Failed to restart container {}: {:?}", name, e))?;
+
+        // but gets a new veth pair, which needs its limits again
+        if let Some(network_limit) = &container.network_limit {
+            if !self.is_windows_host().await && !self.is_rootless().await {
+                let pid = self
+                    .client
+                    .inspect_container(name, None)
+                    .await
+                    .ok()
+                    .and_then(|data| data.state?.pid);
+                apply_network_limit(name, pid, network_limit).await;
+            }
+        }
+
+        // Docker also writes the hosts file again, without the pod's
+        // containers that were started after this one
+        let restored = match self.peer_hosts_entries(name).await {
+            Ok(Some(entries)) if !entries.is_empty() => {
+                self.add_hosts_entries(name, &entries).await
+            }
+            other => other.map(|_| ()),
+        };
+        if let Err(e) = restored {
+            slog::warn!(slog_scope::logger(), "Failed to add hosts entries for the pod's containers";
+                "container" => name,
+                "error" => e.to_string()
+            );
+        }
+        Ok(())
+    }
+
+    async fn exec_container(&self, name: &str, command: &[String]) -> Result<i64> {
+        let exec = self
+            .client
+            .create_exec(
+                name,
+                CreateExecOptions {
+                    cmd: Some(command.to_vec()),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| anyhow!("Failed to create exec in container {}: {:?}", name, e))?;
+
+        // Drain the output so the command runs to completion
+        if let StartExecResults::Attached { mut output, .. } = self
+            .client
+            .start_exec(&exec.id, None::<StartExecOptions>)
+            .await
+            .map_err(|e| anyhow!("Failed to exec in container {}: {:?}", name, e))?
+        {
+            while output.next().await.is_some() {}
+        }
+
+        let inspect = self.client.inspect_exec(&exec.id).await?;
+        inspect
+            .exit_code
+            .ok_or_else(|| anyhow!("No exit code for exec in container {}", name))
+    }
+
     async fn inspect_container(&self, name: &str) -> Result<ContainerStats> {
         let options = Some(StatsOptions {
             stream: false,
@@ -724,6 +1839,16 @@ impl ContainerRuntime for DockerRuntime {
             .ok_or_else(|| anyhow!("No stats available for container {}", name))??;
 
         let container_data = self.client.inspect_container(name, None).await?;
+        let native_health = container_data
+            .state
+            .as_ref()
+            .and_then(|state| state.health.as_ref())
+            .and_then(|health| match health.status {
+                Some(HealthStatusEnum::STARTING) => Some(HealthState::Starting),
+                Some(HealthStatusEnum::HEALTHY) => Some(HealthState::Healthy),
+                Some(HealthStatusEnum::UNHEALTHY) => Some(HealthState::Unhealthy),
+                _ => None,
+            });
 
         let mut ip_address = String::from("");
 
@@ -740,26 +1865,11 @@ impl ContainerRuntime for DockerRuntime {
                 String::new()
             };
 
-            if let Some(ports) = network_settings.ports {
-                for (container_port_proto, host_bindings) in ports {
-                    if let Some(host_bindings) = host_bindings {
-                        for binding in host_bindings {
-                            if let Some(host_port) = binding.host_port {
-                                // Parse "80/tcp" to get just the port number
-                                if let Some(container_port) = container_port_proto
-                                    .split('/')
-                                    .next()
-                                    .and_then(|p| p.parse::<u16>().ok())
-                                {
-                                    if let Ok(host_port) = host_port.parse::<u16>() {
-                                        port_mappings.insert(container_port, host_port);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+            port_mappings = published_ports(network_settings.ports.as_ref());
+        }
+
+        if self.is_rootless().await {
+            ip_address = LOOPBACK_ADDRESS.to_string();
         }
 
         let service_name = name
@@ -768,34 +1878,105 @@ impl ContainerRuntime for DockerRuntime {
             .expect("Split always returns at least one element");
         let service_cfg = get_config_by_service(service_name).await.unwrap();
 
-        let nano_cpus = service_cfg
-            .cpu_limit
-            .as_ref() // Safely access the Option<Value>
-            .and_then(|value| parse_cpu_limit(value).ok()); // Parse and handle Result -> Option
+        // The container's own limit, which vertical scaling may have changed
+        let nano_cpus = container_data
+            .host_config
+            .as_ref()
+            .and_then(|host_config| host_config.nano_cpus)
+            .filter(|nano_cpus| *nano_cpus > 0)
+            .map(|nano_cpus| nano_cpus as u64)
+            .or_else(|| {
+                service_cfg
+                    .cpu_limit
+                    .as_ref() // Safely access the Option<Value>
+                    .and_then(|value| parse_cpu_limit(value).ok()) // Parse and handle Result -> Option
+            });
+
+        // Docker's stats have no GPU usage, so it is sampled separately
+        let mut sample = StatsSample::from(&stats);
+        let gpus = parse_container_name(name).ok().and_then(|parts| {
+            service_cfg
+                .spec
+                .containers
+                .iter()
+                .find(|c| c.name == parts.container_name)
+                .and_then(|c| c.gpus.clone())
+        });
+        if let Some(gpus) = &gpus {
+            sample.gpu = gpu_usage(name, gpus).await;
+        }
 
         let mut container_stats =
-            update_container_stats(service_name, name, stats.clone(), nano_cpus).await;
+            record_container_stats(service_name, name, sample, nano_cpus).await;
         container_stats.ip_address = ip_address;
         container_stats.port_mappings = port_mappings;
+        container_stats.native_health = native_health;
 
         Ok(container_stats)
     }
 
+    async fn runtime_info(&self) -> Result<RuntimeInfo> {
+        let info = self.client.info().await?;
+        let count = |value: Option<i64>| value.map(|v| v.max(0) as u64);
+
+        Ok(RuntimeInfo {
+            name: "docker".to_string(),
+            version: info.server_version,
+            operating_system: info.operating_system,
+            kernel_version: info.kernel_version,
+            cpus: count(info.ncpu),
+            memory_total: count(info.mem_total),
+            containers: count(info.containers).unwrap_or(0),
+            containers_running: count(info.containers_running).unwrap_or(0),
+            images: count(info.images),
+            data_root: info.docker_root_dir,
+            rootless: self.is_rootless().await,
+        })
+    }
+
     async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>> {
-        let mut filters = HashMap::new();
+        let list = |filters: HashMap<String, Vec<String>>| {
+            self.client
+                .list_containers(Some(bollard::container::ListContainersOptions {
+                    all: false, // only get running containers
+                    filters,
+                    ..Default::default()
+                }))
+        };
 
+        // Orbit's containers, found by their ownership labels
+        let mut label_filters = vec![format!("{}=true", MANAGED_LABEL)];
         if let Some(service_name) = service_name {
-            filters.insert("name".to_string(), vec![format!("{}__", service_name)]);
+            label_filters.push(format!("{}={}", SERVICE_LABEL, service_name));
         }
+        let mut containers = list(HashMap::from([("label".to_string(), label_filters)])).await?;
 
-        let containers = self
-            .client
-            .list_containers(Some(bollard::container::ListContainersOptions {
-                all: false, // only get running containers
-                filters,
-                ..Default::default()
-            }))
-            .await?;
+        // Containers created before Orbit labelled them only have their name to go
+        // by; the name filter is a substring match, so names are checked again below
+        let mut name_filters = HashMap::new();
+        if let Some(service_name) = service_name {
+            name_filters.insert("name".to_string(), vec![format!("{}__", service_name)]);
+        }
+        let unlabelled = list(name_filters).await?.into_iter().filter(|c| {
+            !c.labels
+                .as_ref()
+                .is_some_and(|labels| labels.contains_key(MANAGED_LABEL))
+        });
+        containers.extend(unlabelled);
+
+        // Containers of other namespaces belong to another Orbit daemon
+        let namespaces = self.visible_namespaces(service_name).await;
+        let containers: Vec<_> = containers
+            .into_iter()
+            .filter(|c| {
+                let namespace = c
+                    .labels
+                    .as_ref()
+                    .and_then(|labels| labels.get(NAMESPACE_LABEL))
+                    .cloned();
+                namespaces.contains(&namespace)
+            })
+            .collect();
 
         slog::debug!(slog_scope::logger(), "Found containers";
             "service" => service_name,
@@ -822,7 +2003,242 @@ impl ContainerRuntime for DockerRuntime {
                     .first()
                     .and_then(|p| p.public_port)
                     .unwrap_or(0),
+                labels: c.labels.unwrap_or_default(),
+            })
+            .filter(|c| {
+                c.is_labelled()
+                    || c.service_name().is_some_and(|name| {
+                        service_name.is_none_or(|service_name| service_name == name)
+                    })
             })
             .collect())
     }
+
+    async fn list_pod_networks(&self, service_name: &str) -> Result<Vec<(String, Uuid)>> {
+        let filters = HashMap::from([(
+            "label".to_string(),
+            vec![
+                format!("{}=true", MANAGED_LABEL),
+                format!("{}={}", SERVICE_LABEL, service_name),
+            ],
+        )]);
+        let networks = self
+            .client
+            .list_networks(Some(ListNetworksOptions { filters }))
+            .await?;
+
+        Ok(networks
+            .into_iter()
+            .filter_map(|network| {
+                let pod = network
+                    .labels
+                    .as_ref()
+                    .and_then(|labels| labels.get(POD_LABEL))
+                    .and_then(|pod| Uuid::parse_str(pod).ok())?;
+                Some((network.name?, pod))
+            })
+            .collect())
+    }
+}
+
+// Container port -> host port for every published binding
+fn published_ports(ports: Option<&PortMap>) -> HashMap<u16, u16> {
+    let mut port_mappings = HashMap::new();
+
+    for (container_port_proto, host_bindings) in ports.into_iter().flatten() {
+        // Parse "80/tcp" to get just the port number
+        let Some(container_port) = container_port_proto
+            .split('/')
+            .next()
+            .and_then(|p| p.parse::<u16>().ok())
+        else {
+            continue;
+        };
+
+        for binding in host_bindings.iter().flatten() {
+            if let Some(host_port) = binding.host_port.as_ref().and_then(|p| p.parse().ok()) {
+                port_mappings.insert(container_port, host_port);
+            }
+        }
+    }
+
+    port_mappings
+}
+
+// Docker runs exec checks itself and Orbit reads the result when inspecting
+fn native_healthcheck(health_check: &HealthCheckConfig) -> Option<HealthConfig> {
+    let check = health_check.exec_check.as_ref()?;
+    let nanos = |duration: Duration| duration.as_nanos() as i64;
+    Some(HealthConfig {
+        test: Some(
+            std::iter::once("CMD".to_string())
+                .chain(check.command.iter().cloned())
+                .collect(),
+        ),
+        interval: Some(nanos(check.interval)),
+        timeout: Some(nanos(check.timeout)),
+        retries: Some(check.retries.into()),
+        // Failures during the start period don't count towards the retries
+        start_period: (!health_check.initial_delay.is_zero())
+            .then(|| nanos(health_check.initial_delay)),
+        ..Default::default()
+    })
+}
+
+fn docker_credentials(credentials: RegistryCredentials) -> DockerCredentials {
+    let expose = |value: Option<SecretValue>| value.map(|v| v.expose().to_string());
+    DockerCredentials {
+        username: credentials.username,
+        password: expose(credentials.password),
+        auth: expose(credentials.auth),
+        identitytoken: expose(credentials.identity_token),
+        registrytoken: expose(credentials.registry_token),
+        serveraddress: Some(credentials.registry),
+        ..Default::default()
+    }
+}
+
+// Repo digests are "repository@sha256:..."
+fn has_repo_digest(repo_digests: &[String], digest: &str) -> bool {
+    repo_digests
+        .iter()
+        .any(|repo_digest| repo_digest.rsplit_once('@').map(|(_, d)| d) == Some(digest))
+}
+
+// The swap limit for a new memory limit, keeping the swap on top of memory.
+// None leaves unlimited (-1) or unset swap as it is.
+fn resized_memory_swap(memory: i64, current_memory: i64, current_swap: i64) -> Option<i64> {
+    if current_swap <= 0 || current_memory <= 0 {
+        return None;
+    }
+    Some(memory + (current_swap - current_memory).max(0))
+}
+
+// "/dev/host[:/dev/container][:permissions]", as accepted by `docker run --device`
+fn parse_device_mapping(device: &str) -> Result<DeviceMapping> {
+    let is_permissions =
+        |value: &str| !value.is_empty() && value.chars().all(|c| matches!(c, 'r' | 'w' | 'm'));
+
+    let (host, container, permissions) = match device.split(':').collect::<Vec<_>>()[..] {
+        [host] => (host, host, "rwm"),
+        [host, permissions] if is_permissions(permissions) => (host, host, permissions),
+        [host, container] => (host, container, "rwm"),
+        [host, container, permissions] if is_permissions(permissions) => {
+            (host, container, permissions)
+        }
+        _ => return Err(anyhow!("Invalid device {:?}", device)),
+    };
+    if !host.starts_with('/') || !container.starts_with('/') {
+        return Err(anyhow!(
+            "Invalid device {:?}, paths must be absolute",
+            device
+        ));
+    }
+
+    Ok(DeviceMapping {
+        path_on_host: Some(host.to_string()),
+        path_in_container: Some(container.to_string()),
+        cgroup_permissions: Some(permissions.to_string()),
+    })
+}
+
+// Bind propagation options are not understood by Windows daemons
+fn bind_options(windows_host: bool) -> Option<MountBindOptions> {
+    if windows_host {
+        return None;
+    }
+
+    Some(MountBindOptions {
+        propagation: None,
+        non_recursive: Some(false),
+        create_mountpoint: Some(false),
+        read_only_force_recursive: Some(false),
+        read_only_non_recursive: Some(false),
+    })
+}
+
+fn mount_consistency(windows_host: bool) -> Option<String> {
+    if windows_host {
+        None
+    } else {
+        Some("default".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_platform_matches() {
+        let amd64 =
+            HostPlatform::from_daemon(Some("linux".to_string()), Some("x86_64".to_string()));
+        assert_eq!(amd64.as_platform(), "linux/amd64");
+        assert!(amd64.matches(Some("linux"), Some("amd64"), None));
+        assert!(!amd64.matches(Some("linux"), Some("arm64"), Some("v8")));
+        assert!(!amd64.matches(Some("windows"), Some("amd64"), None));
+
+        let arm64 = HostPlatform::from_daemon(None, Some("aarch64".to_string()));
+        assert!(arm64.matches(Some("linux"), Some("arm64"), Some("v8")));
+
+        let armv7 = HostPlatform::from_daemon(None, Some("armv7l".to_string()));
+        assert_eq!(armv7.as_platform(), "linux/arm/v7");
+        assert!(armv7.matches(Some("linux"), Some("arm"), Some("v7")));
+        assert!(!armv7.matches(Some("linux"), Some("arm"), Some("v6")));
+    }
+
+    #[test]
+    fn test_has_repo_digest() {
+        let repo_digests = vec!["registry.local:5000/app@sha256:abc".to_string()];
+        assert!(has_repo_digest(&repo_digests, "sha256:abc"));
+        assert!(!has_repo_digest(&repo_digests, "sha256:def"));
+        assert!(!has_repo_digest(&[], "sha256:abc"));
+    }
+
+    #[test]
+    fn test_resized_memory_swap() {
+        // Docker's default allowance, as much swap as memory
+        assert_eq!(resized_memory_swap(300, 200, 400), Some(500));
+        // Swap disabled stays disabled
+        assert_eq!(resized_memory_swap(300, 200, 200), Some(300));
+        assert_eq!(resized_memory_swap(100, 200, 250), Some(150));
+        // Unlimited or unset swap is left alone
+        assert_eq!(resized_memory_swap(300, 200, -1), None);
+        assert_eq!(resized_memory_swap(300, 0, 0), None);
+    }
+
+    #[test]
+    fn test_parse_device_mapping() {
+        let mapping = |device| {
+            let mapping = parse_device_mapping(device).unwrap();
+            (
+                mapping.path_on_host.unwrap(),
+                mapping.path_in_container.unwrap(),
+                mapping.cgroup_permissions.unwrap(),
+            )
+        };
+        let owned = |host: &str, container: &str, permissions: &str| {
+            (
+                host.to_string(),
+                container.to_string(),
+                permissions.to_string(),
+            )
+        };
+
+        assert_eq!(mapping("/dev/fuse"), owned("/dev/fuse", "/dev/fuse", "rwm"));
+        assert_eq!(mapping("/dev/fuse:r"), owned("/dev/fuse", "/dev/fuse", "r"));
+        assert_eq!(
+            mapping("/dev/ttyUSB0:/dev/modem"),
+            owned("/dev/ttyUSB0", "/dev/modem", "rwm")
+        );
+        assert_eq!(
+            mapping("/dev/ttyUSB0:/dev/modem:rw"),
+            owned("/dev/ttyUSB0", "/dev/modem", "rw")
+        );
+
+        assert!(parse_device_mapping("dev/fuse").is_err());
+        assert!(parse_device_mapping("/dev/fuse:modem").is_err());
+        assert!(parse_device_mapping("/dev/ttyUSB0:/dev/modem:rx").is_err());
+        assert!(parse_device_mapping("/dev/a:/dev/b:rw:extra").is_err());
+    }
 }
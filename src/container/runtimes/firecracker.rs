@@ -0,0 +1,919 @@
+// src/container/runtimes/firecracker.rs
+//
+// Runs each container as a Firecracker microVM. Images are expected to be
+// pre-built ext4 root filesystems; ports are reached on the guest's address
+// on a host bridge, so the proxy works exactly as it does for Docker. Each
+// VM's process, tap device and address are recorded in its state directory,
+// so VMs left behind by a previous daemon are found and torn down.
+use crate::config::daemon::FirecrackerRuntimeConfig;
+use crate::config::{
+    get_config_by_service, parse_container_name, parse_cpu_limit, parse_memory_limit, ServiceConfig,
+};
+use crate::container::dependencies;
+use crate::container::dns::dns_for;
+use crate::container::secrets::secrets_for;
+use crate::container::{
+    logging_for, record_container_stats, Container, ContainerInfo, ContainerPortMetadata,
+    ContainerRuntime, ContainerStats, RuntimeInfo, StatsSample,
+};
+
+use super::{binary_version, exit_code};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::process::{Child, Command};
+use tokio::sync::{Mutex, OnceCell, RwLock};
+use uuid::Uuid;
+
+const DEFAULT_VCPUS: u64 = 1;
+// Also the least guest memory a VM is started with
+const DEFAULT_MEMORY_MIB: u64 = 128;
+const VM_RECORD_FILE: &str = "vm.json";
+const API_SOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+const EXIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_KERNEL_ARGS: &str = "console=ttyS0 reboot=k panic=1 pci=off";
+
+#[derive(Debug)]
+struct MicroVm {
+    child: Child,
+    ip_address: Ipv4Addr,
+    tap: String,
+    state_dir: PathBuf,
+    memory_limit: u64,
+    nano_cpus: u64,
+    labels: HashMap<String, String>,
+}
+
+// What a running VM holds on the host, kept in its state directory
+#[derive(Debug, Serialize, Deserialize)]
+struct VmRecord {
+    pid: u32,
+    tap: String,
+    ip_address: Ipv4Addr,
+}
+
+#[derive(Debug)]
+pub struct FirecrackerRuntime {
+    config: FirecrackerRuntimeConfig,
+    network: Ipv4Net,
+    vms: Arc<RwLock<FxHashMap<String, MicroVm>>>,
+    allocated_ips: Arc<Mutex<FxHashSet<Ipv4Addr>>>,
+    bridge: OnceCell<()>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ipv4Net {
+    network: Ipv4Addr,
+    prefix: u8,
+}
+
+impl Ipv4Net {
+    fn parse(cidr: &str) -> Result<Self> {
+        let (addr, prefix) = cidr
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Invalid subnet {}, expected CIDR notation", cidr))?;
+        let prefix: u8 = prefix.parse()?;
+        if !(8..=30).contains(&prefix) {
+            return Err(anyhow!(
+                "Subnet prefix must be between /8 and /30: {}",
+                cidr
+            ));
+        }
+        let addr: Ipv4Addr = addr.parse()?;
+        let network = Ipv4Addr::from(u32::from(addr) & Self::mask_bits(prefix));
+        Ok(Self { network, prefix })
+    }
+
+    fn mask_bits(prefix: u8) -> u32 {
+        u32::MAX << (32 - prefix)
+    }
+
+    fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(Self::mask_bits(self.prefix))
+    }
+
+    // The first host address belongs to the bridge and acts as the guests' gateway
+    fn gateway(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network) + 1)
+    }
+
+    fn guest_addresses(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let start = u32::from(self.network) + 2;
+        let broadcast = u32::from(self.network) | !Self::mask_bits(self.prefix);
+        (start..broadcast).map(Ipv4Addr::from)
+    }
+}
+
+impl FirecrackerRuntime {
+    pub fn new(config: FirecrackerRuntimeConfig) -> Result<Self> {
+        let network = Ipv4Net::parse(&config.subnet)?;
+
+        if !config.kernel_image.exists() {
+            return Err(anyhow!(
+                "Firecracker kernel image not found: {}",
+                config.kernel_image.display()
+            ));
+        }
+
+        Ok(Self {
+            config,
+            network,
+            vms: Arc::new(RwLock::new(FxHashMap::default())),
+            allocated_ips: Arc::new(Mutex::new(FxHashSet::default())),
+            bridge: OnceCell::new(),
+        })
+    }
+
+    // Images map to ext4 files: an explicit path is used as-is, otherwise
+    // "nginx:1.27" resolves to "<rootfs_dir>/nginx_1.27.ext4"
+    fn resolve_rootfs(&self, image: &str) -> PathBuf {
+        let path = Path::new(image);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+
+        let file_name: String = image
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.config.rootfs_dir.join(format!("{}.ext4", file_name))
+    }
+
+    async fn ensure_bridge(&self) -> Result<()> {
+        self.bridge
+            .get_or_try_init(|| async {
+                let bridge = &self.config.bridge;
+                if !Path::new("/sys/class/net").join(bridge).exists() {
+                    run_ip(&["link", "add", "name", bridge, "type", "bridge"]).await?;
+                    let gateway = format!("{}/{}", self.network.gateway(), self.network.prefix);
+                    run_ip(&["addr", "add", &gateway, "dev", bridge]).await?;
+                }
+                run_ip(&["link", "set", bridge, "up"]).await?;
+
+                slog::info!(slog_scope::logger(), "Firecracker bridge ready";
+                    "bridge" => bridge,
+                    "subnet" => &self.config.subnet
+                );
+                self.reclaim_leftover_vms().await;
+                Ok::<(), anyhow::Error>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // VMs of a previous daemon can't be adopted, as their API sockets and
+    // processes aren't ours to wait on, so they are torn down before any VM
+    // is started and their addresses are free again
+    async fn reclaim_leftover_vms(&self) {
+        let Ok(mut entries) = tokio::fs::read_dir(&self.config.state_dir).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let state_dir = entry.path();
+            if !state_dir.is_dir() {
+                continue;
+            }
+            let record = tokio::fs::read(state_dir.join(VM_RECORD_FILE))
+                .await
+                .ok()
+                .and_then(|contents| serde_json::from_slice::<VmRecord>(&contents).ok());
+            if let Some(record) = &record {
+                // Only if the process is still the VMM of this VM, and not
+                // something that got its pid since
+                let socket = state_dir.join("firecracker.sock");
+                let cmdline =
+                    std::fs::read(format!("/proc/{}/cmdline", record.pid)).unwrap_or_default();
+                if String::from_utf8_lossy(&cmdline).contains(&*socket.to_string_lossy()) {
+                    unsafe {
+                        libc::kill(record.pid as libc::pid_t, libc::SIGKILL);
+                    }
+                }
+                let _ = run_ip(&["link", "del", &record.tap]).await;
+            }
+            let _ = tokio::fs::remove_dir_all(&state_dir).await;
+            slog::warn!(slog_scope::logger(), "Removed microVM left by a previous run";
+                "container" => entry.file_name().to_string_lossy().to_string(),
+                "tap" => record.map(|record| record.tap).unwrap_or_default()
+            );
+        }
+    }
+
+    async fn allocate_ip(&self) -> Result<Ipv4Addr> {
+        let mut allocated = self.allocated_ips.lock().await;
+        let ip = self
+            .network
+            .guest_addresses()
+            .find(|ip| !allocated.contains(ip))
+            .ok_or_else(|| anyhow!("No free addresses left in {}", self.config.subnet))?;
+        allocated.insert(ip);
+        Ok(ip)
+    }
+
+    async fn release_ip(&self, ip: Ipv4Addr) {
+        self.allocated_ips.lock().await.remove(&ip);
+    }
+
+    fn resolve_limits(container: &Container, service_config: &ServiceConfig) -> Result<(u64, u64)> {
+        let memory = container
+            .memory_limit_value()
+            .or(service_config.memory_limit.as_ref())
+            .map(parse_memory_limit)
+            .transpose()?
+            .map(|bytes| bytes / (1024 * 1024))
+            .unwrap_or(DEFAULT_MEMORY_MIB);
+        if memory < DEFAULT_MEMORY_MIB {
+            return Err(anyhow!(
+                "memory_limit of container '{}' is {}MiB, a microVM needs at least {}MiB",
+                container.name,
+                memory,
+                DEFAULT_MEMORY_MIB
+            ));
+        }
+
+        // Firecracker only deals in whole vCPUs, so round fractional limits up
+        let nano_cpus = container
+            .cpu_limit_value()
+            .or(service_config.cpu_limit.as_ref())
+            .map(parse_cpu_limit)
+            .transpose()?
+            .unwrap_or(DEFAULT_VCPUS * 1_000_000_000);
+
+        Ok((memory, nano_cpus))
+    }
+
+    fn boot_args(&self, container: &Container, ip: Ipv4Addr) -> String {
+        // ip=<client>::<gateway>:<netmask>:<hostname>:<device>:<autoconf>
+        let mut args = format!(
+            "{} ip={}::{}:{}:{}:eth0:off",
+            DEFAULT_KERNEL_ARGS,
+            ip,
+            self.network.gateway(),
+            self.network.netmask(),
+            container.hostname.as_deref().unwrap_or_default()
+        );
+
+        if let Some(extra) = &self.config.kernel_args {
+            args.push(' ');
+            args.push_str(extra);
+        }
+
+        // The first element replaces init, the rest are passed through to it
+        let argv = container.argv();
+        if let Some((init, rest)) = argv.as_ref().and_then(|c| c.split_first()) {
+            args.push_str(&format!(" init={}", init));
+            if !rest.is_empty() {
+                args.push_str(" -- ");
+                args.push_str(&rest.join(" "));
+            }
+        }
+
+        args
+    }
+
+    fn warn_unsupported(
+        service_name: &str,
+        container_name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+    ) {
+        let dns = dns_for(service_config, container);
+        let unsupported = [
+            ("privileged", container.privileged.is_some()),
+            ("cap_add", container.cap_add.is_some()),
+            ("cap_drop", container.cap_drop.is_some()),
+            ("security_opt", container.security_opt.is_some()),
+            ("devices", container.devices.is_some()),
+            ("gpus", container.gpus.is_some()),
+            ("ulimits", container.ulimits.is_some()),
+            ("pids_limit", container.pids_limit.is_some()),
+            ("shm_size", container.shm_size.is_some()),
+            ("sysctls", container.sysctls.is_some()),
+            ("stop_signal", container.stop_signal.is_some()),
+            ("stop_grace_period", container.stop_grace_period.is_some()),
+            ("logging", logging_for(service_config, container).is_some()),
+            ("tmpfs", container.tmpfs.is_some()),
+            ("userns_mode", container.userns_mode.is_some()),
+            ("user", container.user.is_some()),
+            ("dns", !dns.servers.is_empty()),
+            ("dns_search", !dns.search.is_empty()),
+            ("extra_hosts", !dns.extra_hosts.is_empty()),
+            ("working_dir", container.working_dir.is_some()),
+            ("volume_mounts", container.volume_mounts.is_some()),
+            ("network_limit", container.network_limit.is_some()),
+            ("blkio_limit", container.blkio_limit.is_some()),
+            ("time_zone", container.time_zone.is_some()),
+            ("locale", container.locale.is_some()),
+            ("env", container.env.is_some()),
+            ("env_file", container.env_file.is_some()),
+            ("restart_policy", container.restart_policy.is_some()),
+            (
+                "secrets",
+                !secrets_for(service_config, container).is_empty(),
+            ),
+            (
+                "target_port",
+                container
+                    .ports
+                    .iter()
+                    .flatten()
+                    .any(|p| p.target_port.is_some()),
+            ),
+        ];
+
+        for (field, _) in unsupported.iter().filter(|(_, set)| *set) {
+            slog::warn!(slog_scope::logger(), "Ignoring option not supported by firecracker runtime";
+                "service" => service_name,
+                "container" => container_name,
+                "option" => *field
+            );
+        }
+    }
+
+    async fn boot_vm(
+        &self,
+        container_name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+    ) -> Result<MicroVm> {
+        let (memory_mib, nano_cpus) = Self::resolve_limits(container, service_config)?;
+        let vcpus = nano_cpus.div_ceil(1_000_000_000).max(1);
+
+        let rootfs = self.resolve_rootfs(&container.image);
+        if !rootfs.exists() {
+            return Err(anyhow!(
+                "Root filesystem for image {} not found at {}",
+                container.image,
+                rootfs.display()
+            ));
+        }
+
+        let ip_address = self.allocate_ip().await?;
+        let tap = format!("fc{}", &Uuid::new_v4().simple().to_string()[..12]);
+        let state_dir = self.config.state_dir.join(container_name);
+
+        // Everything from here on is undone if the VM doesn't start
+        let result = async {
+            tokio::fs::create_dir_all(&state_dir).await?;
+
+            // Each VM gets a private, writable copy of the image
+            let vm_rootfs = state_dir.join("rootfs.ext4");
+            tokio::fs::copy(&rootfs, &vm_rootfs).await?;
+
+            run_ip(&["tuntap", "add", "dev", &tap, "mode", "tap"]).await?;
+            run_ip(&["link", "set", &tap, "master", &self.config.bridge]).await?;
+            run_ip(&["link", "set", &tap, "up"]).await?;
+
+            let socket = state_dir.join("firecracker.sock");
+            let _ = tokio::fs::remove_file(&socket).await;
+            let log_file = std::fs::File::create(state_dir.join("firecracker.log"))?;
+
+            let child = Command::new(&self.config.binary)
+                .arg("--api-sock")
+                .arg(&socket)
+                .arg("--id")
+                .arg(&tap)
+                .stdin(std::process::Stdio::null())
+                .stdout(log_file.try_clone()?)
+                .stderr(log_file)
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|e| anyhow!("Failed to spawn firecracker: {}", e))?;
+
+            let record = VmRecord {
+                pid: child.id().unwrap_or_default(),
+                tap: tap.clone(),
+                ip_address,
+            };
+            tokio::fs::write(state_dir.join(VM_RECORD_FILE), serde_json::to_vec(&record)?).await?;
+
+            wait_for_socket(&socket).await?;
+
+            api_put(
+                &socket,
+                "/machine-config",
+                json!({ "vcpu_count": vcpus, "mem_size_mib": memory_mib }),
+            )
+            .await?;
+            api_put(
+                &socket,
+                "/boot-source",
+                json!({
+                    "kernel_image_path": self.config.kernel_image,
+                    "boot_args": self.boot_args(container, ip_address),
+                }),
+            )
+            .await?;
+            api_put(
+                &socket,
+                "/drives/rootfs",
+                json!({
+                    "drive_id": "rootfs",
+                    "path_on_host": vm_rootfs,
+                    "is_root_device": true,
+                    "is_read_only": container.read_only_root_fs == Some(true),
+                }),
+            )
+            .await?;
+            api_put(
+                &socket,
+                "/network-interfaces/eth0",
+                json!({ "iface_id": "eth0", "host_dev_name": tap }),
+            )
+            .await?;
+            api_put(
+                &socket,
+                "/actions",
+                json!({ "action_type": "InstanceStart" }),
+            )
+            .await?;
+
+            Ok::<Child, anyhow::Error>(child)
+        }
+        .await;
+
+        match result {
+            Ok(child) => Ok(MicroVm {
+                child,
+                ip_address,
+                tap,
+                state_dir,
+                memory_limit: memory_mib * 1024 * 1024,
+                nano_cpus,
+                labels: container.labels.clone().unwrap_or_default(),
+            }),
+            Err(e) => {
+                let _ = run_ip(&["link", "del", &tap]).await;
+                let _ = tokio::fs::remove_dir_all(&state_dir).await;
+                self.release_ip(ip_address).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn teardown_vm(&self, mut vm: MicroVm) {
+        // The guest has no graceful shutdown path besides the i8042 reset, which
+        // minimal kernels often lack, so the VMM process is simply terminated
+        let _ = vm.child.kill().await;
+        let _ = run_ip(&["link", "del", &vm.tap]).await;
+        let _ = tokio::fs::remove_dir_all(&vm.state_dir).await;
+        self.release_ip(vm.ip_address).await;
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for FirecrackerRuntime {
+    fn validate_service(&self, service_config: &ServiceConfig) -> Result<()> {
+        for container in &service_config.spec.containers {
+            Self::resolve_limits(container, service_config)?;
+            // Host directories can't be shared with a VM
+            if container.crash_dumps.is_some() {
+                return Err(anyhow!(
+                    "Container {}: crash_dumps is not supported by the firecracker runtime",
+                    container.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_image_digest(&self, image: &str) -> Result<String> {
+        // Root filesystems are plain files, so size and mtime identify a build
+        let metadata = tokio::fs::metadata(self.resolve_rootfs(image)).await?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Ok(format!("{}-{}", metadata.len(), modified))
+    }
+
+    async fn pull_image(
+        &self,
+        _service_name: &str,
+        _containers: &[Container],
+        _service_config: &ServiceConfig,
+    ) -> Result<()> {
+        // Root filesystems are provisioned out of band
+        Ok(())
+    }
+
+    async fn check_image_updates(
+        &self,
+        _service_name: &str,
+        containers: &[Container],
+        current_hashes: &HashMap<String, String>,
+    ) -> Result<HashMap<String, bool>> {
+        let mut updates = HashMap::new();
+
+        for container in containers {
+            let new_hash = self.get_image_digest(&container.image).await?;
+            updates.insert(
+                container.name.clone(),
+                current_hashes.get(&container.name) != Some(&new_hash),
+            );
+        }
+
+        Ok(updates)
+    }
+
+    async fn remove_pod_network(&self, _network_name: &str, _service_name: &str) -> Result<()> {
+        // All microVMs share the bridge; taps are removed with their VM
+        Ok(())
+    }
+
+    async fn create_pod_network(&self, _service_name: &str, _uuid: &str) -> Result<String> {
+        self.ensure_bridge().await?;
+        Ok(self.config.bridge.clone())
+    }
+
+    async fn start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.attempt_start_containers(service_name, pod_number, containers, service_config)
+            .await
+    }
+
+    async fn attempt_start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.ensure_bridge().await?;
+
+        let uuid = Uuid::new_v4().to_string();
+        let mut started: Vec<(String, String, Vec<ContainerPortMetadata>)> = Vec::new();
+
+        for container in dependencies::start_order(containers)? {
+            let container_name =
+                container.generate_runtime_name(service_name, pod_number, &uuid)?;
+            Self::warn_unsupported(service_name, &container_name, container, service_config);
+
+            if let Err(e) = dependencies::wait_for_dependencies(self, container, containers, |c| {
+                Ok(c.generate_runtime_name(service_name, pod_number, &uuid)?)
+            })
+            .await
+            {
+                slog::error!(slog_scope::logger(), "Failed to start microVM";
+                    "service" => service_name,
+                    "container" => &container_name,
+                    "error" => e.to_string()
+                );
+                for (name, _, _) in &started {
+                    let _ = self.stop_container(name).await;
+                }
+                return Err(anyhow!("Failed to create one or more containers in pod"));
+            }
+
+            let vm = match self
+                .boot_vm(&container_name, container, service_config)
+                .await
+            {
+                Ok(vm) => vm,
+                Err(e) => {
+                    slog::error!(slog_scope::logger(), "Failed to start microVM";
+                        "service" => service_name,
+                        "container" => &container_name,
+                        "error" => e.to_string()
+                    );
+                    for (name, _, _) in &started {
+                        let _ = self.stop_container(name).await;
+                    }
+                    return Err(anyhow!("Failed to create one or more containers in pod"));
+                }
+            };
+
+            let ip_address = vm.ip_address.to_string();
+            self.vms.write().await.insert(container_name.clone(), vm);
+
+            let ports = container
+                .ports
+                .iter()
+                .flatten()
+                .map(|port| ContainerPortMetadata {
+                    port: port.port,
+                    target_port: port.target_port,
+                    node_port: port.node_port,
+                    host_port: None,
+                })
+                .collect();
+
+            slog::info!(slog_scope::logger(), "Started microVM";
+                "service" => service_name,
+                "container" => &container_name,
+                "ip" => &ip_address
+            );
+            started.push((container_name, ip_address, ports));
+        }
+
+        Ok(started)
+    }
+
+    async fn stop_container(&self, name: &str) -> Result<()> {
+        let vm = self
+            .vms
+            .write()
+            .await
+            .remove(name)
+            .ok_or_else(|| anyhow!("Failed to stop container {}: no such microVM", name))?;
+        self.teardown_vm(vm).await;
+        Ok(())
+    }
+
+    async fn wait_container(&self, name: &str) -> Result<i64> {
+        loop {
+            {
+                let mut vms = self.vms.write().await;
+                let vm = vms
+                    .get_mut(name)
+                    .ok_or_else(|| anyhow!("No such microVM for container {}", name))?;
+                if let Some(status) = vm.child.try_wait()? {
+                    return Ok(exit_code(status));
+                }
+            }
+            tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn restart_container(
+        &self,
+        name: &str,
+        _container: &Container,
+        _service_config: &ServiceConfig,
+    ) -> Result<()> {
+        // A new VM would get a new tap device and IP address, which the pod's
+        // load balancer entries do not know about
+        Err(anyhow!(
+            "Failed to restart container {}: in-place restarts are not supported by the firecracker runtime",
+            name
+        ))
+    }
+
+    async fn exec_container(&self, name: &str, _command: &[String]) -> Result<i64> {
+        // The guest runs no agent Orbit could hand the command to
+        Err(anyhow!(
+            "Failed to exec in container {}: exec is not supported by the firecracker runtime",
+            name
+        ))
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerStats> {
+        let (pid, ip_address, tap, memory_limit, nano_cpus) = {
+            let mut vms = self.vms.write().await;
+            let vm = vms
+                .get_mut(name)
+                .ok_or_else(|| anyhow!("No stats available for container {}", name))?;
+
+            if let Ok(Some(status)) = vm.child.try_wait() {
+                return Err(anyhow!("MicroVM {} exited: {}", name, status));
+            }
+
+            let pid = vm
+                .child
+                .id()
+                .ok_or_else(|| anyhow!("MicroVM {} has no process", name))?;
+            (
+                pid,
+                vm.ip_address.to_string(),
+                vm.tap.clone(),
+                vm.memory_limit,
+                vm.nano_cpus,
+            )
+        };
+
+        let sample = sample_vm_stats(pid, &tap, memory_limit)
+            .await
+            .map_err(|e| anyhow!("Failed to read stats for {}: {}", name, e))?;
+
+        let service_name = parse_container_name(name)?.service_name;
+        let nano_cpus = get_config_by_service(&service_name)
+            .await
+            .and_then(|cfg| cfg.cpu_limit)
+            .and_then(|value| parse_cpu_limit(&value).ok())
+            .or(Some(nano_cpus));
+
+        let mut container_stats =
+            record_container_stats(&service_name, name, sample, nano_cpus).await;
+        container_stats.ip_address = ip_address;
+
+        Ok(container_stats)
+    }
+
+    async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>> {
+        let prefix = service_name.map(|name| format!("{}__", name));
+        let vms = self.vms.read().await;
+
+        Ok(vms
+            .iter()
+            .filter(|(name, _)| prefix.as_ref().is_none_or(|p| name.starts_with(p)))
+            .map(|(name, vm)| ContainerInfo {
+                id: vm.child.id().map(|pid| pid.to_string()).unwrap_or_default(),
+                name: name.clone(),
+                state: "running".to_string(),
+                port: 0,
+                labels: vm.labels.clone(),
+            })
+            .collect())
+    }
+
+    async fn runtime_info(&self) -> Result<RuntimeInfo> {
+        // Every microVM Orbit tracks is running
+        let running = self.vms.read().await.len() as u64;
+        let images = std::fs::read_dir(&self.config.rootfs_dir)
+            .map(|entries| entries.count() as u64)
+            .ok();
+
+        Ok(RuntimeInfo {
+            name: "firecracker".to_string(),
+            version: binary_version(&self.config.binary).await,
+            containers: running,
+            containers_running: running,
+            images,
+            data_root: Some(self.config.state_dir.display().to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+async fn run_ip(args: &[&str]) -> Result<()> {
+    let output = Command::new("ip").args(args).output().await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ip {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+async fn wait_for_socket(socket: &Path) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + API_SOCKET_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if UnixStream::connect(socket).await.is_ok() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    Err(anyhow!(
+        "Firecracker API socket {} did not come up",
+        socket.display()
+    ))
+}
+
+// Minimal HTTP/1.1 client for the Firecracker API, which only needs PUT with JSON bodies
+async fn api_put(socket: &Path, path: &str, body: serde_json::Value) -> Result<()> {
+    let body = body.to_string();
+    let request = format!(
+        "PUT {} HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        body.len(),
+        body
+    );
+
+    let mut stream = UnixStream::connect(socket).await?;
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+        if response_complete(&response) {
+            break;
+        }
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Malformed response from firecracker API"))?;
+
+    if !(200..300).contains(&status) {
+        let detail = response.split("\r\n\r\n").nth(1).unwrap_or_default();
+        return Err(anyhow!(
+            "Firecracker API PUT {} failed ({}): {}",
+            path,
+            status,
+            detail
+        ));
+    }
+
+    Ok(())
+}
+
+fn response_complete(response: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(response);
+    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+        return false;
+    };
+
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    body.len() >= content_length
+}
+
+// Sample the VMM process from /proc and the tap device counters from sysfs
+async fn sample_vm_stats(pid: u32, tap: &str, memory_limit: u64) -> Result<StatsSample> {
+    let clock_ticks = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    };
+    let ticks_to_ns = |ticks: u64| ticks * (1_000_000_000 / clock_ticks);
+
+    // utime and stime are fields 14 and 15; skip past the parenthesised comm first
+    let stat = tokio::fs::read_to_string(format!("/proc/{}/stat", pid)).await?;
+    let fields: Vec<&str> = stat
+        .rsplit_once(')')
+        .map(|(_, rest)| rest.split_whitespace().collect())
+        .unwrap_or_default();
+    let process_ticks: u64 = fields
+        .get(11..13)
+        .map(|times| times.iter().filter_map(|t| t.parse::<u64>().ok()).sum())
+        .unwrap_or(0);
+
+    let proc_stat = tokio::fs::read_to_string("/proc/stat").await?;
+    let system_ticks: u64 = proc_stat
+        .lines()
+        .next()
+        .map(|line| {
+            line.split_whitespace()
+                .skip(1)
+                .filter_map(|t| t.parse::<u64>().ok())
+                .sum()
+        })
+        .unwrap_or(0);
+
+    let status = tokio::fs::read_to_string(format!("/proc/{}/status", pid)).await?;
+    let memory_usage = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.split_whitespace().next())
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .unwrap_or(0)
+        * 1024;
+
+    // The tap sees traffic from the host side, so its rx is the guest's tx
+    let statistics = Path::new("/sys/class/net").join(tap).join("statistics");
+    let read_counter = |file: &str| {
+        let path = statistics.join(file);
+        async move {
+            tokio::fs::read_to_string(path)
+                .await
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        }
+    };
+    let network_bytes = match (
+        read_counter("tx_bytes").await,
+        read_counter("rx_bytes").await,
+    ) {
+        (Some(rx), Some(tx)) => Some((rx, tx)),
+        _ => None,
+    };
+
+    Ok(StatsSample {
+        id: pid.to_string(),
+        cpu_total_usage: ticks_to_ns(process_ticks),
+        system_cpu_usage: ticks_to_ns(system_ticks),
+        online_cpus: std::thread::available_parallelism()
+            .map(|n| n.get() as u64)
+            .unwrap_or(1),
+        memory_usage,
+        memory_limit,
+        network_bytes,
+        disk_bytes: None,
+        gpu: None,
+    })
+}
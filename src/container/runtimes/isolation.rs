@@ -0,0 +1,289 @@
+// src/container/runtimes/isolation.rs
+//
+// Wraps a runtime so each service gets its own bounded slice of runtime
+// capacity. A service stuck in a start/fail loop then queues behind its own
+// permits instead of saturating the daemon for everyone else.
+use crate::config::{get_config_by_service, parse_container_name, ServiceConfig};
+use crate::container::{
+    Container, ContainerInfo, ContainerPortMetadata, ContainerRuntime, ContainerStats, ImageLayers,
+    RuntimeInfo,
+};
+use crate::metrics::{SERVICE_RUNTIME_IN_FLIGHT, SERVICE_RUNTIME_QUEUE_DEPTH};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use prometheus::{IntGauge, IntGaugeVec};
+use rustc_hash::FxHashMap;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{RwLock, Semaphore};
+use uuid::Uuid;
+
+pub const DEFAULT_RUNTIME_CONCURRENCY: usize = 4;
+
+#[derive(Debug)]
+pub struct IsolatedRuntime {
+    inner: Arc<dyn ContainerRuntime>,
+    // service name -> (configured limit, permits)
+    limits: RwLock<FxHashMap<String, (usize, Arc<Semaphore>)>>,
+}
+
+impl IsolatedRuntime {
+    pub fn new(inner: Arc<dyn ContainerRuntime>) -> Self {
+        Self {
+            inner,
+            limits: RwLock::new(FxHashMap::default()),
+        }
+    }
+
+    async fn semaphore(&self, service_name: &str) -> Arc<Semaphore> {
+        let limit = get_config_by_service(service_name)
+            .await
+            .and_then(|config| config.runtime_concurrency)
+            .unwrap_or(DEFAULT_RUNTIME_CONCURRENCY)
+            .max(1);
+
+        if let Some((current, semaphore)) = self.limits.read().await.get(service_name) {
+            if *current == limit {
+                return semaphore.clone();
+            }
+        }
+
+        // New service or changed limit; operations holding old permits finish normally
+        let semaphore = Arc::new(Semaphore::new(limit));
+        self.limits
+            .write()
+            .await
+            .insert(service_name.to_string(), (limit, semaphore.clone()));
+        semaphore
+    }
+
+    async fn run<T, F>(&self, service_name: &str, operation: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let semaphore = self.semaphore(service_name).await;
+
+        let queued = GaugeGuard::inc(&SERVICE_RUNTIME_QUEUE_DEPTH, service_name);
+        let permit = semaphore.acquire_owned().await;
+        drop(queued);
+
+        let _in_flight = GaugeGuard::inc(&SERVICE_RUNTIME_IN_FLIGHT, service_name);
+        let result = operation.await;
+        drop(permit);
+        result
+    }
+
+    // Container names follow service__pod__container__uuid; anything else is not throttled
+    async fn run_for_container<T, F>(&self, container_name: &str, operation: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        match parse_container_name(container_name) {
+            Ok(parts) => self.run(&parts.service_name, operation).await,
+            Err(_) => operation.await,
+        }
+    }
+}
+
+/// A service's gauge incremented for as long as the guard lives, so an
+/// operation that is cancelled while queued or running still gives it back
+struct GaugeGuard(Option<IntGauge>);
+
+impl GaugeGuard {
+    fn inc(gauge: &OnceLock<IntGaugeVec>, service_name: &str) -> Self {
+        let gauge = gauge
+            .get()
+            .map(|gauge| gauge.with_label_values(&[service_name]));
+        if let Some(gauge) = &gauge {
+            gauge.inc();
+        }
+        GaugeGuard(gauge)
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        if let Some(gauge) = &self.0 {
+            gauge.dec();
+        }
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for IsolatedRuntime {
+    async fn check_image_updates(
+        &self,
+        service_name: &str,
+        containers: &[Container],
+        current_hashes: &HashMap<String, String>,
+    ) -> Result<HashMap<String, bool>> {
+        self.run(
+            service_name,
+            self.inner
+                .check_image_updates(service_name, containers, current_hashes),
+        )
+        .await
+    }
+
+    async fn get_image_digest(&self, image: &str) -> Result<String> {
+        self.inner.get_image_digest(image).await
+    }
+
+    async fn pull_image(
+        &self,
+        service_name: &str,
+        containers: &[Container],
+        service_config: &ServiceConfig,
+    ) -> Result<()> {
+        self.run(
+            service_name,
+            self.inner
+                .pull_image(service_name, containers, service_config),
+        )
+        .await
+    }
+
+    async fn remove_pod_network(&self, network_name: &str, service_name: &str) -> Result<()> {
+        self.run(
+            service_name,
+            self.inner.remove_pod_network(network_name, service_name),
+        )
+        .await
+    }
+
+    async fn create_pod_network(&self, service_name: &str, uuid: &str) -> Result<String> {
+        self.run(
+            service_name,
+            self.inner.create_pod_network(service_name, uuid),
+        )
+        .await
+    }
+
+    async fn start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.run(
+            service_name,
+            self.inner
+                .start_containers(service_name, pod_number, containers, service_config),
+        )
+        .await
+    }
+
+    async fn stop_container(&self, name: &str) -> Result<()> {
+        self.run_for_container(name, self.inner.stop_container(name))
+            .await
+    }
+
+    // Waiting holds no runtime slot
+    async fn wait_container(&self, name: &str) -> Result<i64> {
+        self.inner.wait_container(name).await
+    }
+
+    async fn restart_container(
+        &self,
+        name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+    ) -> Result<()> {
+        self.run_for_container(
+            name,
+            self.inner
+                .restart_container(name, container, service_config),
+        )
+        .await
+    }
+
+    async fn exec_container(&self, name: &str, command: &[String]) -> Result<i64> {
+        self.run_for_container(name, self.inner.exec_container(name, command))
+            .await
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerStats> {
+        self.run_for_container(name, self.inner.inspect_container(name))
+            .await
+    }
+
+    async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>> {
+        self.inner.list_containers(service_name).await
+    }
+
+    async fn list_pod_networks(&self, service_name: &str) -> Result<Vec<(String, Uuid)>> {
+        self.inner.list_pod_networks(service_name).await
+    }
+
+    async fn runtime_info(&self) -> Result<RuntimeInfo> {
+        self.inner.runtime_info().await
+    }
+
+    async fn image_layers(&self, image: &str) -> Result<Option<ImageLayers>> {
+        self.inner.image_layers(image).await
+    }
+
+    async fn prefetch_image(
+        &self,
+        image: &str,
+        service_config: Option<&ServiceConfig>,
+    ) -> Result<bool> {
+        self.inner.prefetch_image(image, service_config).await
+    }
+
+    async fn pause_container(&self, name: &str) -> Result<()> {
+        self.run_for_container(name, self.inner.pause_container(name))
+            .await
+    }
+
+    async fn unpause_container(&self, name: &str) -> Result<()> {
+        self.run_for_container(name, self.inner.unpause_container(name))
+            .await
+    }
+
+    async fn image_changed_upstream(
+        &self,
+        image: &str,
+        service_config: &ServiceConfig,
+    ) -> Result<bool> {
+        self.inner
+            .image_changed_upstream(image, service_config)
+            .await
+    }
+
+    async fn update_resources(
+        &self,
+        name: &str,
+        memory: Option<u64>,
+        nano_cpus: Option<u64>,
+    ) -> Result<()> {
+        self.run_for_container(name, self.inner.update_resources(name, memory, nano_cpus))
+            .await
+    }
+
+    fn validate_service(&self, service_config: &ServiceConfig) -> Result<()> {
+        self.inner.validate_service(service_config)
+    }
+
+    async fn attempt_start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.run(
+            service_name,
+            self.inner.attempt_start_containers(
+                service_name,
+                pod_number,
+                containers,
+                service_config,
+            ),
+        )
+        .await
+    }
+}
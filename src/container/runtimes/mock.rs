@@ -0,0 +1,454 @@
+// src/container/runtimes/mock.rs
+//
+// An in-memory runtime for tests. Containers are entries in a map, so pods
+// start instantly and never touch Docker. Behaviour is scripted per service
+// (slow or failing starts, fake stats, image digests, exec exit codes), and
+// every call is recorded so tests can assert on what Orbit asked for.
+use crate::config::{parse_container_name, ServiceConfig};
+use crate::container::{
+    Container, ContainerInfo, ContainerPortMetadata, ContainerRuntime, ContainerStats, RuntimeInfo,
+    ServiceStats, SERVICE_STATS,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rustc_hash::FxHashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{watch, RwLock};
+use uuid::Uuid;
+
+// Containers get addresses from 10.88.0.0/16, like a pod network would hand out
+const BASE_ADDRESS: u32 = u32::from_be_bytes([10, 88, 0, 2]);
+const DEFAULT_DIGEST: &str = "sha256:mock";
+
+/// Scripted behaviour of the mock runtime for one service
+#[derive(Debug, Clone, Default)]
+pub struct MockBehavior {
+    /// How long starting a pod takes
+    pub start_delay: Duration,
+    /// Fail this many of the next pod starts
+    pub failing_starts: u32,
+    /// Pods with a container running one of these images fail to start
+    pub failing_images: HashSet<String>,
+    /// Digest reported per image, "sha256:mock" for any other image
+    pub image_digests: HashMap<String, String>,
+    /// Exit code of commands run with `exec_container`
+    pub exec_exit_code: i64,
+    /// Stats reported for every container of the service
+    pub stats: MockStats,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockStats {
+    pub cpu_percentage: f64,
+    pub cpu_percentage_relative: f64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+}
+
+/// A runtime call, as recorded by the mock
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCall {
+    PullImage {
+        service_name: String,
+    },
+    StartContainers {
+        service_name: String,
+        pod_number: u8,
+    },
+    StopContainer {
+        name: String,
+    },
+    RestartContainer {
+        name: String,
+    },
+    ExecContainer {
+        name: String,
+        command: Vec<String>,
+    },
+}
+
+#[derive(Debug)]
+struct MockContainer {
+    ip_address: String,
+    // None while running, the exit code once it has exited
+    exit: watch::Sender<Option<i64>>,
+    labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+pub struct MockRuntime {
+    behaviors: Mutex<FxHashMap<String, MockBehavior>>,
+    containers: RwLock<FxHashMap<String, MockContainer>>,
+    calls: Mutex<Vec<MockCall>>,
+    next_address: AtomicU32,
+}
+
+impl MockRuntime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Behaviour of a service, the default one until it is set
+    pub fn behavior(&self, service_name: &str) -> MockBehavior {
+        self.behaviors
+            .lock()
+            .unwrap()
+            .get(service_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_behavior(&self, service_name: &str, behavior: MockBehavior) {
+        self.behaviors
+            .lock()
+            .unwrap()
+            .insert(service_name.to_string(), behavior);
+    }
+
+    /// Change part of a service's behaviour
+    pub fn update_behavior(&self, service_name: &str, update: impl FnOnce(&mut MockBehavior)) {
+        let mut behaviors = self.behaviors.lock().unwrap();
+        update(behaviors.entry(service_name.to_string()).or_default());
+    }
+
+    /// Calls made for a service's containers, oldest first
+    pub fn calls(&self, service_name: &str) -> Vec<MockCall> {
+        let prefix = format!("{}__", service_name);
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| match call {
+                MockCall::PullImage { service_name: name }
+                | MockCall::StartContainers {
+                    service_name: name, ..
+                } => name == service_name,
+                MockCall::StopContainer { name }
+                | MockCall::RestartContainer { name }
+                | MockCall::ExecContainer { name, .. } => name.starts_with(&prefix),
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Names of a service's containers that are running
+    pub async fn running_containers(&self, service_name: &str) -> Vec<String> {
+        let prefix = format!("{}__", service_name);
+        let mut names: Vec<String> = self
+            .containers
+            .read()
+            .await
+            .iter()
+            .filter(|(name, container)| {
+                name.starts_with(&prefix) && container.exit.borrow().is_none()
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Make a running container exit, as if its process had ended
+    pub async fn exit_container(&self, name: &str, exit_code: i64) -> Result<()> {
+        let containers = self.containers.read().await;
+        let container = containers
+            .get(name)
+            .ok_or_else(|| anyhow!("No such container: {}", name))?;
+        container.exit.send_replace(Some(exit_code));
+        Ok(())
+    }
+
+    fn record(&self, call: MockCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    fn next_ip_address(&self) -> String {
+        let offset = self.next_address.fetch_add(1, Ordering::Relaxed);
+        Ipv4Addr::from(BASE_ADDRESS + offset).to_string()
+    }
+
+    fn digest(behavior: &MockBehavior, image: &str) -> String {
+        behavior
+            .image_digests
+            .get(image)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_DIGEST.to_string())
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for MockRuntime {
+    async fn check_image_updates(
+        &self,
+        service_name: &str,
+        containers: &[Container],
+        current_hashes: &HashMap<String, String>,
+    ) -> Result<HashMap<String, bool>> {
+        let behavior = self.behavior(service_name);
+        Ok(containers
+            .iter()
+            .map(|container| {
+                let digest = Self::digest(&behavior, &container.image);
+                (
+                    container.name.clone(),
+                    current_hashes.get(&container.name) != Some(&digest),
+                )
+            })
+            .collect())
+    }
+
+    async fn get_image_digest(&self, image: &str) -> Result<String> {
+        // Digests are scripted per service, so look the image up in each of them
+        let behaviors = self.behaviors.lock().unwrap();
+        Ok(behaviors
+            .values()
+            .find_map(|behavior| behavior.image_digests.get(image).cloned())
+            .unwrap_or_else(|| DEFAULT_DIGEST.to_string()))
+    }
+
+    async fn pull_image(
+        &self,
+        service_name: &str,
+        _containers: &[Container],
+        _service_config: &ServiceConfig,
+    ) -> Result<()> {
+        self.record(MockCall::PullImage {
+            service_name: service_name.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn remove_pod_network(&self, _network_name: &str, _service_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn create_pod_network(&self, service_name: &str, uuid: &str) -> Result<String> {
+        Ok(format!("{}__{}", service_name, uuid))
+    }
+
+    async fn start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.attempt_start_containers(service_name, pod_number, containers, service_config)
+            .await
+    }
+
+    async fn stop_container(&self, name: &str) -> Result<()> {
+        self.record(MockCall::StopContainer {
+            name: name.to_string(),
+        });
+        let container = self
+            .containers
+            .write()
+            .await
+            .remove(name)
+            .ok_or_else(|| anyhow!("No such container: {}", name))?;
+        container.exit.send_replace(Some(0));
+        Ok(())
+    }
+
+    async fn wait_container(&self, name: &str) -> Result<i64> {
+        let mut exit = self
+            .containers
+            .read()
+            .await
+            .get(name)
+            .map(|container| container.exit.subscribe())
+            .ok_or_else(|| anyhow!("No such container: {}", name))?;
+
+        let exit_code = exit
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| anyhow!("Container {} was removed", name))?;
+        Ok(exit_code.unwrap_or_default())
+    }
+
+    async fn restart_container(
+        &self,
+        name: &str,
+        _container: &Container,
+        _service_config: &ServiceConfig,
+    ) -> Result<()> {
+        self.record(MockCall::RestartContainer {
+            name: name.to_string(),
+        });
+        let containers = self.containers.read().await;
+        let container = containers
+            .get(name)
+            .ok_or_else(|| anyhow!("No such container: {}", name))?;
+        container.exit.send_replace(None);
+        Ok(())
+    }
+
+    async fn exec_container(&self, name: &str, command: &[String]) -> Result<i64> {
+        self.record(MockCall::ExecContainer {
+            name: name.to_string(),
+            command: command.to_vec(),
+        });
+        if !self.containers.read().await.contains_key(name) {
+            return Err(anyhow!("No such container: {}", name));
+        }
+        let service_name = parse_container_name(name)?.service_name;
+        Ok(self.behavior(&service_name).exec_exit_code)
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerStats> {
+        let ip_address = self
+            .containers
+            .read()
+            .await
+            .get(name)
+            .filter(|container| container.exit.borrow().is_none())
+            .map(|container| container.ip_address.clone())
+            .ok_or_else(|| anyhow!("No such container: {}", name))?;
+
+        let service_name = parse_container_name(name)?.service_name;
+        let stats = self.behavior(&service_name).stats;
+        let container_stats = ContainerStats {
+            id: name.to_string(),
+            ip_address,
+            cpu_percentage: stats.cpu_percentage,
+            cpu_percentage_relative: stats.cpu_percentage_relative,
+            memory_usage: stats.memory_usage,
+            memory_limit: stats.memory_limit,
+            cpu_limit: 0,
+            port_mappings: HashMap::new(),
+            network_rx_bytes: 0,
+            network_tx_bytes: 0,
+            network_rx_rate: 0.0,
+            network_tx_rate: 0.0,
+            disk_read_bytes: 0,
+            disk_write_bytes: 0,
+            disk_read_rate: 0.0,
+            disk_write_rate: 0.0,
+            gpu_percentage: None,
+            gpu_memory_usage: 0,
+            gpu_memory_limit: 0,
+            native_health: None,
+            timestamp: SystemTime::now(),
+        };
+
+        // Keep the status API and scaler views in step, as the real runtimes do
+        if let Some(service_stats) = SERVICE_STATS.get() {
+            service_stats
+                .write()
+                .await
+                .entry(service_name)
+                .or_insert_with(ServiceStats::new)
+                .update_stats(name, container_stats.clone());
+        }
+
+        Ok(container_stats)
+    }
+
+    async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>> {
+        let prefix = service_name.map(|name| format!("{}__", name));
+        let containers = self.containers.read().await;
+
+        Ok(containers
+            .iter()
+            .filter(|(name, _)| prefix.as_ref().is_none_or(|p| name.starts_with(p)))
+            .map(|(name, container)| ContainerInfo {
+                id: name.clone(),
+                name: name.clone(),
+                state: match *container.exit.borrow() {
+                    None => "running".to_string(),
+                    Some(_) => "exited".to_string(),
+                },
+                port: 0,
+                labels: container.labels.clone(),
+            })
+            .collect())
+    }
+
+    async fn runtime_info(&self) -> Result<RuntimeInfo> {
+        let containers = self.containers.read().await;
+        Ok(RuntimeInfo {
+            name: "mock".to_string(),
+            containers: containers.len() as u64,
+            containers_running: containers
+                .values()
+                .filter(|container| container.exit.borrow().is_none())
+                .count() as u64,
+            ..Default::default()
+        })
+    }
+
+    async fn attempt_start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        _service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.record(MockCall::StartContainers {
+            service_name: service_name.to_string(),
+            pod_number,
+        });
+
+        let behavior = {
+            let mut behaviors = self.behaviors.lock().unwrap();
+            let behavior = behaviors.entry(service_name.to_string()).or_default();
+            let snapshot = behavior.clone();
+            behavior.failing_starts = behavior.failing_starts.saturating_sub(1);
+            snapshot
+        };
+        if !behavior.start_delay.is_zero() {
+            tokio::time::sleep(behavior.start_delay).await;
+        }
+        if behavior.failing_starts > 0 {
+            return Err(anyhow!("Failed to create one or more containers in pod"));
+        }
+        if let Some(container) = containers
+            .iter()
+            .find(|c| behavior.failing_images.contains(&c.image))
+        {
+            return Err(anyhow!(
+                "Failed to start container {}: image {} failed",
+                container.name,
+                container.image
+            ));
+        }
+
+        let uuid = Uuid::new_v4().to_string();
+        let mut started = Vec::new();
+        let mut store = self.containers.write().await;
+        for container in containers {
+            let container_name =
+                container.generate_runtime_name(service_name, pod_number, &uuid)?;
+            let ip_address = self.next_ip_address();
+            store.insert(
+                container_name.clone(),
+                MockContainer {
+                    ip_address: ip_address.clone(),
+                    exit: watch::channel(None).0,
+                    labels: container.labels.clone().unwrap_or_default(),
+                },
+            );
+
+            let ports = container
+                .ports
+                .iter()
+                .flatten()
+                .map(|port| ContainerPortMetadata {
+                    port: port.port,
+                    target_port: port.target_port,
+                    node_port: port.node_port,
+                    host_port: None,
+                })
+                .collect();
+            started.push((container_name, ip_address, ports));
+        }
+
+        Ok(started)
+    }
+}
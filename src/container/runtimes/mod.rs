@@ -1,13 +1,42 @@
 // src/container/runtimes/mod.rs
 pub mod docker;
+pub mod firecracker;
+pub mod isolation;
+#[cfg(any(test, feature = "mock-runtime"))]
+pub mod mock;
+pub mod nspawn;
+pub mod resilience;
 
 use rustc_hash::FxHashMap;
 use std::{
     collections::HashSet,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::ExitStatus,
     sync::{Arc, OnceLock},
 };
-use tokio::sync::RwLock;
+use tokio::{process::Command, sync::RwLock};
 
 // Track which services are using each network
 pub static NETWORK_USAGE: OnceLock<Arc<RwLock<FxHashMap<String, HashSet<String>>>>> =
     OnceLock::new();
+
+// Shell convention: processes killed by a signal exit with 128 + the signal number
+fn exit_code(status: ExitStatus) -> i64 {
+    match status.code() {
+        Some(code) => code as i64,
+        None => 128 + status.signal().unwrap_or_default() as i64,
+    }
+}
+
+// First line of `<binary> --version`, None if it cannot be run
+async fn binary_version(binary: &Path) -> Option<String> {
+    let output = Command::new(binary).arg("--version").output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
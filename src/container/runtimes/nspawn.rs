@@ -0,0 +1,924 @@
+// src/container/runtimes/nspawn.rs
+//
+// Runs each container as a systemd-nspawn machine, for long-lived system
+// services that want a full OS userland. Machines share the host network, so
+// ports are reached on the loopback address like rootless Docker containers,
+// and only one pod of a service with ports can run at a time. Machines left
+// running by a previous daemon are terminated before the first one starts.
+use crate::config::daemon::NspawnRuntimeConfig;
+use crate::config::{
+    get_config_by_service, parse_container_name, parse_cpu_limit, parse_memory_limit, parse_tmpfs,
+    parse_ulimit, ServiceConfig,
+};
+use crate::container::canary::UpdateStrategy;
+use crate::container::crash_dumps;
+use crate::container::dependencies;
+use crate::container::dns::dns_for;
+use crate::container::secrets::{remove_secret_files, resolve_secrets, write_secret_files};
+use crate::container::{
+    logging_for, parse_disk_rate, record_container_stats, stop_settings, Container, ContainerInfo,
+    ContainerPortMetadata, ContainerRuntime, ContainerStats, RuntimeInfo, StatsSample,
+    LOOPBACK_ADDRESS,
+};
+
+use super::{binary_version, exit_code};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rustc_hash::FxHashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::process::{Child, Command};
+use tokio::sync::{OnceCell, RwLock};
+use uuid::Uuid;
+
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(30);
+const EXIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MACHINE_PREFIX: &str = "orbit-";
+
+#[derive(Debug)]
+struct Machine {
+    child: Child,
+    // Machine names must be valid hostnames, which runtime names are not
+    machine: String,
+    unit: String,
+    log_path: PathBuf,
+    ports: Vec<u16>,
+    memory_limit: u64,
+    nano_cpus: Option<u64>,
+    labels: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct NspawnRuntime {
+    config: NspawnRuntimeConfig,
+    machines: Arc<RwLock<FxHashMap<String, Machine>>>,
+    reclaimed: OnceCell<()>,
+}
+
+impl NspawnRuntime {
+    pub fn new(config: NspawnRuntimeConfig) -> Result<Self> {
+        if !config.images_dir.is_dir() {
+            return Err(anyhow!(
+                "nspawn images directory not found: {}",
+                config.images_dir.display()
+            ));
+        }
+
+        Ok(Self {
+            config,
+            machines: Arc::new(RwLock::new(FxHashMap::default())),
+            reclaimed: OnceCell::new(),
+        })
+    }
+
+    // An explicit path is used as-is, otherwise "debian:12" resolves to
+    // "<images_dir>/debian_12" (a directory tree) or "<images_dir>/debian_12.raw"
+    fn resolve_image(&self, image: &str) -> PathBuf {
+        let path = Path::new(image);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+
+        let file_name: String = image
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        let tree = self.config.images_dir.join(&file_name);
+        if tree.is_dir() {
+            tree
+        } else {
+            self.config.images_dir.join(format!("{}.raw", file_name))
+        }
+    }
+
+    fn machine_name(container_name: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        container_name.hash(&mut hasher);
+        format!("{}{:016x}", MACHINE_PREFIX, hasher.finish())
+    }
+
+    // Machines of a previous daemon can't be adopted, as their processes
+    // aren't ours to wait on, and would hold the ports new machines need
+    async fn reclaim_leftover_machines(&self) {
+        let output = match Command::new("machinectl")
+            .args(["list", "--no-legend", "--no-pager"])
+            .output()
+            .await
+        {
+            Ok(output) if output.status.success() => output,
+            _ => return,
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let leftovers = stdout
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|machine| machine.starts_with(MACHINE_PREFIX));
+        for machine in leftovers {
+            let _ = Command::new("machinectl")
+                .args(["terminate", machine])
+                .status()
+                .await;
+            for suffix in ["log", "resolv.conf", "hosts"] {
+                let path = self
+                    .config
+                    .state_dir
+                    .join(format!("{}.{}", machine, suffix));
+                let _ = tokio::fs::remove_file(path).await;
+            }
+            slog::warn!(slog_scope::logger(), "Terminated machine left by a previous run";
+                "machine" => machine
+            );
+        }
+    }
+
+    fn warn_unsupported(
+        service_name: &str,
+        container_name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+    ) {
+        let unsupported = [
+            ("volume_mounts", container.volume_mounts.is_some()),
+            ("network_limit", container.network_limit.is_some()),
+            ("security_opt", container.security_opt.is_some()),
+            ("shm_size", container.shm_size.is_some()),
+            ("sysctls", container.sysctls.is_some()),
+            ("stop_signal", container.stop_signal.is_some()),
+            ("logging", logging_for(service_config, container).is_some()),
+            ("devices", container.devices.is_some()),
+            ("gpus", container.gpus.is_some()),
+            ("userns_mode", container.userns_mode.is_some()),
+            // A booted machine starts its init as root in /
+            (
+                "user",
+                container.user.is_some() && container.argv().is_none(),
+            ),
+            (
+                "working_dir",
+                container.working_dir.is_some() && container.argv().is_none(),
+            ),
+            (
+                "target_port",
+                container
+                    .ports
+                    .iter()
+                    .flatten()
+                    .any(|p| p.target_port.is_some()),
+            ),
+        ];
+
+        for (field, _) in unsupported.iter().filter(|(_, set)| *set) {
+            slog::warn!(slog_scope::logger(), "Ignoring option not supported by nspawn runtime";
+                "service" => service_name,
+                "container" => container_name,
+                "option" => *field
+            );
+        }
+    }
+
+    fn nspawn_args(
+        &self,
+        machine: &str,
+        image: &Path,
+        container: &Container,
+        memory_limit: Option<u64>,
+        nano_cpus: Option<u64>,
+        environment: Vec<(String, String)>,
+    ) -> Result<Vec<String>> {
+        let mut args = vec![
+            "--quiet".to_string(),
+            "--ephemeral".to_string(),
+            format!("--machine={}", machine),
+            // IP and IO accounting provide the network and disk counters used for autoscaling
+            "--property=IPAccounting=yes".to_string(),
+            "--property=IOAccounting=yes".to_string(),
+        ];
+
+        if image.is_dir() {
+            args.push(format!("--directory={}", image.display()));
+        } else {
+            args.push(format!("--image={}", image.display()));
+        }
+
+        if let Some(bytes) = memory_limit {
+            args.push(format!("--property=MemoryMax={}", bytes));
+        }
+        if let Some(nano_cpus) = nano_cpus {
+            // One CPU is 100% of CPUQuota
+            args.push(format!("--property=CPUQuota={}%", nano_cpus / 10_000_000));
+        }
+        if container.privileged == Some(true) {
+            args.push("--capability=all".to_string());
+        }
+        for capability in container.cap_add.iter().flatten() {
+            args.push(format!("--capability={}", capability_name(capability)));
+        }
+        for capability in container.cap_drop.iter().flatten() {
+            args.push(format!("--drop-capability={}", capability_name(capability)));
+        }
+        for (name, value) in container.ulimits.iter().flatten() {
+            let (soft, hard) = parse_ulimit(name, value)?;
+            args.push(format!(
+                "--rlimit=RLIMIT_{}={}:{}",
+                name.to_ascii_uppercase(),
+                rlimit_value(soft),
+                rlimit_value(hard)
+            ));
+        }
+        if let Some(pids_limit) = container.pids_limit {
+            args.push(format!("--property=TasksMax={}", rlimit_value(pids_limit)));
+        }
+        if let Some(blkio_limit) = &container.blkio_limit {
+            let device = &blkio_limit.device;
+            if let Some(rate) = &blkio_limit.read_bps {
+                args.push(format!(
+                    "--property=IOReadBandwidthMax={} {}",
+                    device,
+                    parse_disk_rate(rate)?
+                ));
+            }
+            if let Some(rate) = &blkio_limit.write_bps {
+                args.push(format!(
+                    "--property=IOWriteBandwidthMax={} {}",
+                    device,
+                    parse_disk_rate(rate)?
+                ));
+            }
+            if let Some(iops) = blkio_limit.read_iops {
+                args.push(format!("--property=IOReadIOPSMax={} {}", device, iops));
+            }
+            if let Some(iops) = blkio_limit.write_iops {
+                args.push(format!("--property=IOWriteIOPSMax={} {}", device, iops));
+            }
+        }
+
+        if let Some(time_zone) = &container.time_zone {
+            if time_zone.host {
+                args.push("--timezone=bind".to_string());
+            }
+            if let Some(tz) = &time_zone.tz {
+                args.push(format!("--setenv=TZ={}", tz));
+            }
+        }
+        if let Some(locale) = &container.locale {
+            args.push(format!("--setenv=LANG={}", locale));
+            args.push(format!("--setenv=LC_ALL={}", locale));
+        }
+        for (key, value) in environment {
+            args.push(format!("--setenv={}={}", key, value));
+        }
+
+        if let Some(hostname) = &container.hostname {
+            args.push(format!("--hostname={}", hostname));
+        }
+        if container.read_only_root_fs == Some(true) {
+            args.push("--read-only".to_string());
+        }
+        for entry in container.tmpfs.iter().flatten() {
+            let (path, options) = parse_tmpfs(entry)?;
+            if options.is_empty() {
+                args.push(format!("--tmpfs={}", path));
+            } else {
+                args.push(format!("--tmpfs={}:{}", path, options));
+            }
+        }
+
+        // Without a command the image's own init is booted, as root
+        match container.argv() {
+            Some(argv) if !argv.is_empty() => {
+                if let Some(user) = &container.user {
+                    args.push(format!("--user={}", user));
+                }
+                if let Some(working_dir) = &container.working_dir {
+                    args.push(format!("--chdir={}", working_dir));
+                }
+                args.push("--".to_string());
+                args.extend(argv);
+            }
+            _ => args.push("--boot".to_string()),
+        }
+
+        Ok(args)
+    }
+
+    async fn spawn_machine(
+        &self,
+        container_name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+    ) -> Result<Machine> {
+        let image = self.resolve_image(&container.image);
+        if !image.exists() {
+            return Err(anyhow!(
+                "OS image {} not found at {}",
+                container.image,
+                image.display()
+            ));
+        }
+
+        let memory_limit = container
+            .memory_limit_value()
+            .or(service_config.memory_limit.as_ref())
+            .map(parse_memory_limit)
+            .transpose()?;
+        let nano_cpus = container
+            .cpu_limit_value()
+            .or(service_config.cpu_limit.as_ref())
+            .map(parse_cpu_limit)
+            .transpose()?;
+
+        let mut environment = container.environment(&service_config.name).await?;
+        let pod = parse_container_name(container_name)?.uuid;
+        let secrets = resolve_secrets(service_config, container, pod).await?;
+        for secret in &secrets {
+            if let Some(name) = &secret.env {
+                environment.push((name.clone(), secret.value.expose().to_string()));
+            }
+        }
+        let mut binds: Vec<String> = write_secret_files(container_name, &secrets)?
+            .into_iter()
+            .map(|(source, target)| format!("--bind-ro={}:{}", source.display(), target.display()))
+            .collect();
+        if let Some((source, target)) = crash_dumps::prepare_dump_dir(container, container_name)? {
+            binds.push(format!("--bind={}:{}", source.display(), target.display()));
+        }
+
+        let machine = Self::machine_name(container_name);
+        tokio::fs::create_dir_all(&self.config.state_dir).await?;
+
+        // Machines share the host network, so the host, and the other
+        // containers of the pod by their names, are reached on loopback
+        let mut dns = dns_for(service_config, container);
+        let containers = &service_config.spec.containers;
+        if containers.len() > 1 {
+            dns.extra_hosts.extend(
+                containers
+                    .iter()
+                    .filter(|peer| peer.name != container.name)
+                    .map(|peer| format!("{}:127.0.0.1", peer.name)),
+            );
+        }
+        let hostname = container.hostname.as_deref().unwrap_or(&machine);
+        let dns_files = [
+            ("resolv.conf", "/etc/resolv.conf", dns.resolv_conf()),
+            (
+                "hosts",
+                "/etc/hosts",
+                dns.hosts_file(hostname, "127.0.0.1")?,
+            ),
+        ];
+        for (suffix, target, contents) in dns_files {
+            if let Some(contents) = contents {
+                let path = self
+                    .config
+                    .state_dir
+                    .join(format!("{}.{}", machine, suffix));
+                tokio::fs::write(&path, contents).await?;
+                binds.push(format!("--bind-ro={}:{}", path.display(), target));
+            }
+        }
+        let log_path = self.config.state_dir.join(format!("{}.log", machine));
+        let log_file = std::fs::File::create(&log_path)?;
+
+        // Options must precede the command, so binds go first
+        let args = binds.into_iter().chain(self.nspawn_args(
+            &machine,
+            &image,
+            container,
+            memory_limit,
+            nano_cpus,
+            environment,
+        )?);
+
+        let mut child = Command::new(&self.config.binary)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stdout(log_file.try_clone()?)
+            .stderr(log_file)
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow!("Failed to spawn systemd-nspawn: {}", e))?;
+
+        let unit = match wait_for_registration(&machine, &mut child).await {
+            Ok(unit) => unit,
+            Err(e) => {
+                let _ = child.kill().await;
+                let log = tokio::fs::read_to_string(&log_path)
+                    .await
+                    .unwrap_or_default();
+                return Err(anyhow!("{}: {}", e, log.trim()));
+            }
+        };
+
+        Ok(Machine {
+            child,
+            machine,
+            unit,
+            log_path,
+            ports: container.ports.iter().flatten().map(|p| p.port).collect(),
+            memory_limit: memory_limit.unwrap_or(0),
+            nano_cpus,
+            labels: container.labels.clone().unwrap_or_default(),
+        })
+    }
+
+    async fn terminate(&self, mut machine: Machine, grace_period: Duration) {
+        // nspawn turns SIGTERM into an orderly shutdown of the machine's init
+        if let Some(pid) = machine.child.id() {
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+
+        if tokio::time::timeout(grace_period, machine.child.wait())
+            .await
+            .is_err()
+        {
+            slog::warn!(slog_scope::logger(), "Machine did not shut down in time, killing it";
+                "machine" => &machine.machine
+            );
+            let _ = machine.child.kill().await;
+        }
+
+        let _ = tokio::fs::remove_file(&machine.log_path).await;
+        for suffix in ["resolv.conf", "hosts"] {
+            let path = self
+                .config
+                .state_dir
+                .join(format!("{}.{}", machine.machine, suffix));
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for NspawnRuntime {
+    // Machines bind their ports on the host, so no two may share one: not the
+    // containers of a pod, nor two pods of the service, which rules out
+    // scaling and starting new pods next to the old ones during an update
+    fn validate_service(&self, service_config: &ServiceConfig) -> Result<()> {
+        let mut ports = HashSet::new();
+        for container in &service_config.spec.containers {
+            for port in container.ports.iter().flatten() {
+                if !ports.insert(port.port) {
+                    return Err(anyhow!(
+                        "port {} is used by more than one container, and nspawn machines share the host network",
+                        port.port
+                    ));
+                }
+            }
+        }
+        if ports.is_empty() {
+            return Ok(());
+        }
+
+        if service_config.instance_count.max > 1 {
+            return Err(anyhow!(
+                "instance_count.max must be 1 for a service with ports, as nspawn machines share the host network"
+            ));
+        }
+        let update = service_config
+            .rolling_update_config
+            .clone()
+            .unwrap_or_default();
+        if update.max_surge > 0 || update.strategy == UpdateStrategy::Canary {
+            return Err(anyhow!(
+                "rolling_update_config must set max_surge to 0 and not use the canary strategy for a service with ports, as nspawn machines share the host network"
+            ));
+        }
+        Ok(())
+    }
+
+    async fn get_image_digest(&self, image: &str) -> Result<String> {
+        // Images are plain trees or files, so size and mtime identify a build
+        let metadata = tokio::fs::metadata(self.resolve_image(image)).await?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Ok(format!("{}-{}", metadata.len(), modified))
+    }
+
+    async fn pull_image(
+        &self,
+        _service_name: &str,
+        _containers: &[Container],
+        _service_config: &ServiceConfig,
+    ) -> Result<()> {
+        // OS images are provisioned out of band (e.g. with mkosi or debootstrap)
+        Ok(())
+    }
+
+    async fn check_image_updates(
+        &self,
+        _service_name: &str,
+        containers: &[Container],
+        current_hashes: &HashMap<String, String>,
+    ) -> Result<HashMap<String, bool>> {
+        let mut updates = HashMap::new();
+
+        for container in containers {
+            let new_hash = self.get_image_digest(&container.image).await?;
+            updates.insert(
+                container.name.clone(),
+                current_hashes.get(&container.name) != Some(&new_hash),
+            );
+        }
+
+        Ok(updates)
+    }
+
+    async fn remove_pod_network(&self, _network_name: &str, _service_name: &str) -> Result<()> {
+        // Machines use the host network
+        Ok(())
+    }
+
+    async fn create_pod_network(&self, _service_name: &str, _uuid: &str) -> Result<String> {
+        Ok("host".to_string())
+    }
+
+    async fn start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.attempt_start_containers(service_name, pod_number, containers, service_config)
+            .await
+    }
+
+    async fn attempt_start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.reclaimed
+            .get_or_init(|| self.reclaim_leftover_machines())
+            .await;
+
+        let uuid = Uuid::new_v4().to_string();
+        let mut started: Vec<(String, String, Vec<ContainerPortMetadata>)> = Vec::new();
+
+        for container in dependencies::start_order(containers)? {
+            let container_name =
+                container.generate_runtime_name(service_name, pod_number, &uuid)?;
+            Self::warn_unsupported(service_name, &container_name, container, service_config);
+
+            if let Err(e) = dependencies::wait_for_dependencies(self, container, containers, |c| {
+                Ok(c.generate_runtime_name(service_name, pod_number, &uuid)?)
+            })
+            .await
+            {
+                slog::error!(slog_scope::logger(), "Failed to start machine";
+                    "service" => service_name,
+                    "container" => &container_name,
+                    "error" => e.to_string()
+                );
+                for (name, _, _) in &started {
+                    let _ = self.stop_container(name).await;
+                }
+                return Err(anyhow!("Failed to create one or more containers in pod"));
+            }
+
+            let machine = match self
+                .spawn_machine(&container_name, container, service_config)
+                .await
+            {
+                Ok(machine) => machine,
+                Err(e) => {
+                    remove_secret_files(&container_name);
+                    slog::error!(slog_scope::logger(), "Failed to start machine";
+                        "service" => service_name,
+                        "container" => &container_name,
+                        "error" => e.to_string()
+                    );
+                    for (name, _, _) in &started {
+                        let _ = self.stop_container(name).await;
+                    }
+                    return Err(anyhow!("Failed to create one or more containers in pod"));
+                }
+            };
+
+            slog::info!(slog_scope::logger(), "Started machine";
+                "service" => service_name,
+                "container" => &container_name,
+                "machine" => &machine.machine
+            );
+            self.machines
+                .write()
+                .await
+                .insert(container_name.clone(), machine);
+
+            // Host networking: every port is already published on the host
+            let ports = container
+                .ports
+                .iter()
+                .flatten()
+                .map(|port| ContainerPortMetadata {
+                    port: port.port,
+                    target_port: port.target_port,
+                    node_port: port.node_port,
+                    host_port: Some(port.port),
+                })
+                .collect();
+
+            started.push((container_name, LOOPBACK_ADDRESS.to_string(), ports));
+        }
+
+        Ok(started)
+    }
+
+    async fn stop_container(&self, name: &str) -> Result<()> {
+        let machine = self
+            .machines
+            .write()
+            .await
+            .remove(name)
+            .ok_or_else(|| anyhow!("Failed to stop container {}: no such machine", name))?;
+        let grace_period = stop_settings(name)
+            .await
+            .grace_period
+            .unwrap_or(self.config.stop_timeout);
+        self.terminate(machine, grace_period).await;
+        remove_secret_files(name);
+        crash_dumps::remove_dump_dir(name);
+        Ok(())
+    }
+
+    async fn wait_container(&self, name: &str) -> Result<i64> {
+        loop {
+            {
+                let mut machines = self.machines.write().await;
+                let machine = machines
+                    .get_mut(name)
+                    .ok_or_else(|| anyhow!("No such machine for container {}", name))?;
+                if let Some(status) = machine.child.try_wait()? {
+                    return Ok(exit_code(status));
+                }
+            }
+            tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn restart_container(
+        &self,
+        name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+    ) -> Result<()> {
+        // Ephemeral machines cannot be started twice, so boot a fresh one under the same name
+        let previous = self.machines.write().await.remove(name);
+        if let Some(machine) = previous {
+            self.terminate(machine, self.config.stop_timeout).await;
+        }
+
+        let machine = self.spawn_machine(name, container, service_config).await?;
+        self.machines
+            .write()
+            .await
+            .insert(name.to_string(), machine);
+        Ok(())
+    }
+
+    async fn exec_container(&self, name: &str, command: &[String]) -> Result<i64> {
+        let machine = self
+            .machines
+            .read()
+            .await
+            .get(name)
+            .map(|machine| machine.machine.clone())
+            .ok_or_else(|| anyhow!("No such machine for container {}", name))?;
+
+        // Enter the namespaces of the machine's leader process, which works without an init inside
+        let output = Command::new("machinectl")
+            .args(["show", &machine, "--property=Leader", "--value"])
+            .output()
+            .await?;
+        let leader = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || leader.is_empty() {
+            return Err(anyhow!("Machine {} has no leader process", machine));
+        }
+
+        let status = Command::new("nsenter")
+            .args(["--target", &leader, "--all", "--"])
+            .args(command)
+            .status()
+            .await
+            .map_err(|e| anyhow!("Failed to exec in container {}: {}", name, e))?;
+        Ok(exit_code(status))
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerStats> {
+        let (pid, unit, ports, memory_limit, nano_cpus) = {
+            let mut machines = self.machines.write().await;
+            let machine = machines
+                .get_mut(name)
+                .ok_or_else(|| anyhow!("No stats available for container {}", name))?;
+
+            if let Ok(Some(status)) = machine.child.try_wait() {
+                return Err(anyhow!("Machine {} exited: {}", machine.machine, status));
+            }
+
+            (
+                machine.child.id().unwrap_or_default(),
+                machine.unit.clone(),
+                machine.ports.clone(),
+                machine.memory_limit,
+                machine.nano_cpus,
+            )
+        };
+
+        let sample = sample_unit_stats(pid, &unit, memory_limit)
+            .await
+            .map_err(|e| anyhow!("Failed to read stats for {}: {}", name, e))?;
+
+        let service_name = parse_container_name(name)?.service_name;
+        let nano_cpus = get_config_by_service(&service_name)
+            .await
+            .and_then(|cfg| cfg.cpu_limit)
+            .and_then(|value| parse_cpu_limit(&value).ok())
+            .or(nano_cpus);
+
+        let mut container_stats =
+            record_container_stats(&service_name, name, sample, nano_cpus).await;
+        container_stats.ip_address = LOOPBACK_ADDRESS.to_string();
+        container_stats.port_mappings = ports.into_iter().map(|port| (port, port)).collect();
+
+        Ok(container_stats)
+    }
+
+    async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>> {
+        let prefix = service_name.map(|name| format!("{}__", name));
+        let machines = self.machines.read().await;
+
+        Ok(machines
+            .iter()
+            .filter(|(name, _)| prefix.as_ref().is_none_or(|p| name.starts_with(p)))
+            .map(|(name, machine)| ContainerInfo {
+                id: machine.machine.clone(),
+                name: name.clone(),
+                state: "running".to_string(),
+                port: machine.ports.first().copied().unwrap_or(0),
+                labels: machine.labels.clone(),
+            })
+            .collect())
+    }
+
+    async fn runtime_info(&self) -> Result<RuntimeInfo> {
+        // Every machine Orbit tracks is running
+        let running = self.machines.read().await.len() as u64;
+        let images = std::fs::read_dir(&self.config.images_dir)
+            .map(|entries| entries.count() as u64)
+            .ok();
+
+        Ok(RuntimeInfo {
+            name: "nspawn".to_string(),
+            version: binary_version(&self.config.binary).await,
+            containers: running,
+            containers_running: running,
+            images,
+            data_root: Some(self.config.state_dir.display().to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+// -1 is how Docker spells unlimited
+fn rlimit_value(value: i64) -> String {
+    if value < 0 {
+        "infinity".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// nspawn wants "CAP_NET_ADMIN" where Docker also accepts "NET_ADMIN"
+fn capability_name(capability: &str) -> String {
+    let capability = capability.to_ascii_uppercase();
+    if capability == "ALL" {
+        "all".to_string()
+    } else if capability.starts_with("CAP_") {
+        capability
+    } else {
+        format!("CAP_{}", capability)
+    }
+}
+
+// Wait for machined to register the machine and return its scope unit
+async fn wait_for_registration(machine: &str, child: &mut Child) -> Result<String> {
+    let deadline = tokio::time::Instant::now() + REGISTRATION_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(anyhow!("systemd-nspawn exited early ({})", status));
+        }
+
+        let output = Command::new("machinectl")
+            .args(["show", machine, "--property=Unit", "--value"])
+            .output()
+            .await?;
+        let unit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !unit.is_empty() {
+            return Ok(unit);
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    Err(anyhow!("Machine {} did not register in time", machine))
+}
+
+// Resource usage comes from the machine's scope unit, host CPU time from /proc/stat
+async fn sample_unit_stats(pid: u32, unit: &str, memory_limit: u64) -> Result<StatsSample> {
+    let output = Command::new("systemctl")
+        .args([
+            "show",
+            unit,
+            "--property=MemoryCurrent",
+            "--property=CPUUsageNSec",
+            "--property=IPIngressBytes",
+            "--property=IPEgressBytes",
+            "--property=IOReadBytes",
+            "--property=IOWriteBytes",
+        ])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "systemctl show {} failed: {}",
+            unit,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // Unset accounting values are reported as "[not set]" or u64::MAX
+    let properties: HashMap<String, u64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .filter_map(|(key, value)| {
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|v| *v != u64::MAX)
+                .map(|v| (key.to_string(), v))
+        })
+        .collect();
+
+    let clock_ticks = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    };
+    let proc_stat = tokio::fs::read_to_string("/proc/stat").await?;
+    let system_ticks: u64 = proc_stat
+        .lines()
+        .next()
+        .map(|line| {
+            line.split_whitespace()
+                .skip(1)
+                .filter_map(|t| t.parse::<u64>().ok())
+                .sum()
+        })
+        .unwrap_or(0);
+
+    let network_bytes = match (
+        properties.get("IPIngressBytes"),
+        properties.get("IPEgressBytes"),
+    ) {
+        (Some(rx), Some(tx)) => Some((*rx, *tx)),
+        _ => None,
+    };
+    let disk_bytes = match (
+        properties.get("IOReadBytes"),
+        properties.get("IOWriteBytes"),
+    ) {
+        (Some(read), Some(write)) => Some((*read, *write)),
+        _ => None,
+    };
+
+    Ok(StatsSample {
+        id: pid.to_string(),
+        cpu_total_usage: properties.get("CPUUsageNSec").copied().unwrap_or(0),
+        system_cpu_usage: system_ticks * (1_000_000_000 / clock_ticks),
+        online_cpus: std::thread::available_parallelism()
+            .map(|n| n.get() as u64)
+            .unwrap_or(1),
+        memory_usage: properties.get("MemoryCurrent").copied().unwrap_or(0),
+        memory_limit,
+        network_bytes,
+        disk_bytes,
+        gpu: None,
+    })
+}
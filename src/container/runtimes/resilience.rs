@@ -0,0 +1,844 @@
+// src/container/runtimes/resilience.rs
+//
+// Puts a deadline on every runtime call, retries read-only calls, and stops
+// calling the daemon for a while once it keeps failing, so a hung API can't
+// stall auto_scale or stop_service.
+use crate::config::daemon::RuntimePolicyConfig;
+use crate::config::{parse_container_name, ServiceConfig};
+use crate::container::{
+    stop_settings, Container, ContainerInfo, ContainerPortMetadata, ContainerRuntime,
+    ContainerStats, ImageLayers, RuntimeInfo, DEFAULT_STOP_GRACE_PERIOD,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum RuntimePolicyError {
+    #[error("Runtime call {0} timed out after {1:?}")]
+    Timeout(&'static str, Duration),
+    #[error("Runtime unavailable after repeated failures, retrying in {0:?}")]
+    CircuitOpen(Duration),
+}
+
+// Names of a service's containers and pod networks at one point in time
+struct PodResources {
+    containers: HashSet<String>,
+    networks: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    // When the single call let through after `reset_after` started
+    probe_started: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub struct ResilientRuntime {
+    inner: Arc<dyn ContainerRuntime>,
+    policy: RuntimePolicyConfig,
+    breaker: Mutex<BreakerState>,
+}
+
+impl ResilientRuntime {
+    pub fn new(inner: Arc<dyn ContainerRuntime>, policy: RuntimePolicyConfig) -> Self {
+        Self {
+            inner,
+            policy,
+            breaker: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    // For calls whose result isn't recorded: they fail fast while the
+    // circuit is open, and never act as the probe
+    fn check_circuit(&self) -> Result<()> {
+        let breaker = self.breaker.lock().unwrap();
+        match breaker.open_until {
+            Some(until) if until > Instant::now() => {
+                Err(RuntimePolicyError::CircuitOpen(until - Instant::now()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Once `reset_after` has passed, a single call is let through as a probe,
+    // and its result closes or re-opens the circuit. The others keep failing
+    // fast meanwhile. True for the probe.
+    fn admit(&self) -> Result<bool> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let Some(until) = breaker.open_until else {
+            return Ok(false);
+        };
+        let now = Instant::now();
+        if until > now {
+            return Err(RuntimePolicyError::CircuitOpen(until - now).into());
+        }
+        // A probe that never reported back, e.g. as its caller was cancelled,
+        // is replaced after another `reset_after`
+        let reset_after = self.policy.circuit_breaker.reset_after;
+        if let Some(started) = breaker.probe_started {
+            let elapsed = now.duration_since(started);
+            if elapsed < reset_after {
+                return Err(RuntimePolicyError::CircuitOpen(reset_after - elapsed).into());
+            }
+        }
+        breaker.probe_started = Some(now);
+        Ok(true)
+    }
+
+    fn record<T>(&self, operation: &'static str, result: &Result<T>, probe: bool) {
+        let mut breaker = self.breaker.lock().unwrap();
+        let reset_after = self.policy.circuit_breaker.reset_after;
+        match result {
+            Err(e) if is_daemon_failure(e) => {
+                breaker.consecutive_failures += 1;
+                if probe {
+                    breaker.open_until = Some(Instant::now() + reset_after);
+                    breaker.probe_started = None;
+                    slog::warn!(slog_scope::logger(), "Runtime still failing, circuit stays open";
+                        "operation" => operation,
+                        "reset_after" => format!("{:?}", reset_after),
+                        "error" => e.to_string()
+                    );
+                    return;
+                }
+                let threshold = self.policy.circuit_breaker.failure_threshold.max(1);
+                if breaker.consecutive_failures >= threshold && breaker.open_until.is_none() {
+                    breaker.open_until = Some(Instant::now() + reset_after);
+                    slog::error!(slog_scope::logger(), "Runtime circuit opened";
+                        "operation" => operation,
+                        "failures" => breaker.consecutive_failures,
+                        "reset_after" => format!("{:?}", reset_after),
+                        "error" => e.to_string()
+                    );
+                }
+            }
+            // Errors reported by a responsive daemon (e.g. no such container)
+            // say nothing about its health, unless it was thought to be down
+            Err(_) if !probe => {}
+            _ => {
+                if probe {
+                    slog::info!(slog_scope::logger(), "Runtime circuit closed";
+                        "operation" => operation
+                    );
+                }
+                *breaker = BreakerState::default();
+            }
+        }
+    }
+
+    async fn call<T, F>(&self, operation: &'static str, timeout: Duration, call: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let probe = self.admit()?;
+
+        let result = match tokio::time::timeout(timeout, call).await {
+            Ok(result) => result,
+            Err(_) => Err(RuntimePolicyError::Timeout(operation, timeout).into()),
+        };
+
+        self.record(operation, &result, probe);
+        result
+    }
+
+    async fn pod_resources(&self, service_name: &str) -> Result<PodResources> {
+        let containers = self.list_containers(Some(service_name)).await?;
+        let networks = self.list_pod_networks(service_name).await?;
+        Ok(PodResources {
+            containers: containers.into_iter().map(|c| c.name).collect(),
+            networks: networks.into_iter().map(|(name, _)| name).collect(),
+        })
+    }
+
+    // A start that timed out is abandoned midway. The containers named for its
+    // pod number that appeared since `before` are removed, then the new pod
+    // networks no remaining container uses.
+    async fn remove_partial_pod(&self, service_name: &str, pod_number: u8, before: &PodResources) {
+        let log = slog_scope::logger();
+        let containers = match self.list_containers(Some(service_name)).await {
+            Ok(containers) => containers,
+            Err(e) => {
+                slog::error!(log, "Failed to list containers of timed out pod start";
+                    "service" => service_name,
+                    "pod" => pod_number,
+                    "error" => e.to_string()
+                );
+                return;
+            }
+        };
+
+        let prefix = format!("{}__{}__", service_name, pod_number);
+        let mut pods_in_use = HashSet::new();
+        for container in containers {
+            if !container.name.starts_with(&prefix) || before.containers.contains(&container.name) {
+                if let Ok(parts) = parse_container_name(&container.name) {
+                    pods_in_use.insert(parts.uuid);
+                }
+                continue;
+            }
+            slog::warn!(log, "Removing container of timed out pod start";
+                "service" => service_name,
+                "container" => &container.name
+            );
+            if let Err(e) = self.stop_container(&container.name).await {
+                slog::error!(log, "Failed to remove container of timed out pod start";
+                    "service" => service_name,
+                    "container" => &container.name,
+                    "error" => e.to_string()
+                );
+            }
+        }
+
+        let networks = match self.list_pod_networks(service_name).await {
+            Ok(networks) => networks,
+            Err(e) => {
+                slog::error!(log, "Failed to list networks of timed out pod start";
+                    "service" => service_name,
+                    "pod" => pod_number,
+                    "error" => e.to_string()
+                );
+                return;
+            }
+        };
+        for (network_name, pod) in networks {
+            if before.networks.contains(&network_name) || pods_in_use.contains(&pod) {
+                continue;
+            }
+            if let Err(e) = self.remove_pod_network(&network_name, service_name).await {
+                slog::error!(log, "Failed to remove network of timed out pod start";
+                    "service" => service_name,
+                    "network" => &network_name,
+                    "error" => e.to_string()
+                );
+            }
+        }
+    }
+
+    // Read-only calls are safe to repeat
+    async fn call_with_retry<T, F, Fut>(&self, operation: &'static str, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.call(operation, self.policy.timeout, call()).await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.policy.retries && is_daemon_failure(&e) => {
+                    attempt += 1;
+                    slog::debug!(slog_scope::logger(), "Retrying runtime call";
+                        "operation" => operation,
+                        "attempt" => attempt,
+                        "error" => e.to_string()
+                    );
+                    tokio::time::sleep(self.policy.retry_backoff * attempt).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// Timeouts and transport errors count against the daemon; API errors do not
+fn is_daemon_failure(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<RuntimePolicyError>() {
+            return matches!(e, RuntimePolicyError::Timeout(..));
+        }
+        if let Some(e) = cause.downcast_ref::<bollard::errors::Error>() {
+            return !matches!(e, bollard::errors::Error::DockerResponseServerError { .. });
+        }
+        cause.is::<std::io::Error>()
+    })
+}
+
+#[async_trait]
+impl ContainerRuntime for ResilientRuntime {
+    async fn check_image_updates(
+        &self,
+        service_name: &str,
+        containers: &[Container],
+        current_hashes: &HashMap<String, String>,
+    ) -> Result<HashMap<String, bool>> {
+        self.call_with_retry("check_image_updates", || {
+            self.inner
+                .check_image_updates(service_name, containers, current_hashes)
+        })
+        .await
+    }
+
+    async fn get_image_digest(&self, image: &str) -> Result<String> {
+        self.call_with_retry("get_image_digest", || self.inner.get_image_digest(image))
+            .await
+    }
+
+    async fn pull_image(
+        &self,
+        service_name: &str,
+        containers: &[Container],
+        service_config: &ServiceConfig,
+    ) -> Result<()> {
+        self.call(
+            "pull_image",
+            self.policy.start_timeout,
+            self.inner
+                .pull_image(service_name, containers, service_config),
+        )
+        .await
+    }
+
+    async fn remove_pod_network(&self, network_name: &str, service_name: &str) -> Result<()> {
+        self.call(
+            "remove_pod_network",
+            self.policy.timeout,
+            self.inner.remove_pod_network(network_name, service_name),
+        )
+        .await
+    }
+
+    async fn create_pod_network(&self, service_name: &str, uuid: &str) -> Result<String> {
+        self.call(
+            "create_pod_network",
+            self.policy.timeout,
+            self.inner.create_pod_network(service_name, uuid),
+        )
+        .await
+    }
+
+    async fn start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        // What the service had before, so that a start that times out can be
+        // undone without touching its other pods
+        let before = self.pod_resources(service_name).await;
+        let result = self
+            .call(
+                "start_containers",
+                self.policy.start_timeout,
+                self.inner
+                    .start_containers(service_name, pod_number, containers, service_config),
+            )
+            .await;
+
+        if let Err(e) = &result {
+            let timed_out = matches!(
+                e.downcast_ref::<RuntimePolicyError>(),
+                Some(RuntimePolicyError::Timeout(..))
+            );
+            match before {
+                Ok(before) if timed_out => {
+                    self.remove_partial_pod(service_name, pod_number, &before)
+                        .await
+                }
+                Err(list_error) if timed_out => {
+                    slog::error!(slog_scope::logger(), "Can't clean up timed out pod start";
+                        "service" => service_name,
+                        "pod" => pod_number,
+                        "error" => list_error.to_string()
+                    );
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
+    async fn stop_container(&self, name: &str) -> Result<()> {
+        // The container's grace period comes on top of the call itself
+        let grace_period = stop_settings(name)
+            .await
+            .grace_period
+            .unwrap_or(DEFAULT_STOP_GRACE_PERIOD);
+        self.call(
+            "stop_container",
+            self.policy.timeout + grace_period,
+            self.inner.stop_container(name),
+        )
+        .await
+    }
+
+    // Waits last as long as the container runs, so they are not subject to the call timeout
+    async fn wait_container(&self, name: &str) -> Result<i64> {
+        self.check_circuit()?;
+        self.inner.wait_container(name).await
+    }
+
+    async fn restart_container(
+        &self,
+        name: &str,
+        container: &Container,
+        service_config: &ServiceConfig,
+    ) -> Result<()> {
+        self.call(
+            "restart_container",
+            self.policy.start_timeout,
+            self.inner
+                .restart_container(name, container, service_config),
+        )
+        .await
+    }
+
+    async fn exec_container(&self, name: &str, command: &[String]) -> Result<i64> {
+        // Hooks carry their own timeout, and a failing command is not a runtime failure
+        self.check_circuit()?;
+        self.inner.exec_container(name, command).await
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<ContainerStats> {
+        self.call_with_retry("inspect_container", || self.inner.inspect_container(name))
+            .await
+    }
+
+    async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>> {
+        self.call_with_retry("list_containers", || {
+            self.inner.list_containers(service_name)
+        })
+        .await
+    }
+
+    async fn list_pod_networks(&self, service_name: &str) -> Result<Vec<(String, Uuid)>> {
+        self.call_with_retry("list_pod_networks", || {
+            self.inner.list_pod_networks(service_name)
+        })
+        .await
+    }
+
+    async fn runtime_info(&self) -> Result<RuntimeInfo> {
+        self.call_with_retry("runtime_info", || self.inner.runtime_info())
+            .await
+    }
+
+    async fn image_layers(&self, image: &str) -> Result<Option<ImageLayers>> {
+        self.call_with_retry("image_layers", || self.inner.image_layers(image))
+            .await
+    }
+
+    async fn prefetch_image(
+        &self,
+        image: &str,
+        service_config: Option<&ServiceConfig>,
+    ) -> Result<bool> {
+        self.call(
+            "prefetch_image",
+            self.policy.start_timeout,
+            self.inner.prefetch_image(image, service_config),
+        )
+        .await
+    }
+
+    async fn pause_container(&self, name: &str) -> Result<()> {
+        self.call(
+            "pause_container",
+            self.policy.timeout,
+            self.inner.pause_container(name),
+        )
+        .await
+    }
+
+    async fn unpause_container(&self, name: &str) -> Result<()> {
+        self.call(
+            "unpause_container",
+            self.policy.timeout,
+            self.inner.unpause_container(name),
+        )
+        .await
+    }
+
+    async fn image_changed_upstream(
+        &self,
+        image: &str,
+        service_config: &ServiceConfig,
+    ) -> Result<bool> {
+        self.call_with_retry("image_changed_upstream", || {
+            self.inner.image_changed_upstream(image, service_config)
+        })
+        .await
+    }
+
+    async fn update_resources(
+        &self,
+        name: &str,
+        memory: Option<u64>,
+        nano_cpus: Option<u64>,
+    ) -> Result<()> {
+        self.call(
+            "update_resources",
+            self.policy.timeout,
+            self.inner.update_resources(name, memory, nano_cpus),
+        )
+        .await
+    }
+
+    fn validate_service(&self, service_config: &ServiceConfig) -> Result<()> {
+        self.inner.validate_service(service_config)
+    }
+
+    async fn attempt_start_containers(
+        &self,
+        service_name: &str,
+        pod_number: u8,
+        containers: &Vec<Container>,
+        service_config: &ServiceConfig,
+    ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+        self.call(
+            "attempt_start_containers",
+            self.policy.start_timeout,
+            self.inner.attempt_start_containers(
+                service_name,
+                pod_number,
+                containers,
+                service_config,
+            ),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::daemon::CircuitBreakerConfig;
+    use crate::container::mock::MockRuntime;
+    use crate::testing::service_config;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Passes calls on to a MockRuntime, unless it is scripted to act as an
+    // unreachable daemon, answer slowly, or hang once a pod's containers exist
+    #[derive(Debug, Default)]
+    struct StubRuntime {
+        mock: MockRuntime,
+        down: AtomicBool,
+        slow: AtomicBool,
+        hang_starts: AtomicBool,
+        networks: Mutex<Vec<(String, Uuid)>>,
+        calls: Mutex<Vec<&'static str>>,
+    }
+
+    impl StubRuntime {
+        async fn enter(&self, operation: &'static str) -> Result<()> {
+            self.calls.lock().unwrap().push(operation);
+            if self.slow.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            if self.down.load(Ordering::SeqCst) {
+                return Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into());
+            }
+            Ok(())
+        }
+
+        fn calls(&self, operation: &str) -> usize {
+            let calls = self.calls.lock().unwrap();
+            calls.iter().filter(|call| **call == operation).count()
+        }
+    }
+
+    #[async_trait]
+    impl ContainerRuntime for StubRuntime {
+        async fn check_image_updates(
+            &self,
+            service_name: &str,
+            containers: &[Container],
+            current_hashes: &HashMap<String, String>,
+        ) -> Result<HashMap<String, bool>> {
+            self.enter("check_image_updates").await?;
+            self.mock
+                .check_image_updates(service_name, containers, current_hashes)
+                .await
+        }
+
+        async fn get_image_digest(&self, image: &str) -> Result<String> {
+            self.enter("get_image_digest").await?;
+            self.mock.get_image_digest(image).await
+        }
+
+        async fn pull_image(
+            &self,
+            service_name: &str,
+            containers: &[Container],
+            service_config: &ServiceConfig,
+        ) -> Result<()> {
+            self.enter("pull_image").await?;
+            self.mock
+                .pull_image(service_name, containers, service_config)
+                .await
+        }
+
+        async fn remove_pod_network(&self, network_name: &str, _service_name: &str) -> Result<()> {
+            self.enter("remove_pod_network").await?;
+            let mut networks = self.networks.lock().unwrap();
+            networks.retain(|(name, _)| name != network_name);
+            Ok(())
+        }
+
+        async fn create_pod_network(&self, service_name: &str, uuid: &str) -> Result<String> {
+            self.enter("create_pod_network").await?;
+            self.mock.create_pod_network(service_name, uuid).await
+        }
+
+        async fn start_containers(
+            &self,
+            service_name: &str,
+            pod_number: u8,
+            containers: &Vec<Container>,
+            service_config: &ServiceConfig,
+        ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+            self.enter("start_containers").await?;
+            let started = self
+                .mock
+                .start_containers(service_name, pod_number, containers, service_config)
+                .await?;
+            let uuid = parse_container_name(&started[0].0)?.uuid;
+            self.networks
+                .lock()
+                .unwrap()
+                .push((format!("{}__{}", service_name, uuid), uuid));
+            if self.hang_starts.load(Ordering::SeqCst) {
+                std::future::pending::<()>().await;
+            }
+            Ok(started)
+        }
+
+        async fn stop_container(&self, name: &str) -> Result<()> {
+            self.enter("stop_container").await?;
+            self.mock.stop_container(name).await
+        }
+
+        async fn exec_container(&self, name: &str, command: &[String]) -> Result<i64> {
+            self.enter("exec_container").await?;
+            self.mock.exec_container(name, command).await
+        }
+
+        async fn inspect_container(&self, name: &str) -> Result<ContainerStats> {
+            self.enter("inspect_container").await?;
+            self.mock.inspect_container(name).await
+        }
+
+        async fn list_containers(&self, service_name: Option<&str>) -> Result<Vec<ContainerInfo>> {
+            self.enter("list_containers").await?;
+            self.mock.list_containers(service_name).await
+        }
+
+        async fn list_pod_networks(&self, _service_name: &str) -> Result<Vec<(String, Uuid)>> {
+            self.enter("list_pod_networks").await?;
+            Ok(self.networks.lock().unwrap().clone())
+        }
+
+        async fn runtime_info(&self) -> Result<RuntimeInfo> {
+            self.enter("runtime_info").await?;
+            self.mock.runtime_info().await
+        }
+
+        async fn update_resources(
+            &self,
+            _name: &str,
+            _memory: Option<u64>,
+            _nano_cpus: Option<u64>,
+        ) -> Result<()> {
+            self.enter("update_resources").await
+        }
+
+        async fn attempt_start_containers(
+            &self,
+            service_name: &str,
+            pod_number: u8,
+            containers: &Vec<Container>,
+            service_config: &ServiceConfig,
+        ) -> Result<Vec<(String, String, Vec<ContainerPortMetadata>)>> {
+            self.start_containers(service_name, pod_number, containers, service_config)
+                .await
+        }
+    }
+
+    fn runtime(retries: u32) -> (Arc<StubRuntime>, Arc<ResilientRuntime>) {
+        let stub = Arc::new(StubRuntime::default());
+        let policy = RuntimePolicyConfig {
+            timeout: Duration::from_secs(5),
+            start_timeout: Duration::from_secs(10),
+            retries,
+            retry_backoff: Duration::from_millis(100),
+            circuit_breaker: CircuitBreakerConfig {
+                failure_threshold: 3,
+                reset_after: Duration::from_secs(30),
+            },
+        };
+        let resilient = Arc::new(ResilientRuntime::new(stub.clone(), policy));
+        (stub, resilient)
+    }
+
+    fn is_circuit_open(error: &anyhow::Error) -> bool {
+        matches!(
+            error.downcast_ref::<RuntimePolicyError>(),
+            Some(RuntimePolicyError::CircuitOpen(_))
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_opens_after_failure_threshold() {
+        let (stub, runtime) = runtime(0);
+        stub.down.store(true, Ordering::SeqCst);
+
+        for _ in 0..3 {
+            let error = runtime.runtime_info().await.unwrap_err();
+            assert!(!is_circuit_open(&error));
+        }
+        assert_eq!(stub.calls("runtime_info"), 3);
+
+        // Open: calls fail fast without reaching the daemon
+        let error = runtime.runtime_info().await.unwrap_err();
+        assert!(is_circuit_open(&error));
+        assert!(is_circuit_open(
+            &runtime.stop_container("app").await.unwrap_err()
+        ));
+        assert_eq!(stub.calls("runtime_info"), 3);
+        assert_eq!(stub.calls("stop_container"), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_closes_after_success() {
+        let (stub, runtime) = runtime(0);
+        stub.down.store(true, Ordering::SeqCst);
+        for _ in 0..3 {
+            let _ = runtime.runtime_info().await;
+        }
+        assert!(is_circuit_open(&runtime.runtime_info().await.unwrap_err()));
+
+        // A probe that fails keeps it open for another reset_after
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(!is_circuit_open(&runtime.runtime_info().await.unwrap_err()));
+        assert!(is_circuit_open(&runtime.runtime_info().await.unwrap_err()));
+        assert_eq!(stub.calls("runtime_info"), 4);
+
+        stub.down.store(false, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(30)).await;
+        runtime.runtime_info().await.unwrap();
+        runtime.runtime_info().await.unwrap();
+        assert_eq!(stub.calls("runtime_info"), 6);
+
+        // Closed again, so it takes the full threshold to open
+        stub.down.store(true, Ordering::SeqCst);
+        for _ in 0..2 {
+            assert!(!is_circuit_open(&runtime.runtime_info().await.unwrap_err()));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_half_open_lets_one_probe_through() {
+        let (stub, runtime) = runtime(0);
+        stub.down.store(true, Ordering::SeqCst);
+        for _ in 0..3 {
+            let _ = runtime.runtime_info().await;
+        }
+        stub.down.store(false, Ordering::SeqCst);
+        stub.slow.store(true, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(30)).await;
+
+        let probe = tokio::spawn({
+            let runtime = runtime.clone();
+            async move { runtime.runtime_info().await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(stub.calls("runtime_info"), 4);
+
+        // Other calls fail fast until the probe has answered
+        assert!(is_circuit_open(&runtime.runtime_info().await.unwrap_err()));
+        assert_eq!(stub.calls("runtime_info"), 4);
+
+        probe.await.unwrap().unwrap();
+        runtime.runtime_info().await.unwrap();
+        assert_eq!(stub.calls("runtime_info"), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_only_read_calls_are_retried() {
+        let (stub, runtime) = runtime(2);
+        stub.down.store(true, Ordering::SeqCst);
+
+        assert!(runtime.list_containers(Some("app")).await.is_err());
+        assert_eq!(stub.calls("list_containers"), 3);
+
+        // The circuit is open by now
+        tokio::time::advance(Duration::from_secs(30)).await;
+        stub.down.store(false, Ordering::SeqCst);
+        runtime.runtime_info().await.unwrap();
+        stub.down.store(true, Ordering::SeqCst);
+
+        assert!(runtime.stop_container("app").await.is_err());
+        assert_eq!(stub.calls("stop_container"), 1);
+        assert!(runtime
+            .update_resources("app", Some(1), None)
+            .await
+            .is_err());
+        assert_eq!(stub.calls("update_resources"), 1);
+        assert!(runtime.exec_container("app", &[]).await.is_err());
+        assert_eq!(stub.calls("exec_container"), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timed_out_start_is_removed() {
+        let (stub, runtime) = runtime(0);
+        let config = service_config(
+            r#"
+name: resilience-partial
+instance_count:
+  min: 1
+  max: 2
+spec:
+  containers:
+    - name: app
+      image: example/app:1
+    - name: sidecar
+      image: example/sidecar:1
+"#,
+        )
+        .unwrap();
+        let containers = config.spec.containers.clone();
+
+        let running = runtime
+            .start_containers("resilience-partial", 0, &containers, &config)
+            .await
+            .unwrap();
+        let before = runtime.list_containers(None).await.unwrap();
+        assert_eq!(before.len(), 2);
+
+        stub.hang_starts.store(true, Ordering::SeqCst);
+        let error = runtime
+            .start_containers("resilience-partial", 1, &containers, &config)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RuntimePolicyError>(),
+            Some(RuntimePolicyError::Timeout(..))
+        ));
+
+        // Only the pod that was running before is left
+        let after: HashSet<String> = runtime
+            .list_containers(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|container| container.name)
+            .collect();
+        let expected: HashSet<String> = running.into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(after, expected);
+        let networks = runtime
+            .list_pod_networks("resilience-partial")
+            .await
+            .unwrap();
+        assert_eq!(networks.len(), 1);
+        assert_eq!(stub.calls("remove_pod_network"), 1);
+    }
+}
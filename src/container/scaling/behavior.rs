@@ -0,0 +1,382 @@
+// src/container/scaling/behavior.rs
+//
+// Scaling behavior, after the Kubernetes HPA's. Rules for each direction
+// limit how fast the pod count changes: with a stabilization window the
+// scaler acts on its most conservative recommendation within the window,
+// and step policies cap the pods added or removed per period, either as a
+// count or as a percentage of the pods at the start of the period.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const MAX_STABILIZATION_WINDOW: Duration = Duration::from_secs(3600);
+const MAX_PERIOD: Duration = Duration::from_secs(1800);
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ScalingBehavior {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_up: Option<ScalingRules>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_down: Option<ScalingRules>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScalingRules {
+    /// Act on the most conservative recommendation made within this window
+    #[serde(with = "humantime_serde", default)]
+    pub stabilization_window: Duration,
+    /// Which of several policies applies
+    #[serde(default)]
+    pub select_policy: SelectPolicy,
+    /// Limits on the pods changed per period; without any, changes are unlimited
+    #[serde(default)]
+    pub policies: Vec<StepPolicy>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectPolicy {
+    /// The policy allowing the largest change
+    #[default]
+    Max,
+    /// The policy allowing the smallest change
+    Min,
+    /// No changes in this direction
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepPolicy {
+    #[serde(rename = "type")]
+    pub kind: StepKind,
+    /// Pods, or percent of the pods at the start of the period
+    pub value: u32,
+    #[serde(with = "humantime_serde")]
+    pub period: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    Pods,
+    Percent,
+}
+
+/// A change of the pod count made by the scaler
+#[derive(Debug, Clone)]
+pub struct PodCountChange {
+    pub at: Instant,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl ScalingBehavior {
+    pub fn validate(&self) -> Result<()> {
+        for (direction, rules) in [
+            ("scale_up", &self.scale_up),
+            ("scale_down", &self.scale_down),
+        ] {
+            if let Some(rules) = rules {
+                rules
+                    .validate()
+                    .map_err(|e| anyhow!("{}: {}", direction, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// How long recommendations and changes must be kept for
+    pub fn retention(&self) -> Duration {
+        [&self.scale_up, &self.scale_down]
+            .into_iter()
+            .flatten()
+            .flat_map(|rules| {
+                rules
+                    .policies
+                    .iter()
+                    .map(|policy| policy.period)
+                    .chain([rules.stabilization_window])
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// The pod count to scale towards instead of `desired`: the most
+    /// conservative of the recommendations within the stabilization window of
+    /// the direction, which `recommendations` includes
+    pub fn stabilize(
+        &self,
+        recommendations: &VecDeque<(Instant, usize)>,
+        now: Instant,
+        current: usize,
+        desired: usize,
+    ) -> usize {
+        let within = |rules: &Option<ScalingRules>| {
+            let window = rules
+                .as_ref()
+                .map_or(Duration::ZERO, |rules| rules.stabilization_window);
+            recommendations
+                .iter()
+                .filter(move |(at, _)| now.duration_since(*at) <= window)
+                .map(|(_, recommended)| *recommended)
+                .chain([desired])
+        };
+        if desired > current {
+            within(&self.scale_up).min().unwrap_or(current).max(current)
+        } else if desired < current {
+            within(&self.scale_down)
+                .max()
+                .unwrap_or(current)
+                .min(current)
+        } else {
+            current
+        }
+    }
+
+    /// The pod count to scale to instead of `desired`, as far as the step
+    /// policies allow after the `changes` made within their periods
+    pub fn limit(
+        &self,
+        changes: &VecDeque<PodCountChange>,
+        now: Instant,
+        current: usize,
+        desired: usize,
+    ) -> usize {
+        if desired > current {
+            let allowed = self.scale_up.as_ref().map_or(usize::MAX, |rules| {
+                rules.allowed(changes, now, current, true)
+            });
+            current + (desired - current).min(allowed)
+        } else if desired < current {
+            let allowed = self.scale_down.as_ref().map_or(usize::MAX, |rules| {
+                rules.allowed(changes, now, current, false)
+            });
+            current - (current - desired).min(allowed)
+        } else {
+            current
+        }
+    }
+}
+
+impl ScalingRules {
+    fn validate(&self) -> Result<()> {
+        if self.stabilization_window > MAX_STABILIZATION_WINDOW {
+            return Err(anyhow!("stabilization_window must be at most 1h"));
+        }
+        for policy in &self.policies {
+            if policy.value == 0 {
+                return Err(anyhow!("policy value must be at least 1"));
+            }
+            if policy.period < Duration::from_secs(1) || policy.period > MAX_PERIOD {
+                return Err(anyhow!("policy period must be between 1s and 30m"));
+            }
+        }
+        Ok(())
+    }
+
+    // The most pods the policies still allow changing in the direction
+    fn allowed(
+        &self,
+        changes: &VecDeque<PodCountChange>,
+        now: Instant,
+        current: usize,
+        up: bool,
+    ) -> usize {
+        if self.select_policy == SelectPolicy::Disabled {
+            return 0;
+        }
+
+        let allowances = self.policies.iter().map(|policy| {
+            // Pods changed in this direction within the period
+            let changed: usize = changes
+                .iter()
+                .filter(|change| now.duration_since(change.at) <= policy.period)
+                .map(|change| {
+                    if up {
+                        change.to.saturating_sub(change.from)
+                    } else {
+                        change.from.saturating_sub(change.to)
+                    }
+                })
+                .sum();
+            let step = match policy.kind {
+                StepKind::Pods => policy.value as usize,
+                StepKind::Percent => {
+                    let start = if up {
+                        current.saturating_sub(changed)
+                    } else {
+                        current + changed
+                    };
+                    let pods = start.max(1) as f64 * f64::from(policy.value) / 100.0;
+                    if up {
+                        pods.ceil() as usize
+                    } else {
+                        pods.floor() as usize
+                    }
+                }
+            };
+            step.saturating_sub(changed)
+        });
+
+        match self.select_policy {
+            SelectPolicy::Max => allowances.max(),
+            SelectPolicy::Min => allowances.min(),
+            SelectPolicy::Disabled => Some(0),
+        }
+        .unwrap_or(usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(kind: StepKind, value: u32, period_secs: u64) -> StepPolicy {
+        StepPolicy {
+            kind,
+            value,
+            period: Duration::from_secs(period_secs),
+        }
+    }
+
+    fn rules(select_policy: SelectPolicy, policies: Vec<StepPolicy>) -> ScalingRules {
+        ScalingRules {
+            stabilization_window: Duration::ZERO,
+            select_policy,
+            policies,
+        }
+    }
+
+    fn behavior(
+        scale_up: Option<ScalingRules>,
+        scale_down: Option<ScalingRules>,
+    ) -> ScalingBehavior {
+        ScalingBehavior {
+            scale_up,
+            scale_down,
+        }
+    }
+
+    fn change(now: Instant, secs_ago: u64, from: usize, to: usize) -> PodCountChange {
+        PodCountChange {
+            at: now - Duration::from_secs(secs_ago),
+            from,
+            to,
+        }
+    }
+
+    #[test]
+    fn test_limit_pods_and_percent_steps() {
+        let now = Instant::now();
+        let policies = vec![
+            policy(StepKind::Pods, 4, 60),
+            policy(StepKind::Percent, 100, 60),
+        ];
+
+        // 4 pods, or 100% of 3
+        let max = behavior(Some(rules(SelectPolicy::Max, policies.clone())), None);
+        assert_eq!(max.limit(&VecDeque::new(), now, 3, 10), 7);
+        let min = behavior(Some(rules(SelectPolicy::Min, policies)), None);
+        assert_eq!(min.limit(&VecDeque::new(), now, 3, 10), 6);
+
+        // Within the allowance, the desired count is kept
+        assert_eq!(max.limit(&VecDeque::new(), now, 3, 5), 5);
+        // Without rules for the direction, changes are unlimited
+        assert_eq!(max.limit(&VecDeque::new(), now, 10, 1), 1);
+    }
+
+    #[test]
+    fn test_limit_rounds_up_and_down() {
+        let now = Instant::now();
+        let percent = || rules(SelectPolicy::Max, vec![policy(StepKind::Percent, 50, 60)]);
+        let behavior = behavior(Some(percent()), Some(percent()));
+
+        // Half of 5 pods: 3 up, 2 down
+        assert_eq!(behavior.limit(&VecDeque::new(), now, 5, 20), 8);
+        assert_eq!(behavior.limit(&VecDeque::new(), now, 5, 0), 3);
+
+        // A single pod can always grow, but half of it can't be removed
+        assert_eq!(behavior.limit(&VecDeque::new(), now, 1, 20), 2);
+        assert_eq!(behavior.limit(&VecDeque::new(), now, 1, 0), 1);
+    }
+
+    #[test]
+    fn test_limit_select_policy_disabled() {
+        let now = Instant::now();
+        let disabled = behavior(
+            Some(rules(
+                SelectPolicy::Disabled,
+                vec![policy(StepKind::Pods, 4, 60)],
+            )),
+            Some(rules(SelectPolicy::Disabled, Vec::new())),
+        );
+        assert_eq!(disabled.limit(&VecDeque::new(), now, 3, 10), 3);
+        assert_eq!(disabled.limit(&VecDeque::new(), now, 3, 1), 3);
+
+        // Without policies, changes are unlimited
+        let unlimited = behavior(Some(rules(SelectPolicy::Max, Vec::new())), None);
+        assert_eq!(unlimited.limit(&VecDeque::new(), now, 3, 10), 10);
+    }
+
+    #[test]
+    fn test_limit_counts_changes_within_period() {
+        let now = Instant::now();
+        let pods = behavior(
+            Some(rules(
+                SelectPolicy::Max,
+                vec![policy(StepKind::Pods, 4, 60)],
+            )),
+            None,
+        );
+
+        // 2 of the 4 pods were added within the period
+        let changes = VecDeque::from([change(now, 30, 3, 5)]);
+        assert_eq!(pods.limit(&changes, now, 5, 10), 7);
+        // Changes before the period, or in the other direction, don't count
+        let changes = VecDeque::from([change(now, 90, 3, 5), change(now, 10, 6, 5)]);
+        assert_eq!(pods.limit(&changes, now, 5, 10), 9);
+
+        // A percentage is of the pods at the start of the period: 100% of 2
+        let percent = behavior(
+            Some(rules(
+                SelectPolicy::Max,
+                vec![policy(StepKind::Percent, 100, 60)],
+            )),
+            None,
+        );
+        let changes = VecDeque::from([change(now, 30, 2, 3), change(now, 20, 3, 4)]);
+        assert_eq!(percent.limit(&changes, now, 4, 10), 4);
+        let changes = VecDeque::from([change(now, 30, 2, 3)]);
+        assert_eq!(percent.limit(&changes, now, 3, 10), 4);
+    }
+
+    #[test]
+    fn test_stabilize_picks_most_conservative() {
+        let now = Instant::now();
+        let window = |secs| ScalingRules {
+            stabilization_window: Duration::from_secs(secs),
+            ..rules(SelectPolicy::Max, Vec::new())
+        };
+        let behavior = behavior(Some(window(60)), Some(window(300)));
+        let recommendations = VecDeque::from([
+            (now - Duration::from_secs(400), 10),
+            (now - Duration::from_secs(200), 8),
+            (now - Duration::from_secs(100), 6),
+            (now - Duration::from_secs(30), 5),
+        ]);
+
+        // Down to the highest recommendation within 5 minutes, never above current
+        assert_eq!(behavior.stabilize(&recommendations, now, 10, 4), 8);
+        assert_eq!(behavior.stabilize(&recommendations, now, 7, 4), 7);
+        // Up to the lowest within a minute, never below current
+        assert_eq!(behavior.stabilize(&recommendations, now, 4, 9), 5);
+        assert_eq!(behavior.stabilize(&recommendations, now, 6, 9), 6);
+        assert_eq!(behavior.stabilize(&recommendations, now, 6, 6), 6);
+
+        // Without a window, the desired count applies at once
+        let immediate = ScalingBehavior::default();
+        assert_eq!(immediate.stabilize(&recommendations, now, 10, 4), 4);
+        assert_eq!(immediate.stabilize(&recommendations, now, 4, 9), 9);
+    }
+}
@@ -1,6 +1,6 @@
 // src/container/scaling/codel.rs
 use rustc_hash::FxHashMap;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::Arc;
 use std::sync::OnceLock;
 use std::time::{Duration, Instant};
@@ -28,12 +28,18 @@ pub struct CoDelMetrics {
     /// Queue of packet sojourn times (time in queue) with their timestamps
     sojourn_times: VecDeque<(Instant, Duration)>,
 
+    /// When responses with a 5xx status were sent
+    server_errors: VecDeque<Instant>,
+
     /// Time when we first went above target
     first_above_time: Option<Instant>,
 
     /// Last time we performed a scaling action
     last_scale_time: Instant,
 
+    /// Last time burst mode scaled up
+    last_burst: Option<Instant>,
+
     /// Configuration reference
     config: CoDelConfig,
 }
@@ -43,8 +49,10 @@ impl CoDelMetrics {
         Self {
             service_name,
             sojourn_times: VecDeque::new(),
+            server_errors: VecDeque::new(),
             first_above_time: None,
             last_scale_time: Instant::now(),
+            last_burst: None,
             config,
         }
     }
@@ -105,6 +113,13 @@ impl CoDelMetrics {
         while self.sojourn_times.len() > 1000 {
             self.sojourn_times.pop_front();
         }
+        while let Some(error_time) = self.server_errors.front() {
+            if now.duration_since(*error_time) > max_age || self.server_errors.len() > 1000 {
+                self.server_errors.pop_front();
+            } else {
+                break;
+            }
+        }
 
         // Log window cleanup
         if self.sojourn_times.is_empty() {
@@ -114,6 +129,11 @@ impl CoDelMetrics {
         }
     }
 
+    /// Count a 5xx response, after its sojourn time has been recorded
+    pub fn record_server_error(&mut self) {
+        self.server_errors.push_back(Instant::now());
+    }
+
     pub fn should_reject(&self) -> bool {
         if self.sojourn_times.len() < 10 {
             slog::trace!(slog_scope::logger(), "Not enough samples for rejection decision";
@@ -288,6 +308,94 @@ impl CoDelMetrics {
 
         can_scale
     }
+
+    /// Instances to add at once, and the metrics seen, when the average queue
+    /// delay or the error rate is above its burst threshold. Only requests
+    /// since the last burst count, so its pods get to take load first.
+    pub fn check_burst(&mut self) -> Option<(u32, BTreeMap<String, f64>)> {
+        let step = self.config.max_scale_step_burst.filter(|step| *step > 0)?;
+        let now = Instant::now();
+        let last_burst = self.last_burst;
+        let counted = |time: &Instant| {
+            now.duration_since(*time) <= self.config.interval
+                && last_burst.is_none_or(|last| *time > last)
+        };
+
+        let recent_samples: Vec<Duration> = self
+            .sojourn_times
+            .iter()
+            .filter(|(time, _)| counted(time))
+            .map(|(_, duration)| *duration)
+            .collect();
+        if recent_samples.len() < 10 {
+            return None;
+        }
+
+        let avg_sojourn = recent_samples.iter().sum::<Duration>() / recent_samples.len() as u32;
+        let errors = self
+            .server_errors
+            .iter()
+            .filter(|time| counted(time))
+            .count();
+        let error_percentage = errors as f64 / recent_samples.len() as f64 * 100.0;
+        let queue_delay = self
+            .config
+            .burst_queue_delay
+            .unwrap_or(self.config.target * 4);
+
+        let delay_exceeded = avg_sojourn > queue_delay;
+        let errors_exceeded = self
+            .config
+            .burst_error_percentage
+            .is_some_and(|threshold| error_percentage > threshold);
+        if !delay_exceeded && !errors_exceeded {
+            return None;
+        }
+
+        slog::warn!(slog_scope::logger(), "Burst condition met";
+            "service" => &self.service_name,
+            "avg_sojourn_ms" => avg_sojourn.as_millis(),
+            "burst_queue_delay_ms" => queue_delay.as_millis(),
+            "error_percentage" => error_percentage
+        );
+
+        // Normal scaling starts over from the burst
+        self.last_burst = Some(now);
+        self.last_scale_time = now;
+        self.first_above_time = None;
+
+        let mut summary = self.sojourn_summary();
+        summary.insert(
+            "burst_queue_delay_ms".to_string(),
+            queue_delay.as_secs_f64() * 1000.0,
+        );
+        summary.insert("error_percentage".to_string(), error_percentage);
+        Some((step, summary))
+    }
+
+    /// Recent sojourn times in milliseconds, for recording why the scaler acted
+    pub fn sojourn_summary(&self) -> BTreeMap<String, f64> {
+        let now = Instant::now();
+        let recent_samples: Vec<Duration> = self
+            .sojourn_times
+            .iter()
+            .filter(|(time, _)| now.duration_since(*time) <= self.config.interval)
+            .map(|(_, duration)| *duration)
+            .collect();
+
+        let mut summary = BTreeMap::new();
+        summary.insert("recent_samples".to_string(), recent_samples.len() as f64);
+        summary.insert(
+            "target_ms".to_string(),
+            self.config.target.as_secs_f64() * 1000.0,
+        );
+        if let Some(max) = recent_samples.iter().max() {
+            let avg = recent_samples.iter().sum::<Duration>() / recent_samples.len() as u32;
+            summary.insert("max_sojourn_ms".to_string(), max.as_secs_f64() * 1000.0);
+            summary.insert("avg_sojourn_ms".to_string(), avg.as_secs_f64() * 1000.0);
+        }
+        summary
+    }
 }
 
 // Function to initialize CoDel metrics tracking
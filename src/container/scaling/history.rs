@@ -0,0 +1,202 @@
+// src/container/scaling/history.rs
+//
+// History of every scaling action, with the policy and metric values that
+// triggered it, so capacity can be reviewed long after the fact. Events are
+// appended to a JSON lines file in the state directory, off the runtime
+// threads, and reloaded on start; only the most recent events of each service
+// are kept.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use super::state_file::StateFile;
+
+static SCALING_HISTORY: OnceLock<HistoryStore> = OnceLock::new();
+
+const STATE_FILE: &str = "scaling_history.jsonl";
+const MAX_EVENTS_PER_SERVICE: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingDirection {
+    Up,
+    Down,
+}
+
+/// Why the scaler acted: the policy and the metric values it saw
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ScalingReason {
+    /// "codel", "burst", "resources", "schedule", "idle", "rps", "latency",
+    /// "predictive", "triggers", "webhook", "vertical", "group", "preemption",
+    /// "stopped" or "wake"
+    pub policy: String,
+    pub metrics: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScalingEvent {
+    #[serde(with = "humantime_serde")]
+    pub timestamp: SystemTime,
+    pub direction: ScalingDirection,
+    /// Pods before and after the action
+    pub from: usize,
+    pub to: usize,
+    #[serde(flatten)]
+    pub reason: ScalingReason,
+    /// Set when some of the requested pods could not be started or stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// One line of the state file
+#[derive(Serialize, Deserialize)]
+struct HistoryLine {
+    service_name: String,
+    #[serde(flatten)]
+    event: ScalingEvent,
+}
+
+#[derive(Debug)]
+struct HistoryStore {
+    file: Option<StateFile>,
+    state: Mutex<HistoryState>,
+}
+
+#[derive(Debug, Default)]
+struct HistoryState {
+    services: FxHashMap<String, VecDeque<ScalingEvent>>,
+    // Lines in the state file, compacted once well above what is retained
+    lines: usize,
+}
+
+pub fn initialize_scaling_history(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let path = state_dir.join(STATE_FILE);
+
+    let mut state = HistoryState::default();
+    if path.exists() {
+        for line in std::fs::read_to_string(&path)?.lines() {
+            // A line cut short by a crash is skipped rather than failing startup
+            let Ok(line) = serde_json::from_str::<HistoryLine>(line) else {
+                continue;
+            };
+            state.push(line.service_name, line.event);
+        }
+    }
+
+    let store = HistoryStore {
+        file: Some(StateFile::open(path)?),
+        state: Mutex::new(state),
+    };
+    store.compact(&mut store.state.lock().unwrap());
+
+    SCALING_HISTORY
+        .set(store)
+        .map_err(|_| anyhow!("Scaling history already initialized"))
+}
+
+fn history_store() -> &'static HistoryStore {
+    SCALING_HISTORY.get_or_init(|| HistoryStore {
+        file: None,
+        state: Mutex::new(HistoryState::default()),
+    })
+}
+
+impl HistoryState {
+    fn push(&mut self, service_name: String, event: ScalingEvent) {
+        let events = self.services.entry(service_name).or_default();
+        events.push_back(event);
+        if events.len() > MAX_EVENTS_PER_SERVICE {
+            events.pop_front();
+        }
+    }
+
+    fn retained(&self) -> usize {
+        self.services.values().map(|events| events.len()).sum()
+    }
+}
+
+impl HistoryStore {
+    fn append(&self, state: &mut HistoryState, service_name: &str, event: &ScalingEvent) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let line = HistoryLine {
+            service_name: service_name.to_string(),
+            event: event.clone(),
+        };
+        if let Ok(json) = serde_json::to_string(&line) {
+            file.append(json);
+            state.lines += 1;
+        }
+    }
+
+    // Rewrite the state file with only the retained events
+    fn compact(&self, state: &mut HistoryState) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let mut lines = Vec::new();
+        for (service_name, events) in &state.services {
+            for event in events {
+                let line = HistoryLine {
+                    service_name: service_name.clone(),
+                    event: event.clone(),
+                };
+                if let Ok(json) = serde_json::to_string(&line) {
+                    lines.push(json);
+                }
+            }
+        }
+
+        state.lines = lines.len();
+        file.replace(lines);
+    }
+}
+
+/// Record a scaling action and persist it
+pub fn record_scaling_event(service_name: &str, event: ScalingEvent) {
+    slog::info!(slog_scope::logger(), "Scaling event";
+        "service" => service_name,
+        "direction" => format!("{:?}", event.direction),
+        "from" => event.from,
+        "to" => event.to,
+        "policy" => &event.reason.policy
+    );
+
+    let store = history_store();
+    let mut state = store.state.lock().unwrap();
+    store.append(&mut state, service_name, &event);
+    state.push(service_name.to_string(), event);
+
+    if state.lines > 2 * state.retained() + MAX_EVENTS_PER_SERVICE {
+        store.compact(&mut state);
+    }
+}
+
+/// A service's scaling events, oldest first, optionally only those after
+/// `since` and at most the `limit` most recent ones
+pub fn scaling_history(
+    service_name: &str,
+    since: Option<SystemTime>,
+    limit: Option<usize>,
+) -> Vec<ScalingEvent> {
+    let state = history_store().state.lock().unwrap();
+    let Some(events) = state.services.get(service_name) else {
+        return Vec::new();
+    };
+
+    let events: Vec<ScalingEvent> = events
+        .iter()
+        .filter(|event| since.is_none_or(|since| event.timestamp > since))
+        .cloned()
+        .collect();
+    let skip = limit.map_or(0, |limit| events.len().saturating_sub(limit));
+    events.into_iter().skip(skip).collect()
+}
@@ -0,0 +1,40 @@
+// src/container/scaling/idle.rs
+//
+// Request activity, for idle-based scale-down. The proxy notes when each
+// service last received or answered a request. With `scaling_policy.idle_timeout`
+// the scaler leaves a service's pods alone while requests keep arriving, even
+// if CPU stays low, and steps it down to `instance_count.min` once none have
+// arrived for the timeout.
+use rustc_hash::FxHashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+static LAST_ACTIVITY: OnceLock<Mutex<FxHashMap<String, Instant>>> = OnceLock::new();
+
+fn last_activity() -> &'static Mutex<FxHashMap<String, Instant>> {
+    LAST_ACTIVITY.get_or_init(|| Mutex::new(FxHashMap::default()))
+}
+
+/// Note a request to the service, as it arrives or is answered
+pub fn record_request(service_name: &str) {
+    let now = Instant::now();
+    let mut activity = last_activity().lock().unwrap();
+    match activity.get_mut(service_name) {
+        Some(last) => *last = now,
+        None => {
+            activity.insert(service_name.to_string(), now);
+        }
+    }
+}
+
+/// How long the service has gone without requests. A service that hasn't had
+/// any counts from the first time it is asked about, so it gets a full idle
+/// period before it is scaled down.
+pub fn idle_for(service_name: &str) -> Duration {
+    last_activity()
+        .lock()
+        .unwrap()
+        .entry(service_name.to_string())
+        .or_insert_with(Instant::now)
+        .elapsed()
+}
@@ -0,0 +1,164 @@
+// src/container/scaling/latency.rs
+//
+// Latency SLO scaling. The proxy records how long each service's upstreams
+// take to respond in a histogram of exponentially sized buckets, a quarter of
+// a doubling wide. On every scaling check the scaler reads the percentile the
+// service's `scaling_policy.latency_slo` names from the responses since the
+// last check, and scales up once it has been over the target for `intervals`
+// checks in a row. This catches slow upstreams that never queue up enough to
+// trip CoDel.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+static LATENCIES: OnceLock<Mutex<FxHashMap<String, Histogram>>> = OnceLock::new();
+
+const BUCKETS_PER_DOUBLING: f64 = 4.0;
+// The last bucket holds everything from about 17 minutes up
+const BUCKETS: usize = 81;
+// Fewer responses than this say little about the tail
+const MIN_SAMPLES: u64 = 10;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LatencyPercentile {
+    P95,
+    #[default]
+    P99,
+}
+
+impl LatencyPercentile {
+    fn fraction(self) -> f64 {
+        match self {
+            LatencyPercentile::P95 => 0.95,
+            LatencyPercentile::P99 => 0.99,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencySlo {
+    /// Upstream response time the percentile should stay under
+    #[serde(with = "humantime_serde")]
+    pub target: Duration,
+    /// "p95" or "p99"
+    #[serde(default)]
+    pub percentile: LatencyPercentile,
+    /// Scaling checks in a row over the target before scaling up
+    #[serde(default = "default_intervals")]
+    pub intervals: u32,
+}
+
+fn default_intervals() -> u32 {
+    3
+}
+
+impl LatencySlo {
+    pub fn validate(&self) -> Result<()> {
+        if self.target.is_zero() {
+            return Err(anyhow!("target must be positive"));
+        }
+        if self.intervals == 0 {
+            return Err(anyhow!("intervals must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Upstream response time percentiles over a window of requests
+#[derive(Debug, Clone)]
+pub struct LatencyPercentiles {
+    pub p95: Duration,
+    pub p99: Duration,
+    pub samples: u64,
+}
+
+impl LatencyPercentiles {
+    pub fn get(&self, percentile: LatencyPercentile) -> Duration {
+        match percentile {
+            LatencyPercentile::P95 => self.p95,
+            LatencyPercentile::P99 => self.p99,
+        }
+    }
+}
+
+/// Response times, counted in buckets
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: [u64; BUCKETS],
+    samples: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; BUCKETS],
+            samples: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn record(&mut self, latency: Duration) {
+        self.counts[bucket(latency)] += 1;
+        self.samples += 1;
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// The upper bound of the bucket holding the percentile
+    pub fn percentile(&self, percentile: LatencyPercentile) -> Duration {
+        let rank = (self.samples as f64 * percentile.fraction()).ceil() as u64;
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_bound(index);
+            }
+        }
+        bucket_bound(BUCKETS - 1)
+    }
+}
+
+fn bucket(latency: Duration) -> usize {
+    let millis = latency.as_secs_f64() * 1000.0;
+    if millis <= 1.0 {
+        return 0;
+    }
+    ((millis.log2() * BUCKETS_PER_DOUBLING).ceil() as usize).min(BUCKETS - 1)
+}
+
+fn bucket_bound(index: usize) -> Duration {
+    Duration::from_secs_f64(2f64.powf(index as f64 / BUCKETS_PER_DOUBLING) / 1000.0)
+}
+
+fn latencies() -> &'static Mutex<FxHashMap<String, Histogram>> {
+    LATENCIES.get_or_init(|| Mutex::new(FxHashMap::default()))
+}
+
+/// Record how long one of the service's upstreams took to respond
+pub fn record(service_name: &str, latency: Duration) {
+    let mut latencies = latencies().lock().unwrap();
+    if !latencies.contains_key(service_name) {
+        latencies.insert(service_name.to_string(), Histogram::default());
+    }
+    latencies.get_mut(service_name).unwrap().record(latency);
+}
+
+/// The service's latency percentiles since the last call, starting a new
+/// window. None when too few responses were recorded to tell.
+pub fn take_percentiles(service_name: &str) -> Option<LatencyPercentiles> {
+    let histogram = latencies().lock().unwrap().remove(service_name)?;
+    if histogram.samples < MIN_SAMPLES {
+        return None;
+    }
+    Some(LatencyPercentiles {
+        p95: histogram.percentile(LatencyPercentile::P95),
+        p99: histogram.percentile(LatencyPercentile::P99),
+        samples: histogram.samples,
+    })
+}
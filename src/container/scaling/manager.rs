@@ -1,13 +1,27 @@
 // src/container/scaling/manager.rs
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
-use crate::config::{PodStats, ResourceThresholds, ServiceConfig};
+use crate::config::{InstanceCount, PodStats, ResourceThresholds, ServiceConfig};
+use crate::container::groups;
+use crate::container::health::webhooks::WebhookConfig;
+use crate::container::scaling::behavior::{PodCountChange, ScalingBehavior};
 use crate::container::scaling::codel::CoDelMetrics;
+use crate::container::scaling::history::ScalingReason;
+use crate::container::scaling::latency::{self, LatencyPercentiles, LatencySlo};
+use crate::container::scaling::predictive::{self, Prediction, PredictiveScaling};
+use crate::container::scaling::schedules::{scheduled_instance_count, ScheduledScaling};
+use crate::container::scaling::triggers::{ScalingTrigger, TriggerReading};
+use crate::container::scaling::vertical::VerticalScaling;
+use crate::container::scaling::webhook::WebhookAnswer;
+use crate::container::scaling::{idle, rps};
+
+// How far the request rate may stray from the target before the pod count changes
+const RPS_TOLERANCE: f64 = 0.1;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ScalingPolicy {
@@ -22,6 +36,46 @@ pub struct ScalingPolicy {
     /// CPU/Memory threshold percentage below which scale down is considered
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scale_down_threshold_percentage: Option<f64>,
+
+    /// Instance count overrides for known traffic windows
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<ScheduledScaling>,
+
+    /// Scale down only once the service has had no requests for this long
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub idle_timeout: Option<Duration>,
+
+    /// Requests per second each instance should handle; scales on the proxied request rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_rps_per_instance: Option<f64>,
+
+    /// Upstream response time to keep a latency percentile under, scaling up when it isn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_slo: Option<LatencySlo>,
+
+    /// Scale up ahead of peaks that recur at the same time each day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub predictive: Option<PredictiveScaling>,
+
+    /// Limits on how fast metric-driven scaling changes the pod count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub behavior: Option<ScalingBehavior>,
+
+    /// Queue lengths to scale on, e.g. of a Redis list or a Kafka consumer group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<ScalingTrigger>,
+
+    /// Endpoint that decides the instance count, with the built-in policies as a fallback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConfig>,
+
+    /// Resize containers in place instead of changing the pod count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vertical: Option<VerticalScaling>,
 }
 
 fn default_cooldown_duration() -> Duration {
@@ -59,6 +113,19 @@ enum ScalingState {
     Cooldown { until: Instant },
 }
 
+// The clock decisions are timed by. A simulation has its own, which only
+// moves when the simulator advances it, so a replay gives the same decisions
+// however fast it runs.
+#[derive(Debug, Clone, Copy)]
+enum Clock {
+    System,
+    Simulated {
+        origin: Instant,
+        start: SystemTime,
+        elapsed: Duration,
+    },
+}
+
 pub struct UnifiedScalingManager {
     service_name: String,
     state: ScalingState,
@@ -67,6 +134,19 @@ pub struct UnifiedScalingManager {
     config: ServiceConfig,
     policy: ScalingPolicy,
     last_scale_time: Instant,
+    // Policy and metrics behind the last scale up or down decision
+    last_reason: Option<ScalingReason>,
+    // Scaling checks in a row that found latency over the SLO target
+    latency_breaches: u32,
+    // Pod counts recommended by recent checks, and the changes made, for the
+    // scaling behavior rules
+    recommendations: VecDeque<(Instant, usize)>,
+    changes: VecDeque<PodCountChange>,
+    // Queue triggers as polled for the coming check
+    trigger_readings: Option<Vec<TriggerReading>>,
+    // The scaling webhook's answer for the coming check
+    webhook_answer: Option<WebhookAnswer>,
+    clock: Clock,
 }
 
 impl UnifiedScalingManager {
@@ -84,15 +164,134 @@ impl UnifiedScalingManager {
             config,
             policy: policy.unwrap_or_default(),
             last_scale_time: Instant::now(),
+            last_reason: None,
+            latency_breaches: 0,
+            recommendations: VecDeque::new(),
+            changes: VecDeque::new(),
+            trigger_readings: None,
+            webhook_answer: None,
+            clock: Clock::System,
+        }
+    }
+
+    /// A manager for a simulation, without CoDel metrics, whose clock starts
+    /// at `start` and only moves with `advance_to`
+    pub fn simulated(service_name: String, config: ServiceConfig, start: SystemTime) -> Self {
+        let policy = config.scaling_policy.clone();
+        let mut manager = Self::new(service_name, config, None, policy);
+        manager.clock = Clock::Simulated {
+            origin: manager.last_scale_time,
+            start,
+            elapsed: Duration::ZERO,
+        };
+        manager
+    }
+
+    /// Move a simulation's clock forward to `time`
+    pub fn advance_to(&mut self, time: SystemTime) {
+        if let Clock::Simulated { start, elapsed, .. } = &mut self.clock {
+            *elapsed = time
+                .duration_since(*start)
+                .unwrap_or_default()
+                .max(*elapsed);
+        }
+    }
+
+    fn now(&self) -> Instant {
+        match self.clock {
+            Clock::System => Instant::now(),
+            Clock::Simulated {
+                origin, elapsed, ..
+            } => origin + elapsed,
         }
     }
 
+    fn wall_time(&self) -> SystemTime {
+        match self.clock {
+            Clock::System => SystemTime::now(),
+            Clock::Simulated { start, elapsed, .. } => start + elapsed,
+        }
+    }
+
+    /// Pick up a changed config, e.g. new schedules or thresholds
+    pub fn update_config(&mut self, config: &ServiceConfig) {
+        self.resource_thresholds = config.resource_thresholds.clone();
+        self.policy = config.scaling_policy.clone().unwrap_or_default();
+        self.config = config.clone();
+    }
+
+    /// The instance count bounds in effect now, overridden by an open schedule window
+    pub fn instance_bounds(&self) -> InstanceCount {
+        let bounds = scheduled_instance_count(
+            &self.policy.schedules,
+            &self.config.instance_count,
+            self.wall_time(),
+        );
+        groups::scaled_bounds(&self.service_name, bounds)
+    }
+
+    /// Hand over the queue triggers read for the next check
+    pub fn set_trigger_readings(&mut self, readings: Vec<TriggerReading>) {
+        self.trigger_readings = Some(readings);
+    }
+
+    /// Hand over the scaling webhook's answer for the next check, if it gave one
+    pub fn set_webhook_answer(&mut self, answer: Option<WebhookAnswer>) {
+        self.webhook_answer = answer;
+    }
+
+    /// Whether the next check is within the cooldown of the last action
+    pub fn in_cooldown(&self) -> bool {
+        self.now().duration_since(self.last_scale_time) < self.policy.get_cooldown_duration()
+    }
+
     pub async fn evaluate(
         &mut self,
         current_instances: usize,
         pod_stats: &HashMap<Uuid, PodStats>,
     ) -> ScalingDecision {
-        let now = Instant::now();
+        let bounds = self.instance_bounds();
+        // Recorded for every service, so there is history to predict from
+        // once predictive scaling is enabled. A simulation's load is not, so
+        // it doesn't predict from its own replay.
+        if matches!(self.clock, Clock::System) {
+            predictive::record_stats(
+                &self.service_name,
+                current_instances,
+                pod_stats,
+                self.wall_time(),
+            );
+        }
+        // Latency is sampled on every check, so breaches in a row are counted
+        // through cooldowns too
+        let latency = self.track_latency();
+
+        // Schedules move the bounds at set times, so they bypass the cooldowns
+        // and the behavior rules too
+        if let Some(decision) = self.evaluate_schedules(current_instances, &bounds) {
+            return decision;
+        }
+
+        let last_scale_time = self.last_scale_time;
+        let decision = self
+            .evaluate_metrics(current_instances, pod_stats, bounds, latency)
+            .await;
+        self.apply_behavior(current_instances, decision, last_scale_time)
+    }
+
+    async fn evaluate_metrics(
+        &mut self,
+        current_instances: usize,
+        pod_stats: &HashMap<Uuid, PodStats>,
+        bounds: InstanceCount,
+        latency: Option<LatencyPercentiles>,
+    ) -> ScalingDecision {
+        let now = self.now();
+
+        // Emergencies bypass the cooldowns
+        if let Some(decision) = self.evaluate_burst(current_instances, &bounds).await {
+            return decision;
+        }
 
         // First check if we're in cooldown
         if now.duration_since(self.last_scale_time) < self.policy.get_cooldown_duration() {
@@ -103,33 +302,87 @@ impl UnifiedScalingManager {
             return ScalingDecision::NoChange;
         }
 
+        // A predicted peak is scaled up for ahead of time, and no other
+        // policy scales down below it until it has passed
+        let prediction =
+            self.policy.predictive.as_ref().and_then(|config| {
+                predictive::predict(&self.service_name, config, self.wall_time())
+            });
+        if let Some(decision) =
+            self.evaluate_predictive(current_instances, &bounds, prediction.as_ref())
+        {
+            return decision;
+        }
+        let bounds = match &prediction {
+            Some(prediction) => InstanceCount {
+                min: bounds
+                    .min
+                    .max(prediction.instances.min(bounds.max as usize) as u8),
+                max: bounds.max,
+            },
+            None => bounds,
+        };
+
+        // With an idle timeout, requests still arriving hold off scaling down
+        let idle = self
+            .policy
+            .idle_timeout
+            .map(|timeout| (idle::idle_for(&self.service_name), timeout));
+        let idle_long_enough = idle.is_none_or(|(idle_for, timeout)| idle_for >= timeout);
+        // An answer from the scaling webhook overrides the built-in policies
+        if let Some(decision) = self.evaluate_webhook(current_instances, &bounds, idle_long_enough)
+        {
+            return decision;
+        }
+        if let Some(decision) = self.evaluate_idle(current_instances, &bounds, idle) {
+            return decision;
+        }
+        // A request rate target or queue triggers decide scale-downs on their own
+        let may_scale_down = idle.is_none()
+            && self.policy.target_rps_per_instance.is_none()
+            && self.policy.triggers.is_empty();
+        if let Some(decision) = self.evaluate_rps(current_instances, &bounds, idle_long_enough) {
+            return decision;
+        }
+        if let Some(decision) = self.evaluate_triggers(current_instances, &bounds, idle_long_enough)
+        {
+            return decision;
+        }
+        if let Some(decision) = self.evaluate_latency(current_instances, &bounds, latency) {
+            return decision;
+        }
+
         // If we have CoDel metrics, check them first
         if let Some(codel) = &self.codel_metrics {
             let mut metrics = codel.lock().await;
             metrics.check_traffic();
 
             // Check if CoDel indicates we can scale down
-            if metrics.can_scale_down() {
-                if current_instances > self.config.instance_count.min as usize {
+            if may_scale_down && metrics.can_scale_down() {
+                if current_instances > bounds.min as usize {
                     slog::info!(slog_scope::logger(), "CoDel indicates scale down";
                         "service" => &self.service_name,
                         "current_instances" => current_instances,
-                        "min_instances" => self.config.instance_count.min
+                        "min_instances" => bounds.min
                     );
                     self.last_scale_time = now; // Update last scale time
+                    self.last_reason = Some(ScalingReason {
+                        policy: "codel".to_string(),
+                        metrics: metrics.sojourn_summary(),
+                    });
                     return ScalingDecision::ScaleDown(1);
                 } else {
                     slog::debug!(slog_scope::logger(), "At minimum instances, cannot scale down";
                         "service" => &self.service_name,
                         "current_instances" => current_instances,
-                        "min_instances" => self.config.instance_count.min
+                        "min_instances" => bounds.min
                     );
                 }
             }
 
             // Check if we need to scale up
             if let Some(action) = metrics.check_state() {
-                if current_instances < self.config.instance_count.max as usize {
+                if current_instances < bounds.max as usize {
                     slog::info!(slog_scope::logger(), "CoDel triggered scale up";
                         "service" => &self.service_name,
                         "instances" => action.instances
@@ -139,16 +392,26 @@ impl UnifiedScalingManager {
                         last_scale: now,
                     };
                     self.last_scale_time = now; // Update last scale time
+                    self.last_reason = Some(ScalingReason {
+                        policy: "codel".to_string(),
+                        metrics: metrics.sojourn_summary(),
+                    });
                     return ScalingDecision::ScaleUp(action.instances);
                 }
             }
         }
 
         // Then check resource thresholds
-        if let Some(decision) = self.evaluate_resources(current_instances, pod_stats).await {
+        if let Some((decision, resource_metrics)) =
+            self.evaluate_resources(current_instances, pod_stats).await
+        {
+            let reason = ScalingReason {
+                policy: "resources".to_string(),
+                metrics: resource_metrics,
+            };
             match decision {
                 ScalingDecision::ScaleDown(n) => {
-                    if current_instances > self.config.instance_count.min as usize {
+                    if may_scale_down && current_instances > bounds.min as usize {
                         if let Some(codel) = &self.codel_metrics {
                             let metrics = codel.lock().await;
                             if !metrics.can_scale_down() {
@@ -164,12 +427,14 @@ impl UnifiedScalingManager {
                             "scale_down_count" => n
                         );
                         self.last_scale_time = now; // Update last scale time
+                        self.last_reason = Some(reason);
                         return ScalingDecision::ScaleDown(n);
                     }
                 }
                 ScalingDecision::ScaleUp(n) => {
-                    if current_instances < self.config.instance_count.max as usize {
+                    if current_instances < bounds.max as usize {
                         self.last_scale_time = now; // Update last scale time
+                        self.last_reason = Some(reason);
                         return ScalingDecision::ScaleUp(n);
                     }
                 }
@@ -180,19 +445,387 @@ impl UnifiedScalingManager {
         ScalingDecision::NoChange
     }
 
+    // Hold a decision to the behavior rules: scale towards the most
+    // conservative recommendation of the stabilization window, by no more pods
+    // than the step policies allow
+    fn apply_behavior(
+        &mut self,
+        current_instances: usize,
+        decision: ScalingDecision,
+        last_scale_time: Instant,
+    ) -> ScalingDecision {
+        let Some(behavior) = &self.policy.behavior else {
+            return decision;
+        };
+        let now = self.now();
+        let desired = match decision {
+            ScalingDecision::ScaleUp(n) => current_instances + n as usize,
+            ScalingDecision::ScaleDown(n) => current_instances.saturating_sub(n as usize),
+            ScalingDecision::NoChange => current_instances,
+        };
+
+        // Checks during a cooldown recommend nothing
+        let in_cooldown = now.duration_since(last_scale_time) < self.policy.get_cooldown_duration();
+        if desired != current_instances || !in_cooldown {
+            self.recommendations.push_back((now, desired));
+        }
+        let retention = behavior.retention();
+        while self
+            .recommendations
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > retention)
+        {
+            self.recommendations.pop_front();
+        }
+        while self
+            .changes
+            .front()
+            .is_some_and(|change| now.duration_since(change.at) > retention)
+        {
+            self.changes.pop_front();
+        }
+
+        let stabilized = behavior.stabilize(&self.recommendations, now, current_instances, desired);
+        let target = behavior.limit(&self.changes, now, current_instances, stabilized);
+        if target == current_instances {
+            if desired != current_instances {
+                slog::debug!(slog_scope::logger(), "Scaling held back by behavior rules";
+                    "service" => &self.service_name,
+                    "current_instances" => current_instances,
+                    "desired_instances" => desired
+                );
+                // Nothing was done, so no cooldown starts
+                self.last_scale_time = last_scale_time;
+                self.last_reason = None;
+            }
+            return ScalingDecision::NoChange;
+        }
+
+        if target != desired {
+            if let Some(reason) = &mut self.last_reason {
+                reason
+                    .metrics
+                    .insert("recommended_instances".to_string(), desired as f64);
+            }
+        }
+        self.changes.push_back(PodCountChange {
+            at: now,
+            from: current_instances,
+            to: target,
+        });
+        if target > current_instances {
+            ScalingDecision::ScaleUp((target - current_instances) as u32)
+        } else {
+            ScalingDecision::ScaleDown((current_instances - target) as u32)
+        }
+    }
+
+    // Bring the pod count within the bounds of an open schedule window, or
+    // back within the configured bounds once it closes
+    fn evaluate_schedules(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+    ) -> Option<ScalingDecision> {
+        if self.policy.schedules.is_empty() {
+            return None;
+        }
+        let (min, max) = (bounds.min as usize, bounds.max as usize);
+        let decision = if current_instances < min {
+            ScalingDecision::ScaleUp((min - current_instances) as u32)
+        } else if current_instances > max {
+            ScalingDecision::ScaleDown((current_instances - max) as u32)
+        } else {
+            return None;
+        };
+
+        slog::info!(slog_scope::logger(), "Scaling to scheduled instance count";
+            "service" => &self.service_name,
+            "current_instances" => current_instances,
+            "min_instances" => min,
+            "max_instances" => max
+        );
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "schedule".to_string(),
+            metrics: BTreeMap::from([
+                ("min_instances".to_string(), min as f64),
+                ("max_instances".to_string(), max as f64),
+            ]),
+        });
+        Some(decision)
+    }
+
+    // Scale up to the pods a recurring peak is predicted to need, ahead of it
+    fn evaluate_predictive(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+        prediction: Option<&Prediction>,
+    ) -> Option<ScalingDecision> {
+        let prediction = prediction?;
+        let desired = prediction.instances.min(bounds.max as usize);
+        if desired <= current_instances {
+            return None;
+        }
+
+        slog::info!(slog_scope::logger(), "Predicted peak, scaling up ahead of it";
+            "service" => &self.service_name,
+            "predicted_load" => prediction.load,
+            "current_instances" => current_instances,
+            "desired_instances" => desired
+        );
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "predictive".to_string(),
+            metrics: BTreeMap::from([
+                ("predicted_load".to_string(), prediction.load),
+                ("trend".to_string(), prediction.trend),
+                ("days".to_string(), prediction.days as f64),
+                ("desired_instances".to_string(), desired as f64),
+            ]),
+        });
+        Some(ScalingDecision::ScaleUp(
+            (desired - current_instances) as u32,
+        ))
+    }
+
+    // Step an idle service down towards its minimum, one pod per cooldown
+    fn evaluate_idle(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+        idle: Option<(Duration, Duration)>,
+    ) -> Option<ScalingDecision> {
+        let (idle_for, timeout) = idle?;
+        if idle_for < timeout || current_instances <= bounds.min as usize {
+            return None;
+        }
+
+        slog::info!(slog_scope::logger(), "Service is idle, scaling down";
+            "service" => &self.service_name,
+            "idle_secs" => idle_for.as_secs(),
+            "current_instances" => current_instances,
+            "min_instances" => bounds.min
+        );
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "idle".to_string(),
+            metrics: BTreeMap::from([("idle_seconds".to_string(), idle_for.as_secs_f64())]),
+        });
+        Some(ScalingDecision::ScaleDown(1))
+    }
+
+    // Scale towards as many pods as the request rate needs at the target rate
+    // per pod, adding them all at once but removing one per cooldown
+    fn evaluate_rps(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+        may_scale_down: bool,
+    ) -> Option<ScalingDecision> {
+        let target = self.policy.target_rps_per_instance?;
+        let rate = rps::take_rate(&self.service_name)?;
+        let desired =
+            ((rate / target).ceil() as usize).clamp(bounds.min as usize, bounds.max as usize);
+        let utilization = rate / (target * current_instances.max(1) as f64);
+        let decision = if desired > current_instances && utilization > 1.0 + RPS_TOLERANCE {
+            ScalingDecision::ScaleUp((desired - current_instances) as u32)
+        } else if may_scale_down && desired < current_instances && utilization < 1.0 - RPS_TOLERANCE
+        {
+            ScalingDecision::ScaleDown(1)
+        } else {
+            return None;
+        };
+
+        slog::info!(slog_scope::logger(), "Request rate indicates scaling";
+            "service" => &self.service_name,
+            "requests_per_second" => rate,
+            "current_instances" => current_instances,
+            "desired_instances" => desired
+        );
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "rps".to_string(),
+            metrics: BTreeMap::from([
+                ("requests_per_second".to_string(), rate),
+                ("target_rps_per_instance".to_string(), target),
+                ("desired_instances".to_string(), desired as f64),
+            ]),
+        });
+        Some(decision)
+    }
+
+    // Scale to the pod count the scaling webhook answered with, all at once.
+    // Its answer is final for the check even when it asks for no change.
+    fn evaluate_webhook(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+        may_scale_down: bool,
+    ) -> Option<ScalingDecision> {
+        let answer = self.webhook_answer.take()?;
+        let desired =
+            (answer.desired_instances as usize).clamp(bounds.min as usize, bounds.max as usize);
+        let decision = if desired > current_instances {
+            ScalingDecision::ScaleUp((desired - current_instances) as u32)
+        } else if may_scale_down && desired < current_instances {
+            ScalingDecision::ScaleDown((current_instances - desired) as u32)
+        } else {
+            return Some(ScalingDecision::NoChange);
+        };
+
+        slog::info!(slog_scope::logger(), "Scaling webhook indicates scaling";
+            "service" => &self.service_name,
+            "current_instances" => current_instances,
+            "desired_instances" => desired
+        );
+        let mut metrics = answer.metrics;
+        metrics.insert("desired_instances".to_string(), desired as f64);
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "webhook".to_string(),
+            metrics,
+        });
+        Some(decision)
+    }
+
+    // Scale towards as many pods as the longest queue needs at its target per
+    // pod, adding them all at once but removing one per cooldown
+    fn evaluate_triggers(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+        may_scale_down: bool,
+    ) -> Option<ScalingDecision> {
+        let readings = self.trigger_readings.take()?;
+        let desired = readings
+            .iter()
+            .map(|reading| (reading.value / reading.target).ceil() as usize)
+            .max()?
+            .clamp(bounds.min as usize, bounds.max as usize);
+        let decision = if desired > current_instances {
+            ScalingDecision::ScaleUp((desired - current_instances) as u32)
+        } else if may_scale_down && desired < current_instances {
+            ScalingDecision::ScaleDown(1)
+        } else {
+            return None;
+        };
+
+        slog::info!(slog_scope::logger(), "Queue triggers indicate scaling";
+            "service" => &self.service_name,
+            "current_instances" => current_instances,
+            "desired_instances" => desired
+        );
+        let mut metrics: BTreeMap<String, f64> = readings
+            .into_iter()
+            .map(|reading| (reading.name, reading.value))
+            .collect();
+        metrics.insert("desired_instances".to_string(), desired as f64);
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "triggers".to_string(),
+            metrics,
+        });
+        Some(decision)
+    }
+
+    // Sample the latency since the last check and count it against the SLO
+    fn track_latency(&mut self) -> Option<LatencyPercentiles> {
+        let slo = self.policy.latency_slo.as_ref()?;
+        let latency = latency::take_percentiles(&self.service_name);
+        let breached = latency
+            .as_ref()
+            .is_some_and(|latency| latency.get(slo.percentile) > slo.target);
+        self.latency_breaches = if breached {
+            self.latency_breaches + 1
+        } else {
+            0
+        };
+        latency
+    }
+
+    // Scale up one pod once latency has been over the SLO target for
+    // `intervals` checks in a row
+    fn evaluate_latency(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+        latency: Option<LatencyPercentiles>,
+    ) -> Option<ScalingDecision> {
+        let slo = self.policy.latency_slo.as_ref()?;
+        let latency = latency?;
+        if self.latency_breaches < slo.intervals || current_instances >= bounds.max as usize {
+            return None;
+        }
+
+        slog::info!(slog_scope::logger(), "Latency over SLO target, scaling up";
+            "service" => &self.service_name,
+            "latency_ms" => latency.get(slo.percentile).as_millis(),
+            "target_ms" => slo.target.as_millis(),
+            "checks" => self.latency_breaches
+        );
+        self.latency_breaches = 0;
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "latency".to_string(),
+            metrics: BTreeMap::from([
+                ("p95_ms".to_string(), latency.p95.as_secs_f64() * 1000.0),
+                ("p99_ms".to_string(), latency.p99.as_secs_f64() * 1000.0),
+                ("target_ms".to_string(), slo.target.as_secs_f64() * 1000.0),
+                ("samples".to_string(), latency.samples as f64),
+            ]),
+        });
+        Some(ScalingDecision::ScaleUp(1))
+    }
+
+    async fn evaluate_burst(
+        &mut self,
+        current_instances: usize,
+        bounds: &InstanceCount,
+    ) -> Option<ScalingDecision> {
+        let codel = self.codel_metrics.as_ref()?;
+        let max_instances = bounds.max as usize;
+        if current_instances >= max_instances {
+            return None;
+        }
+
+        let (step, metrics) = codel.lock().await.check_burst()?;
+        let instances = step.min((max_instances - current_instances) as u32);
+        slog::warn!(slog_scope::logger(), "Burst scaling up";
+            "service" => &self.service_name,
+            "instances" => instances,
+            "current_instances" => current_instances
+        );
+
+        // Normal cooldowns apply again from here
+        self.last_scale_time = self.now();
+        self.last_reason = Some(ScalingReason {
+            policy: "burst".to_string(),
+            metrics,
+        });
+        Some(ScalingDecision::ScaleUp(instances))
+    }
+
     pub fn enter_cooldown(&mut self) {
-        self.last_scale_time = Instant::now();
+        self.last_scale_time = self.now();
+    }
+
+    /// Why the last scale up or down was decided, taken once by the caller
+    pub fn take_reason(&mut self) -> Option<ScalingReason> {
+        self.last_reason.take()
     }
 
     async fn evaluate_resources(
         &self,
         _current_instances: usize,
         pod_stats: &HashMap<Uuid, PodStats>,
-    ) -> Option<ScalingDecision> {
+    ) -> Option<(ScalingDecision, BTreeMap<String, f64>)> {
         let thresholds = self.resource_thresholds.as_ref()?;
 
         let mut pods_exceeding = 0;
         let mut total_evaluated_pods = 0;
+        let mut totals: BTreeMap<String, f64> = BTreeMap::new();
 
         for stats in pod_stats.values() {
             let memory_percentage = if stats.memory_limit > 0 {
@@ -209,6 +842,14 @@ impl UnifiedScalingManager {
             }
 
             total_evaluated_pods += 1;
+            for (name, value) in [
+                ("cpu_percentage", stats.cpu_percentage),
+                ("cpu_percentage_relative", stats.cpu_percentage_relative),
+                ("memory_percentage", memory_percentage),
+                ("gpu_percentage", stats.gpu_percentage),
+            ] {
+                *totals.entry(name.to_string()).or_default() += value;
+            }
 
             let cpu_exceeded = thresholds.cpu_percentage.map_or(false, |threshold| {
                 stats.cpu_percentage >= 5.0 && stats.cpu_percentage > threshold as f64
@@ -226,7 +867,11 @@ impl UnifiedScalingManager {
                 memory_percentage >= 5.0 && memory_percentage > threshold as f64
             });
 
-            if cpu_exceeded || cpu_relative_exceeded || memory_exceeded {
+            let gpu_exceeded = thresholds.gpu_percentage.is_some_and(|threshold| {
+                stats.gpu_percentage >= 5.0 && stats.gpu_percentage > threshold as f64
+            });
+
+            if cpu_exceeded || cpu_relative_exceeded || memory_exceeded || gpu_exceeded {
                 pods_exceeding += 1;
             }
         }
@@ -237,13 +882,24 @@ impl UnifiedScalingManager {
 
         let percentage_exceeding = (pods_exceeding as f64 / total_evaluated_pods as f64) * 100.0;
 
-        if percentage_exceeding >= 75.0 {
-            Some(ScalingDecision::ScaleUp(1))
+        // Averages over the evaluated pods
+        let mut metrics: BTreeMap<String, f64> = totals
+            .into_iter()
+            .map(|(name, total)| (format!("avg_{}", name), total / total_evaluated_pods as f64))
+            .collect();
+        metrics.insert(
+            "pods_exceeding_percentage".to_string(),
+            percentage_exceeding,
+        );
+
+        let decision = if percentage_exceeding >= 75.0 {
+            ScalingDecision::ScaleUp(1)
         } else if percentage_exceeding <= self.policy.get_scale_down_threshold() {
-            Some(ScalingDecision::ScaleDown(1))
+            ScalingDecision::ScaleDown(1)
         } else {
-            Some(ScalingDecision::NoChange)
-        }
+            ScalingDecision::NoChange
+        };
+        Some((decision, metrics))
     }
 
     pub fn get_state(&self) -> String {
@@ -258,14 +914,11 @@ impl UnifiedScalingManager {
             ScalingState::ResourceScalingDown { since } => {
                 format!(
                     "resource_scaling_down_{}",
-                    since.duration_since(Instant::now()).as_secs()
+                    since.duration_since(self.now()).as_secs()
                 )
             }
             ScalingState::Cooldown { until } => {
-                format!(
-                    "cooldown_{}",
-                    until.duration_since(Instant::now()).as_secs()
-                )
+                format!("cooldown_{}", until.duration_since(self.now()).as_secs())
             }
         }
     }
@@ -1,8 +1,22 @@
 // src/container/scaling/mod.rs
+pub mod behavior;
 pub mod codel;
+pub mod history;
+pub mod idle;
+pub mod latency;
 pub mod manager;
-use anyhow::Result;
+pub mod predictive;
+pub mod rps;
+pub mod schedules;
+pub mod simulate;
+pub mod state_file;
+pub mod triggers;
+pub mod vertical;
+pub mod wake;
+pub mod webhook;
+use anyhow::{anyhow, Result};
 use codel::get_service_metrics;
+use history::{record_scaling_event, ScalingDirection, ScalingEvent};
 use manager::{ScalingDecision, UnifiedScalingManager};
 use pingora_load_balancing::Backend;
 use rustc_hash::FxHashMap;
@@ -16,25 +30,33 @@ use uuid::Uuid;
 
 use crate::{
     config::{
-        aggregate_pod_stats, get_config_by_service, parse_container_name, PodMetricsStrategy,
-        ScaleMessage, ServiceConfig, CONFIG_UPDATES,
+        aggregate_pod_stats, get_config_by_service, parse_container_name, register_scaler,
+        PodMetricsStrategy, ScaleMessage, ServiceConfig,
     },
     container::{
-        get_next_pod_number,
+        changes::{record_change, InstanceEvent},
+        dns, get_next_pod_number,
         health::{self},
-        ContainerMetadata, ContainerRuntime, InstanceMetadata, INSTANCE_STORE, RUNTIME,
+        lifecycle, preemption,
+        restart::supervise_containers,
+        rollback,
+        schedule::{self, PeriodicTask},
+        stopped, ContainerMetadata, ContainerRuntime, InstanceMetadata, INSTANCE_STORE, RUNTIME,
     },
     proxy::{run_proxy_for_service, SERVER_BACKENDS},
 };
 
 use super::health::CONTAINER_HEALTH;
 
+// How often each service's stats are collected and scaling is evaluated
+pub const SCALING_INTERVAL: Duration = Duration::from_secs(10);
+
 pub async fn auto_scale(service_name: String) {
     let log = slog_scope::logger();
     let runtime = RUNTIME.get().unwrap().clone();
 
     let (tx, mut rx) = mpsc::channel(100);
-    CONFIG_UPDATES.get_or_init(|| tx);
+    register_scaler(&service_name, tx);
 
     let mut scaling_paused = false;
     let service_name = Arc::new(service_name);
@@ -50,6 +72,11 @@ pub async fn auto_scale(service_name: String) {
             }
         };
 
+        // External services have no pods to scale
+        if config.external.is_some() {
+            return;
+        }
+
         let codel_metrics = if let Some(codel_config) = &config.codel {
             Some(get_service_metrics(&service_name, codel_config).await)
         } else {
@@ -64,9 +91,14 @@ pub async fn auto_scale(service_name: String) {
         )
     };
 
+    let mut vertical_scaler = vertical::VerticalScaler::new(&service_name);
+
+    let mut ticker = schedule::ticker(&service_name, PeriodicTask::Scaling, SCALING_INTERVAL);
+
     loop {
-        if !scaling_paused {
-            let current_config = match get_config_by_service(&service_name).await {
+        // Stopped services stay at zero pods until they are started again
+        if !scaling_paused && !stopped::is_service_stopped(&service_name).await {
+            let mut current_config = match get_config_by_service(&service_name).await {
                 Some(cfg) => cfg,
                 None => {
                     slog::error!(log, "Service config not found, stopping auto_scale";
@@ -74,6 +106,9 @@ pub async fn auto_scale(service_name: String) {
                     break;
                 }
             };
+            // Scale within the bounds of any open schedule window
+            scaling_manager.update_config(&current_config);
+            current_config.instance_count = scaling_manager.instance_bounds();
 
             // Get instance data with read lock
             let instances = {
@@ -92,6 +127,7 @@ pub async fn auto_scale(service_name: String) {
             // Collect stats with timeout protection
             let mut pod_stats = HashMap::new();
             let mut missing_containers = Vec::new();
+            let mut per_container = Vec::new();
 
             for (&uuid, metadata) in &instances {
                 let mut container_stats = Vec::new();
@@ -105,6 +141,7 @@ pub async fn auto_scale(service_name: String) {
                     .await
                     {
                         Ok(Ok(stats)) => {
+                            per_container.push((container.name.clone(), stats.clone()));
                             container_stats.push((uuid, metadata.clone(), stats));
                         }
                         Ok(Err(e)) => {
@@ -137,13 +174,62 @@ pub async fn auto_scale(service_name: String) {
                 }
             }
 
-            // Make scaling decision with timeout protection
-            match tokio::time::timeout(
-                Duration::from_secs(1),
-                scaling_manager.evaluate(instances.len(), &pod_stats),
-            )
-            .await
+            // Queue triggers are read over the network, so they are polled
+            // outside the time limit on the decision
+            if let Some(policy) = current_config
+                .scaling_policy
+                .as_ref()
+                .filter(|policy| !policy.triggers.is_empty())
             {
+                scaling_manager
+                    .set_trigger_readings(triggers::poll(&service_name, &policy.triggers).await);
+            }
+            // So is the scaling webhook, which only decides outside the cooldown
+            if let Some(webhook) = current_config
+                .scaling_policy
+                .as_ref()
+                .and_then(|policy| policy.webhook.as_ref())
+            {
+                let answer = if scaling_manager.in_cooldown() {
+                    None
+                } else {
+                    webhook::ask(
+                        &service_name,
+                        webhook,
+                        instances.len(),
+                        &current_config.instance_count,
+                        &pod_stats,
+                    )
+                    .await
+                };
+                scaling_manager.set_webhook_answer(answer);
+            }
+
+            // Vertical scaling resizes the containers instead of changing the pod count
+            let decision = if let Some(vertical) = current_config
+                .scaling_policy
+                .as_ref()
+                .and_then(|policy| policy.vertical.as_ref())
+            {
+                vertical_scaler
+                    .evaluate(
+                        &current_config,
+                        vertical,
+                        instances.len(),
+                        &per_container,
+                        runtime.as_ref(),
+                    )
+                    .await;
+                Ok(ScalingDecision::NoChange)
+            } else {
+                // Make scaling decision with timeout protection
+                tokio::time::timeout(
+                    Duration::from_secs(1),
+                    scaling_manager.evaluate(instances.len(), &pod_stats),
+                )
+                .await
+            };
+            match decision {
                 Ok(ScalingDecision::ScaleUp(n)) => {
                     slog::info!(log, "Scaling up service";
                         "service" => service_name.as_str(),
@@ -151,6 +237,8 @@ pub async fn auto_scale(service_name: String) {
                         "current_instances" => instances.len()
                     );
 
+                    let mut scaled_up = 0;
+                    let mut error = None;
                     for _ in 0..n {
                         if let Err(e) =
                             scale_up(&service_name, current_config.clone(), runtime.clone()).await
@@ -159,15 +247,29 @@ pub async fn auto_scale(service_name: String) {
                                 "service" => service_name.as_str(),
                                 "error" => e.to_string()
                             );
+                            error = Some(e.to_string());
                             break;
                         }
+                        scaled_up += 1;
                     }
 
+                    record_scaling_event(
+                        &service_name,
+                        ScalingEvent {
+                            timestamp: SystemTime::now(),
+                            direction: ScalingDirection::Up,
+                            from: instances.len(),
+                            to: instances.len() + scaled_up,
+                            reason: scaling_manager.take_reason().unwrap_or_default(),
+                            error,
+                        },
+                    );
+
                     run_proxy_for_service(service_name.to_string(), current_config.clone()).await;
                 }
                 Ok(ScalingDecision::ScaleDown(n)) => {
                     let current_count = instances.len();
-                    let min_count = current_config.instance_count.min as usize;
+                    let min_count = wake::min_instances(&service_name, &current_config);
 
                     if current_count <= min_count {
                         slog::debug!(log, "Already at minimum instance count";
@@ -194,6 +296,7 @@ pub async fn auto_scale(service_name: String) {
                         });
 
                         let mut scaled_down = 0;
+                        let mut error = None;
                         for (uuid, _) in pods.iter().take(scale_down_count) {
                             if let Err(e) = scale_down(
                                 &service_name,
@@ -207,11 +310,24 @@ pub async fn auto_scale(service_name: String) {
                                     "service" => service_name.as_str(),
                                     "error" => e.to_string()
                                 );
+                                error = Some(e.to_string());
                                 break;
                             }
                             scaled_down += 1;
                         }
 
+                        record_scaling_event(
+                            &service_name,
+                            ScalingEvent {
+                                timestamp: SystemTime::now(),
+                                direction: ScalingDirection::Down,
+                                from: current_count,
+                                to: current_count - scaled_down,
+                                reason: scaling_manager.take_reason().unwrap_or_default(),
+                                error,
+                            },
+                        );
+
                         if scaled_down > 0 {
                             slog::info!(log, "Scale down completed";
                                 "service" => service_name.as_str(),
@@ -234,33 +350,33 @@ pub async fn auto_scale(service_name: String) {
 
         // Handle configuration updates and scaling pause/resume
         tokio::select! {
-            _ = tokio::time::sleep(Duration::from_secs(10)) => {
+            _ = ticker.tick() => {
                 if scaling_paused {
                     slog::debug!(log, "Scaling is paused, skipping iteration";
                         "service" => service_name.as_str());
                 }
             }
-            Some((name, message)) = rx.recv() => {
-                if name == *service_name {
-                    match message {
-                        ScaleMessage::ConfigUpdate => {
-                            scaling_paused = true;
-                            slog::debug!(log, "Scaling paused for config update";
-                                "service" => service_name.as_str());
-                        }
-                        ScaleMessage::Resume => {
-                            scaling_paused = false;
-                            slog::debug!(log, "Scaling resumed";
-                                "service" => service_name.as_str());
-                        }
-                        ScaleMessage::RollingUpdate => {
-                            scaling_paused = true;
-                            slog::debug!(log, "Scaling paused for rolling update");
-                        }
-                        ScaleMessage::RollingUpdateComplete => {
-                            scaling_paused = false;
-                            slog::debug!(log, "Scaling resumed after rolling update");
-                        }
+            Some(message) = rx.recv() => {
+                match message {
+                    ScaleMessage::ConfigUpdate => {
+                        scaling_paused = true;
+                        slog::debug!(log, "Scaling paused for config update";
+                            "service" => service_name.as_str());
+                    }
+                    ScaleMessage::Resume => {
+                        scaling_paused = false;
+                        slog::debug!(log, "Scaling resumed";
+                            "service" => service_name.as_str());
+                    }
+                    ScaleMessage::RollingUpdate => {
+                        scaling_paused = true;
+                        slog::debug!(log, "Scaling paused for rolling update";
+                            "service" => service_name.as_str());
+                    }
+                    ScaleMessage::RollingUpdateComplete => {
+                        scaling_paused = false;
+                        slog::debug!(log, "Scaling resumed after rolling update";
+                            "service" => service_name.as_str());
                     }
                 }
             }
@@ -277,6 +393,10 @@ pub async fn scale_up(
     let instance_store = INSTANCE_STORE.get().unwrap();
     let server_backends = SERVER_BACKENDS.get().unwrap();
 
+    if stopped::is_stopped(&config) {
+        return Err(anyhow!("Service {} is stopped", service_name));
+    }
+
     // Check current instance count with read lock
     let current_instances = {
         let store = instance_store.read().await;
@@ -289,11 +409,13 @@ pub async fn scale_up(
     if current_instances >= config.instance_count.max as usize {
         return Ok(());
     }
+    preemption::ensure_capacity(&config).await?;
 
-    let pod_number = get_next_pod_number(service_name).await;
+    let pod_number = get_next_pod_number(service_name).await?;
 
+    let containers = rollback::containers_to_start(&config);
     let started_containers = runtime
-        .start_containers(service_name, pod_number, &config.spec.containers, &config)
+        .start_containers(service_name, pod_number.number(), &containers, &config)
         .await?;
 
     // Initialize health monitoring for new containers
@@ -333,7 +455,7 @@ pub async fn scale_up(
 
     // Get image hashes
     let mut image_hashes = HashMap::new();
-    for container in &config.spec.containers {
+    for container in &containers {
         if let Ok(hash) = runtime.get_image_digest(&container.image).await {
             image_hashes.insert(container.name.clone(), hash);
         }
@@ -353,6 +475,10 @@ pub async fn scale_up(
                 created_at: SystemTime::now(),
                 network: network_name.clone(),
                 image_hash: image_hashes,
+                annotations: config.annotations.clone(),
+                dns_names: dns::pod_dns_names(
+                    started_containers.iter().map(|(name, _, _)| name.as_str()),
+                ),
                 containers: started_containers
                     .iter()
                     .map(|(name, ip, ports)| ContainerMetadata {
@@ -365,10 +491,21 @@ pub async fn scale_up(
                     .collect(),
             },
         );
+        record_change(
+            service_name,
+            InstanceEvent::PodAdded {
+                instance: service_instances[&uuid].clone(),
+            },
+        );
     }
+    supervise_containers(service_name, &config, &started_containers);
+    lifecycle::register_hooks(service_name, &config, &started_containers);
 
-    // Add containers with node_ports to load balancer
+    // Add containers with node_ports to load balancer, once they are ready
     for (container_name, ip, port_metadata) in started_containers {
+        if !health::is_routable(&container_name).await {
+            continue;
+        }
         for port_info in port_metadata {
             if let Some(node_port) = port_info.node_port {
                 let proxy_key = format!("{}__{}", service_name, node_port);
@@ -378,7 +515,7 @@ pub async fn scale_up(
                 };
 
                 if let Some(backends) = backends {
-                    let addr = format!("{}:{}", ip, port_info.port);
+                    let addr = port_info.backend_address(&ip);
                     if let Ok(backend) = Backend::new(&addr) {
                         let mut backend_set = backends.write().await;
                         backend_set.insert(backend);
@@ -456,7 +593,7 @@ pub async fn scale_down(
                 };
 
                 if let Some(backends) = backends {
-                    let addr = format!("{}:{}", container.ip_address, port_info.port);
+                    let addr = port_info.backend_address(&container.ip_address);
                     if let Ok(backend) = Backend::new(&addr) {
                         let mut backend_set = backends.write().await;
                         backend_set.remove(&backend);
@@ -480,13 +617,18 @@ pub async fn scale_down(
     {
         let mut store = instance_store.write().await;
         if let Some(instances) = store.get_mut(service_name) {
-            instances.remove(&target_uuid);
+            if instances.remove(&target_uuid).is_some() {
+                record_change(
+                    service_name,
+                    InstanceEvent::PodRemoved { uuid: target_uuid },
+                );
+            }
         }
     }
 
     // Stop containers
     for container in &target_metadata.containers {
-        if let Err(e) = runtime.stop_container(&container.name).await {
+        if let Err(e) = lifecycle::stop_container(&runtime, &container.name).await {
             slog::error!(log, "Failed to stop container";
                 "service" => service_name,
                 "container" => &container.name,
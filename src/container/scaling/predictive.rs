@@ -0,0 +1,443 @@
+// src/container/scaling/predictive.rs
+//
+// Predictive scaling. The scaler records each service's pod count and CPU and
+// memory usage in five-minute slots, kept for a week in a JSON lines file in
+// the state directory. A service with `scaling_policy.predictive` is scaled up
+// ahead of peaks it had at the same time on previous days: the load expected
+// over the next `lead_time` is the average load at that time of day on the
+// days recorded, adjusted by how the last hour compares with the same hour on
+// those days, and the service gets the pods that load needs at
+// `target_cpu_percentage` each. The file is written off the runtime threads.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use super::state_file::StateFile;
+use crate::config::PodStats;
+
+static STATS_HISTORY: OnceLock<StatsStore> = OnceLock::new();
+
+const STATE_FILE: &str = "stats_history.jsonl";
+/// Length of a slot, in seconds
+pub const SLOT: u64 = 300;
+const SLOTS_PER_HOUR: u64 = 3600 / SLOT;
+const SLOTS_PER_DAY: u64 = 24 * SLOTS_PER_HOUR;
+const RETENTION_DAYS: u64 = 7;
+// A peak is predicted once it has been seen on this many days
+const MIN_DAYS: usize = 2;
+// The last hour can at most halve or double the load predicted from past days
+const MAX_TREND: f64 = 2.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PredictiveScaling {
+    /// How far ahead of a recurring peak pods are added
+    #[serde(with = "humantime_serde", default = "default_lead_time")]
+    pub lead_time: Duration,
+    /// CPU usage each pod should run at under the predicted load
+    #[serde(default = "default_target_cpu_percentage")]
+    pub target_cpu_percentage: f64,
+}
+
+fn default_lead_time() -> Duration {
+    Duration::from_secs(600)
+}
+
+fn default_target_cpu_percentage() -> f64 {
+    70.0
+}
+
+impl PredictiveScaling {
+    pub fn validate(&self) -> Result<()> {
+        if self.lead_time < Duration::from_secs(60)
+            || self.lead_time > Duration::from_secs(6 * 3600)
+        {
+            return Err(anyhow!("lead_time must be between 1m and 6h"));
+        }
+        if !(self.target_cpu_percentage > 0.0 && self.target_cpu_percentage <= 100.0) {
+            return Err(anyhow!(
+                "target_cpu_percentage must be above 0 and at most 100"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A service's average pod count and pod usage over one slot
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct StatsSample {
+    pub pods: f64,
+    pub cpu_percentage: f64,
+    pub memory_percentage: f64,
+}
+
+impl StatsSample {
+    // CPU used by all the pods, in percent of one pod
+    fn load(&self) -> f64 {
+        self.pods * self.cpu_percentage
+    }
+}
+
+/// The pods a service is expected to need soon, and what the estimate is from
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    pub instances: usize,
+    pub load: f64,
+    pub trend: f64,
+    pub days: usize,
+}
+
+// One line of the state file
+#[derive(Serialize, Deserialize)]
+struct SampleLine {
+    service_name: String,
+    slot: u64,
+    #[serde(flatten)]
+    sample: StatsSample,
+}
+
+#[derive(Debug, Default)]
+struct ServiceStats {
+    samples: BTreeMap<u64, StatsSample>,
+    // The slot being collected, the checks seen in it and their sums
+    current: Option<(u64, u32, StatsSample)>,
+}
+
+#[derive(Debug)]
+struct StatsStore {
+    file: Option<StateFile>,
+    state: Mutex<StatsState>,
+}
+
+#[derive(Debug, Default)]
+struct StatsState {
+    services: FxHashMap<String, ServiceStats>,
+    // Lines in the state file, compacted once well above what is retained
+    lines: usize,
+}
+
+pub fn initialize_stats_history(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let path = state_dir.join(STATE_FILE);
+
+    let mut state = StatsState::default();
+    if path.exists() {
+        for line in std::fs::read_to_string(&path)?.lines() {
+            // A line cut short by a crash is skipped rather than failing startup
+            let Ok(line) = serde_json::from_str::<SampleLine>(line) else {
+                continue;
+            };
+            state.push(line.service_name, line.slot, line.sample);
+        }
+    }
+
+    let store = StatsStore {
+        file: Some(StateFile::open(path)?),
+        state: Mutex::new(state),
+    };
+    store.compact(&mut store.state.lock().unwrap());
+
+    STATS_HISTORY
+        .set(store)
+        .map_err(|_| anyhow!("Stats history already initialized"))
+}
+
+fn stats_store() -> &'static StatsStore {
+    STATS_HISTORY.get_or_init(|| StatsStore {
+        file: None,
+        state: Mutex::new(StatsState::default()),
+    })
+}
+
+/// The samples of a service in the stats history of a state directory, by
+/// slot, e.g. for a simulation outside the daemon
+pub fn read_stats_history(
+    state_dir: &Path,
+    service_name: &str,
+) -> Result<BTreeMap<u64, StatsSample>> {
+    let path = state_dir.join(STATE_FILE);
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<SampleLine>(line).ok())
+        .filter(|line| line.service_name == service_name)
+        .map(|line| (line.slot, line.sample))
+        .collect())
+}
+
+/// Add past samples of a service to the history predictions are made from,
+/// without persisting them
+pub fn seed_stats(service_name: &str, samples: impl IntoIterator<Item = (u64, StatsSample)>) {
+    let mut state = stats_store().state.lock().unwrap();
+    for (slot, sample) in samples {
+        state.push(service_name.to_string(), slot, sample);
+    }
+}
+
+fn slot_of(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / SLOT
+}
+
+impl StatsState {
+    fn push(&mut self, service_name: String, slot: u64, sample: StatsSample) {
+        let samples = &mut self.services.entry(service_name).or_default().samples;
+        samples.insert(slot, sample);
+        let oldest = slot.saturating_sub(RETENTION_DAYS * SLOTS_PER_DAY);
+        while samples
+            .first_key_value()
+            .is_some_and(|(&first, _)| first < oldest)
+        {
+            samples.pop_first();
+        }
+    }
+
+    fn retained(&self) -> usize {
+        self.services
+            .values()
+            .map(|stats| stats.samples.len())
+            .sum()
+    }
+}
+
+impl StatsStore {
+    fn append(&self, state: &mut StatsState, line: &SampleLine) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        if let Ok(json) = serde_json::to_string(line) {
+            file.append(json);
+            state.lines += 1;
+        }
+    }
+
+    // Rewrite the state file with only the retained samples
+    fn compact(&self, state: &mut StatsState) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let mut lines = Vec::new();
+        for (service_name, stats) in &state.services {
+            for (&slot, &sample) in &stats.samples {
+                let line = SampleLine {
+                    service_name: service_name.clone(),
+                    slot,
+                    sample,
+                };
+                if let Ok(json) = serde_json::to_string(&line) {
+                    lines.push(json);
+                }
+            }
+        }
+
+        state.lines = lines.len();
+        file.replace(lines);
+    }
+}
+
+/// Add the pod count and stats of a scaling check at `now` to the service's
+/// current slot, persisting the previous slot once it is over
+pub fn record_stats(
+    service_name: &str,
+    pods: usize,
+    pod_stats: &HashMap<Uuid, PodStats>,
+    now: SystemTime,
+) {
+    // Pods without stats yet would read as idle
+    if pods > 0 && pod_stats.is_empty() {
+        return;
+    }
+    let count = pod_stats.len().max(1) as f64;
+    let sample = StatsSample {
+        pods: pods as f64,
+        cpu_percentage: pod_stats
+            .values()
+            .map(|stats| stats.cpu_percentage)
+            .sum::<f64>()
+            / count,
+        memory_percentage: pod_stats
+            .values()
+            .filter(|stats| stats.memory_limit > 0)
+            .map(|stats| stats.memory_usage as f64 / stats.memory_limit as f64 * 100.0)
+            .sum::<f64>()
+            / count,
+    };
+    let slot = slot_of(now);
+
+    let store = stats_store();
+    let mut state = store.state.lock().unwrap();
+    let stats = state.services.entry(service_name.to_string()).or_default();
+    let finished = match &mut stats.current {
+        Some((current, checks, sums)) if *current == slot => {
+            *checks += 1;
+            sums.pods += sample.pods;
+            sums.cpu_percentage += sample.cpu_percentage;
+            sums.memory_percentage += sample.memory_percentage;
+            None
+        }
+        current => current.replace((slot, 1, sample)),
+    };
+    let Some((slot, checks, sums)) = finished else {
+        return;
+    };
+
+    let checks = f64::from(checks);
+    let line = SampleLine {
+        service_name: service_name.to_string(),
+        slot,
+        sample: StatsSample {
+            pods: sums.pods / checks,
+            cpu_percentage: sums.cpu_percentage / checks,
+            memory_percentage: sums.memory_percentage / checks,
+        },
+    };
+    store.append(&mut state, &line);
+    state.push(line.service_name, line.slot, line.sample);
+
+    if state.lines > 2 * state.retained() + SLOTS_PER_DAY as usize {
+        store.compact(&mut state);
+    }
+}
+
+/// The pods the service is expected to need within `lead_time` of `now`,
+/// from its load at that time of day on previous days. None until the
+/// coming slots have samples from at least two days.
+pub fn predict(
+    service_name: &str,
+    config: &PredictiveScaling,
+    now: SystemTime,
+) -> Option<Prediction> {
+    let state = stats_store().state.lock().unwrap();
+    let samples = &state.services.get(service_name)?.samples;
+
+    // The average load in this slot of the day, and the days it is from
+    let seasonal = |slot: u64| -> Option<(f64, usize)> {
+        let loads: Vec<f64> = (1..=RETENTION_DAYS)
+            .filter_map(|day| slot.checked_sub(day * SLOTS_PER_DAY))
+            .filter_map(|slot| samples.get(&slot))
+            .map(StatsSample::load)
+            .collect();
+        (!loads.is_empty()).then(|| (loads.iter().sum::<f64>() / loads.len() as f64, loads.len()))
+    };
+
+    // The peak of the coming slots, so pods stay until it has passed
+    let now = slot_of(now);
+    let ahead = config.lead_time.as_secs().div_ceil(SLOT);
+    let (forecast, days) = (now..=now + ahead)
+        .filter_map(seasonal)
+        .filter(|(_, days)| *days >= MIN_DAYS)
+        .max_by(|a, b| a.0.total_cmp(&b.0))?;
+
+    // How the last hour compares with the same hour on previous days
+    let (recent, usual) = (now.saturating_sub(SLOTS_PER_HOUR)..now)
+        .filter_map(|slot| Some((samples.get(&slot)?.load(), seasonal(slot)?.0)))
+        .fold((0.0, 0.0), |(recent, usual), (load, seasonal)| {
+            (recent + load, usual + seasonal)
+        });
+    let trend = if usual > 0.0 && recent > 0.0 {
+        (recent / usual).clamp(1.0 / MAX_TREND, MAX_TREND)
+    } else {
+        1.0
+    };
+
+    let load = forecast * trend;
+    Some(Prediction {
+        instances: (load / config.target_cpu_percentage).ceil() as usize,
+        load,
+        trend,
+        days,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 20_000 * SLOTS_PER_DAY + 100;
+
+    fn config() -> PredictiveScaling {
+        PredictiveScaling {
+            lead_time: Duration::from_secs(600),
+            target_cpu_percentage: 50.0,
+        }
+    }
+
+    fn sample(pods: f64, cpu_percentage: f64) -> StatsSample {
+        StatsSample {
+            pods,
+            cpu_percentage,
+            memory_percentage: 0.0,
+        }
+    }
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(NOW * SLOT)
+    }
+
+    #[test]
+    fn test_predict_needs_two_days() {
+        seed_stats(
+            "predict-one-day",
+            [(NOW - SLOTS_PER_DAY, sample(2.0, 50.0))],
+        );
+        assert!(predict("predict-one-day", &config(), now()).is_none());
+        assert!(predict("predict-unknown", &config(), now()).is_none());
+    }
+
+    #[test]
+    fn test_predict_upcoming_peak() {
+        // A peak two slots ahead, within the lead time, on the last two days
+        seed_stats(
+            "predict-peak",
+            (1..=2).flat_map(|day| {
+                [
+                    (NOW - day * SLOTS_PER_DAY, sample(2.0, 50.0)),
+                    (NOW + 2 - day * SLOTS_PER_DAY, sample(4.0, 75.0)),
+                    (NOW + 3 - day * SLOTS_PER_DAY, sample(8.0, 100.0)),
+                ]
+            }),
+        );
+
+        let prediction = predict("predict-peak", &config(), now()).unwrap();
+        assert_eq!(prediction.days, 2);
+        assert_eq!(prediction.trend, 1.0);
+        assert_eq!(prediction.load, 300.0);
+        assert_eq!(prediction.instances, 6);
+    }
+
+    #[test]
+    fn test_predict_follows_the_last_hour() {
+        let usual = (1..=2).flat_map(|day| {
+            (NOW - SLOTS_PER_HOUR..=NOW + 2)
+                .map(move |slot| (slot - day * SLOTS_PER_DAY, sample(2.0, 50.0)))
+        });
+        seed_stats("predict-busy", usual.clone());
+        seed_stats("predict-quiet", usual);
+        // The last hour ran at three and a half times the usual load, and a
+        // tenth of it
+        seed_stats(
+            "predict-busy",
+            (NOW - SLOTS_PER_HOUR..NOW).map(|slot| (slot, sample(7.0, 50.0))),
+        );
+        seed_stats(
+            "predict-quiet",
+            (NOW - SLOTS_PER_HOUR..NOW).map(|slot| (slot, sample(1.0, 10.0))),
+        );
+
+        let busy = predict("predict-busy", &config(), now()).unwrap();
+        assert_eq!(busy.trend, MAX_TREND);
+        assert_eq!(busy.instances, 4);
+        let quiet = predict("predict-quiet", &config(), now()).unwrap();
+        assert_eq!(quiet.trend, 1.0 / MAX_TREND);
+        assert_eq!(quiet.instances, 1);
+    }
+}
@@ -0,0 +1,67 @@
+// src/container/scaling/rps.rs
+//
+// Request rates, for traffic-based scaling. The proxy counts the requests each
+// service receives, and the scaler turns the count into requests per second
+// each time it evaluates a service with `scaling_policy.target_rps_per_instance`,
+// so the rate covers the time since its last evaluation.
+use rustc_hash::FxHashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+static REQUEST_COUNTS: OnceLock<Mutex<FxHashMap<String, RequestCount>>> = OnceLock::new();
+
+// Shorter windows give too noisy a rate to scale on
+const MIN_WINDOW: Duration = Duration::from_secs(1);
+
+struct RequestCount {
+    requests: u64,
+    since: Instant,
+}
+
+fn request_counts() -> &'static Mutex<FxHashMap<String, RequestCount>> {
+    REQUEST_COUNTS.get_or_init(|| Mutex::new(FxHashMap::default()))
+}
+
+/// Count a request to the service
+pub fn record_request(service_name: &str) {
+    let mut counts = request_counts().lock().unwrap();
+    match counts.get_mut(service_name) {
+        Some(count) => count.requests += 1,
+        None => {
+            counts.insert(
+                service_name.to_string(),
+                RequestCount {
+                    requests: 1,
+                    since: Instant::now(),
+                },
+            );
+        }
+    }
+}
+
+/// The service's requests per second since the last call, starting a new
+/// window. None for the first call, or when the window is too short.
+pub fn take_rate(service_name: &str) -> Option<f64> {
+    let now = Instant::now();
+    let mut counts = request_counts().lock().unwrap();
+    let Some(count) = counts.get_mut(service_name) else {
+        counts.insert(
+            service_name.to_string(),
+            RequestCount {
+                requests: 0,
+                since: now,
+            },
+        );
+        return None;
+    };
+    let window = now.duration_since(count.since);
+    if window < MIN_WINDOW {
+        return None;
+    }
+    let rate = count.requests as f64 / window.as_secs_f64();
+    *count = RequestCount {
+        requests: 0,
+        since: now,
+    };
+    Some(rate)
+}
@@ -0,0 +1,269 @@
+// src/container/scaling/schedules.rs
+//
+// Schedule-based scaling. Each entry of `scaling_policy.schedules` opens a
+// window at the times its cron expression matches, for `duration`, and
+// overrides the service's instance count bounds while the window is open, so
+// a service can be pre-scaled for traffic it is known to get. Cron
+// expressions have the usual five fields and are evaluated in UTC.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::InstanceCount;
+use crate::container::dates::civil_from_days;
+
+// Windows are found by checking every minute they could have opened in
+const MAX_WINDOW: Duration = Duration::from_secs(7 * 24 * 3600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledScaling {
+    /// "minute hour day-of-month month day-of-week", in UTC
+    pub cron: String,
+    /// How long the window stays open each time the expression matches
+    #[serde(with = "humantime_serde")]
+    pub duration: Duration,
+    /// Overrides `instance_count.min` while the window is open
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<u8>,
+    /// Overrides `instance_count.max` while the window is open
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<u8>,
+}
+
+impl ScheduledScaling {
+    pub fn validate(&self) -> Result<()> {
+        CronExpression::parse(&self.cron)?;
+        if self.duration < Duration::from_secs(60) || self.duration > MAX_WINDOW {
+            return Err(anyhow!("duration must be between 1m and 7days"));
+        }
+        if self.min.is_none() && self.max.is_none() {
+            return Err(anyhow!("schedule '{}' sets neither min nor max", self.cron));
+        }
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Err(anyhow!("min {} is greater than max {}", min, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the expression matched within the last `duration` before `now`
+    pub fn is_active(&self, now: SystemTime) -> bool {
+        let Ok(cron) = CronExpression::parse(&self.cron) else {
+            return false;
+        };
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 60;
+        let minutes = self.duration.as_secs() / 60;
+        (now.saturating_sub(minutes.saturating_sub(1))..=now).any(|minute| cron.matches(minute))
+    }
+}
+
+/// The instance count bounds in effect: those of the first open schedule
+/// window, falling back to `base` for bounds it doesn't override
+pub fn scheduled_instance_count(
+    schedules: &[ScheduledScaling],
+    base: &InstanceCount,
+    now: SystemTime,
+) -> InstanceCount {
+    let Some(schedule) = schedules.iter().find(|schedule| schedule.is_active(now)) else {
+        return base.clone();
+    };
+    let min = schedule.min.unwrap_or(base.min);
+    let max = schedule.max.unwrap_or(base.max);
+    InstanceCount {
+        min: min.min(max),
+        max,
+    }
+}
+
+// A parsed five-field cron expression, each field a bit set of the values it matches
+#[derive(Debug)]
+struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Whether the day-of-month and day-of-week fields were restricted
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronExpression {
+    fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(anyhow!(
+                "cron expression '{}' must have 5 fields",
+                expression
+            ));
+        };
+        let mut weekday_bits = parse_field(weekdays, 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            days_restricted: days != "*",
+            weekdays_restricted: weekdays != "*",
+        })
+    }
+
+    // Whether the expression matches the minute, counted from the Unix epoch
+    fn matches(&self, minute: u64) -> bool {
+        let days = (minute / 1440) as i64;
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday
+        let weekday = (days + 4).rem_euclid(7);
+        let bit = |set: u64, value: i64| set & (1 << value) != 0;
+
+        let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+            // As in cron, restricting both matches either
+            (true, true) => bit(self.days, day) || bit(self.weekdays, weekday),
+            _ => bit(self.days, day) && bit(self.weekdays, weekday),
+        };
+        bit(self.minutes, (minute % 60) as i64)
+            && bit(self.hours, (minute / 60 % 24) as i64)
+            && bit(self.months, month)
+            && day_matches
+    }
+}
+
+// One field: "*", "5", "1-5", "*/15", "0-30/10" or a comma-separated list of those
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64> {
+    let invalid = || anyhow!("invalid cron field '{}'", field);
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    // "5/15" runs from 5 to the end of the range
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::dates::days_from_civil;
+
+    // Minutes since the epoch at a UTC date and time
+    fn minute(year: i64, month: i64, day: i64, hour: u64, min: u64) -> u64 {
+        days_from_civil(year, month, day) as u64 * 1440 + hour * 60 + min
+    }
+
+    fn cron(expression: &str) -> CronExpression {
+        CronExpression::parse(expression).unwrap()
+    }
+
+    #[test]
+    fn test_cron_ranges_and_lists() {
+        let business_hours = cron("0,30 9-17 * * *");
+        assert!(business_hours.matches(minute(2024, 1, 3, 9, 0)));
+        assert!(business_hours.matches(minute(2024, 1, 3, 17, 30)));
+        assert!(!business_hours.matches(minute(2024, 1, 3, 8, 30)));
+        assert!(!business_hours.matches(minute(2024, 1, 3, 18, 0)));
+        assert!(!business_hours.matches(minute(2024, 1, 3, 9, 15)));
+    }
+
+    #[test]
+    fn test_cron_steps() {
+        let quarter_hours = cron("*/15 * * * *");
+        for min in [0, 15, 30, 45] {
+            assert!(quarter_hours.matches(minute(2024, 1, 3, 12, min)));
+        }
+        assert!(!quarter_hours.matches(minute(2024, 1, 3, 12, 10)));
+
+        let from_five = cron("5/20 * * * *");
+        assert!(from_five.matches(minute(2024, 1, 3, 12, 45)));
+        assert!(!from_five.matches(minute(2024, 1, 3, 12, 0)));
+
+        let first_half = cron("0-30/10 * * * *");
+        assert!(first_half.matches(minute(2024, 1, 3, 12, 30)));
+        assert!(!first_half.matches(minute(2024, 1, 3, 12, 40)));
+    }
+
+    #[test]
+    fn test_cron_sunday_is_0_and_7() {
+        // 2024-01-07 was a Sunday
+        for expression in ["0 0 * * 0", "0 0 * * 7", "0 0 * * 6-7"] {
+            let sundays = cron(expression);
+            assert!(sundays.matches(minute(2024, 1, 7, 0, 0)), "{}", expression);
+            assert!(!sundays.matches(minute(2024, 1, 8, 0, 0)), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn test_cron_day_of_month_or_day_of_week() {
+        // Restricting both matches either: the 1st, and every Monday
+        let either = cron("0 0 1 * 1");
+        assert!(either.matches(minute(2024, 1, 1, 0, 0)));
+        assert!(either.matches(minute(2024, 2, 1, 0, 0)));
+        assert!(either.matches(minute(2024, 1, 8, 0, 0)));
+        assert!(!either.matches(minute(2024, 1, 2, 0, 0)));
+
+        // Otherwise only the restricted one applies
+        let first = cron("0 0 1 * *");
+        assert!(first.matches(minute(2024, 2, 1, 0, 0)));
+        assert!(!first.matches(minute(2024, 1, 8, 0, 0)));
+    }
+
+    #[test]
+    fn test_cron_rejects_invalid_expressions() {
+        for expression in [
+            "60 * * * *",
+            "* * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * * * 8",
+            "* * 0 * *",
+            "a * * * *",
+        ] {
+            assert!(CronExpression::parse(expression).is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn test_schedule_window() {
+        let schedule = ScheduledScaling {
+            cron: "0 9 * * *".to_string(),
+            duration: Duration::from_secs(3600),
+            min: Some(4),
+            max: None,
+        };
+        let at = |hour, min| UNIX_EPOCH + Duration::from_secs(minute(2024, 1, 3, hour, min) * 60);
+        assert!(schedule.is_active(at(9, 0)));
+        assert!(schedule.is_active(at(9, 59)));
+        assert!(!schedule.is_active(at(10, 0)));
+        assert!(!schedule.is_active(at(8, 59)));
+
+        let base = InstanceCount { min: 1, max: 3 };
+        let count = scheduled_instance_count(&[schedule], &base, at(9, 30));
+        assert_eq!((count.min, count.max), (3, 3));
+    }
+}
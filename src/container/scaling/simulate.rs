@@ -0,0 +1,408 @@
+// src/container/scaling/simulate.rs
+//
+// Offline simulation of the scaler, for tuning a scaling policy before it
+// goes live. The stats the daemon records for predictive scaling, a week of
+// five-minute samples, are replayed through the UnifiedScalingManager with a
+// candidate policy, one check per scaling interval on a simulated clock, so
+// the same recording and policy always give the same result. The load of each
+// sample is spread evenly over the simulated pods, and the report shows when
+// the pod count would have changed and how long per-pod CPU would have been
+// over the SLO, next to what was recorded.
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use super::history::{ScalingDirection, ScalingEvent};
+use super::manager::{ScalingDecision, ScalingPolicy, UnifiedScalingManager};
+use super::predictive::{self, StatsSample, SLOT};
+use super::SCALING_INTERVAL;
+use crate::config::validate::validate_scaling_policy;
+use crate::config::{config_files, read_yaml_config, PodStats, ServiceConfig};
+
+// Samples only hold memory usage as a percentage, so pods get a nominal limit
+const MEMORY_LIMIT: u64 = 1 << 30;
+// Per-pod CPU the SLO is measured against, without a CPU threshold
+const DEFAULT_SLO_CPU_PERCENTAGE: f64 = 80.0;
+
+#[derive(Debug, Clone)]
+pub struct SimulationOptions {
+    /// How much of the recording to replay, up to its end. Earlier samples
+    /// are the history predictive scaling starts from.
+    pub window: Duration,
+    /// Per-pod CPU usage above which the SLO counts as missed (default: the
+    /// service's CPU threshold)
+    pub slo_cpu_percentage: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationReport {
+    pub service: String,
+    #[serde(with = "humantime_serde")]
+    pub from: SystemTime,
+    #[serde(with = "humantime_serde")]
+    pub to: SystemTime,
+    pub checks: usize,
+    /// Every change of the pod count, with the policy that made it
+    pub timeline: Vec<ScalingEvent>,
+    pub min_pods: usize,
+    pub max_pods: usize,
+    pub pod_hours: f64,
+    pub recorded_pod_hours: f64,
+    pub slo: SloImpact,
+    /// Parts of the policy the recording has no data for, left out of the replay
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignored: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SloImpact {
+    pub cpu_percentage: f64,
+    /// Time with per-pod CPU over the SLO, under the candidate policy
+    pub minutes_over: f64,
+    pub percentage_over: f64,
+    pub peak_cpu_percentage: f64,
+    /// The same, with the pods that were actually running
+    pub recorded_minutes_over: f64,
+    pub recorded_percentage_over: f64,
+}
+
+/// Replay the samples through a scaler with the config's scaling policy
+pub async fn simulate(
+    config: &ServiceConfig,
+    samples: &BTreeMap<u64, StatsSample>,
+    options: &SimulationOptions,
+) -> Result<SimulationReport> {
+    let (&last_slot, _) = samples
+        .last_key_value()
+        .ok_or_else(|| anyhow!("No recorded stats for service {}", config.name))?;
+    // At least the last sample is replayed, however short the window
+    let window_slots = (options.window.as_secs() / SLOT).max(1);
+    let first_slot = last_slot.saturating_sub(window_slots) + 1;
+    let (config, ignored) = simulated_config(config)?;
+
+    // Predictions need the days before the window
+    predictive::seed_stats(
+        &config.name,
+        samples
+            .range(..first_slot)
+            .map(|(&slot, &sample)| (slot, sample)),
+    );
+    let replayed: Vec<(u64, StatsSample)> = samples
+        .range(first_slot..)
+        .map(|(&slot, &sample)| (slot, sample))
+        .collect();
+
+    let slo_cpu_percentage = options.slo_cpu_percentage.unwrap_or_else(|| {
+        config
+            .resource_thresholds
+            .as_ref()
+            .and_then(|thresholds| thresholds.cpu_percentage)
+            .map_or(DEFAULT_SLO_CPU_PERCENTAGE, f64::from)
+    });
+    let interval = SCALING_INTERVAL.as_secs();
+    let slot_time = |slot: u64| UNIX_EPOCH + Duration::from_secs(slot * SLOT);
+
+    let from = slot_time(replayed[0].0);
+    let mut manager = UnifiedScalingManager::simulated(config.name.clone(), config.clone(), from);
+    let bounds = manager.instance_bounds();
+    let mut pods =
+        (replayed[0].1.pods.round() as usize).clamp(bounds.min as usize, bounds.max as usize);
+
+    let mut report = SimulationReport {
+        service: config.name.clone(),
+        from,
+        to: slot_time(last_slot + 1),
+        checks: 0,
+        timeline: Vec::new(),
+        min_pods: pods,
+        max_pods: pods,
+        pod_hours: 0.0,
+        recorded_pod_hours: 0.0,
+        slo: SloImpact {
+            cpu_percentage: slo_cpu_percentage,
+            minutes_over: 0.0,
+            percentage_over: 0.0,
+            peak_cpu_percentage: 0.0,
+            recorded_minutes_over: 0.0,
+            recorded_percentage_over: 0.0,
+        },
+        ignored,
+    };
+
+    for (slot, sample) in &replayed {
+        report.recorded_pod_hours += sample.pods * SLOT as f64 / 3600.0;
+        if sample.cpu_percentage > slo_cpu_percentage {
+            report.slo.recorded_minutes_over += SLOT as f64 / 60.0;
+        }
+
+        for check in 0..SLOT / interval {
+            let time = slot_time(*slot) + Duration::from_secs(check * interval);
+            manager.advance_to(time);
+            let bounds = manager.instance_bounds();
+
+            // The recorded load, shared by the simulated pods
+            let cpu_percentage = sample.pods * sample.cpu_percentage / pods.max(1) as f64;
+            let memory_percentage = sample.pods * sample.memory_percentage / pods.max(1) as f64;
+            let pod_stats: HashMap<Uuid, PodStats> = (0..pods)
+                .map(|pod| {
+                    let stats = PodStats {
+                        cpu_percentage,
+                        cpu_percentage_relative: cpu_percentage,
+                        memory_usage: (MEMORY_LIMIT as f64 * memory_percentage / 100.0) as u64,
+                        memory_limit: MEMORY_LIMIT,
+                        gpu_percentage: 0.0,
+                    };
+                    (Uuid::from_u128(pod as u128), stats)
+                })
+                .collect();
+
+            report.checks += 1;
+            report.pod_hours += (pods as u64 * interval) as f64 / 3600.0;
+            let over = if pods == 0 {
+                sample.cpu_percentage > 0.0
+            } else {
+                cpu_percentage > slo_cpu_percentage
+            };
+            if over {
+                report.slo.minutes_over += interval as f64 / 60.0;
+            }
+            report.slo.peak_cpu_percentage = report.slo.peak_cpu_percentage.max(cpu_percentage);
+
+            let to = match manager.evaluate(pods, &pod_stats).await {
+                ScalingDecision::ScaleUp(n) => pods + n as usize,
+                ScalingDecision::ScaleDown(n) => {
+                    pods - (n as usize).min(pods.saturating_sub(bounds.min as usize))
+                }
+                ScalingDecision::NoChange => pods,
+            };
+            let reason = manager.take_reason().unwrap_or_default();
+            if to == pods {
+                continue;
+            }
+            report.timeline.push(ScalingEvent {
+                timestamp: time,
+                direction: if to > pods {
+                    ScalingDirection::Up
+                } else {
+                    ScalingDirection::Down
+                },
+                from: pods,
+                to,
+                reason,
+                error: None,
+            });
+            pods = to;
+            report.min_pods = report.min_pods.min(pods);
+            report.max_pods = report.max_pods.max(pods);
+        }
+    }
+
+    let total_minutes = (report.checks as u64 * interval) as f64 / 60.0;
+    report.slo.percentage_over = report.slo.minutes_over / total_minutes * 100.0;
+    report.slo.recorded_percentage_over = report.slo.recorded_minutes_over / total_minutes * 100.0;
+    Ok(report)
+}
+
+// The config to replay with, and the parts of its policy left out because
+// the recording has no requests, latencies or queue lengths to drive them
+fn simulated_config(config: &ServiceConfig) -> Result<(ServiceConfig, Vec<String>)> {
+    let mut config = config.clone();
+    let mut ignored = Vec::new();
+    if config.codel.take().is_some() {
+        ignored.push("codel".to_string());
+    }
+    if let Some(policy) = config.scaling_policy.as_mut() {
+        if policy.vertical.is_some() {
+            return Err(anyhow!(
+                "Service {} scales vertically, so its pod count is fixed",
+                config.name
+            ));
+        }
+        if policy.idle_timeout.take().is_some() {
+            ignored.push("idle_timeout".to_string());
+        }
+        if policy.target_rps_per_instance.take().is_some() {
+            ignored.push("target_rps_per_instance".to_string());
+        }
+        if policy.latency_slo.take().is_some() {
+            ignored.push("latency_slo".to_string());
+        }
+        if !std::mem::take(&mut policy.triggers).is_empty() {
+            ignored.push("triggers".to_string());
+        }
+        if policy.webhook.take().is_some() {
+            ignored.push("webhook".to_string());
+        }
+    }
+    Ok((config, ignored))
+}
+
+/// `orbit simulate`: replay the service's recorded stats from the state
+/// directory with its config, or the candidate config file, and the candidate
+/// scaling policy if given, and print the report
+pub async fn run(
+    config_dir: &Path,
+    state_dir: &Path,
+    service_name: &str,
+    config_file: Option<&Path>,
+    policy_file: Option<&Path>,
+    options: &SimulationOptions,
+    json: bool,
+) -> Result<()> {
+    let mut config = match config_file {
+        Some(path) => read_yaml_config(&path.to_path_buf(), None).await?,
+        None => find_config(config_dir, service_name).await?,
+    };
+    if config.name != service_name {
+        return Err(anyhow!(
+            "{} is the config of service {}, not {}",
+            config_file.unwrap_or(config_dir).display(),
+            config.name,
+            service_name
+        ));
+    }
+    if let Some(path) = policy_file {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let policy: ScalingPolicy = serde_yaml::from_str(&contents)
+            .map_err(|e| anyhow!("Invalid scaling policy in {}: {}", path.display(), e))?;
+        config.scaling_policy = Some(policy);
+        validate_scaling_policy(&config)?;
+    }
+
+    let samples = predictive::read_stats_history(state_dir, service_name)?;
+    let report = simulate(&config, &samples, options).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    Ok(())
+}
+
+async fn find_config(config_dir: &Path, service_name: &str) -> Result<ServiceConfig> {
+    for path in config_files(config_dir)? {
+        if let Ok(config) = read_yaml_config(&path, None).await {
+            if config.name == service_name {
+                return Ok(config);
+            }
+        }
+    }
+    Err(anyhow!(
+        "No valid config for service {} in {}",
+        service_name,
+        config_dir.display()
+    ))
+}
+
+fn print_report(report: &SimulationReport) {
+    let time = |time: SystemTime| humantime::format_rfc3339_seconds(time).to_string();
+    println!(
+        "{}: {} to {}, {} checks",
+        report.service,
+        time(report.from),
+        time(report.to),
+        report.checks
+    );
+    for event in &report.timeline {
+        println!(
+            "  {}  {} -> {} pods ({})",
+            time(event.timestamp),
+            event.from,
+            event.to,
+            event.reason.policy
+        );
+    }
+    println!(
+        "pods: {} to {}, {:.1} pod-hours (recorded: {:.1})",
+        report.min_pods, report.max_pods, report.pod_hours, report.recorded_pod_hours
+    );
+    println!(
+        "CPU over {}%: {:.0} minutes, {:.1}% of the time (recorded: {:.0} minutes, {:.1}%), peak {:.1}%",
+        report.slo.cpu_percentage,
+        report.slo.minutes_over,
+        report.slo.percentage_over,
+        report.slo.recorded_minutes_over,
+        report.slo.recorded_percentage_over,
+        report.slo.peak_cpu_percentage
+    );
+    if !report.ignored.is_empty() {
+        println!("not simulated: {}", report.ignored.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ServiceConfig {
+        serde_yaml::from_str(
+            r#"
+name: simulated_service
+instance_count:
+  min: 1
+  max: 10
+resource_thresholds:
+  cpu_percentage: 70
+  cpu_percentage_relative: 80
+  memory_percentage: 75
+scaling_policy:
+  cooldown_duration: 60s
+  scale_down_threshold_percentage: 50
+spec:
+  containers: []
+"#,
+        )
+        .unwrap()
+    }
+
+    // An hour at 90% CPU on 2 pods, then an hour at 10%
+    fn samples(first_slot: u64) -> BTreeMap<u64, StatsSample> {
+        (0..24)
+            .map(|slot| {
+                let sample = StatsSample {
+                    pods: 2.0,
+                    cpu_percentage: if slot < 12 { 90.0 } else { 10.0 },
+                    memory_percentage: 20.0,
+                };
+                (first_slot + slot, sample)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_simulation_replays_deterministically() {
+        let samples = samples(6_000_000);
+        let options = SimulationOptions {
+            window: Duration::from_secs(2 * 3600),
+            slo_cpu_percentage: None,
+        };
+
+        let first = simulate(&config(), &samples, &options).await.unwrap();
+        let second = simulate(&config(), &samples, &options).await.unwrap();
+        assert_eq!(first.checks, 24 * 30);
+        assert!(first.max_pods > 2);
+        assert!(first.slo.minutes_over < first.slo.recorded_minutes_over);
+        assert_eq!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_window_shorter_than_a_sample() {
+        let options = SimulationOptions {
+            window: Duration::from_secs(60),
+            slo_cpu_percentage: None,
+        };
+
+        let report = simulate(&config(), &samples(7_000_000), &options)
+            .await
+            .unwrap();
+        assert_eq!(report.checks, 30);
+    }
+}
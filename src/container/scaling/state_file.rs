@@ -0,0 +1,111 @@
+// src/container/scaling/state_file.rs
+//
+// A JSON lines file in the state directory, written by its own thread so that
+// recording scaling events and stats never blocks the runtime on disk I/O.
+// Lines are appended as they are recorded, and the file is rewritten with only
+// the retained lines when it is compacted. Writes happen in the order they
+// were sent.
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+enum Op {
+    Append(String),
+    Replace(Vec<String>),
+}
+
+#[derive(Debug)]
+pub struct StateFile {
+    sender: mpsc::Sender<Op>,
+}
+
+impl StateFile {
+    /// Start the writer thread of the file at `path`
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let name = format!(
+            "orbit-{}",
+            path.file_stem().and_then(|s| s.to_str()).unwrap_or("state")
+        );
+        std::thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                for op in receiver {
+                    let (result, action) = match op {
+                        Op::Append(line) => (append(&path, &line), "append to"),
+                        Op::Replace(lines) => (replace(&path, &lines), "compact"),
+                    };
+                    if let Err(e) = result {
+                        slog::error!(slog_scope::logger(), "Failed to write state file";
+                            "path" => path.display().to_string(),
+                            "action" => action,
+                            "error" => e.to_string()
+                        );
+                    }
+                }
+            })
+            .map_err(|e| anyhow!("Failed to start the state file writer: {}", e))?;
+        Ok(StateFile { sender })
+    }
+
+    /// Queue a line to be appended
+    pub fn append(&self, line: String) {
+        let _ = self.sender.send(Op::Append(line));
+    }
+
+    /// Queue a rewrite of the file with only these lines
+    pub fn replace(&self, lines: Vec<String>) {
+        let _ = self.sender.send(Op::Replace(lines));
+    }
+}
+
+fn append(path: &Path, line: &str) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+fn replace(path: &Path, lines: &[String]) -> Result<()> {
+    let tmp = path.with_extension("jsonl.tmp");
+    let mut contents = lines.join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_in_order() {
+        let dir = std::env::temp_dir().join(format!("orbit-state-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.jsonl");
+
+        let file = StateFile::open(path.clone()).unwrap();
+        file.append("1".to_string());
+        file.replace(vec!["2".to_string(), "3".to_string()]);
+        file.append("4".to_string());
+        // Dropping the sender lets the writer finish and exit
+        drop(file);
+
+        let mut contents = String::new();
+        for _ in 0..100 {
+            contents = std::fs::read_to_string(&path).unwrap_or_default();
+            if contents == "2\n3\n4\n" {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(contents, "2\n3\n4\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
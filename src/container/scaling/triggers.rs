@@ -0,0 +1,276 @@
+// src/container/scaling/triggers.rs
+//
+// Queue triggers, KEDA-style. Each entry of `scaling_policy.triggers` reads
+// how much work is waiting for the service: the length of a Redis list, the
+// messages in a RabbitMQ queue from its management API, or a Kafka consumer
+// group's lag from a Kafka REST Proxy. The scaler polls them on every check
+// and scales to the instances the longest queue needs at `target` items
+// each. A trigger that can't be read is left out of the decision.
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+// Per trigger, so one slow source doesn't hold up the scaler for long
+const POLL_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScalingTrigger {
+    #[serde(flatten)]
+    pub source: TriggerSource,
+    /// Queued items or lag each instance should handle
+    pub target: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerSource {
+    Redis {
+        /// "host:port" of the Redis server
+        address: String,
+        /// The list whose length is the queue
+        list: String,
+        #[serde(default)]
+        database: u32,
+        /// Env var of Orbit's holding the password
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password_env: Option<String>,
+    },
+    Rabbitmq {
+        /// Base URL of the management API, e.g. "http://rabbitmq:15672"
+        url: String,
+        #[serde(default = "default_vhost")]
+        vhost: String,
+        queue: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password_env: Option<String>,
+    },
+    Kafka {
+        /// Base URL of a Kafka REST Proxy with the v3 API
+        rest_url: String,
+        cluster: String,
+        /// The consumer group whose total lag is the queue
+        group: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password_env: Option<String>,
+    },
+}
+
+fn default_vhost() -> String {
+    "/".to_string()
+}
+
+/// A trigger's queue length as last polled
+#[derive(Debug, Clone)]
+pub struct TriggerReading {
+    pub name: String,
+    pub value: f64,
+    pub target: f64,
+}
+
+impl ScalingTrigger {
+    pub fn validate(&self) -> Result<()> {
+        if !self.target.is_finite() || self.target <= 0.0 {
+            return Err(anyhow!("target must be a positive number"));
+        }
+        match &self.source {
+            TriggerSource::Redis { address, list, .. } => {
+                if address.is_empty() || list.is_empty() {
+                    return Err(anyhow!("redis triggers need an address and a list"));
+                }
+            }
+            TriggerSource::Rabbitmq { url, queue, .. } => {
+                http_url(url)?;
+                if queue.is_empty() {
+                    return Err(anyhow!("rabbitmq triggers need a queue"));
+                }
+            }
+            TriggerSource::Kafka {
+                rest_url,
+                cluster,
+                group,
+                ..
+            } => {
+                http_url(rest_url)?;
+                if cluster.is_empty() || group.is_empty() {
+                    return Err(anyhow!("kafka triggers need a cluster and a group"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// What the trigger is called in logs and scaling history
+    pub fn name(&self) -> String {
+        match &self.source {
+            TriggerSource::Redis { list, .. } => format!("redis_{}", list),
+            TriggerSource::Rabbitmq { queue, .. } => format!("rabbitmq_{}", queue),
+            TriggerSource::Kafka { group, .. } => format!("kafka_{}", group),
+        }
+    }
+
+    async fn read(&self) -> Result<f64> {
+        match &self.source {
+            TriggerSource::Redis {
+                address,
+                list,
+                database,
+                password_env,
+            } => {
+                let password = password_env.as_deref().map(env_password).transpose()?;
+                redis_list_length(address, list, *database, password.as_deref()).await
+            }
+            TriggerSource::Rabbitmq {
+                url,
+                vhost,
+                queue,
+                username,
+                password_env,
+            } => {
+                let mut url = http_url(url)?;
+                url.path_segments_mut()
+                    .map_err(|_| anyhow!("Invalid url"))?
+                    .pop_if_empty()
+                    .extend(["api", "queues", vhost, queue]);
+                let queue = get_json(url, username.as_deref(), password_env.as_deref()).await?;
+                queue["messages"]
+                    .as_f64()
+                    .ok_or_else(|| anyhow!("Queue has no message count"))
+            }
+            TriggerSource::Kafka {
+                rest_url,
+                cluster,
+                group,
+                username,
+                password_env,
+            } => {
+                let mut url = http_url(rest_url)?;
+                url.path_segments_mut()
+                    .map_err(|_| anyhow!("Invalid rest_url"))?
+                    .pop_if_empty()
+                    .extend([
+                        "v3",
+                        "clusters",
+                        cluster,
+                        "consumer-groups",
+                        group,
+                        "lag-summary",
+                    ]);
+                let summary = get_json(url, username.as_deref(), password_env.as_deref()).await?;
+                summary["total_lag"]
+                    .as_f64()
+                    .ok_or_else(|| anyhow!("Lag summary has no total_lag"))
+            }
+        }
+    }
+}
+
+/// Read every trigger of the service at once, leaving out those that fail
+pub async fn poll(service_name: &str, triggers: &[ScalingTrigger]) -> Vec<TriggerReading> {
+    let readings = futures::future::join_all(triggers.iter().map(|trigger| async move {
+        let result = match tokio::time::timeout(POLL_TIMEOUT, trigger.read()).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow!("Timed out after {:?}", POLL_TIMEOUT)),
+        };
+        match result {
+            Ok(value) => Some(TriggerReading {
+                name: trigger.name(),
+                value,
+                target: trigger.target,
+            }),
+            Err(e) => {
+                slog::warn!(slog_scope::logger(), "Failed to read scaling trigger";
+                    "service" => service_name,
+                    "trigger" => trigger.name(),
+                    "error" => e.to_string()
+                );
+                None
+            }
+        }
+    }))
+    .await;
+    readings.into_iter().flatten().collect()
+}
+
+fn http_url(url: &str) -> Result<Url> {
+    let parsed = Url::parse(url).map_err(|e| anyhow!("invalid url '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow!("url '{}' must be http or https", url));
+    }
+    Ok(parsed)
+}
+
+fn env_password(var: &str) -> Result<String> {
+    std::env::var(var).map_err(|_| anyhow!("{} is not set", var))
+}
+
+// GET a JSON document, with basic auth from the arguments or the URL
+async fn get_json(
+    mut url: Url,
+    username: Option<&str>,
+    password_env: Option<&str>,
+) -> Result<Value> {
+    let username = username
+        .map(String::from)
+        .or_else(|| Some(url.username().to_string()).filter(|name| !name.is_empty()));
+    let password = match password_env {
+        Some(var) => Some(env_password(var)?),
+        None => url.password().map(String::from),
+    };
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+
+    let client = reqwest::Client::builder().timeout(POLL_TIMEOUT).build()?;
+    let mut request = client.get(url);
+    if let Some(username) = username {
+        request = request.basic_auth(username, password);
+    }
+    Ok(request.send().await?.error_for_status()?.json().await?)
+}
+
+// LLEN over the Redis protocol, after AUTH and SELECT as needed
+async fn redis_list_length(
+    address: &str,
+    list: &str,
+    database: u32,
+    password: Option<&str>,
+) -> Result<f64> {
+    let database = database.to_string();
+    let mut commands = Vec::new();
+    if let Some(password) = password {
+        commands.push(vec!["AUTH", password]);
+    }
+    if database != "0" {
+        commands.push(vec!["SELECT", &database]);
+    }
+    commands.push(vec!["LLEN", list]);
+
+    let mut stream = BufReader::new(TcpStream::connect(address).await?);
+    for command in &commands {
+        let mut request = format!("*{}\r\n", command.len());
+        for arg in command {
+            request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        stream.get_mut().write_all(request.as_bytes()).await?;
+    }
+
+    let mut reply = String::new();
+    for _ in &commands {
+        reply.clear();
+        stream.read_line(&mut reply).await?;
+        if let Some(error) = reply.strip_prefix('-') {
+            return Err(anyhow!("Redis: {}", error.trim_end()));
+        }
+    }
+    reply
+        .strip_prefix(':')
+        .and_then(|length| length.trim_end().parse().ok())
+        .ok_or_else(|| anyhow!("Unexpected reply from Redis: {}", reply.trim_end()))
+}
@@ -0,0 +1,400 @@
+// src/container/scaling/vertical.rs
+//
+// Vertical scaling, for stateful services that can't run more pods. With
+// `scaling_policy.vertical`, the scaler leaves the pod count alone and
+// instead raises a container's memory or CPU limit in place, through the
+// runtime's update API, when its usage goes over the resource threshold. It
+// lowers the limit again once usage is under the scale-down threshold. Each
+// change is a step of `step_percentage`, between the configured limit (or
+// `min_*`) and the `max_*` ceiling. A container that is recreated starts
+// again with its configured limits.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{Instant, SystemTime};
+
+use super::history::{record_scaling_event, ScalingDirection, ScalingEvent, ScalingReason};
+use crate::config::{parse_container_name, parse_cpu_limit, parse_memory_limit, ServiceConfig};
+use crate::container::{Container, ContainerRuntime, ContainerStats};
+
+const NANO_CPUS_PER_CPU: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerticalScaling {
+    /// Highest memory limit per container, in the format of memory_limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<Value>,
+    /// Highest CPU limit per container, in the format of cpu_limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu: Option<Value>,
+    /// Lowest memory limit (default: the container's configured limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_memory: Option<Value>,
+    /// Lowest CPU limit (default: the container's configured limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_cpu: Option<Value>,
+    /// How much each change raises or lowers a limit, in percent
+    #[serde(default = "default_step_percentage")]
+    pub step_percentage: u8,
+}
+
+fn default_step_percentage() -> u8 {
+    50
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Resource {
+    Memory,
+    Cpu,
+}
+
+// A container's configured limit of one resource, and the bounds on it
+struct Limits {
+    configured: Option<u64>,
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+impl VerticalScaling {
+    pub fn validate(&self, config: &ServiceConfig) -> Result<()> {
+        if self.max_memory.is_none() && self.max_cpu.is_none() {
+            return Err(anyhow!("max_memory or max_cpu must be set"));
+        }
+        if !(1..=100).contains(&self.step_percentage) {
+            return Err(anyhow!("step_percentage must be between 1 and 100"));
+        }
+        if config.instance_count.min != config.instance_count.max {
+            return Err(anyhow!(
+                "instance_count.min and instance_count.max must be equal, as the pod count is fixed"
+            ));
+        }
+        let policy = config.scaling_policy.as_ref();
+        if policy.is_some_and(|policy| {
+            !policy.schedules.is_empty() || !policy.triggers.is_empty() || policy.webhook.is_some()
+        }) {
+            return Err(anyhow!(
+                "can't be combined with schedules, triggers or a webhook, which set the pod count"
+            ));
+        }
+
+        let thresholds = config.resource_thresholds.as_ref();
+        for resource in [Resource::Memory, Resource::Cpu] {
+            let (max, threshold, name) = match resource {
+                Resource::Memory => (
+                    &self.max_memory,
+                    thresholds.and_then(|t| t.memory_percentage),
+                    "memory",
+                ),
+                Resource::Cpu => (
+                    &self.max_cpu,
+                    thresholds.and_then(|t| t.cpu_percentage_relative),
+                    "cpu",
+                ),
+            };
+            if max.is_none() {
+                continue;
+            }
+            if threshold.is_none() {
+                return Err(anyhow!(
+                    "max_{} needs resource_thresholds.{}",
+                    name,
+                    match resource {
+                        Resource::Memory => "memory_percentage",
+                        Resource::Cpu => "cpu_percentage_relative",
+                    }
+                ));
+            }
+            let mut limited = false;
+            for container in &config.spec.containers {
+                let limits = self.limits(resource, container, config)?;
+                let (Some(configured), Some(max)) = (limits.configured, limits.max) else {
+                    continue;
+                };
+                limited = true;
+                let min = limits.min.unwrap_or(configured);
+                if min > configured || configured > max {
+                    return Err(anyhow!(
+                        "container '{}' has a {} limit outside of min_{} and max_{}",
+                        container.name,
+                        name,
+                        name,
+                        name
+                    ));
+                }
+            }
+            if !limited {
+                return Err(anyhow!(
+                    "max_{} needs a {} limit on the containers to scale",
+                    name,
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn limits(
+        &self,
+        resource: Resource,
+        container: &Container,
+        config: &ServiceConfig,
+    ) -> Result<Limits> {
+        let (configured, min, max, parse): (_, _, _, fn(&Value) -> Result<u64>) = match resource {
+            Resource::Memory => (
+                container
+                    .memory_limit_value()
+                    .or(config.memory_limit.as_ref()),
+                &self.min_memory,
+                &self.max_memory,
+                parse_memory_limit,
+            ),
+            Resource::Cpu => (
+                container.cpu_limit_value().or(config.cpu_limit.as_ref()),
+                &self.min_cpu,
+                &self.max_cpu,
+                parse_cpu_limit,
+            ),
+        };
+        Ok(Limits {
+            configured: configured
+                .map(parse)
+                .transpose()?
+                .filter(|limit| *limit > 0),
+            min: min.as_ref().map(parse).transpose()?,
+            max: max.as_ref().map(parse).transpose()?,
+        })
+    }
+
+    // The limit a container should have instead of `current`, when usage at
+    // `percentage` of it is outside the thresholds
+    fn resize(
+        &self,
+        limits: &Limits,
+        current: u64,
+        percentage: f64,
+        threshold: f64,
+        scale_down_threshold: f64,
+    ) -> Option<u64> {
+        let (configured, max) = (limits.configured?, limits.max?);
+        let min = limits.min.unwrap_or(configured);
+        let step = f64::from(self.step_percentage) / 100.0;
+
+        if percentage > threshold && current < max {
+            Some(((current as f64 * (1.0 + step)) as u64).min(max))
+        } else if percentage < scale_down_threshold && current > min {
+            // Not so low that the usage would be over the threshold right away
+            let needed = (current as f64 * percentage / threshold).ceil() as u64;
+            let lowered = ((current as f64 * (1.0 - step)) as u64)
+                .max(min)
+                .max(needed);
+            (lowered < current).then_some(lowered)
+        } else {
+            None
+        }
+    }
+}
+
+/// Resizes the containers of one service, with a cooldown between changes
+#[derive(Debug)]
+pub struct VerticalScaler {
+    service_name: String,
+    last_change: Option<Instant>,
+}
+
+impl VerticalScaler {
+    pub fn new(service_name: &str) -> Self {
+        Self {
+            service_name: service_name.to_string(),
+            last_change: None,
+        }
+    }
+
+    /// Raise or lower the limits of the containers whose usage calls for it
+    pub async fn evaluate(
+        &mut self,
+        config: &ServiceConfig,
+        vertical: &VerticalScaling,
+        pods: usize,
+        container_stats: &[(String, ContainerStats)],
+        runtime: &dyn ContainerRuntime,
+    ) {
+        let log = slog_scope::logger();
+        let policy = config.scaling_policy.as_ref();
+        let cooldown = policy
+            .map(|policy| policy.get_cooldown_duration())
+            .unwrap_or_default();
+        if self.last_change.is_some_and(|at| at.elapsed() < cooldown) {
+            return;
+        }
+        let Some(thresholds) = &config.resource_thresholds else {
+            return;
+        };
+        let scale_down_threshold = policy.map_or(50.0, |policy| policy.get_scale_down_threshold());
+
+        for (name, stats) in container_stats {
+            let Some(container) = parse_container_name(name).ok().and_then(|parts| {
+                config
+                    .spec
+                    .containers
+                    .iter()
+                    .find(|container| container.name == parts.container_name)
+            }) else {
+                continue;
+            };
+
+            for resource in [Resource::Memory, Resource::Cpu] {
+                let (threshold, current, percentage) = match resource {
+                    Resource::Memory => (
+                        thresholds.memory_percentage,
+                        stats.memory_limit,
+                        stats.memory_usage as f64 / stats.memory_limit.max(1) as f64 * 100.0,
+                    ),
+                    Resource::Cpu => (
+                        thresholds.cpu_percentage_relative,
+                        stats.cpu_limit,
+                        stats.cpu_percentage_relative,
+                    ),
+                };
+                // CPU usage is only known from the second sample on
+                let Some(threshold) = threshold.filter(|_| current > 0 && percentage > 0.0) else {
+                    continue;
+                };
+                let limits = match vertical.limits(resource, container, config) {
+                    Ok(limits) => limits,
+                    Err(_) => continue,
+                };
+                let Some(limit) = vertical.resize(
+                    &limits,
+                    current,
+                    percentage,
+                    f64::from(threshold),
+                    scale_down_threshold,
+                ) else {
+                    continue;
+                };
+
+                let result = match resource {
+                    Resource::Memory => runtime.update_resources(name, Some(limit), None).await,
+                    Resource::Cpu => runtime.update_resources(name, None, Some(limit)).await,
+                };
+                let (key, usage_key, from, to) = match resource {
+                    Resource::Memory => {
+                        ("memory", "memory_percentage", current as f64, limit as f64)
+                    }
+                    Resource::Cpu => (
+                        "cpu",
+                        "cpu_percentage_relative",
+                        current as f64 / NANO_CPUS_PER_CPU,
+                        limit as f64 / NANO_CPUS_PER_CPU,
+                    ),
+                };
+                match &result {
+                    Ok(()) => slog::info!(log, "Resized container";
+                        "service" => &self.service_name,
+                        "container" => name,
+                        "resource" => key,
+                        "from" => from,
+                        "to" => to
+                    ),
+                    Err(e) => slog::error!(log, "Failed to resize container";
+                        "service" => &self.service_name,
+                        "container" => name,
+                        "resource" => key,
+                        "error" => e.to_string()
+                    ),
+                }
+
+                let metrics = BTreeMap::from([
+                    (format!("{}_limit_from", key), from),
+                    (format!("{}_limit_to", key), to),
+                    (usage_key.to_string(), percentage),
+                    ("threshold".to_string(), f64::from(threshold)),
+                ]);
+                record_scaling_event(
+                    &self.service_name,
+                    ScalingEvent {
+                        timestamp: SystemTime::now(),
+                        direction: if limit > current {
+                            ScalingDirection::Up
+                        } else {
+                            ScalingDirection::Down
+                        },
+                        from: pods,
+                        to: pods,
+                        reason: ScalingReason {
+                            policy: "vertical".to_string(),
+                            metrics,
+                        },
+                        error: result.err().map(|e| e.to_string()),
+                    },
+                );
+                self.last_change = Some(Instant::now());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertical(step_percentage: u8) -> VerticalScaling {
+        VerticalScaling {
+            max_memory: None,
+            max_cpu: None,
+            min_memory: None,
+            min_cpu: None,
+            step_percentage,
+        }
+    }
+
+    fn limits(configured: u64, min: Option<u64>, max: u64) -> Limits {
+        Limits {
+            configured: Some(configured),
+            min,
+            max: Some(max),
+        }
+    }
+
+    #[test]
+    fn test_resize_up() {
+        let vertical = vertical(50);
+        let limits = limits(100, None, 400);
+
+        assert_eq!(vertical.resize(&limits, 100, 90.0, 80.0, 50.0), Some(150));
+        // Capped at the maximum
+        assert_eq!(vertical.resize(&limits, 300, 90.0, 80.0, 50.0), Some(400));
+        assert_eq!(vertical.resize(&limits, 400, 90.0, 80.0, 50.0), None);
+        // Within the thresholds
+        assert_eq!(vertical.resize(&limits, 200, 60.0, 80.0, 50.0), None);
+    }
+
+    #[test]
+    fn test_resize_down() {
+        let vertical = vertical(50);
+
+        // Not below the configured limit when min is unset
+        let limits = limits(100, None, 400);
+        assert_eq!(vertical.resize(&limits, 400, 10.0, 80.0, 50.0), Some(200));
+        assert_eq!(vertical.resize(&limits, 150, 10.0, 80.0, 50.0), Some(100));
+        assert_eq!(vertical.resize(&limits, 100, 10.0, 80.0, 50.0), None);
+
+        // Not so low that usage would be over the threshold
+        let limits = self::limits(100, Some(50), 400);
+        assert_eq!(vertical.resize(&limits, 400, 40.0, 80.0, 50.0), Some(200));
+        assert_eq!(vertical.resize(&limits, 100, 40.0, 80.0, 50.0), Some(50));
+        assert_eq!(vertical.resize(&limits, 400, 49.0, 80.0, 50.0), Some(245));
+    }
+
+    #[test]
+    fn test_resize_needs_limits() {
+        let vertical = vertical(50);
+        let limits = Limits {
+            configured: None,
+            min: None,
+            max: Some(400),
+        };
+        assert_eq!(vertical.resize(&limits, 100, 90.0, 80.0, 50.0), None);
+    }
+}
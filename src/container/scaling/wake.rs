@@ -0,0 +1,181 @@
+// src/container/scaling/wake.rs
+//
+// Scale-to-zero. A service with `scale_to_zero` runs no pods while idle, with
+// `instance_count.min: 0`, and a request to it wakes it: the proxy holds the
+// request while one pod starts and sends it on once the pod's backend is
+// ready. Requests arriving meanwhile wait for the same pod, up to `max_queued`
+// of them. A woken service keeps at least one pod for `keep_warm`, so the
+// scaler doesn't take it straight back to zero.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::history::{record_scaling_event, ScalingDirection, ScalingEvent, ScalingReason};
+use super::scale_up;
+use crate::config::ServiceConfig;
+use crate::container::{stopped, INSTANCE_STORE, RUNTIME};
+use crate::proxy::run_proxy_for_service;
+
+static WAKE_STATE: OnceLock<Mutex<FxHashMap<String, WakeState>>> = OnceLock::new();
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScaleToZeroConfig {
+    /// How long a request waits for the service to wake before failing
+    #[serde(with = "humantime_serde", default = "default_wake_timeout")]
+    pub wake_timeout: Duration,
+    /// Requests held at once while the service wakes; more are rejected
+    #[serde(default = "default_max_queued")]
+    pub max_queued: usize,
+    /// A woken service keeps at least one pod this long
+    #[serde(with = "humantime_serde", default = "default_keep_warm")]
+    pub keep_warm: Duration,
+}
+
+fn default_wake_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_max_queued() -> usize {
+    100
+}
+
+fn default_keep_warm() -> Duration {
+    Duration::from_secs(300)
+}
+
+impl ScaleToZeroConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.wake_timeout.is_zero() {
+            return Err(anyhow!("wake_timeout must be positive"));
+        }
+        if self.max_queued == 0 {
+            return Err(anyhow!("max_queued must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct WakeState {
+    waking: bool,
+    queued: usize,
+    woken_at: Option<Instant>,
+}
+
+fn wake_state() -> &'static Mutex<FxHashMap<String, WakeState>> {
+    WAKE_STATE.get_or_init(|| Mutex::new(FxHashMap::default()))
+}
+
+/// A request's place in the queue of a waking service, given up when dropped
+pub struct QueueSlot {
+    service_name: String,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        if let Some(state) = wake_state().lock().unwrap().get_mut(&self.service_name) {
+            state.queued -= 1;
+        }
+    }
+}
+
+/// The service's scale-to-zero settings, if requests may wake it
+pub fn wake_config(config: &ServiceConfig) -> Option<&ScaleToZeroConfig> {
+    config
+        .scale_to_zero
+        .as_ref()
+        .filter(|_| config.external.is_none() && !stopped::is_stopped(config))
+}
+
+/// Hold a place in the service's wake queue. None when the queue is full.
+pub fn enqueue(service_name: &str, max_queued: usize) -> Option<QueueSlot> {
+    let mut states = wake_state().lock().unwrap();
+    let state = states.entry(service_name.to_string()).or_default();
+    if state.queued >= max_queued {
+        return None;
+    }
+    state.queued += 1;
+    Some(QueueSlot {
+        service_name: service_name.to_string(),
+    })
+}
+
+/// Start a pod for a service running none, unless one is already starting
+pub async fn wake(service_name: &str, config: &ServiceConfig) {
+    let pods = {
+        let store = INSTANCE_STORE.get().unwrap().read().await;
+        store
+            .get(service_name)
+            .map_or(0, |instances| instances.len())
+    };
+    if pods > 0 {
+        return;
+    }
+    {
+        let mut states = wake_state().lock().unwrap();
+        let state = states.entry(service_name.to_string()).or_default();
+        if state.waking {
+            return;
+        }
+        state.waking = true;
+        state.woken_at = Some(Instant::now());
+    }
+
+    slog::info!(slog_scope::logger(), "Waking service for incoming request";
+        "service" => service_name
+    );
+    let service_name = service_name.to_string();
+    let config = config.clone();
+    tokio::spawn(async move {
+        let runtime = RUNTIME.get().expect("Runtime not initialised").clone();
+        let result = scale_up(&service_name, config.clone(), runtime).await;
+        if let Err(e) = &result {
+            slog::error!(slog_scope::logger(), "Failed to wake service";
+                "service" => &service_name,
+                "error" => e.to_string()
+            );
+        }
+        record_scaling_event(
+            &service_name,
+            ScalingEvent {
+                timestamp: SystemTime::now(),
+                direction: ScalingDirection::Up,
+                from: 0,
+                to: usize::from(result.is_ok()),
+                reason: ScalingReason {
+                    policy: "wake".to_string(),
+                    metrics: BTreeMap::new(),
+                },
+                error: result.err().map(|e| e.to_string()),
+            },
+        );
+        run_proxy_for_service(service_name.clone(), config).await;
+
+        if let Some(state) = wake_state().lock().unwrap().get_mut(&service_name) {
+            state.waking = false;
+        }
+    });
+}
+
+/// The fewest pods the scaler may leave the service with: `instance_count.min`,
+/// or one while a woken service is kept warm
+pub fn min_instances(service_name: &str, config: &ServiceConfig) -> usize {
+    let min = config.instance_count.min as usize;
+    let Some(scale_to_zero) = &config.scale_to_zero else {
+        return min;
+    };
+    let warm = wake_state()
+        .lock()
+        .unwrap()
+        .get(service_name)
+        .and_then(|state| state.woken_at)
+        .is_some_and(|woken_at| woken_at.elapsed() < scale_to_zero.keep_warm);
+    if warm {
+        min.max(1)
+    } else {
+        min
+    }
+}
@@ -0,0 +1,97 @@
+// src/container/scaling/webhook.rs
+//
+// Scaling decisions from an external endpoint. A service with
+// `scaling_policy.webhook` has its pod stats and instance counts POSTed to the
+// endpoint on each scaling check outside the cooldown, and scales to the
+// `desired_instances` it answers with, within the instance count bounds. This
+// lets custom scaling logic run outside Orbit. When the endpoint can't be
+// reached or gives no answer, the built-in policies decide instead.
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::config::{InstanceCount, PodStats};
+use crate::container::health::webhooks::WebhookConfig;
+
+#[derive(Debug, Serialize)]
+struct ScalingRequest<'a> {
+    service_name: &'a str,
+    current_instances: usize,
+    min_instances: u8,
+    max_instances: u8,
+    pods: Vec<PodSample<'a>>,
+    timestamp: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PodSample<'a> {
+    uuid: Uuid,
+    #[serde(flatten)]
+    stats: &'a PodStats,
+}
+
+/// What the endpoint decided
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookAnswer {
+    pub desired_instances: u32,
+    /// Recorded with the scaling action, to tell why the endpoint decided so
+    #[serde(default)]
+    pub metrics: BTreeMap<String, f64>,
+}
+
+/// Ask the endpoint how many instances the service should have. None when it
+/// fails, which is logged.
+pub async fn ask(
+    service_name: &str,
+    webhook: &WebhookConfig,
+    current_instances: usize,
+    bounds: &InstanceCount,
+    pod_stats: &HashMap<Uuid, PodStats>,
+) -> Option<WebhookAnswer> {
+    let mut pods: Vec<PodSample> = pod_stats
+        .iter()
+        .map(|(uuid, stats)| PodSample { uuid: *uuid, stats })
+        .collect();
+    pods.sort_by_key(|pod| pod.uuid);
+    let request = ScalingRequest {
+        service_name,
+        current_instances,
+        min_instances: bounds.min,
+        max_instances: bounds.max,
+        pods,
+        timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+    };
+
+    match send(webhook, &request).await {
+        Ok(answer) => Some(answer),
+        Err(e) => {
+            slog::warn!(slog_scope::logger(), "Scaling webhook failed, using the built-in policies";
+                "service" => service_name,
+                "url" => &webhook.url,
+                "error" => e.to_string()
+            );
+            None
+        }
+    }
+}
+
+async fn send(webhook: &WebhookConfig, request: &ScalingRequest<'_>) -> Result<WebhookAnswer> {
+    let client = reqwest::Client::builder()
+        .timeout(webhook.timeout)
+        .build()?;
+    let mut builder = client.post(&webhook.url).json(request);
+    for (name, value) in &webhook.headers {
+        builder = builder.header(name, value);
+    }
+    let response = builder.send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("webhook answered {}", response.status()));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| anyhow!("invalid answer: {}", e))
+}
@@ -0,0 +1,152 @@
+// src/container/schedule.rs
+//
+// Spreads each service's periodic tasks (image checks, scaling and stats
+// collection) over their interval, so services with the same interval don't
+// all hit the runtime at once. Every task kind hands out slots to services;
+// slot n starts at the fractional part of n times the golden ratio into the
+// interval, which keeps any number of services evenly spaced without gaps or
+// clusters. The effective schedule is kept for the API.
+use humantime_serde::re::humantime;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+// Schedule of every running task, by service and task kind
+static SCHEDULE: Mutex<BTreeMap<(String, PeriodicTask), ScheduleEntry>> =
+    Mutex::new(BTreeMap::new());
+static NEXT_TICKER_ID: AtomicU64 = AtomicU64::new(0);
+
+// Fractional part of the golden ratio
+const GOLDEN_RATIO_FRACTION: f64 = 0.618_033_988_749_895;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PeriodicTask {
+    ImageCheck,
+    Scaling,
+}
+
+#[derive(Debug, Clone)]
+struct ScheduleEntry {
+    // Ticker that owns the entry, so a replaced ticker doesn't remove its successor
+    ticker_id: u64,
+    slot: usize,
+    interval: Duration,
+    offset: Duration,
+    next_run: SystemTime,
+}
+
+/// A periodic task's effective schedule
+#[derive(Debug, Serialize, Clone)]
+pub struct ScheduledTask {
+    pub service_name: String,
+    pub task: PeriodicTask,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Delay of this service's runs into each interval
+    #[serde(with = "humantime_serde")]
+    pub offset: Duration,
+    pub next_run: String,
+}
+
+/// Ticks every `interval`, offset by the service's slot. Releases the slot
+/// when dropped.
+pub struct Ticker {
+    id: u64,
+    key: (String, PeriodicTask),
+    interval: Duration,
+    next: Instant,
+}
+
+/// Start a ticker for one of a service's periodic tasks. The first tick comes
+/// after the service's offset rather than immediately.
+pub fn ticker(service_name: &str, task: PeriodicTask, interval: Duration) -> Ticker {
+    let interval = interval.max(Duration::from_millis(1));
+    let id = NEXT_TICKER_ID.fetch_add(1, Ordering::Relaxed);
+    let key = (service_name.to_string(), task);
+    let mut schedule = SCHEDULE.lock().unwrap();
+
+    // Keep the slot of a ticker being replaced, otherwise take the lowest free one
+    let slot = match schedule.get(&key) {
+        Some(entry) => entry.slot,
+        None => {
+            let used: Vec<usize> = schedule
+                .iter()
+                .filter(|((_, t), _)| *t == task)
+                .map(|(_, entry)| entry.slot)
+                .collect();
+            (0..).find(|slot| !used.contains(slot)).unwrap()
+        }
+    };
+    let fraction = (slot as f64 * GOLDEN_RATIO_FRACTION).fract();
+    let offset = Duration::from_millis((interval.as_millis() as f64 * fraction) as u64);
+
+    schedule.insert(
+        key.clone(),
+        ScheduleEntry {
+            ticker_id: id,
+            slot,
+            interval,
+            offset,
+            next_run: SystemTime::now() + offset,
+        },
+    );
+
+    Ticker {
+        id,
+        key,
+        interval,
+        next: Instant::now() + offset,
+    }
+}
+
+impl Ticker {
+    /// Wait for the next run. Cancel safe: a cancelled wait keeps the deadline.
+    pub async fn tick(&mut self) {
+        tokio::time::sleep_until(self.next).await;
+
+        // Skip runs missed while the task was busy, keeping the phase
+        let now = Instant::now();
+        while self.next <= now {
+            self.next += self.interval;
+        }
+
+        let mut schedule = SCHEDULE.lock().unwrap();
+        if let Some(entry) = schedule.get_mut(&self.key) {
+            if entry.ticker_id == self.id {
+                entry.next_run = SystemTime::now() + (self.next - now);
+            }
+        }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        let mut schedule = SCHEDULE.lock().unwrap();
+        if schedule
+            .get(&self.key)
+            .is_some_and(|entry| entry.ticker_id == self.id)
+        {
+            schedule.remove(&self.key);
+        }
+    }
+}
+
+/// The schedule of every running periodic task, by service
+pub fn scheduled_tasks() -> Vec<ScheduledTask> {
+    SCHEDULE
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((service_name, task), entry)| ScheduledTask {
+            service_name: service_name.clone(),
+            task: *task,
+            interval: entry.interval,
+            offset: entry.offset,
+            next_run: humantime::format_rfc3339_seconds(entry.next_run).to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,350 @@
+// src/container/secrets.rs
+//
+// Secrets declared on a service or container are resolved when a container
+// starts, from a file, Orbit's own environment or an external command, and
+// handed to the runtime as env vars or read-only files. Only secret names and
+// targets ever appear in configs, logs and API output, and resolved values are
+// registered with the redaction layer in case they leak some other way.
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use tokio::process::Command;
+use uuid::Uuid;
+
+use super::{identity, Container};
+use crate::config::ServiceConfig;
+
+static SECRETS_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+const SECRETS_DIR: &str = "secrets";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SecretConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub source: SecretSource,
+    /// Environment variable to expose the value as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    /// Absolute path of a read-only file holding the value inside the container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mount: Option<PathBuf>,
+    /// When the value stops being valid, e.g. "2026-03-01T00:00:00Z"
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<SystemTime>,
+    /// The value is a PEM certificate (or chain); its expiry is read from the certificate
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub certificate: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum SecretSource {
+    /// Contents of a file on the Orbit host
+    #[serde(rename = "from_file")]
+    File(PathBuf),
+    /// A variable from Orbit's own environment
+    #[serde(rename = "from_env")]
+    Env(String),
+    /// Standard output of a command, e.g. a vault or cloud secrets CLI
+    #[serde(rename = "from_command")]
+    Command(Vec<String>),
+}
+
+impl SecretSource {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SecretSource::File(_) => "file",
+            SecretSource::Env(_) => "env",
+            SecretSource::Command(_) => "command",
+        }
+    }
+}
+
+/// A resolved secret value; never printed by Debug or Display
+#[derive(Clone)]
+pub struct SecretValue(String);
+
+impl SecretValue {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretValue {
+    fn from(value: String) -> Self {
+        crate::redact::register(&value);
+        SecretValue(value)
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl fmt::Display for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedSecret {
+    pub name: String,
+    pub value: SecretValue,
+    pub env: Option<String>,
+    pub mount: Option<PathBuf>,
+}
+
+/// Service secrets followed by the container's own, which replace same-named ones.
+pub fn secrets_for<'a>(
+    service_config: &'a ServiceConfig,
+    container: &'a Container,
+) -> Vec<&'a SecretConfig> {
+    let mut secrets: Vec<&SecretConfig> = Vec::new();
+    for secret in service_config
+        .secrets
+        .iter()
+        .chain(container.secrets.iter().flatten())
+    {
+        secrets.retain(|existing| existing.name != secret.name);
+        secrets.push(secret);
+    }
+    secrets
+}
+
+pub async fn resolve_secret(secret: &SecretConfig) -> Result<SecretValue> {
+    resolve_source(&secret.name, &secret.source).await
+}
+
+/// Read a value from its source; `name` identifies it in errors
+pub async fn resolve_source(name: &str, source: &SecretSource) -> Result<SecretValue> {
+    // Errors name the secret and source but never include any output
+    let value = match source {
+        SecretSource::File(path) => tokio::fs::read_to_string(path).await.map_err(|e| {
+            anyhow!(
+                "Failed to read secret {} from {}: {}",
+                name,
+                path.display(),
+                e
+            )
+        })?,
+        SecretSource::Env(var) => std::env::var(var).map_err(|_| {
+            anyhow!(
+                "Secret {} not available: environment variable {} is not set",
+                name,
+                var
+            )
+        })?,
+        SecretSource::Command(command) => {
+            let (program, args) = command
+                .split_first()
+                .ok_or_else(|| anyhow!("Secret {} has an empty command", name))?;
+            let output = tokio::time::timeout(
+                COMMAND_TIMEOUT,
+                Command::new(program).args(args).kill_on_drop(true).output(),
+            )
+            .await
+            .map_err(|_| anyhow!("Secret {} command timed out", name))?
+            .map_err(|e| anyhow!("Failed to run command for secret {}: {}", name, e))?;
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "Command for secret {} failed ({})",
+                    name,
+                    output.status
+                ));
+            }
+            String::from_utf8(output.stdout)
+                .map_err(|_| anyhow!("Command for secret {} returned non UTF-8 output", name))?
+        }
+    };
+
+    // Files and command output usually end with a newline that is not part of the value
+    Ok(SecretValue::from(
+        value.trim_end_matches(['\r', '\n']).to_string(),
+    ))
+}
+
+/// The container's secrets, and the pod's identity token if the service has one
+pub async fn resolve_secrets(
+    service_config: &ServiceConfig,
+    container: &Container,
+    pod: Uuid,
+) -> Result<Vec<ResolvedSecret>> {
+    let mut resolved = Vec::new();
+    for secret in secrets_for(service_config, container) {
+        resolved.push(ResolvedSecret {
+            name: secret.name.clone(),
+            value: resolve_secret(secret).await?,
+            env: secret.env.clone(),
+            mount: secret.mount.clone(),
+        });
+    }
+    resolved.extend(identity::identity_secret(service_config, pod));
+    for secret in &resolved {
+        crate::redact::register_for_service(&service_config.name, secret.value.expose());
+    }
+    Ok(resolved)
+}
+
+/// Keep mounted secret files in the state directory, private to Orbit
+pub fn initialize_secrets_dir(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let root = state_dir.join(SECRETS_DIR);
+    create_private_dir(&root)?;
+    SECRETS_ROOT
+        .set(root)
+        .map_err(|_| anyhow!("Secrets directory already initialized"))
+}
+
+// Without a state directory, e.g. in tests, an unpredictable temp directory
+fn secrets_root() -> &'static PathBuf {
+    SECRETS_ROOT.get_or_init(|| {
+        let root = std::env::temp_dir().join(format!("orbit-secrets-{}", Uuid::new_v4()));
+        create_private_dir(&root).expect("Failed to create secrets directory");
+        root
+    })
+}
+
+// Create a directory only Orbit can enter, refusing one that is a symlink.
+// An existing directory is kept, with its permissions tightened.
+fn create_private_dir(dir: &Path) -> Result<()> {
+    match std::fs::symlink_metadata(dir) {
+        Ok(metadata) if metadata.file_type().is_symlink() || !metadata.is_dir() => {
+            Err(anyhow!("{} is not a directory", dir.display()))
+        }
+        Ok(_) => {
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+            Ok(())
+        }
+        Err(_) => {
+            std::fs::DirBuilder::new().mode(0o700).create(dir)?;
+            Ok(())
+        }
+    }
+}
+
+fn secret_dir(container_name: &str) -> PathBuf {
+    secrets_root().join(container_name)
+}
+
+/// Write the mounted secrets of a container to a private host directory,
+/// returning (host path, container path) pairs to bind mount read-only.
+pub fn write_secret_files(
+    container_name: &str,
+    secrets: &[ResolvedSecret],
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mounted: Vec<&ResolvedSecret> = secrets.iter().filter(|s| s.mount.is_some()).collect();
+    if mounted.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Files left by a previous run of the container are replaced, never
+    // written through
+    let dir = secret_dir(container_name);
+    remove_secret_files(container_name);
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let mut files = Vec::new();
+    for secret in mounted {
+        let host_path = dir.join(&secret.name);
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o400)
+            .open(&host_path)?;
+        file.write_all(secret.value.expose().as_bytes())?;
+        files.push((host_path, secret.mount.clone().unwrap()));
+    }
+    Ok(files)
+}
+
+pub fn remove_secret_files(container_name: &str) {
+    let dir = secret_dir(container_name);
+    let Ok(metadata) = std::fs::symlink_metadata(&dir) else {
+        return;
+    };
+    let result = if metadata.is_dir() {
+        std::fs::remove_dir_all(&dir)
+    } else {
+        std::fs::remove_file(&dir)
+    };
+    if let Err(e) = result {
+        slog::warn!(slog_scope::logger(), "Failed to remove secret files";
+            "container" => container_name,
+            "error" => e.to_string()
+        );
+    }
+}
+
+/// Secret names and targets for API output, never values
+#[derive(Debug, Serialize, Clone)]
+pub struct SecretSummary {
+    pub name: String,
+    /// Set for secrets declared on a single container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    pub source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mount: Option<PathBuf>,
+}
+
+impl SecretSummary {
+    pub fn new(secret: &SecretConfig, container: Option<&str>) -> Self {
+        Self {
+            name: secret.name.clone(),
+            container: container.map(str::to_string),
+            source: secret.source.kind(),
+            env: secret.env.clone(),
+            mount: secret.mount.clone(),
+        }
+    }
+}
+
+/// Every secret declared by a service, for the status API
+pub fn secret_summaries(config: &ServiceConfig) -> Vec<SecretSummary> {
+    let service = config
+        .secrets
+        .iter()
+        .map(|secret| SecretSummary::new(secret, None));
+    let containers = config.spec.containers.iter().flat_map(|container| {
+        container
+            .secrets
+            .iter()
+            .flatten()
+            .map(|secret| SecretSummary::new(secret, Some(&container.name)))
+    });
+    service.chain(containers).collect()
+}
+
+pub fn is_absolute_mount(path: &Path) -> bool {
+    path.is_absolute() && path.file_name().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_missing_env_var() {
+        let source = SecretSource::Env("ORBIT_TEST_UNSET_SECRET".to_string());
+        let error = resolve_source("db-password", &source).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Secret db-password not available: environment variable ORBIT_TEST_UNSET_SECRET is not set"
+        );
+    }
+}
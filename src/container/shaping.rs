@@ -0,0 +1,254 @@
+// src/container/shaping.rs
+//
+// Bandwidth limits for containers, enforced with tc on the host side of the
+// container's veth pair. What the host sends into the veth is what the
+// container receives, so `ingress_rate` shapes the veth's egress with HTB,
+// while `egress_rate` polices the traffic the veth receives from the container.
+// A restarted container gets a new veth, so limits are applied again.
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::process::Command;
+
+use crate::container::{parse_network_rate, NetworkLimit};
+
+// Shaping state by container name
+static SHAPING_STATE: Mutex<BTreeMap<String, ShapingState>> = Mutex::new(BTreeMap::new());
+
+/// The limits applied to a container's interface, or why they could not be
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ShapingState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Bits per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ingress_rate: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub egress_rate: Option<u64>,
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub updated_at: String,
+}
+
+/// Parse a burst size such as "20Mb" into bytes
+pub fn parse_network_burst(burst: &str) -> Result<u64> {
+    let re = regex::Regex::new(r"^(\d+(?:\.\d+)?)(Kb|Mb|Gb)$")?;
+    let caps = re
+        .captures(burst)
+        .ok_or_else(|| anyhow!("Invalid network burst format: {}", burst))?;
+    let value: f64 = caps[1].parse()?;
+    let bits = match &caps[2] {
+        "Kb" => 1_000.0,
+        "Mb" => 1_000_000.0,
+        _ => 1_000_000_000.0,
+    } * value;
+    Ok((bits / 8.0) as u64)
+}
+
+// Rates and bursts in the units tc takes: bits per second and bytes
+struct Limits {
+    ingress: Option<(u64, u64)>,
+    egress: Option<(u64, u64)>,
+}
+
+/// Check a network limit's rates and bursts
+pub fn validate_network_limit(limit: &NetworkLimit) -> Result<()> {
+    parse_limits(limit).map(|_| ())
+}
+
+fn parse_limits(limit: &NetworkLimit) -> Result<Limits> {
+    let parse = |rate: &Option<String>, burst: &Option<String>| -> Result<Option<(u64, u64)>> {
+        let Some(rate) = rate else {
+            if burst.is_some() {
+                return Err(anyhow!("A burst needs a rate"));
+            }
+            return Ok(None);
+        };
+        let rate = parse_network_rate(rate)?;
+        if rate == 0 {
+            return Err(anyhow!("Network rates must be greater than zero"));
+        }
+        let burst = match burst {
+            Some(burst) => parse_network_burst(burst)?,
+            // 100ms worth of traffic, and at least a couple of full frames
+            None => (rate / 80).max(3_200),
+        };
+        Ok(Some((rate, burst)))
+    };
+
+    Ok(Limits {
+        ingress: parse(&limit.ingress_rate, &limit.ingress_burst)?,
+        egress: parse(&limit.egress_rate, &limit.egress_burst)?,
+    })
+}
+
+/// Shaping state of a container, None if it has no network limit
+pub fn get_shaping_state(container_name: &str) -> Option<ShapingState> {
+    SHAPING_STATE.lock().unwrap().get(container_name).cloned()
+}
+
+pub fn clear_shaping_state(container_name: &str) {
+    SHAPING_STATE.lock().unwrap().remove(container_name);
+}
+
+/// Apply a container's network limit to the host side of its veth pair.
+/// Failures are logged and recorded in the shaping state, but the container
+/// keeps running.
+pub async fn apply_network_limit(container_name: &str, pid: Option<i64>, limit: &NetworkLimit) {
+    let mut state = ShapingState {
+        updated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        ..Default::default()
+    };
+
+    let result = async {
+        let limits = parse_limits(limit)?;
+        state.ingress_rate = limits.ingress.map(|(rate, _)| rate);
+        state.egress_rate = limits.egress.map(|(rate, _)| rate);
+
+        let pid = pid
+            .filter(|pid| *pid > 0)
+            .ok_or_else(|| anyhow!("Container has no process"))?;
+        let interface = host_interface(pid).await?;
+        state.interface = Some(interface.clone());
+        shape(&interface, &limits).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            state.applied = true;
+            slog::debug!(slog_scope::logger(), "Network limit applied";
+                "container" => container_name,
+                "interface" => &state.interface
+            );
+        }
+        Err(e) => {
+            slog::error!(slog_scope::logger(), "Failed to apply network limit";
+                "container" => container_name,
+                "error" => e.to_string()
+            );
+            state.error = Some(e.to_string());
+        }
+    }
+
+    SHAPING_STATE
+        .lock()
+        .unwrap()
+        .insert(container_name.to_string(), state);
+}
+
+// The host-side peer of the container's eth0, found through its interface index
+async fn host_interface(pid: i64) -> Result<String> {
+    let output = Command::new("nsenter")
+        .args(["--target", &pid.to_string(), "--net", "--"])
+        .args(["ip", "-o", "link", "show", "eth0"])
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to run nsenter: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to read the container's interface: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // e.g. "27: eth0@if28: <BROADCAST,MULTICAST,UP,LOWER_UP> ..."
+    let link = String::from_utf8_lossy(&output.stdout);
+    let peer_index = link
+        .split_whitespace()
+        .nth(1)
+        .and_then(|name| name.trim_end_matches(':').split_once("@if"))
+        .map(|(_, index)| index.to_string())
+        .ok_or_else(|| anyhow!("The container's eth0 is not a veth pair"))?;
+
+    let mut entries = tokio::fs::read_dir("/sys/class/net").await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let index = tokio::fs::read_to_string(entry.path().join("ifindex"))
+            .await
+            .unwrap_or_default();
+        if index.trim() == peer_index {
+            return Ok(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    Err(anyhow!("No host interface with index {}", peer_index))
+}
+
+async fn shape(interface: &str, limits: &Limits) -> Result<()> {
+    if let Some((rate, burst)) = limits.ingress {
+        tc(&[
+            "qdisc", "replace", "dev", interface, "root", "handle", "1:", "htb", "default", "10",
+        ])
+        .await?;
+        tc(&[
+            "class",
+            "replace",
+            "dev",
+            interface,
+            "parent",
+            "1:",
+            "classid",
+            "1:10",
+            "htb",
+            "rate",
+            &format!("{}bit", rate),
+            "burst",
+            &burst.to_string(),
+        ])
+        .await?;
+    }
+
+    if let Some((rate, burst)) = limits.egress {
+        tc(&[
+            "qdisc", "replace", "dev", interface, "handle", "ffff:", "ingress",
+        ])
+        .await?;
+        tc(&[
+            "filter",
+            "replace",
+            "dev",
+            interface,
+            "parent",
+            "ffff:",
+            "protocol",
+            "all",
+            "prio",
+            "1",
+            "u32",
+            "match",
+            "u32",
+            "0",
+            "0",
+            "police",
+            "rate",
+            &format!("{}bit", rate),
+            "burst",
+            &burst.to_string(),
+            "drop",
+            "flowid",
+            ":1",
+        ])
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn tc(args: &[&str]) -> Result<()> {
+    let output = Command::new("tc")
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to run tc: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "tc {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
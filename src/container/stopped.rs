@@ -0,0 +1,201 @@
+// src/container/stopped.rs
+//
+// Stopped services keep their config, node port proxies and scaling history,
+// but run no pods while the proxy answers with a maintenance page. A service
+// is stopped by `enabled: false` in its config or through the API. Services
+// stopped through the API are persisted, so they stay stopped across daemon
+// restarts until they are started again.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::scaling::history::{
+    record_scaling_event, ScalingDirection, ScalingEvent, ScalingReason,
+};
+use super::scaling::scale_down;
+use super::{manage, INSTANCE_STORE, RUNTIME};
+use crate::config::{get_config_by_service, ServiceConfig};
+use crate::proxy::run_proxy_for_service;
+
+static STOPPED_SERVICES: OnceLock<StoppedStore> = OnceLock::new();
+
+const STATE_FILE: &str = "stopped_services.json";
+
+#[derive(Debug)]
+struct StoppedStore {
+    path: Option<PathBuf>,
+    services: Mutex<BTreeSet<String>>,
+}
+
+pub fn initialize_stopped_services(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let path = state_dir.join(STATE_FILE);
+
+    let services = if path.exists() {
+        serde_json::from_str(&std::fs::read_to_string(&path)?)?
+    } else {
+        BTreeSet::new()
+    };
+
+    STOPPED_SERVICES
+        .set(StoppedStore {
+            path: Some(path),
+            services: Mutex::new(services),
+        })
+        .map_err(|_| anyhow!("Stopped services already initialized"))
+}
+
+fn stopped_store() -> &'static StoppedStore {
+    STOPPED_SERVICES.get_or_init(|| StoppedStore {
+        path: None,
+        services: Mutex::new(BTreeSet::new()),
+    })
+}
+
+impl StoppedStore {
+    // Mark or unmark a service, persisting the change. Returns whether it changed.
+    fn set(&self, service_name: &str, stopped: bool) -> bool {
+        let mut services = self.services.lock().unwrap();
+        let changed = if stopped {
+            services.insert(service_name.to_string())
+        } else {
+            services.remove(service_name)
+        };
+        if changed {
+            self.persist(&services);
+        }
+        changed
+    }
+
+    fn persist(&self, services: &BTreeSet<String>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string(services)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, path)?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            slog::error!(slog_scope::logger(), "Failed to persist stopped services";
+                "path" => path.display().to_string(),
+                "error" => e.to_string()
+            );
+        }
+    }
+}
+
+/// Whether the service should run no pods, by its config or through the API
+pub fn is_stopped(config: &ServiceConfig) -> bool {
+    !config.enabled
+        || stopped_store()
+            .services
+            .lock()
+            .unwrap()
+            .contains(&config.name)
+}
+
+pub async fn is_service_stopped(service_name: &str) -> bool {
+    get_config_by_service(service_name)
+        .await
+        .is_some_and(|config| is_stopped(&config))
+}
+
+/// Forget that a service was stopped through the API, once it is deleted
+pub fn forget(service_name: &str) {
+    stopped_store().set(service_name, false);
+}
+
+/// Stop the service's pods, keeping it registered. Returns the number of pods stopped.
+pub async fn stop(service_name: &str) -> Result<usize> {
+    let config = get_config_by_service(service_name)
+        .await
+        .ok_or_else(|| anyhow!("Service {} not found", service_name))?;
+
+    if stopped_store().set(service_name, true) {
+        slog::info!(slog_scope::logger(), "Stopping service"; "service" => service_name);
+    }
+    Ok(stop_pods(service_name, &config).await)
+}
+
+/// Start a service stopped through the API, back at its minimum instance count
+pub async fn start(service_name: &str) -> Result<()> {
+    let config = get_config_by_service(service_name)
+        .await
+        .ok_or_else(|| anyhow!("Service {} not found", service_name))?;
+    if !config.enabled {
+        return Err(anyhow!(
+            "Service {} is disabled by its config, set enabled: true to start it",
+            service_name
+        ));
+    }
+
+    if stopped_store().set(service_name, false) {
+        slog::info!(slog_scope::logger(), "Starting service"; "service" => service_name);
+    }
+    manage(service_name, config.clone()).await;
+    run_proxy_for_service(service_name.to_string(), config).await;
+    Ok(())
+}
+
+/// Remove every pod of a stopped service. Returns the number of pods removed.
+pub async fn stop_pods(service_name: &str, config: &ServiceConfig) -> usize {
+    // The service stays in the instance store without pods, so its scaling
+    // task keeps running and picks up a restart
+    let pods: Vec<Uuid> = {
+        let mut store = INSTANCE_STORE.get().unwrap().write().await;
+        store
+            .entry(service_name.to_string())
+            .or_insert_with(FxHashMap::default)
+            .keys()
+            .copied()
+            .collect()
+    };
+    if pods.is_empty() {
+        return 0;
+    }
+
+    // scale_down otherwise keeps the minimum instance count
+    let mut config = config.clone();
+    config.instance_count.min = 0;
+    let runtime = RUNTIME.get().expect("Runtime not initialized").clone();
+
+    let results = futures::future::join_all(
+        pods.iter()
+            .map(|uuid| scale_down(service_name, *uuid, config.clone(), runtime.clone())),
+    )
+    .await;
+    let stopped = results.iter().filter(|result| result.is_ok()).count();
+    let error = results.into_iter().find_map(Result::err);
+    if let Some(e) = &error {
+        slog::error!(slog_scope::logger(), "Failed to stop pod of stopped service";
+            "service" => service_name,
+            "error" => e.to_string()
+        );
+    }
+
+    record_scaling_event(
+        service_name,
+        ScalingEvent {
+            timestamp: SystemTime::now(),
+            direction: ScalingDirection::Down,
+            from: pods.len(),
+            to: pods.len() - stopped,
+            reason: ScalingReason {
+                policy: "stopped".to_string(),
+                metrics: BTreeMap::new(),
+            },
+            error: error.map(|e| e.to_string()),
+        },
+    );
+    stopped
+}
@@ -0,0 +1,299 @@
+// src/container/ttl.rs
+//
+// Services with `expires_after` are temporary: once their TTL has elapsed they
+// are taken out of the load balancer, given time to drain, and their config
+// file is removed so the watcher stops them like any deleted service. When
+// each TTL started, and how far it was extended, is persisted so daemon
+// restarts don't reset it.
+use anyhow::{anyhow, Result};
+use humantime_serde::re::humantime;
+use pingora_load_balancing::Backend;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use super::changes::{record_change, InstanceEvent};
+use super::INSTANCE_STORE;
+use crate::config::{get_config_by_service, CONFIG_STORE};
+use crate::proxy::SERVER_BACKENDS;
+
+static SERVICE_TTLS: OnceLock<TtlStore> = OnceLock::new();
+
+const STATE_FILE: &str = "service_ttls.json";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// Warn this long before expiry, or halfway through shorter TTLs
+const WARN_BEFORE: Duration = Duration::from_secs(15 * 60);
+// Time for in-flight requests once the service is out of the load balancer
+const DRAIN_PERIOD: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct TtlEntry {
+    started_at: SystemTime,
+    #[serde(with = "humantime_serde", default)]
+    extended_by: Duration,
+    #[serde(default)]
+    warned: bool,
+}
+
+#[derive(Debug)]
+struct TtlStore {
+    path: Option<PathBuf>,
+    entries: Mutex<FxHashMap<String, TtlEntry>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TtlStatus {
+    pub service_name: String,
+    #[serde(with = "humantime_serde")]
+    pub expires_after: Duration,
+    #[serde(with = "humantime_serde")]
+    pub extended_by: Duration,
+    pub started_at: String,
+    pub expires_at: String,
+    pub remaining_seconds: u64,
+}
+
+pub fn initialize_service_ttls(state_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let path = state_dir.join(STATE_FILE);
+
+    let entries = if path.exists() {
+        serde_json::from_str(&std::fs::read_to_string(&path)?)?
+    } else {
+        FxHashMap::default()
+    };
+
+    SERVICE_TTLS
+        .set(TtlStore {
+            path: Some(path),
+            entries: Mutex::new(entries),
+        })
+        .map_err(|_| anyhow!("Service TTLs already initialized"))
+}
+
+fn ttl_store() -> &'static TtlStore {
+    SERVICE_TTLS.get_or_init(|| TtlStore {
+        path: None,
+        entries: Mutex::new(FxHashMap::default()),
+    })
+}
+
+impl TtlStore {
+    fn persist(&self, entries: &FxHashMap<String, TtlEntry>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string(entries)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, path)?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            slog::error!(slog_scope::logger(), "Failed to persist service TTLs";
+                "path" => path.display().to_string(),
+                "error" => e.to_string()
+            );
+        }
+    }
+}
+
+fn status(service_name: &str, expires_after: Duration, entry: &TtlEntry) -> TtlStatus {
+    let expires_at = entry.started_at + expires_after + entry.extended_by;
+    TtlStatus {
+        service_name: service_name.to_string(),
+        expires_after,
+        extended_by: entry.extended_by,
+        started_at: humantime::format_rfc3339_seconds(entry.started_at).to_string(),
+        expires_at: humantime::format_rfc3339_seconds(expires_at).to_string(),
+        remaining_seconds: expires_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+            .as_secs(),
+    }
+}
+
+/// The TTL of a service, None if it has no `expires_after`
+pub async fn get_ttl(service_name: &str) -> Option<TtlStatus> {
+    let expires_after = get_config_by_service(service_name).await?.expires_after?;
+    let store = ttl_store();
+    let mut entries = store.entries.lock().unwrap();
+    if !entries.contains_key(service_name) {
+        entries.insert(
+            service_name.to_string(),
+            TtlEntry {
+                started_at: SystemTime::now(),
+                extended_by: Duration::ZERO,
+                warned: false,
+            },
+        );
+        store.persist(&entries);
+    }
+    Some(status(service_name, expires_after, &entries[service_name]))
+}
+
+/// Push the expiry of a service back by `by`
+pub async fn extend_ttl(service_name: &str, by: Duration) -> Result<TtlStatus> {
+    let expires_after = get_config_by_service(service_name)
+        .await
+        .and_then(|config| config.expires_after)
+        .ok_or_else(|| anyhow!("Service {} has no expires_after", service_name))?;
+
+    // Makes sure the entry exists
+    get_ttl(service_name).await;
+
+    let store = ttl_store();
+    let mut entries = store.entries.lock().unwrap();
+    let entry = entries
+        .get_mut(service_name)
+        .ok_or_else(|| anyhow!("Service {} has no TTL", service_name))?;
+    entry.extended_by += by;
+    entry.warned = false;
+    let status = status(service_name, expires_after, entry);
+    store.persist(&entries);
+
+    slog::info!(slog_scope::logger(), "Service TTL extended";
+        "service" => service_name,
+        "by" => humantime::format_duration(by).to_string(),
+        "expires_at" => &status.expires_at
+    );
+    Ok(status)
+}
+
+pub fn start_ttl_monitor() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            check().await;
+        }
+    });
+}
+
+async fn check() {
+    let Some(config_store) = CONFIG_STORE.get() else {
+        return;
+    };
+    let services: Vec<(String, PathBuf, Duration)> = {
+        let store = config_store.read().await;
+        store
+            .values()
+            .filter_map(|(path, config)| {
+                config
+                    .expires_after
+                    .map(|ttl| (config.name.clone(), path.clone(), ttl))
+            })
+            .collect()
+    };
+
+    let store = ttl_store();
+    let mut warnings = Vec::new();
+    let mut expired = Vec::new();
+    {
+        let mut entries = store.entries.lock().unwrap();
+        // Forget services that are gone or no longer temporary
+        entries.retain(|name, _| services.iter().any(|(service, _, _)| service == name));
+
+        let now = SystemTime::now();
+        for (service_name, path, expires_after) in &services {
+            let entry = entries
+                .entry(service_name.clone())
+                .or_insert_with(|| TtlEntry {
+                    started_at: now,
+                    extended_by: Duration::ZERO,
+                    warned: false,
+                });
+            let status = status(service_name, *expires_after, entry);
+            let remaining = Duration::from_secs(status.remaining_seconds);
+
+            if remaining.is_zero() {
+                expired.push((service_name.clone(), path.clone()));
+            } else if !entry.warned && remaining <= WARN_BEFORE.min(*expires_after / 2) {
+                entry.warned = true;
+                warnings.push(status);
+            }
+        }
+        store.persist(&entries);
+    }
+
+    for status in warnings {
+        slog::warn!(slog_scope::logger(), "Service expires soon";
+            "service" => &status.service_name,
+            "expires_at" => &status.expires_at
+        );
+        if let Some(instance_store) = INSTANCE_STORE.get() {
+            // Recorded under the store's lock like every other change
+            let _store = instance_store.write().await;
+            record_change(
+                &status.service_name,
+                InstanceEvent::ServiceExpiring {
+                    expires_at: status.expires_at.clone(),
+                },
+            );
+        }
+    }
+
+    for (service_name, path) in expired {
+        slog::info!(slog_scope::logger(), "Service TTL elapsed, draining";
+            "service" => &service_name,
+            "path" => path.display().to_string()
+        );
+        drain(&service_name).await;
+        tokio::time::sleep(DRAIN_PERIOD).await;
+
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            slog::error!(slog_scope::logger(), "Failed to remove expired service";
+                "service" => &service_name,
+                "path" => path.display().to_string(),
+                "error" => e.to_string()
+            );
+        }
+    }
+}
+
+// Take the service's pods out of the load balancer ahead of removing it
+async fn drain(service_name: &str) {
+    let (Some(instance_store), Some(server_backends)) =
+        (INSTANCE_STORE.get(), SERVER_BACKENDS.get())
+    else {
+        return;
+    };
+
+    let containers: Vec<_> = {
+        let store = instance_store.read().await;
+        store
+            .get(service_name)
+            .map(|instances| {
+                instances
+                    .values()
+                    .flat_map(|instance| instance.containers.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    for container in containers {
+        for port_info in &container.ports {
+            let Some(node_port) = port_info.node_port else {
+                continue;
+            };
+            let proxy_key = format!("{}__{}", service_name, node_port);
+            let backends = {
+                let backends_map = server_backends.read().await;
+                backends_map.get(&proxy_key).cloned()
+            };
+            if let Some(backends) = backends {
+                let addr = port_info.backend_address(&container.ip_address);
+                if let Ok(backend) = Backend::new(&addr) {
+                    backends.write().await.remove(&backend);
+                }
+            }
+        }
+    }
+}
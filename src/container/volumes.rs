@@ -50,6 +50,38 @@ pub struct VolumeData {
     pub permissions: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub named_volume: Option<NamedVolume>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim: Option<VolumeClaim>,
+}
+
+/// How a service may use a claim shared with other services
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    /// Mounted read-write by a single service
+    ReadWriteOnce,
+    /// Mounted read-only by any number of services
+    ReadOnlyMany,
+}
+
+/// A named volume that several services can reference, e.g. a producer
+/// writing files that its consumers read
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VolumeClaim {
+    pub name: String,
+    pub access_mode: AccessMode,
+}
+
+impl VolumeClaim {
+    pub fn validate(&self) -> Result<()> {
+        let name_regex = regex::Regex::new(r"^[a-z0-9][a-z0-9_.-]{0,62}$").unwrap();
+        if !name_regex.is_match(&self.name) {
+            return Err(anyhow!(
+                "claim name '{}' must be lowercase alphanumeric characters, '-', '_' or '.'",
+                self.name
+            ));
+        }
+        Ok(())
+    }
 }
 
 use anyhow::{anyhow, Result};
@@ -171,6 +203,30 @@ pub async fn attach_volume(name: &str, container_id: &str) -> Result<()> {
     }
 }
 
+/// Attach the container to the claim's volume, creating the volume on first
+/// use. Returns the volume's directory.
+pub async fn claim_volume(claim: &VolumeClaim, container_id: &str) -> Result<PathBuf> {
+    let volume_store = VOLUME_STORE.get().expect("Volume store not initialized");
+
+    let exists = volume_store.read().await.contains_key(&claim.name);
+    if !exists {
+        let labels = HashMap::from([("orbit.claim".to_string(), "true".to_string())]);
+        // Fails if another container created it first, which is fine
+        if let Err(e) = create_named_volume(&claim.name, Some(labels)).await {
+            if !volume_store.read().await.contains_key(&claim.name) {
+                return Err(e);
+            }
+        }
+    }
+
+    attach_volume(&claim.name, container_id).await?;
+    let store = volume_store.read().await;
+    store
+        .get(&claim.name)
+        .map(|metadata| metadata.path.clone())
+        .ok_or_else(|| anyhow!("Volume {} not found", claim.name))
+}
+
 pub async fn detach_volume(name: &str, container_id: &str) -> Result<()> {
     let volume_store = VOLUME_STORE.get().expect("Volume store not initialized");
 
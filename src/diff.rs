@@ -0,0 +1,331 @@
+// src/diff.rs
+//
+// `orbit diff`: compare the declared service configs against what the runtime
+// (and, when reachable, a running Orbit daemon) actually has.
+use crate::api::instances::ServiceInstance;
+use crate::config::{
+    config_files, parse_container_name, parse_memory_limit, read_yaml_config, ServiceConfig,
+};
+use crate::config::{ContainerNameParts, CONFIG_STORE};
+use crate::container::{ContainerInfo, ContainerRuntime};
+
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+pub struct ServiceDrift {
+    pub service_name: String,
+    pub drift: Vec<String>,
+}
+
+// Subset of the /status response needed to check proxy backends
+#[derive(Deserialize)]
+struct StatusService {
+    service_name: String,
+    pods: Vec<StatusPod>,
+}
+
+#[derive(Deserialize)]
+struct StatusPod {
+    uuid: Uuid,
+    containers: Vec<StatusContainer>,
+}
+
+#[derive(Deserialize)]
+struct StatusContainer {
+    name: String,
+    ports: Vec<StatusPort>,
+}
+
+#[derive(Deserialize)]
+struct StatusPort {
+    port: u16,
+    node_port: Option<u16>,
+    healthy: bool,
+}
+
+// What the running daemon reports, if one answers on the API address
+struct DaemonView {
+    instances: Vec<ServiceInstance>,
+    status: Vec<StatusService>,
+}
+
+/// Print the drift for every declared service. Returns true when everything matches.
+pub async fn run(
+    config_dir: &Path,
+    runtime: Arc<dyn ContainerRuntime>,
+    service_filter: Option<&str>,
+    api_url: &str,
+) -> Result<bool> {
+    let configs = load_configs(config_dir).await?;
+    let daemon = fetch_daemon_view(api_url).await;
+
+    if daemon.is_none() {
+        println!(
+            "note: no Orbit daemon reachable at {}, skipping image and backend checks\n",
+            api_url
+        );
+    }
+
+    let running = runtime.list_containers(None).await?;
+    let mut results = Vec::new();
+
+    for config in configs.values() {
+        if service_filter.is_some_and(|s| s != config.name) {
+            continue;
+        }
+        results.push(diff_service(config, &running, runtime.as_ref(), daemon.as_ref()).await);
+    }
+
+    // Orbit's containers of services nobody declares
+    if service_filter.is_none() {
+        let mut undeclared: BTreeMap<String, usize> = BTreeMap::new();
+        for container in &running {
+            if let Some(service_name) = container.service_name() {
+                if !configs.contains_key(&service_name) {
+                    *undeclared.entry(service_name).or_default() += 1;
+                }
+            }
+        }
+        for (service_name, count) in undeclared {
+            results.push(ServiceDrift {
+                service_name,
+                drift: vec![format!("not declared, {} container(s) running", count)],
+            });
+        }
+    }
+
+    let mut in_sync = true;
+    for result in &results {
+        if result.drift.is_empty() {
+            println!("{}: in sync", result.service_name);
+        } else {
+            in_sync = false;
+            println!("{}:", result.service_name);
+            for line in &result.drift {
+                println!("  - {}", line);
+            }
+        }
+    }
+
+    Ok(in_sync)
+}
+
+async fn load_configs(config_dir: &Path) -> Result<BTreeMap<String, ServiceConfig>> {
+    let config_store = CONFIG_STORE.get().expect("Config store not initialized");
+    let mut configs = BTreeMap::new();
+
+    for path in config_files(config_dir)? {
+        match read_yaml_config(&path, None).await {
+            Ok(config) => {
+                // Runtimes look up limits through the config store when inspecting
+                config_store
+                    .write()
+                    .await
+                    .insert(path.display().to_string(), (path.clone(), config.clone()));
+                configs.insert(config.name.clone(), config);
+            }
+            Err(e) => eprintln!("skipping {}: {}", path.display(), e),
+        }
+    }
+
+    Ok(configs)
+}
+
+async fn fetch_daemon_view(api_url: &str) -> Option<DaemonView> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .ok()?;
+    let base = api_url.trim_end_matches('/');
+
+    let instances = client
+        .get(format!("{}/instances?per_page=500", base))
+        .send()
+        .await
+        .ok()?
+        .json::<Vec<ServiceInstance>>()
+        .await
+        .ok()?;
+
+    let status = client
+        .get(format!("{}/status?per_page=500", base))
+        .send()
+        .await
+        .ok()?
+        .json::<Vec<StatusService>>()
+        .await
+        .ok()?;
+
+    Some(DaemonView { instances, status })
+}
+
+async fn diff_service(
+    config: &ServiceConfig,
+    running: &[ContainerInfo],
+    runtime: &dyn ContainerRuntime,
+    daemon: Option<&DaemonView>,
+) -> ServiceDrift {
+    let mut drift = Vec::new();
+
+    // Group the runtime's containers for this service by pod
+    let mut pods: BTreeMap<Uuid, Vec<ContainerNameParts>> = BTreeMap::new();
+    for container in running {
+        if container.service_name().as_deref() != Some(config.name.as_str()) {
+            continue;
+        }
+        if let Ok(parts) = parse_container_name(&container.name) {
+            pods.entry(parts.uuid).or_default().push(parts);
+        }
+    }
+
+    let (min, max) = (
+        config.instance_count.min as usize,
+        config.instance_count.max as usize,
+    );
+    if pods.len() < min || pods.len() > max {
+        drift.push(format!(
+            "pods: {} running, expected {}-{}",
+            pods.len(),
+            min,
+            max
+        ));
+    }
+
+    let declared: BTreeSet<&str> = config
+        .spec
+        .containers
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+
+    for (uuid, containers) in &pods {
+        let actual: BTreeSet<&str> = containers
+            .iter()
+            .map(|c| c.container_name.as_str())
+            .collect();
+
+        for missing in declared.difference(&actual) {
+            drift.push(format!(
+                "pod {}: container {} is not running",
+                uuid, missing
+            ));
+        }
+        for extra in actual.difference(&declared) {
+            drift.push(format!("pod {}: container {} is not declared", uuid, extra));
+        }
+    }
+
+    // Memory limits as enforced by the runtime
+    for (uuid, containers) in &pods {
+        for parts in containers {
+            let Some(spec) = config
+                .spec
+                .containers
+                .iter()
+                .find(|c| c.name == parts.container_name)
+            else {
+                continue;
+            };
+            let Some(declared_memory) = spec
+                .memory_limit_value()
+                .or(config.memory_limit.as_ref())
+                .and_then(|v| parse_memory_limit(v).ok())
+            else {
+                continue;
+            };
+
+            let name = format!(
+                "{}__{}__{}__{}",
+                parts.service_name, parts.pod_number, parts.container_name, parts.uuid
+            );
+            if let Ok(stats) = runtime.inspect_container(&name).await {
+                if stats.memory_limit != declared_memory {
+                    drift.push(format!(
+                        "pod {}: container {} memory limit is {} bytes, declared {}",
+                        uuid, parts.container_name, stats.memory_limit, declared_memory
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(daemon) = daemon {
+        diff_against_daemon(config, &pods, runtime, daemon, &mut drift).await;
+    }
+
+    ServiceDrift {
+        service_name: config.name.clone(),
+        drift,
+    }
+}
+
+async fn diff_against_daemon(
+    config: &ServiceConfig,
+    pods: &BTreeMap<Uuid, Vec<ContainerNameParts>>,
+    runtime: &dyn ContainerRuntime,
+    daemon: &DaemonView,
+    drift: &mut Vec<String>,
+) {
+    let tracked: FxHashMap<Uuid, &ServiceInstance> = daemon
+        .instances
+        .iter()
+        .filter(|i| i.service_name == config.name)
+        .map(|i| (i.instance.uuid, i))
+        .collect();
+
+    for uuid in pods.keys().filter(|uuid| !tracked.contains_key(uuid)) {
+        drift.push(format!(
+            "pod {}: running but not tracked by the daemon",
+            uuid
+        ));
+    }
+    for uuid in tracked.keys().filter(|uuid| !pods.contains_key(uuid)) {
+        drift.push(format!(
+            "pod {}: tracked by the daemon but not running",
+            uuid
+        ));
+    }
+
+    // Images that changed since the pod was started
+    let mut digests = FxHashMap::default();
+    for container in &config.spec.containers {
+        if let Ok(digest) = runtime.get_image_digest(&container.image).await {
+            digests.insert(container.name.as_str(), digest);
+        }
+    }
+    for (uuid, instance) in &tracked {
+        for (container_name, hash) in &instance.instance.image_hash {
+            if let Some(current) = digests.get(container_name.as_str()) {
+                if current != hash {
+                    drift.push(format!(
+                        "pod {}: container {} runs an outdated image",
+                        uuid, container_name
+                    ));
+                }
+            }
+        }
+    }
+
+    // Proxy backends for node ports
+    if let Some(status) = daemon.status.iter().find(|s| s.service_name == config.name) {
+        for pod in &status.pods {
+            for container in &pod.containers {
+                for port in container.ports.iter().filter(|p| !p.healthy) {
+                    if let Some(node_port) = port.node_port {
+                        drift.push(format!(
+                            "pod {}: {}:{} is not a backend for node port {}",
+                            pod.uuid, container.name, port.port, node_port
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
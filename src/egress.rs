@@ -0,0 +1,325 @@
+// src/egress.rs
+//
+// Forward proxy for pods' outbound HTTP(S) traffic. Pods of services with a
+// `proxy_allowlist` get HTTP_PROXY/HTTPS_PROXY pointing here; each connection
+// is attributed to a service by its source address and only allowlisted
+// domains are reached. HTTPS goes through CONNECT and is never decrypted.
+// The allowlist is advisory: nothing stops a client that ignores the proxy
+// variables from connecting directly, unless the pod's network has no other
+// way out. Pods of rootless runtimes connect from the host's address, so
+// they can't be told apart and get no proxy.
+use crate::config::daemon::EgressProxyConfig;
+use crate::config::get_config_by_service;
+use crate::container::{INSTANCE_STORE, LOOPBACK_ADDRESS};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProxyAllowlist {
+    /// Domains pods may reach, e.g. "api.stripe.com" or "*.githubusercontent.com"
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+impl ProxyAllowlist {
+    pub fn allows(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.allow.iter().any(|pattern| {
+            let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+            match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+                None => host == pattern,
+            }
+        })
+    }
+}
+
+/// Environment variables pointing a pod at the egress proxy
+pub fn proxy_environment(config: &EgressProxyConfig) -> Vec<String> {
+    let url = format!("http://{}:{}", config.advertise_host, config.listen.port());
+    let no_proxy = format!("localhost,{}", LOOPBACK_ADDRESS);
+
+    let mut env = Vec::new();
+    for name in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+        env.push(format!("{}={}", name, url));
+    }
+    env.push(format!("NO_PROXY={}", no_proxy));
+    env.push(format!("no_proxy={}", no_proxy));
+    env
+}
+
+pub async fn run_egress_proxy(config: EgressProxyConfig) -> Result<()> {
+    let listener = TcpListener::bind(config.listen)
+        .await
+        .map_err(|e| anyhow!("Failed to bind egress proxy on {}: {}", config.listen, e))?;
+    slog::info!(slog_scope::logger(), "Egress proxy running";
+        "listen" => config.listen.to_string()
+    );
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                slog::warn!(slog_scope::logger(), "Failed to accept egress connection";
+                    "error" => e.to_string()
+                );
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, peer.ip()).await {
+                slog::debug!(slog_scope::logger(), "Egress connection failed";
+                    "peer" => peer.to_string(),
+                    "error" => e.to_string()
+                );
+            }
+        });
+    }
+}
+
+// Pods are identified by the address they connect from
+async fn service_for_address(ip: IpAddr) -> Option<String> {
+    let ip = ip.to_string();
+    if ip == LOOPBACK_ADDRESS {
+        return None;
+    }
+
+    let store = INSTANCE_STORE.get()?.read().await;
+    store.iter().find_map(|(service_name, instances)| {
+        instances
+            .values()
+            .flat_map(|instance| &instance.containers)
+            .any(|container| container.ip_address == ip)
+            .then(|| service_name.clone())
+    })
+}
+
+async fn read_head(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(anyhow!("Request head too large"));
+        }
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            return Err(anyhow!("Connection closed before request head"));
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    Ok(head)
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+// Split "host:port" (or "[v6]:port"), falling back to `default_port`
+fn split_host_port(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None => default_port,
+        };
+        return Some((host.to_string(), port));
+    }
+
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Some((host.to_string(), port.parse().ok()?)),
+        None => Some((authority.to_string(), default_port)),
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, peer: IpAddr) -> Result<()> {
+    let head = tokio::time::timeout(HEAD_TIMEOUT, read_head(&mut stream)).await??;
+    let head_end = head
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|p| p + 4)
+        .unwrap_or(head.len());
+    let head_text = String::from_utf8_lossy(&head[..head_end]).to_string();
+    let request_line = head_text.lines().next().unwrap_or_default();
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return respond(&mut stream, "400 Bad Request", "Malformed request\n").await;
+    };
+
+    let Some(service_name) = service_for_address(peer).await else {
+        return respond(
+            &mut stream,
+            "403 Forbidden",
+            "Connection does not come from an Orbit pod\n",
+        )
+        .await;
+    };
+    let allowlist = get_config_by_service(&service_name)
+        .await
+        .and_then(|config| config.proxy_allowlist);
+    let Some(allowlist) = allowlist else {
+        return respond(
+            &mut stream,
+            "403 Forbidden",
+            "Service has no proxy allowlist\n",
+        )
+        .await;
+    };
+
+    // CONNECT host:port for HTTPS, absolute URIs for plain HTTP
+    let (host, port, forward_head) = if method.eq_ignore_ascii_case("CONNECT") {
+        let Some((host, port)) = split_host_port(target, 443) else {
+            return respond(&mut stream, "400 Bad Request", "Invalid CONNECT target\n").await;
+        };
+        (host, port, None)
+    } else {
+        let Some(rest) = target.strip_prefix("http://") else {
+            return respond(
+                &mut stream,
+                "400 Bad Request",
+                "Only http:// URLs and CONNECT are supported\n",
+            )
+            .await;
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let Some((host, port)) = split_host_port(authority, 80) else {
+            return respond(&mut stream, "400 Bad Request", "Invalid request URL\n").await;
+        };
+
+        // Origin-form request line, and one request per connection so every
+        // request is checked against the allowlist
+        let mut forward = format!("{} {} {}\r\n", method, path, version);
+        for line in head_text.lines().skip(1) {
+            let name = line.split(':').next().unwrap_or_default().trim();
+            if line.is_empty()
+                || name.eq_ignore_ascii_case("proxy-connection")
+                || name.eq_ignore_ascii_case("proxy-authorization")
+                || name.eq_ignore_ascii_case("connection")
+            {
+                continue;
+            }
+            forward.push_str(line);
+            forward.push_str("\r\n");
+        }
+        forward.push_str("Connection: close\r\n\r\n");
+        (host, port, Some(forward))
+    };
+
+    if !allowlist.allows(&host) {
+        slog::info!(slog_scope::logger(), "Egress request denied";
+            "service" => &service_name,
+            "host" => &host,
+            "port" => port
+        );
+        return respond(
+            &mut stream,
+            "403 Forbidden",
+            &format!("Egress to {} is not allowed for {}\n", host, service_name),
+        )
+        .await;
+    }
+
+    let mut upstream = match tokio::time::timeout(
+        CONNECT_TIMEOUT,
+        TcpStream::connect((host.as_str(), port)),
+    )
+    .await
+    {
+        Ok(Ok(upstream)) => upstream,
+        _ => {
+            return respond(
+                &mut stream,
+                "502 Bad Gateway",
+                &format!("Failed to connect to {}:{}\n", host, port),
+            )
+            .await;
+        }
+    };
+
+    slog::debug!(slog_scope::logger(), "Egress request allowed";
+        "service" => &service_name,
+        "host" => &host,
+        "port" => port
+    );
+
+    match forward_head {
+        Some(forward) => {
+            upstream.write_all(forward.as_bytes()).await?;
+            // Any body bytes that arrived with the head
+            upstream.write_all(&head[head_end..]).await?;
+        }
+        None => {
+            stream
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await?;
+            upstream.write_all(&head[head_end..]).await?;
+        }
+    }
+
+    tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allows() {
+        let allowlist = ProxyAllowlist {
+            allow: vec!["api.stripe.com".to_string(), "*.amazonaws.com.".to_string()],
+        };
+        assert!(allowlist.allows("api.stripe.com"));
+        assert!(allowlist.allows("API.Stripe.com."));
+        assert!(!allowlist.allows("stripe.com"));
+        assert!(!allowlist.allows("evilapi.stripe.com"));
+        assert!(allowlist.allows("s3.amazonaws.com"));
+        assert!(allowlist.allows("a.b.amazonaws.com"));
+        assert!(!allowlist.allows("amazonaws.com"));
+        assert!(!allowlist.allows("evilamazonaws.com"));
+        assert!(!ProxyAllowlist::default().allows("api.stripe.com"));
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        let split = |authority| split_host_port(authority, 443);
+        assert_eq!(split("example.com"), Some(("example.com".to_string(), 443)));
+        assert_eq!(
+            split("example.com:8443"),
+            Some(("example.com".to_string(), 8443))
+        );
+        assert_eq!(split("[::1]"), Some(("::1".to_string(), 443)));
+        assert_eq!(
+            split("[2001:db8::1]:80"),
+            Some(("2001:db8::1".to_string(), 80))
+        );
+        assert_eq!(split("example.com:https"), None);
+        assert_eq!(split("[::1]:x"), None);
+        assert_eq!(split("[::1"), None);
+    }
+}
@@ -0,0 +1,241 @@
+// src/external.rs
+//
+// External services are fronted by Orbit's proxy, with CoDel and metrics, but
+// run elsewhere: their backends are the addresses a hostname resolves to, or
+// a static list of addresses for processes Orbit doesn't manage. The hostname
+// is resolved through the system resolver, which doesn't report record TTLs,
+// so it is re-resolved every `ttl`. When a lookup fails the last known
+// addresses are kept.
+use anyhow::{anyhow, Result};
+use axum::http::Uri;
+use pingora_load_balancing::health_check::{HealthCheck, HttpHealthCheck, TcpHealthCheck};
+use pingora_load_balancing::Backend;
+use rustc_hash::FxHashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use crate::config::get_config_by_service;
+use crate::upstream::{UpstreamConfig, UpstreamScheme};
+
+// Resolver task of each external service
+static RESOLVERS: Mutex<BTreeMap<String, Resolver>> = Mutex::new(BTreeMap::new());
+
+struct Resolver {
+    handle: JoinHandle<()>,
+    backends: Arc<RwLock<FxHashSet<Backend>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExternalConfig {
+    /// Resolved to the service's backends, e.g. "payments.internal.example.com"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Port the backends listen on, with `hostname`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Static backend addresses, e.g. "10.0.0.5:8080", instead of a hostname
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backends: Vec<String>,
+    /// Port Orbit's proxy listens on for the service
+    pub node_port: u16,
+    /// How often the hostname is resolved, or the backends reloaded, again
+    #[serde(with = "humantime_serde", default = "default_ttl")]
+    pub ttl: Duration,
+    /// HTTP check of the backends, instead of connecting to them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<ExternalHealthCheck>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExternalHealthCheck {
+    /// Path a backend must answer with 200 OK, e.g. "/healthz"
+    pub path: String,
+    /// Host header of the checks, `hostname` or "localhost" when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+fn default_ttl() -> Duration {
+    Duration::from_secs(30)
+}
+
+impl ExternalConfig {
+    pub fn validate(&self) -> Result<()> {
+        match &self.hostname {
+            Some(_) if !self.backends.is_empty() => {
+                return Err(anyhow!("Only one of hostname or backends may be set"));
+            }
+            Some(hostname) => {
+                if hostname.is_empty() || hostname.contains(char::is_whitespace) {
+                    return Err(anyhow!("'{}' is not a valid hostname", hostname));
+                }
+                if self.port.unwrap_or_default() == 0 {
+                    return Err(anyhow!("port must be set with hostname"));
+                }
+            }
+            None if self.backends.is_empty() => {
+                return Err(anyhow!("Set hostname or backends"));
+            }
+            None => {
+                if self.port.is_some() {
+                    return Err(anyhow!(
+                        "port is only used with hostname, backends include their port"
+                    ));
+                }
+                let mut seen = FxHashSet::default();
+                for backend in &self.backends {
+                    let address = backend
+                        .parse::<SocketAddr>()
+                        .map_err(|_| anyhow!("'{}' is not an IP address and port", backend))?;
+                    if address.port() == 0 {
+                        return Err(anyhow!("Backend '{}' must have a port", backend));
+                    }
+                    if !seen.insert(address) {
+                        return Err(anyhow!("Backend '{}' is listed twice", backend));
+                    }
+                }
+            }
+        }
+        if self.node_port == 0 {
+            return Err(anyhow!("node_port must be set"));
+        }
+        if self.ttl < Duration::from_secs(1) {
+            return Err(anyhow!("ttl must be at least 1s"));
+        }
+        if let Some(check) = &self.health_check {
+            if !check.path.starts_with('/') || check.path.parse::<Uri>().is_err() {
+                return Err(anyhow!(
+                    "health_check.path '{}' must be an absolute path",
+                    check.path
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Health check of the service's load balancer: the HTTP check when one
+    /// is configured, over TLS like requests with `upstream`, otherwise a TCP
+    /// connect like managed services
+    pub fn load_balancer_health_check(
+        &self,
+        upstream: Option<&UpstreamConfig>,
+    ) -> Box<dyn HealthCheck + Send + Sync> {
+        let Some(check) = &self.health_check else {
+            return TcpHealthCheck::new();
+        };
+        let host = check
+            .host
+            .as_deref()
+            .or(self.hostname.as_deref())
+            .unwrap_or("localhost");
+        let tls = upstream.is_some_and(|upstream| upstream.scheme == UpstreamScheme::Https);
+        let mut http_check = HttpHealthCheck::new(host, tls);
+        if let Some(upstream) = upstream.filter(|_| tls) {
+            http_check.peer_template.options.verify_cert = upstream.verify_cert;
+            http_check.peer_template.options.verify_hostname = upstream.verify_hostname;
+            if let Some(sni) = &upstream.sni {
+                http_check.peer_template.sni = sni.clone();
+            }
+        }
+        // Checked by validate, "/" is checked otherwise
+        if let Ok(uri) = check.path.parse::<Uri>() {
+            http_check.req.set_uri(uri);
+        }
+        Box::new(http_check)
+    }
+}
+
+/// The service's backends: its static addresses, or those `hostname`
+/// currently resolves to
+pub async fn resolve(external: &ExternalConfig) -> Result<FxHashSet<Backend>> {
+    let mut backends = FxHashSet::default();
+    let Some(hostname) = &external.hostname else {
+        for address in &external.backends {
+            backends.insert(Backend::new(address)?);
+        }
+        return Ok(backends);
+    };
+
+    let port = external.port.unwrap_or_default();
+    let addresses = tokio::net::lookup_host((hostname.as_str(), port))
+        .await
+        .map_err(|e| anyhow!("Failed to resolve {}: {}", hostname, e))?;
+    for address in addresses {
+        backends.insert(Backend::new(&address.to_string())?);
+    }
+    if backends.is_empty() {
+        return Err(anyhow!("{} resolved to no addresses", hostname));
+    }
+    Ok(backends)
+}
+
+/// Keep `backends` in line with what the service's hostname resolves to,
+/// replacing the service's previous resolver
+pub fn start_discovery(service_name: &str, backends: Arc<RwLock<FxHashSet<Backend>>>) {
+    let service_name = service_name.to_string();
+    let handle = tokio::spawn({
+        let service_name = service_name.clone();
+        let backends = backends.clone();
+        async move {
+            loop {
+                let Some(external) = get_config_by_service(&service_name)
+                    .await
+                    .and_then(|config| config.external)
+                else {
+                    break;
+                };
+                refresh(&service_name, &external, &backends).await;
+                tokio::time::sleep(external.ttl).await;
+            }
+        }
+    });
+
+    let mut resolvers = RESOLVERS.lock().unwrap();
+    if let Some(previous) = resolvers.insert(service_name, Resolver { handle, backends }) {
+        previous.handle.abort();
+    }
+}
+
+/// Stop resolving a removed service's hostname and drop its backends
+pub async fn stop_discovery(service_name: &str) {
+    let resolver = RESOLVERS.lock().unwrap().remove(service_name);
+    if let Some(resolver) = resolver {
+        resolver.handle.abort();
+        resolver.backends.write().await.clear();
+    }
+}
+
+async fn refresh(
+    service_name: &str,
+    external: &ExternalConfig,
+    backends: &RwLock<FxHashSet<Backend>>,
+) {
+    match resolve(external).await {
+        Ok(resolved) => {
+            let mut backends = backends.write().await;
+            if *backends != resolved {
+                slog::info!(slog_scope::logger(), "External service backends changed";
+                    "service" => service_name,
+                    "hostname" => &external.hostname,
+                    "backends" => resolved
+                        .iter()
+                        .map(|backend| backend.addr.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                );
+                *backends = resolved;
+            }
+        }
+        Err(e) => {
+            slog::warn!(slog_scope::logger(), "Failed to resolve external service, keeping last backends";
+                "service" => service_name,
+                "error" => e.to_string()
+            );
+        }
+    }
+}
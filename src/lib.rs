@@ -0,0 +1,28 @@
+// src/lib.rs
+//
+// The daemon as a library, so other crates can register their own container
+// runtimes and test extensions against the MockRuntime. The orbit binary in
+// src/main.rs only calls `cli::run`.
+pub mod api;
+pub mod bundle;
+pub mod cli;
+pub mod clone;
+pub mod config;
+pub mod container;
+pub mod diff;
+pub mod egress;
+pub mod external;
+pub mod logger;
+pub mod metrics;
+pub mod node;
+pub mod proxy;
+pub mod redact;
+pub mod replay;
+pub mod request_limits;
+pub mod support;
+pub mod systemd;
+#[cfg(any(test, feature = "mock-runtime"))]
+pub mod testing;
+pub mod upstream;
+pub mod validate;
+pub mod wait;
@@ -4,14 +4,22 @@ pub static LOG: OnceLock<slog::Logger> = OnceLock::new();
 extern crate slog_async;
 extern crate slog_json;
 
+use crate::redact::RedactingWriter;
 use slog::Drain;
+use std::collections::VecDeque;
 use std::env;
-use std::sync::OnceLock;
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
 
 pub use slog;
 pub use slog::Logger;
 pub use slog_scope;
 
+static RECENT_LOGS: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+
+// Log lines kept in memory for support bundles
+const MAX_RECENT_LOGS: usize = 5000;
+
 macro_rules! crate_name {
     () => {
         env!("CARGO_PKG_NAME")
@@ -25,7 +33,7 @@ macro_rules! crate_version {
 }
 
 pub fn setup_logger(log_level: String) {
-    let drain = slog_json::Json::new(std::io::stderr())
+    let drain = slog_json::Json::new(RedactingWriter::new(RecentLogsWriter(std::io::stderr())))
         .add_default_keys()
         .build()
         .fuse();
@@ -63,3 +71,39 @@ pub fn get_log_level(log_level: String) -> slog::Level {
         _ => slog::Level::Info,
     }
 }
+
+fn recent_logs_buffer() -> &'static Mutex<VecDeque<String>> {
+    RECENT_LOGS.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// A writer that also keeps the most recent lines written to it
+pub struct RecentLogsWriter<W: Write>(pub W);
+
+impl<W: Write> Write for RecentLogsWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = recent_logs_buffer().lock().unwrap();
+        for line in String::from_utf8_lossy(buf).lines() {
+            lines.push_back(line.to_string());
+            if lines.len() > MAX_RECENT_LOGS {
+                lines.pop_front();
+            }
+        }
+        drop(lines);
+        self.0.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// The most recent log lines, oldest first
+pub fn recent_logs() -> Vec<String> {
+    recent_logs_buffer()
+        .lock()
+        .unwrap()
+        .iter()
+        .cloned()
+        .collect()
+}
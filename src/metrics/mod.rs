@@ -44,6 +44,9 @@ pub static CONFIG_RELOADS: OnceLock<Counter> = OnceLock::new();
 pub static SERVICE_INSTANCES: OnceLock<IntGaugeVec> = OnceLock::new();
 pub static SERVICE_REQUEST_DURATION: OnceLock<HistogramVec> = OnceLock::new();
 pub static SERVICE_REQUEST_TOTAL: OnceLock<CounterVec> = OnceLock::new();
+pub static SERVICE_RUNTIME_QUEUE_DEPTH: OnceLock<IntGaugeVec> = OnceLock::new();
+pub static SERVICE_RUNTIME_IN_FLIGHT: OnceLock<IntGaugeVec> = OnceLock::new();
+pub static EXPIRY_SECONDS: OnceLock<GaugeVec> = OnceLock::new();
 
 // Add to metrics.rs
 
@@ -144,6 +147,38 @@ pub fn initialize_metrics() -> Result<(), Box<dyn Error>> {
     registry.register(Box::new(service_request_total.clone()))?;
     SERVICE_REQUEST_TOTAL.set(service_request_total).unwrap();
 
+    let runtime_queue_depth = IntGaugeVec::new(
+        Opts::new(
+            "orbit_service_runtime_queue_depth",
+            "Runtime operations waiting for a slot per service",
+        ),
+        &["service"],
+    )?;
+    registry.register(Box::new(runtime_queue_depth.clone()))?;
+    SERVICE_RUNTIME_QUEUE_DEPTH
+        .set(runtime_queue_depth)
+        .unwrap();
+
+    let runtime_in_flight = IntGaugeVec::new(
+        Opts::new(
+            "orbit_service_runtime_in_flight",
+            "Runtime operations currently running per service",
+        ),
+        &["service"],
+    )?;
+    registry.register(Box::new(runtime_in_flight.clone()))?;
+    SERVICE_RUNTIME_IN_FLIGHT.set(runtime_in_flight).unwrap();
+
+    let expiry_seconds = GaugeVec::new(
+        Opts::new(
+            "orbit_expiry_seconds",
+            "Seconds until a secret or certificate expires",
+        ),
+        &["service", "container", "name", "kind"],
+    )?;
+    registry.register(Box::new(expiry_seconds.clone()))?;
+    EXPIRY_SECONDS.set(expiry_seconds).unwrap();
+
     // Set the global registry
     REGISTRY.set(registry).unwrap();
 
@@ -0,0 +1,292 @@
+// src/node.rs
+//
+// Host-level metrics for the node Orbit runs on: CPU, memory, disk and load,
+// plus what the container runtime reports about itself. CPU usage needs two
+// readings, so the node is sampled in the background and the API serves the
+// last sample.
+use humantime_serde::re::humantime;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::container::resources::allocated_requests;
+use crate::container::{RuntimeInfo, INSTANCE_STORE, RUNTIME};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+static LAST_SAMPLE: Mutex<Option<NodeStatus>> = Mutex::new(None);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NodeStatus {
+    pub hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operating_system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_version: Option<String>,
+    pub architecture: String,
+    pub uptime_seconds: u64,
+    pub cpu: CpuStats,
+    pub load_average: LoadAverage,
+    pub memory: MemoryStats,
+    pub disks: Vec<DiskStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeInfo>,
+    /// Services and pods managed by this Orbit instance
+    pub services: usize,
+    pub pods: usize,
+    /// Resources requested by the pods on the node, reserved for scheduling
+    pub requested: RequestedResources,
+    pub sampled_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RequestedResources {
+    pub memory: u64,
+    pub cpus: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CpuStats {
+    pub cores: u64,
+    /// Busy time across all cores since the previous sample
+    pub percentage: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MemoryStats {
+    pub total: u64,
+    pub available: u64,
+    pub used: u64,
+    pub percentage: f64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DiskStats {
+    pub path: String,
+    pub total: u64,
+    pub available: u64,
+    pub used: u64,
+    pub percentage: f64,
+}
+
+/// The node's last sample, None until the first one has been taken
+pub fn get_node_status() -> Option<NodeStatus> {
+    LAST_SAMPLE.lock().unwrap().clone()
+}
+
+/// Sample the node every few seconds. Disk usage is reported for the
+/// filesystems holding `disk_paths`, each filesystem once.
+pub fn start_node_monitor(disk_paths: Vec<PathBuf>) {
+    tokio::spawn(async move {
+        let mut previous_cpu = None;
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let status = sample(&disk_paths, &mut previous_cpu).await;
+            *LAST_SAMPLE.lock().unwrap() = Some(status);
+        }
+    });
+}
+
+async fn sample(disk_paths: &[PathBuf], previous_cpu: &mut Option<(u64, u64)>) -> NodeStatus {
+    // The first sample covers the time since boot
+    let cpu_times = read_cpu_times().await;
+    let percentage = match (cpu_times, *previous_cpu) {
+        (Some((busy, total)), Some((prev_busy, prev_total))) if total > prev_total => {
+            busy.saturating_sub(prev_busy) as f64 / (total - prev_total) as f64 * 100.0
+        }
+        (Some((busy, total)), _) if total > 0 => busy as f64 / total as f64 * 100.0,
+        _ => 0.0,
+    };
+    *previous_cpu = cpu_times.or(*previous_cpu);
+
+    let runtime = match RUNTIME.get() {
+        Some(runtime) => match runtime.runtime_info().await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                slog::debug!(slog_scope::logger(), "Failed to query runtime info";
+                    "error" => e.to_string()
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    let (services, pods) = match INSTANCE_STORE.get() {
+        Some(store) => {
+            let store = store.read().await;
+            (store.len(), store.values().map(|pods| pods.len()).sum())
+        }
+        None => (0, 0),
+    };
+    let requested = allocated_requests().await;
+
+    NodeStatus {
+        hostname: read_trimmed("/proc/sys/kernel/hostname")
+            .await
+            .or_else(|| std::env::var("HOSTNAME").ok())
+            .unwrap_or_default(),
+        operating_system: read_os_name().await,
+        kernel_version: read_trimmed("/proc/sys/kernel/osrelease").await,
+        architecture: std::env::consts::ARCH.to_string(),
+        uptime_seconds: read_trimmed("/proc/uptime")
+            .await
+            .and_then(|uptime| uptime.split_whitespace().next()?.parse::<f64>().ok())
+            .unwrap_or(0.0) as u64,
+        cpu: CpuStats {
+            cores: std::thread::available_parallelism()
+                .map(|n| n.get() as u64)
+                .unwrap_or(1),
+            percentage,
+        },
+        load_average: read_load_average().await.unwrap_or_default(),
+        memory: read_memory().await.unwrap_or_default(),
+        disks: disk_usage(disk_paths),
+        runtime,
+        services,
+        pods,
+        requested: RequestedResources {
+            memory: requested.memory,
+            cpus: requested.nano_cpus as f64 / 1_000_000_000.0,
+        },
+        sampled_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+    }
+}
+
+async fn read_trimmed(path: &str) -> Option<String> {
+    tokio::fs::read_to_string(path)
+        .await
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+async fn read_os_name() -> Option<String> {
+    tokio::fs::read_to_string("/etc/os-release")
+        .await
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim_matches('"').to_string())
+}
+
+// Busy and total jiffies across all cores; iowait counts as idle
+async fn read_cpu_times() -> Option<(u64, u64)> {
+    let proc_stat = tokio::fs::read_to_string("/proc/stat").await.ok()?;
+    let times: Vec<u64> = proc_stat
+        .lines()
+        .next()?
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|t| t.parse().ok())
+        .collect();
+    let total: u64 = times.iter().sum();
+    let idle = times.get(3).copied().unwrap_or(0) + times.get(4).copied().unwrap_or(0);
+    Some((total.saturating_sub(idle), total))
+}
+
+async fn read_load_average() -> Option<LoadAverage> {
+    let loadavg = tokio::fs::read_to_string("/proc/loadavg").await.ok()?;
+    let mut values = loadavg
+        .split_whitespace()
+        .map(|value| value.parse::<f64>().unwrap_or(0.0));
+    Some(LoadAverage {
+        one: values.next()?,
+        five: values.next()?,
+        fifteen: values.next()?,
+    })
+}
+
+async fn read_memory() -> Option<MemoryStats> {
+    let meminfo = tokio::fs::read_to_string("/proc/meminfo").await.ok()?;
+    // Values are reported in kB
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| value.split_whitespace().next()?.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+            .unwrap_or(0)
+    };
+
+    let total = field("MemTotal");
+    let available = field("MemAvailable");
+    let swap_total = field("SwapTotal");
+    let used = total.saturating_sub(available);
+    Some(MemoryStats {
+        total,
+        available,
+        used,
+        percentage: percentage(used, total),
+        swap_total,
+        swap_used: swap_total.saturating_sub(field("SwapFree")),
+    })
+}
+
+fn disk_usage(paths: &[PathBuf]) -> Vec<DiskStats> {
+    let mut devices = Vec::new();
+    let mut disks = Vec::new();
+    for path in paths {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            continue;
+        };
+        if devices.contains(&metadata.dev()) {
+            continue;
+        }
+        devices.push(metadata.dev());
+
+        match statvfs(path) {
+            Some((total, available)) => {
+                let used = total.saturating_sub(available);
+                disks.push(DiskStats {
+                    path: path.display().to_string(),
+                    total,
+                    available,
+                    used,
+                    percentage: percentage(used, total),
+                });
+            }
+            None => {
+                slog::debug!(slog_scope::logger(), "Failed to read disk usage";
+                    "path" => path.display().to_string()
+                );
+            }
+        }
+    }
+    disks
+}
+
+// Total and available bytes of the filesystem holding `path`
+fn statvfs(path: &Path) -> Option<(u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block_size = stat.f_frsize as u64;
+    Some((
+        stat.f_blocks as u64 * block_size,
+        stat.f_bavail as u64 * block_size,
+    ))
+}
+
+fn percentage(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64 * 100.0
+    }
+}
@@ -1,15 +1,28 @@
 // src/proxy.rs
 use crate::config::{get_config_by_service, ServiceConfig};
+use crate::container::anomaly;
+use crate::container::canary;
+use crate::container::health::{self, HealthState};
 use crate::container::scaling::codel::get_service_metrics;
 use crate::container::scaling::scale_up;
-use crate::container::{INSTANCE_STORE, RUNTIME};
+use crate::container::scaling::wake;
+use crate::container::scaling::{idle, latency, rps};
+use crate::container::stopped;
+use crate::container::{ContainerMetadata, INSTANCE_STORE, RUNTIME};
+use crate::external;
 use crate::metrics::{SERVICE_REQUEST_DURATION, SERVICE_REQUEST_TOTAL, TOTAL_REQUESTS};
+use crate::replay;
+use crate::request_limits::{self, RequestLimits};
+use crate::systemd;
 use async_trait::async_trait;
-use pingora::http::ResponseHeader;
+use bytes::Bytes;
+use pingora::http::{RequestHeader, ResponseHeader};
 use pingora::lb::discovery::ServiceDiscovery;
 use pingora::lb::{Backend, Backends, LoadBalancer};
 use pingora::prelude::RoundRobin;
+use pingora::protocols::http::ServerSession;
 use pingora::proxy::{http_proxy_service, ProxyHttp, Session};
+use pingora::server::configuration::{Opt, ServerConf};
 use pingora::server::Server;
 use pingora::services::background::background_service;
 use pingora::upstreams::peer::HttpPeer;
@@ -23,22 +36,333 @@ use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 use std::time::Instant;
 use tokio::task::{self, JoinHandle};
+use uuid::Uuid;
 
 // Global OnceLock for storing server instances and backends
 pub static SERVER_TASKS: OnceLock<Arc<RwLock<FxHashMap<String, JoinHandle<()>>>>> = OnceLock::new();
 pub static SERVER_BACKENDS: OnceLock<
     Arc<RwLock<FxHashMap<String, Arc<RwLock<FxHashSet<Backend>>>>>>,
 > = OnceLock::new();
-pub struct Discovery(Arc<RwLock<FxHashSet<Backend>>>);
+pub struct Discovery {
+    backends: Arc<RwLock<FxHashSet<Backend>>>,
+    service_name: String,
+    node_port: u16,
+    ramp: std::sync::Mutex<SlowStartRamp>,
+    cache: Arc<std::sync::RwLock<ProxyCache>>,
+}
+
+// What requests need from the instance store and the service config,
+// refreshed on every discovery update rather than looked up per request
+#[derive(Default)]
+struct ProxyCache {
+    // The pod and container behind each backend address
+    pods: FxHashMap<String, PodIdentity>,
+    access_log: bool,
+    request_limits: Option<RequestLimits>,
+}
+
+// When each backend was first discovered, None for those there when the proxy
+// started, which take their full share at once
+#[derive(Default)]
+struct SlowStartRamp {
+    primed: bool,
+    first_seen: FxHashMap<String, Option<Instant>>,
+}
+
+// Weight of a backend with a full share of traffic, while another has less
+// because it is ramping up or degraded. Shares change in steps of a tenth.
+const FULL_WEIGHT: usize = 10;
+// The same while a canary takes a share of the traffic, which is set to the percent
+const CANARY_FULL_WEIGHT: usize = 100;
+
+// Annotation prefixes that ask the proxy to inject headers, e.g.
+// `orbit.proxy/response-header.x-served-by: orbit`
+const REQUEST_HEADER_ANNOTATION: &str = "orbit.proxy/request-header.";
+const RESPONSE_HEADER_ANNOTATION: &str = "orbit.proxy/response-header.";
+
+// Carries a request's ID from the client, through the proxy, to the pod and
+// back in the response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+// Longer client-supplied IDs are replaced rather than logged
+const MAX_REQUEST_ID_LEN: usize = 200;
+
+// How often a request held for a waking service checks for a ready backend
+const WAKE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Served instead of proxying while a service is stopped
+const MAINTENANCE_PAGE: &str = "<!DOCTYPE html>
+<html>
+<head><title>Service unavailable</title></head>
+<body>
+<h1>Service unavailable</h1>
+<p>This service is down for maintenance. Please try again later.</p>
+</body>
+</html>
+";
+
+/// Per-request state: timing, the request ID and the pod that served it
+pub struct RequestCtx {
+    started: Instant,
+    request_id: String,
+    upstream: Option<String>,
+    pod: Option<PodIdentity>,
+    // When the request was sent upstream, for the upstream's response time
+    upstream_started: Option<Instant>,
+    // The service's limit on the body, and how much of it has been received
+    max_body_bytes: Option<u64>,
+    body_bytes: u64,
+}
+
+// The pod and container behind a backend address
+#[derive(Clone)]
+struct PodIdentity {
+    uuid: Uuid,
+    container: String,
+}
+
+// Keep the client's request ID if it is printable ASCII of a sane length,
+// otherwise generate one
+fn request_id_from(header: Option<&str>) -> String {
+    match header {
+        Some(id)
+            if !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            id.to_string()
+        }
+        _ => Uuid::new_v4().to_string(),
+    }
+}
+
+// Header name/value pairs requested by annotations with the given prefix
+fn annotated_headers<'a>(
+    annotations: &'a HashMap<String, String>,
+    prefix: &'a str,
+) -> impl Iterator<Item = (String, &'a str)> + 'a {
+    annotations.iter().filter_map(move |(key, value)| {
+        key.strip_prefix(prefix)
+            .filter(|name| !name.is_empty())
+            .map(|name| (name.to_string(), value.as_str()))
+    })
+}
+
+// Answer a request over one of the service's limits without reading its body,
+// so the connection can't be reused
+async fn reject_oversized(
+    session: &mut Session,
+    ctx: &RequestCtx,
+    service_name: &str,
+    status_code: u16,
+) -> pingora::Result<()> {
+    slog::debug!(slog_scope::logger(), "Rejecting request over the size limits";
+        "service" => service_name,
+        "request_id" => &ctx.request_id,
+        "status_code" => status_code
+    );
+    let mut response = ResponseHeader::build(status_code, Some(2))?;
+    response.insert_header("content-length", "0")?;
+    response.insert_header(REQUEST_ID_HEADER, &ctx.request_id)?;
+    session.set_keepalive(None);
+    session
+        .write_response_header(Box::new(response), true)
+        .await
+}
+
+async fn write_maintenance_page(session: &mut Session, request_id: &str) -> pingora::Result<()> {
+    let mut response = ResponseHeader::build(503, Some(4))?;
+    response.insert_header("content-type", "text/html; charset=utf-8")?;
+    response.insert_header("content-length", MAINTENANCE_PAGE.len())?;
+    response.insert_header("cache-control", "no-store")?;
+    response.insert_header(REQUEST_ID_HEADER, request_id)?;
+    session
+        .write_response_header(Box::new(response), false)
+        .await?;
+    session
+        .write_response_body(Some(Bytes::from_static(MAINTENANCE_PAGE.as_bytes())), true)
+        .await
+}
+
+impl Discovery {
+    pub fn new(
+        service_name: &str,
+        node_port: u16,
+        backends: Arc<RwLock<FxHashSet<Backend>>>,
+    ) -> Self {
+        Discovery {
+            backends,
+            service_name: service_name.to_string(),
+            node_port,
+            ramp: std::sync::Mutex::new(SlowStartRamp::default()),
+            cache: Arc::default(),
+        }
+    }
+
+    // The backend addresses of this port, with their pod and container
+    async fn backend_containers(&self) -> Vec<(String, Uuid, String)> {
+        let Some(store) = INSTANCE_STORE.get() else {
+            return Vec::new();
+        };
+        let store = store.read().await;
+        store
+            .get(&self.service_name)
+            .into_iter()
+            .flat_map(|instances| instances.values())
+            .flat_map(|instance| {
+                instance.containers.iter().flat_map(move |container| {
+                    container
+                        .ports
+                        .iter()
+                        .filter(|port| port.node_port == Some(self.node_port))
+                        .map(move |port| {
+                            (
+                                port.backend_address(&container.ip_address),
+                                instance.uuid,
+                                container.name.clone(),
+                            )
+                        })
+                })
+            })
+            .collect()
+    }
+
+    // Addresses of the backends whose container's health checks are failing
+    async fn degraded_backends(containers: &[(String, Uuid, String)]) -> FxHashSet<String> {
+        let mut degraded = FxHashSet::default();
+        for (address, _, container_name) in containers {
+            if health::get_container_health(container_name)
+                .await
+                .is_some_and(|health| matches!(health.state, HealthState::Unhealthy))
+            {
+                degraded.insert(address.clone());
+            }
+        }
+        degraded
+    }
+}
+
+impl SlowStartRamp {
+    // The share of its full weight each backend gets, from 0.1 when it was
+    // just added up to 1 once `window` has passed
+    fn shares(&mut self, backends: &FxHashSet<Backend>, window: Duration) -> Vec<f64> {
+        let addresses: FxHashSet<String> = backends.iter().map(|b| b.addr.to_string()).collect();
+        self.first_seen
+            .retain(|address, _| addresses.contains(address));
+
+        let now = Instant::now();
+        let primed = self.primed;
+        self.primed = true;
+        backends
+            .iter()
+            .map(|backend| {
+                let first_seen = *self
+                    .first_seen
+                    .entry(backend.addr.to_string())
+                    .or_insert(primed.then_some(now));
+                match first_seen {
+                    Some(first_seen) if now - first_seen < window => {
+                        ((now - first_seen).as_secs_f64() / window.as_secs_f64()).max(0.1)
+                    }
+                    _ => 1.0,
+                }
+            })
+            .collect()
+    }
+}
 
 #[async_trait]
 impl ServiceDiscovery for Discovery {
     async fn discover(&self) -> pingora::Result<(BTreeSet<Backend>, HashMap<u64, bool>)> {
-        let mut backends = BTreeSet::new();
-        let backend_set = self.0.read().await;
-        for backend in backend_set.iter() {
-            backends.insert(backend.clone());
+        let config = get_config_by_service(&self.service_name).await;
+        let containers = self.backend_containers().await;
+        *self.cache.write().unwrap() = ProxyCache {
+            pods: containers
+                .iter()
+                .map(|(address, uuid, container)| {
+                    let pod = PodIdentity {
+                        uuid: *uuid,
+                        container: container.clone(),
+                    };
+                    (address.clone(), pod)
+                })
+                .collect(),
+            access_log: config.as_ref().is_some_and(|config| config.access_log),
+            request_limits: config
+                .as_ref()
+                .and_then(|config| config.request_limits.clone()),
+        };
+
+        let slow_start = config.as_ref().and_then(|config| config.slow_start);
+        let degraded_weight = config.as_ref().and_then(|config| config.degraded_weight);
+        let degraded = match degraded_weight {
+            Some(_) => Self::degraded_backends(&containers).await,
+            None => FxHashSet::default(),
+        };
+        let canary = match canary::traffic_split(&self.service_name) {
+            Some((pods, percentage)) => {
+                let addresses: FxHashSet<String> = containers
+                    .iter()
+                    .filter(|(_, uuid, _)| pods.contains(uuid))
+                    .map(|(address, _, _)| address.clone())
+                    .collect();
+                Some((addresses, percentage))
+            }
+            None => None,
+        };
+        let backend_set = self.backends.read().await;
+        if slow_start.is_none() && degraded.is_empty() && canary.is_none() {
+            return Ok((backend_set.iter().cloned().collect(), HashMap::new()));
+        }
+
+        let mut shares = match slow_start {
+            Some(window) => self.ramp.lock().unwrap().shares(&backend_set, window),
+            None => vec![1.0; backend_set.len()],
+        };
+        for (share, backend) in shares.iter_mut().zip(backend_set.iter()) {
+            if degraded.contains(&backend.addr.to_string()) {
+                *share *= degraded_weight.unwrap_or(1.0);
+            }
         }
+        // A canary's pods split its percentage of the traffic, the other pods
+        // the rest
+        let mut full_weight = FULL_WEIGHT;
+        if let Some((addresses, percentage)) = canary {
+            let in_canary: Vec<bool> = backend_set
+                .iter()
+                .map(|backend| addresses.contains(&backend.addr.to_string()))
+                .collect();
+            let canaries = in_canary.iter().filter(|in_canary| **in_canary).count();
+            let others = in_canary.len() - canaries;
+            if canaries > 0 && others > 0 {
+                let fraction = f64::from(percentage) / 100.0;
+                for (share, in_canary) in shares.iter_mut().zip(in_canary) {
+                    *share *= if in_canary {
+                        fraction / canaries as f64
+                    } else {
+                        (1.0 - fraction) / others as f64
+                    };
+                }
+                let largest = shares.iter().copied().fold(0.0, f64::max);
+                for share in shares.iter_mut() {
+                    *share /= largest;
+                }
+                full_weight = CANARY_FULL_WEIGHT;
+            }
+        }
+        // Weights stay at 1 unless a backend has less than a full share, as
+        // weighted round robin sends each backend its weight in requests in a row
+        let reduced = shares.iter().any(|share| *share < 1.0);
+        let backends = backend_set
+            .iter()
+            .zip(shares)
+            .map(|(backend, share)| {
+                let mut backend = backend.clone();
+                if reduced {
+                    backend.weight = ((share * full_weight as f64) as usize).max(1);
+                }
+                backend
+            })
+            .collect();
         Ok((backends, HashMap::new()))
     }
 }
@@ -46,37 +370,164 @@ impl ServiceDiscovery for Discovery {
 pub struct ProxyApp {
     pub loadbalancer: Arc<LoadBalancer<RoundRobin>>,
     pub service_name: String,
+    cache: Arc<std::sync::RwLock<ProxyCache>>,
+}
+
+impl ProxyApp {
+    // Hold the request of a service scaled to zero while it wakes, until a
+    // backend is ready or the wake times out
+    async fn wait_for_wake(&self, service_name: &str, ctx: &RequestCtx) -> Option<Backend> {
+        let config = get_config_by_service(service_name).await?;
+        let scale_to_zero = wake::wake_config(&config)?;
+        let Some(_slot) = wake::enqueue(service_name, scale_to_zero.max_queued) else {
+            slog::warn!(slog_scope::logger(), "Wake queue full, rejecting request";
+                "service" => service_name,
+                "request_id" => &ctx.request_id
+            );
+            return None;
+        };
+        wake::wake(service_name, &config).await;
+
+        let deadline = Instant::now() + scale_to_zero.wake_timeout;
+        while Instant::now() < deadline {
+            tokio::time::sleep(WAKE_POLL_INTERVAL).await;
+            if let Some(upstream) = self.loadbalancer.select(b"", 256) {
+                slog::debug!(slog_scope::logger(), "Releasing request held for wake";
+                    "service" => service_name,
+                    "request_id" => &ctx.request_id,
+                    "waited_ms" => ctx.started.elapsed().as_millis() as u64
+                );
+                return Some(upstream);
+            }
+        }
+        slog::warn!(slog_scope::logger(), "Service did not wake in time";
+            "service" => service_name,
+            "request_id" => &ctx.request_id
+        );
+        None
+    }
 }
 
 #[async_trait]
 impl ProxyHttp for ProxyApp {
-    type CTX = Instant; // Use Instant for timing requests
+    type CTX = RequestCtx;
 
     fn new_ctx(&self) -> Self::CTX {
-        // Start timing the request
-        Instant::now()
+        // Start timing the request; the ID is set once the headers are read
+        RequestCtx {
+            started: Instant::now(),
+            request_id: String::new(),
+            upstream: None,
+            pod: None,
+            upstream_started: None,
+            max_body_bytes: None,
+            body_bytes: 0,
+        }
     }
 
-    async fn response_filter(
+    async fn request_filter(
+        &self,
+        session: &mut Session,
+        ctx: &mut RequestCtx,
+    ) -> pingora::Result<bool> {
+        ctx.request_id = request_id_from(
+            session
+                .req_header()
+                .headers
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        );
+        if let Some((service_name, _)) = self.service_name.split_once("__") {
+            let limits = self.cache.read().unwrap().request_limits.clone();
+            if let Some(limits) = limits {
+                if let Some(status_code) = limits.check(session.req_header()) {
+                    reject_oversized(session, ctx, service_name, status_code).await?;
+                    return Ok(true);
+                }
+                ctx.max_body_bytes = limits.max_body_bytes;
+            }
+            idle::record_request(service_name);
+            rps::record_request(service_name);
+        }
+        Ok(false)
+    }
+
+    // Count the body as it streams through, for bodies without a Content-Length
+    async fn request_body_filter(
         &self,
         _session: &mut Session,
+        body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        ctx: &mut RequestCtx,
+    ) -> pingora::Result<()> {
+        let (Some(max_body_bytes), Some(chunk)) = (ctx.max_body_bytes, body.as_ref()) else {
+            return Ok(());
+        };
+        ctx.body_bytes += chunk.len() as u64;
+        if ctx.body_bytes > max_body_bytes {
+            let error = pingora::Error {
+                etype: pingora::ErrorType::HTTPStatus(request_limits::BODY_TOO_LARGE),
+                esource: pingora::ErrorSource::Downstream,
+                retry: pingora::RetryType::Decided(false),
+                cause: None,
+                context: Some(pingora::ImmutStr::Static("Request body too large")),
+            };
+            return Err(Box::new(error));
+        }
+        Ok(())
+    }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
         response: &mut ResponseHeader,
-        ctx: &mut Instant,
+        ctx: &mut RequestCtx,
     ) -> pingora::Result<()> {
-        let total_time = ctx.elapsed();
+        let total_time = ctx.started.elapsed();
+        response.insert_header(REQUEST_ID_HEADER, &ctx.request_id)?;
         let service_name = self.service_name.split_once("__").unwrap().0;
+        // A long request keeps the service busy until it is answered
+        idle::record_request(service_name);
+        if let Some(upstream_started) = ctx.upstream_started {
+            latency::record(service_name, upstream_started.elapsed());
+        }
 
         // Get service configuration and check CoDel metrics here since we now have the complete request time
         if let Some(config) = get_config_by_service(service_name).await {
+            for (name, value) in annotated_headers(&config.annotations, RESPONSE_HEADER_ANNOTATION)
+            {
+                response.insert_header(name, value)?;
+            }
+
+            if let Some(recording) = &config.traffic_recording {
+                if let Some(node_port) = self
+                    .service_name
+                    .split_once("__")
+                    .and_then(|(_, port)| port.parse().ok())
+                {
+                    replay::record(
+                        service_name,
+                        node_port,
+                        session.req_header(),
+                        response.status.as_u16(),
+                        total_time,
+                        recording,
+                    );
+                }
+            }
+
             if let Some(codel_config) = config.codel.clone() {
                 let metrics = get_service_metrics(service_name, &codel_config).await;
                 let mut metrics = metrics.lock().await;
 
                 // Record the total request time
                 metrics.record_sojourn(total_time);
+                if response.status.is_server_error() {
+                    metrics.record_server_error();
+                }
 
-                // Check if we need to take action
-                if let Some(_action) = metrics.check_state() {
+                // Check if we need to take action; external services can only shed load
+                if let Some(_action) = metrics.check_state().filter(|_| config.external.is_none()) {
                     // Spawn scaling as a background task
                     let config_clone = config.clone();
                     let service_name_clone = service_name.to_string();
@@ -117,15 +568,48 @@ impl ProxyHttp for ProxyApp {
         Ok(())
     }
 
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        ctx: &mut RequestCtx,
+    ) -> pingora::Result<()> {
+        let service_name = self.service_name.split_once("__").unwrap().0;
+        upstream_request.insert_header(REQUEST_ID_HEADER, &ctx.request_id)?;
+        ctx.upstream_started = Some(Instant::now());
+
+        if let Some(config) = get_config_by_service(service_name).await {
+            for (name, value) in annotated_headers(&config.annotations, REQUEST_HEADER_ANNOTATION) {
+                upstream_request.insert_header(name, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn upstream_peer(
         &self,
         session: &mut Session,
-        _ctx: &mut Instant,
+        ctx: &mut RequestCtx,
     ) -> pingora::Result<Box<HttpPeer>> {
         let service_name = self.service_name.split_once("__").unwrap().0;
 
         // Check if we should reject the request based on recent metrics
-        if let Some(config) = get_config_by_service(service_name).await {
+        let config = get_config_by_service(service_name).await;
+        if let Some(config) = &config {
+            if stopped::is_stopped(config) {
+                write_maintenance_page(session, &ctx.request_id).await?;
+
+                let error = pingora::Error {
+                    etype: pingora::ErrorType::CustomCode("stopped", 503),
+                    esource: pingora::ErrorSource::Unset,
+                    retry: pingora::RetryType::Decided(false),
+                    cause: None,
+                    context: Some(pingora::ImmutStr::Static("Service stopped")),
+                };
+                return Err(Box::new(error));
+            }
+
             if let Some(codel_config) = config.codel.clone() {
                 let metrics = get_service_metrics(service_name, &codel_config).await;
                 let metrics = metrics.lock().await;
@@ -134,10 +618,12 @@ impl ProxyHttp for ProxyApp {
                     if let Some(status_code) = codel_config.overload_status_code {
                         slog::debug!(slog_scope::logger(), "Rejecting request due to CoDel";
                             "service" => service_name,
+                            "request_id" => &ctx.request_id,
                             "status_code" => status_code
                         );
 
-                        let response = ResponseHeader::build(status_code, Some(0))?;
+                        let mut response = ResponseHeader::build(status_code, Some(1))?;
+                        response.insert_header(REQUEST_ID_HEADER, &ctx.request_id)?;
                         session
                             .write_response_header(Box::new(response), true)
                             .await?;
@@ -155,13 +641,28 @@ impl ProxyHttp for ProxyApp {
             }
         }
 
-        // Proceed with backend selection
-        match self.loadbalancer.select(b"", 256) {
-            Some(upstream) => Ok(Box::new(HttpPeer::new(
-                upstream,
-                false,
-                "host.name".to_string(),
-            ))),
+        // Proceed with backend selection, waking a service scaled to zero
+        let upstream = match self.loadbalancer.select(b"", 256) {
+            Some(upstream) => Some(upstream),
+            None => self.wait_for_wake(service_name, ctx).await,
+        };
+        match upstream {
+            Some(upstream) => {
+                let address = upstream.addr.to_string();
+                ctx.pod = self.cache.read().unwrap().pods.get(&address).cloned();
+                ctx.upstream = Some(address);
+                // Backends reached over TLS are named after the external
+                // hostname they resolve from, or the service
+                let default_sni = config
+                    .as_ref()
+                    .and_then(|config| config.external.as_ref())
+                    .and_then(|external| external.hostname.clone())
+                    .unwrap_or_else(|| service_name.to_string());
+                let upstream_config = config
+                    .and_then(|config| config.upstream)
+                    .unwrap_or_default();
+                Ok(Box::new(upstream_config.peer(upstream, &default_sni)))
+            }
             None => {
                 let error = pingora::Error {
                     etype: pingora::ErrorType::CustomCode("no_upstream", 503),
@@ -174,6 +675,118 @@ impl ProxyHttp for ProxyApp {
             }
         }
     }
+
+    // As the default, but the error response carries the request ID too
+    async fn fail_to_proxy(
+        &self,
+        session: &mut Session,
+        e: &pingora::Error,
+        ctx: &mut RequestCtx,
+    ) -> u16 {
+        // A response was already written, e.g. when CoDel rejected the request
+        if let Some(response) = session.response_written() {
+            return response.status.as_u16();
+        }
+
+        let code = match e.etype() {
+            pingora::ErrorType::HTTPStatus(code) => *code,
+            _ => match e.esource() {
+                pingora::ErrorSource::Upstream => 502,
+                pingora::ErrorSource::Downstream => match e.etype() {
+                    pingora::ErrorType::WriteError
+                    | pingora::ErrorType::ReadError
+                    | pingora::ErrorType::ConnectionClosed => 0,
+                    _ => 400,
+                },
+                pingora::ErrorSource::Internal | pingora::ErrorSource::Unset => 500,
+            },
+        };
+        if code > 0 {
+            let mut response = ServerSession::generate_error(code);
+            let _ = response.insert_header(REQUEST_ID_HEADER, &ctx.request_id);
+            session.set_keepalive(None);
+            let _ = session
+                .write_response_header(Box::new(response), true)
+                .await;
+        }
+        code
+    }
+
+    async fn logging(
+        &self,
+        session: &mut Session,
+        e: Option<&pingora::Error>,
+        ctx: &mut RequestCtx,
+    ) {
+        let service_name = self.service_name.split_once("__").unwrap().0;
+        let status = session
+            .response_written()
+            .map_or(0, |response| response.status.as_u16());
+        let failed = e.is_some() || status >= 500;
+        if let Some(pod) = &ctx.pod {
+            anomaly::record_request(&pod.container, failed);
+            canary::record_request(service_name, pod.uuid, failed, ctx.started.elapsed());
+        }
+
+        let access_log = self.cache.read().unwrap().access_log;
+        if !failed && !access_log {
+            return;
+        }
+
+        let request = session.req_header();
+        let log = slog_scope::logger().new(slog::o!(
+            "service" => service_name.to_string(),
+            "request_id" => ctx.request_id.clone(),
+            "method" => request.method.to_string(),
+            // The query is left out, it may hold credentials
+            "path" => request.uri.path().to_string(),
+            "status" => status,
+            "duration_ms" => ctx.started.elapsed().as_millis() as u64,
+            "upstream" => ctx.upstream.clone(),
+            "pod" => ctx.pod.as_ref().map(|pod| pod.uuid.to_string()),
+            "container" => ctx.pod.as_ref().map(|pod| pod.container.clone())
+        ));
+        match e {
+            Some(e) => slog::warn!(log, "Proxied request failed"; "error" => e.to_string()),
+            None if failed => slog::warn!(log, "Proxied request failed"),
+            None => slog::info!(log, "Proxied request"),
+        }
+    }
+}
+
+/// Add a container's ports to, or remove them from, its service's load
+/// balancers, without touching the container itself
+pub async fn set_container_backends(
+    service_name: &str,
+    container: &ContainerMetadata,
+    enabled: bool,
+) {
+    let Some(server_backends) = SERVER_BACKENDS.get() else {
+        return;
+    };
+
+    for port_info in &container.ports {
+        let Some(node_port) = port_info.node_port else {
+            continue;
+        };
+        let proxy_key = format!("{}__{}", service_name, node_port);
+        let backends = {
+            let backends_map = server_backends.read().await;
+            backends_map.get(&proxy_key).cloned()
+        };
+
+        if let Some(backends) = backends {
+            let addr = port_info.backend_address(&container.ip_address);
+            if let Ok(backend) = Backend::new(&addr) {
+                let mut backend_set = backends.write().await;
+                if enabled {
+                    backend_set.insert(backend);
+                } else {
+                    backend_set.remove(&backend);
+                }
+            }
+        }
+    }
 }
 
 pub async fn run_proxy_for_service(service_name: String, config: ServiceConfig) {
@@ -194,6 +807,9 @@ pub async fn run_proxy_for_service(service_name: String, config: ServiceConfig)
             }
         }
     }
+    if let Some(external) = &config.external {
+        service_ports.insert((external.node_port, external.port.unwrap_or_default()));
+    }
 
     // Only create proxies for containers requesting external access
     for (node_port, _container_port) in service_ports {
@@ -207,6 +823,12 @@ pub async fn run_proxy_for_service(service_name: String, config: ServiceConfig)
         };
 
         if let Some(backends) = backends {
+            if config.external.is_some() {
+                // The hostname or port may have changed
+                external::start_discovery(&service_name, backends);
+                continue;
+            }
+
             // Get read lock to access instance data
             let store = instance_store.read().await;
             if let Some(instances) = store.get(&service_name) {
@@ -214,9 +836,10 @@ pub async fn run_proxy_for_service(service_name: String, config: ServiceConfig)
                     for container in &metadata.containers {
                         for port_info in &container.ports {
                             if let Some(container_node_port) = port_info.node_port {
-                                if container_node_port == node_port {
-                                    let addr =
-                                        format!("{}:{}", container.ip_address, port_info.port);
+                                if container_node_port == node_port
+                                    && health::is_routable(&container.name).await
+                                {
+                                    let addr = port_info.backend_address(&container.ip_address);
                                     if let Ok(backend) = Backend::new(&addr) {
                                         let mut backend_set = backends.write().await;
                                         backend_set.insert(backend);
@@ -252,9 +875,10 @@ pub async fn run_proxy_for_service(service_name: String, config: ServiceConfig)
                     for container in &metadata.containers {
                         for port_info in &container.ports {
                             if let Some(container_node_port) = port_info.node_port {
-                                if container_node_port == node_port {
-                                    let addr =
-                                        format!("{}:{}", container.ip_address, port_info.port);
+                                if container_node_port == node_port
+                                    && health::is_routable(&container.name).await
+                                {
+                                    let addr = port_info.backend_address(&container.ip_address);
                                     if let Ok(backend) = Backend::new(&addr) {
                                         let mut backend_set = backends.write().await;
                                         backend_set.insert(backend);
@@ -267,12 +891,20 @@ pub async fn run_proxy_for_service(service_name: String, config: ServiceConfig)
             }
         }
 
+        if config.external.is_some() {
+            external::start_discovery(&service_name, backends.clone());
+        }
+
         // Create discovery and load balancer
-        let discovery = Discovery(backends.clone());
+        let discovery = Discovery::new(&service_name, node_port, backends.clone());
+        let cache = discovery.cache.clone();
         let mut loadbalancer = LoadBalancer::from_backends(Backends::new(Box::new(discovery)));
         loadbalancer.update_frequency = Some(Duration::from_secs(1));
 
-        let hc = health_check::TcpHealthCheck::new();
+        let hc = match &config.external {
+            Some(external) => external.load_balancer_health_check(config.upstream.as_ref()),
+            None => health_check::TcpHealthCheck::new(),
+        };
         loadbalancer.set_health_check(hc);
         loadbalancer.health_check_frequency = Some(Duration::from_secs(1));
 
@@ -280,12 +912,45 @@ pub async fn run_proxy_for_service(service_name: String, config: ServiceConfig)
         let app = ProxyApp {
             loadbalancer: bg_service.task(),
             service_name: proxy_key.clone(),
+            cache,
         };
 
         let mut router_service = http_proxy_service(&Server::new(None).unwrap().configuration, app);
         router_service.add_tcp(&addr);
 
-        let mut server = Server::new(None).expect("Failed to initialise Pingora server");
+        // Adopt a systemd-activated socket for this node port if one was passed in
+        let mut server = match systemd::take_activated_fd(node_port) {
+            Some(fd) => {
+                let upgrade_sock = systemd::upgrade_sock_path(&proxy_key).display().to_string();
+                let conf = ServerConf {
+                    upgrade_sock: upgrade_sock.clone(),
+                    ..Default::default()
+                };
+                let opt = Opt {
+                    upgrade: true,
+                    ..Default::default()
+                };
+
+                let listen_addr = addr.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) =
+                        systemd::send_listener_to_pingora(fd, &listen_addr, &upgrade_sock)
+                    {
+                        slog::error!(slog_scope::logger(), "Failed to adopt activated socket";
+                            "addr" => listen_addr,
+                            "error" => e.to_string()
+                        );
+                    }
+                });
+
+                slog::info!(log, "Using systemd-activated socket for proxy";
+                    "service" => &service_name,
+                    "node_port" => node_port
+                );
+                Server::new_with_opt_and_conf(opt, conf)
+            }
+            None => Server::new(None).expect("Failed to initialise Pingora server"),
+        };
         server.bootstrap();
         server.add_service(router_service);
         server.add_service(bg_service);
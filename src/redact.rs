@@ -0,0 +1,262 @@
+// src/redact.rs
+//
+// Secret redaction for everything Orbit prints. Secret values are registered
+// as they are resolved, along with the values of env vars whose names look
+// sensitive, and masked wherever they would appear: in log lines, in API
+// responses and in webhook payloads. Values are masked as `[redacted]`, the
+// way `SecretValue` prints itself. Values registered for a service are
+// forgotten once the service is removed.
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::sync::{OnceLock, RwLock};
+
+use crate::config::daemon::get_daemon_config;
+use crate::config::ServiceConfig;
+
+// Registered values and the services they were registered for. Values
+// registered for no service, e.g. daemon-wide credentials, are kept.
+static SECRET_VALUES: OnceLock<RwLock<BTreeMap<String, BTreeSet<String>>>> = OnceLock::new();
+
+const MASK: &str = "[redacted]";
+// Shorter values would mask innocent text, like "1" or "yes"
+const MIN_SECRET_LENGTH: usize = 4;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedactionConfig {
+    /// Env vars whose name contains one of these, ignoring case, hold secrets
+    #[serde(default = "default_sensitive_keys")]
+    pub keys: Vec<String>,
+}
+
+fn default_sensitive_keys() -> Vec<String> {
+    [
+        "PASSWORD",
+        "PASSWD",
+        "SECRET",
+        "TOKEN",
+        "API_KEY",
+        "PRIVATE_KEY",
+        "CREDENTIAL",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            keys: default_sensitive_keys(),
+        }
+    }
+}
+
+fn secret_values() -> &'static RwLock<BTreeMap<String, BTreeSet<String>>> {
+    SECRET_VALUES.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+/// Mask this value from now on, along with its JSON-escaped form
+pub fn register(value: &str) {
+    insert(value, None);
+}
+
+/// Mask this value, along with its JSON-escaped form, until the service is
+/// removed
+pub fn register_for_service(service_name: &str, value: &str) {
+    insert(value, Some(service_name));
+}
+
+fn insert(value: &str, service_name: Option<&str>) {
+    if value.len() < MIN_SECRET_LENGTH {
+        return;
+    }
+    let escaped = serde_json::to_string(value)
+        .ok()
+        .map(|json| json[1..json.len() - 1].to_string())
+        .filter(|escaped| escaped != value);
+
+    let mut values = secret_values().write().unwrap();
+    for value in std::iter::once(value.to_string()).chain(escaped) {
+        let services = values.entry(value).or_default();
+        services.extend(service_name.map(str::to_string));
+    }
+}
+
+/// Stop masking the values registered for a removed service, unless another
+/// service registered them too
+pub fn unregister_service(service_name: &str) {
+    secret_values()
+        .write()
+        .unwrap()
+        .retain(|_, services| !(services.remove(service_name) && services.is_empty()));
+}
+
+// Whether an env var of this name holds a secret, per `redaction.keys`
+fn is_sensitive_key(key: &str, patterns: &[String]) -> bool {
+    let key = key.to_uppercase();
+    patterns
+        .iter()
+        .any(|pattern| key.contains(&pattern.to_uppercase()))
+}
+
+/// Register the values of the service's env vars that hold secrets
+pub fn register_env<'a>(
+    service_name: &str,
+    vars: impl IntoIterator<Item = (&'a String, &'a String)>,
+) {
+    let patterns = get_daemon_config().redaction.keys;
+    for (key, value) in vars {
+        if is_sensitive_key(key, &patterns) {
+            register_for_service(service_name, value);
+        }
+    }
+}
+
+/// Register the sensitive env values of the service's containers, so config
+/// echoes are masked before any container starts
+pub fn register_service_env(config: &ServiceConfig) {
+    for container in &config.spec.containers {
+        register_env(&config.name, container.env.iter().flatten());
+    }
+}
+
+/// The text with every registered value masked
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let values = secret_values().read().unwrap();
+    let mut redacted = Cow::Borrowed(text);
+    // Longest first, so a value containing another is masked whole
+    let mut matching: Vec<&String> = values
+        .keys()
+        .filter(|value| text.contains(value.as_str()))
+        .collect();
+    matching.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    for value in matching {
+        if redacted.contains(value.as_str()) {
+            redacted = Cow::Owned(redacted.replace(value.as_str(), MASK));
+        }
+    }
+    redacted
+}
+
+/// A writer that masks registered values line by line, so a value split
+/// across writes is still masked
+pub struct RedactingWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> RedactingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if let Some(end) = self.buffer.iter().rposition(|byte| *byte == b'\n') {
+            let lines: Vec<u8> = self.buffer.drain(..=end).collect();
+            let lines = String::from_utf8_lossy(&lines);
+            self.inner.write_all(redact(&lines).as_bytes())?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Mask registered values in API responses
+pub async fn redact_responses(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let (parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            slog::error!(slog_scope::logger(), "Failed to read API response";
+                "error" => e.to_string()
+            );
+            return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let body = match std::str::from_utf8(&bytes) {
+        Ok(text) => match redact(text) {
+            Cow::Owned(redacted) => Body::from(redacted),
+            Cow::Borrowed(_) => Body::from(bytes),
+        },
+        Err(_) => Body::from(bytes),
+    };
+    let mut response = Response::from_parts(parts, body);
+    // The length changes with the masked values
+    response
+        .headers_mut()
+        .remove(axum::http::header::CONTENT_LENGTH);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        register_for_service("redact-test", "s3cr3t-value");
+        register_for_service("redact-test", "s3cr3t-value-longer");
+        register_for_service("redact-test", "quo\"ted-secret");
+        register_for_service("redact-test", "abc");
+
+        assert_eq!(
+            redact("a=s3cr3t-value b=s3cr3t-value-longer"),
+            "a=[redacted] b=[redacted]"
+        );
+        assert_eq!(
+            redact(r#"{"password":"quo\"ted-secret"}"#),
+            r#"{"password":"[redacted]"}"#
+        );
+        // Too short to be masked
+        assert!(matches!(redact("abc def"), Cow::Borrowed("abc def")));
+    }
+
+    #[test]
+    fn test_unregister_service() {
+        register_for_service("unregister-a", "shared-secret-value");
+        register_for_service("unregister-b", "shared-secret-value");
+        register_for_service("unregister-a", "own-secret-value");
+        register("daemon-secret-value");
+
+        unregister_service("unregister-a");
+        assert_eq!(redact("own-secret-value"), "own-secret-value");
+        assert_eq!(redact("shared-secret-value"), "[redacted]");
+        assert_eq!(redact("daemon-secret-value"), "[redacted]");
+
+        unregister_service("unregister-b");
+        assert_eq!(redact("shared-secret-value"), "shared-secret-value");
+        assert_eq!(redact("daemon-secret-value"), "[redacted]");
+    }
+
+    #[test]
+    fn test_redacting_writer_split_writes() {
+        register_for_service("writer-test", "split-secret-value");
+        let mut writer = RedactingWriter::new(Vec::new());
+
+        writer.write_all(b"token=split-sec").unwrap();
+        assert!(writer.inner.is_empty());
+        writer.write_all(b"ret-value\nnext line sp").unwrap();
+        assert_eq!(writer.inner, b"token=[redacted]\n");
+        writer.write_all(b"lit-secret-value\n").unwrap();
+        assert_eq!(writer.inner, b"token=[redacted]\nnext line [redacted]\n");
+    }
+}
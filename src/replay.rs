@@ -0,0 +1,688 @@
+// src/replay.rs
+//
+// Built-in load testing: the proxy records a sample of each service's requests
+// (without bodies or credentials), and a replay sends them back through the
+// proxy at a chosen rate. Replayed traffic is handled like real traffic, so
+// CoDel and autoscaling react to it and the report shows how they coped.
+// Only GET and HEAD requests are replayed unless asked for: the others would
+// be sent without their bodies and repeat their side effects. A replay runs in
+// the background, and its report is fetched once it has finished.
+use crate::config::ServiceConfig;
+use crate::container::INSTANCE_STORE;
+
+use anyhow::{anyhow, Result};
+use pingora::http::RequestHeader;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+pub static RECORDINGS: OnceLock<Mutex<FxHashMap<String, Recording>>> = OnceLock::new();
+static REPLAYS: OnceLock<Mutex<VecDeque<ReplayJob>>> = OnceLock::new();
+
+// Never recorded, in addition to the service's redact_headers
+const SENSITIVE_HEADERS: [&str; 6] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+// Recorded with their values replaced, in addition to the service's redact_query_params
+const SENSITIVE_QUERY_PARAMS: [&str; 8] = [
+    "token",
+    "access_token",
+    "api_key",
+    "apikey",
+    "key",
+    "password",
+    "secret",
+    "signature",
+];
+// Set per request by the client or the proxy, so never replayed
+const SKIPPED_HEADERS: [&str; 6] = [
+    "host",
+    "connection",
+    "content-length",
+    "transfer-encoding",
+    "keep-alive",
+    "upgrade",
+];
+const REDACTED: &str = "redacted";
+
+pub const MAX_REPLAY_DURATION: Duration = Duration::from_secs(600);
+const MAX_REPLAY_RATE: f64 = 10_000.0;
+// Replayed without `all_methods`, as they don't change anything
+const SAFE_METHODS: [&str; 2] = ["GET", "HEAD"];
+// Replays kept for their reports, running or finished
+const MAX_REPLAYS: usize = 32;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrafficRecordingConfig {
+    /// Record one request in every `sample_every`
+    #[serde(default = "default_sample_every")]
+    pub sample_every: u32,
+    /// Most recent requests kept per service
+    #[serde(default = "default_max_requests")]
+    pub max_requests: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact_headers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact_query_params: Vec<String>,
+}
+
+fn default_sample_every() -> u32 {
+    10
+}
+
+fn default_max_requests() -> usize {
+    1000
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecordedRequest {
+    pub node_port: u16,
+    pub method: String,
+    /// Path and query, with sensitive query values redacted
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
+    pub status: u16,
+    pub latency_ms: f64,
+    pub recorded_at: SystemTime,
+}
+
+#[derive(Debug, Default)]
+pub struct Recording {
+    seen: u64,
+    requests: VecDeque<RecordedRequest>,
+}
+
+fn recordings() -> &'static Mutex<FxHashMap<String, Recording>> {
+    RECORDINGS.get_or_init(|| Mutex::new(FxHashMap::default()))
+}
+
+fn sanitize_path(path: &str, query: Option<&str>, config: &TrafficRecordingConfig) -> String {
+    let Some(query) = query else {
+        return path.to_string();
+    };
+
+    let pairs: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _))
+                if SENSITIVE_QUERY_PARAMS.contains(&name.to_ascii_lowercase().as_str())
+                    || config
+                        .redact_query_params
+                        .iter()
+                        .any(|p| p.eq_ignore_ascii_case(name)) =>
+            {
+                format!("{}={}", name, REDACTED)
+            }
+            _ => pair.to_string(),
+        })
+        .collect();
+
+    format!("{}?{}", path, pairs.join("&"))
+}
+
+// The request's headers, without credentials and those set per request
+fn recorded_headers(
+    request: &RequestHeader,
+    config: &TrafficRecordingConfig,
+) -> Vec<(String, String)> {
+    request
+        .headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            !SENSITIVE_HEADERS.contains(&name)
+                && !SKIPPED_HEADERS.contains(&name)
+                && !config
+                    .redact_headers
+                    .iter()
+                    .any(|h| h.eq_ignore_ascii_case(name))
+        })
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Called by the proxy for every completed request of a service with traffic_recording.
+pub fn record(
+    service_name: &str,
+    node_port: u16,
+    request: &RequestHeader,
+    status: u16,
+    latency: Duration,
+    config: &TrafficRecordingConfig,
+) {
+    let mut recordings = recordings().lock().unwrap();
+    let recording = recordings.entry(service_name.to_string()).or_default();
+
+    recording.seen += 1;
+    if !recording
+        .seen
+        .is_multiple_of(config.sample_every.max(1) as u64)
+    {
+        return;
+    }
+
+    recording.requests.push_back(RecordedRequest {
+        node_port,
+        method: request.method.to_string(),
+        path: sanitize_path(request.uri.path(), request.uri.query(), config),
+        headers: recorded_headers(request, config),
+        status,
+        latency_ms: latency.as_secs_f64() * 1000.0,
+        recorded_at: SystemTime::now(),
+    });
+    while recording.requests.len() > config.max_requests {
+        recording.requests.pop_front();
+    }
+}
+
+pub fn recorded_requests(service_name: &str) -> Vec<RecordedRequest> {
+    recordings()
+        .lock()
+        .unwrap()
+        .get(service_name)
+        .map(|recording| recording.requests.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub fn clear_recording(service_name: &str) {
+    recordings().lock().unwrap().remove(service_name);
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplayRequest {
+    /// Requests per second
+    pub rate: f64,
+    #[serde(with = "humantime_serde")]
+    pub duration: Duration,
+    /// Requests in flight at once; further requests are skipped, not queued
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    #[serde(with = "humantime_serde", default = "default_request_timeout")]
+    pub timeout: Duration,
+    /// Also replay requests other than GET and HEAD, without their bodies
+    #[serde(default)]
+    pub all_methods: bool,
+}
+
+fn default_concurrency() -> usize {
+    64
+}
+
+fn default_request_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LatencySummary {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencySummary {
+    fn from_samples(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(f64::total_cmp);
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+
+        Self {
+            p50_ms: percentile(0.50),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: samples[samples.len() - 1],
+        }
+    }
+}
+
+/// How the replayed load compared with the service's CoDel target
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CodelComparison {
+    pub target_ms: f64,
+    /// Share of replayed requests slower than the target
+    pub above_target_ratio: f64,
+    /// Target that 95% of replayed requests met
+    pub suggested_target_ms: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReplayReport {
+    pub service_name: String,
+    pub sent: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Requests not sent because `concurrency` requests were already in flight
+    pub skipped: u64,
+    pub achieved_rate: f64,
+    pub status_counts: BTreeMap<u16, u64>,
+    pub latency: LatencySummary,
+    /// Latency of the same requests when they were recorded
+    pub recorded_latency: LatencySummary,
+    /// Recorded requests left out for their method
+    pub excluded: usize,
+    pub pods_before: usize,
+    pub pods_after: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codel: Option<CodelComparison>,
+}
+
+enum Outcome {
+    Response(u16, f64),
+    Failed,
+}
+
+async fn pod_count(service_name: &str) -> usize {
+    match INSTANCE_STORE.get() {
+        Some(store) => store
+            .read()
+            .await
+            .get(service_name)
+            .map_or(0, |instances| instances.len()),
+        None => 0,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayState {
+    Running,
+    Finished,
+    Failed,
+}
+
+/// A replay started through the API, with its report once it has finished
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplayJob {
+    pub id: Uuid,
+    pub service_name: String,
+    pub state: ReplayState,
+    #[serde(with = "humantime_serde")]
+    pub started_at: SystemTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<ReplayReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn replays() -> &'static Mutex<VecDeque<ReplayJob>> {
+    REPLAYS.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+pub fn replay_job(id: Uuid) -> Option<ReplayJob> {
+    replays()
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|job| job.id == id)
+        .cloned()
+}
+
+/// Check the request and start replaying in the background. Returns the
+/// replay, to be polled for its report.
+pub fn start_replay(config: &ServiceConfig, request: &ReplayRequest) -> Result<ReplayJob> {
+    let (recorded, excluded) = replayed_requests(config, request)?;
+    let job = ReplayJob {
+        id: Uuid::new_v4(),
+        service_name: config.name.clone(),
+        state: ReplayState::Running,
+        started_at: SystemTime::now(),
+        report: None,
+        error: None,
+    };
+    {
+        let mut replays = replays().lock().unwrap();
+        replays.push_back(job.clone());
+        while replays.len() > MAX_REPLAYS {
+            replays.pop_front();
+        }
+    }
+
+    let config = config.clone();
+    let request = request.clone();
+    let id = job.id;
+    tokio::spawn(async move {
+        let result = replay(&config, &request, recorded, excluded).await;
+        let mut replays = replays().lock().unwrap();
+        if let Some(job) = replays.iter_mut().find(|job| job.id == id) {
+            match result {
+                Ok(report) => {
+                    job.state = ReplayState::Finished;
+                    job.report = Some(report);
+                }
+                Err(e) => {
+                    job.state = ReplayState::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+        }
+    });
+    Ok(job)
+}
+
+// The recorded requests to replay, and how many were left out for their method
+fn replayed_requests(
+    config: &ServiceConfig,
+    request: &ReplayRequest,
+) -> Result<(Vec<RecordedRequest>, usize)> {
+    if !(request.rate > 0.0 && request.rate <= MAX_REPLAY_RATE) {
+        return Err(anyhow!(
+            "Replay rate must be above 0 and at most {} requests per second",
+            MAX_REPLAY_RATE
+        ));
+    }
+    if request.duration.is_zero() || request.duration > MAX_REPLAY_DURATION {
+        return Err(anyhow!(
+            "Replay duration must be above 0 and at most {:?}",
+            MAX_REPLAY_DURATION
+        ));
+    }
+
+    let recorded = recorded_requests(&config.name);
+    if recorded.is_empty() {
+        return Err(anyhow!("No requests recorded for service {}", config.name));
+    }
+    let total = recorded.len();
+    let replayed: Vec<RecordedRequest> = recorded
+        .into_iter()
+        .filter(|r| request.all_methods || SAFE_METHODS.contains(&r.method.as_str()))
+        .collect();
+    if replayed.is_empty() {
+        return Err(anyhow!(
+            "No GET or HEAD requests recorded for service {}, set all_methods to replay the others",
+            config.name
+        ));
+    }
+    let excluded = total - replayed.len();
+    Ok((replayed, excluded))
+}
+
+/// Replay the requests through the service's proxy at `request.rate`.
+async fn replay(
+    config: &ServiceConfig,
+    request: &ReplayRequest,
+    recorded: Vec<RecordedRequest>,
+    excluded: usize,
+) -> Result<ReplayReport> {
+    let client = reqwest::Client::builder()
+        .timeout(request.timeout)
+        .build()?;
+    let semaphore = Arc::new(Semaphore::new(request.concurrency.max(1)));
+    let pods_before = pod_count(&config.name).await;
+
+    slog::info!(slog_scope::logger(), "Starting traffic replay";
+        "service" => &config.name,
+        "rate" => request.rate,
+        "duration" => format!("{:?}", request.duration),
+        "recorded_requests" => recorded.len(),
+        "excluded" => excluded
+    );
+
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / request.rate));
+    let started = Instant::now();
+    let deadline = started + request.duration;
+    let mut tasks = JoinSet::new();
+    let mut outcomes = Vec::new();
+    let mut sent = 0u64;
+    let mut skipped = 0u64;
+
+    for recorded_request in recorded.iter().cycle() {
+        interval.tick().await;
+        if Instant::now() >= deadline {
+            break;
+        }
+
+        while let Some(outcome) = tasks.try_join_next() {
+            outcomes.extend(outcome.ok());
+        }
+
+        let Ok(permit) = semaphore.clone().try_acquire_owned() else {
+            skipped += 1;
+            continue;
+        };
+        sent += 1;
+
+        let url = format!(
+            "http://127.0.0.1:{}{}",
+            recorded_request.node_port, recorded_request.path
+        );
+        let method = reqwest::Method::from_bytes(recorded_request.method.as_bytes())
+            .unwrap_or(reqwest::Method::GET);
+        let mut builder = client.request(method, url);
+        for (name, value) in &recorded_request.headers {
+            builder = builder.header(name, value);
+        }
+
+        tasks.spawn(async move {
+            let _permit = permit;
+            let sent_at = Instant::now();
+            match builder.send().await {
+                Ok(response) => Outcome::Response(
+                    response.status().as_u16(),
+                    sent_at.elapsed().as_secs_f64() * 1000.0,
+                ),
+                Err(_) => Outcome::Failed,
+            }
+        });
+    }
+
+    while let Some(outcome) = tasks.join_next().await {
+        outcomes.extend(outcome.ok());
+    }
+    let elapsed = started.elapsed();
+
+    let mut report = ReplayReport {
+        service_name: config.name.clone(),
+        sent,
+        skipped,
+        achieved_rate: sent as f64 / elapsed.as_secs_f64(),
+        recorded_latency: LatencySummary::from_samples(
+            recorded.iter().map(|r| r.latency_ms).collect(),
+        ),
+        excluded,
+        pods_before,
+        pods_after: pod_count(&config.name).await,
+        ..Default::default()
+    };
+
+    let mut latencies = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            Outcome::Response(status, latency_ms) => {
+                *report.status_counts.entry(status).or_default() += 1;
+                if status < 500 {
+                    report.succeeded += 1;
+                } else {
+                    report.failed += 1;
+                }
+                latencies.push(latency_ms);
+            }
+            Outcome::Failed => report.failed += 1,
+        }
+    }
+
+    if let Some(codel) = &config.codel {
+        let target_ms = codel.target.as_secs_f64() * 1000.0;
+        let above = latencies.iter().filter(|l| **l > target_ms).count();
+        report.codel = Some(CodelComparison {
+            target_ms,
+            above_target_ratio: above as f64 / latencies.len().max(1) as f64,
+            suggested_target_ms: LatencySummary::from_samples(latencies.clone()).p95_ms,
+        });
+    }
+    report.latency = LatencySummary::from_samples(latencies);
+
+    slog::info!(slog_scope::logger(), "Traffic replay finished";
+        "service" => &config.name,
+        "sent" => report.sent,
+        "failed" => report.failed,
+        "skipped" => report.skipped,
+        "p95_ms" => report.latency.p95_ms,
+        "pods_before" => report.pods_before,
+        "pods_after" => report.pods_after
+    );
+
+    Ok(report)
+}
+
+/// `orbit replay`: ask a running daemon to replay a service's recorded traffic,
+/// and wait for its report.
+pub async fn run(service_name: &str, api_url: &str, request: &ReplayRequest) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+    let url = format!(
+        "{}/services/{}/replay",
+        api_url.trim_end_matches('/'),
+        service_name
+    );
+
+    let response = client
+        .post(&url)
+        .json(request)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to reach Orbit at {}: {}", api_url, e))?;
+    if !response.status().is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(anyhow!(
+            "Replay failed: {}",
+            body["error"].as_str().unwrap_or("unknown error")
+        ));
+    }
+
+    let mut job: ReplayJob = response.json().await?;
+    while job.state == ReplayState::Running {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        job = client
+            .get(format!("{}/{}", url, job.id))
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to reach Orbit at {}: {}", api_url, e))?
+            .error_for_status()?
+            .json()
+            .await?;
+    }
+    let Some(report) = job.report else {
+        return Err(anyhow!(
+            "Replay failed: {}",
+            job.error.as_deref().unwrap_or("unknown error")
+        ));
+    };
+
+    println!(
+        "{}: sent {} ({:.1}/s), {} failed, {} skipped",
+        service_name, report.sent, report.achieved_rate, report.failed, report.skipped
+    );
+    println!(
+        "  latency   p50 {:.1}ms  p95 {:.1}ms  p99 {:.1}ms  max {:.1}ms",
+        report.latency.p50_ms, report.latency.p95_ms, report.latency.p99_ms, report.latency.max_ms
+    );
+    println!(
+        "  recorded  p50 {:.1}ms  p95 {:.1}ms  p99 {:.1}ms  max {:.1}ms",
+        report.recorded_latency.p50_ms,
+        report.recorded_latency.p95_ms,
+        report.recorded_latency.p99_ms,
+        report.recorded_latency.max_ms
+    );
+    for (status, count) in &report.status_counts {
+        println!("  {}: {}", status, count);
+    }
+    if report.excluded > 0 {
+        println!(
+            "  excluded  {} recorded requests other than GET and HEAD",
+            report.excluded
+        );
+    }
+    println!(
+        "  pods      {} -> {}",
+        report.pods_before, report.pods_after
+    );
+    if let Some(codel) = &report.codel {
+        println!(
+            "  codel     target {:.1}ms, {:.1}% of requests above it, p95 {:.1}ms",
+            codel.target_ms,
+            codel.above_target_ratio * 100.0,
+            codel.suggested_target_ms
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording_config() -> TrafficRecordingConfig {
+        TrafficRecordingConfig {
+            sample_every: 1,
+            max_requests: 10,
+            redact_headers: vec!["X-Session-Id".to_string()],
+            redact_query_params: vec!["email".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_sanitize_path() {
+        let config = recording_config();
+        assert_eq!(sanitize_path("/search", None, &config), "/search");
+        assert_eq!(
+            sanitize_path(
+                "/search",
+                Some("q=orbit&Token=abc&EMAIL=a@b.c&flag"),
+                &config
+            ),
+            "/search?q=orbit&Token=redacted&EMAIL=redacted&flag"
+        );
+        assert_eq!(
+            sanitize_path("/", Some("api_key=1&page=2"), &config),
+            "/?api_key=redacted&page=2"
+        );
+    }
+
+    #[test]
+    fn test_recorded_headers() {
+        let mut request = RequestHeader::build("GET", b"/", None).unwrap();
+        for (name, value) in [
+            ("Accept", "application/json"),
+            ("Authorization", "Bearer secret"),
+            ("Cookie", "session=1"),
+            ("X-Session-Id", "42"),
+            ("Host", "example.com"),
+            ("Content-Length", "0"),
+            ("X-Trace", "on"),
+        ] {
+            request.insert_header(name, value).unwrap();
+        }
+
+        let mut headers = recorded_headers(&request, &recording_config());
+        headers.sort();
+        assert_eq!(
+            headers,
+            vec![
+                ("accept".to_string(), "application/json".to_string()),
+                ("x-trace".to_string(), "on".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// src/request_limits.rs
+//
+// Limits on the size of the requests the proxy passes to a service's pods,
+// so a small backend isn't made to buffer oversized payloads. The request
+// line and headers are checked as soon as they are read: a URI over the limit
+// is answered with 414 and headers over it with 431, and a body whose
+// Content-Length is over the limit with 413. Bodies without a length, e.g.
+// chunked uploads, are counted as they stream through and cut off with 413
+// once they go over.
+use anyhow::{anyhow, Result};
+use pingora::http::RequestHeader;
+use serde::{Deserialize, Serialize};
+
+pub const URI_TOO_LONG: u16 = 414;
+pub const HEADERS_TOO_LARGE: u16 = 431;
+pub const BODY_TOO_LARGE: u16 = 413;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RequestLimits {
+    /// Total size of the header names and values, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_header_bytes: Option<usize>,
+    /// Size of the request body, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<u64>,
+    /// Length of the path and query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uri_length: Option<usize>,
+}
+
+impl RequestLimits {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_header_bytes == Some(0) {
+            return Err(anyhow!("max_header_bytes must be greater than zero"));
+        }
+        if self.max_uri_length == Some(0) {
+            return Err(anyhow!("max_uri_length must be greater than zero"));
+        }
+        Ok(())
+    }
+
+    /// The status to reject the request with, if its URI, headers or declared
+    /// body length is over a limit
+    pub fn check(&self, request: &RequestHeader) -> Option<u16> {
+        if let Some(max) = self.max_uri_length {
+            let length = request
+                .uri
+                .path_and_query()
+                .map_or(0, |path_and_query| path_and_query.as_str().len());
+            if length > max {
+                return Some(URI_TOO_LONG);
+            }
+        }
+        if let Some(max) = self.max_header_bytes {
+            // As sent on the wire, with the ": " and line break of each header
+            let size: usize = request
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len() + 4)
+                .sum();
+            if size > max {
+                return Some(HEADERS_TOO_LARGE);
+            }
+        }
+        if let Some(max) = self.max_body_bytes {
+            let content_length = request
+                .headers
+                .get("content-length")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok());
+            if content_length.is_some_and(|length| length > max) {
+                return Some(BODY_TOO_LARGE);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> RequestHeader {
+        let mut request = RequestHeader::build("POST", uri.as_bytes(), None).unwrap();
+        for (name, value) in headers {
+            request
+                .insert_header(name.to_string(), value.to_string())
+                .unwrap();
+        }
+        request
+    }
+
+    #[test]
+    fn test_check_uri_length() {
+        let limits = RequestLimits {
+            max_uri_length: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(limits.check(&request("/a?b=c", &[])), None);
+        assert_eq!(limits.check(&request("/abcde?f=g", &[])), None);
+        assert_eq!(
+            limits.check(&request("/abcde?f=gh", &[])),
+            Some(URI_TOO_LONG)
+        );
+    }
+
+    #[test]
+    fn test_check_header_bytes() {
+        let limits = RequestLimits {
+            max_header_bytes: Some(20),
+            ..Default::default()
+        };
+        // "host: example\r\n" is 15 bytes
+        assert_eq!(limits.check(&request("/", &[("host", "example")])), None);
+        assert_eq!(
+            limits.check(&request("/", &[("host", "example"), ("x-a", "b")])),
+            Some(HEADERS_TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn test_check_content_length() {
+        let limits = RequestLimits {
+            max_body_bytes: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits.check(&request("/", &[("content-length", "100")])),
+            None
+        );
+        assert_eq!(
+            limits.check(&request("/", &[("content-length", "101")])),
+            Some(BODY_TOO_LARGE)
+        );
+        // Bodies without a valid length are counted as they stream through
+        assert_eq!(limits.check(&request("/", &[])), None);
+        assert_eq!(
+            limits.check(&request("/", &[("content-length", "lots")])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_order() {
+        let limits = RequestLimits {
+            max_header_bytes: Some(1),
+            max_body_bytes: Some(1),
+            max_uri_length: Some(1),
+        };
+        assert_eq!(
+            limits.check(&request("/long", &[("content-length", "2")])),
+            Some(URI_TOO_LONG)
+        );
+        assert_eq!(
+            limits.check(&request("/", &[("content-length", "2")])),
+            Some(HEADERS_TOO_LARGE)
+        );
+    }
+}
@@ -0,0 +1,334 @@
+// src/support.rs
+//
+// Support bundles: one archive with what it takes to diagnose a problem away
+// from the host, to attach to a bug report. The daemon gathers its service
+// and daemon configs, status, recent instance events, scaling history,
+// metrics and log lines into a gzipped tarball, masking secrets the way API
+// responses are masked, and `orbit support-bundle` saves it to a file.
+// Anything that can't be gathered is noted in the manifest rather than
+// failing the bundle.
+use anyhow::{anyhow, Result};
+use axum::{
+    body::to_bytes,
+    extract::Query,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use humantime_serde::re::humantime;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::api::query::{ListQuery, MAX_PER_PAGE};
+use crate::api::{expiry, instances, node, schedule, status};
+use crate::config::daemon::get_daemon_config;
+use crate::config::CONFIG_STORE;
+use crate::container::changes::recent_changes;
+use crate::container::scaling::history::scaling_history;
+use crate::logger::recent_logs;
+use crate::metrics::metrics_handler;
+use crate::redact::redact;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+// Tar blocks are 512 bytes; names longer than 100 bytes go in the prefix field
+const BLOCK: usize = 512;
+
+#[derive(Debug, Serialize)]
+struct Manifest {
+    version: &'static str,
+    created_at: String,
+    files: Vec<String>,
+    /// What couldn't be gathered, by the file it would have gone in
+    errors: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+struct Bundle {
+    files: Vec<(String, Vec<u8>)>,
+    errors: BTreeMap<String, String>,
+}
+
+impl Bundle {
+    // Add a file, with secrets masked
+    fn add(&mut self, name: &str, contents: Result<Vec<u8>>) {
+        match contents {
+            Ok(contents) => {
+                let contents = match String::from_utf8(contents) {
+                    Ok(text) => redact(&text).into_owned().into_bytes(),
+                    Err(e) => e.into_bytes(),
+                };
+                self.files.push((name.to_string(), contents));
+            }
+            Err(e) => {
+                self.errors.insert(name.to_string(), e.to_string());
+            }
+        }
+    }
+
+    fn add_json<T: Serialize>(&mut self, name: &str, value: &T) {
+        self.add(name, serde_json::to_vec_pretty(value).map_err(Into::into));
+    }
+
+    fn add_yaml<T: Serialize>(&mut self, name: &str, value: &T) {
+        self.add(
+            name,
+            serde_yaml::to_string(value)
+                .map(String::into_bytes)
+                .map_err(Into::into),
+        );
+    }
+}
+
+// The body of an API response, or its error
+async fn response_body(response: Response) -> Result<Vec<u8>> {
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await?;
+    if !status.is_success() {
+        return Err(anyhow!(
+            "{}: {}",
+            status,
+            String::from_utf8_lossy(&body).trim()
+        ));
+    }
+    Ok(body.to_vec())
+}
+
+// Every page of a list endpoint, as one JSON array
+async fn all_pages<F, Fut>(list: F) -> Result<Vec<u8>>
+where
+    F: Fn(ListQuery) -> Fut,
+    Fut: Future<Output = Response>,
+{
+    let mut items = Vec::new();
+    for page in 1.. {
+        let query = ListQuery {
+            page: Some(page),
+            per_page: Some(MAX_PER_PAGE),
+            ..Default::default()
+        };
+        let page_items: Vec<Value> =
+            serde_json::from_slice(&response_body(list(query).await).await?)?;
+        let last = page_items.len() < MAX_PER_PAGE;
+        items.extend(page_items);
+        if last {
+            break;
+        }
+    }
+    Ok(serde_json::to_vec_pretty(&items)?)
+}
+
+/// Gather the bundle and return it as a gzipped tarball
+pub async fn build_bundle() -> Result<Vec<u8>> {
+    let created_at = SystemTime::now();
+    let mut bundle = Bundle::default();
+
+    let mut configs: Vec<_> = CONFIG_STORE
+        .get()
+        .ok_or_else(|| anyhow!("Config store not initialized"))?
+        .read()
+        .await
+        .values()
+        .map(|entry| entry.1.clone())
+        .collect();
+    configs.sort_by(|a, b| a.name.cmp(&b.name));
+    for config in &configs {
+        bundle.add_yaml(&format!("configs/{}.yaml", config.name), config);
+    }
+    bundle.add_yaml("daemon.yaml", &get_daemon_config());
+
+    bundle.add(
+        "status.json",
+        all_pages(|query| status::get_status(Query(query), HeaderMap::new())).await,
+    );
+    bundle.add(
+        "instances.json",
+        all_pages(|query| instances::get_instances(Query(query), HeaderMap::new())).await,
+    );
+    bundle.add_json("events.json", &recent_changes());
+    bundle.add("node.json", response_body(node::get_node().await).await);
+    bundle.add(
+        "schedule.json",
+        response_body(schedule::get_schedule().await.into_response()).await,
+    );
+    bundle.add(
+        "expiry.json",
+        response_body(expiry::get_expiry().await.into_response()).await,
+    );
+    for config in &configs {
+        bundle.add_json(
+            &format!("scaling/{}.json", config.name),
+            &scaling_history(&config.name, None, None),
+        );
+    }
+    bundle.add("metrics.txt", response_body(metrics_handler().await).await);
+    bundle.add("orbit.log", Ok(recent_logs().join("\n").into_bytes()));
+
+    let manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION"),
+        created_at: humantime::format_rfc3339_seconds(created_at).to_string(),
+        files: bundle.files.iter().map(|(name, _)| name.clone()).collect(),
+        errors: bundle.errors.clone(),
+    };
+    let mut files = vec![(
+        MANIFEST_FILE.to_string(),
+        serde_json::to_vec_pretty(&manifest)?,
+    )];
+    files.append(&mut bundle.files);
+
+    let root = format!(
+        "orbit-support-{}",
+        created_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    );
+    let mut archive = GzEncoder::new(Vec::new(), Compression::default());
+    for (name, contents) in &files {
+        append_file(
+            &mut archive,
+            &format!("{}/{}", root, name),
+            contents,
+            created_at,
+        )?;
+    }
+    // The end of a tar archive is two empty blocks
+    archive.write_all(&[0; 2 * BLOCK])?;
+    Ok(archive.finish()?)
+}
+
+// Write a file to a tar archive, as a ustar header and the padded contents
+fn append_file(
+    archive: &mut impl Write,
+    path: &str,
+    contents: &[u8],
+    modified: SystemTime,
+) -> Result<()> {
+    // Longer paths are split at a '/' between the prefix and name fields
+    let (prefix, name) = if path.len() <= 100 {
+        ("", path)
+    } else {
+        path.char_indices()
+            .filter(|(index, c)| *c == '/' && *index <= 155 && path.len() - index - 1 <= 100)
+            .map(|(index, _)| (&path[..index], &path[index + 1..]))
+            .next()
+            .ok_or_else(|| anyhow!("Path {} is too long for a tar archive", path))?
+    };
+
+    let mut header = [0u8; BLOCK];
+    let mut field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{:011o}\0", contents.len()).as_bytes());
+    let mtime = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    field(136, format!("{:011o}\0", mtime.as_secs()).as_bytes());
+    // The checksum is computed with its own field as spaces
+    field(148, b"        ");
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+    field(345, prefix.as_bytes());
+    let checksum: u32 = header.iter().map(|byte| u32::from(*byte)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    archive.write_all(&header)?;
+    archive.write_all(contents)?;
+    let padding = (BLOCK - contents.len() % BLOCK) % BLOCK;
+    archive.write_all(&vec![0; padding])?;
+    Ok(())
+}
+
+/// Download a support bundle from the daemon to `output`
+pub async fn run(api_url: &str, output: &Path) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()?;
+    let url = format!("{}/support-bundle", api_url.trim_end_matches('/'));
+
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to reach Orbit at {}: {}", api_url, e))?;
+    if !response.status().is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(anyhow!(
+            "Support bundle failed: {}",
+            body["error"].as_str().unwrap_or("unknown error")
+        ));
+    }
+
+    let archive = response.bytes().await?;
+    std::fs::write(output, &archive)
+        .map_err(|e| anyhow!("Failed to write {}: {}", output.display(), e))?;
+    println!(
+        "Support bundle written to {} ({} bytes)",
+        output.display(),
+        archive.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A NUL-terminated header field
+    fn text(header: &[u8], offset: usize, len: usize) -> &str {
+        let field = &header[offset..offset + len];
+        let end = field.iter().position(|byte| *byte == 0).unwrap_or(len);
+        std::str::from_utf8(&field[..end]).unwrap()
+    }
+
+    #[test]
+    fn test_append_file_header() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut archive = Vec::new();
+        append_file(&mut archive, "bundle/logs.txt", b"hello", modified).unwrap();
+
+        assert_eq!(archive.len(), 2 * BLOCK);
+        let (header, data) = archive.split_at(BLOCK);
+        assert_eq!(text(header, 0, 100), "bundle/logs.txt");
+        assert_eq!(text(header, 124, 12), "00000000005");
+        assert_eq!(
+            u64::from_str_radix(text(header, 136, 12), 8).unwrap(),
+            1_700_000_000
+        );
+        assert_eq!(&header[156..157], b"0");
+        assert_eq!(text(header, 257, 6), "ustar");
+        assert_eq!(text(header, 345, 155), "");
+        assert_eq!(&data[..5], b"hello");
+        assert!(data[5..].iter().all(|byte| *byte == 0));
+
+        // The checksum covers the header with the checksum field as spaces
+        let mut blank = header.to_vec();
+        blank[148..156].copy_from_slice(b"        ");
+        let sum: u32 = blank.iter().map(|byte| u32::from(*byte)).sum();
+        assert_eq!(u32::from_str_radix(text(header, 148, 6), 8).unwrap(), sum);
+    }
+
+    #[test]
+    fn test_append_file_long_path() {
+        let dir = "d".repeat(60);
+        let path = format!("{}/{}/{}", dir, dir, "f".repeat(90));
+        let mut archive = Vec::new();
+        append_file(&mut archive, &path, &[1; BLOCK], UNIX_EPOCH).unwrap();
+
+        // Contents filling whole blocks aren't padded
+        assert_eq!(archive.len(), 2 * BLOCK);
+        assert_eq!(text(&archive, 345, 155), format!("{}/{}", dir, dir));
+        assert_eq!(text(&archive, 0, 100), "f".repeat(90));
+
+        let name = "f".repeat(101);
+        assert!(append_file(&mut Vec::new(), &name, b"", UNIX_EPOCH).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+// src/systemd.rs
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use std::env;
+use std::net::{SocketAddr, TcpListener};
+use std::os::fd::{FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+// First file descriptor passed by systemd socket activation (SD_LISTEN_FDS_START)
+const LISTEN_FDS_START: RawFd = 3;
+
+// Sockets handed over by systemd, keyed by local port
+pub static ACTIVATED_LISTENERS: OnceLock<Mutex<FxHashMap<u16, RawFd>>> = OnceLock::new();
+
+// Descriptors systemd passed to this process, read before the runtime starts
+static ACTIVATED_FD_COUNT: OnceLock<RawFd> = OnceLock::new();
+
+// Private directory for the sockets activated listeners are handed to pingora over
+static UPGRADE_SOCK_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+const UPGRADE_SOCK_DIR_NAME: &str = "sockets";
+
+/// Read and clear the socket activation variables. Must run before any other
+/// thread is started, as changing the environment isn't thread safe.
+pub fn take_activation_env() {
+    let for_us = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    let count = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<RawFd>().ok())
+        .unwrap_or(0);
+
+    // Do not leak activation state into containers or child processes
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    let _ = ACTIVATED_FD_COUNT.set(if for_us { count } else { 0 });
+}
+
+// Whether the descriptor is a listening stream socket, rather than e.g. a
+// datagram socket, which also has a local address
+fn is_stream_listener(fd: RawFd) -> bool {
+    let option = |name: libc::c_int| {
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // Safety: value and len point to an int and its size, as getsockopt expects
+        let result = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                name,
+                &mut value as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        (result == 0).then_some(value)
+    };
+    option(libc::SO_TYPE) == Some(libc::SOCK_STREAM) && option(libc::SO_ACCEPTCONN) == Some(1)
+}
+
+/// Collect the TCP listeners systemd passed via `LISTEN_FDS`, and create the
+/// directory they are handed to the proxies through
+pub fn initialize_activated_listeners(state_dir: &Path) -> Result<()> {
+    let log = slog_scope::logger();
+    let mut listeners = FxHashMap::default();
+
+    let count = ACTIVATED_FD_COUNT.get().copied().unwrap_or(0);
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+        if !is_stream_listener(fd) {
+            // Keep the descriptor open, it is not ours to close
+            slog::warn!(log, "Ignoring activated socket that is not a TCP listener";
+                "fd" => fd
+            );
+            continue;
+        }
+        // Safety: systemd guarantees these descriptors are open and owned by us
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        match listener.local_addr() {
+            Ok(addr) => {
+                slog::info!(log, "Received activated socket";
+                    "fd" => fd,
+                    "addr" => addr.to_string()
+                );
+                listeners.insert(addr.port(), listener.into_raw_fd());
+            }
+            Err(e) => {
+                slog::warn!(log, "Ignoring activated socket that is not a TCP listener";
+                    "fd" => fd,
+                    "error" => e.to_string()
+                );
+                let _ = listener.into_raw_fd();
+            }
+        }
+    }
+    ACTIVATED_LISTENERS.get_or_init(|| Mutex::new(listeners));
+
+    let dir = state_dir.join(UPGRADE_SOCK_DIR_NAME);
+    if !dir.is_dir() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)?;
+    }
+    UPGRADE_SOCK_DIR
+        .set(dir)
+        .map_err(|_| anyhow!("Activated listeners already initialized"))
+}
+
+/// The socket an activated listener is handed to the proxy on `proxy_key` over
+pub fn upgrade_sock_path(proxy_key: &str) -> PathBuf {
+    UPGRADE_SOCK_DIR
+        .get()
+        .expect("Activated listeners not initialized")
+        .join(format!("{}.sock", proxy_key))
+}
+
+/// Take ownership of the activated socket bound to `port`, if systemd passed one.
+pub fn take_activated_fd(port: u16) -> Option<RawFd> {
+    ACTIVATED_LISTENERS
+        .get()
+        .and_then(|listeners| listeners.lock().unwrap().remove(&port))
+}
+
+/// Take the activated listener for `addr`, falling back to binding it ourselves.
+pub async fn bind_or_activate(addr: SocketAddr) -> Result<tokio::net::TcpListener> {
+    if let Some(fd) = take_activated_fd(addr.port()) {
+        // Safety: the descriptor was validated as a TCP listener during initialisation
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        listener.set_nonblocking(true)?;
+        return Ok(tokio::net::TcpListener::from_std(listener)?);
+    }
+
+    Ok(tokio::net::TcpListener::bind(addr).await?)
+}
+
+/// Send a state update to the service manager. A no-op when not run under systemd.
+pub fn notify(state: &str) -> Result<()> {
+    let socket_path = match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    let path = socket_path.to_string_lossy();
+
+    if let Some(name) = path.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+        socket.send_to_addr(state.as_bytes(), &addr)?;
+    } else {
+        socket.send_to(state.as_bytes(), path.as_ref())?;
+    }
+
+    Ok(())
+}
+
+pub fn notify_ready() {
+    if let Err(e) = notify("READY=1") {
+        slog::warn!(slog_scope::logger(), "Failed to notify systemd"; "error" => e.to_string());
+    }
+}
+
+pub fn notify_stopping() {
+    let _ = notify("STOPPING=1");
+}
+
+// Returns the configured watchdog interval when it applies to this process
+fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    if usec == 0 {
+        return None;
+    }
+
+    Some(Duration::from_micros(usec))
+}
+
+/// Ping the systemd watchdog at half the configured interval.
+pub fn start_watchdog_task() {
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+
+    slog::info!(slog_scope::logger(), "Systemd watchdog enabled";
+        "interval_ms" => interval.as_millis() as u64
+    );
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval / 2);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = notify("WATCHDOG=1") {
+                slog::warn!(slog_scope::logger(), "Failed to ping systemd watchdog";
+                    "error" => e.to_string()
+                );
+            }
+        }
+    });
+}
+
+/// Hand an activated socket to a pingora server through its upgrade handoff.
+///
+/// Pingora only adopts pre-bound listeners during a graceful upgrade, so the
+/// descriptor is sent over `upgrade_sock` while the server bootstraps.
+pub fn send_listener_to_pingora(fd: RawFd, addr: &str, upgrade_sock: &str) -> Result<()> {
+    let mut fds = pingora::server::Fds::new();
+    fds.add(addr.to_string(), fd);
+    fds.send_to_sock(upgrade_sock)
+        .map_err(|e| anyhow!("Failed to hand over activated socket {}: {}", addr, e))?;
+
+    // Pingora received a duplicate, release our copy
+    // Safety: fd is owned by us and no longer used after the handoff
+    drop(unsafe { TcpListener::from_raw_fd(fd) });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn test_is_stream_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(is_stream_listener(listener.as_raw_fd()));
+
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(!is_stream_listener(udp.as_raw_fd()));
+
+        let stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert!(!is_stream_listener(stream.as_raw_fd()));
+    }
+}
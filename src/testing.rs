@@ -0,0 +1,354 @@
+// src/testing.rs
+//
+// Helpers for driving Orbit against the in-memory MockRuntime: they set up the
+// global stores the daemon would, load services and wait for pods, so tests
+// can exercise `manage`, `auto_scale` and rolling updates without Docker.
+// Crates embedding Orbit get them with the `mock-runtime` feature. Tests
+// share one runtime, so each should use its own service names.
+use anyhow::{anyhow, Result};
+use rustc_hash::FxHashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use crate::config::daemon::DaemonConfig;
+use crate::config::{stop_service, ServiceConfig, CONFIG_STORE};
+use crate::container::health::CONTAINER_HEALTH;
+use crate::container::mock::MockRuntime;
+use crate::container::scaling::auto_scale;
+use crate::container::{
+    create_runtime, manage, register_runtime, InstanceMetadata, CONTAINER_STATS, IMAGE_CHECK_TASKS,
+    INSTANCE_STORE, NETWORK_USAGE, RUNTIME, SCALING_TASKS, SERVICE_STATS,
+};
+use crate::proxy::{SERVER_BACKENDS, SERVER_TASKS};
+
+static MOCK_RUNTIME: OnceLock<Arc<MockRuntime>> = OnceLock::new();
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Initialise the global stores and install the shared MockRuntime, wrapped
+/// with the same call policy and concurrency limits as a real runtime
+pub fn init() -> Arc<MockRuntime> {
+    CONFIG_STORE.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    INSTANCE_STORE.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    CONTAINER_HEALTH.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    CONTAINER_STATS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SCALING_TASKS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SERVICE_STATS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SERVER_TASKS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    SERVER_BACKENDS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    IMAGE_CHECK_TASKS.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+    NETWORK_USAGE.get_or_init(|| Arc::new(RwLock::new(FxHashMap::default())));
+
+    MOCK_RUNTIME
+        .get_or_init(|| {
+            let mock = Arc::new(MockRuntime::new());
+            register_runtime("mock", mock.clone()).expect("Failed to register mock runtime");
+            let runtime = create_runtime("mock", &DaemonConfig::default())
+                .expect("Failed to create mock runtime");
+            RUNTIME
+                .set(runtime)
+                .expect("Runtime already initialised by something other than testing::init");
+            mock
+        })
+        .clone()
+}
+
+/// Parse a service config from YAML, as it would be read from the config directory
+pub fn service_config(yaml: &str) -> Result<ServiceConfig> {
+    Ok(serde_yaml::from_str(yaml)?)
+}
+
+/// Add a service to the config store and start its minimum number of pods
+pub async fn deploy(config: ServiceConfig) {
+    let path = PathBuf::from(format!("testing/{}.yaml", config.name));
+    CONFIG_STORE
+        .get()
+        .expect("testing::init not called")
+        .write()
+        .await
+        .insert(path.display().to_string(), (path, config.clone()));
+
+    let service_name = config.name.clone();
+    manage(&service_name, config).await;
+}
+
+/// Run the autoscaler for a service. It evaluates every 10 seconds, so tests
+/// usually pause tokio's clock and let it advance.
+pub fn start_auto_scale(service_name: &str) -> JoinHandle<()> {
+    tokio::spawn(auto_scale(service_name.to_string()))
+}
+
+/// Remove a service and stop its pods, as deleting its config file would
+pub async fn remove(service_name: &str) {
+    CONFIG_STORE
+        .get()
+        .expect("testing::init not called")
+        .write()
+        .await
+        .retain(|_, (_, config)| config.name != service_name);
+    stop_service(service_name).await;
+}
+
+/// The service's pods in the instance store
+pub async fn pods(service_name: &str) -> Vec<InstanceMetadata> {
+    INSTANCE_STORE
+        .get()
+        .expect("testing::init not called")
+        .read()
+        .await
+        .get(service_name)
+        .map(|instances| instances.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Wait until the service has exactly `count` pods
+pub async fn wait_for_pods(service_name: &str, count: usize, timeout: Duration) -> Result<()> {
+    let wait = async {
+        while pods(service_name).await.len() != count {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    };
+    tokio::time::timeout(timeout, wait).await.map_err(|_| {
+        anyhow!(
+            "Service {} did not reach {} pods within {:?}",
+            service_name,
+            count,
+            timeout
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::canary::CanaryConfig;
+    use crate::container::groups::{self, GroupOperation};
+    use crate::container::mock::{MockCall, MockStats};
+    use crate::container::restart_pods;
+    use crate::container::rolling_update::perform_rolling_update;
+    use crate::container::stopped;
+    use std::collections::HashMap;
+
+    fn config(name: &str, min: u8, max: u8) -> ServiceConfig {
+        service_config(&format!(
+            r#"
+name: {name}
+instance_count:
+  min: {min}
+  max: {max}
+resource_thresholds:
+  cpu_percentage: 50
+scaling_policy:
+  cooldown_duration: 0s
+spec:
+  containers:
+    - name: app
+      image: example/app:1
+"#
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn manage_starts_minimum_pods_and_retries_failed_starts() {
+        let mock = init();
+        mock.update_behavior("harness-manage", |b| b.failing_starts = 1);
+
+        deploy(config("harness-manage", 2, 4)).await;
+        // The failed start is not retried until the next reconcile
+        assert_eq!(pods("harness-manage").await.len(), 1);
+
+        deploy(config("harness-manage", 2, 4)).await;
+        wait_for_pods("harness-manage", 2, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(mock.running_containers("harness-manage").await.len(), 2);
+
+        remove("harness-manage").await;
+        assert!(mock.running_containers("harness-manage").await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stopped_service_keeps_config_without_pods() {
+        let mock = init();
+        deploy(config("harness-stop", 2, 4)).await;
+
+        assert_eq!(stopped::stop("harness-stop").await.unwrap(), 2);
+        assert!(pods("harness-stop").await.is_empty());
+        assert!(mock.running_containers("harness-stop").await.is_empty());
+
+        // Reconciling doesn't start a stopped service
+        deploy(config("harness-stop", 2, 4)).await;
+        assert!(pods("harness-stop").await.is_empty());
+
+        stopped::start("harness-stop").await.unwrap();
+        assert_eq!(pods("harness-stop").await.len(), 2);
+
+        // Disabled by its config, it can't be started through the API
+        let mut disabled = config("harness-stop", 2, 4);
+        disabled.enabled = false;
+        deploy(disabled).await;
+        assert!(pods("harness-stop").await.is_empty());
+        assert!(stopped::start("harness-stop").await.is_err());
+
+        remove("harness-stop").await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn group_operations_apply_to_every_service() {
+        init();
+        for name in ["harness-group-a", "harness-group-b"] {
+            let mut grouped = config(name, 1, 3);
+            grouped.groups = vec!["harness-group".to_string()];
+            deploy(grouped).await;
+        }
+
+        let outcomes = groups::run("harness-group", GroupOperation::Scale(2.0), 1)
+            .await
+            .unwrap();
+        assert!(outcomes.iter().all(|outcome| outcome.ok));
+        assert_eq!(pods("harness-group-a").await.len(), 2);
+        assert_eq!(pods("harness-group-b").await.len(), 2);
+
+        groups::run("harness-group", GroupOperation::Pause, 2)
+            .await
+            .unwrap();
+        assert!(pods("harness-group-a").await.is_empty());
+        groups::run("harness-group", GroupOperation::Resume, 2)
+            .await
+            .unwrap();
+        assert_eq!(pods("harness-group-b").await.len(), 1);
+
+        groups::run("harness-group", GroupOperation::Scale(1.0), 2)
+            .await
+            .unwrap();
+        assert!(groups::run("harness-missing", GroupOperation::Restart, 2)
+            .await
+            .is_err());
+
+        remove("harness-group-a").await;
+        remove("harness-group-b").await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn promoted_canary_keeps_service_size() {
+        let mock = init();
+        deploy(config("harness-canary", 3, 3)).await;
+        let before = mock.running_containers("harness-canary").await;
+
+        let new_hashes = HashMap::from([("app".to_string(), "sha256:new".to_string())]);
+        let canary = CanaryConfig {
+            analysis: Duration::from_secs(10),
+            ..Default::default()
+        };
+        perform_rolling_update(
+            "harness-canary",
+            &config("harness-canary", 3, 3),
+            RUNTIME.get().unwrap().clone(),
+            &new_hashes,
+            None,
+            Some(&canary),
+        )
+        .await
+        .unwrap();
+
+        let after = pods("harness-canary").await;
+        assert_eq!(after.len(), 3);
+        assert!(after.iter().all(|pod| pod.image_hash == new_hashes));
+        let running = mock.running_containers("harness-canary").await;
+        assert_eq!(running.len(), 3);
+        assert!(running.iter().all(|name| !before.contains(name)));
+
+        remove("harness-canary").await;
+    }
+
+    // Pods that started before another failed to are removed again
+    #[tokio::test(start_paused = true)]
+    async fn failed_update_start_leaves_old_pods() {
+        let mock = init();
+        let mut config = config("harness-failed-start", 2, 2);
+        config
+            .rolling_update_config
+            .get_or_insert_with(Default::default)
+            .max_surge = 2;
+        deploy(config.clone()).await;
+        let before = mock.running_containers("harness-failed-start").await;
+
+        mock.update_behavior("harness-failed-start", |b| b.failing_starts = 1);
+        let new_hashes = HashMap::from([("app".to_string(), "sha256:new".to_string())]);
+        assert!(perform_rolling_update(
+            "harness-failed-start",
+            &config,
+            RUNTIME.get().unwrap().clone(),
+            &new_hashes,
+            None,
+            None,
+        )
+        .await
+        .is_err());
+
+        assert_eq!(pods("harness-failed-start").await.len(), 2);
+        assert_eq!(
+            mock.running_containers("harness-failed-start").await,
+            before
+        );
+
+        remove("harness-failed-start").await;
+    }
+
+    // Each scaler has its own channel, so one that has stopped doesn't break
+    // the rolling updates of other services
+    #[tokio::test(start_paused = true)]
+    async fn rolling_update_after_another_scaler_stopped() {
+        init();
+        deploy(config("harness-scaler-gone", 1, 1)).await;
+        let scaler = start_auto_scale("harness-scaler-gone");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        scaler.abort();
+        remove("harness-scaler-gone").await;
+
+        deploy(config("harness-restart", 1, 1)).await;
+        let before = pods("harness-restart").await;
+        restart_pods("harness-restart").await.unwrap();
+        let after = pods("harness-restart").await;
+        assert_eq!(after.len(), 1);
+        assert_ne!(after[0].uuid, before[0].uuid);
+
+        remove("harness-restart").await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rolling_update_and_auto_scale() {
+        let mock = init();
+        deploy(config("harness-scale", 1, 3)).await;
+        let before = mock.running_containers("harness-scale").await;
+
+        restart_pods("harness-scale").await.unwrap();
+
+        let after = mock.running_containers("harness-scale").await;
+        assert_eq!(after.len(), 1);
+        assert_ne!(after, before);
+        assert!(mock
+            .calls("harness-scale")
+            .iter()
+            .any(|call| matches!(call, MockCall::StopContainer { name } if name == &before[0])));
+
+        mock.update_behavior("harness-scale", |b| {
+            b.stats = MockStats {
+                cpu_percentage: 90.0,
+                cpu_percentage_relative: 90.0,
+                ..Default::default()
+            }
+        });
+        let scaler = start_auto_scale("harness-scale");
+        wait_for_pods("harness-scale", 3, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        scaler.abort();
+        remove("harness-scale").await;
+    }
+}
@@ -0,0 +1,72 @@
+// src/upstream.rs
+//
+// How the proxy connects to a service's backends. By default it speaks plain
+// HTTP to them; with `upstream.scheme: https` it connects over TLS, for
+// backends that only serve HTTPS, with the SNI and certificate checks set
+// per service. TLS to backends needs Orbit built with the `upstream-tls`
+// feature, which links pingora against OpenSSL.
+use anyhow::{anyhow, Result};
+use pingora::lb::Backend;
+use pingora::upstreams::peer::HttpPeer;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UpstreamConfig {
+    #[serde(default)]
+    pub scheme: UpstreamScheme,
+    /// Server name sent in the TLS handshake and checked against the certificate
+    /// (default: the external hostname, or the service name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    /// Check that the backend's certificate is signed by a trusted CA
+    #[serde(default = "default_verify", skip_serializing_if = "is_true")]
+    pub verify_cert: bool,
+    /// Check that the certificate is for the SNI name
+    #[serde(default = "default_verify", skip_serializing_if = "is_true")]
+    pub verify_hostname: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamScheme {
+    #[default]
+    Http,
+    Https,
+}
+
+fn default_verify() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl UpstreamConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.scheme == UpstreamScheme::Https && !cfg!(feature = "upstream-tls") {
+            return Err(anyhow!(
+                "scheme https needs Orbit built with the upstream-tls feature"
+            ));
+        }
+        if self.sni.as_deref().is_some_and(str::is_empty) {
+            return Err(anyhow!("sni must not be empty"));
+        }
+        Ok(())
+    }
+
+    /// The peer the proxy connects to for `backend`. `default_sni` is used
+    /// when no SNI is configured.
+    pub fn peer(&self, backend: Backend, default_sni: &str) -> HttpPeer {
+        let tls = self.scheme == UpstreamScheme::Https;
+        let sni = match (&self.sni, tls) {
+            (Some(sni), true) => sni.clone(),
+            (None, true) => default_sni.to_string(),
+            (_, false) => String::new(),
+        };
+        let mut peer = HttpPeer::new(backend, tls, sni);
+        peer.options.verify_cert = self.verify_cert;
+        peer.options.verify_hostname = self.verify_hostname;
+        peer
+    }
+}
@@ -0,0 +1,67 @@
+// src/validate.rs
+//
+// `orbit validate`: parse and validate config files the way the daemon loads
+// them, without starting anything, and report what is wrong with each.
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::config::report::{ValidationIssue, ValidationReport};
+use crate::config::{config_files, read_yaml_config, CONFIG_STORE};
+
+/// Returns true when every file is valid
+pub async fn run(config_dir: &Path, files: &[PathBuf], json: bool) -> Result<bool> {
+    let files = if files.is_empty() {
+        config_files(config_dir)?
+    } else {
+        files.to_vec()
+    };
+
+    let mut reports = Vec::new();
+    for path in files {
+        let file = path.display().to_string();
+        let result = read_yaml_config(&path, None).await;
+        // Later files are checked against earlier ones, as when the daemon loads them
+        if let Ok(config) = &result {
+            let mut store = CONFIG_STORE.get().unwrap().write().await;
+            store.insert(file.clone(), (path.clone(), config.clone()));
+        }
+        reports.push(ValidationReport::new(file, &result));
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            print_report(report);
+        }
+    }
+    Ok(reports.iter().all(|report| report.valid))
+}
+
+fn print_report(report: &ValidationReport) {
+    if report.valid {
+        println!("{}: ok", report.file);
+        return;
+    }
+    for issue in &report.issues {
+        println!("{}: {}", location(&report.file, issue), issue.message);
+        if let Some(expected) = &issue.expected {
+            match &issue.example {
+                Some(example) => println!("  expected {}, e.g. {}", expected, example),
+                None => println!("  expected {}", expected),
+            }
+        }
+    }
+}
+
+// "file:line:column: path", with the parts that are known
+fn location(file: &str, issue: &ValidationIssue) -> String {
+    let mut location = file.to_string();
+    if let (Some(line), Some(column)) = (issue.line, issue.column) {
+        location.push_str(&format!(":{}:{}", line, column));
+    }
+    if let Some(path) = &issue.path {
+        location.push_str(&format!(": {}", path));
+    }
+    location
+}
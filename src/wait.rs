@@ -0,0 +1,64 @@
+// src/wait.rs
+//
+// `orbit wait`: block until a service meets its readiness definition, as
+// reported by a running Orbit daemon.
+use crate::container::readiness::ReadinessReport;
+
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Longest wait asked of the daemon per request; the loop covers the rest
+const POLL_WAIT_SECS: u64 = 30;
+
+/// Returns true once the service is ready, false if `timeout` elapses first.
+pub async fn run(service_name: &str, api_url: &str, timeout: Duration) -> Result<bool> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(POLL_WAIT_SECS + 10))
+        .build()?;
+    let url = format!(
+        "{}/services/{}/ready",
+        api_url.trim_end_matches('/'),
+        service_name
+    );
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let wait = remaining.as_secs().min(POLL_WAIT_SECS);
+
+        let response = client
+            .get(&url)
+            .query(&[("wait", wait)])
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to reach Orbit at {}: {}", api_url, e))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(anyhow!("Service {} is not managed by Orbit", service_name));
+        }
+
+        let report: ReadinessReport = response.json().await?;
+        if report.ready {
+            println!(
+                "{}: ready ({}/{} pods)",
+                service_name, report.ready_pods, report.total_pods
+            );
+            return Ok(true);
+        }
+
+        if Instant::now() >= deadline {
+            println!("{}: not ready after {:?}", service_name, timeout);
+            for reason in &report.pending {
+                println!("  - {}", reason);
+            }
+            return Ok(false);
+        }
+
+        // The daemon answered early (e.g. wait=0); don't spin
+        if wait == 0 {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}